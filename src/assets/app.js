@@ -14,10 +14,16 @@ app.config(function($stateProvider) {
         templateUrl : '/users.html'
     })
     .state('repos', {
-        url: '/repos',
+        // host and repo start a new entry, e.g. from the link in an unconfigured repo's admin message
+        url: '/repos?host&repo',
         controller: 'ReposController',
         templateUrl : '/repos.html'
     })
+    .state('mappings', {
+        url: '/mappings',
+        controller: 'MappingsController',
+        templateUrl : '/mappings.html'
+    })
     .state('versions', {
         url: '/versions',
         controller: 'VersionsController',
@@ -75,6 +81,17 @@ app.service('sessionHttp', function($http, $state) {
     });
   };
 
+  this.delete = function(url) {
+    return $http.delete(url, {
+      headers: {
+        session: sessionStorage['session'],
+      },
+    }).catch(function(e) {
+      catch_403(e);
+      throw e;
+    });
+  };
+
   this.logout = function() {
     self.post('/auth/logout', null).finally(function() {
       console.log('logging out!');
@@ -188,7 +205,7 @@ app.controller('UsersController', function($scope, sessionHttp, notificationServ
   refresh();
 });
 
-app.controller('ReposController', function($rootScope, $scope, sessionHttp, notificationService)  {
+app.controller('ReposController', function($rootScope, $scope, $stateParams, sessionHttp, notificationService)  {
 
   $scope.reposMap = {};
 
@@ -237,16 +254,111 @@ app.controller('ReposController', function($rootScope, $scope, sessionHttp, noti
     });
   };
 
-  $scope.removeRepo = function(host, repo) {
-    for (var i = 0; i < $scope.reposMap[host].length; ++i) {
-      if ($scope.reposMap[host][i].repo == repo) {
-        $scope.reposMap[host].splice(i, 1);
-        return;
+  // a repo can have several entries, one per set of branches, so remove this one in particular
+  $scope.removeRepo = function(host, info) {
+    var i = $scope.reposMap[host].indexOf(info);
+    if (i >= 0) {
+      $scope.reposMap[host].splice(i, 1);
+    }
+  }
+
+  // starts the entry for the repo in the link that brought us here, unless it has one already
+  function addLinkedRepo() {
+    var host = $stateParams.host;
+    var repo = $stateParams.repo;
+    if (!host || !repo) {
+      return;
+    }
+    if (!$scope.reposMap[host]) {
+      $scope.reposMap[host] = [];
+    }
+    var existing = $scope.reposMap[host].some(function(info) { return info.repo === repo; });
+    if (!existing) {
+      $scope.addRepo(host);
+      $scope.reposMap[host][$scope.reposMap[host].length - 1].repo = repo;
+    }
+  }
+
+  // init
+  refresh().then(addLinkedRepo);
+});
+
+// One row per repo and per user, saved one at a time. Unlike the users and repos pages, this can add the
+// first entry for a new github host.
+app.controller('MappingsController', function($scope, sessionHttp, notificationService)  {
+
+  function flatten(hostMap) {
+    var entries = [];
+    for (var host in hostMap) {
+      for (var i = 0; i < hostMap[host].length; ++i) {
+        entries.push({ host: host, info: hostMap[host][i] });
       }
     }
+    return entries;
+  }
+
+  function reset() {
+    $scope.newRepo = { host: '', info: {} };
+    $scope.newUser = { host: '', info: {} };
   }
 
+  function refresh() {
+    return sessionHttp.get('/api/admin/repos').then(function(resp) {
+      $scope.repos = flatten(resp.data.repos);
+      return sessionHttp.get('/api/admin/users');
+    }).then(function(resp) {
+      $scope.users = flatten(resp.data.users);
+    }).catch(function(e) {
+      if (!isLoggedIn()) {
+        return;
+      }
+      notificationService.showError('Error getting mappings: ' + parseError(e));
+    });
+  }
+
+  function update(request, what) {
+    request.then(function(resp) {
+      reset();
+      refresh();
+      notificationService.showSuccess('Updated ' + what + ' succesfully');
+      if (resp.data && resp.data.webhook) {
+        if (resp.data.ok) {
+          notificationService.showSuccess(resp.data.webhook);
+        } else {
+          notificationService.showError(resp.data.webhook);
+        }
+      }
+    }).catch(function(e) {
+      if (!isLoggedIn()) {
+        return;
+      }
+      notificationService.showError('Error updating ' + what + ': ' + parseError(e));
+    });
+  }
+
+  $scope.saveRepo = function(host, info) {
+    update(sessionHttp.post('/api/admin/repos', { host: host, repo: info }), info.repo);
+  };
+
+  $scope.removeRepo = function(host, info) {
+    var query = 'host=' + encodeURIComponent(host) + '&repo=' + encodeURIComponent(info.repo);
+    if (info.branches && info.branches.length) {
+      query += '&branches=' + encodeURIComponent(info.branches.join(','));
+    }
+    update(sessionHttp.delete('/api/admin/repos?' + query), info.repo);
+  };
+
+  $scope.saveUser = function(host, info) {
+    update(sessionHttp.post('/api/admin/users', { host: host, user: info }), info.github);
+  };
+
+  $scope.removeUser = function(host, github) {
+    var query = 'host=' + encodeURIComponent(host) + '&github=' + encodeURIComponent(github);
+    update(sessionHttp.delete('/api/admin/users?' + query), github);
+  };
+
   // init
+  reset();
   refresh();
 });
 
@@ -0,0 +1,520 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json;
+
+use config::Config;
+use errors::*;
+use event_export::ExportedEvent;
+use github::{self, AssignResponse, Comment, Commit, CombinedStatus, Comparison, DiffStats, Hook, Label,
+             PullRequest, PullRequestFile, Review, Status, User};
+use github::api::Session;
+use quiet_hours;
+use worker::WorkSender;
+
+// entries kept when there's no file to append them to
+const MAX_IN_MEMORY: usize = 1000;
+
+// the outcome of what dry run mode only recorded
+pub const DRY_RUN: &'static str = "dry run";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AuditEntry {
+    pub at: i64,
+    // what was done, e.g. "comment_pull_request", "push_branch" or "slack_message"
+    pub action: String,
+    // what it was done to, e.g. "org/repo#12" or the slack channel
+    pub target: String,
+    // the webhook event being handled, or the background worker that did it
+    pub trigger: String,
+    // "ok", or the error
+    pub outcome: String,
+}
+
+// Every change octobot makes on github or sends to slack. Entries are appended to a json-lines file
+// that is never rewritten, or only the latest ones are kept in memory if there's no file.
+pub struct AuditLog {
+    file: Option<PathBuf>,
+    recent: Mutex<VecDeque<AuditEntry>>,
+    // entries also go to the event export, if there is one
+    export: Mutex<Option<WorkSender<ExportedEvent>>>,
+}
+
+thread_local! {
+    static TRIGGER: RefCell<Option<String>> = RefCell::new(None);
+}
+
+// Runs `func` with `trigger` recorded as the reason for anything it does on this thread.
+pub fn with_trigger<T, F: FnOnce() -> T>(trigger: &str, func: F) -> T {
+    let previous = TRIGGER.with(|t| mem::replace(&mut *t.borrow_mut(), Some(trigger.to_string())));
+    let result = func();
+    TRIGGER.with(|t| *t.borrow_mut() = previous);
+    result
+}
+
+pub fn current_trigger() -> String {
+    match TRIGGER.with(|t| t.borrow().clone()) {
+        Some(t) => t,
+        None => thread::current().name().unwrap_or("unknown").to_string(),
+    }
+}
+
+pub fn outcome<T>(result: &Result<T>) -> String {
+    match *result {
+        Ok(_) => "ok".into(),
+        Err(ref e) => format!("error: {}", e),
+    }
+}
+
+impl AuditLog {
+    pub fn new(file: Option<PathBuf>) -> AuditLog {
+        AuditLog {
+            file: file,
+            recent: Mutex::new(VecDeque::new()),
+            export: Mutex::new(None),
+        }
+    }
+
+    pub fn with_export(self, export: Option<WorkSender<ExportedEvent>>) -> AuditLog {
+        let mut log = self;
+        log.export = Mutex::new(export);
+        log
+    }
+
+    pub fn in_memory() -> AuditLog {
+        AuditLog::new(None)
+    }
+
+    pub fn record(&self, action: &str, target: &str, outcome: &str) {
+        self.add(AuditEntry {
+            at: quiet_hours::now(),
+            action: action.into(),
+            target: target.into(),
+            trigger: current_trigger(),
+            outcome: outcome.into(),
+        });
+    }
+
+    pub fn add(&self, entry: AuditEntry) {
+        if let Some(ref export) = *self.export.lock().unwrap() {
+            if let Err(e) = export.send(ExportedEvent::action(&entry)) {
+                error!("Error sending audit entry to the event export: {}", e);
+            }
+        }
+
+        let mut recent = self.recent.lock().unwrap();
+        if let Some(ref file) = self.file {
+            if let Err(e) = append(file, &entry) {
+                error!("Error writing to audit log {:?}: {}", file, e);
+            }
+            return;
+        }
+
+        recent.push_back(entry);
+        while recent.len() > MAX_IN_MEMORY {
+            recent.pop_front();
+        }
+    }
+
+    // The latest `limit` entries that `filter` keeps, newest first.
+    pub fn entries<F>(&self, filter: F, limit: usize) -> Result<Vec<AuditEntry>>
+    where
+        F: Fn(&AuditEntry) -> bool,
+    {
+        let recent = self.recent.lock().unwrap();
+        let all: Vec<AuditEntry> = match self.file {
+            Some(ref file) => load(file)?,
+            None => recent.iter().cloned().collect(),
+        };
+
+        Ok(
+            all.into_iter()
+                .rev()
+                .filter(|e| filter(e))
+                .take(limit)
+                .collect(),
+        )
+    }
+}
+
+fn append(file: &PathBuf, entry: &AuditEntry) -> Result<()> {
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string(entry).map_err(|e| format!("Error serializing audit entry: {}", e))?;
+    let mut f = OpenOptions::new().create(true).append(true).open(file)?;
+    writeln!(f, "{}", json)?;
+    Ok(())
+}
+
+fn load(file: &PathBuf) -> Result<Vec<AuditEntry>> {
+    if !file.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut entries = vec![];
+    for line in BufReader::new(fs::File::open(file)?).lines() {
+        let line = line?;
+        match serde_json::from_str(&line) {
+            Ok(e) => entries.push(e),
+            // e.g. a line cut short by a crash: skip it rather than losing the whole log
+            Err(e) => error!("Skipping bad audit log line in {:?}: {}", file, e),
+        }
+    }
+    Ok(entries)
+}
+
+// A github session that records its write calls in an audit log, and passes everything on to `inner`.
+// Write calls for repos in dry run mode are only recorded, and get `planned` back instead.
+pub struct AuditedSession {
+    inner: Arc<Session>,
+    log: Arc<AuditLog>,
+    config: Arc<Config>,
+}
+
+impl AuditedSession {
+    pub fn new(inner: Arc<Session>, log: Arc<AuditLog>, config: Arc<Config>) -> AuditedSession {
+        AuditedSession {
+            inner: inner,
+            log: log,
+            config: config,
+        }
+    }
+
+    fn audit<T, F>(
+        &self,
+        action: &str,
+        owner: &str,
+        repo: &str,
+        target: String,
+        planned: Result<T>,
+        func: F,
+    ) -> Result<T>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        if self.dry_run(owner, repo) {
+            info!("Dry run: not doing {} on {}", action, target);
+            self.log.record(action, &target, DRY_RUN);
+            return planned;
+        }
+
+        let result = func();
+        self.log.record(action, &target, &outcome(&result));
+        result
+    }
+}
+
+impl Session for AuditedSession {
+    fn user(&self) -> &User {
+        self.inner.user()
+    }
+
+    fn github_host(&self) -> &str {
+        self.inner.github_host()
+    }
+
+    fn github_token(&self) -> &str {
+        self.inner.github_token()
+    }
+
+    fn get_pull_request(&self, owner: &str, repo: &str, number: u32) -> Result<PullRequest> {
+        self.inner.get_pull_request(owner, repo, number)
+    }
+
+    fn get_pull_requests(
+        &self,
+        owner: &str,
+        repo: &str,
+        state: Option<&str>,
+        head: Option<&str>,
+    ) -> Result<Vec<PullRequest>> {
+        self.inner.get_pull_requests(owner, repo, state, head)
+    }
+
+    fn create_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        title: &str,
+        body: &str,
+        head: &str,
+        base: &str,
+    ) -> Result<PullRequest> {
+        let target = format!("{}/{} {} -> {}", owner, repo, head, base);
+        let planned = Err(format!("Dry run: not creating pull request from {}", head).into());
+        self.audit("create_pull_request", owner, repo, target, planned, || {
+            self.inner.create_pull_request(owner, repo, title, body, head, base)
+        })
+    }
+
+    fn get_pull_request_labels(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<Label>> {
+        self.inner.get_pull_request_labels(owner, repo, number)
+    }
+
+    fn add_pull_request_labels(&self, owner: &str, repo: &str, number: u32, labels: Vec<String>) -> Result<()> {
+        let target = format!("{}/{}#{} {}", owner, repo, number, labels.join(","));
+        self.audit("add_pull_request_labels", owner, repo, target, Ok(()), || {
+            self.inner.add_pull_request_labels(owner, repo, number, labels)
+        })
+    }
+
+    fn remove_pull_request_label(&self, owner: &str, repo: &str, number: u32, label: &str) -> Result<()> {
+        let target = format!("{}/{}#{} {}", owner, repo, number, label);
+        self.audit("remove_pull_request_label", owner, repo, target, Ok(()), || {
+            self.inner.remove_pull_request_label(owner, repo, number, label)
+        })
+    }
+
+    fn set_pull_request_milestone(&self, owner: &str, repo: &str, number: u32, milestone: &str) -> Result<()> {
+        let target = format!("{}/{}#{} {}", owner, repo, number, milestone);
+        self.audit("set_pull_request_milestone", owner, repo, target, Ok(()), || {
+            self.inner.set_pull_request_milestone(owner, repo, number, milestone)
+        })
+    }
+
+    fn get_pull_request_commits(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<Commit>> {
+        self.inner.get_pull_request_commits(owner, repo, number)
+    }
+
+    fn get_pull_request_files(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<PullRequestFile>> {
+        self.inner.get_pull_request_files(owner, repo, number)
+    }
+
+    fn get_pull_request_stats(&self, owner: &str, repo: &str, number: u32) -> Result<DiffStats> {
+        self.inner.get_pull_request_stats(owner, repo, number)
+    }
+
+    fn get_pull_request_reviews(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<Review>> {
+        self.inner.get_pull_request_reviews(owner, repo, number)
+    }
+
+    fn get_review_comments(&self, owner: &str, repo: &str, number: u32, review_id: u64) -> Result<Vec<Comment>> {
+        self.inner.get_review_comments(owner, repo, number, review_id)
+    }
+
+    fn assign_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u32,
+        assignees: Vec<String>,
+    ) -> Result<AssignResponse> {
+        let target = format!("{}/{}#{} {}", owner, repo, number, assignees.join(","));
+        let planned = Ok(AssignResponse { assignees: vec![] });
+        self.audit("assign_pull_request", owner, repo, target, planned, || {
+            self.inner.assign_pull_request(owner, repo, number, assignees)
+        })
+    }
+
+    fn request_review(&self, owner: &str, repo: &str, number: u32, reviewers: Vec<String>) -> Result<()> {
+        let target = format!("{}/{}#{} {}", owner, repo, number, reviewers.join(","));
+        self.audit("request_review", owner, repo, target, Ok(()), || {
+            self.inner.request_review(owner, repo, number, reviewers)
+        })
+    }
+
+    fn dismiss_review(&self, owner: &str, repo: &str, number: u32, review_id: u64, message: &str) -> Result<()> {
+        let target = format!("{}/{}#{} review {}", owner, repo, number, review_id);
+        self.audit("dismiss_review", owner, repo, target, Ok(()), || {
+            self.inner.dismiss_review(owner, repo, number, review_id, message)
+        })
+    }
+
+    fn comment_pull_request(&self, owner: &str, repo: &str, number: u32, comment: &str) -> Result<()> {
+        let target = format!("{}/{}#{}", owner, repo, number);
+        self.audit("comment_pull_request", owner, repo, target, Ok(()), || {
+            self.inner.comment_pull_request(owner, repo, number, comment)
+        })
+    }
+
+    fn create_branch(&self, owner: &str, repo: &str, branch_name: &str, sha: &str) -> Result<()> {
+        let target = format!("{}/{} {}", owner, repo, branch_name);
+        self.audit("create_branch", owner, repo, target, Ok(()), || {
+            self.inner.create_branch(owner, repo, branch_name, sha)
+        })
+    }
+
+    fn delete_branch(&self, owner: &str, repo: &str, branch_name: &str) -> Result<()> {
+        let target = format!("{}/{} {}", owner, repo, branch_name);
+        self.audit("delete_branch", owner, repo, target, Ok(()), || self.inner.delete_branch(owner, repo, branch_name))
+    }
+
+    fn get_file_contents(&self, owner: &str, repo: &str, path: &str, ref_name: &str) -> Result<String> {
+        self.inner.get_file_contents(owner, repo, path, ref_name)
+    }
+
+    fn get_user(&self, login: &str) -> Result<User> {
+        self.inner.get_user(login)
+    }
+
+    fn get_user_permission(&self, owner: &str, repo: &str, login: &str) -> Result<String> {
+        self.inner.get_user_permission(owner, repo, login)
+    }
+
+    fn get_team_members(&self, org: &str, team: &str) -> Result<Vec<User>> {
+        self.inner.get_team_members(org, team)
+    }
+
+    fn get_statuses(&self, owner: &str, repo: &str, ref_name: &str) -> Result<Vec<Status>> {
+        self.inner.get_statuses(owner, repo, ref_name)
+    }
+
+    fn create_status(&self, owner: &str, repo: &str, ref_name: &str, status: &Status) -> Result<()> {
+        let target = format!("{}/{} {}", owner, repo, ref_name);
+        self.audit("create_status", owner, repo, target, Ok(()), || {
+            self.inner.create_status(owner, repo, ref_name, status)
+        })
+    }
+
+    fn get_combined_status(&self, owner: &str, repo: &str, ref_name: &str) -> Result<CombinedStatus> {
+        self.inner.get_combined_status(owner, repo, ref_name)
+    }
+
+    fn get_commit(&self, owner: &str, repo: &str, sha: &str) -> Result<Commit> {
+        self.inner.get_commit(owner, repo, sha)
+    }
+
+    fn compare_commits(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<Comparison> {
+        self.inner.compare_commits(owner, repo, base, head)
+    }
+
+    fn get_commits_between(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<Vec<String>> {
+        self.inner.get_commits_between(owner, repo, base, head)
+    }
+
+    fn create_release(&self, owner: &str, repo: &str, tag: &str, name: &str, body: &str) -> Result<()> {
+        let target = format!("{}/{} {}", owner, repo, tag);
+        self.audit("create_release", owner, repo, target, Ok(()), || {
+            self.inner.create_release(owner, repo, tag, name, body)
+        })
+    }
+
+    fn get_tags(&self, owner: &str, repo: &str) -> Result<Vec<String>> {
+        self.inner.get_tags(owner, repo)
+    }
+
+    fn is_branch_protected(&self, owner: &str, repo: &str, branch: &str) -> Result<bool> {
+        self.inner.is_branch_protected(owner, repo, branch)
+    }
+
+    fn protect_branch(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        required_checks: &Vec<String>,
+        required_approvals: u32,
+    ) -> Result<()> {
+        let target = format!("{}/{} {}", owner, repo, branch);
+        self.audit("protect_branch", owner, repo, target, Ok(()), || {
+            self.inner.protect_branch(owner, repo, branch, required_checks, required_approvals)
+        })
+    }
+
+    fn rerun_failed_jobs(&self, owner: &str, repo: &str, run_id: u64) -> Result<()> {
+        let target = format!("{}/{} run {}", owner, repo, run_id);
+        self.audit("rerun_failed_jobs", owner, repo, target, Ok(()), || {
+            self.inner.rerun_failed_jobs(owner, repo, run_id)
+        })
+    }
+
+    fn get_pull_requests_for_commit(&self, owner: &str, repo: &str, sha: &str) -> Result<Vec<PullRequest>> {
+        self.inner.get_pull_requests_for_commit(owner, repo, sha)
+    }
+
+    fn get_hooks(&self, owner: &str, repo: &str) -> Result<Vec<Hook>> {
+        self.inner.get_hooks(owner, repo)
+    }
+
+    fn save_hook(&self, owner: &str, repo: &str, hook: &Hook) -> Result<()> {
+        let target = format!("{}/{} hook {}", owner, repo, hook.config.url);
+        self.audit("save_hook", owner, repo, target, Ok(()), || self.inner.save_hook(owner, repo, hook))
+    }
+
+    fn merge_pull_request(&self, owner: &str, repo: &str, number: u32, sha: &str, merge_method: &str) -> Result<()> {
+        let target = format!("{}/{}#{}", owner, repo, number);
+        self.audit("merge_pull_request", owner, repo, target, Ok(()), || {
+            self.inner.merge_pull_request(owner, repo, number, sha, merge_method)
+        })
+    }
+
+    fn update_pull_request_branch(&self, owner: &str, repo: &str, number: u32, head_sha: &str) -> Result<()> {
+        let target = format!("{}/{}#{}", owner, repo, number);
+        self.audit("update_pull_request_branch", owner, repo, target, Ok(()), || {
+            self.inner.update_pull_request_branch(owner, repo, number, head_sha)
+        })
+    }
+
+    fn get_open_pull_requests_for_head(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        shas: &[&str],
+    ) -> Result<Vec<PullRequest>> {
+        self.inner.get_open_pull_requests_for_head(owner, repo, branch, shas)
+    }
+
+    fn dry_run(&self, owner: &str, repo: &str) -> bool {
+        let url = format!("https://{}/{}/{}", self.github_host(), owner, repo);
+        self.config.dry_run(github::Repo::parse(&url).ok().as_ref())
+    }
+
+    fn clone_url(&self, owner: &str, repo: &str) -> String {
+        self.inner.clone_url(owner, repo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate tempdir;
+    use self::tempdir::TempDir;
+
+    #[test]
+    fn test_in_memory() {
+        let log = AuditLog::in_memory();
+        with_trigger("pull_request closed (delivery 1)", || {
+            log.record("comment_pull_request", "org/repo#1", "ok");
+        });
+        log.record("slack_message", "#reviews", "error: HTTP 500");
+
+        let entries = log.entries(|_| true, 10).unwrap();
+        assert_eq!(2, entries.len());
+        assert_eq!("slack_message", entries[0].action);
+        assert_eq!("pull_request closed (delivery 1)", entries[1].trigger);
+        assert_eq!("ok", entries[1].outcome);
+
+        let comments = log.entries(|e| e.action == "comment_pull_request", 10).unwrap();
+        assert_eq!(vec!["org/repo#1"], comments.iter().map(|e| e.target.as_str()).collect::<Vec<_>>());
+        assert_eq!(1, log.entries(|_| true, 1).unwrap().len());
+    }
+
+    #[test]
+    fn test_appends_to_file() {
+        let dir = TempDir::new("audit.rs").expect("create temp dir for audit.rs test");
+        let file = dir.path().join("data/audit.log");
+
+        AuditLog::new(Some(file.clone())).record("merge_pull_request", "org/repo#2", "ok");
+        let log = AuditLog::new(Some(file.clone()));
+        log.record("push_branch", "org/repo my-branch", "ok");
+
+        let actions: Vec<String> = log.entries(|_| true, 10).unwrap().into_iter().map(|e| e.action).collect();
+        assert_eq!(vec!["push_branch", "merge_pull_request"], actions);
+        assert_eq!(2, BufReader::new(fs::File::open(&file).unwrap()).lines().count());
+    }
+
+    #[test]
+    fn test_trigger() {
+        let current = current_trigger();
+        assert_eq!("push (delivery 2)", with_trigger("push (delivery 2)", current_trigger));
+        assert_eq!(current, current_trigger());
+    }
+}
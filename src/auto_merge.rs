@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use config::Config;
+use github;
+use github::api::Session;
+use messenger::{Messenger, MessengerFactory};
+use slack::SlackAttachmentBuilder;
+use worker;
+
+#[derive(Debug)]
+pub enum AutoMergeRequest {
+    PullRequest(github::Repo, github::PullRequest),
+    // check any open pull requests whose head is at this commit
+    Commit(github::Repo, String),
+}
+
+pub fn pr_req(repo: &github::Repo, pull_request: &github::PullRequest) -> AutoMergeRequest {
+    AutoMergeRequest::PullRequest(repo.clone(), pull_request.clone())
+}
+
+pub fn commit_req(repo: &github::Repo, sha: &str) -> AutoMergeRequest {
+    AutoMergeRequest::Commit(repo.clone(), sha.to_string())
+}
+
+// Only the latest review from each reviewer counts, and a plain comment doesn't undo an earlier
+// approval or change request.
+pub fn is_approved(reviews: &Vec<github::Review>, required_approvals: u32) -> bool {
+    let mut latest: HashMap<String, String> = HashMap::new();
+    for review in reviews {
+        let state = review.state.to_lowercase();
+        if state == "approved" || state == "changes_requested" || state == "dismissed" {
+            latest.insert(review.user.login().to_string(), state);
+        }
+    }
+
+    if latest.values().any(|s| s == "changes_requested") {
+        return false;
+    }
+
+    latest.values().filter(|s| *s == "approved").count() as u32 >= required_approvals
+}
+
+pub fn auto_merge_pull_request(
+    github: &Session,
+    config: &Config,
+    messenger: &Messenger,
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+) {
+    let branch = &pull_request.base.ref_name;
+    let label = match config.repos().auto_merge_label(repo, branch) {
+        Some(l) => l,
+        None => return,
+    };
+
+    if !pull_request.is_open() || pull_request.is_wip() {
+        return;
+    }
+
+    let owner = repo.owner.login();
+    let labels = match github.get_pull_request_labels(owner, &repo.name, pull_request.number) {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Error getting labels for PR #{}: {}", pull_request.number, e);
+            return;
+        }
+    };
+    if !labels.iter().any(|l| l.name == label) {
+        return;
+    }
+
+    let reviews = match github.get_pull_request_reviews(owner, &repo.name, pull_request.number) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Error getting reviews for PR #{}: {}", pull_request.number, e);
+            return;
+        }
+    };
+    if !is_approved(&reviews, config.repos().auto_merge_approvals(repo, branch)) {
+        info!("PR #{} is not approved yet: not auto-merging", pull_request.number);
+        return;
+    }
+
+    match github.get_combined_status(owner, &repo.name, &pull_request.head.sha) {
+        Ok(ref status) if status.is_success() => (),
+        Ok(_) => {
+            info!("PR #{} is not green yet: not auto-merging", pull_request.number);
+            return;
+        }
+        Err(e) => {
+            error!("Error getting status for PR #{}: {}", pull_request.number, e);
+            return;
+        }
+    };
+
+    let merge_method = config.repos().merge_strategy(repo, branch);
+    let title = format!("Pull Request #{}: \"{}\"", pull_request.number, pull_request.title);
+
+    match github.merge_pull_request(owner, &repo.name, pull_request.number, &pull_request.head.sha, &merge_method) {
+        Ok(_) => {
+            let attachments = vec![
+                SlackAttachmentBuilder::new("")
+                    .title(title)
+                    .title_link(pull_request.html_url.as_str())
+                    .build(),
+            ];
+            messenger.for_pull_request(pull_request).send_to_channel(
+                "Pull Request automatically merged",
+                &attachments,
+                repo,
+                Some(branch.as_str()),
+            );
+        }
+        Err(e) => {
+            let attachments = vec![
+                SlackAttachmentBuilder::new(&format!("{}", e))
+                    .title(title)
+                    .title_link(pull_request.html_url.as_str())
+                    .color("danger")
+                    .build(),
+            ];
+            messenger.send_to_owner(
+                "Error auto-merging Pull Request",
+                &attachments,
+                &pull_request.user,
+                repo,
+                Some(branch.as_str()),
+            );
+        }
+    };
+}
+
+struct Runner {
+    config: Arc<Config>,
+    github_session: Arc<Session>,
+    messengers: MessengerFactory,
+}
+
+pub fn new_worker(
+    config: Arc<Config>,
+    github_session: Arc<Session>,
+    messengers: MessengerFactory,
+) -> worker::Worker<AutoMergeRequest> {
+    worker::Worker::new(
+        "auto-merge",
+        Runner {
+            config: config,
+            github_session: github_session,
+            messengers: messengers,
+        },
+    )
+}
+
+impl worker::Runner<AutoMergeRequest> for Runner {
+    fn handle(&self, req: AutoMergeRequest) {
+        let messenger = self.messengers.new_messenger();
+        let github = &*self.github_session;
+
+        match req {
+            AutoMergeRequest::PullRequest(repo, pull_request) => {
+                auto_merge_pull_request(github, &self.config, &*messenger, &repo, &pull_request);
+            }
+            AutoMergeRequest::Commit(repo, sha) => {
+                let prs = match github.get_pull_requests(repo.owner.login(), &repo.name, Some("open"), None) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        error!("Error looking up PRs for {}: {}", sha, e);
+                        return;
+                    }
+                };
+
+                for pull_request in prs.iter().filter(|pr| pr.head.sha == sha) {
+                    auto_merge_pull_request(github, &self.config, &*messenger, &repo, pull_request);
+                }
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review(login: &str, state: &str) -> github::Review {
+        let mut review = github::Review::new("", github::User::new(login));
+        review.state = state.into();
+        review
+    }
+
+    #[test]
+    fn test_is_approved() {
+        assert!(!is_approved(&vec![], 1));
+        assert!(is_approved(&vec![], 0));
+
+        assert!(is_approved(&vec![review("joe", "APPROVED")], 1));
+        assert!(!is_approved(&vec![review("joe", "APPROVED")], 2));
+        assert!(is_approved(&vec![review("joe", "APPROVED"), review("bob", "approved")], 2));
+
+        // same reviewer twice only counts once
+        assert!(!is_approved(&vec![review("joe", "APPROVED"), review("joe", "APPROVED")], 2));
+
+        // comments don't reset anything
+        assert!(is_approved(&vec![review("joe", "APPROVED"), review("joe", "COMMENTED")], 1));
+
+        // latest review wins
+        assert!(!is_approved(&vec![review("joe", "APPROVED"), review("joe", "CHANGES_REQUESTED")], 1));
+        assert!(is_approved(&vec![review("joe", "CHANGES_REQUESTED"), review("joe", "APPROVED")], 1));
+
+        // any outstanding change request blocks
+        assert!(!is_approved(
+            &vec![review("joe", "APPROVED"), review("bob", "APPROVED"), review("sue", "CHANGES_REQUESTED")],
+            1,
+        ));
+    }
+}
@@ -0,0 +1,828 @@
+use std::collections::HashMap;
+
+use serde_json;
+use tokio_core::reactor::Remote;
+use url::form_urlencoded;
+
+use bitbucket::models::*;
+use config::BitbucketConfig;
+use errors::*;
+use github;
+use github::api::Session;
+use http_client::HTTPClient;
+
+#[derive(Deserialize)]
+struct Page<T> {
+    values: Vec<T>,
+}
+
+// A github session backed by the Bitbucket Server REST API, so that bitbucket pull requests can be
+// handled by the same code as github's. Bitbucket Server has no pull request labels nor assignees:
+// there are never any labels, and assigning a pull request adds reviewers instead.
+pub struct BitbucketSession {
+    client: HTTPClient,
+    // for the branch-utils and build-status APIs, which live beside the core API
+    rest_base: String,
+    host: String,
+    token: String,
+    user: github::User,
+}
+
+impl BitbucketSession {
+    pub fn new(core_remote: Remote, config: &BitbucketConfig) -> Result<BitbucketSession> {
+        let api_base = match config.api_base {
+            Some(ref base) if !base.is_empty() => base.trim_right_matches('/').to_string(),
+            _ => format!("https://{}/rest/api/1.0", config.host),
+        };
+        let rest_base = api_base.trim_right_matches("/api/1.0").to_string();
+
+        let client = HTTPClient::new(core_remote, &api_base).with_headers(hashmap!{
+                "Accept" => "application/json".to_string(),
+                "Content-Type" => "application/json".to_string(),
+                "Authorization" => format!("Bearer {}", config.api_token),
+            });
+
+        // make sure we can auth as this user before handing out session.
+        let user: User = client.get(&format!("users/{}", encode(&config.username))).map_err(|e| {
+            Error::from(format!("Error authenticating to bitbucket with token: {}", e))
+        })?;
+
+        Ok(BitbucketSession {
+            client: client,
+            rest_base: rest_base,
+            user: user.to_github(),
+            host: config.host.clone(),
+            token: config.api_token.clone(),
+        })
+    }
+
+    // The commits a push added to a branch, newest first. Bitbucket push webhooks don't include them.
+    pub fn get_push_commits(
+        &self,
+        owner: &str,
+        repo: &str,
+        before: &str,
+        after: &str,
+    ) -> Result<Vec<github::PushCommit>> {
+        let commits: Page<BitbucketCommit> = self.client
+            .get(&format!("{}/commits?since={}&until={}&limit=100", repo_path(owner, repo), before, after))
+            .map_err(|e| Error::from(format!("Error looking up commits: {}/{} {}: {}", owner, repo, after, e)))?;
+
+        let html_url = self.repo(owner, repo).html_url;
+        Ok(
+            commits
+                .values
+                .into_iter()
+                .map(|c| {
+                    github::PushCommit {
+                        url: format!("{}/commits/{}", html_url, c.id),
+                        id: c.id,
+                        tree_id: String::new(),
+                        message: c.message,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn repo(&self, owner: &str, repo: &str) -> github::Repo {
+        Repository::new(owner, repo).to_github(&self.host)
+    }
+
+    fn get_bitbucket_pull_request(&self, owner: &str, repo: &str, number: u32) -> Result<PullRequest> {
+        self.client
+            .get(&format!("{}/pull-requests/{}", repo_path(owner, repo), number))
+            .map_err(|e| format!("Error looking up PR: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
+    // replaces the pull request's reviewers with its current ones plus `logins`
+    fn add_reviewers(&self, owner: &str, repo: &str, number: u32, logins: &Vec<String>) -> Result<PullRequest> {
+        #[derive(Serialize)]
+        struct ReviewerName<'a> {
+            name: &'a str,
+        }
+        #[derive(Serialize)]
+        struct Reviewer<'a> {
+            user: ReviewerName<'a>,
+        }
+        #[derive(Serialize)]
+        struct UpdatePullRequest<'a> {
+            version: u32,
+            title: &'a str,
+            reviewers: Vec<Reviewer<'a>>,
+        }
+
+        let pull_request = self.get_bitbucket_pull_request(owner, repo, number)?;
+        let mut names: Vec<&str> = pull_request.reviewers.iter().map(|r| r.user.name.as_str()).collect();
+        names.extend(logins.iter().map(|l| l.as_str()));
+        names.sort();
+        names.dedup();
+
+        let update = UpdatePullRequest {
+            version: pull_request.version,
+            title: &pull_request.title,
+            reviewers: names.into_iter().map(|n| Reviewer { user: ReviewerName { name: n } }).collect(),
+        };
+        self.client
+            .put(&format!("{}/pull-requests/{}", repo_path(owner, repo), number), &update)
+            .map_err(|e| format!("Error updating PR reviewers: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+}
+
+#[derive(Deserialize)]
+struct BitbucketCommit {
+    id: String,
+    message: String,
+    author: Option<User>,
+}
+
+impl Session for BitbucketSession {
+    fn user(&self) -> &github::User {
+        &self.user
+    }
+
+    fn github_host(&self) -> &str {
+        &self.host
+    }
+
+    fn github_token(&self) -> &str {
+        &self.token
+    }
+
+    fn get_pull_request(&self, owner: &str, repo: &str, number: u32) -> Result<github::PullRequest> {
+        Ok(self.get_bitbucket_pull_request(owner, repo, number)?.to_github(&self.host))
+    }
+
+    fn get_pull_requests(
+        &self,
+        owner: &str,
+        repo: &str,
+        state: Option<&str>,
+        head: Option<&str>,
+    ) -> Result<Vec<github::PullRequest>> {
+        let mut path = format!("{}/pull-requests?limit=100", repo_path(owner, repo));
+        match state {
+            Some("open") => path += "&state=OPEN",
+            // closed could be either merged or declined
+            Some("closed") | Some("all") => path += "&state=ALL",
+            _ => (),
+        };
+        if let Some(head) = head {
+            // github heads are "owner:branch"
+            let branch = head.splitn(2, ':').last().unwrap_or(head);
+            path += &format!("&direction=OUTGOING&at={}", encode(&format!("refs/heads/{}", branch)));
+        }
+
+        let pull_requests: Page<PullRequest> = self.client.get(&path).map_err(|e| {
+            Error::from(format!("Error looking up PRs: {}/{}: {}", owner, repo, e))
+        })?;
+        Ok(
+            pull_requests
+                .values
+                .iter()
+                .map(|p| p.to_github(&self.host))
+                .filter(|p| state != Some("closed") || p.state == "closed")
+                .collect(),
+        )
+    }
+
+    fn create_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        title: &str,
+        body: &str,
+        head: &str,
+        base: &str,
+    ) -> Result<github::PullRequest> {
+        #[derive(Serialize)]
+        struct RefName<'a> {
+            id: String,
+            repository: &'a Repository,
+        }
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct CreatePullRequest<'a> {
+            title: &'a str,
+            description: &'a str,
+            from_ref: RefName<'a>,
+            to_ref: RefName<'a>,
+        }
+
+        let repository = Repository::new(owner, repo);
+        let create = CreatePullRequest {
+            title: title,
+            description: body,
+            from_ref: RefName {
+                id: format!("refs/heads/{}", head),
+                repository: &repository,
+            },
+            to_ref: RefName {
+                id: format!("refs/heads/{}", base),
+                repository: &repository,
+            },
+        };
+        let pull_request: PullRequest = self.client
+            .post(&format!("{}/pull-requests", repo_path(owner, repo)), &create)
+            .map_err(|e| Error::from(format!("Error creating PR: {}/{}: {}", owner, repo, e)))?;
+        Ok(pull_request.to_github(&self.host))
+    }
+
+    fn get_pull_request_labels(&self, _owner: &str, _repo: &str, _number: u32) -> Result<Vec<github::Label>> {
+        Ok(vec![])
+    }
+
+    fn add_pull_request_labels(&self, _: &str, _: &str, _: u32, _: Vec<String>) -> Result<()> {
+        Err("Pull request labels are not supported on bitbucket".into())
+    }
+
+    fn remove_pull_request_label(&self, _: &str, _: &str, _: u32, _: &str) -> Result<()> {
+        Err("Pull request labels are not supported on bitbucket".into())
+    }
+
+    fn set_pull_request_milestone(&self, _: &str, _: &str, _: u32, _: &str) -> Result<()> {
+        Err("Milestones are not supported on bitbucket".into())
+    }
+
+    fn get_pull_request_commits(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<github::Commit>> {
+        let commits: Page<BitbucketCommit> = self.client
+            .get(&format!("{}/pull-requests/{}/commits?limit=100", repo_path(owner, repo), number))
+            .map_err(|e| Error::from(format!("Error looking up PR commits: {}/{} #{}: {}", owner, repo, number, e)))?;
+
+        let html_url = self.repo(owner, repo).html_url;
+        Ok(
+            commits
+                .values
+                .into_iter()
+                .map(|c| {
+                    let mut commit = github::Commit::new();
+                    commit.html_url = format!("{}/commits/{}", html_url, c.id);
+                    commit.sha = c.id;
+                    commit.commit.message = c.message;
+                    commit.author = c.author.map(|a| a.to_github());
+                    commit
+                })
+                .collect(),
+        )
+    }
+
+    // bitbucket doesn't count lines changed per file: only the file names and statuses are known
+    fn get_pull_request_files(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<github::PullRequestFile>> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Path {
+            to_string: String,
+        }
+        #[derive(Deserialize)]
+        struct Change {
+            path: Path,
+            // "ADD", "MODIFY", "DELETE", "MOVE", or "COPY"
+            #[serde(rename = "type")]
+            change_type: String,
+        }
+
+        let changes: Page<Change> = self.client
+            .get(&format!("{}/pull-requests/{}/changes?limit=1000", repo_path(owner, repo), number))
+            .map_err(|e| Error::from(format!("Error looking up PR files: {}/{} #{}: {}", owner, repo, number, e)))?;
+
+        Ok(
+            changes
+                .values
+                .into_iter()
+                .map(|c| {
+                    let status = match c.change_type.as_str() {
+                        "ADD" | "COPY" => "added",
+                        "DELETE" => "removed",
+                        "MOVE" => "renamed",
+                        _ => "modified",
+                    };
+                    github::PullRequestFile {
+                        filename: c.path.to_string,
+                        status: status.into(),
+                        additions: 0,
+                        deletions: 0,
+                        changes: 0,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn get_pull_request_stats(&self, owner: &str, repo: &str, number: u32) -> Result<github::DiffStats> {
+        #[derive(Deserialize)]
+        struct Segment {
+            // "ADDED", "REMOVED", or "CONTEXT"
+            #[serde(rename = "type")]
+            segment_type: String,
+            lines: Vec<serde_json::Value>,
+        }
+        #[derive(Deserialize)]
+        struct Hunk {
+            segments: Vec<Segment>,
+        }
+        #[derive(Deserialize)]
+        struct FileDiff {
+            hunks: Option<Vec<Hunk>>,
+        }
+        #[derive(Deserialize)]
+        struct Diff {
+            diffs: Vec<FileDiff>,
+        }
+
+        let diff: Diff = self.client
+            .get(&format!("{}/pull-requests/{}/diff?contextLines=0", repo_path(owner, repo), number))
+            .map_err(|e| Error::from(format!("Error looking up PR diff: {}/{} #{}: {}", owner, repo, number, e)))?;
+
+        let count = |segment_type: &str| -> u32 {
+            diff.diffs
+                .iter()
+                .flat_map(|d| d.hunks.iter().flat_map(|h| h.iter()))
+                .flat_map(|h| h.segments.iter())
+                .filter(|s| s.segment_type == segment_type)
+                .map(|s| s.lines.len() as u32)
+                .sum()
+        };
+        Ok(github::DiffStats {
+            additions: count("ADDED"),
+            deletions: count("REMOVED"),
+            changed_files: diff.diffs.len() as u32,
+        })
+    }
+
+    fn get_pull_request_reviews(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<github::Review>> {
+        let pull_request = self.get_pull_request(owner, repo, number)?;
+        Ok(pull_request.reviews.unwrap_or(vec![]))
+    }
+
+    fn get_review_comments(&self, _: &str, _: &str, _: u32, _: u64) -> Result<Vec<github::Comment>> {
+        Err("Review comments are not supported on bitbucket".into())
+    }
+
+    fn assign_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u32,
+        assignees: Vec<String>,
+    ) -> Result<github::AssignResponse> {
+        self.add_reviewers(owner, repo, number, &assignees)?;
+        Ok(github::AssignResponse { assignees: assignees.iter().map(|a| github::User::new(a)).collect() })
+    }
+
+    fn request_review(&self, owner: &str, repo: &str, number: u32, reviewers: Vec<String>) -> Result<()> {
+        self.add_reviewers(owner, repo, number, &reviewers)?;
+        Ok(())
+    }
+
+    fn dismiss_review(&self, _: &str, _: &str, _: u32, _: u64, _: &str) -> Result<()> {
+        // bitbucket only lets reviewers change their own status
+        Err("Dismissing approvals is not supported on bitbucket".into())
+    }
+
+    fn comment_pull_request(&self, owner: &str, repo: &str, number: u32, comment: &str) -> Result<()> {
+        self.client
+            .post_void(
+                &format!("{}/pull-requests/{}/comments", repo_path(owner, repo), number),
+                &hashmap!{ "text" => comment },
+            )
+            .map_err(|e| format!("Error commenting on PR: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
+    fn create_branch(&self, owner: &str, repo: &str, branch_name: &str, sha: &str) -> Result<()> {
+        self.client
+            .post_void(
+                &format!("{}/branch-utils/1.0/{}/branches", self.rest_base, repo_path(owner, repo)),
+                &hashmap!{ "name" => branch_name, "startPoint" => sha },
+            )
+            .map_err(|e| format!("Error creating branch {}/{} {}: {}", owner, repo, branch_name, e).into())
+    }
+
+    fn delete_branch(&self, owner: &str, repo: &str, branch_name: &str) -> Result<()> {
+        self.client
+            .delete_void_with_body(
+                &format!("{}/branch-utils/1.0/{}/branches", self.rest_base, repo_path(owner, repo)),
+                &hashmap!{ "name" => format!("refs/heads/{}", branch_name) },
+            )
+            .map_err(|e| format!("Error deleting branch {}/{} {}: {}", owner, repo, branch_name, e).into())
+    }
+
+    fn get_file_contents(&self, owner: &str, repo: &str, path: &str, ref_name: &str) -> Result<String> {
+        #[derive(Deserialize)]
+        struct Line {
+            text: String,
+        }
+        #[derive(Deserialize)]
+        struct FileContents {
+            lines: Vec<Line>,
+        }
+
+        let contents: FileContents = self.client
+            .get(&format!("{}/browse/{}?at={}&limit=100000", repo_path(owner, repo), path, encode(ref_name)))
+            .map_err(|e| Error::from(format!("Error getting {} from {}/{} {}: {}", path, owner, repo, ref_name, e)))?;
+
+        let lines: Vec<String> = contents.lines.into_iter().map(|l| l.text).collect();
+        Ok(lines.join("\n") + "\n")
+    }
+
+    fn get_user(&self, login: &str) -> Result<github::User> {
+        let user: User = self.client.get(&format!("users/{}", encode(login))).map_err(|e| {
+            Error::from(format!("Error looking up bitbucket user {}: {}", login, e))
+        })?;
+        Ok(user.to_github())
+    }
+
+    fn get_user_permission(&self, owner: &str, repo: &str, login: &str) -> Result<String> {
+        #[derive(Deserialize)]
+        struct UserPermission {
+            user: User,
+            // "REPO_READ", "REPO_WRITE", or "REPO_ADMIN"
+            permission: String,
+        }
+
+        // only lists the permissions given on the repo itself, not the ones inherited from its project
+        let permissions: Page<UserPermission> = self.client
+            .get(&format!("{}/permissions/users?filter={}", repo_path(owner, repo), encode(login)))
+            .map_err(|e| Error::from(format!("Error getting permission of {} on {}/{}: {}", login, owner, repo, e)))?;
+
+        let permission = permissions.values.into_iter().find(|p| p.user.name == login);
+        Ok(
+            match permission.as_ref().map(|p| p.permission.as_str()) {
+                Some("REPO_ADMIN") => "admin",
+                Some("REPO_WRITE") => "write",
+                Some("REPO_READ") => "read",
+                _ => "none",
+            }.into(),
+        )
+    }
+
+    fn get_team_members(&self, _org: &str, _team: &str) -> Result<Vec<github::User>> {
+        Err("Team mentions are not supported on bitbucket".into())
+    }
+
+    fn get_statuses(&self, owner: &str, repo: &str, ref_name: &str) -> Result<Vec<github::Status>> {
+        #[derive(Deserialize)]
+        struct BuildStatus {
+            // "SUCCESSFUL", "FAILED", or "INPROGRESS"
+            state: String,
+            key: String,
+            url: Option<String>,
+            description: Option<String>,
+        }
+
+        let statuses: Page<BuildStatus> = self.client
+            .get(&format!("{}/build-status/1.0/commits/{}", self.rest_base, ref_name))
+            .map_err(|e| Error::from(format!("Error getting statuses {}/{} {}: {}", owner, repo, ref_name, e)))?;
+
+        Ok(
+            statuses
+                .values
+                .into_iter()
+                .map(|s| {
+                    let state = match s.state.as_str() {
+                        "SUCCESSFUL" => "success",
+                        "FAILED" => "failure",
+                        _ => "pending",
+                    };
+                    github::Status {
+                        state: state.into(),
+                        target_url: s.url,
+                        context: Some(s.key),
+                        description: s.description,
+                        creator: None,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn create_status(&self, owner: &str, repo: &str, ref_name: &str, status: &github::Status) -> Result<()> {
+        let state = match status.state.as_str() {
+            "success" => "SUCCESSFUL",
+            "failure" | "error" => "FAILED",
+            _ => "INPROGRESS",
+        };
+        let key = status.context.clone().unwrap_or("octobot".into());
+        let mut body: HashMap<&str, String> = hashmap!{
+            "state" => state.to_string(),
+            "name" => key.clone(),
+            "key" => key,
+            // a url is required
+            "url" => status.target_url.clone().unwrap_or(self.repo(owner, repo).html_url),
+        };
+        if let Some(ref description) = status.description {
+            body.insert("description", description.clone());
+        }
+
+        self.client
+            .post_void(&format!("{}/build-status/1.0/commits/{}", self.rest_base, ref_name), &body)
+            .map_err(|e| format!("Error creating status {}/{} {}: {}", owner, repo, ref_name, e).into())
+    }
+
+    fn get_combined_status(&self, owner: &str, repo: &str, ref_name: &str) -> Result<github::CombinedStatus> {
+        let statuses = self.get_statuses(owner, repo, ref_name)?;
+
+        let state = if statuses.iter().any(|s| s.state == "failure") {
+            "failure"
+        } else if statuses.is_empty() || statuses.iter().any(|s| s.state == "pending") {
+            "pending"
+        } else {
+            "success"
+        };
+
+        Ok(github::CombinedStatus {
+            state: state.into(),
+            sha: ref_name.into(),
+            statuses: statuses,
+        })
+    }
+
+    fn get_commit(&self, _: &str, _: &str, _: &str) -> Result<github::Commit> {
+        Err("Commit authors are not supported on bitbucket".into())
+    }
+
+    fn compare_commits(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<github::Comparison> {
+        // the commits reachable from `from` but not from `to`
+        let count = |from: &str, to: &str| -> Result<u32> {
+            let commits: Page<BitbucketCommit> = self.client
+                .get(&format!(
+                    "{}/compare/commits?from={}&to={}&limit=1000",
+                    repo_path(owner, repo),
+                    encode(from),
+                    encode(to)
+                ))
+                .map_err(|e| Error::from(format!("Error comparing {}/{} {}...{}: {}", owner, repo, base, head, e)))?;
+            Ok(commits.values.len() as u32)
+        };
+
+        Ok(github::Comparison {
+            ahead_by: count(head, base)?,
+            behind_by: count(base, head)?,
+        })
+    }
+
+    fn get_commits_between(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<Vec<String>> {
+        let commits: Page<BitbucketCommit> = self.client
+            .get(&format!(
+                "{}/compare/commits?from={}&to={}&limit=1000",
+                repo_path(owner, repo),
+                encode(head),
+                encode(base)
+            ))
+            .map_err(|e| Error::from(format!("Error comparing {}/{} {}...{}: {}", owner, repo, base, head, e)))?;
+        Ok(commits.values.into_iter().map(|c| c.id).collect())
+    }
+
+    fn create_release(&self, _: &str, _: &str, _: &str, _: &str, _: &str) -> Result<()> {
+        Err("Releases are not supported on bitbucket".into())
+    }
+
+    fn get_tags(&self, owner: &str, repo: &str) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Tag {
+            display_id: String,
+        }
+
+        let tags: Page<Tag> = self.client
+            .get(&format!("{}/tags?limit=1000", repo_path(owner, repo)))
+            .map_err(|e| Error::from(format!("Error looking up tags: {}/{}: {}", owner, repo, e)))?;
+        Ok(tags.values.into_iter().map(|t| t.display_id).collect())
+    }
+
+    fn is_branch_protected(&self, _: &str, _: &str, _: &str) -> Result<bool> {
+        Err("Branch protection is not supported on bitbucket".into())
+    }
+
+    fn protect_branch(&self, _: &str, _: &str, _: &str, _: &Vec<String>, _: u32) -> Result<()> {
+        Err("Branch protection is not supported on bitbucket".into())
+    }
+
+    fn rerun_failed_jobs(&self, _: &str, _: &str, _: u64) -> Result<()> {
+        Err("Re-running builds is not supported on bitbucket".into())
+    }
+
+    fn get_pull_requests_for_commit(&self, _: &str, _: &str, _: &str) -> Result<Vec<github::PullRequest>> {
+        Err("Looking up pull requests by commit is not supported on bitbucket".into())
+    }
+
+    fn get_hooks(&self, _: &str, _: &str) -> Result<Vec<github::Hook>> {
+        Err("Setting up webhooks is not supported on bitbucket".into())
+    }
+
+    fn save_hook(&self, _: &str, _: &str, _: &github::Hook) -> Result<()> {
+        Err("Setting up webhooks is not supported on bitbucket".into())
+    }
+
+    fn merge_pull_request(&self, owner: &str, repo: &str, number: u32, sha: &str, merge_method: &str) -> Result<()> {
+        // bitbucket can't check the head itself
+        let pull_request = self.get_bitbucket_pull_request(owner, repo, number)?;
+        if !sha.is_empty() && pull_request.from_ref.latest_commit.as_ref().map(|s| s.as_str()) != Some(sha) {
+            return Err(format!("Head of PR {}/{} #{} is no longer {}", owner, repo, number, sha).into());
+        }
+
+        let strategy = match merge_method {
+            "squash" => "squash",
+            "rebase" => "rebase-no-ff",
+            _ => "no-ff",
+        };
+        self.client
+            .post_void(
+                &format!("{}/pull-requests/{}/merge?version={}", repo_path(owner, repo), number, pull_request.version),
+                &hashmap!{ "strategyId" => strategy },
+            )
+            .map_err(|e| format!("Error merging PR {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
+    fn update_pull_request_branch(&self, owner: &str, repo: &str, number: u32, _head_sha: &str) -> Result<()> {
+        Err(format!("Updating PR branches isn't supported on bitbucket: {}/{} #{}", owner, repo, number).into())
+    }
+
+    fn clone_url(&self, owner: &str, repo: &str) -> String {
+        format!("https://{}@{}/scm/{}/{}.git", self.user.login(), self.host, owner.to_lowercase(), repo)
+    }
+}
+
+fn repo_path(owner: &str, repo: &str) -> String {
+    format!("projects/{}/repos/{}", encode(owner), encode(repo))
+}
+
+fn encode(value: &str) -> String {
+    form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_http::MockHttp;
+    use serde_json::Value;
+
+    fn new_session(http: &MockHttp) -> BitbucketSession {
+        http.expect_header("authorization", "Bearer the-token");
+        http.expect_get("/rest/api/1.0/users/octobot", json!({"name": "octobot", "displayName": "Octobot"}));
+
+        let config = BitbucketConfig {
+            webhook_secret: String::new(),
+            host: "the-bitbucket-host".into(),
+            username: "octobot".into(),
+            api_token: "the-token".into(),
+            api_base: Some(http.url("/rest/api/1.0/")),
+        };
+        BitbucketSession::new(http.core_remote(), &config).expect("bitbucket session")
+    }
+
+    fn branch_ref(branch: &str, sha: &str) -> Value {
+        json!({
+            "id": format!("refs/heads/{}", branch),
+            "displayId": branch,
+            "latestCommit": sha,
+            "repository": {"slug": "some-repo", "project": {"key": "PRJ"}},
+        })
+    }
+
+    fn pull_request(reviewers: Value) -> Value {
+        json!({
+            "id": 5,
+            "version": 3,
+            "title": "The PR",
+            "state": "OPEN",
+            "fromRef": branch_ref("the-branch", "abc123"),
+            "toRef": branch_ref("master", "def456"),
+            "author": {"user": {"name": "joe"}},
+            "reviewers": reviewers,
+        })
+    }
+
+    #[test]
+    fn test_new_session() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        assert_eq!("octobot", session.user().login());
+        assert_eq!(http.url("/rest"), session.rest_base);
+        assert_eq!("https://octobot@the-bitbucket-host/scm/prj/some-repo.git", session.clone_url("PRJ", "some-repo"));
+    }
+
+    #[test]
+    fn test_get_pull_requests() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect_get(
+            "/rest/api/1.0/projects/PRJ/repos/some-repo/pull-requests?limit=100&state=OPEN&direction=OUTGOING\
+             &at=refs%2Fheads%2Fthe-branch",
+            json!({"values": [pull_request(json!([]))]}),
+        );
+
+        let prs = session.get_pull_requests("PRJ", "some-repo", Some("open"), Some("PRJ:the-branch")).unwrap();
+        assert_eq!(1, prs.len());
+        assert_eq!(5, prs[0].number);
+        assert_eq!("the-branch", prs[0].head.ref_name);
+        assert_eq!("abc123", prs[0].head.sha);
+        assert_eq!("https://the-bitbucket-host/projects/PRJ/repos/some-repo/pull-requests/5", prs[0].html_url);
+    }
+
+    #[test]
+    fn test_get_pull_request_reviews() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect_get(
+            "/rest/api/1.0/projects/PRJ/repos/some-repo/pull-requests/5",
+            pull_request(json!([
+                {"user": {"name": "jane"}, "status": "APPROVED"},
+                {"user": {"name": "bob"}, "status": "NEEDS_WORK"},
+                {"user": {"name": "sue"}, "status": "UNAPPROVED"},
+            ])),
+        );
+
+        let reviews = session.get_pull_request_reviews("PRJ", "some-repo", 5).unwrap();
+        let states: Vec<(&str, &str)> = reviews.iter().map(|r| (r.user.login(), r.state.as_str())).collect();
+        assert_eq!(vec![("jane", "APPROVED"), ("bob", "CHANGES_REQUESTED")], states);
+    }
+
+    #[test]
+    fn test_request_review_keeps_reviewers() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect_get(
+            "/rest/api/1.0/projects/PRJ/repos/some-repo/pull-requests/5",
+            pull_request(json!([{"user": {"name": "sue"}, "status": "UNAPPROVED"}])),
+        );
+        http.expect(
+            "PUT",
+            "/rest/api/1.0/projects/PRJ/repos/some-repo/pull-requests/5",
+            Some(json!({
+                "version": 3,
+                "title": "The PR",
+                "reviewers": [{"user": {"name": "jane"}}, {"user": {"name": "sue"}}],
+            })),
+            200,
+            pull_request(json!([])),
+        );
+
+        session.request_review("PRJ", "some-repo", 5, vec!["jane".into(), "sue".into()]).unwrap();
+    }
+
+    #[test]
+    fn test_merge_pull_request() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        for strategy in &["squash", "rebase-no-ff", "no-ff"] {
+            http.expect_get("/rest/api/1.0/projects/PRJ/repos/some-repo/pull-requests/5", pull_request(json!([])));
+            http.expect(
+                "POST",
+                "/rest/api/1.0/projects/PRJ/repos/some-repo/pull-requests/5/merge?version=3",
+                Some(json!({ "strategyId": strategy })),
+                200,
+                json!({}),
+            );
+        }
+
+        session.merge_pull_request("PRJ", "some-repo", 5, "abc123", "squash").unwrap();
+        session.merge_pull_request("PRJ", "some-repo", 5, "abc123", "rebase").unwrap();
+        session.merge_pull_request("PRJ", "some-repo", 5, "abc123", "merge").unwrap();
+    }
+
+    #[test]
+    fn test_merge_pull_request_moved_head() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect_get("/rest/api/1.0/projects/PRJ/repos/some-repo/pull-requests/5", pull_request(json!([])));
+
+        let err = session.merge_pull_request("PRJ", "some-repo", 5, "old-sha", "merge").unwrap_err();
+        assert_eq!("Head of PR PRJ/some-repo #5 is no longer old-sha", format!("{}", err));
+    }
+
+    #[test]
+    fn test_create_branch() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect(
+            "POST",
+            "/rest/branch-utils/1.0/projects/PRJ/repos/some-repo/branches",
+            Some(json!({"name": "backport-5-1.0", "startPoint": "abc123"})),
+            200,
+            json!({}),
+        );
+
+        session.create_branch("PRJ", "some-repo", "backport-5-1.0", "abc123").unwrap();
+    }
+
+    #[test]
+    fn test_get_push_commits() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect_get(
+            "/rest/api/1.0/projects/PRJ/repos/some-repo/commits?since=abc123&until=def456&limit=100",
+            json!({"values": [{"id": "def456", "message": "Second"}, {"id": "bcd234", "message": "First"}]}),
+        );
+
+        let commits = session.get_push_commits("PRJ", "some-repo", "abc123", "def456").unwrap();
+        let commits: Vec<(&str, &str, &str)> =
+            commits.iter().map(|c| (c.id.as_str(), c.message.as_str(), c.url.as_str())).collect();
+        assert_eq!(
+            vec![
+                ("def456", "Second", "https://the-bitbucket-host/projects/PRJ/repos/some-repo/commits/def456"),
+                ("bcd234", "First", "https://the-bitbucket-host/projects/PRJ/repos/some-repo/commits/bcd234"),
+            ],
+            commits
+        );
+    }
+}
@@ -0,0 +1,517 @@
+use serde_json;
+use time;
+
+use errors::*;
+use github;
+
+// Bitbucket Server's objects, and how they map onto github's: projects are owners, reviewers that
+// approved or marked "needs work" are reviews, and the others are requested reviewers.
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub name: String,
+    pub email_address: Option<String>,
+    pub display_name: Option<String>,
+    pub id: Option<u64>,
+    pub slug: Option<String>,
+}
+
+impl User {
+    pub fn to_github(&self) -> github::User {
+        github::User {
+            login: Some(self.name.clone()),
+            name: self.display_name.clone(),
+            email: self.email_address.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Project {
+    pub key: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Repository {
+    pub slug: String,
+    pub project: Project,
+}
+
+impl Repository {
+    pub fn new(key: &str, slug: &str) -> Repository {
+        Repository {
+            slug: slug.into(),
+            project: Project { key: key.into() },
+        }
+    }
+
+    pub fn to_github(&self, host: &str) -> github::Repo {
+        github::Repo {
+            html_url: format!("https://{}/projects/{}/repos/{}", host, self.project.key, self.slug),
+            full_name: format!("{}/{}", self.project.key, self.slug),
+            name: self.slug.clone(),
+            owner: github::User::new(&self.project.key),
+            default_branch: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Ref {
+    // "refs/heads/the-branch"
+    pub id: String,
+    // "the-branch"
+    pub display_id: String,
+    pub latest_commit: Option<String>,
+    pub repository: Repository,
+}
+
+impl Ref {
+    fn to_github(&self, host: &str) -> github::BranchRef {
+        let mut branch = github::BranchRef::new(&self.display_id);
+        branch.sha = self.latest_commit.clone().unwrap_or(String::new());
+        branch.repo = self.repository.to_github(host);
+        branch
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Participant {
+    pub user: User,
+    // "UNAPPROVED", "NEEDS_WORK", or "APPROVED"
+    pub status: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Link {
+    pub href: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Links {
+    #[serde(rename = "self")]
+    pub self_links: Option<Vec<Link>>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CommitRef {
+    pub id: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequestProperties {
+    pub merge_commit: Option<CommitRef>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequest {
+    pub id: u32,
+    // needed for any update to the pull request
+    pub version: u32,
+    pub title: String,
+    pub description: Option<String>,
+    // "OPEN", "MERGED", or "DECLINED"
+    pub state: String,
+    pub from_ref: Ref,
+    pub to_ref: Ref,
+    pub author: Participant,
+    pub reviewers: Vec<Participant>,
+    pub links: Option<Links>,
+    pub properties: Option<PullRequestProperties>,
+    // milliseconds since the epoch
+    pub updated_date: Option<i64>,
+}
+
+impl PullRequest {
+    pub fn to_github(&self, host: &str) -> github::PullRequest {
+        let mut pr = github::PullRequest::new();
+        pr.number = self.id;
+        pr.title = self.title.clone();
+        pr.body = self.description.clone();
+        pr.html_url = self.html_url(host);
+        pr.state = if self.state == "OPEN" { "open".into() } else { "closed".into() };
+        pr.merged = Some(self.state == "MERGED");
+        pr.merge_commit_sha = self.properties.as_ref().and_then(|p| p.merge_commit.as_ref()).map(|c| c.id.clone());
+        pr.user = self.author.user.to_github();
+        pr.requested_reviewers = Some(
+            self.reviewers
+                .iter()
+                .filter(|r| review_state(r).is_none())
+                .map(|r| r.user.to_github())
+                .collect(),
+        );
+        pr.reviews = Some(
+            self.reviewers
+                .iter()
+                .filter_map(|r| {
+                    review_state(r).map(|state| {
+                        github::Review {
+                            id: None,
+                            state: state.into(),
+                            body: None,
+                            html_url: pr.html_url.clone(),
+                            user: r.user.to_github(),
+                        }
+                    })
+                })
+                .collect(),
+        );
+        pr.updated_at = self.updated_date.map(github_time);
+        pr.head = self.from_ref.to_github(host);
+        pr.base = self.to_ref.to_github(host);
+        pr
+    }
+
+    fn html_url(&self, host: &str) -> String {
+        let link = self.links.as_ref().and_then(|l| l.self_links.as_ref()).and_then(|l| l.first());
+        match link {
+            Some(link) => link.href.clone(),
+            None => {
+                let repo = &self.to_ref.repository;
+                format!("https://{}/projects/{}/repos/{}/pull-requests/{}", host, repo.project.key, repo.slug, self.id)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    pub id: u64,
+    pub text: String,
+    pub author: User,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequestHook {
+    pub actor: User,
+    pub pull_request: PullRequest,
+    pub participant: Option<Participant>,
+    pub added_reviewers: Option<Vec<User>>,
+    pub removed_reviewers: Option<Vec<User>>,
+    pub comment: Option<Comment>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CommitCommentHook {
+    pub actor: User,
+    pub repository: Repository,
+    pub comment: Comment,
+    pub commit: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RefChange {
+    pub ref_id: String,
+    pub from_hash: String,
+    pub to_hash: String,
+    // "ADD", "UPDATE", or "DELETE"
+    #[serde(rename = "type")]
+    pub change_type: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PushHook {
+    pub actor: User,
+    pub repository: Repository,
+    pub changes: Vec<RefChange>,
+}
+
+// Turns a Bitbucket Server webhook into the github events it amounts to, as (event, body) pairs.
+// `event` is the X-Event-Key header. A push can be several events, one per branch it changed.
+pub fn hook_events(event: &str, body: &[u8], host: &str) -> Result<Vec<(String, github::HookBody)>> {
+    match event {
+        "repo:refs_changed" => Ok(push_events(&parse(body)?, host)),
+        "repo:comment:added" => Ok(vec![commit_comment_event(&parse(body)?, host)]),
+        e if e.starts_with("pr:") => Ok(pull_request_events(event, &parse(body)?, host)),
+        _ => Ok(vec![]),
+    }
+}
+
+fn parse<T: ::serde::de::DeserializeOwned>(body: &[u8]) -> Result<T> {
+    serde_json::from_slice(body).map_err(|e| Error::from(format!("Error parsing bitbucket event: {}", e)))
+}
+
+fn pull_request_events(event: &str, hook: &PullRequestHook, host: &str) -> Vec<(String, github::HookBody)> {
+    let pull_request = hook.pull_request.to_github(host);
+    let mut data = github::HookBody::new();
+    data.repository = hook.pull_request.to_ref.repository.to_github(host);
+    data.sender = hook.actor.to_github();
+
+    let mut event_with = |github_event: &str, action: &str| {
+        data.action = Some(action.to_string());
+        data.pull_request = Some(pull_request.clone());
+        vec![(github_event.to_string(), data.clone())]
+    };
+
+    match event {
+        "pr:opened" => event_with("pull_request", "opened"),
+        "pr:merged" | "pr:declined" => event_with("pull_request", "closed"),
+        "pr:from_ref_updated" => event_with("pull_request", "synchronize"),
+        "pr:reviewer:updated" => {
+            let mut events = vec![];
+            let changes = vec![
+                ("review_requested", &hook.added_reviewers),
+                ("review_request_removed", &hook.removed_reviewers),
+            ];
+            for (action, reviewers) in changes {
+                for reviewer in reviewers.iter().flat_map(|r| r.iter()) {
+                    let mut event = event_with("pull_request", action);
+                    event[0].1.requested_reviewer = Some(reviewer.to_github());
+                    events.extend(event);
+                }
+            }
+            events
+        }
+        "pr:reviewer:approved" | "pr:reviewer:needs_work" => {
+            let state = match hook.participant.as_ref().and_then(review_state) {
+                Some(state) => state.to_lowercase(),
+                None => return vec![],
+            };
+            let mut events = event_with("pull_request_review", "submitted");
+            events[0].1.review = Some(github::Review {
+                id: None,
+                state: state,
+                body: None,
+                html_url: pull_request.html_url.clone(),
+                user: hook.actor.to_github(),
+            });
+            events
+        }
+        "pr:comment:added" => {
+            let comment = match hook.comment {
+                Some(ref c) => c,
+                None => return vec![],
+            };
+            let mut events = event_with("issue_comment", "created");
+            events[0].1.comment = Some(github::Comment {
+                commit_id: None,
+                path: None,
+                body: Some(comment.text.clone()),
+                html_url: format!("{}/overview?commentId={}", pull_request.html_url, comment.id),
+                user: comment.author.to_github(),
+                pull_request_review_id: None,
+                diff_hunk: None,
+                line: None,
+            });
+            events
+        }
+        _ => vec![],
+    }
+}
+
+fn commit_comment_event(hook: &CommitCommentHook, host: &str) -> (String, github::HookBody) {
+    let mut data = github::HookBody::new();
+    data.repository = hook.repository.to_github(host);
+    data.sender = hook.actor.to_github();
+    data.action = Some("created".into());
+    data.comment = Some(github::Comment {
+        commit_id: Some(hook.commit.clone()),
+        path: None,
+        body: Some(hook.comment.text.clone()),
+        html_url: format!("{}/commits/{}?commentId={}", data.repository.html_url, hook.commit, hook.comment.id),
+        user: hook.comment.author.to_github(),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
+    });
+    ("commit_comment".to_string(), data)
+}
+
+// Push events come without their commits: those are looked up when handling them.
+fn push_events(hook: &PushHook, host: &str) -> Vec<(String, github::HookBody)> {
+    let repo = hook.repository.to_github(host);
+    hook.changes
+        .iter()
+        .filter(|c| c.ref_id.starts_with("refs/heads/"))
+        .map(|change| {
+            let mut data = github::HookBody::new();
+            data.repository = repo.clone();
+            data.sender = hook.actor.to_github();
+            data.ref_name = Some(change.ref_id.clone());
+            data.before = Some(change.from_hash.clone());
+            data.after = Some(change.to_hash.clone());
+            data.created = Some(change.change_type == "ADD");
+            data.deleted = Some(change.change_type == "DELETE");
+            data.compare = Some(format!(
+                "{}/compare/diff?sourceBranch={}&targetBranch={}",
+                repo.html_url,
+                change.to_hash,
+                change.from_hash
+            ));
+            data.commits = Some(vec![]);
+            ("push".to_string(), data)
+        })
+        .collect()
+}
+
+// the github API review state for a reviewer, if they reviewed at all
+fn review_state(reviewer: &Participant) -> Option<&'static str> {
+    match reviewer.status.as_ref().map(|s| s.as_str()) {
+        Some("APPROVED") => Some("APPROVED"),
+        Some("NEEDS_WORK") => Some("CHANGES_REQUESTED"),
+        _ => None,
+    }
+}
+
+// bitbucket has milliseconds since the epoch
+fn github_time(millis: i64) -> String {
+    let t = time::at_utc(time::Timespec::new(millis / 1000, 0));
+    time::strftime("%Y-%m-%dT%H:%M:%SZ", &t).unwrap_or(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPO: &'static str = r#"{"slug": "some-repo", "project": {"key": "PROJ"}}"#;
+
+    fn pull_request_hook(extra: &str) -> String {
+        format!(
+            r#"{{
+                "actor": {{"name": "joe", "displayName": "Joe", "emailAddress": "joe@foo.com"}},
+                "pullRequest": {{
+                    "id": 12,
+                    "version": 3,
+                    "title": "The PR",
+                    "description": "the description",
+                    "state": "OPEN",
+                    "fromRef": {{
+                        "id": "refs/heads/the-branch",
+                        "displayId": "the-branch",
+                        "latestCommit": "abcdef0",
+                        "repository": {repo}
+                    }},
+                    "toRef": {{
+                        "id": "refs/heads/master",
+                        "displayId": "master",
+                        "latestCommit": "1111111",
+                        "repository": {repo}
+                    }},
+                    "author": {{"user": {{"name": "joe"}}, "status": "UNAPPROVED"}},
+                    "reviewers": [
+                        {{"user": {{"name": "mary"}}, "status": "APPROVED"}},
+                        {{"user": {{"name": "bob"}}, "status": "UNAPPROVED"}}
+                    ],
+                    "updatedDate": 1506585600000
+                }}
+                {extra}
+            }}"#,
+            repo = REPO,
+            extra = extra
+        )
+    }
+
+    #[test]
+    fn test_pull_request_opened() {
+        let events = hook_events("pr:opened", pull_request_hook("").as_bytes(), "git.foo.com").unwrap();
+        assert_eq!(1, events.len());
+
+        let (ref event, ref data) = events[0];
+        assert_eq!("pull_request", event);
+        assert_eq!(Some("opened".to_string()), data.action);
+        assert_eq!("joe", data.sender.login());
+        assert_eq!("PROJ", data.repository.owner.login());
+        assert_eq!("PROJ/some-repo", data.repository.full_name);
+
+        let pr = data.pull_request.as_ref().unwrap();
+        assert_eq!(12, pr.number);
+        assert_eq!("open", pr.state);
+        assert_eq!("https://git.foo.com/projects/PROJ/repos/some-repo/pull-requests/12", pr.html_url);
+        assert_eq!("the-branch", pr.head.ref_name);
+        assert_eq!("abcdef0", pr.head.sha);
+        assert_eq!("master", pr.base.ref_name);
+        assert_eq!(Some("2017-09-28T08:00:00Z".to_string()), pr.updated_at);
+        let requested: Vec<&str> = pr.requested_reviewers.as_ref().unwrap().iter().map(|u| u.login()).collect();
+        assert_eq!(vec!["bob"], requested);
+        let reviews = pr.reviews.as_ref().unwrap();
+        assert_eq!(1, reviews.len());
+        assert_eq!("mary", reviews[0].user.login());
+        assert_eq!("APPROVED", reviews[0].state);
+    }
+
+    #[test]
+    fn test_reviewer_events() {
+        let approved = pull_request_hook(r#", "participant": {"user": {"name": "joe"}, "status": "APPROVED"}"#);
+        let events = hook_events("pr:reviewer:approved", approved.as_bytes(), "git.foo.com").unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!("pull_request_review", events[0].0);
+        assert_eq!(Some("submitted".to_string()), events[0].1.action);
+        assert_eq!("approved", events[0].1.review.as_ref().unwrap().state);
+
+        let needs_work = pull_request_hook(r#", "participant": {"user": {"name": "joe"}, "status": "NEEDS_WORK"}"#);
+        let events = hook_events("pr:reviewer:needs_work", needs_work.as_bytes(), "git.foo.com").unwrap();
+        assert_eq!("changes_requested", events[0].1.review.as_ref().unwrap().state);
+
+        let added = pull_request_hook(r#", "addedReviewers": [{"name": "bob"}], "removedReviewers": []"#);
+        let events = hook_events("pr:reviewer:updated", added.as_bytes(), "git.foo.com").unwrap();
+        assert_eq!(Some("review_requested".to_string()), events[0].1.action);
+        assert_eq!("bob", events[0].1.requested_reviewer.as_ref().unwrap().login());
+
+        let removed = pull_request_hook(r#", "addedReviewers": [], "removedReviewers": [{"name": "bob"}]"#);
+        let events = hook_events("pr:reviewer:updated", removed.as_bytes(), "git.foo.com").unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!(Some("review_request_removed".to_string()), events[0].1.action);
+
+        let none = pull_request_hook(r#", "addedReviewers": [], "removedReviewers": []"#);
+        let events = hook_events("pr:reviewer:updated", none.as_bytes(), "git.foo.com").unwrap();
+        assert_eq!(0, events.len());
+    }
+
+    #[test]
+    fn test_pull_request_comment() {
+        let hook = pull_request_hook(r#", "comment": {"id": 7, "text": "looks good", "author": {"name": "mary"}}"#);
+        let events = hook_events("pr:comment:added", hook.as_bytes(), "git.foo.com").unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!("issue_comment", events[0].0);
+
+        let comment = events[0].1.comment.as_ref().unwrap();
+        assert_eq!(Some("looks good".to_string()), comment.body);
+        assert_eq!("mary", comment.user.login());
+        assert_eq!(
+            "https://git.foo.com/projects/PROJ/repos/some-repo/pull-requests/12/overview?commentId=7",
+            comment.html_url
+        );
+    }
+
+    #[test]
+    fn test_push() {
+        let hook = format!(
+            r#"{{
+                "actor": {{"name": "joe"}},
+                "repository": {},
+                "changes": [
+                    {{"refId": "refs/heads/the-branch", "fromHash": "1111111", "toHash": "2222222", "type": "UPDATE"}},
+                    {{"refId": "refs/heads/new-branch", "fromHash": "0000000", "toHash": "3333333", "type": "ADD"}},
+                    {{"refId": "refs/tags/1.0", "fromHash": "0000000", "toHash": "4444444", "type": "ADD"}}
+                ]
+            }}"#,
+            REPO
+        );
+        let events = hook_events("repo:refs_changed", hook.as_bytes(), "git.foo.com").unwrap();
+        assert_eq!(2, events.len());
+
+        let (ref event, ref data) = events[0];
+        assert_eq!("push", event);
+        assert_eq!("refs/heads/the-branch", data.ref_name());
+        assert_eq!("1111111", data.before());
+        assert_eq!("2222222", data.after());
+        assert!(!data.created());
+        assert!(!data.deleted());
+
+        assert!(events[1].1.created());
+    }
+
+    #[test]
+    fn test_unhandled_event() {
+        assert_eq!(0, hook_events("repo:forked", b"{}", "git.foo.com").unwrap().len());
+        assert_eq!(0, hook_events("pr:modified", pull_request_hook("").as_bytes(), "git.foo.com").unwrap().len());
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use audit::{self, AuditLog};
+use conflicts::ConflictedPullRequests;
+use git_clone_manager::GitCloneManager;
+use github;
+use github::api::Session;
+use json_store::JsonStore;
+use messenger::{self, Messenger, MessengerFactory};
+use pr_merge::{self, BranchUpdate};
+use slack::SlackAttachmentBuilder;
+use worker;
+
+pub const LABEL: &'static str = "keep-updated";
+
+#[derive(Debug)]
+pub enum BranchUpdateRequest {
+    PullRequest(github::Repo, github::PullRequest),
+    // the branch moved: update the open pull requests that target it
+    Branch(github::Repo, String),
+}
+
+pub fn pr_req(repo: &github::Repo, pull_request: &github::PullRequest) -> BranchUpdateRequest {
+    BranchUpdateRequest::PullRequest(repo.clone(), pull_request.clone())
+}
+
+pub fn branch_req(repo: &github::Repo, branch: &str) -> BranchUpdateRequest {
+    BranchUpdateRequest::Branch(repo.clone(), branch.to_string())
+}
+
+// Merges the base branch into a pull request labeled "keep-updated" if it's behind. Its owner only hears about
+// it if that conflicts, and then only once until the conflicts are resolved.
+pub fn update_pull_request(
+    github: &Session,
+    clone_mgr: &GitCloneManager,
+    audit_log: &AuditLog,
+    messenger: &Messenger,
+    conflicted: &JsonStore<ConflictedPullRequests>,
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+) {
+    if !pull_request.is_open() || pull_request.is_wip() {
+        return;
+    }
+    // branches in forks can't be pushed to
+    if pull_request.head.repo.full_name != repo.full_name {
+        return;
+    }
+
+    let owner = repo.owner.login();
+    match github.get_pull_request_labels(owner, &repo.name, pull_request.number) {
+        Ok(ref labels) if labels.iter().any(|l| l.name == LABEL) => (),
+        Ok(_) => return,
+        Err(e) => {
+            error!("Error getting labels for PR #{}: {}", pull_request.number, e);
+            return;
+        }
+    };
+
+    match pr_merge::update_branch(github, clone_mgr, audit_log, owner, &repo.name, pull_request) {
+        Ok(BranchUpdate::UpToDate) => conflicted.write(|c| c.remove(repo, pull_request.number)),
+        Ok(BranchUpdate::Updated) => {
+            info!("Updated PR #{} from {}", pull_request.number, pull_request.base.ref_name);
+            conflicted.write(|c| c.remove(repo, pull_request.number));
+        }
+        Ok(BranchUpdate::Conflicts) => {
+            if conflicted.write(|c| c.add(repo, pull_request.number)) {
+                let msg = format!(
+                    "Pull Request can't be kept up to date: it has conflicts with {}",
+                    pull_request.base.ref_name
+                );
+                let attachments = vec![
+                    SlackAttachmentBuilder::new("Merge or rebase the branch to resolve them.")
+                        .title(format!("Pull Request #{}: \"{}\"", pull_request.number, pull_request.title))
+                        .title_link(pull_request.html_url.as_str())
+                        .color("warning")
+                        .build(),
+                ];
+                messenger.send_to_users(
+                    &msg,
+                    &attachments,
+                    &vec![pull_request.user.clone()],
+                    repo,
+                    messenger::Reason::Participant,
+                );
+            }
+        }
+        Err(e) => error!("Error updating PR #{}: {}", pull_request.number, e),
+    };
+}
+
+struct Runner {
+    github_session: Arc<Session>,
+    clone_mgr: Arc<GitCloneManager>,
+    audit_log: Arc<AuditLog>,
+    messengers: MessengerFactory,
+    conflicted: Arc<JsonStore<ConflictedPullRequests>>,
+}
+
+pub fn new_worker(
+    github_session: Arc<Session>,
+    clone_mgr: Arc<GitCloneManager>,
+    audit_log: Arc<AuditLog>,
+    messengers: MessengerFactory,
+    conflicted: Arc<JsonStore<ConflictedPullRequests>>,
+) -> worker::Worker<BranchUpdateRequest> {
+    worker::Worker::new(
+        "branch-updates",
+        Runner {
+            github_session: github_session,
+            clone_mgr: clone_mgr,
+            audit_log: audit_log,
+            messengers: messengers,
+            conflicted: conflicted,
+        },
+    )
+}
+
+impl Runner {
+    fn update(&self, messenger: &Messenger, repo: &github::Repo, pull_request: &github::PullRequest) {
+        let trigger = format!("keep-updated {}#{}", repo.full_name, pull_request.number);
+        audit::with_trigger(&trigger, || {
+            update_pull_request(
+                &*self.github_session,
+                &self.clone_mgr,
+                &self.audit_log,
+                messenger,
+                &self.conflicted,
+                repo,
+                pull_request,
+            )
+        });
+    }
+}
+
+impl worker::Runner<BranchUpdateRequest> for Runner {
+    fn handle(&self, req: BranchUpdateRequest) {
+        let messenger = self.messengers.new_messenger();
+
+        match req {
+            BranchUpdateRequest::PullRequest(repo, pull_request) => self.update(&*messenger, &repo, &pull_request),
+            BranchUpdateRequest::Branch(repo, branch) => {
+                let github = &*self.github_session;
+                let prs = match github.get_pull_requests(repo.owner.login(), &repo.name, Some("open"), None) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        error!("Error looking up PRs for {}: {}", branch, e);
+                        return;
+                    }
+                };
+
+                for pull_request in prs.iter().filter(|pr| pr.base.ref_name == branch) {
+                    self.update(&*messenger, &repo, pull_request);
+                }
+            }
+        };
+    }
+}
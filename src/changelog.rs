@@ -0,0 +1,139 @@
+use regex::Regex;
+
+use codeowners;
+use errors::*;
+use github;
+use github::api::Session;
+
+pub const CONTEXT: &'static str = "octobot/changelog";
+pub const NO_CHANGELOG_LABEL: &'static str = "no-changelog";
+pub const DEFAULT_FILE: &'static str = "CHANGELOG.md";
+
+// A repo's rule that PRs changing code, i.e. files matching any of the gitignore-style patterns, e.g. "src/" or
+// "*.rs", also change its changelog. PRs labeled "no-changelog" are let through.
+pub struct ChangelogRule {
+    patterns: Vec<Regex>,
+    pub file: String,
+}
+
+impl ChangelogRule {
+    pub fn parse(patterns: &Vec<String>, file: Option<String>) -> ChangelogRule {
+        ChangelogRule {
+            patterns: patterns.iter().filter_map(|p| codeowners::pattern_to_regex(p.trim())).collect(),
+            file: file.unwrap_or(DEFAULT_FILE.to_string()).trim_left_matches('/').to_string(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    // whether the changed paths touch code without touching the changelog
+    pub fn is_missing_entry(&self, paths: &Vec<String>) -> bool {
+        !paths.contains(&self.file) && paths.iter().any(|p| self.patterns.iter().any(|re| re.is_match(p)))
+    }
+}
+
+pub fn status(
+    pull_request: &github::PullRequest,
+    rule: &ChangelogRule,
+    missing: bool,
+    labeled: bool,
+) -> github::Status {
+    let (state, description) = if missing {
+        ("failure", format!("Code changes need a {} entry, or the {} label", rule.file, NO_CHANGELOG_LABEL))
+    } else if labeled {
+        ("success", format!("Labeled {}", NO_CHANGELOG_LABEL))
+    } else {
+        ("success", format!("{} is up to date", rule.file))
+    };
+
+    github::Status {
+        state: state.into(),
+        target_url: Some(pull_request.html_url.clone()),
+        context: Some(CONTEXT.into()),
+        description: Some(description),
+        creator: None,
+    }
+}
+
+pub fn comment(rule: &ChangelogRule) -> String {
+    format!(
+        "This pull request changes code but not `{}`. Add a line there describing the change, or, if it doesn't \
+         need one, add the `{}` label, and I'll check again.",
+        rule.file,
+        NO_CHANGELOG_LABEL
+    )
+}
+
+// Checks the PR's files and labels and sets the "octobot/changelog" status on its head. A missing entry is also
+// explained in a comment, unless the status at `previous_sha` (the head before a push) already failed.
+pub fn check(
+    github: &Session,
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+    rule: &ChangelogRule,
+    previous_sha: &str,
+) -> Result<()> {
+    let owner = repo.owner.login();
+    let labeled = github
+        .get_pull_request_labels(owner, &repo.name, pull_request.number)?
+        .iter()
+        .any(|l| l.name.to_lowercase() == NO_CHANGELOG_LABEL);
+    let missing = if labeled {
+        false
+    } else {
+        let files = github.get_pull_request_files(owner, &repo.name, pull_request.number)?;
+        rule.is_missing_entry(&files.into_iter().map(|f| f.filename).collect())
+    };
+    let status = status(pull_request, rule, missing, labeled);
+
+    if missing {
+        let previous = github.get_statuses(owner, &repo.name, previous_sha)?;
+        let previous = previous.into_iter().find(|s| s.context.as_ref().map_or(false, |c| c == CONTEXT));
+        if !previous.map_or(false, |p| p.state == "failure") {
+            github.comment_pull_request(owner, &repo.name, pull_request.number, &comment(rule))?;
+        }
+    }
+
+    github.create_status(owner, &repo.name, &pull_request.head.sha, &status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_is_missing_entry() {
+        let rule = ChangelogRule::parse(&paths(&["src/", "*.rs"]), None);
+        assert_eq!("CHANGELOG.md", rule.file);
+        assert!(rule.is_missing_entry(&paths(&["src/lib.rs", "README.md"])));
+        assert!(rule.is_missing_entry(&paths(&["build.rs"])));
+        assert!(!rule.is_missing_entry(&paths(&["src/lib.rs", "CHANGELOG.md"])));
+        assert!(!rule.is_missing_entry(&paths(&["README.md", "docs/setup.md"])));
+
+        let rule = ChangelogRule::parse(&paths(&["lib/**"]), Some("/docs/CHANGES.md".into()));
+        assert!(rule.is_missing_entry(&paths(&["lib/a.py", "CHANGELOG.md"])));
+        assert!(!rule.is_missing_entry(&paths(&["lib/a.py", "docs/CHANGES.md"])));
+        assert!(ChangelogRule::parse(&vec![], None).is_empty());
+    }
+
+    #[test]
+    fn test_status() {
+        let pr = github::PullRequest::new();
+        let rule = ChangelogRule::parse(&paths(&["src/"]), None);
+
+        let failed = status(&pr, &rule, true, false);
+        assert_eq!("failure", failed.state);
+        assert_eq!(
+            Some("Code changes need a CHANGELOG.md entry, or the no-changelog label".into()),
+            failed.description
+        );
+        assert_eq!(Some("Labeled no-changelog".into()), status(&pr, &rule, false, true).description);
+        assert_eq!("success", status(&pr, &rule, false, false).state);
+    }
+}
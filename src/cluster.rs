@@ -0,0 +1,379 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use libc;
+use ring::rand::{SecureRandom, SystemRandom};
+use url::Url;
+
+use config::ClusterConfig;
+use errors::*;
+
+const DEFAULT_REDIS_PORT: u16 = 6379;
+const KEY_PREFIX: &'static str = "octobot:";
+const IO_TIMEOUT_SECS: u64 = 5;
+
+// takes a lease if it's free, or renews it if this node holds it already. 1 if this node holds it now
+const LEASE_SCRIPT: &'static str = "local holder = redis.call('get', KEYS[1]) \
+     if holder == ARGV[1] then redis.call('expire', KEYS[1], ARGV[2]) return 1 end \
+     if not holder then redis.call('set', KEYS[1], ARGV[1], 'EX', ARGV[2]) return 1 end \
+     return 0";
+
+// lets go of a lock only if this node still holds it
+const UNLOCK_SCRIPT: &'static str = "if redis.call('get', KEYS[1]) == ARGV[1] then \
+     return redis.call('del', KEYS[1]) end return 0";
+
+// how long a node can hold a lock before it's taken from it, and how long to wait for one
+const LOCK_SECS: u64 = 10;
+const LOCK_WAIT_MILLIS: u64 = 50;
+const LOCK_ATTEMPTS: u32 = 200;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reply {
+    Status(String),
+    Error(String),
+    Int(i64),
+    Bulk(Option<String>),
+    Array(Vec<Reply>),
+}
+
+// Where a "redis://[:password@]host[:port][/db]" url says redis is
+#[derive(Clone, Debug, PartialEq)]
+pub struct RedisTarget {
+    pub addr: String,
+    pub password: Option<String>,
+    pub db: Option<u32>,
+}
+
+pub fn redis_target(url: &str) -> Result<RedisTarget> {
+    let parsed = Url::parse(url).map_err(|e| Error::from(format!("Invalid redis url '{}': {}", url, e)))?;
+    if parsed.scheme() != "redis" {
+        return Err(format!("Unsupported redis url '{}': use redis://", url).into());
+    }
+    let host = match parsed.host_str() {
+        Some(h) => h,
+        None => return Err(format!("Redis url '{}' has no host", url).into()),
+    };
+    let db = match parsed.path().trim_matches('/') {
+        "" => None,
+        db => Some(db.parse::<u32>().map_err(|_| Error::from(format!("Invalid redis db in '{}'", url)))?),
+    };
+    Ok(RedisTarget {
+        addr: format!("{}:{}", host, parsed.port().unwrap_or(DEFAULT_REDIS_PORT)),
+        password: parsed.password().map(|p| p.to_string()),
+        db: db,
+    })
+}
+
+pub fn encode_command(args: &[&str]) -> Vec<u8> {
+    let mut cmd = format!("*{}\r\n", args.len());
+    for arg in args {
+        cmd += &format!("${}\r\n{}\r\n", arg.len(), arg);
+    }
+    cmd.into_bytes()
+}
+
+pub fn read_reply<R: BufRead>(reader: &mut R) -> Result<Reply> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err("Redis closed the connection".into());
+    }
+    let line = line.trim_right_matches("\r\n");
+    if line.is_empty() {
+        return Err("Empty reply from redis".into());
+    }
+    let (kind, rest) = line.split_at(1);
+    let number = || rest.parse::<i64>().map_err(|_| Error::from(format!("Invalid reply from redis: {}", line)));
+    match kind {
+        "+" => Ok(Reply::Status(rest.to_string())),
+        "-" => Ok(Reply::Error(rest.to_string())),
+        ":" => Ok(Reply::Int(number()?)),
+        "$" => {
+            let len = number()?;
+            if len < 0 {
+                return Ok(Reply::Bulk(None));
+            }
+            let mut data = vec![0; len as usize + 2];
+            reader.read_exact(&mut data)?;
+            data.truncate(len as usize);
+            Ok(Reply::Bulk(Some(String::from_utf8_lossy(&data).into_owned())))
+        }
+        "*" => {
+            let len = number()?;
+            let mut items = vec![];
+            for _ in 0..len {
+                items.push(read_reply(reader)?);
+            }
+            Ok(Reply::Array(items))
+        }
+        _ => Err(format!("Invalid reply from redis: {}", line).into()),
+    }
+}
+
+// Lets several octobots behind a load balancer share what has to be shared: which webhook deliveries were
+// handled already (github redelivers to whichever node it gets), and which node runs the scheduled jobs that
+// would otherwise post the same thing once per node.
+pub struct Cluster {
+    target: RedisTarget,
+    node_id: String,
+    conn: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+impl Cluster {
+    pub fn new(config: &ClusterConfig) -> Result<Cluster> {
+        let node_id = match config.node_id {
+            Some(ref id) => id.clone(),
+            None => default_node_id(),
+        };
+        Ok(Cluster {
+            target: redis_target(&config.redis_url)?,
+            node_id: node_id,
+            conn: Mutex::new(None),
+        })
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    fn connect(&self) -> Result<BufReader<TcpStream>> {
+        let stream = TcpStream::connect(&self.target.addr).map_err(|e| {
+            Error::from(format!("Error connecting to redis at {}: {}", self.target.addr, e))
+        })?;
+        stream.set_read_timeout(Some(Duration::from_secs(IO_TIMEOUT_SECS)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(IO_TIMEOUT_SECS)))?;
+        let mut conn = BufReader::new(stream);
+        if let Some(ref password) = self.target.password {
+            Cluster::send(&mut conn, &["AUTH", password])?;
+        }
+        if let Some(db) = self.target.db {
+            Cluster::send(&mut conn, &["SELECT", &db.to_string()])?;
+        }
+        Ok(conn)
+    }
+
+    fn send(conn: &mut BufReader<TcpStream>, args: &[&str]) -> Result<Reply> {
+        conn.get_mut().write_all(&encode_command(args))?;
+        match read_reply(conn)? {
+            Reply::Error(e) => Err(format!("Redis error for {}: {}", args[0], e).into()),
+            reply => Ok(reply),
+        }
+    }
+
+    pub fn command(&self, args: &[&str]) -> Result<Reply> {
+        let mut conn = self.conn.lock().unwrap();
+        // once more on a new connection, in case the old one went away without saying so
+        for attempt in 0..2 {
+            if conn.is_none() {
+                *conn = Some(self.connect()?);
+            }
+            let result = match *conn {
+                Some(ref mut c) => Cluster::send(c, args),
+                None => continue,
+            };
+            let err = match result {
+                Ok(reply) => return Ok(reply),
+                Err(e) => e,
+            };
+            *conn = None;
+            match *err.kind() {
+                ErrorKind::Io(_) if attempt == 0 => error!("Error talking to redis, reconnecting: {}", err),
+                _ => return Err(err),
+            }
+        }
+        Err("Error talking to redis".into())
+    }
+
+    pub fn exists(&self, key: &str) -> Result<bool> {
+        match self.command(&["EXISTS", &(KEY_PREFIX.to_string() + key)])? {
+            Reply::Int(n) => Ok(n > 0),
+            reply => Err(format!("Unexpected reply to EXISTS: {:?}", reply).into()),
+        }
+    }
+
+    pub fn set(&self, key: &str, value: &str, ttl_secs: u64) -> Result<()> {
+        self.command(&["SET", &(KEY_PREFIX.to_string() + key), value, "EX", &ttl_secs.to_string()])?;
+        Ok(())
+    }
+
+    // sets the key only if it isn't set yet. false if it was
+    pub fn set_new(&self, key: &str, value: &str, ttl_secs: u64) -> Result<bool> {
+        let key = KEY_PREFIX.to_string() + key;
+        match self.command(&["SET", &key, value, "NX", "EX", &ttl_secs.to_string()])? {
+            Reply::Status(_) => Ok(true),
+            Reply::Bulk(None) => Ok(false),
+            reply => Err(format!("Unexpected reply to SET: {:?}", reply).into()),
+        }
+    }
+
+    pub fn delete(&self, key: &str) -> Result<()> {
+        self.command(&["DEL", &(KEY_PREFIX.to_string() + key)])?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        match self.command(&["GET", &(KEY_PREFIX.to_string() + key)])? {
+            Reply::Bulk(value) => Ok(value),
+            reply => Err(format!("Unexpected reply to GET: {:?}", reply).into()),
+        }
+    }
+
+    // sets a key that doesn't expire
+    pub fn store(&self, key: &str, value: &str) -> Result<()> {
+        self.command(&["SET", &(KEY_PREFIX.to_string() + key), value])?;
+        Ok(())
+    }
+
+    // Waits until no other node holds the lock `name`, and takes it. It's taken from this node if it isn't let go of
+    // within a few seconds, so it's only for quick changes to shared state.
+    pub fn lock(&self, name: &str) -> Result<()> {
+        let key = format!("{}lock:{}", KEY_PREFIX, name);
+        for _ in 0..LOCK_ATTEMPTS {
+            match self.command(&["SET", &key, &self.node_id, "NX", "EX", &LOCK_SECS.to_string()])? {
+                Reply::Status(_) => return Ok(()),
+                _ => thread::sleep(Duration::from_millis(LOCK_WAIT_MILLIS)),
+            }
+        }
+        Err(format!("Timed out waiting for lock {}", name).into())
+    }
+
+    pub fn unlock(&self, name: &str) -> Result<()> {
+        self.command(&["EVAL", UNLOCK_SCRIPT, "1", &format!("{}lock:{}", KEY_PREFIX, name), &self.node_id])?;
+        Ok(())
+    }
+
+    // Whether this node is the one that runs `job`, for `ttl_secs` from now. The node that has it keeps it as long
+    // as it asks again before then; if it goes away, the next node to ask takes over.
+    pub fn is_leader(&self, job: &str, ttl_secs: u64) -> bool {
+        let key = format!("{}leader:{}", KEY_PREFIX, job);
+        match self.command(&["EVAL", LEASE_SCRIPT, "1", &key, &self.node_id, &ttl_secs.to_string()]) {
+            Ok(Reply::Int(1)) => true,
+            Ok(_) => false,
+            Err(e) => {
+                // better that nobody runs it for a while than that everybody does
+                error!("Error checking who runs {}: {}", job, e);
+                false
+            }
+        }
+    }
+}
+
+// The host's name and a random suffix: process ids don't tell containers apart, since octobot is pid 1 in each.
+fn default_node_id() -> String {
+    let mut name = [0u8; 256];
+    let hostname = match unsafe { libc::gethostname(name.as_mut_ptr() as *mut libc::c_char, name.len()) } {
+        0 => {
+            let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+            String::from_utf8_lossy(&name[..len]).into_owned()
+        }
+        _ => "octobot".to_string(),
+    };
+
+    let mut suffix = [0u8; 4];
+    SystemRandom::new().fill(&mut suffix).expect("get random");
+    format!("{}-{}", hostname, suffix.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_redis_target() {
+        assert_eq!(
+            RedisTarget {
+                addr: "redis.local:6379".into(),
+                password: None,
+                db: None,
+            },
+            redis_target("redis://redis.local").unwrap()
+        );
+        assert_eq!(
+            RedisTarget {
+                addr: "redis.local:6380".into(),
+                password: Some("secret".into()),
+                db: Some(2),
+            },
+            redis_target("redis://:secret@redis.local:6380/2").unwrap()
+        );
+        assert!(redis_target("http://redis.local").is_err());
+        assert!(redis_target("redis://redis.local/db").is_err());
+    }
+
+    #[test]
+    fn test_encode_command() {
+        assert_eq!(b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n".to_vec(), encode_command(&["GET", "key"]));
+    }
+
+    #[test]
+    fn test_read_reply() {
+        let read = |s: &str| read_reply(&mut Cursor::new(s.as_bytes().to_vec())).unwrap();
+        assert_eq!(Reply::Status("OK".into()), read("+OK\r\n"));
+        assert_eq!(Reply::Error("ERR nope".into()), read("-ERR nope\r\n"));
+        assert_eq!(Reply::Int(3), read(":3\r\n"));
+        assert_eq!(Reply::Bulk(Some("a\r\nb".into())), read("$4\r\na\r\nb\r\n"));
+        assert_eq!(Reply::Bulk(None), read("$-1\r\n"));
+        assert_eq!(
+            Reply::Array(vec![Reply::Int(1), Reply::Bulk(Some("x".into()))]),
+            read("*2\r\n:1\r\n$1\r\nx\r\n")
+        );
+        assert!(read_reply(&mut Cursor::new(b"?\r\n".to_vec())).is_err());
+    }
+
+    #[test]
+    fn test_cluster() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut conn = BufReader::new(stream);
+            let mut commands = vec![];
+            for reply in vec![":1\r\n", "+OK\r\n", ":0\r\n", "+OK\r\n", "+OK\r\n", ":1\r\n"] {
+                match read_reply(&mut conn).unwrap() {
+                    Reply::Array(args) => commands.push(args),
+                    other => panic!("Expected a command, got {:?}", other),
+                }
+                conn.get_mut().write_all(reply.as_bytes()).unwrap();
+            }
+            commands
+        });
+
+        let cluster = Cluster::new(&ClusterConfig {
+            redis_url: format!("redis://{}", addr),
+            node_id: Some("node-1".into()),
+        }).unwrap();
+        assert!(cluster.exists("delivery:abc").unwrap());
+        cluster.set("delivery:abc", "1", 60).unwrap();
+        assert!(!cluster.is_leader("stale-prs", 180));
+        cluster.lock("digests").unwrap();
+        cluster.store("digests", "{}").unwrap();
+        cluster.unlock("digests").unwrap();
+
+        let commands = server.join().unwrap();
+        let bulk = |s: &str| Reply::Bulk(Some(s.into()));
+        assert_eq!(vec![bulk("EXISTS"), bulk("octobot:delivery:abc")], commands[0]);
+        assert_eq!(
+            vec![bulk("SET"), bulk("octobot:delivery:abc"), bulk("1"), bulk("EX"), bulk("60")],
+            commands[1]
+        );
+        assert_eq!(bulk("octobot:leader:stale-prs"), commands[2][3]);
+        assert_eq!(bulk("node-1"), commands[2][4]);
+        assert_eq!(
+            vec![bulk("SET"), bulk("octobot:lock:digests"), bulk("node-1"), bulk("NX"), bulk("EX"), bulk("10")],
+            commands[3]
+        );
+        assert_eq!(vec![bulk("SET"), bulk("octobot:digests"), bulk("{}")], commands[4]);
+        assert_eq!(bulk("octobot:lock:digests"), commands[5][3]);
+    }
+
+    #[test]
+    fn test_default_node_id() {
+        // containers that all run octobot as pid 1 on the same image still get different ids
+        assert!(default_node_id() != default_node_id());
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use regex::{self, Regex};
+
+use github;
+use github::api::Session;
+
+// where github looks for the file, in order
+const CODEOWNERS_PATHS: [&'static str; 3] = ["CODEOWNERS", ".github/CODEOWNERS", "docs/CODEOWNERS"];
+
+const CACHE_SECS: u64 = 10 * 60;
+
+struct Rule {
+    pattern: Regex,
+    owners: Vec<String>,
+}
+
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+impl CodeOwners {
+    pub fn empty() -> CodeOwners {
+        CodeOwners { rules: vec![] }
+    }
+
+    pub fn parse(contents: &str) -> CodeOwners {
+        let mut rules = vec![];
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let pattern = match parts.next().and_then(|p| pattern_to_regex(p)) {
+                Some(p) => p,
+                None => continue,
+            };
+            rules.push(Rule {
+                pattern: pattern,
+                owners: parts.take_while(|p| !p.starts_with("#")).map(|p| p.to_string()).collect(),
+            });
+        }
+
+        CodeOwners { rules: rules }
+    }
+
+    // as with github, the last matching rule wins
+    pub fn owners_of(&self, path: &str) -> Vec<String> {
+        match self.rules.iter().rev().find(|r| r.pattern.is_match(path)) {
+            Some(rule) => rule.owners.clone(),
+            None => vec![],
+        }
+    }
+
+    // github logins owning any of the given paths. teams and email addresses are skipped.
+    pub fn user_owners(&self, paths: &Vec<String>) -> Vec<String> {
+        let mut users = vec![];
+        for path in paths {
+            for owner in self.owners_of(path) {
+                if owner.starts_with("@") && !owner.contains("/") {
+                    let login = owner[1..].to_string();
+                    if !users.contains(&login) {
+                        users.push(login);
+                    }
+                }
+            }
+        }
+        users
+    }
+}
+
+// Translates a gitignore-style pattern, as used by CODEOWNERS, into a regex.
+pub fn pattern_to_regex(pattern: &str) -> Option<Regex> {
+    // patterns with a slash (other than a trailing one) are relative to the repo root;
+    // the rest can match at any depth.
+    let anchored = pattern.trim_end_matches('/').contains('/');
+    let pattern = pattern.trim_start_matches('/');
+    let dir_only = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+
+    let mut re = String::from("^");
+    if !anchored {
+        re += "(?:.*/)?";
+    }
+
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '*' && i + 1 < chars.len() && chars[i + 1] == '*' {
+            if i + 2 < chars.len() && chars[i + 2] == '/' {
+                re += "(?:.*/)?";
+                i += 3;
+            } else {
+                re += ".*";
+                i += 2;
+            }
+        } else if chars[i] == '*' {
+            re += "[^/]*";
+            i += 1;
+        } else if chars[i] == '?' {
+            re += "[^/]";
+            i += 1;
+        } else {
+            re += &regex::escape(&chars[i].to_string());
+            i += 1;
+        }
+    }
+
+    // a pattern matches the path itself as well as anything under it
+    if dir_only {
+        re += "/.*$";
+    } else {
+        re += "(?:/.*)?$";
+    }
+
+    match Regex::new(&re) {
+        Ok(r) => Some(r),
+        Err(e) => {
+            error!("Invalid path pattern '{}': {}", pattern, e);
+            None
+        }
+    }
+}
+
+pub struct CodeOwnersCache {
+    entries: Mutex<HashMap<String, (Instant, Arc<CodeOwners>)>>,
+}
+
+impl CodeOwnersCache {
+    pub fn new() -> CodeOwnersCache {
+        CodeOwnersCache { entries: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn get(&self, github: &Session, repo: &github::Repo, branch: &str) -> Arc<CodeOwners> {
+        let key = format!("{}:{}", repo.html_url, branch);
+        if let Some(&(ref fetched, ref owners)) = self.entries.lock().unwrap().get(&key) {
+            if fetched.elapsed() < Duration::from_secs(CACHE_SECS) {
+                return owners.clone();
+            }
+        }
+
+        let owners = Arc::new(fetch(github, repo, branch));
+        self.entries.lock().unwrap().insert(key, (Instant::now(), owners.clone()));
+        owners
+    }
+}
+
+fn fetch(github: &Session, repo: &github::Repo, branch: &str) -> CodeOwners {
+    for path in CODEOWNERS_PATHS.iter() {
+        match github.get_file_contents(repo.owner.login(), &repo.name, path, branch) {
+            Ok(contents) => return CodeOwners::parse(&contents),
+            Err(e) => debug!("No CODEOWNERS at {}: {}", path, e),
+        };
+    }
+
+    CodeOwners::empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owners(contents: &str, path: &str) -> Vec<String> {
+        CodeOwners::parse(contents).owners_of(path)
+    }
+
+    #[test]
+    fn test_match_anywhere() {
+        let contents = "*.js @js-owner\ndocs @docs-owner";
+        assert_eq!(vec!["@js-owner"], owners(contents, "app.js"));
+        assert_eq!(vec!["@js-owner"], owners(contents, "src/web/app.js"));
+        assert_eq!(vec!["@docs-owner"], owners(contents, "docs/index.md"));
+        assert_eq!(vec!["@docs-owner"], owners(contents, "src/docs/index.md"));
+        assert_eq!(Vec::<String>::new(), owners(contents, "src/main.rs"));
+    }
+
+    #[test]
+    fn test_match_anchored() {
+        let contents = "/build/ @build-owner\nsrc/*.rs @rust-owner";
+        assert_eq!(vec!["@build-owner"], owners(contents, "build/logs/out.log"));
+        assert_eq!(Vec::<String>::new(), owners(contents, "src/build/out.log"));
+        assert_eq!(vec!["@rust-owner"], owners(contents, "src/main.rs"));
+        assert_eq!(Vec::<String>::new(), owners(contents, "src/github/api.rs"));
+        assert_eq!(Vec::<String>::new(), owners(contents, "other/src/main.rs"));
+    }
+
+    #[test]
+    fn test_match_double_star() {
+        let contents = "src/**/api.rs @api-owner\n/docs/** @docs-owner";
+        assert_eq!(vec!["@api-owner"], owners(contents, "src/api.rs"));
+        assert_eq!(vec!["@api-owner"], owners(contents, "src/github/api.rs"));
+        assert_eq!(vec!["@docs-owner"], owners(contents, "docs/a/b/c.md"));
+    }
+
+    #[test]
+    fn test_last_match_wins() {
+        let contents = "# the default\n* @default-owner @other-owner\n\n*.rs @rust-owner # rust stuff\n";
+        assert_eq!(vec!["@default-owner", "@other-owner"], owners(contents, "README.md"));
+        assert_eq!(vec!["@rust-owner"], owners(contents, "src/main.rs"));
+    }
+
+    #[test]
+    fn test_user_owners() {
+        let codeowners = CodeOwners::parse("* @default-owner\n*.rs @rust-owner @some-org/team owner@company.com");
+        assert_eq!(
+            vec!["rust-owner", "default-owner"],
+            codeowners.user_owners(&vec!["src/main.rs".into(), "README.md".into(), "src/lib.rs".into()])
+        );
+    }
+}
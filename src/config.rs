@@ -1,9 +1,16 @@
 use std::fs;
 use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use ring::constant_time;
+use serde::Serialize;
+use serde_json;
 use toml;
 
+use config_db::ConfigDb;
 use errors::*;
+use github;
+use messages::{Catalogs, Messages};
 use repos;
 use users;
 
@@ -13,9 +20,23 @@ pub struct Config {
     pub github: GithubConfig,
     pub jira: Option<JiraConfig>,
     pub ldap: Option<LdapConfig>,
+    // more github hosts to serve besides [github], each with their own credentials
+    pub github_hosts: Option<Vec<GithubConfig>>,
+    pub gitlab: Option<GitlabConfig>,
+    pub bitbucket: Option<BitbucketConfig>,
+    pub api_tokens: Option<Vec<ApiTokenConfig>>,
+    pub pagerduty: Option<PagerDutyConfig>,
+    pub export: Option<ExportConfig>,
+    pub tracing: Option<TracingConfig>,
+    pub error_reporting: Option<ErrorReportingConfig>,
+    pub cluster: Option<ClusterConfig>,
+    // the message catalogs in main.locale_dir, and the built-in ones
+    pub catalogs: Catalogs,
 
     pub users: RwLock<users::UserConfig>,
     pub repos: RwLock<repos::RepoConfig>,
+    // where users and repos are kept when main.config_db is set, instead of the JSON files
+    pub db: Option<ConfigDb>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -25,6 +46,15 @@ pub struct ConfigModel {
     pub github: GithubConfig,
     pub jira: Option<JiraConfig>,
     pub ldap: Option<LdapConfig>,
+    pub github_hosts: Option<Vec<GithubConfig>>,
+    pub gitlab: Option<GitlabConfig>,
+    pub bitbucket: Option<BitbucketConfig>,
+    pub api_tokens: Option<Vec<ApiTokenConfig>>,
+    pub pagerduty: Option<PagerDutyConfig>,
+    pub export: Option<ExportConfig>,
+    pub tracing: Option<TracingConfig>,
+    pub error_reporting: Option<ErrorReportingConfig>,
+    pub cluster: Option<ClusterConfig>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -37,7 +67,62 @@ pub struct MainConfig {
     pub clone_root_dir: String,
     pub ssl_cert_file: Option<String>,
     pub ssl_key_file: Option<String>,
+    // check the cert and key files for changes this often, and use the new ones without restarting
+    pub ssl_reload_interval_secs: Option<u64>,
     pub num_http_threads: Option<usize>,
+    // directory to persist octobot's own state (queues, schedules, etc). state is kept in memory only if not set.
+    pub data_dir: Option<String>,
+    // when digests go out, e.g. "09:00". UTC for channels, and in each user's own timezone for users.
+    pub digest_time: Option<String>,
+    // SQLite database to keep users and repos in instead of users_config_file and repos_config_file.
+    // Those files are imported into it the first time it's used.
+    pub config_db: Option<String>,
+    // slack API token with the users:read.email scope. If set, slack users are looked up by their github
+    // email, and users config is only needed for users that can't be found that way.
+    pub slack_api_token: Option<String>,
+    // send messages through the web API (chat.postMessage) with slack_api_token, which then also needs the
+    // chat:write scope, instead of through slack_webhook_url
+    pub slack_web_api: Option<bool>,
+    // slack channel for problems with octobot itself, e.g. backports being dropped because too many are waiting
+    pub admin_channel: Option<String>,
+    // log and audit what would be done (comments, merges, pushes, slack messages) without doing any of it.
+    // repos can also be put in dry run mode one at a time in the repos config.
+    pub dry_run: Option<bool>,
+    // the signing secret of the slack app whose slash commands (e.g. "/octobot prs") go to /slack/commands, and
+    // whose button clicks go to /slack/actions. both are turned off if not set.
+    pub slack_signing_secret: Option<String>,
+    // most commits listed one by one in a push notification, the rest are linked to as a compare. defaults to 20
+    pub max_push_commits: Option<usize>,
+    // send messages as block kit blocks instead of attachments. off by default, since older slack installations
+    // only show attachments.
+    pub slack_blocks: Option<bool>,
+    // where github reaches octobot, e.g. "https://octobot.company.com". repos added through the admin API get
+    // their webhook set up to it, if it's set.
+    pub public_url: Option<String>,
+    // what to do with events of repos that aren't in the repos config. they're handled like any other repo's if not
+    // set, which sends direct messages but nothing to a channel.
+    pub unknown_repos: Option<UnknownRepos>,
+    // where their channel messages go with unknown_repos = "channel"
+    pub unknown_repos_channel: Option<String>,
+    // github logins whose comments, reviews, and pushes don't notify anyone, on top of octobot's own. defaults to
+    // dependabot[bot] and renovate[bot]
+    pub ignored_users: Option<Vec<String>>,
+    // the language of messages, e.g. "fr" or "pt-BR", unless a repo says otherwise. octobot comes with catalogs for
+    // "fr", "de", and "es". English if not set.
+    pub locale: Option<String>,
+    // directory of "<locale>.json" message catalogs, which add to or reword the built-in ones
+    pub locale_dir: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum UnknownRepos {
+    // drop their events, with a log line
+    Drop,
+    // handle their events, with their channel messages going to unknown_repos_channel
+    Channel,
+    // drop their events, and tell the admin channel about the first one of each repo
+    Notify,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -47,11 +132,70 @@ pub struct AdminConfig {
     pub pass_hash: String,
 }
 
+// What a web UI login or an API token may do: viewers can look at the config and queues, admins can also
+// change the config and replay or re-send things.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Admin,
+}
+
+// for scripts: sent as "Authorization: Bearer <token>"
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiTokenConfig {
+    pub name: String,
+    pub token: String,
+    pub role: Role,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct GithubConfig {
     pub webhook_secret: String,
     pub host: String,
     pub api_token: String,
+    // defaults to https://api.github.com for github.com, and https://<host>/api/v3 for enterprise hosts
+    pub api_base: Option<String>,
+    pub retry: Option<RetryConfig>,
+    // most pages of 100 to fetch from list APIs (e.g. open PRs, labels). defaults to 30
+    pub max_pages: Option<u32>,
+}
+
+// How API requests that fail with server or network errors, or that are rate limited, are retried.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RetryConfig {
+    // tries in all, including the first one. defaults to 3
+    pub attempts: Option<u32>,
+    // wait before the first retry, doubled for each one after it. defaults to 500
+    pub backoff_ms: Option<u64>,
+    // defaults to 10000
+    pub max_backoff_ms: Option<u64>,
+    // wait a random part of the backoff (between half and all of it). defaults to true
+    pub jitter: Option<bool>,
+    // longest to wait for a rate limit to reset before giving up. defaults to 60
+    pub max_rate_limit_wait_secs: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GitlabConfig {
+    // the "secret token" set on the gitlab webhook
+    pub webhook_secret: String,
+    pub host: String,
+    pub api_token: String,
+    // defaults to https://<host>/api/v4
+    pub api_base: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BitbucketConfig {
+    // the secret set on the bitbucket webhook
+    pub webhook_secret: String,
+    pub host: String,
+    pub username: String,
+    // an HTTP access token for `username`
+    pub api_token: String,
+    // defaults to https://<host>/rest/api/1.0
+    pub api_base: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -74,6 +218,45 @@ pub struct JiraConfig {
     pub pending_versions_field: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PagerDutyConfig {
+    // the integration key of an Events API v2 integration on the service to page
+    pub routing_key: String,
+    // defaults to https://events.pagerduty.com
+    pub api_base: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExportConfig {
+    // where every handled event and every action goes, as JSON: "nats://host[:port][/subject]", or an http(s)
+    // URL to POST to
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TracingConfig {
+    // the OTLP/HTTP collector that spans are sent to, e.g. http://localhost:4318 (they're POSTed to /v1/traces)
+    pub otlp_endpoint: String,
+    // defaults to "octobot"
+    pub service_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ErrorReportingConfig {
+    // the DSN of the sentry project that panics and unexpected errors are reported to
+    pub sentry_dsn: String,
+    // e.g. "production"
+    pub environment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClusterConfig {
+    // the redis that the nodes share: "redis://[:password@]host[:port][/db]"
+    pub redis_url: String,
+    // this node's name, unique in the cluster. defaults to the host's name and a random suffix
+    pub node_id: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct LdapConfig {
     // LDAP URL (e.g. ldaps://ldap.company.com)
@@ -89,6 +272,8 @@ pub struct LdapConfig {
     // Additional LDAP search filter for user types and group membership
     // e.g. (&(objectCategory=Person)(memberOf=cn=octobot-admins,ou=users,dc=company,dc=com))
     pub search_filter: Option<String>,
+    // what LDAP users may do. defaults to admin
+    pub role: Option<Role>,
 }
 
 impl Config {
@@ -101,14 +286,83 @@ impl Config {
     }
 
     fn new_with_model(config: ConfigModel, users: users::UserConfig, repos: repos::RepoConfig) -> Config {
+        let catalogs = match config.main.locale_dir {
+            Some(ref dir) => Catalogs::load(Path::new(dir)),
+            None => Catalogs::new(),
+        };
         Config {
             main: config.main,
             admin: config.admin,
             github: config.github,
             jira: config.jira,
             ldap: config.ldap,
+            github_hosts: config.github_hosts,
+            gitlab: config.gitlab,
+            bitbucket: config.bitbucket,
+            api_tokens: config.api_tokens,
+            pagerduty: config.pagerduty,
+            export: config.export,
+            tracing: config.tracing,
+            error_reporting: config.error_reporting,
+            cluster: config.cluster,
+            catalogs: catalogs,
             users: RwLock::new(users),
             repos: RwLock::new(repos),
+            db: None,
+        }
+    }
+
+    pub fn api_token_role(&self, token: &str) -> Option<Role> {
+        let tokens = match self.api_tokens {
+            Some(ref t) => t,
+            None => return None,
+        };
+        tokens
+            .iter()
+            .find(|t| constant_time::verify_slices_are_equal(t.token.as_bytes(), token.as_bytes()).is_ok())
+            .map(|t| t.role)
+    }
+
+    // whether to only record changes to the repo instead of making them. None for ones that aren't about a repo.
+    pub fn dry_run(&self, repo: Option<&github::Repo>) -> bool {
+        self.main.dry_run.unwrap_or(false) || repo.map_or(false, |r| self.repos().dry_run(r))
+    }
+
+    // the repo's wording of the built-in messages, in its locale
+    pub fn messages(&self, repo: &github::Repo, branch: Option<&str>) -> Messages {
+        let locale = self.repos().locale(repo).or(self.main.locale.clone());
+        self.messages_in(repo, branch, locale)
+    }
+
+    // the repo's wording of the built-in messages, in the user's locale if they have one: for their direct messages
+    pub fn user_messages(&self, login: &str, repo: &github::Repo, branch: Option<&str>) -> Messages {
+        match self.users().locale(login, repo) {
+            Some(locale) => self.messages_in(repo, branch, Some(locale)),
+            None => self.messages(repo, branch),
+        }
+    }
+
+    fn messages_in(&self, repo: &github::Repo, branch: Option<&str>, locale: Option<String>) -> Messages {
+        let messages = self.repos().messages(repo, branch);
+        match locale {
+            Some(ref locale) if !locale.is_empty() => messages.with_catalog(self.catalogs.catalog(locale)),
+            _ => messages,
+        }
+    }
+
+    // the channel for channel messages of repos that aren't in the repos config, if they go anywhere
+    pub fn unknown_repos_channel(&self) -> Option<String> {
+        match (self.main.unknown_repos, &self.main.unknown_repos_channel) {
+            (Some(UnknownRepos::Channel), &Some(ref channel)) if !channel.is_empty() => Some(channel.clone()),
+            _ => None,
+        }
+    }
+
+    // github logins (besides octobot's own) whose comments, reviews, and pushes are ignored
+    pub fn ignored_users(&self) -> Vec<String> {
+        match self.main.ignored_users {
+            Some(ref users) => users.clone(),
+            None => vec!["dependabot[bot]".into(), "renovate[bot]".into()],
         }
     }
 
@@ -119,6 +373,15 @@ impl Config {
             github: self.github.clone(),
             jira: self.jira.clone(),
             ldap: self.ldap.clone(),
+            github_hosts: self.github_hosts.clone(),
+            gitlab: self.gitlab.clone(),
+            bitbucket: self.bitbucket.clone(),
+            api_tokens: self.api_tokens.clone(),
+            pagerduty: self.pagerduty.clone(),
+            export: self.export.clone(),
+            tracing: self.tracing.clone(),
+            error_reporting: self.error_reporting.clone(),
+            cluster: self.cluster.clone(),
         };
 
         let serialized = toml::to_string(&model).map_err(
@@ -139,18 +402,66 @@ impl Config {
     }
 
     pub fn reload_users_repos(&self) -> Result<()> {
+        let (users, repos) = match self.db {
+            Some(ref db) => {
+                (users::UserConfig::from_host_map(db.load_users()?), repos::RepoConfig::from_host_map(db.load_repos()?))
+            }
+            None => self.load_users_repos_files()?,
+        };
+
+        let mut users = users;
+        users.keep_resolved(&*self.users());
+        *self.users.write().unwrap() = users;
+        *self.repos.write().unwrap() = repos;
+        Ok(())
+    }
+
+    fn load_users_repos_files(&self) -> Result<(users::UserConfig, repos::RepoConfig)> {
         let users = users::load_config(&self.main.users_config_file).map_err(|e| {
             Error::from(format!("Error reading user config file: {}", e))
         })?;
         let repos = repos::load_config(&self.main.repos_config_file).map_err(|e| {
             Error::from(format!("Error reading repo config file: {}", e))
         })?;
+        Ok((users, repos))
+    }
 
-        *self.users.write().unwrap() = users;
-        *self.repos.write().unwrap() = repos;
+    // imports the users and repos files into a new, empty config db
+    fn migrate_to_db(&self) -> Result<()> {
+        let db = match self.db {
+            Some(ref db) if db.is_empty()? => db,
+            _ => return Ok(()),
+        };
+
+        let (users, repos) = match self.load_users_repos_files() {
+            Ok(c) => c,
+            Err(e) => {
+                info!("Nothing to import into config db: {}", e);
+                return Ok(());
+            }
+        };
+        db.save_users(users.host_map())?;
+        db.save_repos(repos.host_map())?;
+        info!("Imported {} and {} into config db", self.main.users_config_file, self.main.repos_config_file);
         Ok(())
     }
 
+    pub fn save_users(&self, users: users::UserHostMap) -> Result<()> {
+        match self.db {
+            Some(ref db) => db.save_users(&users)?,
+            None => save_json(&self.main.users_config_file, &users)?,
+        };
+        self.reload_users_repos()
+    }
+
+    pub fn save_repos(&self, repos: repos::RepoHostMap) -> Result<()> {
+        match self.db {
+            Some(ref db) => db.save_repos(&repos)?,
+            None => save_json(&self.main.repos_config_file, &repos)?,
+        };
+        self.reload_users_repos()
+    }
+
     pub fn users(&self) -> RwLockReadGuard<users::UserConfig> {
         self.users.read().unwrap()
     }
@@ -166,6 +477,34 @@ impl Config {
     pub fn repos_write(&self) -> RwLockWriteGuard<repos::RepoConfig> {
         self.repos.write().unwrap()
     }
+
+    pub fn data_file(&self, name: &str) -> Option<PathBuf> {
+        match self.main.data_dir {
+            Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir).join(name)),
+            _ => None,
+        }
+    }
+
+    // state that belongs to one github host. hosts from github_hosts keep theirs in a directory of their own.
+    pub fn host_data_file(&self, host: &str, name: &str) -> Option<PathBuf> {
+        if host == self.github.host {
+            self.data_file(name)
+        } else {
+            self.data_file(&format!("{}/{}", host, name))
+        }
+    }
+
+    pub fn github_configs(&self) -> Vec<&GithubConfig> {
+        let mut configs = vec![&self.github];
+        if let Some(ref hosts) = self.github_hosts {
+            configs.extend(hosts.iter());
+        }
+        configs
+    }
+
+    pub fn github_config(&self, host: &str) -> Option<&GithubConfig> {
+        self.github_configs().into_iter().find(|c| c.host == host)
+    }
 }
 
 impl ConfigModel {
@@ -180,16 +519,45 @@ impl ConfigModel {
                 clone_root_dir: String::new(),
                 ssl_cert_file: None,
                 ssl_key_file: None,
+                ssl_reload_interval_secs: None,
                 num_http_threads: None,
+                data_dir: None,
+                digest_time: None,
+                config_db: None,
+                slack_api_token: None,
+                slack_web_api: None,
+                admin_channel: None,
+                dry_run: None,
+                slack_signing_secret: None,
+                max_push_commits: None,
+                slack_blocks: None,
+                public_url: None,
+                unknown_repos: None,
+                unknown_repos_channel: None,
+                ignored_users: None,
+                locale: None,
+                locale_dir: None,
             },
             admin: None,
             github: GithubConfig {
                 webhook_secret: String::new(),
                 host: String::new(),
                 api_token: String::new(),
+                api_base: None,
+                retry: None,
+                max_pages: None,
             },
             jira: None,
             ldap: None,
+            github_hosts: None,
+            gitlab: None,
+            bitbucket: None,
+            api_tokens: None,
+            pagerduty: None,
+            export: None,
+            tracing: None,
+            error_reporting: None,
+            cluster: None,
         }
     }
 }
@@ -260,16 +628,30 @@ fn parse_string(config_contents: &str) -> Result<ConfigModel> {
 fn parse_string_and_load(config_contents: &str) -> Result<Config> {
     let config = parse_string(config_contents)?;
 
-    let the_config = Config::new_with_model(config, users::UserConfig::new(), repos::RepoConfig::new());
+    let mut the_config = Config::new_with_model(config, users::UserConfig::new(), repos::RepoConfig::new());
+    if let Some(db_file) = the_config.main.config_db.clone() {
+        the_config.db = Some(ConfigDb::open(&db_file)?);
+        the_config.migrate_to_db()?;
+    }
     the_config.reload_users_repos()?;
 
     Ok(the_config)
 }
 
+fn save_json<T: Serialize>(file: &str, value: &T) -> Result<()> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| format!("Error serializing JSON: {}", e))?;
+
+    let tmp_file = file.to_string() + ".tmp";
+    fs::File::create(&tmp_file)?.write_all(json.as_bytes())?;
+    fs::rename(&tmp_file, file)?;
+    Ok(())
+}
+
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use messages;
 
     #[test]
     fn test_parse() {
@@ -292,4 +674,116 @@ api_token = "some-tokens"
         assert_eq!("repos.json", config.main.repos_config_file);
 
     }
+
+    #[test]
+    fn test_parse_github_hosts() {
+        let config_str = r#"
+[main]
+slack_webhook_url = "https://hooks.slack.com/foo"
+users_config_file = "users.json"
+repos_config_file = "repos.json"
+clone_root_dir = "./repos"
+data_dir = "/data"
+
+[github]
+webhook_secret = "abcd"
+host = "git.company.com"
+api_token = "some-tokens"
+
+[[github_hosts]]
+webhook_secret = "efgh"
+host = "github.com"
+api_token = "other-tokens"
+
+[[github_hosts]]
+webhook_secret = "ijkl"
+host = "git.other-company.com"
+api_token = "more-tokens"
+api_base = "https://git-api.other-company.com"
+"#;
+        let model = parse_string(config_str).unwrap();
+        let config = Config::new_with_model(model, users::UserConfig::new(), repos::RepoConfig::new());
+
+        let hosts: Vec<&str> = config.github_configs().iter().map(|c| c.host.as_str()).collect();
+        assert_eq!(vec!["git.company.com", "github.com", "git.other-company.com"], hosts);
+        assert_eq!("efgh", config.github_config("github.com").unwrap().webhook_secret);
+        assert_eq!(
+            Some("https://git-api.other-company.com".to_string()),
+            config.github_config("git.other-company.com").unwrap().api_base
+        );
+        assert!(config.github_config("git.unknown.com").is_none());
+
+        assert_eq!(Some(PathBuf::from("/data/queue.json")), config.host_data_file("git.company.com", "queue.json"));
+        assert_eq!(
+            Some(PathBuf::from("/data/github.com/queue.json")),
+            config.host_data_file("github.com", "queue.json")
+        );
+    }
+
+    #[test]
+    fn test_unknown_repos_channel() {
+        let config_str = r#"
+[main]
+slack_webhook_url = "https://hooks.slack.com/foo"
+users_config_file = "users.json"
+repos_config_file = "repos.json"
+clone_root_dir = "./repos"
+unknown_repos = "channel"
+unknown_repos_channel = "unconfigured"
+
+[github]
+webhook_secret = "abcd"
+host = "git.company.com"
+api_token = "some-tokens"
+"#;
+        let model = parse_string(config_str).unwrap();
+        let mut config = Config::new_with_model(model, users::UserConfig::new(), repos::RepoConfig::new());
+        assert_eq!(Some(UnknownRepos::Channel), config.main.unknown_repos);
+        assert_eq!(Some("unconfigured".into()), config.unknown_repos_channel());
+
+        config.main.unknown_repos = Some(UnknownRepos::Notify);
+        assert_eq!(None, config.unknown_repos_channel());
+    }
+
+    #[test]
+    fn test_ignored_users() {
+        let mut config = Config::new(users::UserConfig::new(), repos::RepoConfig::new());
+        assert_eq!(vec!["dependabot[bot]", "renovate[bot]"], config.ignored_users());
+
+        config.main.ignored_users = Some(vec!["ci-bot".into()]);
+        assert_eq!(vec!["ci-bot"], config.ignored_users());
+    }
+
+    #[test]
+    fn test_messages() {
+        let mut repos = repos::RepoConfig::new();
+        repos.insert_info("git.foo.com", repos::RepoInfo::new("some-user/some-repo", "reviews"));
+        repos.insert_info("git.foo.com", repos::RepoInfo::new("some-user/german-repo", "reviews").with_locale("de"));
+        let mut config = Config::new(users::UserConfig::new(), repos);
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        let merged = |config: &Config, name: &str| config.messages(&repo(name), None).format(messages::PR_MERGED, &[]);
+        assert_eq!("Pull Request merged", merged(&config, "some-repo"));
+        assert_eq!("Pull Request gemergt", merged(&config, "german-repo"));
+
+        config.main.locale = Some("fr".into());
+        assert_eq!("Pull Request fusionnée", merged(&config, "some-repo"));
+        assert_eq!("Pull Request gemergt", merged(&config, "german-repo"));
+    }
+
+    #[test]
+    fn test_user_messages() {
+        let mut repos = repos::RepoConfig::new();
+        repos.insert_info("git.foo.com", repos::RepoInfo::new("some-user/german-repo", "reviews").with_locale("de"));
+        let mut users = users::UserConfig::new();
+        users.insert("git.foo.com", "joe", "joe");
+        users.insert_info("git.foo.com", users::UserInfo::new("marie", "marie").with_locale("fr"));
+        let config = Config::new(users, repos);
+
+        let repo = github::Repo::parse("http://git.foo.com/some-user/german-repo").unwrap();
+        let merged = |login: &str| config.user_messages(login, &repo, None).format(messages::PR_MERGED, &[]);
+        assert_eq!("Pull Request fusionnée", merged("marie"));
+        assert_eq!("Pull Request gemergt", merged("joe"));
+        assert_eq!("Pull Request gemergt", merged("someone-else"));
+    }
 }
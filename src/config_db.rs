@@ -0,0 +1,153 @@
+use std::sync::Mutex;
+
+use rusqlite::{Connection, NO_PARAMS};
+use serde_json;
+
+use errors::*;
+use repos::{RepoHostMap, RepoInfo};
+use users::{UserHostMap, UserInfo};
+
+// Users and repos kept in a SQLite database instead of JSON files. Each entry is stored as a row of
+// JSON so that new settings don't need a schema change.
+pub struct ConfigDb {
+    conn: Mutex<Connection>,
+}
+
+impl ConfigDb {
+    pub fn open(path: &str) -> Result<ConfigDb> {
+        let conn = Connection::open(path).map_err(|e| format!("Error opening config db {}: {}", path, e))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS users (
+                 id INTEGER PRIMARY KEY,
+                 host TEXT NOT NULL,
+                 github TEXT NOT NULL,
+                 info TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS repos (
+                 id INTEGER PRIMARY KEY,
+                 host TEXT NOT NULL,
+                 repo TEXT NOT NULL,
+                 info TEXT NOT NULL
+             );",
+        ).map_err(|e| format!("Error creating config db tables: {}", e))?;
+
+        Ok(ConfigDb { conn: Mutex::new(conn) })
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM users) + (SELECT COUNT(*) FROM repos)",
+            NO_PARAMS,
+            |row| row.get(0),
+        ).map_err(|e| format!("Error counting config db entries: {}", e))?;
+        Ok(count == 0)
+    }
+
+    pub fn load_users(&self) -> Result<UserHostMap> {
+        let mut users = UserHostMap::new();
+        for (host, info) in self.load("users")? {
+            let info: UserInfo = serde_json::from_str(&info).map_err(|e| format!("Invalid user in config db: {}", e))?;
+            users.entry(host).or_insert(vec![]).push(info);
+        }
+        Ok(users)
+    }
+
+    pub fn load_repos(&self) -> Result<RepoHostMap> {
+        let mut repos = RepoHostMap::new();
+        for (host, info) in self.load("repos")? {
+            let info: RepoInfo = serde_json::from_str(&info).map_err(|e| format!("Invalid repo in config db: {}", e))?;
+            repos.entry(host).or_insert(vec![]).push(info);
+        }
+        Ok(repos)
+    }
+
+    pub fn save_users(&self, users: &UserHostMap) -> Result<()> {
+        let mut rows = vec![];
+        for (host, infos) in users {
+            for info in infos {
+                let json = serde_json::to_string(info).map_err(|e| format!("Error serializing user: {}", e))?;
+                rows.push((host.clone(), info.github.clone(), json));
+            }
+        }
+        self.replace("users", "github", rows)
+    }
+
+    pub fn save_repos(&self, repos: &RepoHostMap) -> Result<()> {
+        let mut rows = vec![];
+        for (host, infos) in repos {
+            for info in infos {
+                let json = serde_json::to_string(info).map_err(|e| format!("Error serializing repo: {}", e))?;
+                rows.push((host.clone(), info.repo.clone(), json));
+            }
+        }
+        self.replace("repos", "repo", rows)
+    }
+
+    // (host, info) in the order they were saved, which matters for repo lookups
+    fn load(&self, table: &str) -> Result<Vec<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT host, info FROM {} ORDER BY id", table))?;
+        let rows = stmt.query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let mut result = vec![];
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
+    fn replace(&self, table: &str, name_column: &str, rows: Vec<(String, String, String)>) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(&format!("DELETE FROM {}", table), NO_PARAMS)?;
+        for (host, name, info) in rows {
+            tx.execute(
+                &format!("INSERT INTO {} (host, {}, info) VALUES (?1, ?2, ?3)", table, name_column),
+                &[&host, &name, &info],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let db = ConfigDb::open(":memory:").unwrap();
+        assert!(db.is_empty().unwrap());
+
+        let mut users = UserHostMap::new();
+        users.insert("git.foo.com".into(), vec![UserInfo::new("joe", "joe.slack").with_digest(true)]);
+        let mut repos = RepoHostMap::new();
+        repos.insert(
+            "git.foo.com".into(),
+            vec![
+                RepoInfo::new("some-user/some-repo", "the-channel").with_branches(vec!["release/*".into()]),
+                RepoInfo::new("some-user/some-repo", "the-other-channel"),
+            ],
+        );
+        db.save_users(&users).unwrap();
+        db.save_repos(&repos).unwrap();
+        assert!(!db.is_empty().unwrap());
+
+        let users = db.load_users().unwrap();
+        assert_eq!("joe.slack", users["git.foo.com"][0].slack);
+        assert_eq!(Some(true), users["git.foo.com"][0].digest);
+
+        let repos = db.load_repos().unwrap();
+        assert_eq!(
+            vec!["the-channel", "the-other-channel"],
+            repos["git.foo.com"].iter().map(|r| r.channel.as_str()).collect::<Vec<_>>()
+        );
+
+        // saving replaces everything
+        db.save_repos(&RepoHostMap::new()).unwrap();
+        assert_eq!(0, db.load_repos().unwrap().len());
+        assert_eq!(1, db.load_users().unwrap().len());
+    }
+}
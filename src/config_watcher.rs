@@ -0,0 +1,58 @@
+use std::fs;
+use std::sync::Arc;
+use std::sync::atomic::{ATOMIC_BOOL_INIT, AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use libc;
+
+use config::Config;
+
+const POLL_INTERVAL_SECS: u64 = 5;
+
+static RELOAD_REQUESTED: AtomicBool = ATOMIC_BOOL_INIT;
+
+extern "C" fn handle_sighup(_: libc::c_int) {
+    RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+// Reloads the users and repos files when they change on disk or when the process gets a SIGHUP,
+// so that repos and user mappings can be added without a restart. The main config file still
+// needs a restart.
+pub fn start(config: Arc<Config>) {
+    unsafe {
+        libc::signal(libc::SIGHUP, handle_sighup as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+
+    let result = thread::Builder::new().name("config-watcher".into()).spawn(move || {
+        let files = vec![config.main.users_config_file.clone(), config.main.repos_config_file.clone()];
+        let mut last_modified: Vec<Option<SystemTime>> = files.iter().map(|f| modified(f)).collect();
+
+        loop {
+            thread::sleep(Duration::from_secs(POLL_INTERVAL_SECS));
+
+            let now_modified: Vec<Option<SystemTime>> = files.iter().map(|f| modified(f)).collect();
+            let changed = now_modified != last_modified;
+            let signaled = RELOAD_REQUESTED.swap(false, Ordering::SeqCst);
+            if !changed && !signaled {
+                continue;
+            }
+
+            last_modified = now_modified;
+
+            info!("Reloading users and repos config ({})", if signaled { "SIGHUP" } else { "file changed" });
+            // a bad file leaves the old config in place until the next change
+            if let Err(e) = config.reload_users_repos() {
+                error!("Error reloading config: {}", e);
+            }
+        }
+    });
+
+    if let Err(e) = result {
+        error!("Error starting config watcher thread: {}", e);
+    }
+}
+
+fn modified(file: &str) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|m| m.modified()).ok()
+}
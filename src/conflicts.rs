@@ -0,0 +1,202 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use github;
+use github::api::Session;
+use json_store::JsonStore;
+use messenger::{self, Messenger, MessengerFactory};
+use slack::SlackAttachmentBuilder;
+use worker;
+
+// github works out whether a PR can still be merged in the background after its base branch moves, and reports
+// `mergeable: null` until it has.
+const MERGEABLE_ATTEMPTS: u32 = 4;
+const MERGEABLE_RETRY_SECS: u64 = 5;
+
+// The PRs whose owners were told they have conflicts, so that they're only told once. A PR is forgotten once it
+// can be merged again (or is closed), so that a later conflict gets its own message.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ConflictedPullRequests {
+    conflicted: Vec<ConflictedPullRequest>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct ConflictedPullRequest {
+    repo_url: String,
+    number: u32,
+}
+
+impl ConflictedPullRequests {
+    // true if the PR wasn't already known to be conflicted, i.e. if its owner should be told
+    pub fn add(&mut self, repo: &github::Repo, number: u32) -> bool {
+        let pr = ConflictedPullRequest {
+            repo_url: repo.html_url.clone(),
+            number: number,
+        };
+        if self.conflicted.contains(&pr) {
+            return false;
+        }
+        self.conflicted.push(pr);
+        true
+    }
+
+    pub fn remove(&mut self, repo: &github::Repo, number: u32) {
+        self.conflicted.retain(|c| c.repo_url != repo.html_url || c.number != number);
+    }
+
+    // forgets the repo's PRs that aren't open any more
+    pub fn retain_open(&mut self, repo: &github::Repo, open: &Vec<github::PullRequest>) {
+        self.conflicted.retain(|c| c.repo_url != repo.html_url || open.iter().any(|pr| pr.number == c.number));
+    }
+
+    pub fn len(&self) -> usize {
+        self.conflicted.len()
+    }
+}
+
+// Looks at the open PRs targeting `branch` after it was pushed to, and tells the owners of any that now have
+// merge conflicts. `retry_delay` is how long to wait for github while it still hasn't decided.
+pub fn check_branch(
+    github: &Session,
+    messenger: &Messenger,
+    conflicted: &JsonStore<ConflictedPullRequests>,
+    repo: &github::Repo,
+    branch: &str,
+    retry_delay: Duration,
+) {
+    let prs = match github.get_pull_requests(repo.owner.login(), &repo.name, Some("open"), None) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Error looking up PRs for {}: {}", branch, e);
+            return;
+        }
+    };
+    conflicted.write(|c| c.retain_open(repo, &prs));
+
+    for pull_request in prs.iter().filter(|pr| pr.base.ref_name == branch) {
+        match mergeable(github, repo, pull_request.number, retry_delay) {
+            Some(true) => conflicted.write(|c| c.remove(repo, pull_request.number)),
+            Some(false) => {
+                if conflicted.write(|c| c.add(repo, pull_request.number)) {
+                    notify(messenger, repo, pull_request);
+                }
+            }
+            None => info!("Couldn't tell whether PR #{} can still be merged", pull_request.number),
+        };
+    }
+}
+
+fn mergeable(github: &Session, repo: &github::Repo, number: u32, retry_delay: Duration) -> Option<bool> {
+    for attempt in 0..MERGEABLE_ATTEMPTS {
+        if attempt > 0 {
+            thread::sleep(retry_delay);
+        }
+        match github.get_pull_request(repo.owner.login(), &repo.name, number) {
+            Ok(pr) => {
+                if pr.mergeable.is_some() {
+                    return pr.mergeable;
+                }
+            }
+            Err(e) => {
+                error!("Error getting PR #{}: {}", number, e);
+                return None;
+            }
+        };
+    }
+    None
+}
+
+fn notify(messenger: &Messenger, repo: &github::Repo, pull_request: &github::PullRequest) {
+    let msg = format!("Pull Request now has conflicts with {}", pull_request.base.ref_name);
+    let attachments = vec![
+        SlackAttachmentBuilder::new("Merge or rebase the branch to resolve them.")
+            .title(format!("Pull Request #{}: \"{}\"", pull_request.number, pull_request.title))
+            .title_link(pull_request.html_url.as_str())
+            .color("warning")
+            .build(),
+    ];
+    messenger.send_to_users(
+        &msg,
+        &attachments,
+        &vec![pull_request.user.clone()],
+        repo,
+        messenger::Reason::Participant,
+    );
+}
+
+#[derive(Debug)]
+pub struct ConflictRequest {
+    pub repo: github::Repo,
+    pub branch: String,
+}
+
+pub fn req(repo: &github::Repo, branch: &str) -> ConflictRequest {
+    ConflictRequest {
+        repo: repo.clone(),
+        branch: branch.to_string(),
+    }
+}
+
+struct Runner {
+    github_session: Arc<Session>,
+    messengers: MessengerFactory,
+    conflicted: Arc<JsonStore<ConflictedPullRequests>>,
+}
+
+pub fn new_worker(
+    github_session: Arc<Session>,
+    messengers: MessengerFactory,
+    conflicted: Arc<JsonStore<ConflictedPullRequests>>,
+) -> worker::Worker<ConflictRequest> {
+    worker::Worker::new(
+        "conflicts",
+        Runner {
+            github_session: github_session,
+            messengers: messengers,
+            conflicted: conflicted,
+        },
+    )
+}
+
+impl worker::Runner<ConflictRequest> for Runner {
+    fn handle(&self, req: ConflictRequest) {
+        let messenger = self.messengers.new_messenger();
+        check_branch(
+            &*self.github_session,
+            &*messenger,
+            &self.conflicted,
+            &req.repo,
+            &req.branch,
+            Duration::from_secs(MERGEABLE_RETRY_SECS),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conflicted_pull_requests() {
+        let repo = github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap();
+        let other = github::Repo::parse("http://git.foo.com/some-user/other-repo").unwrap();
+
+        let mut conflicted = ConflictedPullRequests::default();
+        assert!(conflicted.add(&repo, 1));
+        assert!(!conflicted.add(&repo, 1));
+        assert!(conflicted.add(&repo, 2));
+        assert!(conflicted.add(&other, 1));
+        assert_eq!(3, conflicted.len());
+
+        conflicted.remove(&repo, 1);
+        assert!(conflicted.add(&repo, 1));
+
+        let mut open = github::PullRequest::new();
+        open.number = 2;
+        conflicted.retain_open(&repo, &vec![open]);
+        assert_eq!(2, conflicted.len());
+        assert!(!conflicted.add(&repo, 2));
+        assert!(!conflicted.add(&other, 1));
+    }
+}
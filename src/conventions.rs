@@ -0,0 +1,183 @@
+use regex::Regex;
+
+use errors::*;
+use github;
+use github::api::Session;
+use github::Commit;
+
+pub const CONTEXT: &'static str = "octobot/conventions";
+
+// github cuts status descriptions off at 140 characters
+const MAX_DESCRIPTION: usize = 140;
+
+// A repo's rules for PR titles and commit messages, e.g. "^[A-Z]+-[0-9]+" for a leading JIRA key, or
+// "^(feat|fix|chore)(\(.+\))?: " for conventional commits. Commit messages are checked by their first line.
+#[derive(Debug, Clone)]
+pub struct Conventions {
+    pub title_pattern: Option<String>,
+    pub commit_pattern: Option<String>,
+}
+
+impl Conventions {
+    pub fn is_empty(&self) -> bool {
+        self.title_pattern.is_none() && self.commit_pattern.is_none()
+    }
+
+    pub fn violations(&self, pull_request: &github::PullRequest, commits: &Vec<Commit>) -> Result<Vec<String>> {
+        let mut violations = vec![];
+        if let Some(ref pattern) = self.title_pattern {
+            let re = compile(pattern)?;
+            if !re.is_match(&pull_request.title) {
+                violations.push(format!("The title \"{}\" doesn't match `{}`", pull_request.title, pattern));
+            }
+        }
+        if let Some(ref pattern) = self.commit_pattern {
+            let re = compile(pattern)?;
+            for commit in commits {
+                let title = Commit::title(commit);
+                // merges from the base branch are written by git, not by whoever opened the PR
+                if title.starts_with("Merge branch ") || title.starts_with("Merge remote-tracking branch ") {
+                    continue;
+                }
+                if !re.is_match(&title) {
+                    violations.push(format!(
+                        "Commit {} \"{}\" doesn't match `{}`",
+                        Commit::short_hash(commit),
+                        title,
+                        pattern
+                    ));
+                }
+            }
+        }
+        Ok(violations)
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| Error::from(format!("Invalid convention pattern `{}`: {}", pattern, e)))
+}
+
+pub fn status(pull_request: &github::PullRequest, violations: &Vec<String>) -> github::Status {
+    let (state, description) = match violations.len() {
+        0 => ("success", "Title and commit messages follow the conventions".to_string()),
+        1 => ("failure", violations[0].clone()),
+        n => ("failure", format!("{} problems, first: {}", n, violations[0])),
+    };
+
+    github::Status {
+        state: state.into(),
+        target_url: Some(pull_request.html_url.clone()),
+        context: Some(CONTEXT.into()),
+        description: Some(truncate(&description, MAX_DESCRIPTION)),
+        creator: None,
+    }
+}
+
+pub fn comment(violations: &Vec<String>) -> String {
+    let mut comment = "Thanks for the pull request! It doesn't follow this repo's conventions yet:\n".to_string();
+    for violation in violations {
+        comment += &format!("\n* {}", violation);
+    }
+    comment += "\n\nEdit the title or reword the commits, and I'll check again.";
+    comment
+}
+
+fn truncate(s: &str, max_chars: usize) -> String {
+    if s.chars().count() <= max_chars {
+        return s.to_string();
+    }
+    let mut truncated: String = s.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+// Checks the PR and sets the "octobot/conventions" status on its head. Violations are also explained in a
+// comment, unless the status at `previous_sha` (the head before a push) already reported the same ones.
+pub fn check(
+    github: &Session,
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+    conventions: &Conventions,
+    previous_sha: &str,
+) -> Result<()> {
+    let owner = repo.owner.login();
+    let commits = github.get_pull_request_commits(owner, &repo.name, pull_request.number)?;
+    let violations = conventions.violations(pull_request, &commits)?;
+    let status = status(pull_request, &violations);
+
+    if !violations.is_empty() {
+        let previous = github.get_statuses(owner, &repo.name, previous_sha)?;
+        let previous = previous.into_iter().find(|s| s.context.as_ref().map_or(false, |c| c == CONTEXT));
+        let already_told = previous.map_or(false, |p| p.state == "failure" && p.description == status.description);
+        if !already_told {
+            github.comment_pull_request(owner, &repo.name, pull_request.number, &comment(&violations))?;
+        }
+    }
+
+    github.create_status(owner, &repo.name, &pull_request.head.sha, &status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(sha: &str, message: &str) -> Commit {
+        let mut commit = Commit::new();
+        commit.sha = sha.into();
+        commit.commit.message = message.into();
+        commit
+    }
+
+    fn pull_request(title: &str) -> github::PullRequest {
+        let mut pr = github::PullRequest::new();
+        pr.title = title.into();
+        pr
+    }
+
+    #[test]
+    fn test_violations() {
+        let conventions = Conventions {
+            title_pattern: Some(r"\b[A-Z]+-[0-9]+\b".into()),
+            commit_pattern: Some(r"^(feat|fix|chore): ".into()),
+        };
+        let commits = vec![
+            commit("aaaaaaa111", "feat: add things\n\nmore about it"),
+            commit("bbbbbbb222", "Merge branch 'master' into the-branch"),
+            commit("ccccccc333", "oops"),
+        ];
+
+        assert_eq!(
+            vec!["Commit ccccccc \"oops\" doesn't match `^(feat|fix|chore): `".to_string()],
+            conventions.violations(&pull_request("SER-12: Add things"), &commits).unwrap()
+        );
+        assert_eq!(
+            vec![
+                "The title \"Add things\" doesn't match `\\b[A-Z]+-[0-9]+\\b`".to_string(),
+                "Commit ccccccc \"oops\" doesn't match `^(feat|fix|chore): `".to_string(),
+            ],
+            conventions.violations(&pull_request("Add things"), &commits).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let conventions = Conventions {
+            title_pattern: Some("(unclosed".into()),
+            commit_pattern: None,
+        };
+        assert!(conventions.violations(&pull_request("Add things"), &vec![]).is_err());
+    }
+
+    #[test]
+    fn test_status() {
+        let pr = pull_request("Add things");
+        assert_eq!("success", status(&pr, &vec![]).state);
+
+        let failed = status(&pr, &vec!["first".into(), "second".into()]);
+        assert_eq!("failure", failed.state);
+        assert_eq!(Some("2 problems, first: first".into()), failed.description);
+
+        let long = status(&pr, &vec!["x".repeat(200)]);
+        assert_eq!(MAX_DESCRIPTION, long.description.unwrap().chars().count());
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde_json;
+
+use cluster::Cluster;
+use errors::*;
+use json_store::JsonStore;
+use util;
+
+// how many deliveries to keep around for replaying
+const MAX_DELIVERIES: usize = 1000;
+// how long the cluster remembers handled deliveries. github only redelivers recent ones
+const CLUSTER_HANDLED_SECS: u64 = 7 * 24 * 60 * 60;
+// how long the cluster takes a queued delivery to be on its way, in case the node handling it goes away
+const CLUSTER_IN_FLIGHT_SECS: u64 = 60 * 60;
+// how many failed deliveries to keep around for replaying
+const MAX_FAILED: usize = 200;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Delivery {
+    // the X-Github-Delivery header
+    pub id: String,
+    // the X-Github-Event header
+    pub event: String,
+    pub received_at: i64,
+    // the payload, as it came
+    pub body: String,
+}
+
+// The latest webhook deliveries, so that they can be handled again, e.g. after slack was down.
+// Each one is a file in `dir`, or they are only kept in memory if there's no `dir`.
+pub struct DeliveryStore {
+    dir: Option<PathBuf>,
+    max: usize,
+    // ids of the deliveries we have, oldest first, and the deliveries themselves if there's no dir
+    recent: Mutex<VecDeque<(String, Option<Delivery>)>>,
+}
+
+impl DeliveryStore {
+    pub fn new(dir: Option<PathBuf>) -> DeliveryStore {
+        DeliveryStore::with_max(dir, MAX_DELIVERIES)
+    }
+
+    pub fn with_max(dir: Option<PathBuf>, max: usize) -> DeliveryStore {
+        let recent = match dir {
+            Some(ref d) => {
+                match list_ids(d) {
+                    Ok(ids) => ids.into_iter().map(|id| (id, None)).collect(),
+                    Err(e) => {
+                        error!("Error listing deliveries in {:?}: {}", d, e);
+                        VecDeque::new()
+                    }
+                }
+            }
+            None => VecDeque::new(),
+        };
+
+        DeliveryStore {
+            dir: dir,
+            max: max,
+            recent: Mutex::new(recent),
+        }
+    }
+
+    pub fn save(&self, delivery: Delivery) -> Result<()> {
+        check_id(&delivery.id)?;
+
+        let mut recent = self.recent.lock().unwrap();
+        if let Some(ref dir) = self.dir {
+            fs::create_dir_all(dir)?;
+            let json = serde_json::to_string(&delivery).map_err(|e| format!("Error serializing delivery: {}", e))?;
+            fs::File::create(dir.join(file_name(&delivery.id)))?.write_all(json.as_bytes())?;
+        }
+
+        recent.retain(|&(ref id, _)| *id != delivery.id);
+        let id = delivery.id.clone();
+        recent.push_back((id, if self.dir.is_some() { None } else { Some(delivery) }));
+        while recent.len() > self.max {
+            if let Some((old, _)) = recent.pop_front() {
+                if let Some(ref dir) = self.dir {
+                    if let Err(e) = fs::remove_file(dir.join(file_name(&old))) {
+                        error!("Error removing old delivery {}: {}", old, e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load(&self, id: &str) -> Result<Option<Delivery>> {
+        check_id(id)?;
+
+        let recent = self.recent.lock().unwrap();
+        let dir = match self.dir {
+            Some(ref d) => d,
+            None => return Ok(recent.iter().find(|&&(ref i, _)| i == id).and_then(|&(_, ref d)| d.clone())),
+        };
+
+        let file = dir.join(file_name(id));
+        if !file.exists() {
+            return Ok(None);
+        }
+        let mut contents = String::new();
+        fs::File::open(file)?.read_to_string(&mut contents)?;
+        let delivery = serde_json::from_str(&contents).map_err(|e| format!("Error parsing delivery {}: {}", id, e))?;
+        Ok(Some(delivery))
+    }
+}
+
+// Ids of the deliveries that were handled, or are queued to be, so that github's redeliveries of them can be
+// acked without handling them twice. Deliveries that failed are left out: redelivering them has to work. In a
+// cluster, the ids are shared, since a redelivery can go to another node.
+pub struct HandledDeliveries {
+    ids: JsonStore<Vec<String>>,
+    in_flight: Mutex<HashSet<String>>,
+    cluster: Option<Arc<Cluster>>,
+}
+
+impl HandledDeliveries {
+    pub fn new(file: Option<PathBuf>) -> HandledDeliveries {
+        HandledDeliveries {
+            ids: JsonStore::new(file),
+            in_flight: Mutex::new(HashSet::new()),
+            cluster: None,
+        }
+    }
+
+    pub fn with_cluster(self, cluster: Option<Arc<Cluster>>) -> HandledDeliveries {
+        let mut h = self;
+        h.cluster = cluster;
+        h
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        if self.in_flight.lock().unwrap().contains(id) || self.ids.read(|ids| ids.iter().any(|i| i == id)) {
+            return true;
+        }
+        match self.cluster {
+            Some(ref cluster) => {
+                cluster.exists(&format!("delivery:{}", id)).unwrap_or_else(|e| {
+                    error!("Error checking the cluster for delivery {}: {}", id, e);
+                    false
+                })
+            }
+            None => false,
+        }
+    }
+
+    // Takes on a delivery that's about to be queued. false if it was handled already, or is queued already, here or
+    // on another node: the same delivery can come in again before the first one gets its turn.
+    pub fn start(&self, id: &str) -> bool {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight.contains(id) || self.ids.read(|ids| ids.iter().any(|i| i == id)) {
+            return false;
+        }
+        if let Some(ref cluster) = self.cluster {
+            match cluster.set_new(&format!("delivery:{}", id), cluster.node_id(), CLUSTER_IN_FLIGHT_SECS) {
+                Ok(true) => (),
+                Ok(false) => return false,
+                // better to handle it twice than not at all
+                Err(e) => error!("Error telling the cluster about delivery {}: {}", id, e),
+            }
+        }
+        in_flight.insert(id.to_string());
+        true
+    }
+
+    pub fn add(&self, id: &str) {
+        self.ids.write(|ids| {
+            util::check_unique_event(id.to_string(), ids, MAX_DELIVERIES, MAX_DELIVERIES / 10);
+        });
+        self.in_flight.lock().unwrap().remove(id);
+        if let Some(ref cluster) = self.cluster {
+            if let Err(e) = cluster.set(&format!("delivery:{}", id), cluster.node_id(), CLUSTER_HANDLED_SECS) {
+                error!("Error telling the cluster about delivery {}: {}", id, e);
+            }
+        }
+    }
+
+    // Gives up on a delivery that was started, e.g. because it failed or couldn't be queued, so that it gets
+    // handled when it comes in again.
+    pub fn abandon(&self, id: &str) {
+        self.in_flight.lock().unwrap().remove(id);
+        if let Some(ref cluster) = self.cluster {
+            if let Err(e) = cluster.delete(&format!("delivery:{}", id)) {
+                error!("Error telling the cluster about delivery {}: {}", id, e);
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FailedDelivery {
+    pub id: String,
+    pub event: String,
+    pub failed_at: i64,
+    pub error: String,
+}
+
+// Deliveries whose handling failed after github was told they were queued, so github won't redeliver them by
+// itself. They stay here until they're replayed.
+pub struct FailedDeliveries {
+    failed: JsonStore<Vec<FailedDelivery>>,
+}
+
+impl FailedDeliveries {
+    pub fn new(file: Option<PathBuf>) -> FailedDeliveries {
+        FailedDeliveries { failed: JsonStore::new(file) }
+    }
+
+    pub fn add(&self, failed: FailedDelivery) {
+        self.failed.write(|f| {
+            f.retain(|d| d.id != failed.id);
+            f.push(failed);
+            if f.len() > MAX_FAILED {
+                let excess = f.len() - MAX_FAILED;
+                f.drain(0..excess);
+            }
+        });
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.failed.write(|f| f.retain(|d| d.id != id));
+    }
+
+    // oldest first
+    pub fn list(&self) -> Vec<FailedDelivery> {
+        self.failed.read(|f| f.clone())
+    }
+}
+
+// ids become file names: github's are guids, and nothing else is let through
+fn check_id(id: &str) -> Result<()> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid delivery id: {}", id).into());
+    }
+    Ok(())
+}
+
+fn file_name(id: &str) -> String {
+    format!("{}.json", id)
+}
+
+// the ids of the deliveries in dir, oldest first
+fn list_ids(dir: &PathBuf) -> Result<Vec<String>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".json") {
+            files.push((entry.metadata()?.modified()?, name.trim_right_matches(".json").to_string()));
+        }
+    }
+    files.sort();
+    Ok(files.into_iter().map(|(_, id)| id).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempdir;
+    use self::tempdir::TempDir;
+
+    fn delivery(id: &str) -> Delivery {
+        Delivery {
+            id: id.into(),
+            event: "pull_request".into(),
+            received_at: 100,
+            body: "{}".into(),
+        }
+    }
+
+    #[test]
+    fn test_in_memory() {
+        let store = DeliveryStore::with_max(None, 2);
+        store.save(delivery("a")).unwrap();
+        store.save(delivery("b")).unwrap();
+        store.save(delivery("c")).unwrap();
+
+        assert_eq!(None, store.load("a").unwrap());
+        assert_eq!(Some(delivery("c")), store.load("c").unwrap());
+    }
+
+    #[test]
+    fn test_persisted() {
+        let dir = TempDir::new("deliveries.rs").expect("create temp dir for deliveries.rs test");
+        let deliveries = dir.path().join("deliveries");
+
+        {
+            let store = DeliveryStore::with_max(Some(deliveries.clone()), 2);
+            store.save(delivery("a")).unwrap();
+            store.save(delivery("b")).unwrap();
+        }
+
+        let store = DeliveryStore::with_max(Some(deliveries.clone()), 2);
+        assert_eq!(Some(delivery("a")), store.load("a").unwrap());
+        store.save(delivery("c")).unwrap();
+        assert_eq!(None, store.load("a").unwrap());
+        assert_eq!(Some(delivery("b")), store.load("b").unwrap());
+        assert_eq!(Some(delivery("c")), store.load("c").unwrap());
+        assert_eq!(2, fs::read_dir(&deliveries).unwrap().count());
+    }
+
+    #[test]
+    fn test_invalid_id() {
+        let store = DeliveryStore::new(None);
+        assert!(store.save(delivery("../../etc/passwd")).is_err());
+        assert!(store.load("../secrets").is_err());
+    }
+
+    #[test]
+    fn test_handled_deliveries() {
+        let dir = TempDir::new("deliveries.rs").expect("create temp dir for deliveries.rs test");
+        let file = dir.path().join("handled_deliveries.json");
+
+        {
+            let handled = HandledDeliveries::new(Some(file.clone()));
+            // the first delivery failed, so it wasn't added: the redelivery gets handled
+            assert!(!handled.contains("a"));
+            handled.add("a");
+            assert!(handled.contains("a"));
+        }
+
+        // and redeliveries after a restart are still caught
+        let handled = HandledDeliveries::new(Some(file.clone()));
+        assert!(handled.contains("a"));
+        assert!(!handled.contains("b"));
+    }
+
+    #[test]
+    fn test_in_flight_deliveries() {
+        let handled = HandledDeliveries::new(None);
+        assert!(handled.start("a"));
+        // a redelivery while the first one is still queued
+        assert!(handled.contains("a"));
+        assert!(!handled.start("a"));
+
+        handled.abandon("a");
+        assert!(!handled.contains("a"));
+        assert!(handled.start("a"));
+        handled.add("a");
+        assert!(!handled.start("a"));
+    }
+
+    #[test]
+    fn test_failed_deliveries() {
+        let failed = FailedDeliveries::new(None);
+        let delivery = |id: &str, error: &str| {
+            FailedDelivery {
+                id: id.into(),
+                event: "pull_request".into(),
+                failed_at: 100,
+                error: error.into(),
+            }
+        };
+        failed.add(delivery("a", "slack is down"));
+        failed.add(delivery("b", "github is down"));
+        failed.add(delivery("a", "slack is still down"));
+        assert_eq!(vec![delivery("b", "github is down"), delivery("a", "slack is still down")], failed.list());
+
+        failed.remove("b");
+        assert_eq!(vec![delivery("a", "slack is still down")], failed.list());
+    }
+}
@@ -0,0 +1,150 @@
+use config::Config;
+use quiet_hours;
+use slack::{self, SlackAttachment, SlackRequest};
+
+// users who want a digest get these kinds of events batched up instead of sent right away
+const LOW_PRIORITY_KINDS: [&'static str; 2] = ["comment", "push"];
+
+// slack won't show more than this many attachments on a message
+const MAX_ATTACHMENTS: usize = 100;
+
+const DEFAULT_DIGEST_TIME: i64 = 9 * 60 * 60;
+
+pub fn is_low_priority(kind: &str) -> bool {
+    LOW_PRIORITY_KINDS.contains(&kind)
+}
+
+// when the next digest goes out for a recipient at the given offset from UTC
+pub fn next_delivery(config: &Config, utc_offset: i64, now: i64) -> i64 {
+    let time_of_day = match config.main.digest_time {
+        Some(ref t) => {
+            quiet_hours::parse_time_of_day(t).unwrap_or_else(|| {
+                error!("Invalid digest_time: '{}'", t);
+                DEFAULT_DIGEST_TIME
+            })
+        }
+        None => DEFAULT_DIGEST_TIME,
+    };
+    quiet_hours::next_time_of_day(time_of_day, utc_offset, now)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Entry {
+    msg: String,
+    attachments: Vec<SlackAttachment>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Digest {
+    channel: String,
+    deliver_at: i64,
+    entries: Vec<Entry>,
+}
+
+// Messages waiting to go out as one summary per channel or user
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Digests {
+    pending: Vec<Digest>,
+}
+
+impl Digests {
+    pub fn add(&mut self, req: SlackRequest) {
+        let entry = Entry {
+            msg: req.msg,
+            attachments: req.attachments,
+        };
+
+        let channel = req.channel;
+        if let Some(digest) = self.pending.iter_mut().find(|d| d.channel == channel) {
+            digest.entries.push(entry);
+            return;
+        }
+
+        self.pending.push(Digest {
+            channel: channel,
+            deliver_at: req.deliver_at.unwrap_or(0),
+            entries: vec![entry],
+        });
+    }
+
+    // removes the digests that are due and returns their summary messages
+    pub fn take_due(&mut self, now: i64) -> Vec<SlackRequest> {
+        let (due, waiting): (Vec<Digest>, Vec<Digest>) = self.pending.drain(..).partition(|d| d.deliver_at <= now);
+        self.pending = waiting;
+        due.into_iter().map(summarize).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+fn summarize(digest: Digest) -> SlackRequest {
+    let msg = format!("Daily digest: {} update(s)", digest.entries.len());
+
+    let mut attachments = vec![];
+    for entry in &digest.entries {
+        attachments.push(SlackAttachment::new(&entry.msg));
+        attachments.extend(entry.attachments.iter().cloned());
+    }
+    if attachments.len() > MAX_ATTACHMENTS {
+        let more = attachments.len() - (MAX_ATTACHMENTS - 1);
+        attachments.truncate(MAX_ATTACHMENTS - 1);
+        attachments.push(SlackAttachment::new(&format!("... and {} more", more)));
+    }
+
+    slack::req(&digest.channel, &msg, attachments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slack::SlackAttachmentBuilder;
+
+    #[test]
+    fn test_take_due() {
+        let attach = SlackAttachmentBuilder::new("the comment").build();
+
+        let mut digests = Digests::default();
+        digests.add(slack::digest_req("the-channel", "first", vec![attach.clone()], 100));
+        digests.add(slack::digest_req("@joe", "second", vec![], 200));
+        digests.add(slack::digest_req("the-channel", "third", vec![], 100));
+
+        assert_eq!(0, digests.take_due(99).len());
+        assert_eq!(
+            vec![
+                slack::req(
+                    "the-channel",
+                    "Daily digest: 2 update(s)",
+                    vec![SlackAttachment::new("first"), attach.clone(), SlackAttachment::new("third")],
+                ),
+            ],
+            digests.take_due(150)
+        );
+        assert_eq!(1, digests.len());
+        assert_eq!(
+            vec![slack::req("@joe", "Daily digest: 1 update(s)", vec![SlackAttachment::new("second")])],
+            digests.take_due(200)
+        );
+        assert_eq!(0, digests.len());
+    }
+
+    #[test]
+    fn test_too_many_attachments() {
+        let mut digests = Digests::default();
+        for i in 0..150 {
+            digests.add(slack::digest_req("the-channel", &format!("msg {}", i), vec![], 100));
+        }
+
+        let summary = digests.take_due(100).pop().unwrap();
+        assert_eq!(MAX_ATTACHMENTS, summary.attachments.len());
+        assert_eq!("... and 51 more", summary.attachments[MAX_ATTACHMENTS - 1].text);
+    }
+
+    #[test]
+    fn test_is_low_priority() {
+        assert!(is_low_priority("comment"));
+        assert!(is_low_priority("push"));
+        assert!(!is_low_priority("review"));
+    }
+}
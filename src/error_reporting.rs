@@ -0,0 +1,248 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+use std::panic;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::atomic::{ATOMIC_BOOL_INIT, AtomicBool, Ordering};
+use std::thread;
+
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::Value;
+use tokio_core::reactor::Remote;
+use url::Url;
+
+use audit;
+use config::ErrorReportingConfig;
+use errors::*;
+use http_client::HTTPClient;
+use quiet_hours;
+use worker::{self, Worker};
+
+const SENTRY_VERSION: u32 = 7;
+
+// Something that went wrong, as it goes to Sentry
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorReport {
+    // "error", or "fatal" for panics
+    pub level: String,
+    pub message: String,
+    // where it happened, e.g. the source line of a panic
+    pub culprit: Option<String>,
+    pub tags: HashMap<String, String>,
+    // what octobot was working on, e.g. the webhook event it couldn't handle
+    pub payload: Option<String>,
+}
+
+thread_local! {
+    static PAYLOAD: RefCell<Option<String>> = RefCell::new(None);
+}
+
+// set once panics are reported, so that workers only describe their requests when it's for something
+static REPORTING_PANICS: AtomicBool = ATOMIC_BOOL_INIT;
+
+// Runs `func` with `payload` attached to anything reported from this thread, panics included.
+pub fn with_payload<T, F: FnOnce() -> T>(payload: Option<String>, func: F) -> T {
+    let previous = PAYLOAD.with(|p| mem::replace(&mut *p.borrow_mut(), payload));
+    let result = func();
+    PAYLOAD.with(|p| *p.borrow_mut() = previous);
+    result
+}
+
+pub fn current_payload() -> Option<String> {
+    PAYLOAD.with(|p| p.borrow().clone())
+}
+
+// A worker request as a payload, if a panic handling it would be reported
+pub fn payload_of<T: Debug>(req: &T) -> Option<String> {
+    if REPORTING_PANICS.load(Ordering::SeqCst) {
+        Some(format!("{:#?}", req))
+    } else {
+        None
+    }
+}
+
+impl ErrorReport {
+    // a report with what this thread is working on
+    pub fn new(level: &str, message: &str, culprit: Option<String>) -> ErrorReport {
+        let mut tags = HashMap::new();
+        tags.insert("thread".to_string(), thread::current().name().unwrap_or("unknown").to_string());
+        tags.insert("trigger".to_string(), audit::current_trigger());
+        ErrorReport {
+            level: level.into(),
+            message: message.into(),
+            culprit: culprit,
+            tags: tags,
+            payload: current_payload(),
+        }
+    }
+
+    // the event for Sentry's store API
+    pub fn sentry_event(&self, event_id: &str, environment: Option<&str>) -> Value {
+        let mut event = json!({
+            "event_id": event_id,
+            "timestamp": quiet_hours::now(),
+            "level": self.level,
+            "logger": "octobot",
+            "platform": "other",
+            "message": self.message,
+            "tags": self.tags,
+            "extra": {},
+        });
+        if let Some(ref culprit) = self.culprit {
+            event["culprit"] = json!(culprit);
+        }
+        if let Some(environment) = environment {
+            event["environment"] = json!(environment);
+        }
+        if let Some(ref payload) = self.payload {
+            event["extra"]["payload"] = json!(payload);
+        }
+        event
+    }
+}
+
+// Where a Sentry DSN ("https://<key>@<host>/<project>") says to send events, and the key to send them with
+pub fn sentry_target(dsn: &str) -> Result<(String, String)> {
+    let url = Url::parse(dsn).map_err(|e| Error::from(format!("Invalid sentry DSN: {}", e)))?;
+    if url.username().is_empty() {
+        return Err("Sentry DSN has no key".into());
+    }
+    let host = match url.host_str() {
+        Some(h) => h,
+        None => return Err("Sentry DSN has no host".into()),
+    };
+    let path = url.path().trim_matches('/');
+    let (prefix, project) = match path.rfind('/') {
+        Some(i) => (format!("/{}", &path[..i]), &path[i + 1..]),
+        None => (String::new(), path),
+    };
+    if project.is_empty() {
+        return Err("Sentry DSN has no project".into());
+    }
+    let port = url.port().map(|p| format!(":{}", p)).unwrap_or(String::new());
+    let store_url = format!("{}://{}{}{}/api/{}/store/", url.scheme(), host, port, prefix, project);
+    Ok((store_url, url.username().to_string()))
+}
+
+// Sends reports to Sentry in the background
+pub struct ErrorReporter {
+    worker: Worker<ErrorReport>,
+}
+
+impl ErrorReporter {
+    pub fn new(core_remote: Remote, config: &ErrorReportingConfig) -> Result<ErrorReporter> {
+        let (url, key) = sentry_target(&config.sentry_dsn)?;
+        let auth = format!("Sentry sentry_version={}, sentry_key={}, sentry_client=octobot", SENTRY_VERSION, key);
+        let client = HTTPClient::new(core_remote, "").with_headers(hashmap!{
+            "Content-Type" => "application/json".to_string(),
+            "X-Sentry-Auth" => auth,
+        });
+        let runner = Runner {
+            client: client,
+            url: url,
+            environment: config.environment.clone(),
+        };
+        Ok(ErrorReporter { worker: worker::Worker::new("error-reporter", runner) })
+    }
+
+    pub fn report(&self, report: ErrorReport) {
+        if let Err(e) = self.worker.new_sender().send(report) {
+            error!("Error sending to the error reporter: {}", e);
+        }
+    }
+
+    // `payload` is what octobot was working on, if it isn't the payload of this thread
+    pub fn report_error(&self, message: &str, payload: Option<&str>) {
+        let mut report = ErrorReport::new("error", message, None);
+        if let Some(payload) = payload {
+            report.payload = Some(payload.to_string());
+        }
+        self.report(report);
+    }
+}
+
+// Reports every panic, whichever thread it's in, before it goes on as it would have otherwise.
+pub fn report_panics(reporter: Arc<ErrorReporter>) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = match payload.downcast_ref::<&str>() {
+            Some(s) => s.to_string(),
+            None => payload.downcast_ref::<String>().cloned().unwrap_or("Box<Any>".into()),
+        };
+        let culprit = info.location().map(|l| format!("{}:{}", l.file(), l.line()));
+        reporter.report(ErrorReport::new("fatal", &format!("panic: {}", message), culprit));
+        default_hook(info);
+    }));
+    REPORTING_PANICS.store(true, Ordering::SeqCst);
+}
+
+struct Runner {
+    client: HTTPClient,
+    url: String,
+    environment: Option<String>,
+}
+
+fn event_id() -> String {
+    let mut buf = [0; 16];
+    SystemRandom::new().fill(&mut buf).expect("get random");
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl worker::Runner<ErrorReport> for Runner {
+    fn handle(&self, report: ErrorReport) {
+        let event = report.sentry_event(&event_id(), self.environment.as_ref().map(|e| e.as_str()));
+        if let Err(e) = self.client.post_void(&self.url, &event) {
+            error!("Error reporting '{}' to sentry: {}", report.message, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sentry_target() {
+        assert_eq!(
+            ("https://sentry.io/api/42/store/".to_string(), "abc123".to_string()),
+            sentry_target("https://abc123@sentry.io/42").unwrap()
+        );
+        assert_eq!(
+            ("http://sentry.company.com:9000/sentry/api/7/store/".to_string(), "abc123".to_string()),
+            sentry_target("http://abc123@sentry.company.com:9000/sentry/7").unwrap()
+        );
+        assert!(sentry_target("https://sentry.io/42").is_err());
+        assert!(sentry_target("https://abc123@sentry.io/").is_err());
+        assert!(sentry_target("sentry").is_err());
+    }
+
+    #[test]
+    fn test_with_payload() {
+        assert_eq!(None, current_payload());
+        let report = with_payload(Some("{\"action\": \"opened\"}".into()), || {
+            audit::with_trigger("pull_request", || ErrorReport::new("error", "Error parsing JSON", None))
+        });
+        assert_eq!(None, current_payload());
+
+        assert_eq!(Some("{\"action\": \"opened\"}".to_string()), report.payload);
+        assert_eq!(Some(&"pull_request".to_string()), report.tags.get("trigger"));
+    }
+
+    #[test]
+    fn test_sentry_event() {
+        let mut report = ErrorReport::new("fatal", "panic: oops", Some("src/pr_merge.rs:12".into()));
+        report.tags = hashmap!{"thread".to_string() => "pr-merge".to_string()};
+        report.payload = Some("{}".into());
+
+        let event = report.sentry_event("fc6d8c0c43fc4630ad850ee518f1b9d0", Some("prod"));
+        assert_eq!(json!("fc6d8c0c43fc4630ad850ee518f1b9d0"), event["event_id"]);
+        assert_eq!(json!("fatal"), event["level"]);
+        assert_eq!(json!("panic: oops"), event["message"]);
+        assert_eq!(json!("src/pr_merge.rs:12"), event["culprit"]);
+        assert_eq!(json!("prod"), event["environment"]);
+        assert_eq!(json!({"thread": "pr-merge"}), event["tags"]);
+        assert_eq!(json!({"payload": "{}"}), event["extra"]);
+    }
+}
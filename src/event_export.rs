@@ -0,0 +1,285 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde_json::{self, Value};
+use tokio_core::reactor::Remote;
+use url::Url;
+
+use audit::AuditEntry;
+use errors::*;
+use github;
+use http_client::HTTPClient;
+use quiet_hours;
+use worker;
+
+const DEFAULT_NATS_PORT: u16 = 4222;
+const DEFAULT_NATS_SUBJECT: &'static str = "octobot.events";
+
+// One line of the export stream: an event octobot handled, or something it did about one
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ExportedEvent {
+    // "event" for the webhooks octobot handles, "action" for what it does (the audit log's entries)
+    pub kind: String,
+    // the github host the event came from or the action was for
+    pub host: String,
+    pub at: i64,
+    // the event, e.g. "pull_request", or the action, e.g. "merge_pull_request"
+    pub name: String,
+    // the event as octobot sees it (the same for github, gitlab and bitbucket), or the audit entry
+    pub data: Value,
+}
+
+impl ExportedEvent {
+    pub fn event(event: &str, data: &github::HookBody) -> ExportedEvent {
+        ExportedEvent {
+            kind: "event".into(),
+            host: String::new(),
+            at: quiet_hours::now(),
+            name: event.into(),
+            data: serde_json::to_value(data).unwrap_or(Value::Null),
+        }
+    }
+
+    pub fn action(entry: &AuditEntry) -> ExportedEvent {
+        ExportedEvent {
+            kind: "action".into(),
+            host: String::new(),
+            at: entry.at,
+            name: entry.action.clone(),
+            data: serde_json::to_value(entry).unwrap_or(Value::Null),
+        }
+    }
+}
+
+// Where exported events go
+pub trait Sink: Send {
+    fn publish(&self, event: &ExportedEvent) -> Result<()>;
+}
+
+// POSTs each event as JSON
+struct HttpSink {
+    client: HTTPClient,
+    url: String,
+}
+
+impl Sink for HttpSink {
+    fn publish(&self, event: &ExportedEvent) -> Result<()> {
+        self.client.post_void(&self.url, event)
+    }
+}
+
+// Publishes each event as JSON to a NATS subject, over a plain connection that's made again if it drops.
+pub struct NatsSink {
+    addr: String,
+    subject: String,
+    conn: Mutex<Option<TcpStream>>,
+}
+
+impl NatsSink {
+    pub fn new(addr: &str, subject: &str) -> NatsSink {
+        NatsSink {
+            addr: addr.into(),
+            subject: subject.into(),
+            conn: Mutex::new(None),
+        }
+    }
+
+    fn connect(&self) -> Result<TcpStream> {
+        let mut stream = TcpStream::connect(&self.addr).map_err(|e| {
+            Error::from(format!("Error connecting to NATS at {}: {}", self.addr, e))
+        })?;
+        stream.set_read_timeout(Some(Duration::from_millis(1)))?;
+        stream.write_all(b"CONNECT {\"verbose\":false,\"pedantic\":false,\"name\":\"octobot\"}\r\n")?;
+        Ok(stream)
+    }
+
+    // answers the server's keepalive PINGs, which pile up between events. false if the server hung up
+    fn keep_alive(stream: &mut TcpStream) -> Result<bool> {
+        let mut buf = [0; 4096];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => return Ok(false),
+                Ok(n) => {
+                    if String::from_utf8_lossy(&buf[..n]).contains("PING") {
+                        stream.write_all(b"PONG\r\n")?;
+                    }
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                    return Ok(true)
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn send(&self, conn: &mut Option<TcpStream>, payload: &str) -> Result<()> {
+        let alive = match *conn {
+            Some(ref mut stream) => NatsSink::keep_alive(stream).unwrap_or(false),
+            None => false,
+        };
+        if !alive {
+            *conn = Some(self.connect()?);
+        }
+
+        let msg = format!("PUB {} {}\r\n{}\r\n", self.subject, payload.len(), payload);
+        match *conn {
+            Some(ref mut stream) => stream.write_all(msg.as_bytes()).map_err(|e| e.into()),
+            None => Err("Not connected to NATS".into()),
+        }
+    }
+}
+
+impl Sink for NatsSink {
+    fn publish(&self, event: &ExportedEvent) -> Result<()> {
+        let payload = serde_json::to_string(event).map_err(|e| Error::from(format!("Error encoding event: {}", e)))?;
+        let mut conn = self.conn.lock().unwrap();
+        if let Err(e) = self.send(&mut conn, &payload) {
+            // once more on a new connection, in case the old one went away without saying so
+            error!("Error publishing to NATS, reconnecting: {}", e);
+            *conn = None;
+            return self.send(&mut conn, &payload);
+        }
+        Ok(())
+    }
+}
+
+// The sink for an [export] url: "nats://host[:port][/subject]", or an http(s) URL to POST to
+pub fn new_sink(core_remote: Remote, url: &str) -> Result<Box<Sink>> {
+    let parsed = Url::parse(url).map_err(|e| Error::from(format!("Invalid export url '{}': {}", url, e)))?;
+    match parsed.scheme() {
+        "http" | "https" => {
+            let client = HTTPClient::new(core_remote, "").with_headers(hashmap!{
+                "Content-Type" => "application/json".to_string(),
+            });
+            Ok(Box::new(HttpSink {
+                client: client,
+                url: url.into(),
+            }))
+        }
+        "nats" => {
+            let (addr, subject) = nats_target(&parsed)?;
+            Ok(Box::new(NatsSink::new(&addr, &subject)))
+        }
+        // octobot has no kafka client: events get there through a bridge from nats
+        "kafka" => Err(format!("Unsupported export url '{}': export to nats and bridge it to kafka", url).into()),
+        scheme => Err(format!("Unsupported export url '{}': use nats or http(s) instead of {}", url, scheme).into()),
+    }
+}
+
+fn nats_target(url: &Url) -> Result<(String, String)> {
+    let host = match url.host_str() {
+        Some(h) => h,
+        None => return Err(format!("Export url '{}' has no host", url).into()),
+    };
+    let subject = url.path().trim_matches('/');
+    let subject = if subject.is_empty() { DEFAULT_NATS_SUBJECT } else { subject };
+    Ok((format!("{}:{}", host, url.port().unwrap_or(DEFAULT_NATS_PORT)), subject.replace('/', ".")))
+}
+
+struct Runner {
+    host: String,
+    sink: Box<Sink>,
+}
+
+pub fn new_worker(host: &str, sink: Box<Sink>) -> worker::Worker<ExportedEvent> {
+    worker::Worker::new(
+        "event-export",
+        Runner {
+            host: host.into(),
+            sink: sink,
+        },
+    )
+}
+
+impl worker::Runner<ExportedEvent> for Runner {
+    fn handle(&self, event: ExportedEvent) {
+        let mut event = event;
+        event.host = self.host.clone();
+        if let Err(e) = self.sink.publish(&event) {
+            error!("Error exporting {} {}: {}", event.kind, event.name, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread;
+    use tokio_core::reactor::Core;
+
+    #[test]
+    fn test_nats_target() {
+        let target = |url: &str| nats_target(&Url::parse(url).unwrap()).unwrap();
+        assert_eq!(("nats.local:4222".to_string(), "octobot.events".to_string()), target("nats://nats.local"));
+        assert_eq!(
+            ("nats.local:4333".to_string(), "ci.github".to_string()),
+            target("nats://nats.local:4333/ci/github")
+        );
+    }
+
+    #[test]
+    fn test_new_sink() {
+        let core = Core::new().unwrap();
+        let error = |url: &str| new_sink(core.remote(), url).err().map(|e| format!("{}", e)).unwrap_or_default();
+        assert!(new_sink(core.remote(), "nats://nats.local").is_ok());
+        assert!(new_sink(core.remote(), "https://events.local/octobot").is_ok());
+        assert_eq!(
+            "Unsupported export url 'kafka://kafka.local:9092/octobot': export to nats and bridge it to kafka",
+            error("kafka://kafka.local:9092/octobot")
+        );
+        assert_eq!(
+            "Unsupported export url 'ftp://events.local': use nats or http(s) instead of ftp",
+            error("ftp://events.local")
+        );
+    }
+
+    #[test]
+    fn test_nats_sink() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut lines = BufReader::new(stream).lines().map(|l| l.unwrap());
+            let connect = lines.next().unwrap();
+            (connect, lines.next().unwrap(), lines.next().unwrap())
+        });
+
+        let sink = NatsSink::new(&addr, "octobot.events");
+        let event = ExportedEvent {
+            kind: "event".into(),
+            host: "git.foo.com".into(),
+            at: 1000,
+            name: "ping".into(),
+            data: json!({}),
+        };
+        sink.publish(&event).unwrap();
+
+        let (connect, publish, payload) = server.join().unwrap();
+        assert!(connect.starts_with("CONNECT {"), "{}", connect);
+        assert_eq!(format!("PUB octobot.events {}", payload.len()), publish);
+        assert_eq!(
+            json!({"kind": "event", "host": "git.foo.com", "at": 1000, "name": "ping", "data": {}}),
+            serde_json::from_str::<Value>(&payload).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_action() {
+        let entry = AuditEntry {
+            at: 1000,
+            action: "merge_pull_request".into(),
+            target: "some-user/some-repo#12".into(),
+            trigger: "pull_request".into(),
+            outcome: "ok".into(),
+        };
+        let event = ExportedEvent::action(&entry);
+        assert_eq!("action", event.kind);
+        assert_eq!("merge_pull_request", event.name);
+        assert_eq!(1000, event.at);
+        assert_eq!(json!("some-user/some-repo#12"), event.data["target"]);
+    }
+}
@@ -0,0 +1,368 @@
+use regex::Regex;
+
+use errors::*;
+use github;
+use github::api::Session;
+use messenger::{self, Messenger};
+use repos;
+use util;
+
+// github cuts status descriptions off at 140 characters
+const MAX_DESCRIPTION: usize = 140;
+
+// One of a repo's "rules": what an event has to look like, and what to do when one does. e.g.
+// {"events": ["pull_request"], "actions": ["opened"], "labels": ["security"], "notify_channel": "#security"}
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct EventRule {
+    // The conditions, all of which have to hold. Ones that aren't set always do.
+    // webhook events, e.g. "pull_request" or "issues"
+    pub events: Option<Vec<String>>,
+    // their actions, e.g. "opened" or "labeled"
+    pub actions: Option<Vec<String>>,
+    // labels of the PR or issue, any of which it has to have
+    pub labels: Option<Vec<String>>,
+    // the PR's base branch, or the pushed branch, where '*' matches anything, e.g. "release/*"
+    pub branch: Option<String>,
+    // github logins of the PR or issue's author (of the sender for other events), where '*' matches anything
+    pub authors: Option<Vec<String>>,
+    // regex the PR or issue's title has to match
+    pub title: Option<String>,
+
+    // What to do.
+    // slack channel to tell about the event, on top of wherever it goes otherwise
+    pub notify_channel: Option<String>,
+    // github logins of users to tell about the event in a direct message
+    pub notify_users: Option<Vec<String>>,
+    // labels to add to the PR or issue
+    pub add_labels: Option<Vec<String>>,
+    // "<context> <state> [description]" status to set on the PR's head, e.g.
+    // "octobot/security pending Needs a security review"
+    pub status: Option<String>,
+    // drop the event: octobot does nothing about it, not even what other rules say
+    pub ignore: Option<bool>,
+}
+
+impl EventRule {
+    pub fn new() -> EventRule {
+        EventRule {
+            events: None,
+            actions: None,
+            labels: None,
+            branch: None,
+            authors: None,
+            title: None,
+            notify_channel: None,
+            notify_users: None,
+            add_labels: None,
+            status: None,
+            ignore: None,
+        }
+    }
+}
+
+// What rules look at in an event
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleSubject {
+    pub event: String,
+    pub action: String,
+    pub labels: Vec<String>,
+    pub branch: Option<String>,
+    pub author: String,
+    pub title: Option<String>,
+}
+
+impl RuleSubject {
+    pub fn new(event: &str, action: &str, data: &github::HookBody) -> RuleSubject {
+        let issue = data.issue.as_ref();
+        let pull_request = data.pull_request.as_ref();
+
+        let mut labels: Vec<String> = match pull_request.and_then(|pr| pr.labels.as_ref()) {
+            Some(labels) => labels.iter().map(|l| l.name.clone()).collect(),
+            None => issue.map_or(vec![], |i| i.labels.iter().map(|l| l.name.clone()).collect()),
+        };
+        // the label a "labeled" action is about isn't always in the refetched PR's labels yet
+        if let Some(ref label) = data.label {
+            if action == "labeled" && !labels.contains(&label.name) {
+                labels.push(label.name.clone());
+            }
+        }
+
+        let branch = match pull_request {
+            Some(pr) => Some(pr.base.ref_name.clone()),
+            None => data.ref_name.as_ref().map(|r| r.trim_left_matches("refs/heads/").to_string()),
+        };
+        let author = match (pull_request, issue) {
+            (Some(pr), _) => pr.user.login(),
+            (None, Some(i)) => i.user.login(),
+            (None, None) => data.sender.login(),
+        };
+        let title = match (pull_request, issue) {
+            (Some(pr), _) => Some(pr.title.clone()),
+            (None, Some(i)) => Some(i.title.clone()),
+            (None, None) => None,
+        };
+
+        RuleSubject {
+            event: event.into(),
+            action: action.into(),
+            labels: labels,
+            branch: branch,
+            author: author.into(),
+            title: title,
+        }
+    }
+}
+
+// A repo's rules, with their title regexes compiled. Rules with invalid regexes are left out.
+pub struct EventRules {
+    rules: Vec<(EventRule, Option<Regex>)>,
+}
+
+impl EventRules {
+    pub fn parse(rules: &Vec<EventRule>) -> EventRules {
+        let mut parsed = vec![];
+        for rule in rules {
+            let title = match rule.title {
+                Some(ref pattern) => {
+                    match Regex::new(pattern) {
+                        Ok(re) => Some(re),
+                        Err(e) => {
+                            error!("Invalid title pattern `{}` in rule: {}", pattern, e);
+                            continue;
+                        }
+                    }
+                }
+                None => None,
+            };
+            parsed.push((rule.clone(), title));
+        }
+        EventRules { rules: parsed }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // the rules that apply to the event, in the order they're configured
+    pub fn matching(&self, subject: &RuleSubject) -> Vec<&EventRule> {
+        self.rules.iter().filter(|&&(ref r, ref title)| matches(r, title, subject)).map(|&(ref r, _)| r).collect()
+    }
+}
+
+fn any_is(values: &Option<Vec<String>>, value: &str) -> bool {
+    match *values {
+        Some(ref values) => values.iter().any(|v| v == value),
+        None => true,
+    }
+}
+
+fn matches(rule: &EventRule, title: &Option<Regex>, subject: &RuleSubject) -> bool {
+    if !any_is(&rule.events, &subject.event) || !any_is(&rule.actions, &subject.action) {
+        return false;
+    }
+    if let Some(ref labels) = rule.labels {
+        if !labels.iter().any(|l| subject.labels.contains(l)) {
+            return false;
+        }
+    }
+    if let Some(ref branch) = rule.branch {
+        if !subject.branch.as_ref().map_or(false, |b| repos::branch_matches(branch, b)) {
+            return false;
+        }
+    }
+    if let Some(ref authors) = rule.authors {
+        if !authors.iter().any(|a| repos::branch_matches(a, &subject.author)) {
+            return false;
+        }
+    }
+    if let Some(ref title) = *title {
+        if !subject.title.as_ref().map_or(false, |t| title.is_match(t)) {
+            return false;
+        }
+    }
+    true
+}
+
+// The status a "<context> <state> [description]" rule asks for
+pub fn parse_status(rule: &str, target_url: &str) -> Result<github::Status> {
+    let mut parts = rule.trim().splitn(3, char::is_whitespace);
+    let (context, state) = match (parts.next(), parts.next()) {
+        (Some(context), Some(state)) if !context.is_empty() => (context, state.trim()),
+        _ => return Err(format!("Status rule '{}' needs a context and a state", rule).into()),
+    };
+    match state {
+        "pending" | "success" | "failure" | "error" => (),
+        _ => return Err(format!("Invalid state '{}' in status rule '{}'", state, rule).into()),
+    };
+    let description = match parts.next().map(|d| d.trim()) {
+        Some(d) if !d.is_empty() => Some(d.chars().take(MAX_DESCRIPTION).collect()),
+        _ => None,
+    };
+    Ok(github::Status {
+        state: state.into(),
+        target_url: Some(target_url.into()),
+        context: Some(context.into()),
+        description: description,
+        creator: None,
+    })
+}
+
+// What the channel and users the rules notify are told
+pub fn message(subject: &RuleSubject, data: &github::HookBody) -> String {
+    let what = if subject.action.is_empty() {
+        subject.event.clone()
+    } else {
+        format!("{} {}", subject.event, subject.action)
+    };
+    let on = match (data.pull_request.as_ref(), data.issue.as_ref()) {
+        (Some(pr), _) => util::make_link(&pr.html_url, &format!("#{}: {}", pr.number, pr.title)),
+        (None, Some(i)) => util::make_link(&i.html_url, &format!("#{}: {}", i.number, i.title)),
+        (None, None) => subject.branch.clone().unwrap_or(data.repository.full_name.clone()),
+    };
+    format!("{} on {} by {}", what, on, data.sender.login())
+}
+
+// Does what the matching rules say about the event, apart from ignoring it
+pub fn apply(
+    github: &Session,
+    messenger: &Messenger,
+    data: &github::HookBody,
+    subject: &RuleSubject,
+    rules: &Vec<&EventRule>,
+) -> Result<()> {
+    let repo = &data.repository;
+    let msg = message(subject, data);
+    let number = match (data.pull_request.as_ref(), data.issue.as_ref()) {
+        (Some(pr), _) => Some(pr.number),
+        (None, Some(i)) => Some(i.number),
+        (None, None) => None,
+    };
+    let mut errors = vec![];
+
+    for rule in rules {
+        if let Some(ref channel) = rule.notify_channel {
+            messenger.send_to_team_channel(&msg, &vec![], channel, repo);
+        }
+        if let Some(ref logins) = rule.notify_users {
+            let users = logins.iter().map(|l| github::User::new(l)).collect();
+            messenger.send_to_users(&msg, &vec![], &users, repo, messenger::Reason::Mention);
+        }
+
+        if let (Some(ref labels), Some(number)) = (rule.add_labels.as_ref(), number) {
+            let missing: Vec<String> = labels.iter().filter(|l| !subject.labels.contains(l)).cloned().collect();
+            if !missing.is_empty() {
+                if let Err(e) = github.add_pull_request_labels(repo.owner.login(), &repo.name, number, missing) {
+                    errors.push(format!("Error adding labels to #{}: {}", number, e));
+                }
+            }
+        }
+
+        if let (Some(ref status), Some(pr)) = (rule.status.as_ref(), data.pull_request.as_ref()) {
+            let result = parse_status(status, &pr.html_url).and_then(|status| {
+                github.create_status(repo.owner.login(), &repo.name, &pr.head.sha, &status)
+            });
+            if let Err(e) = result {
+                errors.push(format!("Error setting status of #{}: {}", pr.number, e));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; ").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pull_request_event() -> github::HookBody {
+        let mut data = github::HookBody::new();
+        data.sender = github::User::new("joe");
+        let mut pr = github::PullRequest::new();
+        pr.number = 12;
+        pr.title = "Fix the login page".into();
+        pr.html_url = "http://git.company.com/some-user/some-repo/pull/12".into();
+        pr.user = github::User::new("joe");
+        pr.base = github::BranchRef::new("release/1.0");
+        pr.labels = Some(vec![github::Label::new("security")]);
+        data.pull_request = Some(pr);
+        data
+    }
+
+    #[test]
+    fn test_rule_subject() {
+        let subject = RuleSubject::new("pull_request", "opened", &pull_request_event());
+        assert_eq!("opened", subject.action);
+        assert_eq!(vec!["security".to_string()], subject.labels);
+        assert_eq!(Some("release/1.0".to_string()), subject.branch);
+        assert_eq!("joe", subject.author);
+        assert_eq!(Some("Fix the login page".to_string()), subject.title);
+
+        let mut push = github::HookBody::new();
+        push.ref_name = Some("refs/heads/master".into());
+        push.sender = github::User::new("dependabot[bot]");
+        let subject = RuleSubject::new("push", "", &push);
+        assert_eq!(Some("master".to_string()), subject.branch);
+        assert_eq!("dependabot[bot]", subject.author);
+        assert_eq!(None, subject.title);
+    }
+
+    #[test]
+    fn test_matching() {
+        let mut security = EventRule::new();
+        security.events = Some(vec!["pull_request".into()]);
+        security.labels = Some(vec!["security".into(), "auth".into()]);
+        security.notify_channel = Some("#security".into());
+
+        let mut releases = EventRule::new();
+        releases.branch = Some("release/*".into());
+        releases.title = Some("(?i)^fix".into());
+
+        let mut bots = EventRule::new();
+        bots.authors = Some(vec!["*[bot]".into()]);
+        bots.ignore = Some(true);
+
+        let mut closed = EventRule::new();
+        closed.actions = Some(vec!["closed".into()]);
+
+        let mut invalid = EventRule::new();
+        invalid.title = Some("(".into());
+
+        let rules = EventRules::parse(&vec![security.clone(), releases.clone(), bots.clone(), closed, invalid]);
+        let subject = RuleSubject::new("pull_request", "opened", &pull_request_event());
+        assert_eq!(vec![&security, &releases], rules.matching(&subject));
+
+        let mut subject = subject;
+        subject.author = "renovate[bot]".into();
+        subject.labels = vec![];
+        subject.title = None;
+        assert_eq!(vec![&bots], rules.matching(&subject));
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let status = parse_status("octobot/security pending Needs a security review", "http://pr").unwrap();
+        assert_eq!("pending", status.state);
+        assert_eq!(Some("octobot/security".to_string()), status.context);
+        assert_eq!(Some("Needs a security review".to_string()), status.description);
+        assert_eq!(Some("http://pr".to_string()), status.target_url);
+
+        assert_eq!(None, parse_status("ci/manual success", "http://pr").unwrap().description);
+        assert!(parse_status("ci/manual", "http://pr").is_err());
+        assert!(parse_status("ci/manual done", "http://pr").is_err());
+    }
+
+    #[test]
+    fn test_message() {
+        let data = pull_request_event();
+        let subject = RuleSubject::new("pull_request", "opened", &data);
+        assert_eq!(
+            "pull_request opened on <http://git.company.com/some-user/some-repo/pull/12|#12: Fix the login page> \
+             by joe",
+            message(&subject, &data)
+        );
+    }
+}
@@ -10,10 +10,14 @@ use git::Git;
 use git_clone_manager::GitCloneManager;
 use github;
 use github::Commit;
+use messages::{self, Messages};
+use messenger::MessengerFactory;
+use slack::SlackAttachmentBuilder;
 use worker;
 
 pub fn comment_force_push(
     diffs: Result<DiffOfDiffs>,
+    comparison: Option<&github::Comparison>,
     reapply_statuses: Vec<String>,
     github: &github::api::Session,
     owner: &str,
@@ -21,26 +25,23 @@ pub fn comment_force_push(
     pull_request: &github::PullRequest,
     before_hash: &str,
     after_hash: &str,
+    messages: &Messages,
 ) -> Result<()> {
-    let mut comment = format!(
-        "Force-push detected: before: {}, after: {}: ",
-        Commit::short_hash_str(before_hash),
-        Commit::short_hash_str(after_hash)
-    );
-
+    let result;
+    let mut details = String::new();
     let identical_diff;
     match diffs {
         Ok(ref diffs) => {
             if diffs.are_equal() {
-                comment += "Identical diff post-rebase";
+                result = "Identical diff post-rebase";
                 identical_diff = true;
             } else {
-                comment += "Diff changed post-rebase";
+                result = "Diff changed post-rebase";
                 let different_files = diffs.different_patch_files();
                 if different_files.len() > 0 {
-                    comment += "\n\nChanged files:\n";
+                    details += "\n\nChanged files:\n";
                     for file in different_files {
-                        comment += &format!("* {}\n", file.path());
+                        details += &format!("* {}\n", file.path());
                     }
                 }
 
@@ -48,12 +49,28 @@ pub fn comment_force_push(
             }
         }
         Err(e) => {
-            comment += "Unable to calculate diff";
+            result = "Unable to calculate diff";
             identical_diff = false;
             error!("Error calculating force push diff: {}", e);
         }
     };
 
+    let mut comment = messages.format(
+        messages::FORCE_PUSH_COMMENT,
+        &[
+            ("before", &Commit::short_hash_str(before_hash)),
+            ("after", &Commit::short_hash_str(after_hash)),
+            ("result", result),
+            ("number", &pull_request.number.to_string()),
+            ("title", &pull_request.title),
+        ],
+    );
+    comment += &details;
+
+    if let Some(comparison) = comparison {
+        comment += &format!("\n\n{}", describe_commits(comparison));
+    }
+
     if let Err(e) = github.comment_pull_request(owner, repo, pull_request.number, &comment) {
         error!("Error sending github PR comment: {}", e);
     }
@@ -92,6 +109,92 @@ pub fn comment_force_push(
     Ok(())
 }
 
+// Dismisses the approvals that were given before a force-push, and asks the approvers to review again.
+pub fn dismiss_approvals(
+    github: &github::api::Session,
+    owner: &str,
+    repo: &str,
+    pull_request: &github::PullRequest,
+    before_hash: &str,
+    after_hash: &str,
+) -> Result<()> {
+    let reviews = github.get_pull_request_reviews(owner, repo, pull_request.number)?;
+
+    // only each reviewer's latest say counts
+    let mut latest: Vec<&github::Review> = vec![];
+    for review in &reviews {
+        let state = review.state.to_lowercase();
+        if state == "approved" || state == "changes_requested" || state == "dismissed" {
+            latest.retain(|r| r.user.login() != review.user.login());
+            latest.push(review);
+        }
+    }
+
+    let message = format!(
+        "The diff changed in force-push {}..{}",
+        Commit::short_hash_str(before_hash),
+        Commit::short_hash_str(after_hash)
+    );
+    let mut approvers = vec![];
+    for review in latest.into_iter().filter(|r| r.state.to_lowercase() == "approved") {
+        if let Some(id) = review.id {
+            github.dismiss_review(owner, repo, pull_request.number, id, &message)?;
+            approvers.push(review.user.login().to_string());
+        }
+    }
+
+    if !approvers.is_empty() {
+        github.request_review(owner, repo, pull_request.number, approvers)?;
+    }
+    Ok(())
+}
+
+pub fn describe_commits(comparison: &github::Comparison) -> String {
+    format!("{} commit(s) removed, {} added", comparison.behind_by, comparison.ahead_by)
+}
+
+// Tells the channel how the force-push changed the PR, e.g. "Force-push to Pull Request #12: Identical diff
+// post-rebase, 3 commit(s) removed, 3 added", with the files whose changes differ.
+pub fn notify_force_push(
+    messengers: &MessengerFactory,
+    diffs: &Result<DiffOfDiffs>,
+    comparison: Option<&github::Comparison>,
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+    messages: &Messages,
+) {
+    let mut summary = vec![];
+    let mut files = vec![];
+    match *diffs {
+        Ok(ref diffs) if diffs.are_equal() => summary.push("Identical diff post-rebase".to_string()),
+        Ok(ref diffs) => {
+            summary.push("Diff changed post-rebase".to_string());
+            files = diffs.different_patch_files().iter().map(|f| f.path().to_string()).collect();
+        }
+        Err(_) => summary.push("Unable to calculate diff".to_string()),
+    };
+    if let Some(comparison) = comparison {
+        summary.push(describe_commits(comparison));
+    }
+
+    let msg = messages.format(
+        messages::FORCE_PUSH,
+        &[
+            ("number", &pull_request.number.to_string()),
+            ("title", &pull_request.title),
+            ("summary", &summary.join(", ")),
+        ],
+    );
+    let attachments = vec![
+        SlackAttachmentBuilder::new(&files.join("\n"))
+            .title(format!("Pull Request #{}: \"{}\"", pull_request.number, pull_request.title))
+            .title_link(pull_request.html_url.as_str())
+            .build(),
+    ];
+    let messenger = messengers.for_event(None, Some(pull_request)).for_kind("force_push");
+    messenger.send_to_channel(&msg, &attachments, repo, Some(pull_request.base.ref_name.as_str()));
+}
+
 pub fn diff_force_push(
     github: &github::api::Session,
     clone_mgr: &GitCloneManager,
@@ -138,6 +241,7 @@ struct Runner {
     config: Arc<Config>,
     github_session: Arc<github::api::Session>,
     clone_mgr: Arc<GitCloneManager>,
+    messengers: MessengerFactory,
     thread_pool: ThreadPool,
 }
 
@@ -161,6 +265,7 @@ pub fn new_worker(
     config: Arc<Config>,
     github_session: Arc<github::api::Session>,
     clone_mgr: Arc<GitCloneManager>,
+    messengers: MessengerFactory,
 ) -> worker::Worker<ForcePushRequest> {
     worker::Worker::new(
         "force-push",
@@ -168,6 +273,7 @@ pub fn new_worker(
             config: config,
             github_session: github_session,
             clone_mgr: clone_mgr,
+            messengers: messengers,
             thread_pool: threadpool::Builder::new()
                 .num_threads(max_concurrency)
                 .thread_name("force-push".to_string())
@@ -181,7 +287,10 @@ impl worker::Runner<ForcePushRequest> for Runner {
         let github_session = self.github_session.clone();
         let clone_mgr = self.clone_mgr.clone();
         let config = self.config.clone();
+        let messengers = self.messengers.clone();
         let statuses = config.repos().force_push_reapply_statuses(&req.repo);
+        let dismiss = config.repos().force_push_dismiss_approvals(&req.repo);
+        let messages = config.messages(&req.repo, Some(&req.pull_request.base.ref_name));
 
         // launch another thread to do the version calculation
         self.thread_pool.execute(move || {
@@ -195,9 +304,22 @@ impl worker::Runner<ForcePushRequest> for Runner {
                 &req.before_hash,
                 &req.after_hash,
             );
+            // a rebase that didn't change the diff doesn't need another look
+            let diff_changed = diffs.as_ref().map(|d| !d.are_equal()).unwrap_or(true);
+
+            let owner = req.repo.owner.login();
+            let comparison = match github.compare_commits(owner, &req.repo.name, &req.before_hash, &req.after_hash) {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    error!("Error comparing force push commits: {}", e);
+                    None
+                }
+            };
+            notify_force_push(&messengers, &diffs, comparison.as_ref(), &req.repo, &req.pull_request, &messages);
 
             let comment = comment_force_push(
                 diffs,
+                comparison.as_ref(),
                 statuses,
                 github,
                 &req.repo.owner.login(),
@@ -205,10 +327,25 @@ impl worker::Runner<ForcePushRequest> for Runner {
                 &req.pull_request,
                 &req.before_hash,
                 &req.after_hash,
+                &messages,
             );
             if let Err(e) = comment {
                 error!("Error diffing force push: {}", e);
             }
+
+            if dismiss && diff_changed {
+                if let Err(e) = dismiss_approvals(
+                    github,
+                    &req.repo.owner.login(),
+                    &req.repo.name,
+                    &req.pull_request,
+                    &req.before_hash,
+                    &req.after_hash,
+                )
+                {
+                    error!("Error dismissing approvals after force push: {}", e);
+                }
+            }
         });
     }
 }
@@ -81,6 +81,29 @@ impl Git {
         Ok(())
     }
 
+    // merge |source_ref| into the current branch. Returns false if there were conflicts, after putting the
+    // branch back the way it was.
+    pub fn merge(&self, source_ref: &str, msg: &str) -> Result<bool> {
+        match self.run(&["merge", "--no-ff", "-m", msg, source_ref]) {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                // only a conflicted merge leaves something to abort
+                if self.run(&["merge", "--abort"]).is_ok() {
+                    Ok(false)
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    // true if every commit between |base| and |head| is already on |upstream|, as itself or as a cherry-pick with
+    // the same changes (going by `git cherry`'s patch ids)
+    pub fn has_equivalent_commits(&self, upstream: &str, head: &str, base: &str) -> Result<bool> {
+        let output = self.run(&["cherry", upstream, head, base])?;
+        Ok(output.lines().all(|l| l.starts_with("-")))
+    }
+
     pub fn diff(&self, base: &str, head: &str) -> Result<String> {
         self.run(&["diff", base, head, "-w"])
     }
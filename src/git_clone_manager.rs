@@ -32,13 +32,7 @@ impl GitCloneManager {
     }
 
     fn clone_repo(&self, owner: &str, repo: &str, clone_dir: &PathBuf) -> Result<()> {
-        let url = format!(
-            "https://{}@{}/{}/{}",
-            self.github_session.user().login(),
-            self.github_session.github_host(),
-            owner,
-            repo
-        );
+        let url = self.github_session.clone_url(owner, repo);
 
         let git = Git::new(self.github_session.github_host(), self.github_session.github_token(), clone_dir);
 
@@ -1,8 +1,15 @@
+use base64;
+use serde::de::DeserializeOwned;
+use serde_json;
+use std::marker::PhantomData;
 use tokio_core::reactor::Remote;
+use url::form_urlencoded;
+use url::percent_encoding::{PATH_SEGMENT_ENCODE_SET, utf8_percent_encode};
 
+use config::GithubConfig;
 use errors::*;
 use github::models::*;
-use http_client::HTTPClient;
+use http_client::{HTTPClient, ResponseCache, RetryPolicy};
 
 pub trait Session: Send + Sync {
     fn user(&self) -> &User;
@@ -28,10 +35,18 @@ pub trait Session: Send + Sync {
     ) -> Result<PullRequest>;
 
     fn get_pull_request_labels(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<Label>>;
+    fn add_pull_request_labels(&self, owner: &str, repo: &str, number: u32, labels: Vec<String>) -> Result<()>;
+    fn remove_pull_request_label(&self, owner: &str, repo: &str, number: u32, label: &str) -> Result<()>;
+    // by the milestone's title
+    fn set_pull_request_milestone(&self, owner: &str, repo: &str, number: u32, milestone: &str) -> Result<()>;
 
     fn get_pull_request_commits(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<Commit>>;
+    fn get_pull_request_files(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<PullRequestFile>>;
+    fn get_pull_request_stats(&self, owner: &str, repo: &str, number: u32) -> Result<DiffStats>;
 
     fn get_pull_request_reviews(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<Review>>;
+    // the inline comments of a review
+    fn get_review_comments(&self, owner: &str, repo: &str, number: u32, review_id: u64) -> Result<Vec<Comment>>;
 
     fn assign_pull_request(
         &self,
@@ -41,26 +56,201 @@ pub trait Session: Send + Sync {
         assignees: Vec<String>,
     ) -> Result<AssignResponse>;
 
+    fn request_review(&self, owner: &str, repo: &str, number: u32, reviewers: Vec<String>) -> Result<()>;
+    fn dismiss_review(&self, owner: &str, repo: &str, number: u32, review_id: u64, message: &str) -> Result<()>;
+
     fn comment_pull_request(&self, owner: &str, repo: &str, number: u32, comment: &str) -> Result<()>;
     fn create_branch(&self, owner: &str, repo: &str, branch_name: &str, sha: &str) -> Result<()>;
     fn delete_branch(&self, owner: &str, repo: &str, branch_name: &str) -> Result<()>;
+    fn get_file_contents(&self, owner: &str, repo: &str, path: &str, ref_name: &str) -> Result<String>;
+    fn get_user(&self, login: &str) -> Result<User>;
+    // "admin", "write", "read", or "none"
+    fn get_user_permission(&self, owner: &str, repo: &str, login: &str) -> Result<String>;
+    // the members of @org/team
+    fn get_team_members(&self, org: &str, team: &str) -> Result<Vec<User>>;
     fn get_statuses(&self, owner: &str, repo: &str, ref_name: &str) -> Result<Vec<Status>>;
     fn create_status(&self, owner: &str, repo: &str, ref_name: &str, status: &Status) -> Result<()>;
+    fn get_combined_status(&self, owner: &str, repo: &str, ref_name: &str) -> Result<CombinedStatus>;
+    fn get_commit(&self, owner: &str, repo: &str, sha: &str) -> Result<Commit>;
+    fn compare_commits(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<Comparison>;
+    // the shas of the commits reachable from `head` but not from `base`, e.g. between two tags
+    fn get_commits_between(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<Vec<String>>;
+    fn create_release(&self, owner: &str, repo: &str, tag: &str, name: &str, body: &str) -> Result<()>;
+    // the names of the repo's tags
+    fn get_tags(&self, owner: &str, repo: &str) -> Result<Vec<String>>;
+    fn is_branch_protected(&self, owner: &str, repo: &str, branch: &str) -> Result<bool>;
+    // keeps force pushes and deletions off the branch, and merges into it waiting for the status checks and approvals
+    fn protect_branch(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        required_checks: &Vec<String>,
+        required_approvals: u32,
+    ) -> Result<()>;
+    // runs a workflow run's (or pipeline's) failed jobs again
+    fn rerun_failed_jobs(&self, owner: &str, repo: &str, run_id: u64) -> Result<()>;
+    // the pull requests, open or not, that a commit is part of or was merged by
+    fn get_pull_requests_for_commit(&self, owner: &str, repo: &str, sha: &str) -> Result<Vec<PullRequest>>;
+    fn get_hooks(&self, owner: &str, repo: &str) -> Result<Vec<Hook>>;
+    // creates the hook, or changes it if it has an id
+    fn save_hook(&self, owner: &str, repo: &str, hook: &Hook) -> Result<()>;
+
+    fn merge_pull_request(&self, owner: &str, repo: &str, number: u32, sha: &str, merge_method: &str) -> Result<()>;
+    fn update_pull_request_branch(&self, owner: &str, repo: &str, number: u32, head_sha: &str) -> Result<()>;
+
+    // Open PRs from the given branch whose head is one of the given commits. Looks through all open PRs for the
+    // commits by default; sessions that can ask for just the branch's PRs should.
+    fn get_open_pull_requests_for_head(
+        &self,
+        owner: &str,
+        repo: &str,
+        _branch: &str,
+        shas: &[&str],
+    ) -> Result<Vec<PullRequest>> {
+        let prs = self.get_pull_requests(owner, repo, Some("open"), None)?;
+        Ok(prs.into_iter().filter(|pr| shas.contains(&pr.head.sha.as_str())).collect())
+    }
+
+    // whether changes to the repo should only be recorded instead of made, e.g. for the git pushes that
+    // don't go through the session
+    fn dry_run(&self, _owner: &str, _repo: &str) -> bool {
+        false
+    }
+
+    fn clone_url(&self, owner: &str, repo: &str) -> String {
+        format!("https://{}@{}/{}/{}", self.user().login(), self.github_host(), owner, repo)
+    }
 }
 
+const PER_PAGE: u32 = 100;
+// github lists at most 3000 files for a PR anyway
+const DEFAULT_MAX_PAGES: u32 = 30;
+const MAX_CACHED_RESPONSES: usize = 1000;
+
 pub struct GithubSession {
     client: HTTPClient,
+    graphql_url: String,
     host: String,
     token: String,
     user: User,
+    max_pages: u32,
+}
+
+// Goes through a list API a page at a time by following github's Link headers, so that callers that only keep
+// some of the items don't need to hold on to all of them.
+pub struct Pages<'a, T> {
+    client: &'a HTTPClient,
+    next: Option<String>,
+    pages_left: u32,
+    item: PhantomData<T>,
+}
+
+impl<'a, T> Iterator for Pages<'a, T>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    type Item = Result<Vec<T>>;
+
+    fn next(&mut self) -> Option<Result<Vec<T>>> {
+        let path = self.next.take()?;
+        if self.pages_left == 0 {
+            warn!("Not fetching more pages after {}", path);
+            return None;
+        }
+        self.pages_left -= 1;
+
+        match self.client.get_page::<Vec<T>>(&path) {
+            Ok((items, next)) => {
+                self.next = next;
+                Some(Ok(items))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+// github.com has it at https://api.github.com/graphql, enterprise at https://<host>/api/graphql
+fn graphql_url(api_base: &str) -> String {
+    let api_base = api_base.trim_right_matches('/');
+    if api_base.ends_with("/v3") {
+        format!("{}/graphql", &api_base[..api_base.len() - "/v3".len()])
+    } else {
+        format!("{}/graphql", api_base)
+    }
+}
+
+const OPEN_PRS_FOR_BRANCH_QUERY: &'static str = "query($owner: String!, $repo: String!, $branch: String!) {
+  repository(owner: $owner, name: $repo) {
+    pullRequests(states: OPEN, headRefName: $branch, first: 100) {
+      nodes { number headRefOid }
+    }
+  }
+}";
+
+#[derive(Deserialize)]
+struct GraphQLError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct GraphQLResponse<T> {
+    data: Option<T>,
+    errors: Option<Vec<GraphQLError>>,
+}
+
+#[derive(Deserialize)]
+struct OpenPRsData {
+    repository: Option<OpenPRsRepository>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenPRsRepository {
+    pull_requests: OpenPRsConnection,
+}
+
+#[derive(Deserialize)]
+struct OpenPRsConnection {
+    nodes: Vec<OpenPRNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenPRNode {
+    number: u32,
+    head_ref_oid: String,
+}
+
+// the numbers of the PRs whose head is one of the given commits
+fn pull_requests_at(data: OpenPRsData, shas: &[&str]) -> Vec<u32> {
+    match data.repository {
+        Some(repository) => {
+            repository
+                .pull_requests
+                .nodes
+                .into_iter()
+                .filter(|n| shas.contains(&n.head_ref_oid.as_str()))
+                .map(|n| n.number)
+                .collect()
+        }
+        None => vec![],
+    }
+}
+
+fn with_per_page(path: &str) -> String {
+    let separator = if path.contains('?') { "&" } else { "?" };
+    format!("{}{}per_page={}", path, separator, PER_PAGE)
 }
 
 impl GithubSession {
-    pub fn new(core_remote: Remote, host: &str, token: &str) -> Result<GithubSession> {
-        let api_base = if host == "github.com" {
-            "https://api.github.com".to_string()
-        } else {
-            format!("https://{}/api/v3", host)
+    pub fn new(core_remote: Remote, config: &GithubConfig) -> Result<GithubSession> {
+        let host = config.host.as_str();
+        let token = config.api_token.as_str();
+        let api_base = match config.api_base {
+            Some(ref base) if !base.is_empty() => base.clone(),
+            _ if host == "github.com" => "https://api.github.com".to_string(),
+            _ => format!("https://{}/api/v3", host),
         };
 
         let client = HTTPClient::new(core_remote, &api_base).with_headers(hashmap!{
@@ -68,6 +258,8 @@ impl GithubSession {
                 "Content-Type" => "application/json".to_string(),
                 "Authorization" => format!("Token {}", token),
             });
+        let retry = config.retry.clone().unwrap_or_default();
+        let client = client.with_retries(RetryPolicy::new(&retry)).with_cache(ResponseCache::new(MAX_CACHED_RESPONSES));
 
         // make sure we can auth as this user befor handing out session.
         let user: User = client.get("/user").map_err(|e| {
@@ -76,11 +268,55 @@ impl GithubSession {
 
         Ok(GithubSession {
             client: client,
+            graphql_url: graphql_url(&api_base),
             user: user,
             host: host.to_string(),
             token: token.to_string(),
+            max_pages: config.max_pages.unwrap_or(DEFAULT_MAX_PAGES),
         })
     }
+
+    pub fn graphql<T>(&self, query: &str, variables: serde_json::Value) -> Result<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let body = json!({
+            "query": query,
+            "variables": variables,
+        });
+        let resp: GraphQLResponse<T> = self.client.post(&self.graphql_url, &body)?;
+        if let Some(errors) = resp.errors {
+            if !errors.is_empty() {
+                let messages: Vec<String> = errors.into_iter().map(|e| e.message).collect();
+                return Err(format!("GraphQL errors: {}", messages.join(", ")).into());
+            }
+        }
+        resp.data.ok_or_else(|| "GraphQL response has no data".into())
+    }
+
+    pub fn pages<T>(&self, path: &str) -> Pages<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        Pages {
+            client: &self.client,
+            next: Some(with_per_page(path)),
+            pages_left: self.max_pages,
+            item: PhantomData,
+        }
+    }
+
+    // all the items of a list API, up to `max_pages` pages of them
+    pub fn get_all<T>(&self, path: &str) -> Result<Vec<T>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let mut all = vec![];
+        for page in self.pages(path) {
+            all.extend(page?);
+        }
+        Ok(all)
+    }
 }
 
 impl Session for GithubSession {
@@ -109,20 +345,43 @@ impl Session for GithubSession {
         state: Option<&str>,
         head: Option<&str>,
     ) -> Result<Vec<PullRequest>> {
-        self.client
-            .get::<Vec<PullRequest>>(
-                &format!("repos/{}/{}/pulls?state={}&head={}", owner, repo, state.unwrap_or(""), head.unwrap_or("")),
-            )
-            .map_err(|e| format!("Error looking up PRs: {}/{}: {}", owner, repo, e).into())
-            .map(|prs| {
-                prs.into_iter()
-                    .filter(|p| if let Some(head) = head {
-                        p.head.ref_name == head || p.head.sha == head
-                    } else {
-                        true
-                    })
-                    .collect::<Vec<_>>()
-            })
+        let path = format!("repos/{}/{}/pulls?state={}&head={}", owner, repo, state.unwrap_or(""), head.unwrap_or(""));
+        let mut prs = vec![];
+        for page in self.pages::<PullRequest>(&path) {
+            let page = page.map_err(|e| Error::from(format!("Error looking up PRs: {}/{}: {}", owner, repo, e)))?;
+            prs.extend(page.into_iter().filter(|p| if let Some(head) = head {
+                p.head.ref_name == head || p.head.sha == head
+            } else {
+                true
+            }));
+        }
+        Ok(prs)
+    }
+
+    // One query for the branch's open PRs rather than paging through all of them, then only the matching ones
+    // are looked up in full. Falls back to REST if the query fails, e.g. on older enterprise hosts.
+    fn get_open_pull_requests_for_head(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        shas: &[&str],
+    ) -> Result<Vec<PullRequest>> {
+        let variables = json!({
+            "owner": owner,
+            "repo": repo,
+            "branch": branch,
+        });
+        let numbers = match self.graphql::<OpenPRsData>(OPEN_PRS_FOR_BRANCH_QUERY, variables) {
+            Ok(data) => pull_requests_at(data, shas),
+            Err(e) => {
+                warn!("Error looking up PRs for {}/{} {} with GraphQL, using REST: {}", owner, repo, branch, e);
+                let prs = self.get_pull_requests(owner, repo, Some("open"), None)?;
+                return Ok(prs.into_iter().filter(|pr| shas.contains(&pr.head.sha.as_str())).collect());
+            }
+        };
+
+        numbers.into_iter().map(|n| self.get_pull_request(owner, repo, n)).collect()
     }
 
     fn create_pull_request(
@@ -152,29 +411,86 @@ impl Session for GithubSession {
     }
 
     fn get_pull_request_labels(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<Label>> {
-        self.client.get(&format!("repos/{}/{}/issues/{}/labels", owner, repo, number)).map_err(
+        self.get_all(&format!("repos/{}/{}/issues/{}/labels", owner, repo, number)).map_err(
             |e| {
                 format!("error looking up pr labels: {}/{} #{}: {}", owner, repo, number, e).into()
             },
         )
     }
 
+    fn add_pull_request_labels(&self, owner: &str, repo: &str, number: u32, labels: Vec<String>) -> Result<()> {
+        #[derive(Serialize)]
+        struct AddLabels {
+            labels: Vec<String>,
+        }
+
+        self.client
+            .post_void(&format!("repos/{}/{}/issues/{}/labels", owner, repo, number), &AddLabels { labels: labels })
+            .map_err(|e| format!("Error adding PR labels: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
+    fn remove_pull_request_label(&self, owner: &str, repo: &str, number: u32, label: &str) -> Result<()> {
+        let label_path: String = utf8_percent_encode(label, PATH_SEGMENT_ENCODE_SET).collect();
+        self.client
+            .delete_void(&format!("repos/{}/{}/issues/{}/labels/{}", owner, repo, number, label_path))
+            .map_err(|e| format!("Error removing PR label {}: {}/{} #{}: {}", label, owner, repo, number, e).into())
+    }
+
+    fn set_pull_request_milestone(&self, owner: &str, repo: &str, number: u32, milestone: &str) -> Result<()> {
+        #[derive(Deserialize)]
+        struct NumberedMilestone {
+            number: u32,
+            title: String,
+        }
+
+        let milestones: Vec<NumberedMilestone> = self.get_all(&format!("repos/{}/{}/milestones?state=all", owner, repo))
+            .map_err(|e| Error::from(format!("Error looking up milestones: {}/{}: {}", owner, repo, e)))?;
+        let found = match milestones.iter().find(|m| m.title == milestone) {
+            Some(m) => m.number,
+            None => return Err(format!("No milestone '{}' in {}/{}", milestone, owner, repo).into()),
+        };
+
+        self.client
+            .patch_void(&format!("repos/{}/{}/issues/{}", owner, repo, number), &hashmap!{ "milestone" => found })
+            .map_err(|e| format!("Error setting PR milestone: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
     fn get_pull_request_commits(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<Commit>> {
-        self.client.get(&format!("repos/{}/{}/pulls/{}/commits", owner, repo, number)).map_err(
+        self.get_all(&format!("repos/{}/{}/pulls/{}/commits", owner, repo, number)).map_err(
             |e| {
                 format!("Error looking up PR commits: {}/{} #{}: {}", owner, repo, number, e).into()
             },
         )
     }
 
+    fn get_pull_request_files(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<PullRequestFile>> {
+        self.get_all(&format!("repos/{}/{}/pulls/{}/files", owner, repo, number)).map_err(|e| {
+            format!("Error looking up PR files: {}/{} #{}: {}", owner, repo, number, e).into()
+        })
+    }
+
+    fn get_pull_request_stats(&self, owner: &str, repo: &str, number: u32) -> Result<DiffStats> {
+        // the full pull request has the totals, the list API doesn't
+        self.client.get(&format!("repos/{}/{}/pulls/{}", owner, repo, number)).map_err(|e| {
+            format!("Error looking up PR stats: {}/{} #{}: {}", owner, repo, number, e).into()
+        })
+    }
+
     fn get_pull_request_reviews(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<Review>> {
-        self.client.get(&format!("repos/{}/{}/pulls/{}/reviews", owner, repo, number)).map_err(
+        self.get_all(&format!("repos/{}/{}/pulls/{}/reviews", owner, repo, number)).map_err(
             |e| {
                 format!("Error looking up PR reviews: {}/{} #{}: {}", owner, repo, number, e).into()
             },
         )
     }
 
+    fn get_review_comments(&self, owner: &str, repo: &str, number: u32, review_id: u64) -> Result<Vec<Comment>> {
+        self.get_all(&format!("repos/{}/{}/pulls/{}/reviews/{}/comments", owner, repo, number, review_id))
+            .map_err(|e| {
+                format!("Error looking up review comments: {}/{} #{}: {}", owner, repo, number, e).into()
+            })
+    }
+
     fn assign_pull_request(
         &self,
         owner: &str,
@@ -194,6 +510,33 @@ impl Session for GithubSession {
             .map_err(|e| format!("Error assigning PR: {}/{} #{}: {}", owner, repo, number, e).into())
     }
 
+    fn request_review(&self, owner: &str, repo: &str, number: u32, reviewers: Vec<String>) -> Result<()> {
+        #[derive(Serialize)]
+        struct RequestReview {
+            reviewers: Vec<String>,
+        }
+
+        let body = RequestReview { reviewers: reviewers };
+
+        self.client
+            .post_void(&format!("repos/{}/{}/pulls/{}/requested_reviewers", owner, repo, number), &body)
+            .map_err(|e| format!("Error requesting review: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
+    fn dismiss_review(&self, owner: &str, repo: &str, number: u32, review_id: u64, message: &str) -> Result<()> {
+        #[derive(Serialize)]
+        struct DismissReview<'a> {
+            message: &'a str,
+        }
+
+        self.client
+            .put_void(
+                &format!("repos/{}/{}/pulls/{}/reviews/{}/dismissals", owner, repo, number, review_id),
+                &DismissReview { message: message },
+            )
+            .map_err(|e| format!("Error dismissing review: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
     fn comment_pull_request(&self, owner: &str, repo: &str, number: u32, comment: &str) -> Result<()> {
         #[derive(Serialize)]
         struct CommentPR {
@@ -230,9 +573,49 @@ impl Session for GithubSession {
             .map_err(|e| format!("Error deleting branch {}/{} {}: {}", owner, repo, branch_name, e).into())
     }
 
-    fn get_statuses(&self, owner: &str, repo: &str, ref_name: &str) -> Result<Vec<Status>> {
+    fn get_file_contents(&self, owner: &str, repo: &str, path: &str, ref_name: &str) -> Result<String> {
+        #[derive(Deserialize)]
+        struct FileContents {
+            content: String,
+        }
+
+        let contents: FileContents = self.client
+            .get(&contents_path(owner, repo, path, ref_name))
+            .map_err(|e| Error::from(format!("Error getting {} from {}/{} {}: {}", path, owner, repo, ref_name, e)))?;
+
+        // github wraps the base64 content in newlines
+        let content: String = contents.content.split_whitespace().collect();
+        let bytes = base64::decode(&content).map_err(|e| Error::from(format!("Error decoding {}: {}", path, e)))?;
+
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn get_user(&self, login: &str) -> Result<User> {
+        self.client
+            .get(&format!("users/{}", login))
+            .map_err(|e| format!("Error getting user {}: {}", login, e).into())
+    }
+
+    fn get_user_permission(&self, owner: &str, repo: &str, login: &str) -> Result<String> {
+        #[derive(Deserialize)]
+        struct Permission {
+            permission: String,
+        }
+
         self.client
-            .get(&format!("repos/{}/{}/commits/{}/statuses", owner, repo, ref_name))
+            .get::<Permission>(&format!("repos/{}/{}/collaborators/{}/permission", owner, repo, login))
+            .map(|p| p.permission)
+            .map_err(|e| format!("Error getting permission of {} on {}/{}: {}", login, owner, repo, e).into())
+    }
+
+    fn get_team_members(&self, org: &str, team: &str) -> Result<Vec<User>> {
+        self.get_all(&format!("orgs/{}/teams/{}/members", org, team)).map_err(|e| {
+            format!("Error looking up members of {}/{}: {}", org, team, e).into()
+        })
+    }
+
+    fn get_statuses(&self, owner: &str, repo: &str, ref_name: &str) -> Result<Vec<Status>> {
+        self.get_all(&format!("repos/{}/{}/commits/{}/statuses", owner, repo, ref_name))
             .map_err(|e| format!("Error getting statuses {}/{} {}: {}", owner, repo, ref_name, e).into())
     }
 
@@ -241,4 +624,213 @@ impl Session for GithubSession {
             .post_void(&format!("repos/{}/{}/commits/{}/statuses", owner, repo, ref_name), status)
             .map_err(|e| format!("Error creating status {}/{} {}: {}", owner, repo, ref_name, e).into())
     }
+
+    fn get_combined_status(&self, owner: &str, repo: &str, ref_name: &str) -> Result<CombinedStatus> {
+        self.client
+            .get(&format!("repos/{}/{}/commits/{}/status", owner, repo, ref_name))
+            .map_err(|e| format!("Error getting combined status {}/{} {}: {}", owner, repo, ref_name, e).into())
+    }
+
+    fn get_commit(&self, owner: &str, repo: &str, sha: &str) -> Result<Commit> {
+        self.client.get(&format!("repos/{}/{}/commits/{}", owner, repo, sha)).map_err(|e| {
+            format!("Error looking up commit {}/{} {}: {}", owner, repo, sha, e).into()
+        })
+    }
+
+    fn compare_commits(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<Comparison> {
+        self.client
+            .get(&format!("repos/{}/{}/compare/{}...{}", owner, repo, base, head))
+            .map_err(|e| format!("Error comparing {}/{} {}...{}: {}", owner, repo, base, head, e).into())
+    }
+
+    // github lists at most 250 of them
+    fn get_commits_between(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct CommitSha {
+            sha: String,
+        }
+        #[derive(Deserialize)]
+        struct Compare {
+            commits: Vec<CommitSha>,
+        }
+
+        let compare: Compare = self.client
+            .get(&format!("repos/{}/{}/compare/{}...{}", owner, repo, base, head))
+            .map_err(|e| Error::from(format!("Error comparing {}/{} {}...{}: {}", owner, repo, base, head, e)))?;
+        Ok(compare.commits.into_iter().map(|c| c.sha).collect())
+    }
+
+    fn create_release(&self, owner: &str, repo: &str, tag: &str, name: &str, body: &str) -> Result<()> {
+        self.client
+            .post_void(
+                &format!("repos/{}/{}/releases", owner, repo),
+                &hashmap!{ "tag_name" => tag, "name" => name, "body" => body },
+            )
+            .map_err(|e| format!("Error creating release {}/{} {}: {}", owner, repo, tag, e).into())
+    }
+
+    fn get_tags(&self, owner: &str, repo: &str) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Tag {
+            name: String,
+        }
+
+        let tags: Vec<Tag> = self.get_all(&format!("repos/{}/{}/tags", owner, repo))
+            .map_err(|e| Error::from(format!("Error looking up tags: {}/{}: {}", owner, repo, e)))?;
+        Ok(tags.into_iter().map(|t| t.name).collect())
+    }
+
+    fn is_branch_protected(&self, owner: &str, repo: &str, branch: &str) -> Result<bool> {
+        #[derive(Deserialize)]
+        struct Branch {
+            protected: bool,
+        }
+
+        let branch: Branch = self.client
+            .get(&format!("repos/{}/{}/branches/{}", owner, repo, branch))
+            .map_err(|e| Error::from(format!("Error looking up branch {}/{} {}: {}", owner, repo, branch, e)))?;
+        Ok(branch.protected)
+    }
+
+    fn protect_branch(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        required_checks: &Vec<String>,
+        required_approvals: u32,
+    ) -> Result<()> {
+        // github wants all of these, with null for the ones that are off
+        let checks = if required_checks.is_empty() {
+            json!(null)
+        } else {
+            json!({ "strict": false, "contexts": required_checks })
+        };
+        let reviews = if required_approvals == 0 {
+            json!(null)
+        } else {
+            json!({ "required_approving_review_count": required_approvals })
+        };
+        let body = json!({
+            "required_status_checks": checks,
+            "enforce_admins": false,
+            "required_pull_request_reviews": reviews,
+            "restrictions": null,
+        });
+
+        self.client
+            .put_void(&format!("repos/{}/{}/branches/{}/protection", owner, repo, branch), &body)
+            .map_err(|e| format!("Error protecting branch {}/{} {}: {}", owner, repo, branch, e).into())
+    }
+
+    fn rerun_failed_jobs(&self, owner: &str, repo: &str, run_id: u64) -> Result<()> {
+        self.client
+            .post_void(&format!("repos/{}/{}/actions/runs/{}/rerun-failed-jobs", owner, repo, run_id), &json!({}))
+            .map_err(|e| format!("Error re-running workflow run {}/{} {}: {}", owner, repo, run_id, e).into())
+    }
+
+    fn get_pull_requests_for_commit(&self, owner: &str, repo: &str, sha: &str) -> Result<Vec<PullRequest>> {
+        self.get_all(&format!("repos/{}/{}/commits/{}/pulls", owner, repo, sha))
+            .map_err(|e| format!("Error looking up PRs for commit {}/{} {}: {}", owner, repo, sha, e).into())
+    }
+
+    fn get_hooks(&self, owner: &str, repo: &str) -> Result<Vec<Hook>> {
+        self.get_all(&format!("repos/{}/{}/hooks", owner, repo))
+            .map_err(|e| format!("Error looking up hooks for {}/{}: {}", owner, repo, e).into())
+    }
+
+    fn save_hook(&self, owner: &str, repo: &str, hook: &Hook) -> Result<()> {
+        let res = match hook.id {
+            Some(id) => self.client.patch_void(&format!("repos/{}/{}/hooks/{}", owner, repo, id), hook),
+            None => self.client.post_void(&format!("repos/{}/{}/hooks", owner, repo), hook),
+        };
+        res.map_err(|e| format!("Error saving hook for {}/{}: {}", owner, repo, e).into())
+    }
+
+    fn merge_pull_request(&self, owner: &str, repo: &str, number: u32, sha: &str, merge_method: &str) -> Result<()> {
+        #[derive(Serialize)]
+        struct MergePR {
+            sha: String,
+            merge_method: String,
+        }
+
+        let body = MergePR {
+            sha: sha.to_string(),
+            merge_method: merge_method.to_string(),
+        };
+
+        self.client
+            .put_void(&format!("repos/{}/{}/pulls/{}/merge", owner, repo, number), &body)
+            .map_err(|e| format!("Error merging PR: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
+    fn update_pull_request_branch(&self, owner: &str, repo: &str, number: u32, head_sha: &str) -> Result<()> {
+        #[derive(Serialize)]
+        struct UpdateBranch {
+            expected_head_sha: String,
+        }
+
+        let body = UpdateBranch { expected_head_sha: head_sha.to_string() };
+
+        self.client
+            .put_void(&format!("repos/{}/{}/pulls/{}/update-branch", owner, repo, number), &body)
+            .map_err(|e| format!("Error updating PR branch: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+}
+
+// file paths can have spaces and such, and refs can have '#', '&', ...
+fn contents_path(owner: &str, repo: &str, path: &str, ref_name: &str) -> String {
+    let path: Vec<String> =
+        path.split('/').map(|p| utf8_percent_encode(p, PATH_SEGMENT_ENCODE_SET).collect()).collect();
+    let ref_name: String = form_urlencoded::byte_serialize(ref_name.as_bytes()).collect();
+    format!("repos/{}/{}/contents/{}?ref={}", owner, repo, path.join("/"), ref_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contents_path() {
+        assert_eq!(
+            "repos/some-org/some-repo/contents/.github/CODEOWNERS?ref=master",
+            contents_path("some-org", "some-repo", ".github/CODEOWNERS", "master")
+        );
+        assert_eq!(
+            "repos/some-org/some-repo/contents/docs/my%20file%23.md?ref=feature%2Fa%26b",
+            contents_path("some-org", "some-repo", "docs/my file#.md", "feature/a&b")
+        );
+    }
+
+    #[test]
+    fn test_with_per_page() {
+        assert_eq!("repos/o/r/pulls/1/files?per_page=100", with_per_page("repos/o/r/pulls/1/files"));
+        assert_eq!("repos/o/r/pulls?state=open&head=&per_page=100", with_per_page("repos/o/r/pulls?state=open&head="));
+    }
+
+    #[test]
+    fn test_graphql_url() {
+        assert_eq!("https://api.github.com/graphql", graphql_url("https://api.github.com"));
+        assert_eq!("https://git.company.com/api/graphql", graphql_url("https://git.company.com/api/v3"));
+        assert_eq!("https://git.company.com/api/graphql", graphql_url("https://git.company.com/api/v3/"));
+    }
+
+    #[test]
+    fn test_pull_requests_at() {
+        let data: OpenPRsData = serde_json::from_value(json!({
+            "repository": {
+                "pullRequests": {
+                    "nodes": [
+                        {"number": 1, "headRefOid": "abcdef"},
+                        {"number": 2, "headRefOid": "123456"},
+                        {"number": 3, "headRefOid": "fedcba"},
+                    ]
+                }
+            }
+        })).unwrap();
+        assert_eq!(vec![1, 3], pull_requests_at(data, &["abcdef", "fedcba"]));
+
+        let data: OpenPRsData = serde_json::from_value(json!({"repository": null})).unwrap();
+        assert_eq!(Vec::<u32>::new(), pull_requests_at(data, &["abcdef"]));
+    }
 }
@@ -14,6 +14,13 @@ pub struct HookBody {
     pub pull_request: Option<PullRequest>,
     pub review: Option<Review>,
     pub label: Option<Label>,
+    // the reviewer a "review_requested" or "review_request_removed" action is about. github sends one event
+    // per reviewer, and none of this for team review requests.
+    pub requested_reviewer: Option<User>,
+    // the user an issue's "assigned" or "unassigned" action is about
+    pub assignee: Option<User>,
+    // what an "edited" action changed
+    pub changes: Option<Changes>,
 
     // push event related stuff
     #[serde(rename = "ref")]
@@ -25,6 +32,26 @@ pub struct HookBody {
     pub deleted: Option<bool>,
     pub created: Option<bool>,
     pub commits: Option<Vec<PushCommit>>,
+
+    // create event related stuff: "tag" or "branch", with the tag or branch's name in `ref_name`
+    pub ref_type: Option<String>,
+    pub release: Option<Release>,
+
+    // deployment and deployment_status event related stuff
+    pub deployment: Option<Deployment>,
+    pub deployment_status: Option<DeploymentStatus>,
+
+    // workflow_run event related stuff
+    pub workflow_run: Option<WorkflowRun>,
+
+    // dependabot_alert, code_scanning_alert, and repository_vulnerability_alert event related stuff
+    pub alert: Option<SecurityAlert>,
+
+    // status event related stuff
+    pub sha: Option<String>,
+    pub state: Option<String>,
+    pub context: Option<String>,
+    pub target_url: Option<String>,
 }
 
 impl HookBody {
@@ -38,6 +65,9 @@ impl HookBody {
             pull_request: None,
             review: None,
             label: None,
+            requested_reviewer: None,
+            assignee: None,
+            changes: None,
             ref_name: None,
             after: None,
             before: None,
@@ -46,6 +76,16 @@ impl HookBody {
             deleted: None,
             created: None,
             commits: None,
+            ref_type: None,
+            release: None,
+            deployment: None,
+            deployment_status: None,
+            workflow_run: None,
+            alert: None,
+            sha: None,
+            state: None,
+            context: None,
+            target_url: None,
         }
     }
 
@@ -88,6 +128,8 @@ impl HookBody {
 pub struct User {
     pub login: Option<String>,
     pub name: Option<String>,
+    // only set when the user was fetched on its own, and only if it's public
+    pub email: Option<String>,
 }
 
 impl User {
@@ -95,6 +137,7 @@ impl User {
         User {
             login: Some(login.to_string()),
             name: Some(login.to_string()),
+            email: None,
         }
     }
 
@@ -121,6 +164,8 @@ pub struct Repo {
     pub full_name: String,
     pub name: String,
     pub owner: User,
+    // only set on repos that come with webhook events
+    pub default_branch: Option<String>,
 }
 
 impl Repo {
@@ -130,6 +175,7 @@ impl Repo {
             full_name: String::new(),
             name: String::new(),
             owner: User::new(""),
+            default_branch: None,
         }
     }
 
@@ -151,8 +197,15 @@ impl Repo {
             full_name: format!("{}/{}", user, repo),
             name: repo.to_string(),
             owner: User::new(user),
+            default_branch: None,
         })
     }
+
+    // another repo on the same host, by its full name
+    pub fn sibling(&self, full_name: &str) -> Result<Repo> {
+        let url = Url::parse(&self.html_url)?.join(&format!("/{}", full_name))?;
+        Repo::parse(url.as_str())
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -198,6 +251,17 @@ pub struct PullRequest {
     pub base: BranchRef,
     pub requested_reviewers: Option<Vec<User>>,
     pub reviews: Option<Vec<Review>>,
+    pub mergeable: Option<bool>,
+    pub mergeable_state: Option<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub draft: Option<bool>,
+    // only in pull_request events and single PR lookups, not in lists of PRs
+    pub labels: Option<Vec<Label>>,
+    pub milestone: Option<Milestone>,
+    pub additions: Option<u32>,
+    pub deletions: Option<u32>,
+    pub changed_files: Option<u32>,
 }
 
 impl PullRequest {
@@ -214,6 +278,16 @@ impl PullRequest {
             assignees: vec![],
             requested_reviewers: None,
             reviews: None,
+            mergeable: None,
+            mergeable_state: None,
+            created_at: None,
+            updated_at: None,
+            draft: None,
+            labels: None,
+            milestone: None,
+            additions: None,
+            deletions: None,
+            changed_files: None,
             head: BranchRef::new(""),
             base: BranchRef::new(""),
         }
@@ -223,9 +297,28 @@ impl PullRequest {
         self.merged.unwrap_or(false)
     }
 
+    pub fn diff_stats(&self) -> Option<DiffStats> {
+        match (self.additions, self.deletions, self.changed_files) {
+            (Some(additions), Some(deletions), Some(changed_files)) => Some(DiffStats {
+                additions: additions,
+                deletions: deletions,
+                changed_files: changed_files,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state == "open"
+    }
+
     pub fn is_wip(&self) -> bool {
         self.title.to_lowercase().starts_with("wip:")
     }
+
+    pub fn is_draft(&self) -> bool {
+        self.draft.unwrap_or(false)
+    }
 }
 
 impl<'a> PullRequestLike for &'a PullRequest {
@@ -262,8 +355,11 @@ pub struct Issue {
     pub number: u32,
     pub html_url: String,
     pub title: String,
+    pub body: Option<String>,
     pub user: User,
     pub assignees: Vec<User>,
+    #[serde(default)]
+    pub labels: Vec<Label>,
 }
 
 impl<'a> PullRequestLike for &'a Issue {
@@ -288,6 +384,216 @@ impl<'a> PullRequestLike for &'a Issue {
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct Changes {
+    pub title: Option<ChangedFrom>,
+    // the old name of a renamed repository
+    pub repository: Option<RepositoryChanges>,
+    // the old owner of a transferred repository
+    pub owner: Option<OwnerChanges>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ChangedFrom {
+    pub from: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct RepositoryChanges {
+    pub name: Option<ChangedFrom>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct OwnerChanges {
+    pub from: OwnerFrom,
+}
+
+// either of them, depending on whether the repository belonged to a user or to an organization
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct OwnerFrom {
+    pub user: Option<User>,
+    pub organization: Option<User>,
+}
+
+impl Changes {
+    // the full name `repo` had before it was renamed or transferred, if it was
+    pub fn previous_repo_name(&self, repo: &Repo) -> Option<String> {
+        if let Some(RepositoryChanges { name: Some(ref name) }) = self.repository {
+            return Some(format!("{}/{}", repo.owner.login(), name.from));
+        }
+        match self.owner {
+            Some(OwnerChanges { from: OwnerFrom { user: Some(ref owner), .. } }) |
+            Some(OwnerChanges { from: OwnerFrom { organization: Some(ref owner), .. } }) => {
+                Some(format!("{}/{}", owner.login(), repo.name))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Release {
+    pub tag_name: String,
+    pub name: Option<String>,
+    pub body: Option<String>,
+    pub html_url: String,
+    pub prerelease: bool,
+    pub author: User,
+}
+
+impl Release {
+    // the tag name for releases without a name of their own
+    pub fn name(&self) -> &str {
+        match self.name {
+            Some(ref n) if !n.trim().is_empty() => n,
+            _ => &self.tag_name,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Deployment {
+    pub sha: String,
+    #[serde(rename = "ref")]
+    pub ref_name: String,
+    pub environment: String,
+    pub description: Option<String>,
+    pub creator: User,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DeploymentStatus {
+    // "pending", "queued", "in_progress", "success", "failure", "error", or "inactive"
+    pub state: String,
+    pub description: Option<String>,
+    // the deployment's logs
+    pub target_url: Option<String>,
+    // where the deployed app can be seen
+    pub environment_url: Option<String>,
+}
+
+// A repo's webhook, as listed and as sent to create or change one
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Hook {
+    // only set on existing hooks
+    #[serde(skip_serializing)]
+    pub id: Option<u64>,
+    // always "web"
+    pub name: String,
+    pub active: bool,
+    pub events: Vec<String>,
+    pub config: HookConfig,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct HookConfig {
+    pub url: String,
+    pub content_type: Option<String>,
+    // github only shows it masked, so it's left out of the hooks it lists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct WorkflowRun {
+    pub id: u64,
+    // the workflow's name
+    pub name: String,
+    pub run_number: u32,
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    // "success", "failure", "timed_out", "cancelled", etc. once the run has completed
+    pub conclusion: Option<String>,
+    pub html_url: String,
+    // who triggered the run, e.g. by pushing
+    pub actor: Option<User>,
+}
+
+// The alerts of the three kinds of security events in one: dependabot alerts have an advisory and a dependency, code
+// scanning alerts a rule and a tool, and the older vulnerability alerts just the package and severity.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct SecurityAlert {
+    pub html_url: Option<String>,
+    pub security_advisory: Option<SecurityAdvisory>,
+    pub dependency: Option<AlertDependency>,
+    pub rule: Option<AlertRule>,
+    pub tool: Option<AlertTool>,
+    pub affected_package_name: Option<String>,
+    pub severity: Option<String>,
+    pub external_identifier: Option<String>,
+    pub external_reference: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SecurityAdvisory {
+    pub ghsa_id: String,
+    pub summary: String,
+    pub severity: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AlertDependency {
+    pub package: AlertPackage,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AlertPackage {
+    pub name: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AlertRule {
+    pub id: String,
+    pub description: Option<String>,
+    // "error", "warning", or "note"
+    pub severity: Option<String>,
+    // "critical", "high", "medium", or "low" for security rules
+    pub security_severity_level: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AlertTool {
+    pub name: String,
+}
+
+impl SecurityAlert {
+    pub fn summary(&self) -> String {
+        if let Some(ref advisory) = self.security_advisory {
+            return advisory.summary.clone();
+        }
+        if let Some(ref rule) = self.rule {
+            return rule.description.clone().unwrap_or(rule.id.clone());
+        }
+        match (&self.affected_package_name, &self.external_identifier) {
+            (&Some(ref package), &Some(ref id)) => format!("{} in {}", id, package),
+            (&Some(ref package), &None) => format!("Vulnerability in {}", package),
+            _ => "Security alert".into(),
+        }
+    }
+
+    pub fn severity(&self) -> Option<&str> {
+        if let Some(ref advisory) = self.security_advisory {
+            return Some(&advisory.severity);
+        }
+        if let Some(ref rule) = self.rule {
+            return rule.security_severity_level.as_ref().or(rule.severity.as_ref()).map(|s| s.as_str());
+        }
+        self.severity.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn package(&self) -> Option<&str> {
+        match self.dependency {
+            Some(ref d) => Some(&d.package.name),
+            None => self.affected_package_name.as_ref().map(|p| p.as_str()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Milestone {
+    pub title: String,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Label {
     pub name: String,
@@ -304,11 +610,22 @@ pub trait CommentLike {
     fn user(&self) -> &User;
     fn body(&self) -> &str;
     fn html_url(&self) -> &str;
+
+    // the file and line an inline comment is on, e.g. "src/main.rs:12"
+    fn location(&self) -> Option<String> {
+        None
+    }
+
+    // the diff leading up to that line
+    fn diff_hunk(&self) -> Option<&str> {
+        None
+    }
 }
 
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Review {
+    pub id: Option<u64>,
     pub state: String,
     pub body: Option<String>,
     pub html_url: String,
@@ -318,6 +635,7 @@ pub struct Review {
 impl Review {
     pub fn new(body: &str, user: User) -> Review {
         Review {
+            id: None,
             state: "COMMENTED".into(),
             body: Some(body.into()),
             html_url: String::new(),
@@ -351,6 +669,14 @@ pub struct Comment {
     pub body: Option<String>,
     pub html_url: String,
     pub user: User,
+    // set on inline PR comments, which are all part of some review
+    #[serde(default)]
+    pub pull_request_review_id: Option<u64>,
+    // github cuts inline comments' diff hunks off at the line they're on
+    #[serde(default)]
+    pub diff_hunk: Option<String>,
+    #[serde(default)]
+    pub line: Option<u32>,
 }
 
 impl<'a> CommentLike for &'a Comment {
@@ -368,6 +694,17 @@ impl<'a> CommentLike for &'a Comment {
     fn html_url(&self) -> &str {
         &self.html_url
     }
+
+    fn location(&self) -> Option<String> {
+        match (&self.path, self.line) {
+            (&Some(ref path), Some(line)) => Some(format!("{}:{}", path, line)),
+            _ => None,
+        }
+    }
+
+    fn diff_hunk(&self) -> Option<&str> {
+        self.diff_hunk.as_ref().map(|h| h.as_str())
+    }
 }
 
 pub trait CommitLike {
@@ -509,9 +846,72 @@ pub struct Status {
     pub creator: Option<User>,
 }
 
+// How two commits' histories differ: `ahead_by` commits are only in the head, `behind_by` only in the base.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub ahead_by: u32,
+    pub behind_by: u32,
+}
+
+// How big a pull request's change is
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct DiffStats {
+    pub additions: u32,
+    pub deletions: u32,
+    pub changed_files: u32,
+}
+
+impl DiffStats {
+    pub fn from_files(files: &Vec<PullRequestFile>) -> DiffStats {
+        DiffStats {
+            additions: files.iter().map(|f| f.additions).sum(),
+            deletions: files.iter().map(|f| f.deletions).sum(),
+            changed_files: files.len() as u32,
+        }
+    }
+
+    pub fn changed_lines(&self) -> u32 {
+        self.additions + self.deletions
+    }
+
+    // e.g. "+120 −40, 7 files"
+    pub fn describe(&self) -> String {
+        let files = if self.changed_files == 1 { "file" } else { "files" };
+        format!("+{} −{}, {} {}", self.additions, self.deletions, self.changed_files, files)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CombinedStatus {
+    pub state: String,
+    pub sha: String,
+    pub statuses: Vec<Status>,
+}
+
+impl CombinedStatus {
+    // note: github reports "pending" when there are no statuses at all
+    pub fn is_success(&self) -> bool {
+        self.state == "success" || self.statuses.is_empty()
+    }
+
+    pub fn is_failure(&self) -> bool {
+        self.state == "failure" || self.state == "error"
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PullRequestFile {
+    pub filename: String,
+    pub status: String,
+    pub additions: u32,
+    pub deletions: u32,
+    pub changes: u32,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json;
 
     #[test]
     fn test_repo_parse() {
@@ -541,6 +941,16 @@ mod tests {
         Repo::parse("http://git.company.com/users/repo/huh").unwrap();
     }
 
+    #[test]
+    fn test_repo_sibling() {
+        let repo = Repo::parse("https://git.company.com/users/repo").unwrap();
+        let sibling = repo.sibling("other-org/fork").unwrap();
+
+        assert_eq!("https://git.company.com/other-org/fork", sibling.html_url);
+        assert_eq!("other-org/fork", sibling.full_name);
+        assert!(repo.sibling("no-repo").is_err());
+    }
+
     #[test]
     fn test_hook_body_funcs() {
         // test defaults
@@ -675,4 +1085,22 @@ mod tests {
         pr.title = "Wip: why would you even think about doing it this way?".into();
         assert!(pr.is_wip());
     }
+
+    #[test]
+    fn test_previous_repo_name() {
+        let repo = Repo::parse("http://git.company.com/new-org/new-name").unwrap();
+
+        let renamed: Changes = serde_json::from_str(r#"{"repository": {"name": {"from": "old-name"}}}"#).unwrap();
+        assert_eq!(Some("new-org/old-name".into()), renamed.previous_repo_name(&repo));
+
+        let transferred: Changes =
+            serde_json::from_str(r#"{"owner": {"from": {"organization": {"login": "old-org"}}}}"#).unwrap();
+        assert_eq!(Some("old-org/new-name".into()), transferred.previous_repo_name(&repo));
+
+        let from_user: Changes =
+            serde_json::from_str(r#"{"owner": {"from": {"user": {"login": "someone"}}}}"#).unwrap();
+        assert_eq!(Some("someone/new-name".into()), from_user.previous_repo_name(&repo));
+
+        assert_eq!(None, Changes::default().previous_repo_name(&repo));
+    }
 }
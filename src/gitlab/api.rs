@@ -0,0 +1,874 @@
+use std::collections::HashMap;
+
+use base64;
+use hyper::StatusCode;
+use tokio_core::reactor::Remote;
+use url::form_urlencoded;
+
+use config::GitlabConfig;
+use errors::*;
+use github;
+use github::api::Session;
+use gitlab::models::*;
+use http_client::HTTPClient;
+
+// A github session backed by the gitlab API, so that gitlab merge requests can be handled by the
+// same code as github pull requests.
+pub struct GitlabSession {
+    client: HTTPClient,
+    host: String,
+    token: String,
+    user: github::User,
+}
+
+impl GitlabSession {
+    pub fn new(core_remote: Remote, config: &GitlabConfig) -> Result<GitlabSession> {
+        let api_base = match config.api_base {
+            Some(ref base) if !base.is_empty() => base.clone(),
+            _ => format!("https://{}/api/v4", config.host),
+        };
+
+        let client = HTTPClient::new(core_remote, &api_base).with_headers(hashmap!{
+                "Accept" => "application/json".to_string(),
+                "Content-Type" => "application/json".to_string(),
+                "PRIVATE-TOKEN" => config.api_token.clone(),
+            });
+
+        // make sure we can auth as this user before handing out session.
+        let user: User = client.get("/user").map_err(|e| {
+            Error::from(format!("Error authenticating to gitlab with token: {}", e))
+        })?;
+
+        Ok(GitlabSession {
+            client: client,
+            user: user.to_github(),
+            host: config.host.clone(),
+            token: config.api_token.clone(),
+        })
+    }
+
+    fn repo(&self, owner: &str, repo: &str) -> github::Repo {
+        let project = Project {
+            path_with_namespace: format!("{}/{}", owner, repo),
+            web_url: format!("https://{}/{}/{}", self.host, owner, repo),
+        };
+        project.to_github()
+    }
+
+    fn get_merge_request(&self, owner: &str, repo: &str, number: u32) -> Result<MergeRequest> {
+        self.client
+            .get(&format!("projects/{}/merge_requests/{}", project_id(owner, repo), number))
+            .map_err(|e| format!("Error looking up MR: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
+    fn update_merge_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u32,
+        update: &HashMap<&str, Vec<u64>>,
+    ) -> Result<MergeRequest> {
+        self.client
+            .put(&format!("projects/{}/merge_requests/{}", project_id(owner, repo), number), update)
+            .map_err(|e| format!("Error updating MR: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
+    fn user_ids(&self, logins: &Vec<String>) -> Result<Vec<u64>> {
+        let mut ids = vec![];
+        for login in logins {
+            match self.find_user(login)?.id {
+                Some(id) => ids.push(id),
+                None => return Err(format!("No id for gitlab user {}", login).into()),
+            }
+        }
+        Ok(ids)
+    }
+
+    fn find_user(&self, login: &str) -> Result<User> {
+        let users: Vec<User> = self.client.get(&format!("users?username={}", encode(login))).map_err(|e| {
+            Error::from(format!("Error looking up gitlab user {}: {}", login, e))
+        })?;
+        users.into_iter().next().ok_or(format!("No such gitlab user: {}", login).into())
+    }
+}
+
+impl Session for GitlabSession {
+    fn user(&self) -> &github::User {
+        &self.user
+    }
+
+    fn github_host(&self) -> &str {
+        &self.host
+    }
+
+    fn github_token(&self) -> &str {
+        &self.token
+    }
+
+    fn get_pull_request(&self, owner: &str, repo: &str, number: u32) -> Result<github::PullRequest> {
+        Ok(self.get_merge_request(owner, repo, number)?.to_github(&self.repo(owner, repo)))
+    }
+
+    fn get_pull_requests(
+        &self,
+        owner: &str,
+        repo: &str,
+        state: Option<&str>,
+        head: Option<&str>,
+    ) -> Result<Vec<github::PullRequest>> {
+        let mut path = format!("projects/{}/merge_requests?per_page=100", project_id(owner, repo));
+        match state {
+            Some("open") => path += "&state=opened",
+            Some("closed") => path += "&state=closed",
+            _ => (),
+        };
+        if let Some(head) = head {
+            // github heads are "owner:branch"
+            let branch = head.splitn(2, ':').last().unwrap_or(head);
+            path += &format!("&source_branch={}", encode(branch));
+        }
+
+        let merge_requests: Vec<MergeRequest> = self.client.get(&path).map_err(|e| {
+            Error::from(format!("Error looking up MRs: {}/{}: {}", owner, repo, e))
+        })?;
+        let repo = self.repo(owner, repo);
+        Ok(merge_requests.iter().map(|m| m.to_github(&repo)).collect())
+    }
+
+    fn create_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        title: &str,
+        body: &str,
+        head: &str,
+        base: &str,
+    ) -> Result<github::PullRequest> {
+        #[derive(Serialize)]
+        struct CreateMergeRequest<'a> {
+            title: &'a str,
+            description: &'a str,
+            source_branch: &'a str,
+            target_branch: &'a str,
+        }
+
+        let create = CreateMergeRequest {
+            title: title,
+            description: body,
+            source_branch: head,
+            target_branch: base,
+        };
+        let merge_request: MergeRequest = self.client
+            .post(&format!("projects/{}/merge_requests", project_id(owner, repo)), &create)
+            .map_err(|e| Error::from(format!("Error creating MR: {}/{}: {}", owner, repo, e)))?;
+        Ok(merge_request.to_github(&self.repo(owner, repo)))
+    }
+
+    fn get_pull_request_labels(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<github::Label>> {
+        let labels = self.get_merge_request(owner, repo, number)?.labels.unwrap_or(vec![]);
+        Ok(labels.iter().map(|l| github::Label::new(l)).collect())
+    }
+
+    fn add_pull_request_labels(&self, owner: &str, repo: &str, number: u32, labels: Vec<String>) -> Result<()> {
+        self.client
+            .put_void(
+                &format!("projects/{}/merge_requests/{}", project_id(owner, repo), number),
+                &hashmap!{ "add_labels" => labels.join(",") },
+            )
+            .map_err(|e| format!("Error adding MR labels: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
+    fn remove_pull_request_label(&self, owner: &str, repo: &str, number: u32, label: &str) -> Result<()> {
+        self.client
+            .put_void(
+                &format!("projects/{}/merge_requests/{}", project_id(owner, repo), number),
+                &hashmap!{ "remove_labels" => label },
+            )
+            .map_err(|e| format!("Error removing MR label {}: {}/{} #{}: {}", label, owner, repo, number, e).into())
+    }
+
+    fn set_pull_request_milestone(&self, owner: &str, repo: &str, number: u32, milestone: &str) -> Result<()> {
+        #[derive(Deserialize)]
+        struct ProjectMilestone {
+            id: u64,
+            title: String,
+        }
+
+        let milestones: Vec<ProjectMilestone> = self.client
+            .get(&format!("projects/{}/milestones?title={}", project_id(owner, repo), encode(milestone)))
+            .map_err(|e| Error::from(format!("Error looking up milestones: {}/{}: {}", owner, repo, e)))?;
+        let found = match milestones.iter().find(|m| m.title == milestone) {
+            Some(m) => m.id,
+            None => return Err(format!("No milestone '{}' in {}/{}", milestone, owner, repo).into()),
+        };
+
+        self.client
+            .put_void(
+                &format!("projects/{}/merge_requests/{}", project_id(owner, repo), number),
+                &hashmap!{ "milestone_id" => found },
+            )
+            .map_err(|e| format!("Error setting MR milestone: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
+    fn get_pull_request_commits(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<github::Commit>> {
+        #[derive(Deserialize)]
+        struct MergeRequestCommit {
+            id: String,
+            message: String,
+            web_url: Option<String>,
+        }
+
+        let commits: Vec<MergeRequestCommit> = self.client
+            .get(&format!("projects/{}/merge_requests/{}/commits", project_id(owner, repo), number))
+            .map_err(|e| Error::from(format!("Error looking up MR commits: {}/{} #{}: {}", owner, repo, number, e)))?;
+
+        let repo = self.repo(owner, repo);
+        Ok(
+            commits
+                .into_iter()
+                .map(|c| {
+                    let mut commit = github::Commit::new();
+                    commit.html_url = c.web_url.unwrap_or(format!("{}/commit/{}", repo.html_url, c.id));
+                    commit.sha = c.id;
+                    commit.commit.message = c.message;
+                    commit
+                })
+                .collect(),
+        )
+    }
+
+    fn get_pull_request_files(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<github::PullRequestFile>> {
+        #[derive(Deserialize)]
+        struct Change {
+            new_path: String,
+            new_file: bool,
+            deleted_file: bool,
+            renamed_file: bool,
+            diff: String,
+        }
+        #[derive(Deserialize)]
+        struct Changes {
+            changes: Vec<Change>,
+        }
+
+        let changes: Changes = self.client
+            .get(&format!("projects/{}/merge_requests/{}/changes", project_id(owner, repo), number))
+            .map_err(|e| Error::from(format!("Error looking up MR files: {}/{} #{}: {}", owner, repo, number, e)))?;
+
+        Ok(
+            changes
+                .changes
+                .into_iter()
+                .map(|c| {
+                    let status = if c.new_file {
+                        "added"
+                    } else if c.deleted_file {
+                        "removed"
+                    } else if c.renamed_file {
+                        "renamed"
+                    } else {
+                        "modified"
+                    };
+                    let additions = c.diff.lines().filter(|l| l.starts_with('+') && !l.starts_with("+++")).count();
+                    let deletions = c.diff.lines().filter(|l| l.starts_with('-') && !l.starts_with("---")).count();
+                    github::PullRequestFile {
+                        filename: c.new_path,
+                        status: status.into(),
+                        additions: additions as u32,
+                        deletions: deletions as u32,
+                        changes: (additions + deletions) as u32,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn get_pull_request_stats(&self, owner: &str, repo: &str, number: u32) -> Result<github::DiffStats> {
+        Ok(github::DiffStats::from_files(&self.get_pull_request_files(owner, repo, number)?))
+    }
+
+    fn get_review_comments(&self, _: &str, _: &str, _: u32, _: u64) -> Result<Vec<github::Comment>> {
+        Err("Review comments are not supported on gitlab".into())
+    }
+
+    // approvals are the closest thing gitlab has to reviews
+    fn get_pull_request_reviews(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<github::Review>> {
+        #[derive(Deserialize)]
+        struct Approver {
+            user: User,
+        }
+        #[derive(Deserialize)]
+        struct Approvals {
+            approved_by: Vec<Approver>,
+        }
+
+        let approvals: Approvals = self.client
+            .get(&format!("projects/{}/merge_requests/{}/approvals", project_id(owner, repo), number))
+            .map_err(|e| Error::from(format!("Error looking up MR approvals: {}/{} #{}: {}", owner, repo, number, e)))?;
+
+        Ok(
+            approvals
+                .approved_by
+                .iter()
+                .map(|a| {
+                    github::Review {
+                        id: None,
+                        state: "APPROVED".into(),
+                        body: None,
+                        html_url: String::new(),
+                        user: a.user.to_github(),
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn assign_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u32,
+        assignees: Vec<String>,
+    ) -> Result<github::AssignResponse> {
+        let ids = self.user_ids(&assignees)?;
+        let merge_request = self.update_merge_request(owner, repo, number, &hashmap!{ "assignee_ids" => ids })?;
+        Ok(github::AssignResponse { assignees: merge_request.to_github(&self.repo(owner, repo)).assignees })
+    }
+
+    // adds to the reviewers: gitlab replaces them all
+    fn request_review(&self, owner: &str, repo: &str, number: u32, reviewers: Vec<String>) -> Result<()> {
+        let current = self.get_merge_request(owner, repo, number)?.reviewers.unwrap_or(vec![]);
+        let mut ids: Vec<u64> = current.iter().filter_map(|u| u.id).collect();
+        ids.extend(self.user_ids(&reviewers)?);
+        ids.sort();
+        ids.dedup();
+
+        self.update_merge_request(owner, repo, number, &hashmap!{ "reviewer_ids" => ids })?;
+        Ok(())
+    }
+
+    fn dismiss_review(&self, _: &str, _: &str, _: u32, _: u64, _: &str) -> Result<()> {
+        // gitlab only lets users take back their own approvals
+        Err("Dismissing approvals is not supported on gitlab".into())
+    }
+
+    fn comment_pull_request(&self, owner: &str, repo: &str, number: u32, comment: &str) -> Result<()> {
+        self.client
+            .post_void(
+                &format!("projects/{}/merge_requests/{}/notes", project_id(owner, repo), number),
+                &hashmap!{ "body" => comment },
+            )
+            .map_err(|e| format!("Error commenting on MR: {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
+    fn create_branch(&self, owner: &str, repo: &str, branch_name: &str, sha: &str) -> Result<()> {
+        self.client
+            .post_void(
+                &format!("projects/{}/repository/branches", project_id(owner, repo)),
+                &hashmap!{ "branch" => branch_name, "ref" => sha },
+            )
+            .map_err(|e| format!("Error creating branch {}/{} {}: {}", owner, repo, branch_name, e).into())
+    }
+
+    fn delete_branch(&self, owner: &str, repo: &str, branch_name: &str) -> Result<()> {
+        self.client
+            .delete_void(&format!("projects/{}/repository/branches/{}", project_id(owner, repo), encode(branch_name)))
+            .map_err(|e| format!("Error deleting branch {}/{} {}: {}", owner, repo, branch_name, e).into())
+    }
+
+    fn get_file_contents(&self, owner: &str, repo: &str, path: &str, ref_name: &str) -> Result<String> {
+        #[derive(Deserialize)]
+        struct FileContents {
+            content: String,
+        }
+
+        let contents: FileContents = self.client
+            .get(&format!(
+                "projects/{}/repository/files/{}?ref={}",
+                project_id(owner, repo),
+                encode(path),
+                encode(ref_name)
+            ))
+            .map_err(|e| Error::from(format!("Error getting {} from {}/{} {}: {}", path, owner, repo, ref_name, e)))?;
+
+        let content: String = contents.content.split_whitespace().collect();
+        let bytes = base64::decode(&content).map_err(|e| Error::from(format!("Error decoding {}: {}", path, e)))?;
+
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn get_user(&self, login: &str) -> Result<github::User> {
+        Ok(self.find_user(login)?.to_github())
+    }
+
+    fn get_user_permission(&self, owner: &str, repo: &str, login: &str) -> Result<String> {
+        #[derive(Deserialize)]
+        struct Member {
+            // 10 guest, 20 reporter, 30 developer, 40 maintainer, 50 owner
+            access_level: u32,
+        }
+
+        let id = match self.find_user(login)?.id {
+            Some(id) => id,
+            None => return Err(format!("No id for gitlab user {}", login).into()),
+        };
+        // not a member at all is a 404
+        let path = format!("projects/{}/members/all/{}", project_id(owner, repo), id);
+        let member: Member = match self.client.get(&path) {
+            Ok(m) => m,
+            Err(e) => {
+                if let ErrorKind::HttpStatus(_, StatusCode::NotFound, _, _) = *e.kind() {
+                    return Ok("none".into());
+                }
+                return Err(format!("Error getting permission of {} on {}/{}: {}", login, owner, repo, e).into());
+            }
+        };
+        Ok(
+            match member.access_level {
+                l if l >= 40 => "admin",
+                l if l >= 30 => "write",
+                l if l >= 10 => "read",
+                _ => "none",
+            }.into(),
+        )
+    }
+
+    // teams are subgroups on gitlab, e.g. @org/team is the group "org/team"
+    fn get_team_members(&self, org: &str, team: &str) -> Result<Vec<github::User>> {
+        let users: Vec<User> = self.client
+            .get(&format!("groups/{}/members/all?per_page=100", encode(&format!("{}/{}", org, team))))
+            .map_err(|e| Error::from(format!("Error looking up members of {}/{}: {}", org, team, e)))?;
+        Ok(users.iter().map(|u| u.to_github()).collect())
+    }
+
+    fn get_statuses(&self, owner: &str, repo: &str, ref_name: &str) -> Result<Vec<github::Status>> {
+        #[derive(Deserialize)]
+        struct CommitStatus {
+            // "pending", "running", "success", "failed", "canceled", ...
+            status: String,
+            name: String,
+            target_url: Option<String>,
+            description: Option<String>,
+            author: Option<User>,
+        }
+
+        let statuses: Vec<CommitStatus> = self.client
+            .get(&format!("projects/{}/repository/commits/{}/statuses", project_id(owner, repo), ref_name))
+            .map_err(|e| Error::from(format!("Error getting statuses {}/{} {}: {}", owner, repo, ref_name, e)))?;
+
+        Ok(
+            statuses
+                .into_iter()
+                .map(|s| {
+                    let state = match s.status.as_str() {
+                        "success" => "success",
+                        "failed" | "canceled" => "failure",
+                        _ => "pending",
+                    };
+                    github::Status {
+                        state: state.into(),
+                        target_url: s.target_url,
+                        context: Some(s.name),
+                        description: s.description,
+                        creator: s.author.map(|a| a.to_github()),
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn create_status(&self, owner: &str, repo: &str, ref_name: &str, status: &github::Status) -> Result<()> {
+        let state = match status.state.as_str() {
+            "success" => "success",
+            "failure" | "error" => "failed",
+            _ => "pending",
+        };
+        let mut body = hashmap!{ "state" => state.to_string() };
+        if let Some(ref context) = status.context {
+            body.insert("name", context.clone());
+        }
+        if let Some(ref url) = status.target_url {
+            body.insert("target_url", url.clone());
+        }
+        if let Some(ref description) = status.description {
+            body.insert("description", description.clone());
+        }
+
+        self.client
+            .post_void(&format!("projects/{}/statuses/{}", project_id(owner, repo), ref_name), &body)
+            .map_err(|e| format!("Error creating status {}/{} {}: {}", owner, repo, ref_name, e).into())
+    }
+
+    fn get_combined_status(&self, owner: &str, repo: &str, ref_name: &str) -> Result<github::CombinedStatus> {
+        // gitlab lists every status ever set, oldest first: only the latest of each counts
+        let mut statuses: Vec<github::Status> = vec![];
+        for status in self.get_statuses(owner, repo, ref_name)?.into_iter().rev() {
+            if !statuses.iter().any(|s| s.context == status.context) {
+                statuses.push(status);
+            }
+        }
+
+        let state = if statuses.iter().any(|s| s.state == "failure") {
+            "failure"
+        } else if statuses.is_empty() || statuses.iter().any(|s| s.state == "pending") {
+            "pending"
+        } else {
+            "success"
+        };
+
+        Ok(github::CombinedStatus {
+            state: state.into(),
+            sha: ref_name.into(),
+            statuses: statuses,
+        })
+    }
+
+    // gitlab commits only know their author's email, not their user
+    fn get_commit(&self, _: &str, _: &str, _: &str) -> Result<github::Commit> {
+        Err("Commit authors are not supported on gitlab".into())
+    }
+
+    fn compare_commits(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<github::Comparison> {
+        #[derive(Deserialize)]
+        struct Compare {
+            commits: Vec<CommitRef>,
+        }
+
+        // the commits reachable from `to` but not from `from`
+        let count = |from: &str, to: &str| -> Result<u32> {
+            let compare: Compare = self.client
+                .get(&format!(
+                    "projects/{}/repository/compare?from={}&to={}",
+                    project_id(owner, repo),
+                    encode(from),
+                    encode(to)
+                ))
+                .map_err(|e| Error::from(format!("Error comparing {}/{} {}...{}: {}", owner, repo, base, head, e)))?;
+            Ok(compare.commits.len() as u32)
+        };
+
+        Ok(github::Comparison {
+            ahead_by: count(base, head)?,
+            behind_by: count(head, base)?,
+        })
+    }
+
+    fn get_commits_between(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Compare {
+            commits: Vec<CommitRef>,
+        }
+
+        let compare: Compare = self.client
+            .get(&format!(
+                "projects/{}/repository/compare?from={}&to={}",
+                project_id(owner, repo),
+                encode(base),
+                encode(head)
+            ))
+            .map_err(|e| Error::from(format!("Error comparing {}/{} {}...{}: {}", owner, repo, base, head, e)))?;
+        Ok(compare.commits.into_iter().map(|c| c.id).collect())
+    }
+
+    fn create_release(&self, owner: &str, repo: &str, tag: &str, name: &str, body: &str) -> Result<()> {
+        self.client
+            .post_void(
+                &format!("projects/{}/releases", project_id(owner, repo)),
+                &hashmap!{ "tag_name" => tag, "name" => name, "description" => body },
+            )
+            .map_err(|e| format!("Error creating release {}/{} {}: {}", owner, repo, tag, e).into())
+    }
+
+    fn get_tags(&self, owner: &str, repo: &str) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Tag {
+            name: String,
+        }
+
+        let tags: Vec<Tag> = self.client
+            .get(&format!("projects/{}/repository/tags?per_page=100", project_id(owner, repo)))
+            .map_err(|e| Error::from(format!("Error looking up tags: {}/{}: {}", owner, repo, e)))?;
+        Ok(tags.into_iter().map(|t| t.name).collect())
+    }
+
+    fn is_branch_protected(&self, owner: &str, repo: &str, branch: &str) -> Result<bool> {
+        #[derive(Deserialize)]
+        struct Branch {
+            protected: bool,
+        }
+
+        let branch: Branch = self.client
+            .get(&format!("projects/{}/repository/branches/{}", project_id(owner, repo), encode(branch)))
+            .map_err(|e| Error::from(format!("Error looking up branch {}/{} {}: {}", owner, repo, branch, e)))?;
+        Ok(branch.protected)
+    }
+
+    // gitlab has no required status checks, and approvals are set for the whole project
+    fn protect_branch(&self, owner: &str, repo: &str, branch: &str, _: &Vec<String>, _: u32) -> Result<()> {
+        self.client
+            .post_void(
+                &format!("projects/{}/protected_branches", project_id(owner, repo)),
+                &hashmap!{ "name" => branch },
+            )
+            .map_err(|e| format!("Error protecting branch {}/{} {}: {}", owner, repo, branch, e).into())
+    }
+
+    fn rerun_failed_jobs(&self, owner: &str, repo: &str, run_id: u64) -> Result<()> {
+        self.client
+            .post_void(&format!("projects/{}/pipelines/{}/retry", project_id(owner, repo), run_id), &json!({}))
+            .map_err(|e| format!("Error retrying pipeline {}/{} {}: {}", owner, repo, run_id, e).into())
+    }
+
+    fn get_pull_requests_for_commit(&self, owner: &str, repo: &str, sha: &str) -> Result<Vec<github::PullRequest>> {
+        let merge_requests: Vec<MergeRequest> = self.client
+            .get(&format!("projects/{}/repository/commits/{}/merge_requests", project_id(owner, repo), sha))
+            .map_err(|e| Error::from(format!("Error looking up MRs for commit {}/{} {}: {}", owner, repo, sha, e)))?;
+        let repo = self.repo(owner, repo);
+        Ok(merge_requests.iter().map(|m| m.to_github(&repo)).collect())
+    }
+
+    fn get_hooks(&self, _: &str, _: &str) -> Result<Vec<github::Hook>> {
+        Err("Setting up webhooks is not supported on gitlab".into())
+    }
+
+    fn save_hook(&self, _: &str, _: &str, _: &github::Hook) -> Result<()> {
+        Err("Setting up webhooks is not supported on gitlab".into())
+    }
+
+    fn merge_pull_request(&self, owner: &str, repo: &str, number: u32, sha: &str, merge_method: &str) -> Result<()> {
+        #[derive(Serialize)]
+        struct MergeBody<'a> {
+            sha: &'a str,
+            squash: bool,
+        }
+
+        if merge_method == "rebase" {
+            return Err(format!("Rebase merges aren't supported on gitlab: {}/{} #{}", owner, repo, number).into());
+        }
+        let body = MergeBody {
+            sha: sha,
+            squash: merge_method == "squash",
+        };
+        self.client
+            .put_void(&format!("projects/{}/merge_requests/{}/merge", project_id(owner, repo), number), &body)
+            .map_err(|e| format!("Error merging MR {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+
+    fn update_pull_request_branch(&self, owner: &str, repo: &str, number: u32, _head_sha: &str) -> Result<()> {
+        self.client
+            .put_void(
+                &format!("projects/{}/merge_requests/{}/rebase", project_id(owner, repo), number),
+                &HashMap::<String, String>::new(),
+            )
+            .map_err(|e| format!("Error rebasing MR {}/{} #{}: {}", owner, repo, number, e).into())
+    }
+}
+
+// projects are addressed by their url-encoded path
+fn project_id(owner: &str, repo: &str) -> String {
+    encode(&format!("{}/{}", owner, repo))
+}
+
+fn encode(value: &str) -> String {
+    form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_http::MockHttp;
+    use serde_json::Value;
+
+    fn new_session(http: &MockHttp) -> GitlabSession {
+        http.expect_header("private-token", "the-token");
+        http.expect_get("/api/v4/user", json!({"id": 1, "username": "octobot"}));
+
+        let config = GitlabConfig {
+            webhook_secret: String::new(),
+            host: "the-gitlab-host".into(),
+            api_token: "the-token".into(),
+            api_base: Some(http.url("/api/v4")),
+        };
+        GitlabSession::new(http.core_remote(), &config).expect("gitlab session")
+    }
+
+    fn merge_request(reviewer_ids: Vec<u64>) -> Value {
+        let reviewers: Vec<Value> =
+            reviewer_ids.iter().map(|id| json!({"id": id, "username": format!("user{}", id)})).collect();
+        json!({
+            "iid": 5,
+            "title": "The MR",
+            "state": "opened",
+            "web_url": "https://the-gitlab-host/some-group/some-repo/merge_requests/5",
+            "author": {"username": "joe"},
+            "reviewers": reviewers,
+            "source_branch": "the-branch",
+            "target_branch": "master",
+            "sha": "abc123",
+        })
+    }
+
+    #[test]
+    fn test_new_session() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        assert_eq!("octobot", session.user().login());
+        assert_eq!("the-gitlab-host", session.github_host());
+    }
+
+    #[test]
+    fn test_new_session_bad_token() {
+        let http = MockHttp::new();
+        http.expect("GET", "/api/v4/user", None, 401, json!({"message": "401 Unauthorized"}));
+
+        let config = GitlabConfig {
+            webhook_secret: String::new(),
+            host: "the-gitlab-host".into(),
+            api_token: "the-token".into(),
+            api_base: Some(http.url("/api/v4")),
+        };
+        let err = GitlabSession::new(http.core_remote(), &config).err().expect("auth error");
+        assert!(format!("{}", err).starts_with("Error authenticating to gitlab with token"));
+    }
+
+    #[test]
+    fn test_get_pull_requests() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect_get(
+            "/api/v4/projects/some-group%2Fsome-repo/merge_requests?per_page=100&state=opened&source_branch=the-branch",
+            json!([merge_request(vec![])]),
+        );
+
+        let prs = session
+            .get_pull_requests("some-group", "some-repo", Some("open"), Some("some-group:the-branch"))
+            .unwrap();
+        assert_eq!(1, prs.len());
+        assert_eq!(5, prs[0].number);
+        assert_eq!("the-branch", prs[0].head.ref_name);
+        assert_eq!("abc123", prs[0].head.sha);
+        assert_eq!("master", prs[0].base.ref_name);
+    }
+
+    #[test]
+    fn test_assign_pull_request() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect_get("/api/v4/users?username=jane", json!([{"id": 7, "username": "jane"}]));
+        http.expect(
+            "PUT",
+            "/api/v4/projects/some-group%2Fsome-repo/merge_requests/5",
+            Some(json!({"assignee_ids": [7]})),
+            200,
+            merge_request(vec![]),
+        );
+
+        session.assign_pull_request("some-group", "some-repo", 5, vec!["jane".into()]).unwrap();
+    }
+
+    #[test]
+    fn test_assign_pull_request_unknown_user() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect_get("/api/v4/users?username=nobody", json!([]));
+
+        let err = session.assign_pull_request("some-group", "some-repo", 5, vec!["nobody".into()]).unwrap_err();
+        assert_eq!("No such gitlab user: nobody", format!("{}", err));
+    }
+
+    #[test]
+    fn test_request_review_keeps_reviewers() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect_get("/api/v4/projects/some-group%2Fsome-repo/merge_requests/5", merge_request(vec![9, 3]));
+        http.expect_get("/api/v4/users?username=user3", json!([{"id": 3, "username": "user3"}]));
+        http.expect_get("/api/v4/users?username=jane", json!([{"id": 7, "username": "jane"}]));
+        http.expect(
+            "PUT",
+            "/api/v4/projects/some-group%2Fsome-repo/merge_requests/5",
+            Some(json!({"reviewer_ids": [3, 7, 9]})),
+            200,
+            merge_request(vec![3, 7, 9]),
+        );
+
+        session.request_review("some-group", "some-repo", 5, vec!["user3".into(), "jane".into()]).unwrap();
+    }
+
+    #[test]
+    fn test_get_pull_request_reviews() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect_get(
+            "/api/v4/projects/some-group%2Fsome-repo/merge_requests/5/approvals",
+            json!({"approved_by": [{"user": {"id": 7, "username": "jane"}}, {"user": {"id": 3, "username": "bob"}}]}),
+        );
+
+        let reviews = session.get_pull_request_reviews("some-group", "some-repo", 5).unwrap();
+        let approved: Vec<(&str, &str)> = reviews.iter().map(|r| (r.user.login(), r.state.as_str())).collect();
+        assert_eq!(vec![("jane", "APPROVED"), ("bob", "APPROVED")], approved);
+    }
+
+    #[test]
+    fn test_merge_pull_request() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect(
+            "PUT",
+            "/api/v4/projects/some-group%2Fsome-repo/merge_requests/5/merge",
+            Some(json!({"sha": "abc123", "squash": true})),
+            200,
+            merge_request(vec![]),
+        );
+        http.expect(
+            "PUT",
+            "/api/v4/projects/some-group%2Fsome-repo/merge_requests/5/merge",
+            Some(json!({"sha": "abc123", "squash": false})),
+            200,
+            merge_request(vec![]),
+        );
+
+        session.merge_pull_request("some-group", "some-repo", 5, "abc123", "squash").unwrap();
+        session.merge_pull_request("some-group", "some-repo", 5, "abc123", "merge").unwrap();
+
+        let err = session.merge_pull_request("some-group", "some-repo", 5, "abc123", "rebase").unwrap_err();
+        assert_eq!("Rebase merges aren't supported on gitlab: some-group/some-repo #5", format!("{}", err));
+    }
+
+    #[test]
+    fn test_pull_request_labels() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect(
+            "PUT",
+            "/api/v4/projects/some-group%2Fsome-repo/merge_requests/5",
+            Some(json!({"add_labels": "backport-1.0,size/L"})),
+            200,
+            merge_request(vec![]),
+        );
+        http.expect(
+            "PUT",
+            "/api/v4/projects/some-group%2Fsome-repo/merge_requests/5",
+            Some(json!({"remove_labels": "size/L"})),
+            200,
+            merge_request(vec![]),
+        );
+
+        session
+            .add_pull_request_labels("some-group", "some-repo", 5, vec!["backport-1.0".into(), "size/L".into()])
+            .unwrap();
+        session.remove_pull_request_label("some-group", "some-repo", 5, "size/L").unwrap();
+    }
+
+    #[test]
+    fn test_get_user_permission() {
+        let http = MockHttp::new();
+        let session = new_session(&http);
+        http.expect_get("/api/v4/users?username=jane", json!([{"id": 7, "username": "jane"}]));
+        http.expect_get("/api/v4/projects/some-group%2Fsome-repo/members/all/7", json!({"access_level": 30}));
+        http.expect_get("/api/v4/users?username=bob", json!([{"id": 3, "username": "bob"}]));
+        http.expect("GET", "/api/v4/projects/some-group%2Fsome-repo/members/all/3", None, 404, json!({}));
+
+        assert_eq!("write", session.get_user_permission("some-group", "some-repo", "jane").unwrap());
+        assert_eq!("none", session.get_user_permission("some-group", "some-repo", "bob").unwrap());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod api;
+mod models;
+
+pub use self::models::*;
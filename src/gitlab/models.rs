@@ -0,0 +1,558 @@
+use serde_json;
+
+use errors::*;
+use github;
+
+// Gitlab's objects, and how they map onto github's so that gitlab events can go through the same
+// handling as github events: merge requests are pull requests, notes are comments, and approvals
+// are reviews.
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct User {
+    pub id: Option<u64>,
+    pub username: String,
+    pub name: Option<String>,
+    pub public_email: Option<String>,
+}
+
+impl User {
+    pub fn to_github(&self) -> github::User {
+        github::User {
+            login: Some(self.username.clone()),
+            name: self.name.clone(),
+            email: self.public_email.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Project {
+    pub path_with_namespace: String,
+    pub web_url: String,
+}
+
+impl Project {
+    pub fn to_github(&self) -> github::Repo {
+        // namespaces can be nested groups: the owner is everything but the last part
+        let mut parts = self.path_with_namespace.rsplitn(2, '/');
+        let name = parts.next().unwrap_or("");
+        let owner = parts.next().unwrap_or("");
+        github::Repo {
+            html_url: self.web_url.clone(),
+            full_name: self.path_with_namespace.clone(),
+            name: name.to_string(),
+            owner: github::User::new(owner),
+            default_branch: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Label {
+    pub title: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CommitRef {
+    pub id: String,
+}
+
+// A merge request as the API returns it
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MergeRequest {
+    pub iid: u32,
+    pub title: String,
+    pub description: Option<String>,
+    // "opened", "closed", "locked", or "merged"
+    pub state: String,
+    pub web_url: String,
+    pub author: User,
+    pub assignees: Option<Vec<User>>,
+    pub reviewers: Option<Vec<User>>,
+    pub source_branch: String,
+    pub target_branch: String,
+    pub sha: Option<String>,
+    pub merge_commit_sha: Option<String>,
+    pub labels: Option<Vec<String>>,
+    pub milestone: Option<github::Milestone>,
+    // "can_be_merged", "cannot_be_merged", or "unchecked"
+    pub merge_status: Option<String>,
+    pub updated_at: Option<String>,
+    pub draft: Option<bool>,
+}
+
+impl MergeRequest {
+    pub fn to_github(&self, repo: &github::Repo) -> github::PullRequest {
+        let mut pr = github::PullRequest::new();
+        pr.number = self.iid;
+        pr.title = self.title.clone();
+        pr.body = self.description.clone();
+        pr.html_url = self.web_url.clone();
+        pr.state = github_state(&self.state);
+        pr.merged = Some(self.state == "merged");
+        pr.merge_commit_sha = self.merge_commit_sha.clone();
+        pr.user = self.author.to_github();
+        pr.assignees = to_github_users(&self.assignees);
+        pr.requested_reviewers = Some(to_github_users(&self.reviewers));
+        pr.mergeable = match self.merge_status {
+            Some(ref s) if s == "can_be_merged" => Some(true),
+            Some(ref s) if s == "cannot_be_merged" => Some(false),
+            _ => None,
+        };
+        pr.updated_at = self.updated_at.as_ref().map(|t| github_time(t));
+        pr.draft = self.draft;
+        pr.milestone = self.milestone.clone();
+        pr.head = branch_ref(&self.source_branch, self.sha.clone().unwrap_or(String::new()), repo);
+        pr.base = branch_ref(&self.target_branch, String::new(), repo);
+        pr
+    }
+}
+
+// The merge request as it comes in webhooks: it lacks the author and the reviewers, so the whole
+// merge request gets looked up when handling the event.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MergeRequestAttributes {
+    pub iid: u32,
+    pub title: String,
+    pub description: Option<String>,
+    pub state: String,
+    pub url: String,
+    pub source_branch: String,
+    pub target_branch: String,
+    pub last_commit: Option<CommitRef>,
+    pub merge_commit_sha: Option<String>,
+    // "open", "close", "reopen", "update", "approved", "unapproved", or "merge"
+    pub action: Option<String>,
+    // set on updates that pushed new commits
+    pub oldrev: Option<String>,
+}
+
+impl MergeRequestAttributes {
+    fn to_github(&self, repo: &github::Repo) -> github::PullRequest {
+        let mut pr = github::PullRequest::new();
+        pr.number = self.iid;
+        pr.title = self.title.clone();
+        pr.body = self.description.clone();
+        pr.html_url = self.url.clone();
+        pr.state = github_state(&self.state);
+        pr.merged = Some(self.state == "merged");
+        pr.merge_commit_sha = self.merge_commit_sha.clone();
+        let sha = self.last_commit.as_ref().map(|c| c.id.clone()).unwrap_or(String::new());
+        pr.head = branch_ref(&self.source_branch, sha, repo);
+        pr.base = branch_ref(&self.target_branch, String::new(), repo);
+        pr
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Changes<T> {
+    pub previous: T,
+    pub current: T,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MergeRequestChanges {
+    pub labels: Option<Changes<Vec<Label>>>,
+    pub assignees: Option<Changes<Vec<User>>>,
+    pub reviewers: Option<Changes<Vec<User>>>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MergeRequestHook {
+    pub user: User,
+    pub project: Project,
+    pub object_attributes: MergeRequestAttributes,
+    pub changes: Option<MergeRequestChanges>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct NotePosition {
+    pub new_path: Option<String>,
+    pub new_line: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct NoteAttributes {
+    pub note: String,
+    // "MergeRequest", "Commit", "Issue", or "Snippet"
+    pub noteable_type: String,
+    pub url: String,
+    pub commit_id: Option<String>,
+    // only on notes on a diff
+    pub position: Option<NotePosition>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct NoteHook {
+    pub user: User,
+    pub project: Project,
+    pub object_attributes: NoteAttributes,
+    pub merge_request: Option<MergeRequestAttributes>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct HookCommit {
+    pub id: String,
+    pub message: String,
+    pub url: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PushHook {
+    pub before: String,
+    pub after: String,
+    #[serde(rename = "ref")]
+    pub ref_name: String,
+    pub user_username: String,
+    pub project: Project,
+    pub commits: Vec<HookCommit>,
+}
+
+// Turns a gitlab webhook into the github events it amounts to, as (event, body) pairs. `event` is the
+// X-Gitlab-Event header. A single merge request update can be several events (e.g. labeled twice).
+pub fn hook_events(event: &str, body: &[u8]) -> Result<Vec<(String, github::HookBody)>> {
+    match event {
+        "Merge Request Hook" => Ok(merge_request_events(&parse(body)?)),
+        "Note Hook" => Ok(note_events(&parse(body)?)),
+        "Push Hook" => Ok(vec![push_event(&parse(body)?)]),
+        _ => Ok(vec![]),
+    }
+}
+
+fn parse<T: ::serde::de::DeserializeOwned>(body: &[u8]) -> Result<T> {
+    serde_json::from_slice(body).map_err(|e| Error::from(format!("Error parsing gitlab event: {}", e)))
+}
+
+fn merge_request_events(hook: &MergeRequestHook) -> Vec<(String, github::HookBody)> {
+    let attrs = &hook.object_attributes;
+    let mut data = github::HookBody::new();
+    data.repository = hook.project.to_github();
+    data.sender = hook.user.to_github();
+    data.pull_request = Some(attrs.to_github(&data.repository));
+
+    let pr_event = |action: &str| {
+        let mut data = data.clone();
+        data.action = Some(action.to_string());
+        ("pull_request".to_string(), data)
+    };
+
+    let action = attrs.action.as_ref().map(|a| a.as_str()).unwrap_or("");
+    match action {
+        "open" => vec![pr_event("opened")],
+        "reopen" => vec![pr_event("reopened")],
+        "close" | "merge" => vec![pr_event("closed")],
+        "approved" => {
+            let mut data = data.clone();
+            data.action = Some("submitted".into());
+            data.review = Some(github::Review {
+                id: None,
+                state: "approved".into(),
+                body: None,
+                html_url: attrs.url.clone(),
+                user: data.sender.clone(),
+            });
+            vec![("pull_request_review".to_string(), data)]
+        }
+        "update" => {
+            let mut events = vec![];
+            if let Some(ref changes) = hook.changes {
+                if let Some(ref labels) = changes.labels {
+                    for label in &labels.current {
+                        if !labels.previous.iter().any(|l| l.title == label.title) {
+                            let (event, mut data) = pr_event("labeled");
+                            data.label = Some(github::Label::new(&label.title));
+                            events.push((event, data));
+                        }
+                    }
+                }
+                if let Some(ref assignees) = changes.assignees {
+                    if !assignees.current.is_empty() {
+                        events.push(pr_event("assigned"));
+                    }
+                }
+                if let Some(ref reviewers) = changes.reviewers {
+                    let (before, after) = (&reviewers.previous, &reviewers.current);
+                    let changes = vec![("review_requested", before, after), ("review_request_removed", after, before)];
+                    for (action, from, to) in changes {
+                        for reviewer in to.iter().filter(|r| !from.iter().any(|f| f.username == r.username)) {
+                            let (event, mut data) = pr_event(action);
+                            data.requested_reviewer = Some(reviewer.to_github());
+                            events.push((event, data));
+                        }
+                    }
+                }
+            }
+            if attrs.oldrev.is_some() {
+                events.push(pr_event("synchronize"));
+            }
+            events
+        }
+        _ => vec![],
+    }
+}
+
+fn note_events(hook: &NoteHook) -> Vec<(String, github::HookBody)> {
+    let note = &hook.object_attributes;
+    let mut data = github::HookBody::new();
+    data.repository = hook.project.to_github();
+    data.sender = hook.user.to_github();
+    data.action = Some("created".into());
+    data.comment = Some(github::Comment {
+        commit_id: note.commit_id.clone(),
+        path: note.position.as_ref().and_then(|p| p.new_path.clone()),
+        body: Some(note.note.clone()),
+        html_url: note.url.clone(),
+        user: data.sender.clone(),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: note.position.as_ref().and_then(|p| p.new_line),
+    });
+
+    match note.noteable_type.as_str() {
+        "MergeRequest" => {
+            let merge_request = match hook.merge_request {
+                Some(ref m) => m,
+                None => return vec![],
+            };
+            data.pull_request = Some(merge_request.to_github(&data.repository));
+            let event = if note.position.is_some() { "pull_request_review_comment" } else { "issue_comment" };
+            vec![(event.to_string(), data)]
+        }
+        "Commit" => vec![("commit_comment".to_string(), data)],
+        _ => vec![],
+    }
+}
+
+fn push_event(hook: &PushHook) -> (String, github::HookBody) {
+    let no_commit = |sha: &str| !sha.is_empty() && sha.chars().all(|c| c == '0');
+
+    let mut data = github::HookBody::new();
+    data.repository = hook.project.to_github();
+    data.sender = github::User::new(&hook.user_username);
+    data.ref_name = Some(hook.ref_name.clone());
+    data.before = Some(hook.before.clone());
+    data.after = Some(hook.after.clone());
+    data.created = Some(no_commit(&hook.before));
+    data.deleted = Some(no_commit(&hook.after));
+    data.compare = Some(format!("{}/compare/{}...{}", hook.project.web_url, hook.before, hook.after));
+    data.commits = Some(
+        hook.commits
+            .iter()
+            .map(|c| {
+                github::PushCommit {
+                    id: c.id.clone(),
+                    tree_id: String::new(),
+                    message: c.message.clone(),
+                    url: c.url.clone(),
+                }
+            })
+            .collect(),
+    );
+    ("push".to_string(), data)
+}
+
+fn github_state(state: &str) -> String {
+    if state == "opened" || state == "locked" { "open".into() } else { "closed".into() }
+}
+
+// gitlab includes milliseconds: "2017-09-28T08:00:00.000Z"
+fn github_time(time: &str) -> String {
+    if time.len() > 19 && time.ends_with('Z') { format!("{}Z", &time[0..19]) } else { time.to_string() }
+}
+
+fn to_github_users(users: &Option<Vec<User>>) -> Vec<github::User> {
+    match *users {
+        Some(ref users) => users.iter().map(|u| u.to_github()).collect(),
+        None => vec![],
+    }
+}
+
+fn branch_ref(name: &str, sha: String, repo: &github::Repo) -> github::BranchRef {
+    let mut branch = github::BranchRef::new(name);
+    branch.sha = sha;
+    branch.repo = repo.clone();
+    branch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &'static str = r#"{
+        "path_with_namespace": "some-group/sub-group/some-repo",
+        "web_url": "https://gitlab.foo.com/some-group/sub-group/some-repo"
+    }"#;
+
+    fn merge_request_hook(action: &str, changes: &str) -> String {
+        format!(
+            r#"{{
+                "object_kind": "merge_request",
+                "user": {{"username": "joe", "name": "Joe"}},
+                "project": {},
+                "object_attributes": {{
+                    "iid": 12,
+                    "title": "The MR",
+                    "description": "the description",
+                    "state": "opened",
+                    "url": "https://gitlab.foo.com/some-group/sub-group/some-repo/merge_requests/12",
+                    "source_branch": "the-branch",
+                    "target_branch": "master",
+                    "last_commit": {{"id": "abcdef0"}},
+                    "action": "{}"
+                }},
+                "changes": {}
+            }}"#,
+            PROJECT,
+            action,
+            changes
+        )
+    }
+
+    #[test]
+    fn test_project_to_github() {
+        let project: Project = serde_json::from_str(PROJECT).unwrap();
+        let repo = project.to_github();
+        assert_eq!("some-group/sub-group", repo.owner.login());
+        assert_eq!("some-repo", repo.name);
+        assert_eq!("some-group/sub-group/some-repo", repo.full_name);
+    }
+
+    #[test]
+    fn test_merge_request_opened() {
+        let events = hook_events("Merge Request Hook", merge_request_hook("open", "{}").as_bytes()).unwrap();
+        assert_eq!(1, events.len());
+
+        let (ref event, ref data) = events[0];
+        assert_eq!("pull_request", event);
+        assert_eq!(Some("opened".to_string()), data.action);
+        assert_eq!("joe", data.sender.login());
+
+        let pr = data.pull_request.as_ref().unwrap();
+        assert_eq!(12, pr.number);
+        assert_eq!("open", pr.state);
+        assert_eq!("the-branch", pr.head.ref_name);
+        assert_eq!("abcdef0", pr.head.sha);
+        assert_eq!("master", pr.base.ref_name);
+        // to be filled in by looking up the merge request
+        assert!(pr.requested_reviewers.is_none());
+    }
+
+    #[test]
+    fn test_merge_request_labeled() {
+        let changes = r#"{"labels": {
+            "previous": [{"title": "bug"}],
+            "current": [{"title": "bug"}, {"title": "backport-1.0"}, {"title": "backport-2.0"}]
+        }}"#;
+        let events = hook_events("Merge Request Hook", merge_request_hook("update", changes).as_bytes()).unwrap();
+
+        let labels: Vec<String> = events
+            .iter()
+            .map(|&(ref event, ref data)| {
+                assert_eq!("pull_request", event);
+                assert_eq!(Some("labeled".to_string()), data.action);
+                data.label.as_ref().unwrap().name.clone()
+            })
+            .collect();
+        assert_eq!(vec!["backport-1.0", "backport-2.0"], labels);
+    }
+
+    #[test]
+    fn test_merge_request_reviewers() {
+        let changes = r#"{"reviewers": {
+            "previous": [{"username": "bob"}, {"username": "mary"}],
+            "current": [{"username": "mary"}, {"username": "jane"}]
+        }}"#;
+        let events = hook_events("Merge Request Hook", merge_request_hook("update", changes).as_bytes()).unwrap();
+
+        let actions: Vec<(String, String)> = events
+            .iter()
+            .map(|&(_, ref d)| (d.action.clone().unwrap(), d.requested_reviewer.as_ref().unwrap().login().to_string()))
+            .collect();
+        assert_eq!(
+            vec![("review_requested".into(), "jane".into()), ("review_request_removed".into(), "bob".into())],
+            actions
+        );
+    }
+
+    #[test]
+    fn test_merge_request_approved() {
+        let events = hook_events("Merge Request Hook", merge_request_hook("approved", "{}").as_bytes()).unwrap();
+        assert_eq!(1, events.len());
+
+        let (ref event, ref data) = events[0];
+        assert_eq!("pull_request_review", event);
+        assert_eq!(Some("submitted".to_string()), data.action);
+        let review = data.review.as_ref().unwrap();
+        assert_eq!("approved", review.state);
+        assert_eq!("joe", review.user.login());
+    }
+
+    #[test]
+    fn test_note_on_merge_request() {
+        let hook = format!(
+            r#"{{
+                "object_kind": "note",
+                "user": {{"username": "joe"}},
+                "project": {},
+                "object_attributes": {{
+                    "note": "looks good",
+                    "noteable_type": "MergeRequest",
+                    "url": "https://gitlab.foo.com/some-group/sub-group/some-repo/merge_requests/12#note_1"
+                }},
+                "merge_request": {{
+                    "iid": 12,
+                    "title": "The MR",
+                    "state": "opened",
+                    "url": "https://gitlab.foo.com/some-group/sub-group/some-repo/merge_requests/12",
+                    "source_branch": "the-branch",
+                    "target_branch": "master"
+                }}
+            }}"#,
+            PROJECT
+        );
+        let events = hook_events("Note Hook", hook.as_bytes()).unwrap();
+        assert_eq!(1, events.len());
+
+        let (ref event, ref data) = events[0];
+        assert_eq!("issue_comment", event);
+        assert_eq!(Some("created".to_string()), data.action);
+        assert_eq!(Some("looks good".to_string()), data.comment.as_ref().unwrap().body);
+        assert_eq!(12, data.pull_request.as_ref().unwrap().number);
+    }
+
+    #[test]
+    fn test_push() {
+        let hook = format!(
+            r#"{{
+                "object_kind": "push",
+                "before": "0000000000000000000000000000000000000000",
+                "after": "abcdef0123456789",
+                "ref": "refs/heads/the-branch",
+                "user_username": "joe",
+                "project": {},
+                "commits": [{{"id": "abcdef0123456789", "message": "the commit", "url": "http://the-commit"}}]
+            }}"#,
+            PROJECT
+        );
+        let events = hook_events("Push Hook", hook.as_bytes()).unwrap();
+        assert_eq!(1, events.len());
+
+        let (ref event, ref data) = events[0];
+        assert_eq!("push", event);
+        assert_eq!("refs/heads/the-branch", data.ref_name());
+        assert!(data.created());
+        assert!(!data.deleted());
+        assert_eq!(1, data.commits.as_ref().unwrap().len());
+    }
+
+    #[test]
+    fn test_unknown_event() {
+        assert_eq!(0, hook_events("Pipeline Hook", b"{}").unwrap().len());
+    }
+
+    #[test]
+    fn test_github_time() {
+        assert_eq!("2017-09-28T08:00:00Z", github_time("2017-09-28T08:00:00.000Z"));
+        assert_eq!("2017-09-28T08:00:00Z", github_time("2017-09-28T08:00:00Z"));
+    }
+}
@@ -2,26 +2,187 @@ use futures::Stream;
 use futures::future::Future;
 use futures::sync::oneshot;
 use hyper;
-use hyper::{Method, Request};
+use hyper::{Method, Request, StatusCode};
 use hyper::header::UserAgent;
 use hyper_rustls::HttpsConnector;
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 use serde_json;
+use std::cell::RefCell;
+use std::cmp;
 use std::collections::HashMap;
-use tokio_core::reactor::Remote;
-
+use std::str;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use time;
+use tokio_core::reactor::{CoreId, Handle, Remote};
+
+use config::RetryConfig;
 use errors;
 use errors::*;
+use tracing;
 
 pub struct HTTPClient {
     api_base: String,
     headers: HashMap<&'static str, String>,
     core_remote: Remote,
+    retry: RetryPolicy,
+    cache: Option<Arc<ResponseCache>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct CachedResponse {
+    etag: String,
+    data: Vec<u8>,
+    next_page: Option<String>,
+    last_used: u64,
+}
+
+// GET responses by url, along with their ETags. Requests for urls in here ask github whether they changed,
+// and get the cached body back if they didn't: those "304 Not Modified"s don't count against the rate limit.
+pub struct ResponseCache {
+    max_entries: usize,
+    entries: Mutex<(u64, HashMap<String, CachedResponse>)>,
+}
+
+impl ResponseCache {
+    pub fn new(max_entries: usize) -> ResponseCache {
+        ResponseCache {
+            max_entries: max_entries,
+            entries: Mutex::new((0, HashMap::new())),
+        }
+    }
+
+    fn etag(&self, url: &str) -> Option<String> {
+        let entries = self.entries.lock().unwrap();
+        entries.1.get(url).map(|e| e.etag.clone())
+    }
+
+    fn get(&self, url: &str) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.0 += 1;
+        let now = entries.0;
+        entries.1.get_mut(url).map(|e| {
+            e.last_used = now;
+            e.clone()
+        })
+    }
+
+    fn put(&self, url: &str, etag: String, data: Vec<u8>, next_page: Option<String>) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.0 += 1;
+        let now = entries.0;
+        if !entries.1.contains_key(url) && entries.1.len() >= self.max_entries {
+            let oldest = entries.1.iter().min_by_key(|&(_, e)| e.last_used).map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                entries.1.remove(&oldest);
+            }
+        }
+        entries.1.insert(
+            url.to_string(),
+            CachedResponse {
+                etag: etag,
+                data: data,
+                next_page: next_page,
+                last_used: now,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().1.len()
+    }
+}
+
+// When the blocking requests are tried again. Server and network errors are retried with exponential
+// backoff, except for POSTs, which may have gone through already. Rate limited requests are retried
+// once the limit resets, if that's soon enough.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    attempts: u32,
+    backoff_ms: u64,
+    max_backoff_ms: u64,
+    jitter: bool,
+    max_rate_limit_wait_secs: i64,
+}
+
+impl RetryPolicy {
+    pub fn none() -> RetryPolicy {
+        RetryPolicy::new(&RetryConfig { attempts: Some(1), ..RetryConfig::default() })
+    }
+
+    pub fn new(config: &RetryConfig) -> RetryPolicy {
+        RetryPolicy {
+            attempts: cmp::max(1, config.attempts.unwrap_or(3)),
+            backoff_ms: config.backoff_ms.unwrap_or(500),
+            max_backoff_ms: config.max_backoff_ms.unwrap_or(10000),
+            jitter: config.jitter.unwrap_or(true),
+            max_rate_limit_wait_secs: config.max_rate_limit_wait_secs.unwrap_or(60) as i64,
+        }
+    }
+
+    // how long to wait before trying again after the given attempt (the first is 1) failed, or None to give up
+    pub fn retry_delay(&self, method: &Method, err: &Error, attempt: u32, now: i64) -> Option<Duration> {
+        if attempt >= self.attempts {
+            return None;
+        }
+
+        let may_repeat = *method != Method::Post && *method != Method::Patch;
+        match *err.kind() {
+            ErrorKind::HttpStatus(_, status, Some(reset), _)
+                if status == StatusCode::Forbidden || status == StatusCode::TooManyRequests => {
+                let wait = cmp::max(0, reset - now) + 1;
+                if wait > self.max_rate_limit_wait_secs {
+                    None
+                } else {
+                    Some(Duration::from_secs(wait as u64))
+                }
+            }
+            ErrorKind::HttpStatus(_, status, _, _) if status.is_server_error() && may_repeat => {
+                Some(self.backoff(attempt))
+            }
+            ErrorKind::HttpCancelled if may_repeat => Some(self.backoff(attempt)),
+            _ => None,
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let doubled = self.backoff_ms.saturating_mul(1 << cmp::min(attempt - 1, 20));
+        let ms = cmp::min(doubled, self.max_backoff_ms);
+        let ms = if self.jitter && ms > 1 {
+            ms / 2 + (time::precise_time_ns() % (ms / 2 + 1))
+        } else {
+            ms
+        };
+        Duration::from_millis(ms)
+    }
+
+    pub fn run<T, F>(&self, method: &Method, path: &str, mut request: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        let mut attempt = 1;
+        loop {
+            let err = match request() {
+                Ok(r) => return Ok(r),
+                Err(e) => e,
+            };
+            match self.retry_delay(method, &err, attempt, time::get_time().sec) {
+                Some(delay) => {
+                    warn!("Retrying {} {} in {:?} (attempt {}): {}", method, path, delay, attempt, err);
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                None => return Err(err),
+            }
+        }
+    }
 }
 
 struct InternalResp {
     data: hyper::Chunk,
+    next_page: Option<String>,
 }
 
 type InternalResponseResult = Result<InternalResp>;
@@ -35,9 +196,23 @@ impl HTTPClient {
             api_base: api_base.into(),
             headers: HashMap::new(),
             core_remote: core_remote,
+            retry: RetryPolicy::none(),
+            cache: None,
         }
     }
 
+    pub fn with_cache(self, cache: ResponseCache) -> HTTPClient {
+        let mut c = self;
+        c.cache = Some(Arc::new(cache));
+        c
+    }
+
+    pub fn with_retries(self, retry: RetryPolicy) -> HTTPClient {
+        let mut c = self;
+        c.retry = retry;
+        c
+    }
+
     pub fn with_headers(self, headers: HashMap<&'static str, String>) -> HTTPClient {
         let mut c = self;
         c.headers = headers;
@@ -102,10 +277,27 @@ impl HTTPClient {
         self.request_void_async::<U>(Method::Put, path, Some(body))
     }
 
+    pub fn patch_void<U: Serialize>(&self, path: &str, body: &U) -> Result<()> {
+        self.request_void::<U>(Method::Patch, path, Some(body))
+    }
+
+    // gets one page of a list, along with the url of the next page if there is one (from the Link header)
+    pub fn get_page<T>(&self, path: &str) -> Result<(T, Option<String>)>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let result = self.run(&Method::Get, path, || self.request_page_async(path).wait());
+        result.map_err(waiting_error)
+    }
+
     pub fn delete_void(&self, path: &str) -> Result<()> {
         self.request_void::<()>(Method::Delete, path, None)
     }
 
+    pub fn delete_void_with_body<U: Serialize>(&self, path: &str, body: &U) -> Result<()> {
+        self.request_void::<U>(Method::Delete, path, Some(body))
+    }
+
     pub fn delete_void_async(&self, path: &str) -> FutureResult<()> {
         self.request_void_async::<()>(Method::Delete, path, None)
     }
@@ -119,19 +311,26 @@ impl HTTPClient {
         self.core_remote.spawn(move |_| fut);
     }
 
+    // makes the request, retries and all, as a span of the trace going on in this thread
+    fn run<T, F>(&self, method: &Method, path: &str, request: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        let attributes = [("http.method", method.to_string()), ("http.target", path.to_string())];
+        tracing::span_result(&format!("HTTP {}", method), &attributes, || self.retry.run(method, path, request))
+    }
+
     fn request_de<T, U: Serialize>(&self, method: Method, path: &str, body: Option<&U>) -> Result<T>
     where
         T: DeserializeOwned + Send + 'static,
     {
-        self.request_de_async(method, path, body).wait().map_err(|e| {
-            Error::from(format!("Error waiting for HTTP response: {}", e))
-        })
+        let result = self.run(&method, path, || self.request_de_async(method.clone(), path, body).wait());
+        result.map_err(waiting_error)
     }
 
     fn request_void<U: Serialize>(&self, method: Method, path: &str, body: Option<&U>) -> Result<()> {
-        self.request_void_async(method, path, body).wait().map_err(|e| {
-            Error::from(format!("Error waiting for HTTP response: {}", e))
-        })
+        let result = self.run(&method, path, || self.request_void_async(method.clone(), path, body).wait());
+        result.map_err(waiting_error)
     }
 
     fn request_de_async<T, U: Serialize>(&self, method: Method, path: &str, body: Option<&U>) -> FutureResult<T>
@@ -140,7 +339,7 @@ impl HTTPClient {
     {
         Box::new(
             self.request_async(method, path, body)
-                .or_else(|_| Err("HTTP Request was cancelled".into()))
+                .or_else(|_| Err(ErrorKind::HttpCancelled.into()))
                 .and_then(|res| {
                     res.and_then(|res| {
                         serde_json::from_slice::<T>(&res.data).map_err(|e| {
@@ -152,10 +351,29 @@ impl HTTPClient {
         )
     }
 
+    fn request_page_async<T>(&self, path: &str) -> FutureResult<(T, Option<String>)>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        Box::new(
+            self.request_async::<()>(Method::Get, path, None)
+                .or_else(|_| Err(ErrorKind::HttpCancelled.into()))
+                .and_then(|res| {
+                    res.and_then(|res| {
+                        let next_page = res.next_page.clone();
+                        serde_json::from_slice::<T>(&res.data).map(|data| (data, next_page)).map_err(|e| {
+                            format!("Error parsing response: {}\n---\n{}\n---", e, String::from_utf8_lossy(&res.data))
+                                .into()
+                        })
+                    })
+                }),
+        )
+    }
+
     fn request_void_async<U: Serialize>(&self, method: Method, path: &str, body: Option<&U>) -> FutureResult<()> {
         Box::new(
             self.request_async(method, path, body)
-                .or_else(|_| Err("HTTP Request was cancelled".into()))
+                .or_else(|_| Err(ErrorKind::HttpCancelled.into()))
                 .and_then(|res| res.map(|_| ())),
         )
     }
@@ -186,6 +404,8 @@ impl HTTPClient {
         };
 
         let headers = self.headers.clone();
+        let cache = if method == Method::Get { self.cache.clone() } else { None };
+        let cache_key = url.to_string();
 
         let mut req = Request::new(method, url);
         req.headers_mut().set(UserAgent::new("octobot"));
@@ -193,6 +413,9 @@ impl HTTPClient {
         for (k, v) in &headers {
             req.headers_mut().set_raw(k.clone(), v.clone());
         }
+        if let Some(etag) = cache.as_ref().and_then(|c| c.etag(&cache_key)) {
+            req.headers_mut().set_raw("If-None-Match", etag);
+        }
 
         if let Some(body) = body {
             let body_json = match serde_json::to_string(&body) {
@@ -208,17 +431,18 @@ impl HTTPClient {
         let path = path.to_string();
 
         self.core_remote.spawn(move |handle| {
-            // TODO: I wonder if these objects are expensive to create and we should be sharing them across requests?
-            let https = HttpsConnector::new(4, &handle);
-            let client = hyper::Client::configure().connector(https).build(&handle);
-
-            client
+            shared_client(handle)
                 .request(req)
                 .map_err(|e| {
                     error!("Error in HTTP request: {}", e);
                 })
                 .and_then(|res| {
                     let status = res.status();
+                    let rate_limit_reset = rate_limit_reset(res.headers(), time::get_time().sec);
+                    let next_page = next_page_link(res.headers());
+                    let etag = res.headers().get_raw("etag").and_then(|h| h.one()).and_then(|v| {
+                        str::from_utf8(v).ok().map(|v| v.to_string())
+                    });
                     res.body()
                         .concat2()
                         .map_err(|e| {
@@ -226,17 +450,30 @@ impl HTTPClient {
                         })
                         .map(move |buffer| {
                             debug!("Response: HTTP {}\n---\n{}\n---", status, String::from_utf8_lossy(&buffer));
-                            if !status.is_success() {
-                                send_future(Err(
-                                    format!(
-                                        "Failed request to {}: HTTP {}\n---\n{}\n---",
-                                        path,
-                                        status,
-                                        String::from_utf8_lossy(&buffer)
-                                    ).into(),
-                                ));
+                            if status == StatusCode::NotModified {
+                                match cache.as_ref().and_then(|c| c.get(&cache_key)) {
+                                    Some(cached) => {
+                                        send_future(Ok(InternalResp {
+                                            data: hyper::Chunk::from(cached.data),
+                                            next_page: cached.next_page,
+                                        }))
+                                    }
+                                    None => {
+                                        let msg = format!("Got {} for {}, but it isn't cached", status, path);
+                                        send_future(Err(msg.into()))
+                                    }
+                                }
+                            } else if !status.is_success() {
+                                let body = String::from_utf8_lossy(&buffer).into_owned();
+                                send_future(Err(ErrorKind::HttpStatus(path, status, rate_limit_reset, body).into()));
                             } else {
-                                send_future(Ok(InternalResp { data: buffer }));
+                                if let (Some(cache), Some(etag)) = (cache, etag) {
+                                    cache.put(&cache_key, etag, buffer.to_vec(), next_page.clone());
+                                }
+                                send_future(Ok(InternalResp {
+                                    data: buffer,
+                                    next_page: next_page,
+                                }));
                             }
                         })
                 })
@@ -245,3 +482,199 @@ impl HTTPClient {
         rx
     }
 }
+
+thread_local! {
+    static CLIENT: RefCell<Option<(CoreId, hyper::Client<HttpsConnector>)>> = RefCell::new(None);
+}
+
+// One client per reactor, so that its connections are kept alive and reused by later requests instead of doing
+// a new TLS handshake (and starting new DNS threads) for each of them.
+fn shared_client(handle: &Handle) -> hyper::Client<HttpsConnector> {
+    CLIENT.with(|client| {
+        let mut client = client.borrow_mut();
+        if client.as_ref().map_or(true, |&(id, _)| id != handle.id()) {
+            let https = HttpsConnector::new(4, handle);
+            *client = Some((handle.id(), hyper::Client::configure().connector(https).build(handle)));
+        }
+        client.as_ref().unwrap().1.clone()
+    })
+}
+
+// when a rate limited request may be tried again: github sends Retry-After for its abuse limits, and
+// X-RateLimit-Reset once the hourly limit is used up.
+fn rate_limit_reset(headers: &hyper::Headers, now: i64) -> Option<i64> {
+    let number = |name: &str| {
+        headers.get_raw(name).and_then(|h| h.one()).and_then(|v| str::from_utf8(v).ok()).and_then(|v| {
+            v.trim().parse::<i64>().ok()
+        })
+    };
+
+    if let Some(secs) = number("retry-after") {
+        return Some(now + secs);
+    }
+    match (number("x-ratelimit-remaining"), number("x-ratelimit-reset")) {
+        (Some(0), Some(reset)) => Some(reset),
+        _ => None,
+    }
+}
+
+// keeps the status of failed requests, which callers check for e.g. a 404
+fn waiting_error(e: Error) -> Error {
+    let is_status = match *e.kind() {
+        ErrorKind::HttpStatus(..) => true,
+        _ => false,
+    };
+    if is_status { e } else { Error::from(format!("Error waiting for HTTP response: {}", e)) }
+}
+
+// e.g. `<https://api.github.com/repositories/1/pulls?page=2>; rel="next", <...?page=5>; rel="last"`
+fn next_page_link(headers: &hyper::Headers) -> Option<String> {
+    let value = headers.get_raw("link").and_then(|h| h.one()).and_then(|v| str::from_utf8(v).ok())?;
+    value
+        .split(',')
+        .map(|link| link.split(';').map(|p| p.trim()).collect::<Vec<_>>())
+        .find(|parts| parts[1..].iter().any(|p| *p == "rel=\"next\""))
+        .and_then(|parts| if parts[0].starts_with('<') && parts[0].ends_with('>') {
+            Some(parts[0][1..parts[0].len() - 1].to_string())
+        } else {
+            None
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::new(&RetryConfig {
+            attempts: Some(3),
+            backoff_ms: Some(100),
+            max_backoff_ms: Some(150),
+            jitter: Some(false),
+            max_rate_limit_wait_secs: Some(60),
+        })
+    }
+
+    fn status(status: StatusCode, reset: Option<i64>) -> Error {
+        ErrorKind::HttpStatus("/path".into(), status, reset, String::new()).into()
+    }
+
+    #[test]
+    fn test_retry_server_errors() {
+        let policy = policy();
+        let err = status(StatusCode::BadGateway, None);
+        assert_eq!(Some(Duration::from_millis(100)), policy.retry_delay(&Method::Get, &err, 1, 0));
+        assert_eq!(Some(Duration::from_millis(150)), policy.retry_delay(&Method::Put, &err, 2, 0));
+        assert_eq!(None, policy.retry_delay(&Method::Get, &err, 3, 0));
+        assert_eq!(None, policy.retry_delay(&Method::Post, &err, 1, 0));
+
+        let cancelled: Error = ErrorKind::HttpCancelled.into();
+        assert_eq!(Some(Duration::from_millis(100)), policy.retry_delay(&Method::Delete, &cancelled, 1, 0));
+        assert_eq!(None, policy.retry_delay(&Method::Post, &cancelled, 1, 0));
+
+        assert_eq!(None, policy.retry_delay(&Method::Get, &status(StatusCode::NotFound, None), 1, 0));
+        assert_eq!(None, policy.retry_delay(&Method::Get, &"other".into(), 1, 0));
+    }
+
+    #[test]
+    fn test_retry_rate_limits() {
+        let policy = policy();
+        let limited = status(StatusCode::Forbidden, Some(1030));
+        assert_eq!(Some(Duration::from_secs(31)), policy.retry_delay(&Method::Post, &limited, 1, 1000));
+        assert_eq!(Some(Duration::from_secs(1)), policy.retry_delay(&Method::Get, &limited, 1, 2000));
+        assert_eq!(None, policy.retry_delay(&Method::Get, &limited, 1, 900));
+        assert_eq!(None, policy.retry_delay(&Method::Get, &limited, 3, 1000));
+
+        let abuse = status(StatusCode::TooManyRequests, Some(1010));
+        assert_eq!(Some(Duration::from_secs(11)), policy.retry_delay(&Method::Get, &abuse, 1, 1000));
+
+        // a 403 without rate limit headers is a permissions problem
+        assert_eq!(None, policy.retry_delay(&Method::Get, &status(StatusCode::Forbidden, None), 1, 1000));
+    }
+
+    #[test]
+    fn test_retry_jitter() {
+        let policy = RetryPolicy::new(&RetryConfig { backoff_ms: Some(1000), ..RetryConfig::default() });
+        let err = status(StatusCode::ServiceUnavailable, None);
+        for _ in 0..20 {
+            let delay = policy.retry_delay(&Method::Get, &err, 2, 0).unwrap();
+            assert!(delay >= Duration::from_millis(1000) && delay <= Duration::from_millis(2000), "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn test_run_retries() {
+        let policy = RetryPolicy::new(&RetryConfig { backoff_ms: Some(1), ..RetryConfig::default() });
+        let mut calls = 0;
+        let result: Result<u32> = policy.run(&Method::Get, "/path", || {
+            calls += 1;
+            if calls < 3 {
+                Err(status(StatusCode::InternalServerError, None))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(3, result.unwrap());
+
+        let mut calls = 0;
+        let result: Result<u32> = RetryPolicy::none().run(&Method::Get, "/path", || {
+            calls += 1;
+            Err(status(StatusCode::InternalServerError, None))
+        });
+        assert!(result.is_err());
+        assert_eq!(1, calls);
+    }
+
+    #[test]
+    fn test_rate_limit_reset() {
+        let mut headers = hyper::Headers::new();
+        assert_eq!(None, rate_limit_reset(&headers, 1000));
+
+        headers.set_raw("X-RateLimit-Remaining", "10");
+        headers.set_raw("X-RateLimit-Reset", "2000");
+        assert_eq!(None, rate_limit_reset(&headers, 1000));
+
+        headers.set_raw("X-RateLimit-Remaining", "0");
+        assert_eq!(Some(2000), rate_limit_reset(&headers, 1000));
+
+        headers.set_raw("Retry-After", "30");
+        assert_eq!(Some(1030), rate_limit_reset(&headers, 1000));
+    }
+
+    #[test]
+    fn test_next_page_link() {
+        let mut headers = hyper::Headers::new();
+        assert_eq!(None, next_page_link(&headers));
+
+        headers.set_raw(
+            "Link",
+            "<https://api.github.com/repositories/1/pulls?page=3>; rel=\"next\", \
+             <https://api.github.com/repositories/1/pulls?page=5>; rel=\"last\"",
+        );
+        assert_eq!(Some("https://api.github.com/repositories/1/pulls?page=3".to_string()), next_page_link(&headers));
+
+        headers.set_raw("Link", "<https://api.github.com/repositories/1/pulls?page=1>; rel=\"first\"");
+        assert_eq!(None, next_page_link(&headers));
+    }
+
+    #[test]
+    fn test_response_cache() {
+        let cache = ResponseCache::new(2);
+        assert_eq!(None, cache.etag("/a"));
+
+        cache.put("/a", "\"1\"".into(), b"a".to_vec(), None);
+        cache.put("/b", "\"2\"".into(), b"b".to_vec(), Some("/b?page=2".into()));
+        assert_eq!(Some("\"1\"".to_string()), cache.etag("/a"));
+        assert_eq!(Some("/b?page=2".to_string()), cache.get("/b").unwrap().next_page);
+
+        // /a was used the longest ago
+        cache.put("/c", "\"3\"".into(), b"c".to_vec(), None);
+        assert_eq!(2, cache.len());
+        assert_eq!(None, cache.etag("/a"));
+
+        cache.put("/b", "\"4\"".into(), b"bb".to_vec(), None);
+        assert_eq!(2, cache.len());
+        assert_eq!(b"bb".to_vec(), cache.get("/b").unwrap().data);
+        assert_eq!(Some("\"3\"".to_string()), cache.etag("/c"));
+    }
+}
@@ -1,4 +1,4 @@
-use regex::Regex;
+use regex::{Captures, Regex};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use version;
@@ -8,6 +8,7 @@ use errors::*;
 use github::{Commit, CommitLike, PullRequest, PushCommit};
 use jira;
 use jira::Transition;
+use util;
 
 fn get_jira_keys(strings: Vec<String>, projects: &Vec<String>) -> Vec<String> {
     let re = Regex::new(r"\b([A-Z]+-[0-9]+)\b").unwrap();
@@ -30,6 +31,45 @@ fn get_jira_keys(strings: Vec<String>, projects: &Vec<String>) -> Vec<String> {
     all_keys
 }
 
+// Turns the keys of the given projects in text into slack links to the issues. Keys already in a link, e.g. in its
+// url, or in `code` are left alone.
+pub fn link_jira_keys(text: &str, projects: &Vec<String>, jira_base: &str) -> String {
+    let re = Regex::new(r"\b([A-Z]+-[0-9]+)\b").unwrap();
+    let link = |plain: &str| {
+        re.replace_all(plain, |c: &Captures| {
+            let key = &c[1];
+            if projects.iter().any(|p| p == get_jira_project(key)) {
+                util::make_link(&format!("{}/browse/{}", jira_base, key), key)
+            } else {
+                key.to_string()
+            }
+        }).into_owned()
+    };
+
+    let mut linked = String::new();
+    let mut plain = String::new();
+    let mut closing: Option<char> = None;
+    for c in text.chars() {
+        match closing {
+            Some(end) => {
+                linked.push(c);
+                if c == end {
+                    closing = None;
+                }
+            }
+            None if c == '<' || c == '`' => {
+                linked += &link(&plain);
+                plain.clear();
+                linked.push(c);
+                closing = Some(if c == '<' { '>' } else { '`' });
+            }
+            None => plain.push(c),
+        }
+    }
+    linked += &link(&plain);
+    linked
+}
+
 fn get_fixed_jira_keys<T: CommitLike>(commits: &Vec<T>, projects: &Vec<String>) -> Vec<String> {
     // Fix [ABC-123][OTHER-567], [YEAH-999]
     let re = Regex::new(r"(?i)(?:Fix(?:es|ed)?):?\s*(?-i)((\[?([A-Z]+-[0-9]+)(?:\]|\b)[\s,]*)+)")
@@ -399,6 +439,21 @@ mod tests {
         assert_eq!(vec!["KEY-7"], get_referenced_jira_keys(&vec![commit.clone()], &projects));
     }
 
+    #[test]
+    pub fn test_link_jira_keys() {
+        let projects = vec!["SER".to_string()];
+        let base = "https://the-jira";
+        assert_eq!(
+            "Fixes <https://the-jira/browse/SER-1|SER-1> but not OTHER-2",
+            link_jira_keys("Fixes SER-1 but not OTHER-2", &projects, base)
+        );
+        assert_eq!(
+            "<http://the-pr/SER-1|PR for SER-1>, `SER-2`, and <https://the-jira/browse/SER-3|SER-3>",
+            link_jira_keys("<http://the-pr/SER-1|PR for SER-1>, `SER-2`, and SER-3", &projects, base)
+        );
+        assert_eq!("SER-1 here", link_jira_keys("SER-1 here", &vec![], base));
+    }
+
     #[test]
     pub fn test_get_jira_keys_alt_format() {
         let projects = vec!["KEY".to_string(), "OTHER".to_string()];
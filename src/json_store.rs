@@ -0,0 +1,228 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+
+use cluster::Cluster;
+use errors::*;
+
+// A small piece of state that is kept in memory and (optionally) persisted to a json file after
+// every modification so that it survives restarts.
+pub struct JsonStore<T> {
+    file: Option<PathBuf>,
+    data: Mutex<T>,
+    // the cluster that keeps the data instead, and its key there
+    cluster: Option<(Arc<Cluster>, String)>,
+}
+
+impl<T> JsonStore<T>
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    pub fn new(file: Option<PathBuf>) -> JsonStore<T> {
+        let data = match file {
+            Some(ref f) => {
+                match load(f) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        error!("Error loading state from {:?}: {}", f, e);
+                        T::default()
+                    }
+                }
+            }
+            None => T::default(),
+        };
+
+        JsonStore {
+            file: file,
+            data: Mutex::new(data),
+            cluster: None,
+        }
+    }
+
+    // Keeps the data in the cluster's redis as `key`, so that every node reads and changes the same data. The file,
+    // if any, still gets written: it's what this node goes on with while redis can't be reached.
+    pub fn with_cluster(self, cluster: Option<Arc<Cluster>>, key: &str) -> JsonStore<T> {
+        let mut store = self;
+        store.cluster = cluster.map(|c| (c, key.to_string()));
+        store
+    }
+
+    pub fn in_memory() -> JsonStore<T> {
+        JsonStore::new(None)
+    }
+
+    pub fn read<R, F>(&self, func: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let mut data = self.data.lock().unwrap();
+        self.refresh(&mut *data);
+        func(&*data)
+    }
+
+    // modify the data and save the results
+    pub fn write<R, F>(&self, func: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let mut data = self.data.lock().unwrap();
+        // other nodes' changes to the same data wait until this one is back in redis
+        let locked = match self.cluster {
+            Some((ref cluster, ref key)) => {
+                match cluster.lock(key) {
+                    Ok(()) => Some((cluster, key)),
+                    Err(e) => {
+                        error!("Error locking {} in the cluster, changing this node's copy: {}", key, e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        if locked.is_some() {
+            self.refresh(&mut *data);
+        }
+
+        let result = func(&mut *data);
+
+        if let Some((cluster, key)) = locked {
+            let stored = serde_json::to_string(&*data)
+                .map_err(|e| Error::from(format!("Error serializing json: {}", e)))
+                .and_then(|json| cluster.store(key, &json));
+            if let Err(e) = stored {
+                error!("Error saving {} to the cluster: {}", key, e);
+            }
+            if let Err(e) = cluster.unlock(key) {
+                error!("Error unlocking {} in the cluster: {}", key, e);
+            }
+        }
+        if let Some(ref file) = self.file {
+            if let Err(e) = save(file, &*data) {
+                error!("Error saving state to {:?}: {}", file, e);
+            }
+        }
+        result
+    }
+
+    // replaces the data with the cluster's copy, if it has one
+    fn refresh(&self, data: &mut T) {
+        let (cluster, key) = match self.cluster {
+            Some((ref cluster, ref key)) => (cluster, key),
+            None => return,
+        };
+        match cluster.get(key) {
+            Ok(Some(json)) => {
+                match serde_json::from_str(&json) {
+                    Ok(d) => *data = d,
+                    Err(e) => error!("Error parsing {} from the cluster: {}", key, e),
+                }
+            }
+            Ok(None) => (),
+            Err(e) => error!("Error reading {} from the cluster, using this node's copy: {}", key, e),
+        }
+    }
+}
+
+fn load<T: DeserializeOwned + Default>(file: &PathBuf) -> Result<T> {
+    if !file.exists() {
+        return Ok(T::default());
+    }
+
+    let mut contents = String::new();
+    fs::File::open(file)?.read_to_string(&mut contents)?;
+
+    serde_json::from_str(&contents).map_err(|e| Error::from(format!("Error parsing json: {}", e)))
+}
+
+fn save<T: Serialize>(file: &PathBuf, data: &T) -> Result<()> {
+    let json = serde_json::to_string_pretty(data).map_err(|e| Error::from(format!("Error serializing json: {}", e)))?;
+
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tmp_file = file.with_extension("tmp");
+    fs::File::create(&tmp_file)?.write_all(json.as_bytes())?;
+    fs::rename(&tmp_file, file)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    extern crate tempdir;
+    use self::tempdir::TempDir;
+
+    #[test]
+    fn test_in_memory() {
+        let store: JsonStore<Vec<u32>> = JsonStore::in_memory();
+        store.write(|v| v.push(1));
+        store.write(|v| v.push(2));
+
+        assert_eq!(vec![1, 2], store.read(|v| v.clone()));
+    }
+
+    #[test]
+    fn test_cluster() {
+        use config::ClusterConfig;
+        use cluster::{self, Reply};
+        use std::io::{BufReader, Write};
+        use std::net::TcpListener;
+        use std::thread;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut conn = BufReader::new(stream);
+            let mut commands = vec![];
+            // lock, get, store, unlock, then another node's changes
+            for reply in vec!["+OK\r\n", "$-1\r\n", "+OK\r\n", ":1\r\n", "$5\r\n[1,7]\r\n"] {
+                match cluster::read_reply(&mut conn).unwrap() {
+                    Reply::Array(args) => commands.push(args),
+                    other => panic!("Expected a command, got {:?}", other),
+                }
+                conn.get_mut().write_all(reply.as_bytes()).unwrap();
+            }
+            commands
+        });
+
+        let cluster = Arc::new(
+            Cluster::new(&ClusterConfig {
+                redis_url: format!("redis://{}", addr),
+                node_id: Some("node-1".into()),
+            }).unwrap(),
+        );
+        let store: JsonStore<Vec<u32>> = JsonStore::in_memory().with_cluster(Some(cluster), "numbers");
+        store.write(|v| v.push(1));
+        assert_eq!(vec![1, 7], store.read(|v| v.clone()));
+
+        let commands = server.join().unwrap();
+        let bulk = |s: &str| Reply::Bulk(Some(s.into()));
+        assert_eq!(vec![bulk("GET"), bulk("octobot:numbers")], commands[1]);
+        assert_eq!(vec![bulk("SET"), bulk("octobot:numbers"), bulk("[1]")], commands[2]);
+    }
+
+    #[test]
+    fn test_persisted() {
+        let dir = TempDir::new("json_store.rs").expect("create temp dir for json_store.rs test");
+        let file = dir.path().join("sub").join("state.json");
+
+        {
+            let store: JsonStore<HashMap<String, u32>> = JsonStore::new(Some(file.clone()));
+            assert_eq!(0, store.read(|m| m.len()));
+            store.write(|m| m.insert("a".into(), 1));
+        }
+
+        let store: JsonStore<HashMap<String, u32>> = JsonStore::new(Some(file.clone()));
+        assert_eq!(Some(1), store.read(|m| m.get("a").map(|v| *v)));
+    }
+}
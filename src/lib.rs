@@ -8,10 +8,12 @@ extern crate futures;
 extern crate hyper;
 extern crate hyper_rustls;
 extern crate ldap3;
+extern crate libc;
 #[macro_use]
 extern crate maplit;
 extern crate regex;
 extern crate ring;
+extern crate rusqlite;
 extern crate rustc_serialize;
 extern crate rustls;
 extern crate serde;
@@ -21,7 +23,9 @@ extern crate threadpool;
 extern crate tokio_core;
 extern crate toml;
 extern crate unidiff;
+extern crate untrusted;
 extern crate url;
+extern crate webpki;
 extern crate tokio_proto;
 extern crate tokio_rustls;
 extern crate time;
@@ -31,26 +35,71 @@ extern crate log;
 #[macro_use]
 extern crate serde_derive;
 
+pub mod audit;
+pub mod auto_merge;
+pub mod bitbucket;
+pub mod branch_updates;
+pub mod changelog;
+pub mod cluster;
+pub mod codeowners;
 pub mod config;
+pub mod config_db;
+pub mod config_watcher;
+pub mod conflicts;
+pub mod conventions;
+pub mod deliveries;
 pub mod diffs;
+pub mod digest;
+pub mod error_reporting;
+pub mod event_export;
+pub mod event_rules;
 pub mod dir_pool;
 pub mod force_push;
 pub mod git;
 pub mod git_clone_manager;
 pub mod github;
+pub mod gitlab;
 pub mod http_client;
 pub mod ldap_auth;
 pub mod jira;
+pub mod json_store;
+pub mod merge_queue;
+pub mod messages;
 pub mod messenger;
+#[cfg(test)]
+mod mock_http;
+pub mod pagerduty;
+pub mod path_labels;
+pub mod pr_commands;
+pub mod pr_dependencies;
 pub mod pr_merge;
+pub mod pr_threads;
+pub mod quiet_hours;
+pub mod release_branches;
+pub mod release_notes;
+pub mod repo_hooks;
 pub mod repos;
 pub mod repo_version;
+pub mod review_reminders;
+pub mod review_status;
+pub mod reviewers;
 pub mod server;
+pub mod size_labels;
 pub mod slack;
+pub mod slack_api;
+pub mod slack_retries;
+pub mod slack_users;
+pub mod stale_prs;
+pub mod teams;
+pub mod tracing;
 pub mod users;
 pub mod util;
 pub mod version;
+pub mod version_bumps;
+pub mod webhooks;
+pub mod weekly_metrics;
 pub mod worker;
+pub mod workflow_alerts;
 
 pub mod errors {
     // Create the Error, ErrorKind, ResultExt, and Result types
@@ -58,8 +107,22 @@ pub mod errors {
         foreign_links {
             Fmt(::std::fmt::Error);
             Io(::std::io::Error);
+            Sqlite(::rusqlite::Error);
             Url(::url::ParseError);
         }
+
+        errors {
+            // rate_limit_reset: when (unix seconds) the request may be tried again, if it was rate limited
+            HttpStatus(path: String, status: ::hyper::StatusCode, rate_limit_reset: Option<i64>, body: String) {
+                description("HTTP request failed")
+                display("Failed request to {}: HTTP {}\n---\n{}\n---", path, status, body)
+            }
+            // the connection failed, or went away before there was a response
+            HttpCancelled {
+                description("HTTP Request was cancelled")
+                display("HTTP Request was cancelled")
+            }
+        }
     }
 }
 
@@ -0,0 +1,355 @@
+use std::sync::Arc;
+
+use config::Config;
+use github;
+use github::api::Session;
+use json_store::JsonStore;
+use messenger::{Messenger, MessengerFactory};
+use slack::{SlackAttachment, SlackAttachmentBuilder};
+use worker;
+
+pub const QUEUE_LABEL: &'static str = "queue-for-merge";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct QueueEntry {
+    pub number: u32,
+    pub title: String,
+    pub html_url: String,
+    pub user: github::User,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct BranchQueue {
+    repo: github::Repo,
+    branch: String,
+    entries: Vec<QueueEntry>,
+}
+
+// All pull requests queued for merge, grouped by repo and target branch.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MergeQueue {
+    queues: Vec<BranchQueue>,
+}
+
+impl QueueEntry {
+    pub fn new(pull_request: &github::PullRequest) -> QueueEntry {
+        QueueEntry {
+            number: pull_request.number,
+            title: pull_request.title.clone(),
+            html_url: pull_request.html_url.clone(),
+            user: pull_request.user.clone(),
+        }
+    }
+}
+
+impl MergeQueue {
+    pub fn new() -> MergeQueue {
+        MergeQueue { queues: vec![] }
+    }
+
+    // returns the 1-based position of the pull request in its queue
+    pub fn add(&mut self, repo: &github::Repo, branch: &str, entry: QueueEntry) -> usize {
+        let index = match self.queues.iter().position(|q| is_queue_for(q, repo, branch)) {
+            Some(i) => i,
+            None => {
+                self.queues.push(BranchQueue {
+                    repo: repo.clone(),
+                    branch: branch.to_string(),
+                    entries: vec![],
+                });
+                self.queues.len() - 1
+            }
+        };
+
+        let entries = &mut self.queues[index].entries;
+        if let Some(pos) = entries.iter().position(|e| e.number == entry.number) {
+            return pos + 1;
+        }
+
+        entries.push(entry);
+        entries.len()
+    }
+
+    // returns the branch the pull request was queued for, if any
+    pub fn remove(&mut self, repo: &github::Repo, number: u32) -> Option<String> {
+        let mut removed = None;
+        for queue in self.queues.iter_mut().filter(|q| q.repo.html_url == repo.html_url) {
+            let len = queue.entries.len();
+            queue.entries.retain(|e| e.number != number);
+            if queue.entries.len() != len {
+                removed = Some(queue.branch.clone());
+            }
+        }
+        self.queues.retain(|q| !q.entries.is_empty());
+
+        removed
+    }
+
+    pub fn front(&self, repo: &github::Repo, branch: &str) -> Option<QueueEntry> {
+        self.entries(repo, branch).into_iter().next()
+    }
+
+    pub fn entries(&self, repo: &github::Repo, branch: &str) -> Vec<QueueEntry> {
+        match self.queues.iter().find(|q| is_queue_for(q, repo, branch)) {
+            Some(q) => q.entries.clone(),
+            None => vec![],
+        }
+    }
+
+    pub fn branches(&self, repo: &github::Repo) -> Vec<String> {
+        self.queues.iter().filter(|q| q.repo.html_url == repo.html_url).map(|q| q.branch.clone()).collect()
+    }
+}
+
+fn is_queue_for(queue: &BranchQueue, repo: &github::Repo, branch: &str) -> bool {
+    queue.repo.html_url == repo.html_url && queue.branch == branch
+}
+
+enum EntryState {
+    Waiting,
+    Merged,
+    Dropped(Option<String>),
+}
+
+// Try to move the front of the queue along: update it if it is behind, wait for its statuses,
+// and merge it when everything is green. Keep going until something needs to wait.
+pub fn process_queue(
+    github: &Session,
+    queue: &JsonStore<MergeQueue>,
+    messenger: &Messenger,
+    repo: &github::Repo,
+    branch: &str,
+    merge_method: &str,
+) {
+    loop {
+        let entry = match queue.read(|q| q.front(repo, branch)) {
+            Some(e) => e,
+            None => return,
+        };
+
+        match advance_entry(github, repo, &entry, merge_method) {
+            EntryState::Waiting => return,
+            EntryState::Merged => {
+                info!("Merged PR #{} from merge queue for {}", entry.number, branch);
+                queue.write(|q| q.remove(repo, entry.number));
+            }
+            EntryState::Dropped(reason) => {
+                queue.write(|q| q.remove(repo, entry.number));
+                if let Some(reason) = reason {
+                    messenger.send_to_owner(
+                        &format!("Pull Request removed from merge queue for {}", branch),
+                        &vec![
+                            pr_attachment(&entry)
+                                .text(reason)
+                                .color("danger")
+                                .build(),
+                        ],
+                        &entry.user,
+                        repo,
+                        Some(branch),
+                    );
+                }
+            }
+        };
+
+        send_queue_positions(queue, messenger, repo, branch);
+    }
+}
+
+fn advance_entry(github: &Session, repo: &github::Repo, entry: &QueueEntry, merge_method: &str) -> EntryState {
+    let owner = repo.owner.login();
+
+    let pr = match github.get_pull_request(owner, &repo.name, entry.number) {
+        Ok(pr) => pr,
+        Err(e) => {
+            error!("Error looking up queued PR #{}: {}", entry.number, e);
+            return EntryState::Waiting;
+        }
+    };
+
+    if !pr.is_open() {
+        return EntryState::Dropped(None);
+    }
+
+    if pr.mergeable == Some(false) {
+        return EntryState::Dropped(Some("Pull Request has merge conflicts".into()));
+    }
+
+    if pr.mergeable_state.as_ref().map_or(false, |s| s == "behind") {
+        return match github.update_pull_request_branch(owner, &repo.name, pr.number, &pr.head.sha) {
+            Ok(_) => EntryState::Waiting,
+            Err(e) => EntryState::Dropped(Some(format!("Unable to update branch: {}", e))),
+        };
+    }
+
+    let status = match github.get_combined_status(owner, &repo.name, &pr.head.sha) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Error looking up status for queued PR #{}: {}", entry.number, e);
+            return EntryState::Waiting;
+        }
+    };
+
+    if status.is_failure() {
+        return EntryState::Dropped(Some(format!("Commit status is {}", status.state)));
+    }
+    if !status.is_success() {
+        return EntryState::Waiting;
+    }
+
+    match github.merge_pull_request(owner, &repo.name, pr.number, &pr.head.sha, merge_method) {
+        Ok(_) => EntryState::Merged,
+        Err(e) => EntryState::Dropped(Some(format!("{}", e))),
+    }
+}
+
+fn pr_attachment(entry: &QueueEntry) -> SlackAttachmentBuilder {
+    let mut attach = SlackAttachmentBuilder::new("");
+    attach
+        .title(format!("Pull Request #{}: \"{}\"", entry.number, entry.title))
+        .title_link(entry.html_url.as_str());
+    attach
+}
+
+fn send_queue_positions(queue: &JsonStore<MergeQueue>, messenger: &Messenger, repo: &github::Repo, branch: &str) {
+    let entries = queue.read(|q| q.entries(repo, branch));
+    if entries.is_empty() {
+        return;
+    }
+
+    let attachments: Vec<SlackAttachment> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            SlackAttachmentBuilder::new("")
+                .title(format!("{}. Pull Request #{}: \"{}\"", i + 1, e.number, e.title))
+                .title_link(e.html_url.as_str())
+                .build()
+        })
+        .collect();
+
+    messenger.send_to_channel(&format!("Merge queue for {} updated", branch), &attachments, repo, Some(branch));
+}
+
+#[derive(Debug)]
+pub enum MergeQueueRequest {
+    Add(github::Repo, github::PullRequest),
+    Remove(github::Repo, github::PullRequest),
+    Check(github::Repo),
+}
+
+pub fn add_req(repo: &github::Repo, pull_request: &github::PullRequest) -> MergeQueueRequest {
+    MergeQueueRequest::Add(repo.clone(), pull_request.clone())
+}
+
+pub fn remove_req(repo: &github::Repo, pull_request: &github::PullRequest) -> MergeQueueRequest {
+    MergeQueueRequest::Remove(repo.clone(), pull_request.clone())
+}
+
+pub fn check_req(repo: &github::Repo) -> MergeQueueRequest {
+    MergeQueueRequest::Check(repo.clone())
+}
+
+struct Runner {
+    config: Arc<Config>,
+    github_session: Arc<Session>,
+    messengers: MessengerFactory,
+    queue: JsonStore<MergeQueue>,
+}
+
+// Note: merges are done serially on the worker thread on purpose: each merge changes the base
+// branch for the next pull request in line.
+pub fn new_worker(
+    config: Arc<Config>,
+    github_session: Arc<Session>,
+    messengers: MessengerFactory,
+) -> worker::Worker<MergeQueueRequest> {
+    let queue = JsonStore::new(config.host_data_file(github_session.github_host(), "merge_queue.json"));
+    worker::Worker::new(
+        "merge-queue",
+        Runner {
+            config: config,
+            github_session: github_session,
+            messengers: messengers,
+            queue: queue,
+        },
+    )
+}
+
+impl Runner {
+    fn process(&self, messenger: &Messenger, repo: &github::Repo, branch: &str) {
+        let merge_method = self.config.repos().merge_strategy(repo, branch);
+        process_queue(&*self.github_session, &self.queue, messenger, repo, branch, &merge_method);
+    }
+}
+
+impl worker::Runner<MergeQueueRequest> for Runner {
+    fn handle(&self, req: MergeQueueRequest) {
+        let messenger = self.messengers.new_messenger();
+        match req {
+            MergeQueueRequest::Add(repo, pull_request) => {
+                let branch = pull_request.base.ref_name.clone();
+                let position = self.queue.write(|q| q.add(&repo, &branch, QueueEntry::new(&pull_request)));
+
+                messenger.for_pull_request(&pull_request).send_to_channel(
+                    &format!("Pull Request queued for merge into {} (position {})", branch, position),
+                    &vec![pr_attachment(&QueueEntry::new(&pull_request)).build()],
+                    &repo,
+                    Some(branch.as_str()),
+                );
+
+                self.process(&*messenger, &repo, &branch);
+            }
+            MergeQueueRequest::Remove(repo, pull_request) => {
+                if let Some(branch) = self.queue.write(|q| q.remove(&repo, pull_request.number)) {
+                    send_queue_positions(&self.queue, &*messenger, &repo, &branch);
+                    self.process(&*messenger, &repo, &branch);
+                }
+            }
+            MergeQueueRequest::Check(repo) => {
+                for branch in self.queue.read(|q| q.branches(&repo)) {
+                    self.process(&*messenger, &repo, &branch);
+                }
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(number: u32) -> QueueEntry {
+        let mut pr = github::PullRequest::new();
+        pr.number = number;
+        QueueEntry::new(&pr)
+    }
+
+    #[test]
+    fn test_merge_queue() {
+        let repo = github::Repo::parse("http://git.company.com/some-user/the-repo").unwrap();
+        let other_repo = github::Repo::parse("http://git.company.com/some-user/other-repo").unwrap();
+
+        let mut queue = MergeQueue::new();
+        assert_eq!(1, queue.add(&repo, "master", entry(1)));
+        assert_eq!(2, queue.add(&repo, "master", entry(2)));
+        assert_eq!(1, queue.add(&repo, "release/1.0", entry(3)));
+        assert_eq!(1, queue.add(&other_repo, "master", entry(4)));
+
+        // adding again keeps the same spot
+        assert_eq!(1, queue.add(&repo, "master", entry(1)));
+
+        assert_eq!(vec!["master", "release/1.0"], queue.branches(&repo));
+        assert_eq!(Some(entry(1)), queue.front(&repo, "master"));
+        assert_eq!(vec![entry(1), entry(2)], queue.entries(&repo, "master"));
+
+        assert_eq!(Some("master".to_string()), queue.remove(&repo, 1));
+        assert_eq!(None, queue.remove(&repo, 1));
+        assert_eq!(Some(entry(2)), queue.front(&repo, "master"));
+
+        // empty queues go away
+        assert_eq!(Some("release/1.0".to_string()), queue.remove(&repo, 3));
+        assert_eq!(vec!["master"], queue.branches(&repo));
+        assert_eq!(vec!["master"], queue.branches(&other_repo));
+    }
+}
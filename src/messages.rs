@@ -0,0 +1,830 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde_json;
+
+// The built-in messages that repos can reword with "message_templates", and that catalogs translate, by name.
+// Their placeholders are listed with their default wording below.
+pub const PR_OPENED: &'static str = "pull_request_opened";
+pub const PR_MERGED: &'static str = "pull_request_merged";
+pub const PR_CLOSED: &'static str = "pull_request_closed";
+pub const PR_REOPENED: &'static str = "pull_request_reopened";
+pub const PR_ASSIGNED: &'static str = "pull_request_assigned";
+pub const PR_UNASSIGNED: &'static str = "pull_request_unassigned";
+pub const PR_SUBMITTED_FOR_REVIEW: &'static str = "pull_request_submitted_for_review";
+pub const REVIEW_TITLE: &'static str = "review_title";
+pub const REVIEW_APPROVED: &'static str = "review_approved";
+pub const REVIEW_CHANGES_REQUESTED: &'static str = "review_changes_requested";
+pub const FORCE_PUSH: &'static str = "force_push";
+pub const FORCE_PUSH_COMMENT: &'static str = "force_push_comment";
+pub const PR_READY_FOR_REVIEW: &'static str = "pull_request_ready_for_review";
+pub const PR_TITLE: &'static str = "pull_request_title";
+pub const REVIEW_REQUESTED: &'static str = "review_requested";
+pub const REVIEW_REQUEST_REMOVED: &'static str = "review_request_removed";
+pub const FIELD_BRANCH: &'static str = "field_branch";
+pub const FIELD_AUTHOR: &'static str = "field_author";
+pub const FIELD_CHANGES: &'static str = "field_changes";
+pub const FIELD_LABELS: &'static str = "field_labels";
+pub const REVIEW_APPROVED_BY: &'static str = "review_approved_by";
+pub const REVIEW_CHANGES_REQUESTED_BY: &'static str = "review_changes_requested_by";
+pub const INLINE_COMMENT: &'static str = "inline_comment";
+pub const INLINE_COMMENTS: &'static str = "inline_comments";
+pub const MORE_INLINE_COMMENTS: &'static str = "more_inline_comments";
+pub const COMMENT_ON_PR: &'static str = "comment_on_pull_request";
+pub const COMMENT_ON_COMMIT: &'static str = "comment_on_commit";
+pub const COMMENT_SAID: &'static str = "comment_said";
+pub const COMMENT_LEFT: &'static str = "comment_left";
+pub const COMMENT_EDITED: &'static str = "comment_edited";
+pub const COMMENT_DELETED: &'static str = "comment_deleted";
+pub const REVIEW_EDITED: &'static str = "review_edited";
+pub const REVIEW_DELETED: &'static str = "review_deleted";
+pub const COMMENT_NOW_SAYS: &'static str = "comment_now_says";
+pub const PUSH: &'static str = "push";
+pub const DIRECT_PUSH: &'static str = "direct_push";
+pub const PUSH_MORE_COMMITS: &'static str = "push_more_commits";
+pub const ISSUE_TITLE: &'static str = "issue_title";
+pub const ISSUE_OPENED: &'static str = "issue_opened";
+pub const ISSUE_REOPENED: &'static str = "issue_reopened";
+pub const ISSUE_CLOSED: &'static str = "issue_closed";
+pub const ISSUE_ASSIGNED: &'static str = "issue_assigned";
+pub const ISSUE_LABELED: &'static str = "issue_labeled";
+pub const REPO_CHANGED: &'static str = "repository_changed";
+pub const REPO_MOVED: &'static str = "repository_moved";
+pub const REPO_CREATED: &'static str = "repository_created";
+pub const REPO_DELETED: &'static str = "repository_deleted";
+pub const REPO_ARCHIVED: &'static str = "repository_archived";
+pub const REPO_UNARCHIVED: &'static str = "repository_unarchived";
+pub const REPO_RENAMED: &'static str = "repository_renamed";
+pub const REPO_TRANSFERRED: &'static str = "repository_transferred";
+pub const REPO_PUBLICIZED: &'static str = "repository_publicized";
+pub const REPO_PRIVATIZED: &'static str = "repository_privatized";
+pub const REPOS_CONFIG_UPDATED: &'static str = "repos_config_updated";
+pub const REPOS_CONFIG_ERROR: &'static str = "repos_config_error";
+pub const SECURITY_ALERT: &'static str = "security_alert";
+pub const DEPENDABOT_ALERT: &'static str = "dependabot_alert";
+pub const CODE_SCANNING_ALERT: &'static str = "code_scanning_alert";
+pub const VULNERABILITY_ALERT: &'static str = "vulnerability_alert";
+pub const ALERT_OPENED: &'static str = "alert_opened";
+pub const ALERT_REOPENED: &'static str = "alert_reopened";
+pub const ALERT_FIXED: &'static str = "alert_fixed";
+pub const ALERT_DISMISSED: &'static str = "alert_dismissed";
+pub const ALERT_AUTO_DISMISSED: &'static str = "alert_auto_dismissed";
+pub const FIELD_SEVERITY: &'static str = "field_severity";
+pub const FIELD_PACKAGE: &'static str = "field_package";
+pub const FIELD_TOOL: &'static str = "field_tool";
+pub const TAG_CREATED: &'static str = "tag_created";
+pub const RELEASE_PUBLISHED: &'static str = "release_published";
+pub const RELEASE_EDITED: &'static str = "release_edited";
+pub const PRERELEASE_PUBLISHED: &'static str = "prerelease_published";
+pub const PRERELEASE_EDITED: &'static str = "prerelease_edited";
+pub const CHANGES_SINCE: &'static str = "changes_since";
+pub const RELEASE_BRANCH_CUT: &'static str = "release_branch_cut";
+pub const RELEASE_BRANCH_FROM: &'static str = "release_branch_from";
+pub const RELEASE_BRANCH_UNPROTECTED: &'static str = "release_branch_unprotected";
+pub const RELEASE_BRANCH_FAILED: &'static str = "release_branch_failed";
+pub const DEPLOYING: &'static str = "deploying";
+pub const DEPLOYMENT_SUCCEEDED: &'static str = "deployment_succeeded";
+pub const DEPLOYMENT_FAILED: &'static str = "deployment_failed";
+pub const DEPLOYMENT_REPLACED: &'static str = "deployment_replaced";
+pub const DEPLOYMENT_IN_PROGRESS: &'static str = "deployment_in_progress";
+pub const DEPLOYMENT_PENDING: &'static str = "deployment_pending";
+pub const DEPLOYMENT_PRS: &'static str = "deployment_pull_requests";
+pub const DEPLOYMENT_LOGS: &'static str = "deployment_logs";
+pub const AT_COMMIT: &'static str = "at_commit";
+pub const WORKFLOW_FAILED: &'static str = "workflow_failed";
+pub const WORKFLOW_FAILING: &'static str = "workflow_failing";
+pub const WORKFLOW_FIXED: &'static str = "workflow_fixed";
+pub const RERUN_FAILED_JOBS: &'static str = "rerun_failed_jobs";
+pub const UNCONFIGURED_REPO: &'static str = "unconfigured_repo";
+pub const UNCONFIGURED_REPO_ADD: &'static str = "unconfigured_repo_add";
+pub const PR_LABELS_ERROR: &'static str = "pull_request_labels_error";
+pub const OUT_OF_OFFICE: &'static str = "out_of_office";
+pub const COMMAND_NOT_ALLOWED: &'static str = "command_not_allowed";
+pub const COMMAND_PERMISSION_ERROR: &'static str = "command_permission_error";
+pub const BACKPORT_LABEL_INSTEAD: &'static str = "backport_label_instead";
+pub const BACKPORT_NOT_MERGED: &'static str = "backport_not_merged";
+pub const BACKPORTING: &'static str = "backporting";
+pub const BACKPORTING_AGAIN: &'static str = "backporting_again";
+pub const BACKPORT_CHAINED: &'static str = "backport_chained";
+pub const BACKPORT_LABELS_ERROR: &'static str = "backport_labels_error";
+pub const BACKPORT_NO_LABELS: &'static str = "backport_no_labels";
+pub const MERGE_NOT_OPEN: &'static str = "merge_not_open";
+pub const MERGE_DONE: &'static str = "merge_done";
+pub const MERGE_ERROR: &'static str = "merge_error";
+pub const UNKNOWN_COMMAND: &'static str = "unknown_command";
+
+// the messages of pull request events, from pull_request_opened to pull_request_submitted_for_review, also get
+// {number}, {title}, and {author}
+const DEFAULTS: &'static [(&'static str, &'static str)] = &[
+    (PR_OPENED, "Pull Request opened by {user}"),
+    (PR_MERGED, "Pull Request merged"),
+    (PR_CLOSED, "Pull Request closed"),
+    (PR_REOPENED, "Pull Request reopened"),
+    (PR_ASSIGNED, "Pull Request assigned to {assignees}"),
+    (PR_UNASSIGNED, "Pull Request unassigned"),
+    (PR_SUBMITTED_FOR_REVIEW, "Pull Request submitted for review to {reviewers}"),
+    // {state} is review_approved or review_changes_requested
+    (REVIEW_TITLE, "Review: {state}"),
+    (REVIEW_APPROVED, "Approved"),
+    (REVIEW_CHANGES_REQUESTED, "Changes Requested"),
+    // {summary} is e.g. "Identical diff post-rebase, 3 commit(s) removed, 3 added"
+    (FORCE_PUSH, "Force-push to Pull Request #{number}: {summary}"),
+    // the comment's first line. {result} is e.g. "Identical diff post-rebase"
+    (FORCE_PUSH_COMMENT, "Force-push detected: before: {before}, after: {after}: {result}"),
+    (PR_READY_FOR_REVIEW, "Pull Request is ready for review"),
+    // the title of the pull request's attachment
+    (PR_TITLE, "Pull Request #{number}: \"{title}\""),
+    // direct messages to the reviewer
+    (REVIEW_REQUESTED, "Your review was requested by {user}"),
+    (REVIEW_REQUEST_REMOVED, "{user} removed the request for your review"),
+    // the fields of a new pull request's attachment
+    (FIELD_BRANCH, "Branch"),
+    (FIELD_AUTHOR, "Author"),
+    (FIELD_CHANGES, "Changes"),
+    (FIELD_LABELS, "Labels"),
+    // {link} is a link to the pull request
+    (REVIEW_APPROVED_BY, "{user} approved PR \"{link}\""),
+    (REVIEW_CHANGES_REQUESTED_BY, "{user} requested changes to PR \"{link}\""),
+    (INLINE_COMMENT, "1 inline comment"),
+    (INLINE_COMMENTS, "{count} inline comments"),
+    // after the first few inline comments
+    (MORE_INLINE_COMMENTS, "… and {count} more"),
+    (COMMENT_ON_PR, "Comment on \"{link}\""),
+    // {path} is the commented file, or the commit if it's on the whole commit
+    (COMMENT_ON_COMMIT, "Comment on \"{path}\" ({commit})"),
+    (COMMENT_SAID, "{user} said:"),
+    // {inline} is inline_comment or inline_comments
+    (COMMENT_LEFT, "{user} left {inline}:"),
+    (COMMENT_EDITED, "Comment edited on \"{link}\""),
+    (COMMENT_DELETED, "Comment from {author} deleted on \"{link}\""),
+    (REVIEW_EDITED, "Review edited on \"{link}\""),
+    (REVIEW_DELETED, "Review from {author} deleted on \"{link}\""),
+    (COMMENT_NOW_SAYS, "{author} now says:"),
+    (PUSH, "{user} pushed {count} commit(s) to branch {branch}"),
+    // to a branch that only changes through pull requests
+    (DIRECT_PUSH, "{user} pushed {count} commit(s) directly to {branch}"),
+    (PUSH_MORE_COMMITS, "…and {count} more commits"),
+    (ISSUE_TITLE, "Issue #{number}: \"{title}\""),
+    (ISSUE_OPENED, "Issue opened by {user}"),
+    (ISSUE_REOPENED, "Issue reopened by {user}"),
+    (ISSUE_CLOSED, "Issue closed by {user}"),
+    (ISSUE_ASSIGNED, "Issue assigned to you by {user}"),
+    (ISSUE_LABELED, "Issue labeled {label} by {user}"),
+    // {action} is one of the repository_* actions below
+    (REPO_CHANGED, "Repository {action} by {user}"),
+    (REPO_MOVED, "Repository {action} from {previous} by {user}"),
+    (REPO_CREATED, "created"),
+    (REPO_DELETED, "deleted"),
+    (REPO_ARCHIVED, "archived"),
+    (REPO_UNARCHIVED, "unarchived"),
+    (REPO_RENAMED, "renamed"),
+    (REPO_TRANSFERRED, "transferred"),
+    (REPO_PUBLICIZED, "made public"),
+    (REPO_PRIVATIZED, "made private"),
+    (REPOS_CONFIG_UPDATED, "Updated the repos config from {previous}"),
+    (REPOS_CONFIG_ERROR, "Error updating the repos config from {previous}: {error}"),
+    // {alert} is one of the *_alert kinds and {action} one of the alert_* actions below
+    (SECURITY_ALERT, "{alert} {action}"),
+    (DEPENDABOT_ALERT, "Dependabot alert"),
+    (CODE_SCANNING_ALERT, "Code scanning alert"),
+    (VULNERABILITY_ALERT, "Vulnerability alert"),
+    (ALERT_OPENED, "opened"),
+    (ALERT_REOPENED, "reopened"),
+    (ALERT_FIXED, "fixed"),
+    (ALERT_DISMISSED, "dismissed"),
+    (ALERT_AUTO_DISMISSED, "auto-dismissed"),
+    (FIELD_SEVERITY, "Severity"),
+    (FIELD_PACKAGE, "Package"),
+    (FIELD_TOOL, "Tool"),
+    (TAG_CREATED, "Tag {tag} created by {user}"),
+    (RELEASE_PUBLISHED, "Release {name} published by {user}"),
+    (RELEASE_EDITED, "Release {name} edited by {user}"),
+    (PRERELEASE_PUBLISHED, "Pre-release {name} published by {user}"),
+    (PRERELEASE_EDITED, "Pre-release {name} edited by {user}"),
+    // {previous} is the tag before
+    (CHANGES_SINCE, "Changes since {previous}"),
+    (RELEASE_BRANCH_CUT, "{user} cut release branch {branch}"),
+    (RELEASE_BRANCH_FROM, "From {branch} at {sha}"),
+    (RELEASE_BRANCH_UNPROTECTED, "It is not protected: {error}"),
+    (RELEASE_BRANCH_FAILED, "Could not cut a release branch for {version} of {repo}"),
+    (DEPLOYING, "Deploying {ref} to {environment} ({user})"),
+    (DEPLOYMENT_SUCCEEDED, "Deployment of {ref} to {environment} succeeded"),
+    (DEPLOYMENT_FAILED, "Deployment of {ref} to {environment} failed"),
+    (DEPLOYMENT_REPLACED, "Deployment of {ref} to {environment} was replaced"),
+    (DEPLOYMENT_IN_PROGRESS, "Deployment of {ref} to {environment} is in progress"),
+    (DEPLOYMENT_PENDING, "Deployment of {ref} to {environment} is pending"),
+    (DEPLOYMENT_PRS, "Pull requests: {links}"),
+    (DEPLOYMENT_LOGS, "Logs"),
+    // a deployed or tested branch, with a link to its commit
+    (AT_COMMIT, "{ref} at {commit}"),
+    (WORKFLOW_FAILED, "Workflow {workflow} failed on {branch}"),
+    // also the summary of its PagerDuty incident
+    (WORKFLOW_FAILING, "{workflow} has failed {count} times in a row on {branch}"),
+    (WORKFLOW_FIXED, "Workflow {workflow} is back to green on {branch}"),
+    (RERUN_FAILED_JOBS, "Re-run failed jobs"),
+    (UNCONFIGURED_REPO, "Received {event} events for unconfigured repo {repo}"),
+    (UNCONFIGURED_REPO_ADD, "Add it"),
+    (PR_LABELS_ERROR, "Error getting Pull Request labels"),
+    // a direct message to the delegate of someone who is out of office
+    (OUT_OF_OFFICE, "For {user} (out of office): {message}"),
+    // the replies to octobot commands in pull request comments
+    (COMMAND_NOT_ALLOWED, "Only users with write access to {repo} can give octobot commands."),
+    (COMMAND_PERMISSION_ERROR, "Could not check your permissions: {error}"),
+    (BACKPORT_LABEL_INSTEAD, "Only merged PRs can be backported: label it {labels} instead."),
+    (BACKPORT_NOT_MERGED, "Only merged PRs can be backported."),
+    (BACKPORTING, "Backporting to {branches}."),
+    (BACKPORTING_AGAIN, "Backporting again to {branches}."),
+    // the {branches} of backporting and backporting_again when the repo chains backports
+    (BACKPORT_CHAINED, "{first}, then {rest} as each backport is merged"),
+    (BACKPORT_LABELS_ERROR, "Could not get the PR's labels: {error}"),
+    (BACKPORT_NO_LABELS, "There are no `backport-` labels to backport for."),
+    (MERGE_NOT_OPEN, "Only open PRs can be merged."),
+    (MERGE_DONE, "Merged."),
+    (MERGE_ERROR, "Could not merge: {error}"),
+    // {help} lists the commands
+    (UNKNOWN_COMMAND, "Unknown command `{command}`. {help}"),
+];
+
+const FR: &'static [(&'static str, &'static str)] = &[
+    (PR_OPENED, "Pull Request ouverte par {user}"),
+    (PR_MERGED, "Pull Request fusionnée"),
+    (PR_CLOSED, "Pull Request fermée"),
+    (PR_REOPENED, "Pull Request rouverte"),
+    (PR_ASSIGNED, "Pull Request assignée à {assignees}"),
+    (PR_UNASSIGNED, "Pull Request désassignée"),
+    (PR_SUBMITTED_FOR_REVIEW, "Pull Request soumise pour revue à {reviewers}"),
+    (REVIEW_TITLE, "Revue : {state}"),
+    (REVIEW_APPROVED, "Approuvée"),
+    (REVIEW_CHANGES_REQUESTED, "Modifications demandées"),
+    (FORCE_PUSH, "Force-push sur la Pull Request #{number} : {summary}"),
+    (FORCE_PUSH_COMMENT, "Force-push détecté : avant : {before}, après : {after} : {result}"),
+    (PR_READY_FOR_REVIEW, "Pull Request prête pour revue"),
+    (PR_TITLE, "Pull Request #{number} : \"{title}\""),
+    (REVIEW_REQUESTED, "{user} a demandé votre revue"),
+    (REVIEW_REQUEST_REMOVED, "{user} a retiré la demande de revue"),
+    (FIELD_BRANCH, "Branche"),
+    (FIELD_AUTHOR, "Auteur"),
+    (FIELD_CHANGES, "Modifications"),
+    (FIELD_LABELS, "Labels"),
+    (REVIEW_APPROVED_BY, "{user} a approuvé la PR \"{link}\""),
+    (REVIEW_CHANGES_REQUESTED_BY, "{user} a demandé des modifications sur la PR \"{link}\""),
+    (INLINE_COMMENT, "1 commentaire dans le code"),
+    (INLINE_COMMENTS, "{count} commentaires dans le code"),
+    (MORE_INLINE_COMMENTS, "… et {count} de plus"),
+    (COMMENT_ON_PR, "Commentaire sur \"{link}\""),
+    (COMMENT_ON_COMMIT, "Commentaire sur \"{path}\" ({commit})"),
+    (COMMENT_SAID, "{user} a dit :"),
+    (COMMENT_LEFT, "{user} a laissé {inline} :"),
+    (COMMENT_EDITED, "Commentaire modifié sur \"{link}\""),
+    (COMMENT_DELETED, "Commentaire de {author} supprimé sur \"{link}\""),
+    (REVIEW_EDITED, "Revue modifiée sur \"{link}\""),
+    (REVIEW_DELETED, "Revue de {author} supprimée sur \"{link}\""),
+    (COMMENT_NOW_SAYS, "{author} dit maintenant :"),
+    (PUSH, "{user} a poussé {count} commit(s) sur la branche {branch}"),
+    (DIRECT_PUSH, "{user} a poussé {count} commit(s) directement sur {branch}"),
+    (PUSH_MORE_COMMITS, "…et {count} commits de plus"),
+    (ISSUE_TITLE, "Issue #{number} : \"{title}\""),
+    (ISSUE_OPENED, "Issue ouverte par {user}"),
+    (ISSUE_REOPENED, "Issue rouverte par {user}"),
+    (ISSUE_CLOSED, "Issue fermée par {user}"),
+    (ISSUE_ASSIGNED, "Issue qui vous est assignée par {user}"),
+    (ISSUE_LABELED, "Issue étiquetée {label} par {user}"),
+    (REPO_CHANGED, "Dépôt {action} par {user}"),
+    (REPO_MOVED, "Dépôt {action} depuis {previous} par {user}"),
+    (REPO_CREATED, "créé"),
+    (REPO_DELETED, "supprimé"),
+    (REPO_ARCHIVED, "archivé"),
+    (REPO_UNARCHIVED, "désarchivé"),
+    (REPO_RENAMED, "renommé"),
+    (REPO_TRANSFERRED, "transféré"),
+    (REPO_PUBLICIZED, "rendu public"),
+    (REPO_PRIVATIZED, "rendu privé"),
+    (REPOS_CONFIG_UPDATED, "Configuration des dépôts mise à jour depuis {previous}"),
+    (REPOS_CONFIG_ERROR, "Erreur de mise à jour de la configuration des dépôts depuis {previous} : {error}"),
+    (SECURITY_ALERT, "{alert} {action}"),
+    (DEPENDABOT_ALERT, "Alerte Dependabot"),
+    (CODE_SCANNING_ALERT, "Alerte d'analyse de code"),
+    (VULNERABILITY_ALERT, "Alerte de vulnérabilité"),
+    (ALERT_OPENED, "ouverte"),
+    (ALERT_REOPENED, "rouverte"),
+    (ALERT_FIXED, "corrigée"),
+    (ALERT_DISMISSED, "ignorée"),
+    (ALERT_AUTO_DISMISSED, "ignorée automatiquement"),
+    (FIELD_SEVERITY, "Gravité"),
+    (FIELD_PACKAGE, "Paquet"),
+    (FIELD_TOOL, "Outil"),
+    (TAG_CREATED, "Tag {tag} créé par {user}"),
+    (RELEASE_PUBLISHED, "Release {name} publiée par {user}"),
+    (RELEASE_EDITED, "Release {name} modifiée par {user}"),
+    (PRERELEASE_PUBLISHED, "Pré-release {name} publiée par {user}"),
+    (PRERELEASE_EDITED, "Pré-release {name} modifiée par {user}"),
+    (CHANGES_SINCE, "Modifications depuis {previous}"),
+    (RELEASE_BRANCH_CUT, "{user} a créé la branche de release {branch}"),
+    (RELEASE_BRANCH_FROM, "Depuis {branch} à {sha}"),
+    (RELEASE_BRANCH_UNPROTECTED, "Elle n'est pas protégée : {error}"),
+    (RELEASE_BRANCH_FAILED, "Impossible de créer une branche de release pour {version} de {repo}"),
+    (DEPLOYING, "Déploiement de {ref} sur {environment} ({user})"),
+    (DEPLOYMENT_SUCCEEDED, "Déploiement de {ref} sur {environment} réussi"),
+    (DEPLOYMENT_FAILED, "Déploiement de {ref} sur {environment} échoué"),
+    (DEPLOYMENT_REPLACED, "Déploiement de {ref} sur {environment} remplacé"),
+    (DEPLOYMENT_IN_PROGRESS, "Déploiement de {ref} sur {environment} en cours"),
+    (DEPLOYMENT_PENDING, "Déploiement de {ref} sur {environment} en attente"),
+    (DEPLOYMENT_PRS, "Pull requests : {links}"),
+    (DEPLOYMENT_LOGS, "Logs"),
+    (AT_COMMIT, "{ref} à {commit}"),
+    (WORKFLOW_FAILED, "Le workflow {workflow} a échoué sur {branch}"),
+    (WORKFLOW_FAILING, "{workflow} a échoué {count} fois de suite sur {branch}"),
+    (WORKFLOW_FIXED, "Le workflow {workflow} est de nouveau vert sur {branch}"),
+    (RERUN_FAILED_JOBS, "Relancer les jobs en échec"),
+    (UNCONFIGURED_REPO, "Événements {event} reçus pour le dépôt non configuré {repo}"),
+    (UNCONFIGURED_REPO_ADD, "L'ajouter"),
+    (PR_LABELS_ERROR, "Erreur de récupération des labels de la Pull Request"),
+    (OUT_OF_OFFICE, "Pour {user} (absent) : {message}"),
+    (
+        COMMAND_NOT_ALLOWED,
+        "Seuls les utilisateurs ayant accès en écriture à {repo} peuvent donner des commandes à octobot.",
+    ),
+    (COMMAND_PERMISSION_ERROR, "Impossible de vérifier vos permissions : {error}"),
+    (
+        BACKPORT_LABEL_INSTEAD,
+        "Seules les PR fusionnées peuvent être backportées : ajoutez-lui plutôt le label {labels}.",
+    ),
+    (BACKPORT_NOT_MERGED, "Seules les PR fusionnées peuvent être backportées."),
+    (BACKPORTING, "Backport vers {branches}."),
+    (BACKPORTING_AGAIN, "Nouveau backport vers {branches}."),
+    (BACKPORT_CHAINED, "{first}, puis {rest} à mesure que chaque backport est fusionné"),
+    (BACKPORT_LABELS_ERROR, "Impossible de récupérer les labels de la PR : {error}"),
+    (BACKPORT_NO_LABELS, "Il n'y a aucun label `backport-` pour lequel backporter."),
+    (MERGE_NOT_OPEN, "Seules les PR ouvertes peuvent être fusionnées."),
+    (MERGE_DONE, "Fusionnée."),
+    (MERGE_ERROR, "Impossible de fusionner : {error}"),
+    (UNKNOWN_COMMAND, "Commande inconnue `{command}`. {help}"),
+];
+
+const DE: &'static [(&'static str, &'static str)] = &[
+    (PR_OPENED, "Pull Request von {user} eröffnet"),
+    (PR_MERGED, "Pull Request gemergt"),
+    (PR_CLOSED, "Pull Request geschlossen"),
+    (PR_REOPENED, "Pull Request wieder eröffnet"),
+    (PR_ASSIGNED, "Pull Request {assignees} zugewiesen"),
+    (PR_UNASSIGNED, "Pull Request nicht mehr zugewiesen"),
+    (PR_SUBMITTED_FOR_REVIEW, "Pull Request zum Review an {reviewers} gegeben"),
+    (REVIEW_TITLE, "Review: {state}"),
+    (REVIEW_APPROVED, "Genehmigt"),
+    (REVIEW_CHANGES_REQUESTED, "Änderungen angefordert"),
+    (FORCE_PUSH, "Force-Push auf Pull Request #{number}: {summary}"),
+    (FORCE_PUSH_COMMENT, "Force-Push erkannt: vorher: {before}, nachher: {after}: {result}"),
+    (PR_READY_FOR_REVIEW, "Pull Request ist bereit zum Review"),
+    (PR_TITLE, "Pull Request #{number}: \"{title}\""),
+    (REVIEW_REQUESTED, "{user} hat dich um ein Review gebeten"),
+    (REVIEW_REQUEST_REMOVED, "{user} hat die Bitte um dein Review zurückgezogen"),
+    (FIELD_BRANCH, "Branch"),
+    (FIELD_AUTHOR, "Autor"),
+    (FIELD_CHANGES, "Änderungen"),
+    (FIELD_LABELS, "Labels"),
+    (REVIEW_APPROVED_BY, "{user} hat PR \"{link}\" genehmigt"),
+    (REVIEW_CHANGES_REQUESTED_BY, "{user} hat Änderungen an PR \"{link}\" angefordert"),
+    (INLINE_COMMENT, "1 Kommentar im Code"),
+    (INLINE_COMMENTS, "{count} Kommentare im Code"),
+    (MORE_INLINE_COMMENTS, "… und {count} weitere"),
+    (COMMENT_ON_PR, "Kommentar zu \"{link}\""),
+    (COMMENT_ON_COMMIT, "Kommentar zu \"{path}\" ({commit})"),
+    (COMMENT_SAID, "{user} schrieb:"),
+    (COMMENT_LEFT, "{user} hinterließ {inline}:"),
+    (COMMENT_EDITED, "Kommentar zu \"{link}\" bearbeitet"),
+    (COMMENT_DELETED, "Kommentar von {author} zu \"{link}\" gelöscht"),
+    (REVIEW_EDITED, "Review zu \"{link}\" bearbeitet"),
+    (REVIEW_DELETED, "Review von {author} zu \"{link}\" gelöscht"),
+    (COMMENT_NOW_SAYS, "{author} schreibt jetzt:"),
+    (PUSH, "{user} hat {count} Commit(s) auf Branch {branch} gepusht"),
+    (DIRECT_PUSH, "{user} hat {count} Commit(s) direkt auf {branch} gepusht"),
+    (PUSH_MORE_COMMITS, "…und {count} weitere Commits"),
+    (ISSUE_TITLE, "Issue #{number}: \"{title}\""),
+    (ISSUE_OPENED, "Issue von {user} eröffnet"),
+    (ISSUE_REOPENED, "Issue von {user} wieder eröffnet"),
+    (ISSUE_CLOSED, "Issue von {user} geschlossen"),
+    (ISSUE_ASSIGNED, "Issue von {user} dir zugewiesen"),
+    (ISSUE_LABELED, "Issue von {user} mit {label} gelabelt"),
+    (REPO_CHANGED, "Repository von {user} {action}"),
+    (REPO_MOVED, "Repository von {user} von {previous} aus {action}"),
+    (REPO_CREATED, "erstellt"),
+    (REPO_DELETED, "gelöscht"),
+    (REPO_ARCHIVED, "archiviert"),
+    (REPO_UNARCHIVED, "dearchiviert"),
+    (REPO_RENAMED, "umbenannt"),
+    (REPO_TRANSFERRED, "übertragen"),
+    (REPO_PUBLICIZED, "öffentlich gemacht"),
+    (REPO_PRIVATIZED, "privat gemacht"),
+    (REPOS_CONFIG_UPDATED, "Repo-Konfiguration von {previous} aus aktualisiert"),
+    (REPOS_CONFIG_ERROR, "Fehler beim Aktualisieren der Repo-Konfiguration von {previous} aus: {error}"),
+    (SECURITY_ALERT, "{alert} {action}"),
+    (DEPENDABOT_ALERT, "Dependabot-Warnung"),
+    (CODE_SCANNING_ALERT, "Code-Scanning-Warnung"),
+    (VULNERABILITY_ALERT, "Schwachstellenwarnung"),
+    (ALERT_OPENED, "eröffnet"),
+    (ALERT_REOPENED, "wieder eröffnet"),
+    (ALERT_FIXED, "behoben"),
+    (ALERT_DISMISSED, "verworfen"),
+    (ALERT_AUTO_DISMISSED, "automatisch verworfen"),
+    (FIELD_SEVERITY, "Schweregrad"),
+    (FIELD_PACKAGE, "Paket"),
+    (FIELD_TOOL, "Werkzeug"),
+    (TAG_CREATED, "Tag {tag} von {user} erstellt"),
+    (RELEASE_PUBLISHED, "Release {name} von {user} veröffentlicht"),
+    (RELEASE_EDITED, "Release {name} von {user} bearbeitet"),
+    (PRERELEASE_PUBLISHED, "Pre-Release {name} von {user} veröffentlicht"),
+    (PRERELEASE_EDITED, "Pre-Release {name} von {user} bearbeitet"),
+    (CHANGES_SINCE, "Änderungen seit {previous}"),
+    (RELEASE_BRANCH_CUT, "{user} hat den Release-Branch {branch} erstellt"),
+    (RELEASE_BRANCH_FROM, "Von {branch} bei {sha}"),
+    (RELEASE_BRANCH_UNPROTECTED, "Er ist nicht geschützt: {error}"),
+    (RELEASE_BRANCH_FAILED, "Release-Branch für {version} von {repo} konnte nicht erstellt werden"),
+    (DEPLOYING, "Deployment von {ref} auf {environment} ({user})"),
+    (DEPLOYMENT_SUCCEEDED, "Deployment von {ref} auf {environment} erfolgreich"),
+    (DEPLOYMENT_FAILED, "Deployment von {ref} auf {environment} fehlgeschlagen"),
+    (DEPLOYMENT_REPLACED, "Deployment von {ref} auf {environment} wurde ersetzt"),
+    (DEPLOYMENT_IN_PROGRESS, "Deployment von {ref} auf {environment} läuft"),
+    (DEPLOYMENT_PENDING, "Deployment von {ref} auf {environment} steht aus"),
+    (DEPLOYMENT_PRS, "Pull Requests: {links}"),
+    (DEPLOYMENT_LOGS, "Logs"),
+    (AT_COMMIT, "{ref} bei {commit}"),
+    (WORKFLOW_FAILED, "Workflow {workflow} auf {branch} fehlgeschlagen"),
+    (WORKFLOW_FAILING, "{workflow} ist auf {branch} {count} Mal in Folge fehlgeschlagen"),
+    (WORKFLOW_FIXED, "Workflow {workflow} ist auf {branch} wieder grün"),
+    (RERUN_FAILED_JOBS, "Fehlgeschlagene Jobs neu starten"),
+    (UNCONFIGURED_REPO, "{event}-Events für nicht konfiguriertes Repository {repo} empfangen"),
+    (UNCONFIGURED_REPO_ADD, "Hinzufügen"),
+    (PR_LABELS_ERROR, "Fehler beim Abrufen der Labels der Pull Request"),
+    (OUT_OF_OFFICE, "Für {user} (abwesend): {message}"),
+    (COMMAND_NOT_ALLOWED, "Nur Nutzer mit Schreibzugriff auf {repo} können octobot Befehle geben."),
+    (COMMAND_PERMISSION_ERROR, "Deine Berechtigungen konnten nicht geprüft werden: {error}"),
+    (BACKPORT_LABEL_INSTEAD, "Nur gemergte PRs können gebackportet werden: label sie stattdessen mit {labels}."),
+    (BACKPORT_NOT_MERGED, "Nur gemergte PRs können gebackportet werden."),
+    (BACKPORTING, "Backport nach {branches}."),
+    (BACKPORTING_AGAIN, "Erneuter Backport nach {branches}."),
+    (BACKPORT_CHAINED, "{first}, dann {rest}, sobald der jeweilige Backport gemergt ist"),
+    (BACKPORT_LABELS_ERROR, "Die Labels der PR konnten nicht abgerufen werden: {error}"),
+    (BACKPORT_NO_LABELS, "Es gibt keine `backport-`-Labels, für die gebackportet werden kann."),
+    (MERGE_NOT_OPEN, "Nur offene PRs können gemergt werden."),
+    (MERGE_DONE, "Gemergt."),
+    (MERGE_ERROR, "Merge nicht möglich: {error}"),
+    (UNKNOWN_COMMAND, "Unbekannter Befehl `{command}`. {help}"),
+];
+
+const ES: &'static [(&'static str, &'static str)] = &[
+    (PR_OPENED, "Pull Request abierta por {user}"),
+    (PR_MERGED, "Pull Request fusionada"),
+    (PR_CLOSED, "Pull Request cerrada"),
+    (PR_REOPENED, "Pull Request reabierta"),
+    (PR_ASSIGNED, "Pull Request asignada a {assignees}"),
+    (PR_UNASSIGNED, "Pull Request sin asignar"),
+    (PR_SUBMITTED_FOR_REVIEW, "Pull Request enviada a revisión a {reviewers}"),
+    (REVIEW_TITLE, "Revisión: {state}"),
+    (REVIEW_APPROVED, "Aprobada"),
+    (REVIEW_CHANGES_REQUESTED, "Cambios solicitados"),
+    (FORCE_PUSH, "Force-push en la Pull Request #{number}: {summary}"),
+    (FORCE_PUSH_COMMENT, "Force-push detectado: antes: {before}, después: {after}: {result}"),
+    (PR_READY_FOR_REVIEW, "Pull Request lista para revisión"),
+    (PR_TITLE, "Pull Request #{number}: \"{title}\""),
+    (REVIEW_REQUESTED, "{user} solicitó tu revisión"),
+    (REVIEW_REQUEST_REMOVED, "{user} retiró la solicitud de tu revisión"),
+    (FIELD_BRANCH, "Rama"),
+    (FIELD_AUTHOR, "Autor"),
+    (FIELD_CHANGES, "Cambios"),
+    (FIELD_LABELS, "Etiquetas"),
+    (REVIEW_APPROVED_BY, "{user} aprobó la PR \"{link}\""),
+    (REVIEW_CHANGES_REQUESTED_BY, "{user} solicitó cambios en la PR \"{link}\""),
+    (INLINE_COMMENT, "1 comentario en el código"),
+    (INLINE_COMMENTS, "{count} comentarios en el código"),
+    (MORE_INLINE_COMMENTS, "… y {count} más"),
+    (COMMENT_ON_PR, "Comentario en \"{link}\""),
+    (COMMENT_ON_COMMIT, "Comentario en \"{path}\" ({commit})"),
+    (COMMENT_SAID, "{user} dijo:"),
+    (COMMENT_LEFT, "{user} dejó {inline}:"),
+    (COMMENT_EDITED, "Comentario editado en \"{link}\""),
+    (COMMENT_DELETED, "Comentario de {author} eliminado en \"{link}\""),
+    (REVIEW_EDITED, "Revisión editada en \"{link}\""),
+    (REVIEW_DELETED, "Revisión de {author} eliminada en \"{link}\""),
+    (COMMENT_NOW_SAYS, "{author} ahora dice:"),
+    (PUSH, "{user} subió {count} commit(s) a la rama {branch}"),
+    (DIRECT_PUSH, "{user} subió {count} commit(s) directamente a {branch}"),
+    (PUSH_MORE_COMMITS, "…y {count} commits más"),
+    (ISSUE_TITLE, "Issue #{number}: \"{title}\""),
+    (ISSUE_OPENED, "Issue abierta por {user}"),
+    (ISSUE_REOPENED, "Issue reabierta por {user}"),
+    (ISSUE_CLOSED, "Issue cerrada por {user}"),
+    (ISSUE_ASSIGNED, "Issue asignada a ti por {user}"),
+    (ISSUE_LABELED, "Issue etiquetada {label} por {user}"),
+    (REPO_CHANGED, "Repositorio {action} por {user}"),
+    (REPO_MOVED, "Repositorio {action} desde {previous} por {user}"),
+    (REPO_CREATED, "creado"),
+    (REPO_DELETED, "eliminado"),
+    (REPO_ARCHIVED, "archivado"),
+    (REPO_UNARCHIVED, "desarchivado"),
+    (REPO_RENAMED, "renombrado"),
+    (REPO_TRANSFERRED, "transferido"),
+    (REPO_PUBLICIZED, "hecho público"),
+    (REPO_PRIVATIZED, "hecho privado"),
+    (REPOS_CONFIG_UPDATED, "Configuración de repositorios actualizada desde {previous}"),
+    (REPOS_CONFIG_ERROR, "Error al actualizar la configuración de repositorios desde {previous}: {error}"),
+    (SECURITY_ALERT, "{alert} {action}"),
+    (DEPENDABOT_ALERT, "Alerta de Dependabot"),
+    (CODE_SCANNING_ALERT, "Alerta de análisis de código"),
+    (VULNERABILITY_ALERT, "Alerta de vulnerabilidad"),
+    (ALERT_OPENED, "abierta"),
+    (ALERT_REOPENED, "reabierta"),
+    (ALERT_FIXED, "corregida"),
+    (ALERT_DISMISSED, "descartada"),
+    (ALERT_AUTO_DISMISSED, "descartada automáticamente"),
+    (FIELD_SEVERITY, "Gravedad"),
+    (FIELD_PACKAGE, "Paquete"),
+    (FIELD_TOOL, "Herramienta"),
+    (TAG_CREATED, "Tag {tag} creado por {user}"),
+    (RELEASE_PUBLISHED, "Release {name} publicada por {user}"),
+    (RELEASE_EDITED, "Release {name} editada por {user}"),
+    (PRERELEASE_PUBLISHED, "Pre-release {name} publicada por {user}"),
+    (PRERELEASE_EDITED, "Pre-release {name} editada por {user}"),
+    (CHANGES_SINCE, "Cambios desde {previous}"),
+    (RELEASE_BRANCH_CUT, "{user} creó la rama de release {branch}"),
+    (RELEASE_BRANCH_FROM, "Desde {branch} en {sha}"),
+    (RELEASE_BRANCH_UNPROTECTED, "No está protegida: {error}"),
+    (RELEASE_BRANCH_FAILED, "No se pudo crear una rama de release para {version} de {repo}"),
+    (DEPLOYING, "Desplegando {ref} en {environment} ({user})"),
+    (DEPLOYMENT_SUCCEEDED, "Despliegue de {ref} en {environment} exitoso"),
+    (DEPLOYMENT_FAILED, "Despliegue de {ref} en {environment} fallido"),
+    (DEPLOYMENT_REPLACED, "Despliegue de {ref} en {environment} reemplazado"),
+    (DEPLOYMENT_IN_PROGRESS, "Despliegue de {ref} en {environment} en curso"),
+    (DEPLOYMENT_PENDING, "Despliegue de {ref} en {environment} pendiente"),
+    (DEPLOYMENT_PRS, "Pull requests: {links}"),
+    (DEPLOYMENT_LOGS, "Registros"),
+    (AT_COMMIT, "{ref} en {commit}"),
+    (WORKFLOW_FAILED, "El workflow {workflow} falló en {branch}"),
+    (WORKFLOW_FAILING, "{workflow} ha fallado {count} veces seguidas en {branch}"),
+    (WORKFLOW_FIXED, "El workflow {workflow} vuelve a estar en verde en {branch}"),
+    (RERUN_FAILED_JOBS, "Volver a ejecutar los jobs fallidos"),
+    (UNCONFIGURED_REPO, "Eventos {event} recibidos para el repositorio no configurado {repo}"),
+    (UNCONFIGURED_REPO_ADD, "Añadirlo"),
+    (PR_LABELS_ERROR, "Error al obtener las etiquetas de la Pull Request"),
+    (OUT_OF_OFFICE, "Para {user} (fuera de la oficina): {message}"),
+    (COMMAND_NOT_ALLOWED, "Solo los usuarios con acceso de escritura a {repo} pueden dar órdenes a octobot."),
+    (COMMAND_PERMISSION_ERROR, "No se pudieron comprobar tus permisos: {error}"),
+    (BACKPORT_LABEL_INSTEAD, "Solo las PR fusionadas se pueden backportear: etiquétala con {labels} en su lugar."),
+    (BACKPORT_NOT_MERGED, "Solo las PR fusionadas se pueden backportear."),
+    (BACKPORTING, "Backport a {branches}."),
+    (BACKPORTING_AGAIN, "Nuevo backport a {branches}."),
+    (BACKPORT_CHAINED, "{first}, luego {rest} a medida que se fusiona cada backport"),
+    (BACKPORT_LABELS_ERROR, "No se pudieron obtener las etiquetas de la PR: {error}"),
+    (BACKPORT_NO_LABELS, "No hay etiquetas `backport-` para las que hacer backport."),
+    (MERGE_NOT_OPEN, "Solo las PR abiertas se pueden fusionar."),
+    (MERGE_DONE, "Fusionada."),
+    (MERGE_ERROR, "No se pudo fusionar: {error}"),
+    (UNKNOWN_COMMAND, "Orden desconocida `{command}`. {help}"),
+];
+
+pub fn default_template(name: &str) -> Option<&'static str> {
+    DEFAULTS.iter().find(|&&(n, _)| n == name).map(|&(_, t)| t)
+}
+
+// A locale's wording of the messages, by name
+pub type Catalog = HashMap<String, String>;
+
+fn builtin_catalog(language: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    match language {
+        "fr" => Some(FR),
+        "de" => Some(DE),
+        "es" => Some(ES),
+        _ => None,
+    }
+}
+
+// The catalogs octobot comes with (fr, de, and es), and the ones in `locale_dir`, which add to them or reword them.
+#[derive(Clone, Debug)]
+pub struct Catalogs {
+    catalogs: HashMap<String, Catalog>,
+}
+
+impl Catalogs {
+    pub fn new() -> Catalogs {
+        Catalogs { catalogs: HashMap::new() }
+    }
+
+    // loads each "<locale>.json" in dir, e.g. "pt-BR.json", as an object of message names and templates
+    pub fn load(dir: &Path) -> Catalogs {
+        let mut catalogs = Catalogs::new();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("Error reading locale_dir {}: {}", dir.display(), e);
+                return catalogs;
+            }
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            let locale = match (path.file_stem(), path.extension()) {
+                (Some(stem), Some(ext)) if ext == "json" => stem.to_string_lossy().into_owned(),
+                _ => continue,
+            };
+            let mut contents = String::new();
+            if let Err(e) = fs::File::open(&path).and_then(|mut f| f.read_to_string(&mut contents)) {
+                error!("Error reading message catalog {}: {}", path.display(), e);
+                continue;
+            }
+            match serde_json::from_str::<Catalog>(&contents) {
+                Ok(catalog) => catalogs.insert(&locale, catalog),
+                Err(e) => error!("Invalid JSON in message catalog {}: {}", path.display(), e),
+            }
+        }
+        catalogs
+    }
+
+    pub fn insert(&mut self, locale: &str, catalog: Catalog) {
+        self.catalogs.insert(locale.to_string(), catalog);
+    }
+
+    // the wording for a locale such as "fr" or "pt-BR", which falls back to its language's ("pt"), and then to
+    // what octobot comes with
+    pub fn catalog(&self, locale: &str) -> Catalog {
+        let language = locale.split(|c| c == '-' || c == '_').next().unwrap_or(locale);
+        let mut catalog = Catalog::new();
+        if let Some(builtin) = builtin_catalog(language) {
+            catalog.extend(builtin.iter().map(|&(n, t)| (n.to_string(), t.to_string())));
+        }
+        for name in &[language, locale] {
+            if let Some(loaded) = self.catalogs.get(*name) {
+                catalog.extend(loaded.iter().map(|(n, t)| (n.clone(), t.clone())));
+            }
+        }
+        catalog
+    }
+}
+
+// Fills in a template's {placeholders}. Ones it doesn't have a value for are left as they are.
+pub fn render(template: &str, values: &[(&str, &str)]) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered += &rest[..start];
+        rest = &rest[start..];
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        match values.iter().find(|&&(k, _)| k == &rest[1..end]) {
+            Some(&(_, v)) => rendered += v,
+            None => rendered += &rest[..end + 1],
+        }
+        rest = &rest[end + 1..];
+    }
+    rendered + rest
+}
+
+// A repo's wording of the built-in messages: its own templates where it has them, then its locale's catalog, then
+// the defaults.
+#[derive(Clone, Debug)]
+pub struct Messages {
+    templates: HashMap<String, String>,
+    catalog: Catalog,
+}
+
+impl Messages {
+    pub fn new(templates: &HashMap<String, String>) -> Messages {
+        for name in templates.keys().filter(|n| default_template(n).is_none()) {
+            error!("Unknown message template '{}'", name);
+        }
+        Messages {
+            templates: templates.clone(),
+            catalog: Catalog::new(),
+        }
+    }
+
+    pub fn defaults() -> Messages {
+        Messages::new(&HashMap::new())
+    }
+
+    pub fn with_catalog(self, catalog: Catalog) -> Messages {
+        let mut messages = self;
+        messages.catalog = catalog;
+        messages
+    }
+
+    pub fn format(&self, name: &str, values: &[(&str, &str)]) -> String {
+        let template = [self.templates.get(name), self.catalog.get(name)]
+            .iter()
+            .filter_map(|t| *t)
+            .find(|t| !t.is_empty())
+            .map(|t| t.as_str());
+        render(template.or(default_template(name)).unwrap_or(name), values)
+    }
+}
+
+// How to word a message with any wording of the built-in messages, so that direct messages can be in each user's
+// language. See Messenger::with_wording.
+pub type Wording = Arc<Fn(&Messages) -> String + Send + Sync>;
+
+// the wording of a built-in message with these values
+pub fn wording(name: &'static str, values: Vec<(&'static str, String)>) -> Wording {
+    Arc::new(move |messages: &Messages| {
+        let values: Vec<(&str, &str)> = values.iter().map(|&(k, ref v)| (k, v.as_str())).collect();
+        messages.format(name, &values)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    extern crate tempdir;
+    use self::tempdir::TempDir;
+
+    #[test]
+    fn test_render() {
+        assert_eq!("Pull Request opened by joe", render("Pull Request opened by {user}", &[("user", "joe")]));
+        assert_eq!("{a} b {c", render("{a} {b} {c", &[("b", "b")]));
+        assert_eq!("{user}", render("{user}", &[("user", "{user}")]));
+    }
+
+    #[test]
+    fn test_messages() {
+        let defaults = Messages::defaults();
+        assert_eq!("Review: Approved", defaults.format(REVIEW_TITLE, &[("state", "Approved")]));
+
+        let messages = Messages::new(&hashmap!{
+            PR_OPENED.to_string() => ":tada: {user} opened #{number}".to_string(),
+            REVIEW_TITLE.to_string() => String::new(),
+        });
+        assert_eq!(
+            ":tada: joe opened #12",
+            messages.format(PR_OPENED, &[("user", "joe"), ("number", "12")])
+        );
+        assert_eq!("Review: Approved", messages.format(REVIEW_TITLE, &[("state", "Approved")]));
+        assert_eq!("Pull Request merged", messages.format(PR_MERGED, &[]));
+    }
+
+    #[test]
+    fn test_wording() {
+        let wording = wording(PR_OPENED, vec![("user", "joe".to_string())]);
+        assert_eq!("Pull Request opened by joe", wording(&Messages::defaults()));
+        let french = Messages::defaults().with_catalog(Catalogs::new().catalog("fr"));
+        assert_eq!("Pull Request ouverte par joe", wording(&french));
+    }
+
+    #[test]
+    fn test_catalogs() {
+        let mut catalogs = Catalogs::new();
+        catalogs.insert("pt", hashmap!{PR_MERGED.to_string() => "Pull Request mesclada".to_string()});
+        catalogs.insert("fr-CA", hashmap!{PR_MERGED.to_string() => "Pull Request fusionnée!".to_string()});
+
+        assert_eq!(Some(&"Pull Request mesclada".to_string()), catalogs.catalog("pt-BR").get(PR_MERGED));
+        assert_eq!(Some(&"Pull Request fusionnée!".to_string()), catalogs.catalog("fr-CA").get(PR_MERGED));
+        assert_eq!(Some(&"Pull Request fusionnée".to_string()), catalogs.catalog("fr").get(PR_MERGED));
+        assert!(catalogs.catalog("en").is_empty());
+
+        let messages = Messages::new(&hashmap!{PR_OPENED.to_string() => "{user} opened it".to_string()})
+            .with_catalog(catalogs.catalog("fr"));
+        assert_eq!("joe opened it", messages.format(PR_OPENED, &[("user", "joe")]));
+        assert_eq!("Revue : Approuvée", messages.format(REVIEW_TITLE, &[("state", "Approuvée")]));
+        assert_eq!("Pull Request rouverte", messages.format(PR_REOPENED, &[]));
+    }
+
+    #[test]
+    fn test_load_catalogs() {
+        let dir = TempDir::new("catalogs").unwrap();
+        let write = |name: &str, contents: &str| {
+            fs::File::create(dir.path().join(name)).unwrap().write_all(contents.as_bytes()).unwrap();
+        };
+        write("pt-BR.json", r#"{"pull_request_merged": "Pull Request mesclada"}"#);
+        write("nl.json", "not json");
+        write("README", "catalogs");
+
+        let catalogs = Catalogs::load(dir.path());
+        assert_eq!(Some(&"Pull Request mesclada".to_string()), catalogs.catalog("pt-BR").get(PR_MERGED));
+        assert!(catalogs.catalog("nl").is_empty());
+    }
+
+    #[test]
+    fn test_builtin_catalogs() {
+        for catalog in &[FR, DE, ES] {
+            let names: Vec<&str> = catalog.iter().map(|&(n, _)| n).collect();
+            assert_eq!(DEFAULTS.iter().map(|&(n, _)| n).collect::<Vec<_>>(), names);
+        }
+    }
+
+    fn placeholders(template: &str) -> Vec<&str> {
+        let mut placeholders: Vec<&str> =
+            template.split('{').skip(1).filter_map(|p| p.split('}').next()).collect();
+        placeholders.sort();
+        placeholders
+    }
+
+    #[test]
+    fn test_builtin_placeholders() {
+        for catalog in &[FR, DE, ES] {
+            for (&(name, default), &(_, translated)) in DEFAULTS.iter().zip(catalog.iter()) {
+                assert_eq!(placeholders(default), placeholders(translated), "{}", name);
+            }
+        }
+    }
+}
@@ -1,10 +1,16 @@
 use std::sync::Arc;
 
 use config::Config;
+use digest;
 use github;
+use jira::workflow;
+use messages::{self, Wording};
+use quiet_hours;
 use slack::{self, SlackAttachment, SlackRequest};
+use tracing;
 use users;
 use util;
+use webhooks::{OutboundEvent, PullRequestRef, WebhookRequest};
 use worker::WorkSender;
 
 pub trait Messenger {
@@ -15,7 +21,9 @@ pub trait Messenger {
         item_owner: &github::User,
         sender: &github::User,
         repo: &github::Repo,
+        branch: Option<&str>,
         participants: &Vec<github::User>,
+        mentioned: &Vec<github::User>,
     );
 
     fn send_to_owner(
@@ -24,26 +32,108 @@ pub trait Messenger {
         attachments: &Vec<SlackAttachment>,
         item_owner: &github::User,
         repo: &github::Repo,
+        branch: Option<&str>,
     );
 
-    fn send_to_channel(&self, msg: &str, attachments: &Vec<SlackAttachment>, repo: &github::Repo);
+    // branch is the PR's base branch, if any, and is used to pick the channel
+    fn send_to_channel(
+        &self,
+        msg: &str,
+        attachments: &Vec<SlackAttachment>,
+        repo: &github::Repo,
+        branch: Option<&str>,
+    );
+
+    // direct messages only: nothing goes to the channel
+    fn send_to_users(
+        &self,
+        msg: &str,
+        attachments: &Vec<SlackAttachment>,
+        users: &Vec<github::User>,
+        repo: &github::Repo,
+        reason: Reason,
+    );
+
+    // a channel other than the repo's, e.g. one configured for a team that was @mentioned
+    fn send_to_team_channel(&self, msg: &str, attachments: &Vec<SlackAttachment>, channel: &str, repo: &github::Repo);
+
+    // the admin_channel, if there is one: for problems with octobot itself rather than with a repo
+    fn send_to_admins(&self, msg: &str, attachments: &Vec<SlackAttachment>);
+
+    // the same messenger for a particular kind of event, which applies the repo's notification filters
+    // and digests. see repos::Notifications for the kinds.
+    fn for_kind(&self, kind: &str) -> Box<Messenger>;
+
+    // the same messenger for messages about a PR, which go in the PR's thread in the channel if the repo has
+    // pr_threads on
+    fn for_pull_request(&self, pull_request: &github::PullRequest) -> Box<Messenger>;
+
+    // the same messenger, whose messages in a PR's thread also react to the PR's message with this emoji, e.g.
+    // pr_threads::APPROVED
+    fn with_reaction(&self, reaction: &str) -> Box<Messenger>;
+
+    // the same messenger, whose direct messages are worded in each user's locale (see users::UserInfo::locale)
+    // rather than sent as they are
+    fn with_wording(&self, wording: Wording) -> Box<Messenger>;
 }
 
+// Why a user is getting a direct message, checked against their notification preferences.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Reason {
+    Participant,
+    Mention,
+    ReviewRequest,
+}
+
+// the users that send_to_all sends direct messages to: first the participants, then anyone else
+// who was mentioned.
+fn all_recipients(
+    item_owner: &github::User,
+    sender: &github::User,
+    participants: &Vec<github::User>,
+    mentioned: &Vec<github::User>,
+) -> (Vec<github::User>, Vec<github::User>) {
+    let mut slackbots: Vec<github::User> = vec![item_owner.clone()];
+
+    slackbots.extend(
+        participants.iter().filter(|a| a.login != item_owner.login).map(|a| a.clone()),
+    );
+
+    let mut mentions: Vec<github::User> = vec![];
+    for user in mentioned {
+        if !mentions.iter().any(|u| u.login == user.login) {
+            mentions.push(user.clone());
+        }
+    }
+    slackbots.retain(|u| !mentions.iter().any(|m| m.login == u.login));
+
+    // make sure we do not send private message to author of that message
+    slackbots.retain(|u| u.login != sender.login && u.login() != "octobot");
+    mentions.retain(|u| u.login != sender.login && u.login() != "octobot");
+    (slackbots, mentions)
+}
 
 struct SlackMessenger {
     pub config: Arc<Config>,
     pub slack: WorkSender<SlackRequest>,
+    pub kind: Option<String>,
+    // the url of the PR the messages are about
+    pub thread: Option<String>,
+    pub reaction: Option<String>,
+    pub wording: Option<Wording>,
 }
 
 pub fn new(config: Arc<Config>, slack: WorkSender<SlackRequest>) -> Box<Messenger> {
     Box::new(SlackMessenger {
         slack: slack,
         config: config.clone(),
+        kind: None,
+        thread: None,
+        reaction: None,
+        wording: None,
     })
 }
 
-const DND_MARKER: &'static str = "DO NOT DISTURB";
-
 impl Messenger for SlackMessenger {
     fn send_to_all(
         &self,
@@ -52,20 +142,15 @@ impl Messenger for SlackMessenger {
         item_owner: &github::User,
         sender: &github::User,
         repo: &github::Repo,
+        branch: Option<&str>,
         participants: &Vec<github::User>,
+        mentioned: &Vec<github::User>,
     ) {
-        self.send_to_channel(msg, attachments, repo);
-
-        let mut slackbots: Vec<github::User> = vec![item_owner.clone()];
-
-        slackbots.extend(
-            participants.iter().filter(|a| a.login != item_owner.login).map(|a| a.clone()),
-        );
-
-        // make sure we do not send private message to author of that message
-        slackbots.retain(|u| u.login != sender.login && u.login() != "octobot");
+        self.send_to_channel(msg, attachments, repo, branch);
 
-        self.send_to_slackbots(slackbots, repo, msg, attachments);
+        let (slackbots, mentions) = all_recipients(item_owner, sender, participants, mentioned);
+        self.send_to_slackbots(slackbots, repo, branch, msg, attachments, Reason::Participant);
+        self.send_to_slackbots(mentions, repo, branch, msg, attachments, Reason::Mention);
     }
 
     fn send_to_owner(
@@ -74,41 +159,456 @@ impl Messenger for SlackMessenger {
         attachments: &Vec<SlackAttachment>,
         item_owner: &github::User,
         repo: &github::Repo,
+        branch: Option<&str>,
     ) {
-        self.send_to_channel(msg, attachments, repo);
-        self.send_to_slackbots(vec![item_owner.clone()], repo, msg, attachments);
+        self.send_to_channel(msg, attachments, repo, branch);
+        self.send_to_slackbots(vec![item_owner.clone()], repo, branch, msg, attachments, Reason::Participant);
     }
 
-    fn send_to_channel(&self, msg: &str, attachments: &Vec<SlackAttachment>, repo: &github::Repo) {
-        if let Some(channel) = self.config.repos().lookup_channel(repo) {
+    fn send_to_channel(
+        &self,
+        msg: &str,
+        attachments: &Vec<SlackAttachment>,
+        repo: &github::Repo,
+        branch: Option<&str>,
+    ) {
+        if let Some(ref kind) = self.kind {
+            if !self.config.repos().notify_channel(repo, kind) {
+                return;
+            }
+        }
+
+        let channel = self.config.repos().lookup_channel(repo, branch).or_else(|| self.config.unknown_repos_channel());
+        if let Some(channel) = channel {
             let channel_msg = format!("{} ({})", msg, util::make_link(&repo.html_url, &repo.full_name));
-            self.send_to_slack(channel.as_str(), &channel_msg, attachments);
+            let digest = self.kind.as_ref().map_or(false, |k| self.config.repos().digest_channel(repo, k));
+            if digest {
+                let deliver_at = digest::next_delivery(&self.config, 0, quiet_hours::now());
+                let req = slack::digest_req(&channel, &channel_msg, attachments.clone(), deliver_at);
+                self.send_to_slack(req, Some(repo));
+            } else {
+                let mut req = slack::req(&channel, &channel_msg, attachments.clone());
+                if self.config.repos().pr_threads(repo, branch) {
+                    req.thread = self.thread.clone();
+                    req.reaction = self.reaction.clone();
+                }
+                self.send_to_slack(req, Some(repo));
+            }
+        }
+    }
+
+    fn send_to_team_channel(&self, msg: &str, attachments: &Vec<SlackAttachment>, channel: &str, repo: &github::Repo) {
+        if let Some(ref kind) = self.kind {
+            if !self.config.repos().notify_channel(repo, kind) {
+                return;
+            }
+        }
+
+        let channel_msg = format!("{} ({})", msg, util::make_link(&repo.html_url, &repo.full_name));
+        self.send_to_slack(slack::req(channel, &channel_msg, attachments.clone()), Some(repo));
+    }
+
+    fn send_to_admins(&self, msg: &str, attachments: &Vec<SlackAttachment>) {
+        if let Some(ref channel) = self.config.main.admin_channel {
+            self.send_to_slack(slack::req(channel, msg, attachments.clone()), None);
         }
     }
+
+    fn send_to_users(
+        &self,
+        msg: &str,
+        attachments: &Vec<SlackAttachment>,
+        users: &Vec<github::User>,
+        repo: &github::Repo,
+        reason: Reason,
+    ) {
+        self.send_to_slackbots(users.clone(), repo, None, msg, attachments, reason);
+    }
+
+    fn for_kind(&self, kind: &str) -> Box<Messenger> {
+        Box::new(SlackMessenger {
+            slack: self.slack.clone(),
+            config: self.config.clone(),
+            kind: Some(kind.to_string()),
+            thread: self.thread.clone(),
+            reaction: self.reaction.clone(),
+            wording: self.wording.clone(),
+        })
+    }
+
+    fn for_pull_request(&self, pull_request: &github::PullRequest) -> Box<Messenger> {
+        Box::new(SlackMessenger {
+            slack: self.slack.clone(),
+            config: self.config.clone(),
+            kind: self.kind.clone(),
+            thread: Some(pull_request.html_url.clone()),
+            reaction: self.reaction.clone(),
+            wording: self.wording.clone(),
+        })
+    }
+
+    fn with_reaction(&self, reaction: &str) -> Box<Messenger> {
+        Box::new(SlackMessenger {
+            slack: self.slack.clone(),
+            config: self.config.clone(),
+            kind: self.kind.clone(),
+            thread: self.thread.clone(),
+            reaction: Some(reaction.to_string()),
+            wording: self.wording.clone(),
+        })
+    }
+
+    fn with_wording(&self, wording: Wording) -> Box<Messenger> {
+        Box::new(SlackMessenger {
+            slack: self.slack.clone(),
+            config: self.config.clone(),
+            kind: self.kind.clone(),
+            thread: self.thread.clone(),
+            reaction: self.reaction.clone(),
+            wording: Some(wording),
+        })
+    }
 }
 
 impl SlackMessenger {
-    fn send_to_slack(&self, channel: &str, msg: &str, attachments: &Vec<SlackAttachment>) {
+    fn wants_direct_message(&self, user: &github::User, repo: &github::Repo, reason: Reason) -> bool {
+        match self.config.users().direct_messages(user.login(), repo).as_str() {
+            "none" => false,
+            "reviews" => reason == Reason::ReviewRequest,
+            "mentions" => reason == Reason::Mention,
+            _ => true,
+        }
+    }
+
+    // The messages of users who are out of office go to their delegate instead, along with the name of who they
+    // were for, or nowhere if they don't have one or the delegate is getting the message anyway.
+    fn redirect_out_of_office(
+        &self,
+        user: &github::User,
+        repo: &github::Repo,
+        recipients: &Vec<String>,
+    ) -> Option<(github::User, Option<String>)> {
+        let users = self.config.users();
+        if !users.is_out_of_office(user.login(), repo) {
+            return Some((user.clone(), None));
+        }
+        match users.delegate(user.login(), repo) {
+            Some(ref delegate) if !recipients.contains(delegate) => {
+                let name = users.slack_user_name(user.login(), repo);
+                Some((github::User::new(delegate), Some(name)))
+            }
+            _ => {
+                info!("Not sending direct message to {}: out of office", user.login());
+                None
+            }
+        }
+    }
+
+    fn send_to_slack(&self, req: SlackRequest, repo: Option<&github::Repo>) {
         // user desires peace and quiet. do not disturb!
-        if channel == DND_MARKER || channel == users::mention(DND_MARKER) {
+        if req.channel == users::DND_MARKER || req.channel == users::mention(users::DND_MARKER) {
             return;
         }
 
-        if let Err(e) = self.slack.send(slack::req(channel, msg, attachments.clone())) {
-            error!("Error sending to slack worker: {}", e);
+        let mut req = req;
+        req.dry_run = self.config.dry_run(repo);
+        if let (Some(repo), Some(jira)) = (repo, self.config.jira.as_ref()) {
+            link_jira_keys(&mut req, &self.config.repos().all_jira_projects(repo), &jira.base_url());
         }
+
+        tracing::span("slack.queue", &[("slack.channel", req.channel.clone())], || {
+            req.trace = tracing::current();
+            if let Err(e) = self.slack.send(req) {
+                error!("Error sending to slack worker: {}", e);
+            }
+        });
     }
 
     fn send_to_slackbots(
         &self,
         users: Vec<github::User>,
         repo: &github::Repo,
+        branch: Option<&str>,
         msg: &str,
         attachments: &Vec<SlackAttachment>,
+        reason: Reason,
     ) {
+        if let Some(ref kind) = self.kind {
+            if !self.config.repos().notify_direct(repo, kind) {
+                return;
+            }
+        }
+
+        let now = quiet_hours::now();
+        let recipients: Vec<String> = users.iter().map(|u| u.login().to_string()).collect();
         for user in users {
-            let slack_ref = self.config.users().slack_user_ref(user.login(), repo);
-            self.send_to_slack(slack_ref.as_str(), msg, attachments);
+            let (user, away_name) = match self.redirect_out_of_office(&user, repo, &recipients) {
+                Some(redirected) => redirected,
+                None => continue,
+            };
+            let msg = match self.wording {
+                Some(ref wording) => wording(&self.config.user_messages(user.login(), repo, branch)),
+                None => msg.to_string(),
+            };
+            let msg = match away_name {
+                Some(name) => {
+                    let messages = self.config.user_messages(user.login(), repo, branch);
+                    messages.format(messages::OUT_OF_OFFICE, &[("user", &name), ("message", &msg)])
+                }
+                None => msg,
+            };
+            let msg = msg.as_str();
+
+            if !self.wants_direct_message(&user, repo, reason) {
+                info!("Not sending direct message to {}: not wanted", user.login());
+                continue;
+            }
+
+            let users = self.config.users();
+            let slack_ref = users.slack_user_ref(user.login(), repo);
+            let digest = reason == Reason::Participant && users.digest(user.login(), repo) &&
+                self.kind.as_ref().map_or(false, |k| digest::is_low_priority(k));
+
+            let req = if digest {
+                let deliver_at = digest::next_delivery(&self.config, users.utc_offset(user.login(), repo), now);
+                slack::digest_req(&slack_ref, msg, attachments.clone(), deliver_at)
+            } else {
+                match users.work_hours(user.login(), repo).and_then(|h| h.next_start(now)) {
+                    Some(deliver_at) => slack::deferred_req(&slack_ref, msg, attachments.clone(), deliver_at),
+                    None => slack::req(&slack_ref, msg, attachments.clone()),
+                }
+            };
+            self.send_to_slack(req, Some(repo));
+        }
+    }
+}
+
+// the jira keys in PR titles, branch names, and comments. Attachment titles are plain text to slack, so keys in
+// them stay as they are.
+fn link_jira_keys(req: &mut SlackRequest, projects: &Vec<String>, jira_base: &str) {
+    if projects.is_empty() {
+        return;
+    }
+    req.msg = workflow::link_jira_keys(&req.msg, projects, jira_base);
+    for attachment in req.attachments.iter_mut() {
+        attachment.text = workflow::link_jira_keys(&attachment.text, projects, jira_base);
+    }
+}
+
+// Makes the messengers for a host, so that whatever sends notifications (event handlers, workers,
+// schedulers) reaches both slack and the repos' webhook_urls.
+#[derive(Clone)]
+pub struct MessengerFactory {
+    config: Arc<Config>,
+    slack: WorkSender<SlackRequest>,
+    webhooks: Option<WorkSender<WebhookRequest>>,
+}
+
+impl MessengerFactory {
+    pub fn new(config: Arc<Config>, slack: WorkSender<SlackRequest>) -> MessengerFactory {
+        MessengerFactory {
+            config: config,
+            slack: slack,
+            webhooks: None,
+        }
+    }
+
+    pub fn with_webhooks(self, webhooks: WorkSender<WebhookRequest>) -> MessengerFactory {
+        let mut factory = self;
+        factory.webhooks = Some(webhooks);
+        factory
+    }
+
+    pub fn new_messenger(&self) -> Box<Messenger> {
+        self.for_event(None, None)
+    }
+
+    // action and pull_request describe what the messages are about to webhook receivers
+    pub fn for_event(&self, action: Option<&str>, pull_request: Option<&github::PullRequest>) -> Box<Messenger> {
+        let slack = new(self.config.clone(), self.slack.clone());
+        let slack = match pull_request {
+            Some(pull_request) => slack.for_pull_request(pull_request),
+            None => slack,
+        };
+        match self.webhooks {
+            Some(ref webhooks) => with_webhooks(slack, self.config.clone(), webhooks.clone(), action, pull_request),
+            None => slack,
+        }
+    }
+}
+
+// Sends everything the wrapped messenger does, and also posts what goes to the channel to the repo's
+// webhook_urls, with the same filters as the channel. Direct messages stay private.
+struct WebhookMessenger {
+    inner: Box<Messenger>,
+    config: Arc<Config>,
+    webhooks: WorkSender<WebhookRequest>,
+    kind: Option<String>,
+    action: Option<String>,
+    pull_request: Option<PullRequestRef>,
+}
+
+pub fn with_webhooks(
+    inner: Box<Messenger>,
+    config: Arc<Config>,
+    webhooks: WorkSender<WebhookRequest>,
+    action: Option<&str>,
+    pull_request: Option<&github::PullRequest>,
+) -> Box<Messenger> {
+    Box::new(WebhookMessenger {
+        inner: inner,
+        config: config,
+        webhooks: webhooks,
+        kind: None,
+        action: action.map(|a| a.to_string()),
+        pull_request: pull_request.map(PullRequestRef::new),
+    })
+}
+
+impl Messenger for WebhookMessenger {
+    fn send_to_all(
+        &self,
+        msg: &str,
+        attachments: &Vec<SlackAttachment>,
+        item_owner: &github::User,
+        sender: &github::User,
+        repo: &github::Repo,
+        branch: Option<&str>,
+        participants: &Vec<github::User>,
+        mentioned: &Vec<github::User>,
+    ) {
+        self.inner.send_to_all(msg, attachments, item_owner, sender, repo, branch, participants, mentioned);
+        self.send_event(msg, attachments, repo, branch);
+    }
+
+    fn send_to_owner(
+        &self,
+        msg: &str,
+        attachments: &Vec<SlackAttachment>,
+        item_owner: &github::User,
+        repo: &github::Repo,
+        branch: Option<&str>,
+    ) {
+        self.inner.send_to_owner(msg, attachments, item_owner, repo, branch);
+        self.send_event(msg, attachments, repo, branch);
+    }
+
+    fn send_to_channel(
+        &self,
+        msg: &str,
+        attachments: &Vec<SlackAttachment>,
+        repo: &github::Repo,
+        branch: Option<&str>,
+    ) {
+        self.inner.send_to_channel(msg, attachments, repo, branch);
+        self.send_event(msg, attachments, repo, branch);
+    }
+
+    // webhooks already get the message from the repo's channel
+    fn send_to_team_channel(&self, msg: &str, attachments: &Vec<SlackAttachment>, channel: &str, repo: &github::Repo) {
+        self.inner.send_to_team_channel(msg, attachments, channel, repo);
+    }
+
+    // admin messages are about octobot, not about a repo's events, so they don't go to webhooks
+    fn send_to_admins(&self, msg: &str, attachments: &Vec<SlackAttachment>) {
+        self.inner.send_to_admins(msg, attachments);
+    }
+
+    fn send_to_users(
+        &self,
+        msg: &str,
+        attachments: &Vec<SlackAttachment>,
+        users: &Vec<github::User>,
+        repo: &github::Repo,
+        reason: Reason,
+    ) {
+        self.inner.send_to_users(msg, attachments, users, repo, reason);
+    }
+
+    fn for_kind(&self, kind: &str) -> Box<Messenger> {
+        Box::new(WebhookMessenger {
+            inner: self.inner.for_kind(kind),
+            config: self.config.clone(),
+            webhooks: self.webhooks.clone(),
+            kind: Some(kind.to_string()),
+            action: self.action.clone(),
+            pull_request: self.pull_request.clone(),
+        })
+    }
+
+    fn for_pull_request(&self, pull_request: &github::PullRequest) -> Box<Messenger> {
+        Box::new(WebhookMessenger {
+            inner: self.inner.for_pull_request(pull_request),
+            config: self.config.clone(),
+            webhooks: self.webhooks.clone(),
+            kind: self.kind.clone(),
+            action: self.action.clone(),
+            pull_request: Some(PullRequestRef::new(pull_request)),
+        })
+    }
+
+    fn with_reaction(&self, reaction: &str) -> Box<Messenger> {
+        Box::new(WebhookMessenger {
+            inner: self.inner.with_reaction(reaction),
+            config: self.config.clone(),
+            webhooks: self.webhooks.clone(),
+            kind: self.kind.clone(),
+            action: self.action.clone(),
+            pull_request: self.pull_request.clone(),
+        })
+    }
+
+    fn with_wording(&self, wording: Wording) -> Box<Messenger> {
+        Box::new(WebhookMessenger {
+            inner: self.inner.with_wording(wording),
+            config: self.config.clone(),
+            webhooks: self.webhooks.clone(),
+            kind: self.kind.clone(),
+            action: self.action.clone(),
+            pull_request: self.pull_request.clone(),
+        })
+    }
+}
+
+impl WebhookMessenger {
+    fn send_event(&self, msg: &str, attachments: &Vec<SlackAttachment>, repo: &github::Repo, branch: Option<&str>) {
+        // only what the channel gets: nothing for kinds the repo has muted, or for repos without a channel
+        if let Some(ref kind) = self.kind {
+            if !self.config.repos().notify_channel(repo, kind) {
+                return;
+            }
+        }
+        if self.config.repos().lookup_channel(repo, branch).is_none() {
+            return;
+        }
+
+        let urls = self.config.repos().webhook_urls(repo, branch);
+        if urls.is_empty() {
+            return;
+        }
+        if self.config.dry_run(Some(repo)) {
+            info!("Dry run: not posting to {} webhook(s) for {}", urls.len(), repo.full_name);
+            return;
+        }
+
+        let event = OutboundEvent {
+            repo: repo.full_name.clone(),
+            repo_url: repo.html_url.clone(),
+            branch: branch.map(|b| b.to_string()),
+            pull_request: self.pull_request.clone(),
+            kind: self.kind.clone(),
+            action: self.action.clone(),
+            message: msg.to_string(),
+            attachments: attachments.clone(),
+        };
+        for url in urls {
+            let req = WebhookRequest {
+                url: url,
+                event: event.clone(),
+            };
+            if let Err(e) = self.webhooks.send(req) {
+                error!("Error sending to webhooks worker: {}", e);
+            }
         }
     }
 }
@@ -0,0 +1,210 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::thread;
+
+use serde_json::{self, Value};
+use tokio_core::reactor::{Core, Remote};
+
+// An HTTP server for the tests of API sessions, which answers each request with the next expected response,
+// after checking that it's the expected request. Like the mock github session, it fails the test if a request
+// wasn't expected or an expected one never came.
+pub struct MockHttp {
+    addr: String,
+    core_remote: Remote,
+    calls: Arc<Mutex<Vec<MockCall>>>,
+    headers: Arc<Mutex<Vec<(String, String)>>>,
+    errors: Arc<Mutex<Vec<String>>>,
+}
+
+struct MockCall {
+    method: String,
+    path: String,
+    // checked against the request's json body, if there is one to check
+    body: Option<Value>,
+    status: u16,
+    response: String,
+}
+
+impl MockHttp {
+    pub fn new() -> MockHttp {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let calls = Arc::new(Mutex::new(vec![]));
+        let headers = Arc::new(Mutex::new(vec![]));
+        let errors = Arc::new(Mutex::new(vec![]));
+
+        let (server_calls, server_headers, server_errors) = (calls.clone(), headers.clone(), errors.clone());
+        thread::spawn(move || for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => answer(stream, &server_calls, &server_headers, &server_errors),
+                Err(e) => server_errors.lock().unwrap().push(format!("Error accepting: {}", e)),
+            }
+        });
+
+        // the sessions' http clients run their requests on a core
+        let (core_tx, core_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut core = Core::new().expect("core");
+            core_tx.send(core.remote()).expect("send core handle");
+            loop {
+                core.turn(None);
+            }
+        });
+
+        MockHttp {
+            addr: addr,
+            core_remote: core_rx.recv().expect("recv core handle"),
+            calls: calls,
+            headers: headers,
+            errors: errors,
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    pub fn core_remote(&self) -> Remote {
+        self.core_remote.clone()
+    }
+
+    // a header that every request has to have, e.g. the API token
+    pub fn expect_header(&self, name: &str, value: &str) {
+        self.headers.lock().unwrap().push((name.to_lowercase(), value.into()));
+    }
+
+    pub fn expect(&self, method: &str, path: &str, body: Option<Value>, status: u16, response: Value) {
+        self.calls.lock().unwrap().push(MockCall {
+            method: method.into(),
+            path: path.into(),
+            body: body,
+            status: status,
+            response: response.to_string(),
+        });
+    }
+
+    pub fn expect_get(&self, path: &str, response: Value) {
+        self.expect("GET", path, None, 200, response);
+    }
+}
+
+impl Drop for MockHttp {
+    fn drop(&mut self) {
+        if !thread::panicking() {
+            assert_eq!(Vec::<String>::new(), *self.errors.lock().unwrap());
+            let unmet: Vec<String> =
+                self.calls.lock().unwrap().iter().map(|c| format!("{} {}", c.method, c.path)).collect();
+            assert!(unmet.is_empty(), "Unmet HTTP calls: {:?}", unmet);
+        }
+    }
+}
+
+fn answer(
+    mut stream: TcpStream,
+    calls: &Mutex<Vec<MockCall>>,
+    headers: &Mutex<Vec<(String, String)>>,
+    errors: &Mutex<Vec<String>>,
+) {
+    let (request_line, request_headers, body) = match read_request(&mut stream) {
+        Some(r) => r,
+        None => return,
+    };
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    for &(ref name, ref value) in headers.lock().unwrap().iter() {
+        if !request_headers.iter().any(|h| h.0 == *name && h.1 == *value) {
+            errors.lock().unwrap().push(format!("{} {} without {}: {}", method, path, name, value));
+        }
+    }
+
+    let (status, response) = {
+        let mut calls = calls.lock().unwrap();
+        if calls.is_empty() || calls[0].method != method || calls[0].path != path {
+            let expected = calls.first().map(|c| format!("{} {}", c.method, c.path));
+            errors.lock().unwrap().push(format!("Unexpected {} {}, expected {:?}", method, path, expected));
+            (500, "{}".to_string())
+        } else {
+            let call = calls.remove(0);
+            if let Some(ref expected) = call.body {
+                let actual = serde_json::from_slice::<Value>(&body).unwrap_or(Value::Null);
+                if actual != *expected {
+                    errors.lock().unwrap().push(format!("{} {} with {}, expected {}", method, path, actual, expected));
+                }
+            }
+            (call.status, call.response)
+        }
+    };
+
+    let reply = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        response.len(),
+        response
+    );
+    stream.write_all(reply.as_bytes()).ok();
+}
+
+// the request line, the headers (with lowercase names), and the body
+fn read_request(stream: &mut TcpStream) -> Option<(String, Vec<(String, String)>, Vec<u8>)> {
+    let mut data = vec![];
+    let mut buf = [0; 4096];
+    let head_len = loop {
+        if let Some(i) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break i + 4;
+        }
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => return None,
+            Ok(n) => data.extend_from_slice(&buf[..n]),
+        }
+    };
+
+    let head = String::from_utf8_lossy(&data[..head_len]).into_owned();
+    let mut lines = head.lines();
+    let request_line = lines.next().unwrap_or("").to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|l| {
+            let mut parts = l.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => Some((name.trim().to_lowercase(), value.trim().to_string())),
+                _ => None,
+            }
+        })
+        .collect();
+
+    let chunked = headers.iter().any(|h| h.0 == "transfer-encoding" && h.1 == "chunked");
+    let length = headers.iter().find(|h| h.0 == "content-length").and_then(|h| h.1.parse().ok()).unwrap_or(0);
+    loop {
+        let done = if chunked {
+            data[head_len..].windows(5).any(|w| w == b"0\r\n\r\n")
+        } else {
+            data.len() >= head_len + length
+        };
+        if done {
+            break;
+        }
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => data.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    let body = if chunked { unchunk(&data[head_len..]) } else { data[head_len..].to_vec() };
+    Some((request_line, headers, body))
+}
+
+// hyper sends bodies in chunks: each is its hex length on a line, then the data
+fn unchunk(mut data: &[u8]) -> Vec<u8> {
+    let mut body = vec![];
+    while let Some(i) = data.windows(2).position(|w| w == b"\r\n") {
+        let size = usize::from_str_radix(String::from_utf8_lossy(&data[..i]).trim(), 16).unwrap_or(0);
+        if size == 0 || data.len() < i + 2 + size {
+            break;
+        }
+        body.extend_from_slice(&data[i + 2..i + 2 + size]);
+        data = &data[(i + 4 + size).min(data.len())..];
+    }
+    body
+}
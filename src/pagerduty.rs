@@ -0,0 +1,87 @@
+use tokio_core::reactor::Remote;
+
+use config::PagerDutyConfig;
+use errors::*;
+use http_client::HTTPClient;
+
+// Opens and resolves PagerDuty incidents through the Events API v2. Incidents are keyed by a dedup key of our
+// choosing, so that triggering the same one twice doesn't page twice, and resolving needs nothing else.
+pub trait Session: Send + Sync {
+    fn trigger(&self, dedup_key: &str, summary: &str, source: &str, link: &str) -> Result<()>;
+
+    fn resolve(&self, dedup_key: &str) -> Result<()>;
+}
+
+pub struct PagerDutySession {
+    client: HTTPClient,
+    routing_key: String,
+}
+
+#[derive(Serialize, Debug)]
+struct Event<'a> {
+    routing_key: &'a str,
+    event_action: &'a str,
+    dedup_key: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<Payload<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    links: Vec<Link<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+struct Payload<'a> {
+    summary: &'a str,
+    source: &'a str,
+    severity: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+struct Link<'a> {
+    href: &'a str,
+    text: &'a str,
+}
+
+impl PagerDutySession {
+    pub fn new(core_remote: Remote, config: &PagerDutyConfig) -> PagerDutySession {
+        let api_base = config.api_base.clone().unwrap_or("https://events.pagerduty.com".into());
+        let client = HTTPClient::new(core_remote, &api_base).with_headers(hashmap!{
+            "Content-Type" => "application/json".to_string(),
+        });
+        PagerDutySession {
+            client: client,
+            routing_key: config.routing_key.clone(),
+        }
+    }
+
+    fn send(&self, event: &Event) -> Result<()> {
+        self.client.post_void("v2/enqueue", event).map_err(|e| {
+            format!("Error sending {} event to PagerDuty: {}", event.event_action, e).into()
+        })
+    }
+}
+
+impl Session for PagerDutySession {
+    fn trigger(&self, dedup_key: &str, summary: &str, source: &str, link: &str) -> Result<()> {
+        self.send(&Event {
+            routing_key: &self.routing_key,
+            event_action: "trigger",
+            dedup_key: dedup_key,
+            payload: Some(Payload {
+                summary: summary,
+                source: source,
+                severity: "error",
+            }),
+            links: vec![Link { href: link, text: "Details" }],
+        })
+    }
+
+    fn resolve(&self, dedup_key: &str) -> Result<()> {
+        self.send(&Event {
+            routing_key: &self.routing_key,
+            event_action: "resolve",
+            dedup_key: dedup_key,
+            payload: None,
+            links: vec![],
+        })
+    }
+}
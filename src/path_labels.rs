@@ -0,0 +1,117 @@
+use regex::Regex;
+
+use codeowners;
+use errors::*;
+use github;
+use github::api::Session;
+
+struct Rule {
+    pattern: Regex,
+    label: String,
+}
+
+// Labels for pull requests that change files matching gitignore-style patterns, one "<pattern> <label>" per
+// rule, e.g. "docs/** documentation" or "migrations/ db-change".
+pub struct PathLabels {
+    rules: Vec<Rule>,
+}
+
+impl PathLabels {
+    pub fn parse(rules: &Vec<String>) -> PathLabels {
+        let mut parsed = vec![];
+        for rule in rules {
+            let rule = rule.trim();
+            let split = match rule.find(char::is_whitespace) {
+                Some(i) => i,
+                None => {
+                    error!("Path label rule '{}' needs a pattern and a label", rule);
+                    continue;
+                }
+            };
+            if let Some(pattern) = codeowners::pattern_to_regex(&rule[..split]) {
+                parsed.push(Rule {
+                    pattern: pattern,
+                    label: rule[split..].trim().to_string(),
+                });
+            }
+        }
+        PathLabels { rules: parsed }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // labels that any of the paths call for, in rule order
+    pub fn labels_for(&self, paths: &Vec<String>) -> Vec<String> {
+        let mut labels: Vec<String> = vec![];
+        for rule in &self.rules {
+            if !labels.contains(&rule.label) && paths.iter().any(|p| rule.pattern.is_match(p)) {
+                labels.push(rule.label.clone());
+            }
+        }
+        labels
+    }
+
+    fn manages(&self, label: &str) -> bool {
+        self.rules.iter().any(|r| r.label == label)
+    }
+}
+
+// Adds the labels the PR's files call for, and takes off the ones they no longer do, e.g. after a push
+// that reverted the docs changes. Labels that no rule gives are left alone.
+pub fn apply(
+    github: &Session,
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+    path_labels: &PathLabels,
+) -> Result<()> {
+    let owner = repo.owner.login();
+    let files = github.get_pull_request_files(owner, &repo.name, pull_request.number)?;
+    let paths = files.into_iter().map(|f| f.filename).collect();
+    let wanted = path_labels.labels_for(&paths);
+
+    let current: Vec<String> = github
+        .get_pull_request_labels(owner, &repo.name, pull_request.number)?
+        .into_iter()
+        .map(|l| l.name)
+        .collect();
+    for label in current.iter().filter(|l| path_labels.manages(l) && !wanted.contains(l)) {
+        github.remove_pull_request_label(owner, &repo.name, pull_request.number, label)?;
+    }
+
+    let missing: Vec<String> = wanted.into_iter().filter(|l| !current.contains(l)).collect();
+    if !missing.is_empty() {
+        github.add_pull_request_labels(owner, &repo.name, pull_request.number, missing)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_labels_for() {
+        let rules = PathLabels::parse(&paths(
+            &["docs/** documentation", "migrations/ db change", "*.md documentation"],
+        ));
+        assert_eq!(paths(&["documentation"]), rules.labels_for(&paths(&["docs/setup.md", "README.md"])));
+        assert_eq!(
+            paths(&["db change", "documentation"]),
+            rules.labels_for(&paths(&["src/main.rs", "migrations/001.sql", "README.md"]))
+        );
+        assert!(rules.labels_for(&paths(&["src/main.rs"])).is_empty());
+        assert!(rules.manages("db change"));
+        assert!(!rules.manages("bug"));
+    }
+
+    #[test]
+    fn test_parse_bad_rules() {
+        assert!(PathLabels::parse(&paths(&["docs/**", ""])).is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+use self::PrCommand::*;
+
+// Commands given to octobot in pull request comments, one per line: "octobot backport 2.3 2.4",
+// "@octobot retry-backport", "octobot: merge".
+#[derive(Debug, PartialEq, Clone)]
+pub enum PrCommand {
+    // backport the merged PR to the release branches of these versions
+    Backport(Vec<String>),
+    // backport the merged PR again for each of its backport labels
+    RetryBackport,
+    Merge,
+    Unknown(String),
+}
+
+pub const HELP: &'static str = "octobot commands: `octobot backport <version>...`, `octobot retry-backport`, \
+                                `octobot merge`";
+
+pub fn parse(body: &str) -> Vec<PrCommand> {
+    body.lines().filter_map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> Option<PrCommand> {
+    let mut words = line.split_whitespace();
+    match words.next() {
+        Some(w) if w.trim_left_matches('@').trim_right_matches(':').to_lowercase() == "octobot" => (),
+        _ => return None,
+    }
+
+    let words: Vec<&str> = words.collect();
+    let command = match words.get(0) {
+        Some(c) => c.to_lowercase(),
+        // just saying octobot's name
+        None => return None,
+    };
+    Some(match command.as_str() {
+        "backport" if words.len() > 1 => Backport(words[1..].iter().map(|v| v.to_string()).collect()),
+        "retry-backport" if words.len() == 1 => RetryBackport,
+        "merge" if words.len() == 1 => Merge,
+        _ => Unknown(words.join(" ")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(vec![Backport(vec!["2.3".into()])], parse("octobot backport 2.3"));
+        assert_eq!(vec![Backport(vec!["2.3".into(), "2.4".into()])], parse("@octobot: backport 2.3 2.4\n"));
+        assert_eq!(vec![RetryBackport], parse("Looks like it failed.\r\n\r\nOctobot retry-backport"));
+        assert_eq!(vec![Merge, RetryBackport], parse("octobot merge\n  octobot retry-backport"));
+        assert_eq!(vec![Unknown("backport".into())], parse("octobot backport"));
+        assert_eq!(vec![Unknown("merge now".into())], parse("octobot merge now"));
+    }
+
+    #[test]
+    fn test_parse_not_commands() {
+        assert!(parse("").is_empty());
+        assert!(parse("octobot").is_empty());
+        assert!(parse("I asked octobot merge to merge it").is_empty());
+        assert!(parse("> octobot merge").is_empty());
+        assert!(parse("octobots merge").is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use errors::*;
+use github;
+use github::api::Session;
+use json_store::JsonStore;
+use messenger::{self, Messenger};
+use slack::SlackAttachmentBuilder;
+
+pub const CONTEXT: &'static str = "octobot/dependencies";
+
+// A pull request on the same github host, e.g. "org/repo#12".
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PrRef {
+    pub repo: github::Repo,
+    pub number: u32,
+}
+
+impl PrRef {
+    pub fn new(repo: &github::Repo, number: u32) -> PrRef {
+        PrRef {
+            repo: repo.clone(),
+            number: number,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!("{}#{}", self.repo.full_name, self.number)
+    }
+}
+
+impl PartialEq for PrRef {
+    fn eq(&self, other: &PrRef) -> bool {
+        self.name() == other.name()
+    }
+}
+
+// The open pull requests waiting for others to merge, by the name of the one they wait for.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Dependents {
+    waiting: HashMap<String, Vec<PrRef>>,
+}
+
+impl Dependents {
+    // Makes the dependent wait for exactly these, returning whether it was waiting for anything before.
+    pub fn set(&mut self, dependent: &PrRef, dependencies: &Vec<PrRef>) -> bool {
+        let mut was_waiting = false;
+        for dependents in self.waiting.values_mut() {
+            let before = dependents.len();
+            dependents.retain(|d| d != dependent);
+            was_waiting = was_waiting || dependents.len() != before;
+        }
+        self.waiting.retain(|_, dependents| !dependents.is_empty());
+
+        for dependency in dependencies {
+            self.waiting.entry(dependency.name()).or_insert(vec![]).push(dependent.clone());
+        }
+        was_waiting
+    }
+
+    // the pull requests that were waiting for this one, which no longer are
+    pub fn take(&mut self, dependency: &PrRef) -> Vec<PrRef> {
+        self.waiting.remove(&dependency.name()).unwrap_or(vec![])
+    }
+}
+
+// "Depends on #12", "Blocked by org/repo#34", or several at once: "Depends on #12, #13 and org/repo#34".
+pub fn parse(body: &str, repo: &github::Repo) -> Vec<PrRef> {
+    let keyword = Regex::new(r"(?i)\b(depends on|blocked by)\b").unwrap();
+    let reference = Regex::new(r"^[\s,:]*(?:and\s+)?([\w.-]+/[\w.-]+)?#(\d+)").unwrap();
+
+    let mut found = vec![];
+    for line in body.lines() {
+        for keyword in keyword.find_iter(line) {
+            let mut rest = &line[keyword.end()..];
+            while let Some(captures) = reference.captures(rest) {
+                let number = captures.get(2).and_then(|n| n.as_str().parse::<u32>().ok());
+                let dependency_repo = match captures.get(1) {
+                    Some(name) => repo.sibling(name.as_str()).ok(),
+                    None => Some(repo.clone()),
+                };
+                if let (Some(dependency_repo), Some(number)) = (dependency_repo, number) {
+                    let dependency = PrRef::new(&dependency_repo, number);
+                    if !found.contains(&dependency) {
+                        found.push(dependency);
+                    }
+                }
+                rest = &rest[captures.get(0).unwrap().end()..];
+            }
+        }
+    }
+    found
+}
+
+pub fn status(pull_request: &github::PullRequest, open: &Vec<PrRef>) -> github::Status {
+    let (state, description) = if open.is_empty() {
+        ("success", "Nothing to wait for".to_string())
+    } else {
+        let names: Vec<String> = open.iter().map(|d| d.name()).collect();
+        ("pending", format!("Waiting for {} to merge", names.join(", ")))
+    };
+
+    github::Status {
+        state: state.into(),
+        target_url: Some(pull_request.html_url.clone()),
+        context: Some(CONTEXT.into()),
+        description: Some(description),
+        creator: None,
+    }
+}
+
+// Sets the "octobot/dependencies" status on the PR's head: pending while any of the pull requests its description
+// depends on are open. PRs that never had any dependencies don't get one.
+pub fn check(
+    github: &Session,
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+    dependents: &JsonStore<Dependents>,
+) -> Result<()> {
+    let this = PrRef::new(repo, pull_request.number);
+    if !pull_request.is_open() {
+        dependents.write(|d| d.set(&this, &vec![]));
+        return Ok(());
+    }
+
+    let dependencies = parse(pull_request.body.as_ref().map_or("", |b| b.as_str()), repo);
+    let mut open = vec![];
+    for dependency in dependencies.iter().cloned() {
+        let dependency_repo = &dependency.repo;
+        match github.get_pull_request(dependency_repo.owner.login(), &dependency_repo.name, dependency.number) {
+            Ok(ref pr) if !pr.is_open() => (),
+            Ok(_) => open.push(dependency),
+            Err(e) => {
+                error!("Error looking up dependency {} of PR #{}: {}", dependency.name(), pull_request.number, e);
+                open.push(dependency);
+            }
+        }
+    }
+
+    let was_waiting = dependents.write(|d| d.set(&this, &open));
+    if dependencies.is_empty() && !was_waiting {
+        return Ok(());
+    }
+    github.create_status(repo.owner.login(), &repo.name, &pull_request.head.sha, &status(pull_request, &open))
+}
+
+// Once a pull request merges, the ones waiting for it get their status updated, and their owners are told.
+pub fn dependency_merged(
+    github: &Session,
+    messenger: &Messenger,
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+    dependents: &JsonStore<Dependents>,
+) {
+    let merged = PrRef::new(repo, pull_request.number);
+    for dependent in dependents.write(|d| d.take(&merged)) {
+        let dependent_repo = &dependent.repo;
+        let dependent_pr = match github.get_pull_request(
+            dependent_repo.owner.login(),
+            &dependent_repo.name,
+            dependent.number,
+        ) {
+            Ok(pr) => pr,
+            Err(e) => {
+                error!("Error looking up dependent PR {}: {}", dependent.name(), e);
+                continue;
+            }
+        };
+        if let Err(e) = check(github, dependent_repo, &dependent_pr, dependents) {
+            error!("Error checking dependencies of PR {}: {}", dependent.name(), e);
+        }
+
+        let msg = format!("Pull Request {} was merged", merged.name());
+        let attachments = vec![
+            SlackAttachmentBuilder::new("")
+                .title(format!("Pull Request #{}: \"{}\"", dependent_pr.number, dependent_pr.title))
+                .title_link(dependent_pr.html_url.as_str())
+                .build(),
+        ];
+        messenger.send_to_users(
+            &msg,
+            &attachments,
+            &vec![dependent_pr.user.clone()],
+            dependent_repo,
+            messenger::Reason::Participant,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(name: &str) -> github::Repo {
+        github::Repo::parse(&format!("http://git.foo.com/{}", name)).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let the_repo = repo("org/repo");
+        assert_eq!(Vec::<PrRef>::new(), parse("Fixes #12", &the_repo));
+        assert_eq!(vec![PrRef::new(&the_repo, 12)], parse("Depends on #12", &the_repo));
+        assert_eq!(vec![PrRef::new(&repo("org/other"), 34)], parse("This is blocked by org/other#34.", &the_repo));
+        assert_eq!(
+            vec![PrRef::new(&the_repo, 12), PrRef::new(&the_repo, 13), PrRef::new(&repo("org/other"), 34)],
+            parse("Some change\n\ndepends on #12, #13 and org/other#34\nDepends on: #12", &the_repo)
+        );
+        assert_eq!("org/other#34", PrRef::new(&repo("org/other"), 34).name());
+    }
+
+    #[test]
+    fn test_dependents() {
+        let mut dependents = Dependents::default();
+        let dependent = PrRef::new(&repo("org/repo"), 1);
+        let first = PrRef::new(&repo("org/repo"), 2);
+        let second = PrRef::new(&repo("org/other"), 3);
+
+        assert!(!dependents.set(&dependent, &vec![first.clone(), second.clone()]));
+        assert!(dependents.set(&dependent, &vec![second.clone()]));
+        assert_eq!(Vec::<PrRef>::new(), dependents.take(&first));
+        assert_eq!(vec![dependent.clone()], dependents.take(&second));
+        assert!(!dependents.set(&dependent, &vec![]));
+    }
+
+    #[test]
+    fn test_status() {
+        let mut pr = github::PullRequest::new();
+        pr.html_url = "http://the-pr".into();
+
+        let pending = status(&pr, &vec![PrRef::new(&repo("org/repo"), 2), PrRef::new(&repo("org/other"), 3)]);
+        assert_eq!("pending", pending.state);
+        assert_eq!(Some("Waiting for org/repo#2, org/other#3 to merge".to_string()), pending.description);
+        assert_eq!(Some(CONTEXT.to_string()), pending.context);
+
+        assert_eq!("success", status(&pr, &vec![]).state);
+    }
+}
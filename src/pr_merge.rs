@@ -1,46 +1,181 @@
 use std::borrow::Borrow;
-use std::sync::Arc;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 use regex::Regex;
 use threadpool::{self, ThreadPool};
 
+use audit::{self, AuditLog};
 use config::Config;
 use errors::*;
 use git::Git;
 use git_clone_manager::GitCloneManager;
 use github;
 use github::api::Session;
-use messenger;
-use slack::{SlackAttachmentBuilder, SlackRequest};
-use worker::{self, WorkSender};
+use json_store::JsonStore;
+use messenger::MessengerFactory;
+use repos::BackportMirror;
+use slack::SlackAttachmentBuilder;
+use tracing::{self, SpanContext, Tracer};
+use version::Version;
+use worker;
+
+// How a backport brings a pull request's changes over to the target branch.
+#[derive(Debug, Clone)]
+pub struct BackportOptions {
+    // "squash" cherry-picks the pull request's merge as one commit, "rebase" cherry-picks each of its commits
+    pub strategy: String,
+    // the backport's commit message (and PR title and description), see `render_template`
+    pub message_template: Option<String>,
+    // instead of the original PR's milestone
+    pub milestone: Option<String>,
+    // backport labels to put on the backport, so that merging it backports it on to their branches
+    pub carried_labels: Vec<String>,
+}
+
+impl BackportOptions {
+    pub fn new() -> BackportOptions {
+        BackportOptions {
+            strategy: "squash".into(),
+            message_template: None,
+            milestone: None,
+            carried_labels: vec![],
+        }
+    }
+
+    pub fn for_repo(config: &Config, repo: &github::Repo, branch: &str, target_branch: &str) -> BackportOptions {
+        BackportOptions {
+            strategy: config.repos().backport_strategy(repo, branch),
+            message_template: config.repos().backport_message_template(repo, branch),
+            milestone: config.repos().backport_milestone(repo, branch, target_branch),
+            carried_labels: vec![],
+        }
+    }
+}
 
 pub fn merge_pull_request(
     session: &Session,
     clone_mgr: &GitCloneManager,
+    audit_log: &AuditLog,
     owner: &str,
     repo: &str,
     pull_request: &github::PullRequest,
     target_branch: &str,
-) -> Result<github::PullRequest> {
-    Merger::new(session, clone_mgr).merge_pull_request(
+    options: &BackportOptions,
+) -> Result<Option<github::PullRequest>> {
+    Merger::new(session, clone_mgr, audit_log).merge_pull_request(
         owner,
         repo,
         pull_request,
         target_branch,
+        options,
     )
 }
 
+#[derive(Debug, PartialEq)]
+pub enum BranchUpdate {
+    UpToDate,
+    Updated,
+    Conflicts,
+}
+
+// Brings a pull request's branch up to date with its base by merging the base into it, like github's "Update
+// branch" button. Nothing is pushed if that conflicts.
+pub fn update_branch(
+    session: &Session,
+    clone_mgr: &GitCloneManager,
+    audit_log: &AuditLog,
+    owner: &str,
+    repo: &str,
+    pull_request: &github::PullRequest,
+) -> Result<BranchUpdate> {
+    let head = &pull_request.head.ref_name;
+    let base = format!("origin/{}", pull_request.base.ref_name);
+
+    let held_clone_dir = clone_mgr.clone(owner, repo)?;
+    let git = Git::new(session.github_host(), session.github_token(), held_clone_dir.dir());
+
+    git.checkout_branch(head, &format!("origin/{}", head))?;
+    if git.does_branch_contain(&base, head)? {
+        return Ok(BranchUpdate::UpToDate);
+    }
+
+    let msg = format!("Merge branch '{}' into {}", pull_request.base.ref_name, head);
+    if !git.merge(&base, &msg)? {
+        return Ok(BranchUpdate::Conflicts);
+    }
+
+    let target = format!("{}/{} {}", owner, repo, head);
+    if session.dry_run(owner, repo) {
+        audit_log.record("push_branch", &target, audit::DRY_RUN);
+        return Err(format!("Dry run: not pushing {}", head).into());
+    }
+    let pushed = git.run(&["push", "origin", &format!("{}:{}", head, head)]);
+    audit_log.record("push_branch", &target, &audit::outcome(&pushed));
+    pushed?;
+
+    Ok(BranchUpdate::Updated)
+}
+
+// Gives a backport the original PR's labels (except for backport labels), requested reviewers, and milestone, or
+// the given milestone instead, so that whatever tracks them keeps working. Each of them is copied even if another
+// couldn't be.
+pub fn copy_to_backport(
+    session: &Session,
+    owner: &str,
+    repo: &str,
+    pull_request: &github::PullRequest,
+    backport: &github::PullRequest,
+    milestone: Option<&String>,
+) {
+    match session.get_pull_request_labels(owner, repo, pull_request.number) {
+        Ok(labels) => {
+            let labels: Vec<String> = labels
+                .into_iter()
+                .map(|l| l.name)
+                .filter(|l| !l.to_lowercase().starts_with("backport-"))
+                .collect();
+            if !labels.is_empty() {
+                if let Err(e) = session.add_pull_request_labels(owner, repo, backport.number, labels) {
+                    error!("Error labeling backport PR #{}: {}", backport.number, e);
+                }
+            }
+        }
+        Err(e) => error!("Error getting labels for PR #{}: {}", pull_request.number, e),
+    };
+
+    let reviewers: Vec<String> = match pull_request.requested_reviewers {
+        Some(ref r) => r.iter().map(|u| u.login().to_string()).collect(),
+        None => vec![],
+    };
+    if !reviewers.is_empty() {
+        if let Err(e) = session.request_review(owner, repo, backport.number, reviewers) {
+            error!("Error requesting review of backport PR #{}: {}", backport.number, e);
+        }
+    }
+
+    let milestone = milestone.or(pull_request.milestone.as_ref().map(|m| &m.title));
+    if let Some(milestone) = milestone {
+        if let Err(e) = session.set_pull_request_milestone(owner, repo, backport.number, milestone) {
+            error!("Error setting milestone of backport PR #{}: {}", backport.number, e);
+        }
+    }
+}
 
 struct Merger<'a> {
     session: &'a Session,
     clone_mgr: &'a GitCloneManager,
+    audit_log: &'a AuditLog,
 }
 
 impl<'a> Merger<'a> {
-    pub fn new(session: &'a Session, clone_mgr: &'a GitCloneManager) -> Merger<'a> {
+    pub fn new(session: &'a Session, clone_mgr: &'a GitCloneManager, audit_log: &'a AuditLog) -> Merger<'a> {
         Merger {
             session: session,
             clone_mgr: clone_mgr,
+            audit_log: audit_log,
         }
     }
 
@@ -50,58 +185,172 @@ impl<'a> Merger<'a> {
         repo: &str,
         pull_request: &github::PullRequest,
         target_branch: &str,
-    ) -> Result<github::PullRequest> {
-        if !pull_request.is_merged() {
-            return Err(format!("Pull Request #{} is not yet merged.", pull_request.number).into());
+        options: &BackportOptions,
+    ) -> Result<Option<github::PullRequest>> {
+        let merge_commit_sha = merge_commit(pull_request)?;
+        let pr_branch_name = pr_branch_name(pull_request, target_branch);
+
+        let held_clone_dir = self.clone_mgr.clone(owner, repo)?;
+        let clone_dir = held_clone_dir.dir();
+
+        let git = Git::new(self.session.github_host(), self.session.github_token(), clone_dir);
+        check_no_branch(&git, &pr_branch_name)?;
+
+        let commits = if options.strategy == "rebase" {
+            Some(self.get_commits(&git, owner, repo, pull_request)?)
+        } else {
+            None
+        };
+
+        if self.already_on_target(&git, merge_commit_sha, commits.as_ref(), target_branch)? {
+            let msg = format!("Not backporting to {}: its changes are already there.", target_branch);
+            self.session.comment_pull_request(owner, repo, pull_request.number, &msg)?;
+            return Ok(None);
         }
 
-        let merge_commit_sha;
-        if let Some(ref sha) = pull_request.merge_commit_sha {
-            merge_commit_sha = sha;
+        let pr_ref = format!("#{}", pull_request.number);
+        let (title, body) = if let Some(ref commits) = commits {
+            self.cherry_pick_commits(&git, commits, pull_request, &pr_branch_name, target_branch, options)?
         } else {
-            return Err(format!("Pull Request #{} has no merge commit.", pull_request.number).into());
+            self.cherry_pick(&git, &merge_commit_sha, &pr_branch_name, pull_request, &pr_ref, &target_branch, options)?
+        };
+
+        let new_pr = self.push_and_open(&git, owner, repo, &pr_branch_name, target_branch, &title, &body)?;
+
+        let assignees: Vec<String> = pull_request.assignees.iter().map(|a| a.login().to_string()).collect();
+        self.session.assign_pull_request(owner, repo, new_pr.number, assignees)?;
+
+        copy_to_backport(self.session, owner, repo, pull_request, &new_pr, options.milestone.as_ref());
+        if !options.carried_labels.is_empty() {
+            self.session.add_pull_request_labels(owner, repo, new_pr.number, options.carried_labels.clone())?;
         }
 
-        // strip everything before last slash
-        let regex = Regex::new(r".*/").unwrap();
-        let pr_branch_name =
-            format!("{}-{}", regex.replace(&pull_request.head.ref_name, ""), regex.replace(&target_branch, ""));
+        Ok(Some(new_pr))
+    }
+
+    // Backports a pull request merged in `source` into `target_branch` of `target`, another repo on the same host.
+    // Its merge commit is cherry-picked as one commit, or with path mappings, only its changes under the mapped paths
+    // are brought over, moved to where they live in `target`.
+    pub fn mirror_pull_request(
+        &self,
+        source: &github::Repo,
+        target: &github::Repo,
+        pull_request: &github::PullRequest,
+        target_branch: &str,
+        path_mappings: &Vec<(String, String)>,
+        options: &BackportOptions,
+    ) -> Result<Option<github::PullRequest>> {
+        let merge_commit_sha = merge_commit(pull_request)?;
+        let pr_branch_name = pr_branch_name(pull_request, target_branch);
+        let (owner, repo) = (target.owner.login(), target.name.as_str());
 
         let held_clone_dir = self.clone_mgr.clone(owner, repo)?;
-        let clone_dir = held_clone_dir.dir();
+        let git = Git::new(self.session.github_host(), self.session.github_token(), held_clone_dir.dir());
+        check_no_branch(&git, &pr_branch_name)?;
 
-        let git = Git::new(self.session.github_host(), self.session.github_token(), clone_dir);
+        // the merge commit is only in the source repo, on the branch it was merged into
+        let source_url = self.session.clone_url(source.owner.login(), &source.name);
+        let source_branch = format!("+refs/heads/{}:{}", pull_request.base.ref_name, MIRROR_SOURCE_REF);
+        git.run(&["fetch", &source_url, &source_branch])?;
+
+        let pr_ref = format!("{}#{}", source.full_name, pull_request.number);
+        let (title, body) = if path_mappings.is_empty() {
+            if self.already_on_target(&git, merge_commit_sha, None, target_branch)? {
+                let msg =
+                    format!("Not backporting to {} in {}: its changes are already there.", target_branch, repo);
+                self.session.comment_pull_request(source.owner.login(), &source.name, pull_request.number, &msg)?;
+                return Ok(None);
+            }
+            self.cherry_pick(&git, merge_commit_sha, &pr_branch_name, pull_request, &pr_ref, target_branch, options)?
+        } else {
+            self.apply_mapped(
+                &git,
+                merge_commit_sha,
+                path_mappings,
+                &pr_branch_name,
+                pull_request,
+                &pr_ref,
+                target_branch,
+                options,
+            )?
+        };
 
-        // make sure there isn't already such a branch
-        let current_remotes = git.run(&["ls-remote", "--heads"])?;
-        if current_remotes.contains(&format!("refs/heads/{}", pr_branch_name)) {
-            return Err(format!("PR branch already exists on origin: '{}'", pr_branch_name).into());
+        let new_pr = self.push_and_open(&git, owner, repo, &pr_branch_name, target_branch, &title, &body)?;
+
+        // the source PR's assignees may not have access to the mirror: that shouldn't fail the backport
+        let assignees: Vec<String> = pull_request.assignees.iter().map(|a| a.login().to_string()).collect();
+        if let Err(e) = self.session.assign_pull_request(owner, repo, new_pr.number, assignees) {
+            error!("Error assigning backport PR {}#{}: {}", target.full_name, new_pr.number, e);
         }
 
-        let (title, body) = self.cherry_pick(
-            &git,
-            &merge_commit_sha,
-            &pr_branch_name,
-            pull_request.number,
-            &target_branch,
-            &pull_request.base.ref_name,
-        )?;
+        Ok(Some(new_pr))
+    }
 
-        git.run(&["push", "origin", &format!("{}:{}", pr_branch_name, pr_branch_name)])?;
+    fn push_and_open(
+        &self,
+        git: &Git,
+        owner: &str,
+        repo: &str,
+        pr_branch_name: &str,
+        target_branch: &str,
+        title: &str,
+        body: &str,
+    ) -> Result<github::PullRequest> {
+        let target = format!("{}/{} {}", owner, repo, pr_branch_name);
+        if self.session.dry_run(owner, repo) {
+            self.audit_log.record("push_branch", &target, audit::DRY_RUN);
+            return Err(format!("Dry run: not pushing {}", pr_branch_name).into());
+        }
+        let pushed = git.run(&["push", "origin", &format!("{}:{}", pr_branch_name, pr_branch_name)]);
+        self.audit_log.record("push_branch", &target, &audit::outcome(&pushed));
+        pushed?;
 
-        let new_pr = self.session.create_pull_request(
-            owner,
-            repo,
-            &title,
-            &body,
-            &pr_branch_name,
-            &target_branch,
-        )?;
+        self.session.create_pull_request(owner, repo, title, body, pr_branch_name, target_branch)
+    }
 
-        let assignees: Vec<String> = pull_request.assignees.iter().map(|a| a.login().to_string()).collect();
-        self.session.assign_pull_request(owner, repo, new_pr.number, assignees)?;
+    fn get_commits(
+        &self,
+        git: &Git,
+        owner: &str,
+        repo: &str,
+        pull_request: &github::PullRequest,
+    ) -> Result<Vec<github::Commit>> {
+        let commits = self.session.get_pull_request_commits(owner, repo, pull_request.number)?;
+        if commits.is_empty() {
+            return Err(format!("Pull Request #{} has no commits.", pull_request.number).into());
+        }
+
+        // after a squash or rebase merge, the PR's commits are only reachable from github's ref for it
+        git.run(&["fetch", "origin", &format!("+refs/pull/{}/head", pull_request.number)])?;
+        Ok(commits)
+    }
+
+    // Whether the target branch already has the PR's changes, e.g. because it was cherry-picked by hand, so that
+    // backporting it would only make an empty or conflicting PR.
+    fn already_on_target(
+        &self,
+        git: &Git,
+        commit_hash: &str,
+        commits: Option<&Vec<github::Commit>>,
+        target_branch: &str,
+    ) -> Result<bool> {
+        let target = format!("origin/{}", target_branch);
+        if let Some(commits) = commits {
+            for commit in commits {
+                if !git.has_equivalent_commits(&target, &commit.sha, &format!("{}^", commit.sha))? {
+                    return Ok(false);
+                }
+            }
+            return Ok(true);
+        }
 
-        Ok(new_pr)
+        // a merge commit brings in the commits of its second parent
+        let merged = format!("{}^2", commit_hash);
+        if git.run(&["rev-parse", "--verify", "--quiet", &merged]).is_ok() {
+            git.has_equivalent_commits(&target, &merged, &format!("{}^1", commit_hash))
+        } else {
+            git.has_equivalent_commits(&target, commit_hash, &format!("{}^", commit_hash))
+        }
     }
 
     fn cherry_pick(
@@ -109,26 +358,25 @@ impl<'a> Merger<'a> {
         git: &Git,
         commit_hash: &str,
         pr_branch_name: &str,
-        pr_number: u32,
+        pull_request: &github::PullRequest,
+        pr_ref: &str,
         target_branch: &str,
-        orig_base_branch: &str,
+        options: &BackportOptions,
     ) -> Result<(String, String)> {
         git.checkout_branch(pr_branch_name, &format!("origin/{}", target_branch))?;
 
+        // a merge commit is picked as its changes to the branch it was merged into
+        let is_merge = git.run(&["rev-parse", "--verify", "--quiet", &format!("{}^2", commit_hash)]).is_ok();
+        let mut args = vec!["-c", "merge.renameLimit=999999", "cherry-pick", "-X", "ignore-all-space"];
+        if is_merge {
+            args.extend(&["-m", "1"]);
+        }
+        args.push(commit_hash);
+
         // cherry-pick!
-        git.run(
-            &[
-                "-c",
-                "merge.renameLimit=999999",
-                "cherry-pick",
-                "-X",
-                "ignore-all-space",
-                commit_hash,
-            ],
-        )?;
+        git.run(&args)?;
 
-        let desc = git.get_commit_desc(commit_hash)?;
-        let desc = make_merge_desc(desc, commit_hash, pr_number, target_branch, orig_base_branch);
+        let desc = self.describe(git, commit_hash, pull_request, pr_ref, target_branch, options)?;
 
         // change commit message
         git.run_with_stdin(
@@ -138,12 +386,170 @@ impl<'a> Merger<'a> {
 
         Ok(desc)
     }
+
+    // Brings over the changes of `commit_hash` under each mapping's first path, moved to its second one, as one
+    // commit by the same author.
+    fn apply_mapped(
+        &self,
+        git: &Git,
+        commit_hash: &str,
+        path_mappings: &Vec<(String, String)>,
+        pr_branch_name: &str,
+        pull_request: &github::PullRequest,
+        pr_ref: &str,
+        target_branch: &str,
+        options: &BackportOptions,
+    ) -> Result<(String, String)> {
+        git.checkout_branch(pr_branch_name, &format!("origin/{}", target_branch))?;
+
+        // against the first parent, so that a merge commit is its changes to the branch it was merged into
+        let parent = format!("{}^1", commit_hash);
+        for &(ref from, ref to) in path_mappings {
+            let relative = format!("--relative={}", from);
+            let mut diff_args = vec!["diff", "--binary"];
+            if !from.is_empty() {
+                diff_args.push(&relative);
+            }
+            diff_args.extend(&[parent.as_str(), commit_hash]);
+            let patch = git.run(&diff_args)?;
+            if patch.is_empty() {
+                continue;
+            }
+
+            let directory = format!("--directory={}", to);
+            let mut apply_args = vec!["apply", "--index"];
+            if !to.is_empty() {
+                apply_args.push(&directory);
+            }
+            git.run_with_stdin(&apply_args, &format!("{}\n", patch))?;
+        }
+        if git.run(&["diff", "--cached", "--quiet"]).is_ok() {
+            return Err(format!("{} changes nothing under the mirrored paths", pr_ref).into());
+        }
+
+        let desc = self.describe(git, commit_hash, pull_request, pr_ref, target_branch, options)?;
+        let author = git.run(&["log", "-1", "--pretty=%an <%ae>", commit_hash])?;
+        git.run_with_stdin(
+            &["commit", "--author", &author, "-F", "-"],
+            &format!("{}\n\n{}", &desc.0, &desc.1),
+        )?;
+
+        Ok(desc)
+    }
+
+    fn describe(
+        &self,
+        git: &Git,
+        commit_hash: &str,
+        pull_request: &github::PullRequest,
+        pr_ref: &str,
+        target_branch: &str,
+        options: &BackportOptions,
+    ) -> Result<(String, String)> {
+        let orig_desc = git.get_commit_desc(commit_hash)?;
+        Ok(match options.message_template {
+            Some(ref template) => render_template(template, &orig_desc, commit_hash, pull_request, target_branch),
+            None => make_merge_desc(orig_desc, commit_hash, pr_ref, target_branch, &pull_request.base.ref_name),
+        })
+    }
+
+    // Cherry-picks each of the PR's own commits, with their own messages. The backport PR is described by the
+    // template, or like a squashed backport of the PR's title and description.
+    fn cherry_pick_commits(
+        &self,
+        git: &Git,
+        commits: &Vec<github::Commit>,
+        pull_request: &github::PullRequest,
+        pr_branch_name: &str,
+        target_branch: &str,
+        options: &BackportOptions,
+    ) -> Result<(String, String)> {
+        git.checkout_branch(pr_branch_name, &format!("origin/{}", target_branch))?;
+
+        for commit in commits {
+            git.run(
+                &[
+                    "-c",
+                    "merge.renameLimit=999999",
+                    "cherry-pick",
+                    "-x",
+                    "-X",
+                    "ignore-all-space",
+                    &commit.sha,
+                ],
+            )?;
+        }
+
+        let orig_desc = (pull_request.title.clone(), pull_request.body.clone().unwrap_or(String::new()));
+        let head = &pull_request.head.sha;
+        Ok(match options.message_template {
+            Some(ref template) => render_template(template, &orig_desc, head, pull_request, target_branch),
+            None => {
+                let pr_ref = format!("#{}", pull_request.number);
+                make_merge_desc(orig_desc, head, &pr_ref, target_branch, &pull_request.base.ref_name)
+            }
+        })
+    }
+}
+
+// where the branch a mirrored pull request was merged into is fetched to in the mirror's clone
+const MIRROR_SOURCE_REF: &'static str = "refs/octobot/mirror-source";
+
+fn merge_commit(pull_request: &github::PullRequest) -> Result<&String> {
+    if !pull_request.is_merged() {
+        return Err(format!("Pull Request #{} is not yet merged.", pull_request.number).into());
+    }
+    match pull_request.merge_commit_sha {
+        Some(ref sha) => Ok(sha),
+        None => Err(format!("Pull Request #{} has no merge commit.", pull_request.number).into()),
+    }
+}
+
+fn pr_branch_name(pull_request: &github::PullRequest, target_branch: &str) -> String {
+    // strip everything before last slash
+    let regex = Regex::new(r".*/").unwrap();
+    format!("{}-{}", regex.replace(&pull_request.head.ref_name, ""), regex.replace(&target_branch, ""))
+}
+
+// make sure there isn't already such a branch
+fn check_no_branch(git: &Git, pr_branch_name: &str) -> Result<()> {
+    let current_remotes = git.run(&["ls-remote", "--heads"])?;
+    if current_remotes.contains(&format!("refs/heads/{}", pr_branch_name)) {
+        return Err(format!("PR branch already exists on origin: '{}'", pr_branch_name).into());
+    }
+    Ok(())
+}
+
+// A backport's (title, body) from a template like "{title} [{target_branch}]\n\n{body}\n\nBackport of #{number}".
+// {title} and {body} are the original commit's (without a trailing PR number), and {number}, {author},
+// {base_branch}, {target_branch}, and {sha} describe the original pull request. The first line is the title.
+pub fn render_template(
+    template: &str,
+    orig_desc: &(String, String),
+    commit_hash: &str,
+    pull_request: &github::PullRequest,
+    target_branch: &str,
+) -> (String, String) {
+    let pr_regex = Regex::new(r"(\s*\(#\d+\))+$").unwrap();
+    let message = template
+        .replace("{title}", &pr_regex.replace(&orig_desc.0, ""))
+        .replace("{body}", &orig_desc.1)
+        .replace("{number}", &pull_request.number.to_string())
+        .replace("{author}", pull_request.user.login())
+        .replace("{base_branch}", &pull_request.base.ref_name)
+        .replace("{target_branch}", target_branch)
+        .replace("{sha}", commit_hash);
+
+    let mut lines = message.trim().lines();
+    let title = lines.next().unwrap_or("").trim().to_string();
+    let body: Vec<&str> = lines.skip_while(|l| l.trim().is_empty()).collect();
+    (title, body.join("\n"))
 }
 
 fn make_merge_desc(
     orig_desc: (String, String),
     commit_hash: &str,
-    pr_number: u32,
+    pr_ref: &str,
     target_branch: &str,
     orig_base_branch: &str,
 ) -> (String, String) {
@@ -158,24 +564,113 @@ fn make_merge_desc(
     if body.len() != 0 {
         body += "\n\n";
     }
-    body += format!("(cherry-picked from {}, PR #{})", commit_hash, pr_number).as_str();
+    body += format!("(cherry-picked from {}, PR {})", commit_hash, pr_ref).as_str();
 
     (title, body)
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PRMergeRequest {
     pub repo: github::Repo,
     pub pull_request: github::PullRequest,
     pub target_branch: String,
+    // see BackportOptions
+    pub carried_labels: Vec<String>,
+    // a mirror of `repo` to make the backport in instead, see `RepoInfo::backport_mirrors`
+    #[serde(default)]
+    pub target_repo: Option<github::Repo>,
+    // see `BackportMirror`
+    #[serde(default)]
+    pub path_mappings: Vec<(String, String)>,
+    // the span that asked for the backport, so that the merge goes on the same trace
+    #[serde(skip)]
+    pub trace: Option<SpanContext>,
+}
+
+impl PRMergeRequest {
+    // the repo the backport PR goes in
+    pub fn target(&self) -> &github::Repo {
+        self.target_repo.as_ref().unwrap_or(&self.repo)
+    }
 }
 
 struct Runner {
     config: Arc<Config>,
     github_session: Arc<Session>,
     clone_mgr: Arc<GitCloneManager>,
-    slack: WorkSender<SlackRequest>,
+    messengers: MessengerFactory,
     thread_pool: ThreadPool,
+    branches: Arc<Mutex<BranchQueues>>,
+    audit_log: Arc<AuditLog>,
+    tracer: Option<Arc<Tracer>>,
+    running: Arc<JsonStore<RunningMerges>>,
+}
+
+// The backports that were taken off the queue and haven't finished: running, or waiting for the one before them
+// into the same branch. They're started again when octobot restarts.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RunningMerges {
+    merges: Vec<PRMergeRequest>,
+}
+
+impl RunningMerges {
+    fn key(req: &PRMergeRequest) -> String {
+        format!("{}#{}:{}:{}", req.repo.full_name, req.pull_request.number, req.target().full_name, req.target_branch)
+    }
+
+    pub fn add(&mut self, req: &PRMergeRequest) {
+        self.merges.push(req.clone());
+    }
+
+    pub fn finish(&mut self, req: &PRMergeRequest) {
+        let key = RunningMerges::key(req);
+        if let Some(i) = self.merges.iter().position(|m| RunningMerges::key(m) == key) {
+            self.merges.remove(i);
+        }
+    }
+
+    pub fn take_all(&mut self) -> Vec<PRMergeRequest> {
+        self.merges.drain(..).collect()
+    }
+}
+
+// Merges into different repos and branches run at the same time, but ones into the same branch wait for
+// each other so that they are cherry-picked in the order they were merged.
+#[derive(Default)]
+struct BranchQueues {
+    // keyed by repo and target branch: present while a merge into it is running, with the ones waiting behind it
+    waiting: HashMap<String, VecDeque<PRMergeRequest>>,
+}
+
+impl BranchQueues {
+    fn key(req: &PRMergeRequest) -> String {
+        format!("{}:{}", req.target().full_name, req.target_branch)
+    }
+
+    // the request back if it can run right away
+    fn start(&mut self, req: PRMergeRequest) -> Option<PRMergeRequest> {
+        let key = BranchQueues::key(&req);
+        if let Some(queue) = self.waiting.get_mut(&key) {
+            queue.push_back(req);
+            return None;
+        }
+        self.waiting.insert(key, VecDeque::new());
+        Some(req)
+    }
+
+    // the next request for the same branch, if any
+    fn finish(&mut self, req: &PRMergeRequest) -> Option<PRMergeRequest> {
+        let key = BranchQueues::key(req);
+        let next = self.waiting.get_mut(&key).and_then(|q| q.pop_front());
+        if next.is_none() {
+            self.waiting.remove(&key);
+        }
+        next
+    }
+
+    fn len(&self) -> usize {
+        self.waiting.values().map(|q| q.len()).sum()
+    }
 }
 
 pub fn req(repo: &github::Repo, pull_request: &github::PullRequest, target_branch: &str) -> PRMergeRequest {
@@ -183,80 +678,347 @@ pub fn req(repo: &github::Repo, pull_request: &github::PullRequest, target_branc
         repo: repo.clone(),
         pull_request: pull_request.clone(),
         target_branch: target_branch.to_string(),
+        carried_labels: vec![],
+        target_repo: None,
+        path_mappings: vec![],
+        trace: tracing::current(),
     }
 }
 
+// A backport of a pull request in `repo` into `target_branch` of one of its mirrors.
+pub fn mirror_req(
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+    target_branch: &str,
+    mirror: &BackportMirror,
+) -> Result<PRMergeRequest> {
+    let mut req = req(repo, pull_request, target_branch);
+    req.target_repo = Some(repo.sibling(&mirror.repo)?);
+    req.path_mappings = mirror.path_mappings.clone();
+    Ok(req)
+}
+
+// A backport whose backport PR gets `carried_labels`, to chain backports from one branch to the next.
+pub fn chained_req(
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+    target_branch: &str,
+    carried_labels: Vec<String>,
+) -> PRMergeRequest {
+    let mut req = req(repo, pull_request, target_branch);
+    req.carried_labels = carried_labels;
+    req
+}
+
+// Sorts backport target branches oldest first, going by the version they end in (e.g. "release/1.10" comes after
+// "release/1.9"). Branches without a version go last, in the order they were in.
+pub fn sort_backport_branches<T, F>(targets: &mut Vec<T>, branch: F)
+where
+    F: Fn(&T) -> &str,
+{
+    let version = |t: &T| Version::parse_trailing(branch(t));
+    targets.sort_by(|a, b| match (version(a), version(b)) {
+        (Some(ref a), Some(ref b)) => a.cmp(b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+}
+
+// backports waiting, either for a merge thread or for the one before it into the same branch. more than this
+// get turned away, and the admin channel is told.
+const MAX_QUEUED_MERGES: usize = 1000;
+
 pub fn new_worker(
     max_concurrency: usize,
     config: Arc<Config>,
     github_session: Arc<Session>,
     clone_mgr: Arc<GitCloneManager>,
-    slack: WorkSender<SlackRequest>,
+    messengers: MessengerFactory,
+    audit_log: Arc<AuditLog>,
+    tracer: Option<Arc<Tracer>>,
+    queue_file: Option<PathBuf>,
+    running_file: Option<PathBuf>,
 ) -> worker::Worker<PRMergeRequest> {
-    worker::Worker::new(
+    let running = Arc::new(JsonStore::new(running_file));
+    let interrupted = running.write(|r: &mut RunningMerges| r.take_all());
+    let worker = worker::Worker::bounded_persistent(
         "pr-merge",
         Runner {
             config: config,
             github_session: github_session,
             clone_mgr: clone_mgr.clone(),
-            slack: slack,
+            messengers: messengers,
             thread_pool: threadpool::Builder::new()
                 .num_threads(max_concurrency)
                 .thread_name("pr-merge".to_string())
                 .build(),
+            branches: Arc::new(Mutex::new(BranchQueues::default())),
+            audit_log: audit_log,
+            tracer: tracer,
+            running: running,
         },
-    )
+        MAX_QUEUED_MERGES,
+        queue_file,
+    );
+
+    // the ones a restart cut off start over, as if they had just been queued
+    if !interrupted.is_empty() {
+        info!("Restarting {} interrupted backports", interrupted.len());
+    }
+    let sender = worker.new_sender();
+    for req in interrupted {
+        if let Err(e) = sender.send(req) {
+            error!("Error restarting backport: {}", e);
+        }
+    }
+    worker
 }
 
 impl worker::Runner<PRMergeRequest> for Runner {
     fn handle(&self, req: PRMergeRequest) {
-        let github_session = self.github_session.clone();
-        let clone_mgr = self.clone_mgr.clone();
-        let config = self.config.clone();
-
-        let slack = self.slack.clone();
-
-        // launch another thread to do the merge
-        self.thread_pool.execute(move || if let Err(e) = merge_pull_request(
-            github_session.borrow(),
-            &clone_mgr,
-            &req.repo.owner.login(),
-            &req.repo.name,
-            &req.pull_request,
-            &req.target_branch,
-        )
-        {
-
-            let attach = SlackAttachmentBuilder::new(&format!("{}", e))
-                .title(
-                    format!("Source PR: #{}: \"{}\"", req.pull_request.number, req.pull_request.title)
-                        .as_str(),
-                )
+        let waiting = self.thread_pool.queued_count() + self.branches.lock().unwrap().len();
+        if waiting >= MAX_QUEUED_MERGES {
+            error!(
+                "{} backports are waiting, dropping PR #{} to {}",
+                waiting,
+                req.pull_request.number,
+                req.target_branch
+            );
+            let attach = SlackAttachmentBuilder::new(&format!("{} into {}", req.target().full_name, req.target_branch))
+                .title(format!("Source PR: #{}: \"{}\"", req.pull_request.number, req.pull_request.title).as_str())
                 .title_link(req.pull_request.html_url.clone())
                 .color("danger")
                 .build();
-
-            let messenger = messenger::new(config, slack);
-            messenger.send_to_owner(
-                "Error creating merge Pull Request",
+            self.messengers.new_messenger().send_to_admins(
+                &format!("Too many backports are waiting ({}), skipped one", waiting),
                 &vec![attach],
-                &req.pull_request.user,
-                &req.repo,
             );
+            return;
+        }
+
+        self.running.write(|r| r.add(&req));
+        let req = match self.branches.lock().unwrap().start(req) {
+            Some(r) => r,
+            None => return,
+        };
+
+        let config = self.config.clone();
+        let github_session = self.github_session.clone();
+        let clone_mgr = self.clone_mgr.clone();
+        let messengers = self.messengers.clone();
+        let branches = self.branches.clone();
+        let audit_log = self.audit_log.clone();
+        let tracer = self.tracer.clone();
+        let running = self.running.clone();
+
+        // launch another thread to do the merge, and the ones into the same branch that come in meanwhile
+        self.thread_pool.execute(move || {
+            let mut next = Some(req);
+            while let Some(req) = next {
+                let trigger = format!("backport of {}#{}", req.repo.full_name, req.pull_request.number);
+                let mut options = BackportOptions::for_repo(
+                    &config,
+                    &req.repo,
+                    &req.pull_request.base.ref_name,
+                    &req.target_branch,
+                );
+                options.carried_labels = req.carried_labels.clone();
+                let attributes = [
+                    ("repo", req.repo.full_name.clone()),
+                    ("pull_request", req.pull_request.number.to_string()),
+                    ("target_branch", req.target_branch.clone()),
+                    ("target_repo", req.target().full_name.clone()),
+                ];
+                tracing::trace(tracer.as_ref().map(|t| &**t), "pr_merge", req.trace.as_ref(), &attributes, || {
+                    audit::with_trigger(&trigger, || {
+                        merge_and_report(github_session.borrow(), &clone_mgr, &audit_log, &messengers, &req, &options)
+                    })
+                });
+                running.write(|r| r.finish(&req));
+                next = branches.lock().unwrap().finish(&req);
+            }
         });
     }
 }
 
+fn merge_and_report(
+    github_session: &Session,
+    clone_mgr: &GitCloneManager,
+    audit_log: &AuditLog,
+    messengers: &MessengerFactory,
+    req: &PRMergeRequest,
+    options: &BackportOptions,
+) {
+    let merged = match req.target_repo {
+        Some(ref target) => {
+            Merger::new(github_session, clone_mgr, audit_log)
+                .mirror_pull_request(
+                    &req.repo,
+                    target,
+                    &req.pull_request,
+                    &req.target_branch,
+                    &req.path_mappings,
+                    options,
+                )
+                .map_err(|e| Error::from(format!("{}: {}", target.full_name, e)))
+        }
+        None => {
+            merge_pull_request(
+                github_session,
+                clone_mgr,
+                audit_log,
+                &req.repo.owner.login(),
+                &req.repo.name,
+                &req.pull_request,
+                &req.target_branch,
+                options,
+            )
+        }
+    };
+    if let Err(e) = merged {
+        let attach = SlackAttachmentBuilder::new(&format!("{}", e))
+            .title(format!("Source PR: #{}: \"{}\"", req.pull_request.number, req.pull_request.title).as_str())
+            .title_link(req.pull_request.html_url.clone())
+            .color("danger")
+            .build();
+
+        let messenger = messengers.for_event(None, Some(&req.pull_request));
+        messenger.send_to_owner(
+            "Error creating merge Pull Request",
+            &vec![attach],
+            &req.pull_request.user,
+            &req.repo,
+            Some(req.pull_request.base.ref_name.as_str()),
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn merge_req(repo: &str, number: u32, target_branch: &str) -> PRMergeRequest {
+        let mut repo_info = github::Repo::new();
+        repo_info.full_name = repo.into();
+        let mut pr = github::PullRequest::new();
+        pr.number = number;
+        req(&repo_info, &pr, target_branch)
+    }
+
+    #[test]
+    fn test_sort_backport_branches() {
+        let mut branches: Vec<String> = vec![
+            "release/1.10".into(),
+            "esr/stable".into(),
+            "release/1.9".into(),
+            "hotfix/2".into(),
+            "release/1.9.1".into(),
+            "next".into(),
+        ];
+        sort_backport_branches(&mut branches, |b| b.as_str());
+        assert_eq!(
+            vec!["release/1.9", "release/1.9.1", "release/1.10", "hotfix/2", "esr/stable", "next"],
+            branches
+        );
+    }
+
+    #[test]
+    fn test_branch_queues() {
+        let mut queues = BranchQueues::default();
+        let first = queues.start(merge_req("org/repo", 1, "release/1.0")).unwrap();
+        assert!(queues.start(merge_req("org/repo", 2, "release/1.0")).is_none());
+        assert!(queues.start(merge_req("org/repo", 3, "release/1.0")).is_none());
+
+        // other branches and repos don't wait
+        let other_branch = queues.start(merge_req("org/repo", 4, "release/2.0")).unwrap();
+        let other_repo = queues.start(merge_req("org/other", 5, "release/1.0")).unwrap();
+        assert_eq!(2, queues.len());
+
+        let second = queues.finish(&first).unwrap();
+        assert_eq!(2, second.pull_request.number);
+        assert_eq!(3, queues.finish(&second).unwrap().pull_request.number);
+        assert_eq!(0, queues.len());
+        assert!(queues.finish(&other_branch).is_none());
+        assert!(queues.finish(&other_repo).is_none());
+        assert!(queues.waiting.contains_key("org/repo:release/1.0"));
+
+        assert!(queues.finish(&merge_req("org/repo", 3, "release/1.0")).is_none());
+        assert!(queues.waiting.is_empty());
+        assert!(queues.start(merge_req("org/repo", 6, "release/1.0")).is_some());
+    }
+
+    #[test]
+    fn test_branch_queues_mirrors() {
+        let repo = github::Repo::parse("http://git.foo.com/org/repo").unwrap();
+        let mirror = BackportMirror {
+            repo: "internal/repo".into(),
+            path_mappings: vec![("lib/".into(), "vendor/lib/".into())],
+        };
+        let mut pr = github::PullRequest::new();
+        pr.number = 1;
+        let mirrored = mirror_req(&repo, &pr, "release/1.0", &mirror).unwrap();
+        assert_eq!("internal/repo", mirrored.target().full_name);
+        assert_eq!(mirror.path_mappings, mirrored.path_mappings);
+
+        // a mirror's branch doesn't wait for the repo's own
+        let mut queues = BranchQueues::default();
+        assert!(queues.start(req(&repo, &pr, "release/1.0")).is_some());
+        assert!(queues.start(mirrored.clone()).is_some());
+        assert!(queues.start(mirrored.clone()).is_none());
+        assert!(queues.waiting.contains_key("internal/repo:release/1.0"));
+
+        let mut running = RunningMerges::default();
+        running.add(&req(&repo, &pr, "release/1.0"));
+        running.add(&mirrored);
+        running.finish(&req(&repo, &pr, "release/1.0"));
+        let left: Vec<String> = running.take_all().iter().map(|r| r.target().full_name.clone()).collect();
+        assert_eq!(vec!["internal/repo".to_string()], left);
+    }
+
+    #[test]
+    fn test_running_merges() {
+        let mut running = RunningMerges::default();
+        running.add(&merge_req("org/repo", 1, "release/1.0"));
+        running.add(&merge_req("org/repo", 1, "release/2.0"));
+        running.add(&merge_req("org/repo", 2, "release/1.0"));
+        running.finish(&merge_req("org/repo", 1, "release/1.0"));
+        running.finish(&merge_req("org/other", 2, "release/1.0"));
+
+        let left: Vec<(u32, String)> =
+            running.take_all().into_iter().map(|r| (r.pull_request.number, r.target_branch)).collect();
+        assert_eq!(vec![(1, "release/2.0".to_string()), (2, "release/1.0".to_string())], left);
+        assert!(running.take_all().is_empty());
+    }
+
+    #[test]
+    fn test_render_template() {
+        let mut pr = github::PullRequest::new();
+        pr.number = 99;
+        pr.user = github::User::new("the-author");
+        pr.base.ref_name = "master".into();
+        let orig = (String::from("Yay, I made a change (#99)"), String::from("here is more data about it"));
+
+        let desc = render_template(
+            "{title} [{target_branch}]\n\n{body}\n\nBackport of #{number} by @{author} from {base_branch} ({sha})",
+            &orig,
+            "abcdef",
+            &pr,
+            "release/1.0",
+        );
+        assert_eq!(desc.0, "Yay, I made a change [release/1.0]");
+        assert_eq!(desc.1, "here is more data about it\n\nBackport of #99 by @the-author from master (abcdef)");
+
+        let desc = render_template("{title}", &orig, "abcdef", &pr, "release/1.0");
+        assert_eq!(desc, ("Yay, I made a change".to_string(), String::new()));
+    }
+
     #[test]
     fn test_make_merge_desc() {
         let desc = make_merge_desc(
             (String::from("Yay, I made a change (#99)"), String::from("here is more data about it")),
             "abcdef",
-            99,
+            "#99",
             "release/target_branch",
             "source_branch",
         );
@@ -265,12 +1027,25 @@ mod tests {
         assert_eq!(desc.1, "here is more data about it\n\n(cherry-picked from abcdef, PR #99)");
     }
 
+    #[test]
+    fn test_make_merge_desc_other_repo() {
+        let desc = make_merge_desc(
+            (String::from("Yay, I made a change (#99)"), String::from("")),
+            "abcdef",
+            "org/repo#99",
+            "release/target_branch",
+            "source_branch",
+        );
+
+        assert_eq!(desc.1, "(cherry-picked from abcdef, PR org/repo#99)");
+    }
+
     #[test]
     fn test_make_merge_desc_no_body() {
         let desc = make_merge_desc(
             (String::from("Yay, I made a change (#99)"), String::from("")),
             "abcdef",
-            99,
+            "#99",
             "release/target_branch",
             "source_branch",
         );
@@ -284,7 +1059,7 @@ mod tests {
         let desc = make_merge_desc(
             (String::from("Yay, I made a change (#99)"), String::from("")),
             "abcdef",
-            99,
+            "#99",
             "other_branch",
             "source_branch",
         );
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use slack::SlackAttachment;
+use slack_api::PostedMessage;
+
+// reactions to a PR's message for where its reviews stand, and for its merge
+pub const APPROVED: &'static str = "white_check_mark";
+pub const CHANGES_REQUESTED: &'static str = "x";
+pub const MERGED: &'static str = "twisted_rightwards_arrows";
+
+// threads of PRs that haven't heard anything in this long are forgotten: their next message starts a new one
+const MAX_AGE_SECS: i64 = 30 * 24 * 60 * 60;
+
+// The first message about a PR in a channel, which later messages about the PR are threaded under, and which is
+// updated to show the latest of them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PrThread {
+    pub posted: PostedMessage,
+    pub text: String,
+    pub attachments: Vec<SlackAttachment>,
+    pub updated_at: i64,
+}
+
+impl PrThread {
+    // the first message, with what happened last added to it
+    pub fn with_latest(&self, latest: &str) -> (String, Vec<SlackAttachment>) {
+        let mut attachments = self.attachments.clone();
+        let mut status = SlackAttachment::new(latest);
+        status.title = Some("Latest".into());
+        attachments.push(status);
+        (self.text.clone(), attachments)
+    }
+}
+
+// the reactions that a new one takes the place of: a PR is either approved or has changes requested
+pub fn replaced_reactions(reaction: &str) -> Vec<&'static str> {
+    match reaction {
+        APPROVED => vec![CHANGES_REQUESTED],
+        CHANGES_REQUESTED => vec![APPROVED],
+        _ => vec![],
+    }
+}
+
+// by PR url and channel
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PrThreads {
+    threads: HashMap<String, PrThread>,
+}
+
+fn key(pull_request: &str, channel: &str) -> String {
+    format!("{} {}", pull_request, channel)
+}
+
+impl PrThreads {
+    pub fn get(&self, pull_request: &str, channel: &str) -> Option<PrThread> {
+        self.threads.get(&key(pull_request, channel)).cloned()
+    }
+
+    pub fn add(&mut self, pull_request: &str, channel: &str, thread: PrThread, now: i64) {
+        self.threads.retain(|_, t| now - t.updated_at < MAX_AGE_SECS);
+        self.threads.insert(key(pull_request, channel), thread);
+    }
+
+    pub fn touch(&mut self, pull_request: &str, channel: &str, now: i64) {
+        if let Some(thread) = self.threads.get_mut(&key(pull_request, channel)) {
+            thread.updated_at = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thread(text: &str, updated_at: i64) -> PrThread {
+        PrThread {
+            posted: PostedMessage {
+                channel: "C024BE91L".into(),
+                ts: "1401383885.000061".into(),
+            },
+            text: text.into(),
+            attachments: vec![SlackAttachment::new("the PR")],
+            updated_at: updated_at,
+        }
+    }
+
+    #[test]
+    fn test_pr_threads() {
+        let mut threads = PrThreads::default();
+        threads.add("http://the-pr", "reviews", thread("PR opened", 1000), 1000);
+
+        assert_eq!(Some(thread("PR opened", 1000)), threads.get("http://the-pr", "reviews"));
+        assert_eq!(None, threads.get("http://the-pr", "releases"));
+        assert_eq!(None, threads.get("http://other-pr", "reviews"));
+
+        threads.touch("http://the-pr", "reviews", 2000);
+        assert_eq!(2000, threads.get("http://the-pr", "reviews").unwrap().updated_at);
+
+        // the first thread hasn't been touched in too long by now
+        threads.add("http://other-pr", "reviews", thread("PR opened", 2000), 2000 + MAX_AGE_SECS);
+        assert_eq!(None, threads.get("http://the-pr", "reviews"));
+        assert!(threads.get("http://other-pr", "reviews").is_some());
+    }
+
+    #[test]
+    fn test_replaced_reactions() {
+        assert_eq!(vec![CHANGES_REQUESTED], replaced_reactions(APPROVED));
+        assert_eq!(vec![APPROVED], replaced_reactions(CHANGES_REQUESTED));
+        assert!(replaced_reactions(MERGED).is_empty());
+    }
+
+    #[test]
+    fn test_with_latest() {
+        let (text, attachments) = thread("PR opened", 1000).with_latest("PR merged");
+        assert_eq!("PR opened", text);
+        assert_eq!(2, attachments.len());
+        assert_eq!(Some("Latest".to_string()), attachments[1].title);
+        assert_eq!("PR merged", attachments[1].text);
+    }
+}
@@ -0,0 +1,281 @@
+use std::cmp;
+
+use time;
+
+use slack::SlackRequest;
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+// A user's working hours, e.g. "09:00-17:00" at "-08:00". The window may wrap past midnight.
+// Named time zones aren't supported: the offset from UTC is fixed.
+#[derive(Debug, PartialEq)]
+pub struct WorkHours {
+    start: i64,
+    end: i64,
+    utc_offset: i64,
+}
+
+impl WorkHours {
+    pub fn parse(hours: &str, timezone: &str) -> Option<WorkHours> {
+        let mut parts = hours.splitn(2, '-');
+        let start = parts.next().and_then(parse_time_of_day);
+        let end = parts.next().and_then(parse_time_of_day);
+        let utc_offset = parse_utc_offset(timezone);
+
+        match (start, end, utc_offset) {
+            (Some(start), Some(end), Some(utc_offset)) if start != end => {
+                Some(WorkHours {
+                    start: start,
+                    end: end,
+                    utc_offset: utc_offset,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // working hours are on weekdays only. a window that wraps past midnight belongs to the day it starts on.
+    pub fn is_working(&self, now: i64) -> bool {
+        let local = self.local_secs(now);
+        let today = is_weekday(now + self.utc_offset);
+        let yesterday = is_weekday(now + self.utc_offset - DAY_SECS);
+        if self.start < self.end {
+            today && local >= self.start && local < self.end
+        } else {
+            (today && local >= self.start) || (yesterday && local < self.end)
+        }
+    }
+
+    // when the next working window starts, or None if it's working hours now
+    pub fn next_start(&self, now: i64) -> Option<i64> {
+        if self.is_working(now) {
+            return None;
+        }
+
+        let mut start = next_time_of_day(self.start, self.utc_offset, now);
+        while !is_weekday(start + self.utc_offset) {
+            start += DAY_SECS;
+        }
+        Some(start)
+    }
+
+    // how much of the time between from and to was in working hours
+    pub fn working_secs(&self, from: i64, to: i64) -> i64 {
+        let length = modulo(self.end - self.start, DAY_SECS);
+        // start with the day before: its window may reach past midnight
+        let local_from = from + self.utc_offset;
+        let mut day = local_from - modulo(local_from, DAY_SECS) - DAY_SECS;
+
+        let mut total = 0;
+        while day - self.utc_offset < to {
+            if is_weekday(day) {
+                let open = day + self.start - self.utc_offset;
+                let close = open + length;
+                total += cmp::max(0, cmp::min(close, to) - cmp::max(open, from));
+            }
+            day += DAY_SECS;
+        }
+        total
+    }
+
+    fn local_secs(&self, now: i64) -> i64 {
+        modulo(now + self.utc_offset, DAY_SECS)
+    }
+}
+
+// the next time (unix seconds) that it's the given time of day at the given offset from UTC
+pub fn next_time_of_day(time_of_day: i64, utc_offset: i64, now: i64) -> i64 {
+    now + modulo(time_of_day - modulo(now + utc_offset, DAY_SECS), DAY_SECS)
+}
+
+// whether the day of the given (local) time is monday through friday. 1970-01-01 was a thursday.
+pub fn is_weekday(local: i64) -> bool {
+    let day = (local - modulo(local, DAY_SECS)) / DAY_SECS;
+    modulo(day + 3, 7) < 5
+}
+
+fn modulo(value: i64, n: i64) -> i64 {
+    ((value % n) + n) % n
+}
+
+// "HH:MM" -> seconds since midnight
+pub fn parse_time_of_day(value: &str) -> Option<i64> {
+    let mut parts = value.trim().splitn(2, ':');
+    let hours = parts.next().and_then(|h| h.parse::<i64>().ok());
+    let minutes = parts.next().map_or(Some(0), |m| m.parse::<i64>().ok());
+
+    match (hours, minutes) {
+        (Some(h), Some(m)) if h >= 0 && h <= 24 && m >= 0 && m < 60 && h * 60 + m <= 24 * 60 => {
+            Some(modulo((h * 60 + m) * 60, DAY_SECS))
+        }
+        _ => None,
+    }
+}
+
+// "UTC", "+05:30", "-08:00", "UTC-8" -> seconds east of UTC
+pub fn parse_utc_offset(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let value = if value.to_uppercase().starts_with("UTC") { &value[3..] } else { value };
+    if value.is_empty() {
+        return Some(0);
+    }
+
+    let sign = match value.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return None,
+    };
+    match parse_time_of_day(&value[1..]) {
+        Some(secs) if secs <= 14 * 60 * 60 => Some(sign * secs),
+        _ => None,
+    }
+}
+
+pub fn now() -> i64 {
+    time::get_time().sec
+}
+
+// Direct messages waiting for the start of someone's working hours
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DeferredMessages {
+    messages: Vec<SlackRequest>,
+}
+
+impl DeferredMessages {
+    pub fn add(&mut self, req: SlackRequest) {
+        self.messages.push(req);
+    }
+
+    // removes and returns the messages that are due, oldest first
+    pub fn take_due(&mut self, now: i64) -> Vec<SlackRequest> {
+        let (due, waiting) = self.messages.drain(..).partition(|m| m.deliver_at.map_or(true, |t| t <= now));
+        self.messages = waiting;
+        due
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slack;
+
+    // 2017-10-02 00:00:00 UTC, a monday
+    const MIDNIGHT: i64 = 1506902400;
+
+    fn at(hours: i64, minutes: i64) -> i64 {
+        MIDNIGHT + hours * 60 * 60 + minutes * 60
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Some(WorkHours {
+                start: 9 * 60 * 60,
+                end: 17 * 60 * 60 + 30 * 60,
+                utc_offset: -8 * 60 * 60,
+            }),
+            WorkHours::parse("09:00-17:30", "-08:00")
+        );
+        assert_eq!(Some(5 * 60 * 60 + 30 * 60), WorkHours::parse("9-17", "UTC+05:30").map(|w| w.utc_offset));
+        assert_eq!(Some(0), WorkHours::parse("9-17", "").map(|w| w.utc_offset));
+
+        assert_eq!(None, WorkHours::parse("9", ""));
+        assert_eq!(None, WorkHours::parse("9-9", ""));
+        assert_eq!(None, WorkHours::parse("9-25", ""));
+        assert_eq!(None, WorkHours::parse("9-17", "PST"));
+    }
+
+    #[test]
+    fn test_next_start() {
+        let hours = WorkHours::parse("09:00-17:00", "UTC").unwrap();
+        assert_eq!(None, hours.next_start(at(9, 0)));
+        assert_eq!(None, hours.next_start(at(16, 59)));
+        assert_eq!(Some(at(9, 0)), hours.next_start(at(8, 15)));
+        assert_eq!(Some(at(24 + 9, 0)), hours.next_start(at(17, 0)));
+    }
+
+    #[test]
+    fn test_next_start_with_offset() {
+        // 09:00 at -08:00 is 17:00 UTC
+        let hours = WorkHours::parse("09:00-17:00", "-08:00").unwrap();
+        assert_eq!(Some(at(17, 0)), hours.next_start(at(10, 0)));
+        assert_eq!(None, hours.next_start(at(20, 0)));
+        assert_eq!(None, hours.next_start(at(24, 30)));
+        assert_eq!(Some(at(24 + 17, 0)), hours.next_start(at(24 + 1, 0)));
+    }
+
+    #[test]
+    fn test_overnight() {
+        let hours = WorkHours::parse("22:00-06:00", "UTC").unwrap();
+        assert_eq!(None, hours.next_start(at(23, 0)));
+        assert_eq!(None, hours.next_start(at(24 + 5, 0)));
+        assert_eq!(Some(at(22, 0)), hours.next_start(at(12, 0)));
+        // monday morning's hours started on sunday
+        assert_eq!(Some(at(22, 0)), hours.next_start(at(5, 0)));
+        // but saturday morning's started on friday
+        assert_eq!(None, hours.next_start(at(5 * 24 + 5, 0)));
+    }
+
+    #[test]
+    fn test_weekends() {
+        let hours = WorkHours::parse("09:00-17:00", "UTC").unwrap();
+        let friday = 4 * 24;
+        let monday = 7 * 24;
+        assert_eq!(Some(at(monday + 9, 0)), hours.next_start(at(friday + 18, 0)));
+        assert_eq!(Some(at(monday + 9, 0)), hours.next_start(at(friday + 24 + 10, 0)));
+        assert_eq!(Some(at(monday + 9, 0)), hours.next_start(at(friday + 48 + 23, 0)));
+
+        // friday evening at -08:00 is already saturday in UTC
+        let hours = WorkHours::parse("09:00-17:00", "-08:00").unwrap();
+        assert_eq!(Some(at(monday + 17, 0)), hours.next_start(at(friday + 24 + 2, 0)));
+    }
+
+    #[test]
+    fn test_working_secs() {
+        let hour = 60 * 60;
+        let hours = WorkHours::parse("09:00-17:00", "UTC").unwrap();
+        assert_eq!(0, hours.working_secs(at(0, 0), at(9, 0)));
+        assert_eq!(3 * hour, hours.working_secs(at(0, 0), at(12, 0)));
+        assert_eq!(8 * hour, hours.working_secs(at(0, 0), at(24, 0)));
+        assert_eq!(5 * 8 * hour, hours.working_secs(at(0, 0), at(7 * 24, 0)));
+        // friday noon to monday noon
+        assert_eq!(8 * hour, hours.working_secs(at(4 * 24 + 12, 0), at(7 * 24 + 12, 0)));
+
+        // 09:00-17:00 at -08:00 is 17:00-01:00 UTC
+        let hours = WorkHours::parse("09:00-17:00", "-08:00").unwrap();
+        assert_eq!(8 * hour, hours.working_secs(at(12, 0), at(24 + 12, 0)));
+        // monday 00:00 UTC is still sunday there
+        assert_eq!(0, hours.working_secs(at(0, 0), at(12, 0)));
+        assert_eq!(hour, hours.working_secs(at(24, 0), at(24 + 12, 0)));
+
+        let hours = WorkHours::parse("22:00-06:00", "UTC").unwrap();
+        // sunday night's window doesn't count, monday night's does
+        assert_eq!(2 * hour, hours.working_secs(at(0, 0), at(24, 0)));
+    }
+
+    #[test]
+    fn test_is_weekday() {
+        assert!(is_weekday(at(0, 0)));
+        assert!(is_weekday(at(4 * 24 + 23, 59)));
+        assert!(!is_weekday(at(5 * 24, 0)));
+        assert!(!is_weekday(at(6 * 24 + 12, 0)));
+        assert!(is_weekday(at(7 * 24, 0)));
+    }
+
+    #[test]
+    fn test_take_due() {
+        let mut deferred = DeferredMessages::default();
+        deferred.add(slack::deferred_req("@joe", "first", vec![], at(9, 0)));
+        deferred.add(slack::deferred_req("@bob", "second", vec![], at(10, 0)));
+
+        assert_eq!(0, deferred.take_due(at(8, 0)).len());
+        assert_eq!(vec!["first"], deferred.take_due(at(9, 30)).iter().map(|m| m.msg.as_str()).collect::<Vec<_>>());
+        assert_eq!(1, deferred.len());
+        assert_eq!(vec!["second"], deferred.take_due(at(11, 0)).iter().map(|m| m.msg.as_str()).collect::<Vec<_>>());
+        assert_eq!(0, deferred.len());
+    }
+}
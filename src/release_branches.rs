@@ -0,0 +1,113 @@
+use std::fs;
+use std::io::Write;
+
+use audit::{self, AuditLog};
+use config::Config;
+use errors::*;
+use git::Git;
+use git_clone_manager::GitCloneManager;
+use github;
+use github::api::Session;
+use version::Version;
+
+// A release branch that was just cut.
+#[derive(Debug)]
+pub struct ReleaseBranch {
+    pub name: String,
+    // the default branch it was cut from, and the commit there that it starts at
+    pub from_branch: String,
+    pub sha: String,
+    // why it couldn't be protected, if it couldn't
+    pub protect_error: Option<String>,
+}
+
+// Cuts `<release branch prefix><version>` from the head of the repo's default branch, with a first commit writing
+// the repo's release version files if it has any, and protects it. The branch is kept even if it can't be protected.
+pub fn cut(
+    session: &Session,
+    clone_mgr: &GitCloneManager,
+    audit_log: &AuditLog,
+    config: &Config,
+    repo: &github::Repo,
+    version: &str,
+) -> Result<ReleaseBranch> {
+    let parsed = match Version::parse(version) {
+        Some(v) => v,
+        None => return Err(format!("Invalid version: {}", version).into()),
+    };
+    let (owner, repo_name) = (repo.owner.login(), repo.name.as_str());
+
+    let held_clone_dir = clone_mgr.clone(owner, repo_name)?;
+    let clone_dir = held_clone_dir.dir();
+    let git = Git::new(session.github_host(), session.github_token(), clone_dir);
+
+    // the clone's origin/HEAD is the default branch
+    let head = git.run(&["symbolic-ref", "--short", "refs/remotes/origin/HEAD"])?;
+    let from_branch = head.trim_left_matches("origin/").to_string();
+    let name = config.repos().release_branch_prefix(repo, &from_branch) + version;
+
+    if !git.run(&["ls-remote", "--heads", "origin", &name])?.is_empty() {
+        return Err(format!("{} already exists", name).into());
+    }
+    git.checkout_branch(&name, &head)?;
+    let sha = git.run(&["rev-parse", "HEAD"])?;
+
+    let files = config.repos().release_version_files(repo, &from_branch);
+    for &(ref path, ref template) in &files {
+        let file = clone_dir.join(path);
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::File::create(&file)?.write_all(render_version_file(template, version, &parsed).as_bytes())?;
+        git.run(&["add", path])?;
+    }
+    if !files.is_empty() {
+        git.run(&["commit", "-m", &format!("Set version to {}", version)])?;
+    }
+
+    let target = format!("{}/{} {}", owner, repo_name, name);
+    if session.dry_run(owner, repo_name) {
+        audit_log.record("push_branch", &target, audit::DRY_RUN);
+        return Err(format!("Dry run: not pushing {}", name).into());
+    }
+    let pushed = git.run(&["push", "origin", &format!("{}:refs/heads/{}", name, name)]);
+    audit_log.record("push_branch", &target, &audit::outcome(&pushed));
+    pushed?;
+
+    let (checks, approvals) = config.repos().release_branch_protection(repo, &from_branch);
+    let protect_error = session.protect_branch(owner, repo_name, &name, &checks, approvals).err().map(|e| {
+        error!("Error protecting release branch {}: {}", target, e);
+        format!("{}", e)
+    });
+
+    Ok(ReleaseBranch {
+        name: name,
+        from_branch: from_branch,
+        sha: sha,
+        protect_error: protect_error,
+    })
+}
+
+// a release version file's contents, from a template with {version}, {major}, and {minor}
+pub fn render_version_file(template: &str, version: &str, parsed: &Version) -> String {
+    let contents = template
+        .replace("{version}", version)
+        .replace("{major}", &parsed.major().to_string())
+        .replace("{minor}", &parsed.minor().to_string());
+    contents + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_version_file() {
+        let version = Version::parse("2.4").unwrap();
+        assert_eq!("2.4.0\n", render_version_file("{version}.0", "2.4", &version));
+        assert_eq!(
+            "version = \"2.4-rc\" # 2 4\n",
+            render_version_file("version = \"{version}-rc\" # {major} {minor}", "2.4", &version)
+        );
+    }
+}
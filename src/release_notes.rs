@@ -0,0 +1,82 @@
+use github;
+use github::api::Session;
+
+use errors::*;
+
+// A pull request merged between two tags, and its labels (which lists of pull requests leave out)
+#[derive(Debug, Clone)]
+pub struct ReleaseNote {
+    pub pull_request: github::PullRequest,
+    pub labels: Vec<String>,
+}
+
+// The pull requests merged after `from_tag` up to `to_tag`: the ones whose merge commit is between the two.
+pub fn merged_pull_requests(
+    github: &Session,
+    repo: &github::Repo,
+    from_tag: &str,
+    to_tag: &str,
+) -> Result<Vec<ReleaseNote>> {
+    let owner = repo.owner.login();
+    let shas = github.get_commits_between(owner, &repo.name, from_tag, to_tag)?;
+    let prs = github.get_pull_requests(owner, &repo.name, Some("closed"), None)?;
+
+    let mut notes = vec![];
+    for pull_request in prs {
+        let merged = match pull_request.merge_commit_sha {
+            Some(ref sha) => shas.contains(sha),
+            None => false,
+        };
+        if !merged {
+            continue;
+        }
+        let labels = github.get_pull_request_labels(owner, &repo.name, pull_request.number)?;
+        notes.push(ReleaseNote {
+            pull_request: pull_request,
+            labels: labels.into_iter().map(|l| l.name).collect(),
+        });
+    }
+    notes.sort_by_key(|n| n.pull_request.number);
+    Ok(notes)
+}
+
+// Markdown release notes, in a section for each of the `sections`' headings (in order) that has pull requests.
+// `sections` are (label, heading) pairs, and a pull request goes in the first section for one of its labels, or
+// else under "Other changes".
+pub fn render(notes: &Vec<ReleaseNote>, sections: &Vec<(String, String)>) -> String {
+    let mut headings: Vec<&str> = vec![];
+    for &(_, ref heading) in sections {
+        if !headings.contains(&heading.as_str()) {
+            headings.push(heading);
+        }
+    }
+    headings.push("Other changes");
+
+    let heading_of = |note: &ReleaseNote| {
+        sections
+            .iter()
+            .find(|&&(ref label, _)| note.labels.iter().any(|l| l.to_lowercase() == label.to_lowercase()))
+            .map_or("Other changes", |&(_, ref heading)| heading.as_str())
+    };
+
+    let mut out = vec![];
+    for heading in headings {
+        let lines: Vec<String> = notes.iter().filter(|n| heading_of(n) == heading).map(note_line).collect();
+        if !lines.is_empty() {
+            out.push(format!("### {}\n\n{}", heading, lines.join("\n")));
+        }
+    }
+    if out.is_empty() {
+        return "No pull requests were merged.".into();
+    }
+    out.join("\n\n")
+}
+
+fn note_line(note: &ReleaseNote) -> String {
+    let pr = &note.pull_request;
+    let mut line = format!("- {} (#{} by @{})", pr.title, pr.number, pr.user.login());
+    if !note.labels.is_empty() {
+        line += &format!(" [{}]", note.labels.join(", "));
+    }
+    line
+}
@@ -0,0 +1,84 @@
+use errors::*;
+use github;
+use github::api::Session;
+
+// The events octobot handles, which are what new webhooks subscribe to
+pub const EVENTS: &[&str] = &[
+    "pull_request",
+    "pull_request_review",
+    "pull_request_review_comment",
+    "commit_comment",
+    "issue_comment",
+    "issues",
+    "push",
+    "status",
+    "create",
+    "release",
+    "deployment",
+    "deployment_status",
+    "workflow_run",
+    "repository",
+    "dependabot_alert",
+    "code_scanning_alert",
+    "repository_vulnerability_alert",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum HookSetup {
+    Created,
+    // it was turned off, or missed some events
+    Updated,
+    Unchanged,
+}
+
+impl HookSetup {
+    pub fn describe(&self, owner: &str, repo: &str) -> String {
+        match *self {
+            HookSetup::Created => format!("Created the webhook of {}/{}", owner, repo),
+            HookSetup::Updated => format!("Updated the webhook of {}/{}", owner, repo),
+            HookSetup::Unchanged => format!("The webhook of {}/{} was already set up", owner, repo),
+        }
+    }
+}
+
+// Makes sure a repo has a webhook to `url` with the events octobot handles, creating it if there isn't one. A hook
+// that's already there is turned on and gets the missing events and the secret if needed: github doesn't show
+// secrets, so a hook that's otherwise right keeps whichever one it has.
+pub fn ensure_hook(session: &Session, owner: &str, repo: &str, url: &str, secret: &str) -> Result<HookSetup> {
+    let existing = session.get_hooks(owner, repo)?.into_iter().find(|h| h.config.url == url);
+
+    let mut hook = match existing {
+        Some(hook) => {
+            let has_events = EVENTS.iter().all(|e| hook.events.iter().any(|h| h == e || h == "*"));
+            let is_json = hook.config.content_type.as_ref().map_or(false, |t| t == "json");
+            if hook.active && has_events && is_json {
+                return Ok(HookSetup::Unchanged);
+            }
+            hook
+        }
+        None => github::Hook {
+            id: None,
+            name: "web".into(),
+            active: true,
+            events: vec![],
+            config: github::HookConfig {
+                url: url.into(),
+                content_type: None,
+                secret: None,
+            },
+        },
+    };
+
+    let created = hook.id.is_none();
+    hook.active = true;
+    for event in EVENTS {
+        if !hook.events.iter().any(|e| e == event || e == "*") {
+            hook.events.push(event.to_string());
+        }
+    }
+    hook.config.content_type = Some("json".into());
+    hook.config.secret = Some(secret.into());
+    session.save_hook(owner, repo, &hook)?;
+
+    Ok(if created { HookSetup::Created } else { HookSetup::Updated })
+}
@@ -13,9 +13,9 @@ use git::Git;
 use git_clone_manager::GitCloneManager;
 use github;
 use jira;
-use messenger;
-use slack::{SlackAttachmentBuilder, SlackRequest};
-use worker::{self, WorkSender};
+use messenger::MessengerFactory;
+use slack::SlackAttachmentBuilder;
+use worker;
 
 #[cfg(target_os = "linux")]
 use docker;
@@ -151,7 +151,7 @@ struct Runner {
     github_session: Arc<github::api::Session>,
     jira_session: Option<Arc<jira::api::Session>>,
     clone_mgr: Arc<GitCloneManager>,
-    slack: WorkSender<SlackRequest>,
+    messengers: MessengerFactory,
     thread_pool: ThreadPool,
 }
 
@@ -175,7 +175,7 @@ pub fn new_worker(
     github_session: Arc<github::api::Session>,
     jira_session: Option<Arc<jira::api::Session>>,
     clone_mgr: Arc<GitCloneManager>,
-    slack: WorkSender<SlackRequest>,
+    messengers: MessengerFactory,
 ) -> worker::Worker<RepoVersionRequest> {
     worker::Worker::new(
         "repo-version",
@@ -184,7 +184,7 @@ pub fn new_worker(
             github_session: github_session,
             jira_session: jira_session,
             clone_mgr: clone_mgr,
-            slack: slack,
+            messengers: messengers,
             thread_pool: threadpool::Builder::new()
                 .num_threads(max_concurrency)
                 .thread_name("repo-version".to_string())
@@ -199,7 +199,7 @@ impl worker::Runner<RepoVersionRequest> for Runner {
         let jira_session = self.jira_session.clone();
         let clone_mgr = self.clone_mgr.clone();
         let config = self.config.clone();
-        let slack = self.slack.clone();
+        let messengers = self.messengers.clone();
 
         // launch another thread to do the version calculation
         self.thread_pool.execute(move || {
@@ -233,14 +233,19 @@ impl worker::Runner<RepoVersionRequest> for Runner {
                         )
                         {
                             error!("Error running version script {}: {}", version_script, e);
-                            let messenger = messenger::new(config.clone(), slack);
+                            let messenger = messengers.new_messenger();
 
                             let attach = SlackAttachmentBuilder::new(&format!("{}", e))
                                 .title(version_script.clone())
                                 .color("danger")
                                 .build();
 
-                            messenger.send_to_channel("Error running version script", &vec![attach], &req.repo);
+                            messenger.send_to_channel(
+                                "Error running version script",
+                                &vec![attach],
+                                &req.repo,
+                                Some(req.branch.as_str()),
+                            );
 
                             // resolve the issue with no version
                             jira::workflow::resolve_issue(
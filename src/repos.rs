@@ -1,20 +1,35 @@
+use regex::Regex;
 use serde_json;
 use std;
 use std::collections::HashMap;
 use std::io::Read;
 use url::Url;
 
+use changelog::ChangelogRule;
+use conventions::Conventions;
+use event_rules::{EventRule, EventRules};
 use github;
+use messages::Messages;
+use path_labels::PathLabels;
+use quiet_hours;
+use size_labels;
+use version_bumps::BumpRule;
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct RepoInfo {
-    // github org or full repo name. i.e. "some-org" or "some-org/octobot"
+    // github org, full repo name, or a pattern of full repo names where '*' matches anything. i.e. "some-org",
+    // "some-org/octobot", or "some-org/service-*". A repo's own entries win over pattern entries, which win over
+    // its org's entries
     pub repo: String,
+    // patterns of full repo names that this org or pattern entry doesn't apply to, e.g. "some-org/sandbox-*"
+    pub exclude_repos: Option<Vec<String>>,
     // slack channel to send all messages to
     pub channel: String,
     pub force_push_notify: Option<bool>,
     // white-listed statuses to reapply on force-push w/ identical diff
     pub force_push_reapply_statuses: Option<Vec<String>>,
+    // dismiss approvals, and ask the approvers to review again, when a force-push changes a PR's diff
+    pub force_push_dismiss_approvals: Option<bool>,
     // list of branches this jira/version config is for
     pub branches: Option<Vec<String>>,
     // A list of jira projects to be respected in processing.
@@ -23,6 +38,189 @@ pub struct RepoInfo {
     pub version_script: Option<String>,
     // Used for backporting. Defaults to "release/"
     pub release_branch_prefix: Option<String>,
+    // PRs with this label get merged once approved and green. Disabled if not set.
+    pub auto_merge_label: Option<String>,
+    // number of approvals needed before auto-merging. Defaults to 1
+    pub auto_merge_approvals: Option<u32>,
+    // post an "octobot/reviews" status on PRs that only passes with this many approvals from reviewers other than
+    // the author, and fails when changes are requested. Disabled if not set.
+    pub review_status_approvals: Option<u32>,
+    // regexes that PR titles, and the first line of each of their commit messages, must match. A failing
+    // "octobot/conventions" status and a comment point out the ones that don't. Not checked if not set.
+    pub title_pattern: Option<String>,
+    pub commit_message_pattern: Option<String>,
+    // patterns for code paths, e.g. "src/" or "*.rs": PRs changing them must also change the changelog file (defaults
+    // to CHANGELOG.md) or be labeled "no-changelog", or an "octobot/changelog" status fails. Not checked if not set.
+    pub changelog_paths: Option<Vec<String>>,
+    pub changelog_file: Option<String>,
+    // label PRs size/XS to size/XL by how many lines they change
+    pub size_labels: Option<bool>,
+    // changed lines up to which a PR is XS, S, M, and L. Defaults to 10, 30, 100, 500
+    pub size_label_thresholds: Option<Vec<u32>>,
+    // "<pattern> <label>" rules: PRs changing files that match a pattern get its label, e.g. "docs/** documentation"
+    pub path_labels: Option<Vec<String>>,
+    // "<org/team> <channel>" rules: @org/team mentions go to the channel instead of to each member of the team
+    pub team_channels: Option<Vec<String>>,
+    // "merge", "squash", or "rebase". Defaults to "merge"
+    pub merge_strategy: Option<String>,
+    // "squash" to backport a PR as one commit, or "rebase" to backport each of its commits. Defaults to "squash"
+    pub backport_strategy: Option<String>,
+    // commit message for backports, with {title}, {body}, {number}, {author}, {base_branch}, {target_branch},
+    // and {sha} filled in. Defaults to "<base>-><target>: <title>", then the body and where it was cherry-picked from
+    pub backport_message_template: Option<String>,
+    // "<target branch> <milestone>" rules for the milestone of backports to a branch, e.g. "release/1.0 1.0.5".
+    // Backports to other branches get the original PR's milestone
+    pub backport_milestones: Option<Vec<String>>,
+    // "<label pattern> <target branch>" rules for the branches backport labels ask for, e.g. "hotfix-(.*) hotfix/$1"
+    // or "backport-esr esr/stable". Other "backport-<version>" labels go to <release branch prefix><version>
+    pub backport_branches: Option<Vec<String>>,
+    // backport to the oldest of several branches first, and on to the next one when that backport is merged
+    pub chain_backports: Option<bool>,
+    // "<org/repo> [<path>=<path> ...]" rules for mirrors of the repo on the same host, e.g. an internal fork or a
+    // repo that vendors it, that get a backport PR of their own into the same branch. With paths, only changes under
+    // the first path are brought over, to the second one, e.g. "some-org/vendor-repo lib/=third_party/lib/"
+    pub backport_mirrors: Option<Vec<String>>,
+    // "<label> <heading>" rules for the sections of release notes, e.g. "bug Bug fixes"
+    pub release_note_sections: Option<Vec<String>>,
+    // where new tags and releases are announced. The repo's channel if not set
+    pub release_channel: Option<String>,
+    // the status checks that merges into a release branch cut with "/octobot cut-release" have to pass
+    pub release_branch_checks: Option<Vec<String>>,
+    // the approvals that merges into a release branch cut with "/octobot cut-release" need. None if not set
+    pub release_branch_approvals: Option<u32>,
+    // "<path> <template>" rules for files that a new release branch's first commit writes, with {version}, {major},
+    // and {minor} filled in, e.g. "VERSION {version}.0"
+    pub release_version_files: Option<Vec<String>>,
+    // "<path> <major|minor|patch> <pattern>" rules for the version files that a pull request on the default branch
+    // bumps after a release branch is cut or a version is tagged, e.g. "Cargo.toml minor ^version = \"(.*)\"". The
+    // pattern's first capture is the version
+    pub version_bump_files: Option<Vec<String>>,
+    // github login that version bump pull requests are assigned to
+    pub release_manager: Option<String>,
+    // "<environment> <channel>" rules for where deployments to an environment are announced, e.g.
+    // "production #deploys". Deployments to other environments go to the repo's channel
+    pub deployment_channels: Option<Vec<String>>,
+    // the github actions workflows whose runs on protected branches are announced when they fail, and again when
+    // they pass after that
+    pub watched_workflows: Option<Vec<String>>,
+    // after this many failed runs in a row of a watched workflow on the default branch, the repo's channel gets an
+    // @here, and a PagerDuty incident is opened if PagerDuty is configured. Disabled if not set
+    pub broken_branch_escalation: Option<u32>,
+    // "<label> <channel>" rules for issues that go to a channel of their own instead of the repo's, e.g.
+    // "security #security". The first rule whose label an issue has wins
+    pub issue_channels: Option<Vec<String>>,
+    // where dependabot, code scanning, and vulnerability alerts go. they aren't announced anywhere if not set
+    pub security_channel: Option<String>,
+    // branches (e.g. "master", "release/*") that should only change through pull requests: pushes to them that
+    // no pull request merged are announced in the security channel, or else the admin channel
+    pub direct_push_branches: Option<Vec<String>>,
+    // branches (e.g. "gh-pages", "renovate/*") whose pushes never notify anyone
+    pub push_ignore_branches: Option<Vec<String>>,
+    // github logins (e.g. "*[bot]", "some-deploy-user") whose pushes never notify anyone
+    pub push_ignore_authors: Option<Vec<String>>,
+    // where repositories being created, deleted, archived, renamed, transferred, or made public are announced.
+    // usually set on an org's entry. they go to the main admin_channel if not set
+    pub admin_channel: Option<String>,
+    // github logins to request reviews from in turn when a PR is opened
+    pub reviewer_pool: Option<Vec<String>>,
+    // how many reviewers to pick from the pool (1 or 2). Defaults to 1
+    pub reviewer_count: Option<u32>,
+    // message the CODEOWNERS of changed files when a PR is opened
+    pub codeowners_notify: Option<bool>,
+    // request review from the CODEOWNERS of changed files when a PR is opened
+    pub codeowners_request_review: Option<bool>,
+    // message PR owners when a push to the branch their PR targets leaves it with merge conflicts
+    pub notify_conflicts: Option<bool>,
+    // merge the base branch into PRs labeled "keep-updated" whenever it moves
+    pub keep_updated: Option<bool>,
+    // keep PRs whose description says "Depends on #N" or "Blocked by org/repo#N" pending until those merge
+    pub track_dependencies: Option<bool>,
+    // post one message per PR in the channel and thread the PR's later messages under it, updating it to show the
+    // latest. needs slack_web_api
+    pub pr_threads: Option<bool>,
+    // tell the channel when comments and reviews are edited (with their new text) or deleted
+    pub comment_edits: Option<bool>,
+    // post the week's PR numbers (opened, merged, time to first review and to merge, reviews by reviewer) to the
+    // channel on monday mornings
+    pub weekly_metrics: Option<bool>,
+    // conditions on events (their type, action, labels, branch, author, and title) and what to do when they hold:
+    // tell a channel or users, add labels, set a status, or ignore the event. see event_rules::EventRule
+    pub rules: Option<Vec<EventRule>>,
+    // the repo's wording of octobot's messages, by name, e.g.
+    // {"pull_request_opened": ":tada: {user} opened #{number}"}. see messages for the names and their placeholders
+    pub message_templates: Option<HashMap<String, String>>,
+    // the language of the repo's messages, e.g. "fr". the main locale if not set
+    pub locale: Option<String>,
+    // which events go to the channel and which go out as direct messages
+    pub notifications: Option<Notifications>,
+    // kinds of events (see Notifications) that go to the channel in a daily digest instead of right away
+    pub digest_events: Option<Vec<String>>,
+    // remind about open PRs that haven't been updated in this many days. Disabled if not set.
+    pub stale_pr_days: Option<u32>,
+    // time of day (UTC) to send stale PR reminders, e.g. "09:00". Defaults to 09:00
+    pub stale_pr_reminder_time: Option<String>,
+    // remind requested reviewers after this many of their working hours without a review. Disabled if not set.
+    pub review_reminder_hours: Option<u32>,
+    // then tell the channel if there's still no review after this many more working hours
+    pub review_escalation_hours: Option<u32>,
+    // github login of a lead, or a slack channel like "#leads", to tell about PRs that nobody has reviewed at all
+    // after review_lead_hours
+    pub review_lead: Option<String>,
+    pub review_lead_hours: Option<u32>,
+    // @here the channel about PRs that still haven't been reviewed after this many hours
+    pub review_here_hours: Option<u32>,
+    // when looking up slack users by email, users without a public github email are looked up as
+    // <login>@<this domain>
+    pub slack_email_domain: Option<String>,
+    // also POST each notification as JSON to these URLs, for other systems to consume
+    pub webhook_urls: Option<Vec<String>>,
+    // only record what would be done for this repo (see the audit log) without doing it, e.g. while onboarding it
+    pub dry_run: Option<bool>,
+}
+
+// Event kinds are "pull_request", "merge", "review", "comment", "push", "force_push", "release", "deployment",
+// "workflow", "issue", "security", and "repository".
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Notifications {
+    pub channel: Option<EventFilter>,
+    pub direct: Option<EventFilter>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct EventFilter {
+    // only send these kinds of events. all of them if not set
+    pub allow: Option<Vec<String>>,
+    // never send these kinds of events
+    pub deny: Option<Vec<String>>,
+}
+
+impl EventFilter {
+    pub fn new(allow: Option<Vec<String>>, deny: Option<Vec<String>>) -> EventFilter {
+        EventFilter {
+            allow: allow,
+            deny: deny,
+        }
+    }
+
+    pub fn allows(&self, kind: &str) -> bool {
+        let allowed = match self.allow {
+            Some(ref allow) if allow.len() > 0 => allow.iter().any(|k| k == kind),
+            _ => true,
+        };
+        let denied = match self.deny {
+            Some(ref deny) => deny.iter().any(|k| k == kind),
+            None => false,
+        };
+        allowed && !denied
+    }
+}
+
+// Another repo that backports are also made in, see `RepoInfo::backport_mirrors`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BackportMirror {
+    pub repo: String,
+    // (from, to) path prefixes: only changes under `from` are brought over, to `to`. Everything as is if empty
+    pub path_mappings: Vec<(String, String)>,
 }
 
 // maps github host to a list of repos
@@ -47,14 +245,75 @@ impl RepoInfo {
     pub fn new(repo: &str, channel: &str) -> RepoInfo {
         RepoInfo {
             repo: repo.into(),
+            exclude_repos: None,
             branches: None,
             channel: channel.into(),
             force_push_notify: None,
             force_push_reapply_statuses: None,
+            force_push_dismiss_approvals: None,
             jira_projects: None,
             jira_versions_enabled: None,
             version_script: None,
             release_branch_prefix: None,
+            auto_merge_label: None,
+            auto_merge_approvals: None,
+            review_status_approvals: None,
+            title_pattern: None,
+            commit_message_pattern: None,
+            changelog_paths: None,
+            changelog_file: None,
+            size_labels: None,
+            size_label_thresholds: None,
+            path_labels: None,
+            team_channels: None,
+            merge_strategy: None,
+            backport_strategy: None,
+            backport_message_template: None,
+            backport_milestones: None,
+            backport_branches: None,
+            chain_backports: None,
+            backport_mirrors: None,
+            release_note_sections: None,
+            release_channel: None,
+            release_branch_checks: None,
+            release_branch_approvals: None,
+            release_version_files: None,
+            version_bump_files: None,
+            release_manager: None,
+            deployment_channels: None,
+            watched_workflows: None,
+            broken_branch_escalation: None,
+            issue_channels: None,
+            security_channel: None,
+            direct_push_branches: None,
+            push_ignore_branches: None,
+            push_ignore_authors: None,
+            admin_channel: None,
+            reviewer_pool: None,
+            reviewer_count: None,
+            codeowners_notify: None,
+            codeowners_request_review: None,
+            notify_conflicts: None,
+            keep_updated: None,
+            track_dependencies: None,
+            pr_threads: None,
+            comment_edits: None,
+            weekly_metrics: None,
+            rules: None,
+            message_templates: None,
+            locale: None,
+            notifications: None,
+            digest_events: None,
+            stale_pr_days: None,
+            stale_pr_reminder_time: None,
+            review_reminder_hours: None,
+            review_escalation_hours: None,
+            review_lead: None,
+            review_lead_hours: None,
+            review_here_hours: None,
+            slack_email_domain: None,
+            webhook_urls: None,
+            dry_run: None,
         }
     }
 
@@ -70,6 +329,12 @@ impl RepoInfo {
         info
     }
 
+    pub fn with_force_push_dismiss_approvals(self, value: Option<bool>) -> RepoInfo {
+        let mut info = self;
+        info.force_push_dismiss_approvals = value;
+        info
+    }
+
     pub fn with_jira(self, value: Vec<String>) -> RepoInfo {
         let mut info = self;
         info.jira_projects = Some(value);
@@ -87,6 +352,304 @@ impl RepoInfo {
         info.release_branch_prefix = value;
         info
     }
+
+    pub fn with_auto_merge(self, label: Option<String>, approvals: Option<u32>) -> RepoInfo {
+        let mut info = self;
+        info.auto_merge_label = label;
+        info.auto_merge_approvals = approvals;
+        info
+    }
+
+    pub fn with_merge_strategy(self, value: Option<String>) -> RepoInfo {
+        let mut info = self;
+        info.merge_strategy = value;
+        info
+    }
+
+    pub fn with_reviewer_pool(self, pool: Vec<String>, count: Option<u32>) -> RepoInfo {
+        let mut info = self;
+        info.reviewer_pool = Some(pool);
+        info.reviewer_count = count;
+        info
+    }
+
+    pub fn with_codeowners(self, notify: Option<bool>, request_review: Option<bool>) -> RepoInfo {
+        let mut info = self;
+        info.codeowners_notify = notify;
+        info.codeowners_request_review = request_review;
+        info
+    }
+
+    pub fn with_notify_conflicts(self, value: bool) -> RepoInfo {
+        let mut info = self;
+        info.notify_conflicts = Some(value);
+        info
+    }
+
+    pub fn with_keep_updated(self, value: bool) -> RepoInfo {
+        let mut info = self;
+        info.keep_updated = Some(value);
+        info
+    }
+
+    pub fn with_track_dependencies(self, value: bool) -> RepoInfo {
+        let mut info = self;
+        info.track_dependencies = Some(value);
+        info
+    }
+
+    pub fn with_backport_strategy(self, strategy: Option<String>, message_template: Option<String>) -> RepoInfo {
+        let mut info = self;
+        info.backport_strategy = strategy;
+        info.backport_message_template = message_template;
+        info
+    }
+
+    pub fn with_backport_milestones(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.backport_milestones = Some(value);
+        info
+    }
+
+    pub fn with_backport_branches(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.backport_branches = Some(value);
+        info
+    }
+
+    pub fn with_chain_backports(self, value: bool) -> RepoInfo {
+        let mut info = self;
+        info.chain_backports = Some(value);
+        info
+    }
+
+    pub fn with_backport_mirrors(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.backport_mirrors = Some(value);
+        info
+    }
+
+    pub fn with_release_note_sections(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.release_note_sections = Some(value);
+        info
+    }
+
+    pub fn with_release_channel(self, value: &str) -> RepoInfo {
+        let mut info = self;
+        info.release_channel = Some(value.into());
+        info
+    }
+
+    pub fn with_release_branch_protection(self, checks: Vec<String>, approvals: u32) -> RepoInfo {
+        let mut info = self;
+        info.release_branch_checks = Some(checks);
+        info.release_branch_approvals = Some(approvals);
+        info
+    }
+
+    pub fn with_release_version_files(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.release_version_files = Some(value);
+        info
+    }
+
+    pub fn with_version_bump_files(self, value: Vec<String>, release_manager: Option<String>) -> RepoInfo {
+        let mut info = self;
+        info.version_bump_files = Some(value);
+        info.release_manager = release_manager;
+        info
+    }
+
+    pub fn with_deployment_channels(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.deployment_channels = Some(value);
+        info
+    }
+
+    pub fn with_watched_workflows(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.watched_workflows = Some(value);
+        info
+    }
+
+    pub fn with_broken_branch_escalation(self, value: u32) -> RepoInfo {
+        let mut info = self;
+        info.broken_branch_escalation = Some(value);
+        info
+    }
+
+    pub fn with_issue_channels(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.issue_channels = Some(value);
+        info
+    }
+
+    pub fn with_security_channel(self, value: &str) -> RepoInfo {
+        let mut info = self;
+        info.security_channel = Some(value.into());
+        info
+    }
+
+    pub fn with_direct_push_branches(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.direct_push_branches = Some(value);
+        info
+    }
+
+    pub fn with_push_ignore_branches(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.push_ignore_branches = Some(value);
+        info
+    }
+
+    pub fn with_push_ignore_authors(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.push_ignore_authors = Some(value);
+        info
+    }
+
+    pub fn with_admin_channel(self, value: &str) -> RepoInfo {
+        let mut info = self;
+        info.admin_channel = Some(value.into());
+        info
+    }
+
+    pub fn with_exclude_repos(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.exclude_repos = Some(value);
+        info
+    }
+
+    pub fn with_pr_threads(self, value: bool) -> RepoInfo {
+        let mut info = self;
+        info.pr_threads = Some(value);
+        info
+    }
+
+    pub fn with_comment_edits(self, value: bool) -> RepoInfo {
+        let mut info = self;
+        info.comment_edits = Some(value);
+        info
+    }
+
+    pub fn with_weekly_metrics(self, value: bool) -> RepoInfo {
+        let mut info = self;
+        info.weekly_metrics = Some(value);
+        info
+    }
+
+    pub fn with_rules(self, value: Vec<EventRule>) -> RepoInfo {
+        let mut info = self;
+        info.rules = Some(value);
+        info
+    }
+
+    pub fn with_message_templates(self, value: HashMap<String, String>) -> RepoInfo {
+        let mut info = self;
+        info.message_templates = Some(value);
+        info
+    }
+
+    pub fn with_locale(self, value: &str) -> RepoInfo {
+        let mut info = self;
+        info.locale = Some(value.into());
+        info
+    }
+
+    pub fn with_digest_events(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.digest_events = Some(value);
+        info
+    }
+
+    pub fn with_stale_prs(self, days: Option<u32>, reminder_time: Option<String>) -> RepoInfo {
+        let mut info = self;
+        info.stale_pr_days = days;
+        info.stale_pr_reminder_time = reminder_time;
+        info
+    }
+
+    pub fn with_review_status_approvals(self, value: Option<u32>) -> RepoInfo {
+        let mut info = self;
+        info.review_status_approvals = value;
+        info
+    }
+
+    pub fn with_conventions(self, title_pattern: Option<String>, commit_message_pattern: Option<String>) -> RepoInfo {
+        let mut info = self;
+        info.title_pattern = title_pattern;
+        info.commit_message_pattern = commit_message_pattern;
+        info
+    }
+
+    pub fn with_changelog_check(self, paths: Vec<String>, file: Option<String>) -> RepoInfo {
+        let mut info = self;
+        info.changelog_paths = Some(paths);
+        info.changelog_file = file;
+        info
+    }
+
+    pub fn with_size_labels(self, thresholds: Option<Vec<u32>>) -> RepoInfo {
+        let mut info = self;
+        info.size_labels = Some(true);
+        info.size_label_thresholds = thresholds;
+        info
+    }
+
+    pub fn with_path_labels(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.path_labels = Some(value);
+        info
+    }
+
+    pub fn with_team_channels(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.team_channels = Some(value);
+        info
+    }
+
+    pub fn with_review_reminders(self, remind_hours: Option<u32>, escalation_hours: Option<u32>) -> RepoInfo {
+        let mut info = self;
+        info.review_reminder_hours = remind_hours;
+        info.review_escalation_hours = escalation_hours;
+        info
+    }
+
+    pub fn with_unreviewed_escalation(self, lead: &str, lead_hours: Option<u32>, here_hours: Option<u32>) -> RepoInfo {
+        let mut info = self;
+        info.review_lead = Some(lead.to_string());
+        info.review_lead_hours = lead_hours;
+        info.review_here_hours = here_hours;
+        info
+    }
+
+    pub fn with_slack_email_domain(self, domain: &str) -> RepoInfo {
+        let mut info = self;
+        info.slack_email_domain = Some(domain.to_string());
+        info
+    }
+
+    pub fn with_webhook_urls(self, value: Vec<String>) -> RepoInfo {
+        let mut info = self;
+        info.webhook_urls = Some(value);
+        info
+    }
+
+    pub fn with_dry_run(self, value: bool) -> RepoInfo {
+        let mut info = self;
+        info.dry_run = Some(value);
+        info
+    }
+
+    pub fn with_notifications(self, channel: Option<EventFilter>, direct: Option<EventFilter>) -> RepoInfo {
+        let mut info = self;
+        info.notifications = Some(Notifications {
+            channel: channel,
+            direct: direct,
+        });
+        info
+    }
 }
 
 impl RepoConfig {
@@ -94,6 +657,14 @@ impl RepoConfig {
         RepoConfig { repos: RepoHostMap::new() }
     }
 
+    pub fn from_host_map(repos: RepoHostMap) -> RepoConfig {
+        RepoConfig { repos: repos }
+    }
+
+    pub fn host_map(&self) -> &RepoHostMap {
+        &self.repos
+    }
+
     pub fn insert(&mut self, host: &str, repo: &str, channel: &str) {
         self.insert_info(host, RepoInfo::new(repo, channel));
     }
@@ -102,8 +673,26 @@ impl RepoConfig {
         self.repos.entry(host.to_string()).or_insert(vec![]).push(info);
     }
 
-    pub fn lookup_channel(&self, repo: &github::Repo) -> Option<String> {
-        match self.lookup_info(repo, None) {
+    // points the entries for repo `from` at `to` instead, e.g. after it's been renamed or transferred.
+    // returns how many entries changed
+    pub fn rename_repo(&mut self, host: &str, from: &str, to: &str) -> usize {
+        let mut renamed = 0;
+        if let Some(repos) = self.repos.get_mut(host) {
+            for info in repos.iter_mut().filter(|r| r.repo == from) {
+                info.repo = to.to_string();
+                renamed += 1;
+            }
+        }
+        renamed
+    }
+
+    // whether the repo or its org has an entry
+    pub fn is_configured(&self, repo: &github::Repo) -> bool {
+        self.lookup_info(repo, None).is_some()
+    }
+
+    pub fn lookup_channel(&self, repo: &github::Repo, branch: Option<&str>) -> Option<String> {
+        match self.lookup_info(repo, branch) {
             Some(info) => Some(info.channel.clone()),
             None => None,
         }
@@ -135,6 +724,13 @@ impl RepoConfig {
         }
     }
 
+    pub fn force_push_dismiss_approvals(&self, repo: &github::Repo) -> bool {
+        match self.lookup_info(repo, None) {
+            None => false,
+            Some(ref info) => info.force_push_dismiss_approvals.unwrap_or(false),
+        }
+    }
+
     pub fn jira_projects(&self, repo: &github::Repo, branch: &str) -> Vec<String> {
         match self.lookup_info(repo, Some(branch)) {
             None => vec![],
@@ -147,6 +743,26 @@ impl RepoConfig {
         }
     }
 
+    // the jira projects of the repo on any of its branches, e.g. for linking their keys in its messages
+    pub fn all_jira_projects(&self, repo: &github::Repo) -> Vec<String> {
+        let mut projects: Vec<String> = vec![];
+        if let Ok(url) = Url::parse(&repo.html_url) {
+            if let Some(repos) = url.host_str().and_then(|host| self.repos.get(host)) {
+                let applies = |r: &&RepoInfo| {
+                    r.repo == repo.full_name || matches_by_pattern(r, repo) || matches_by_org(r, repo)
+                };
+                for info in repos.iter().filter(applies) {
+                    for project in info.jira_projects.iter().flat_map(|p| p.iter()) {
+                        if !projects.contains(project) {
+                            projects.push(project.clone());
+                        }
+                    }
+                }
+            }
+        }
+        projects
+    }
+
     // never enable on unconfigured repos/orgs;
     // defaults to true for configured repos/orgs w/ no value set
     pub fn jira_versions_enabled(&self, repo: &github::Repo, branch: &str) -> bool {
@@ -186,171 +802,1709 @@ impl RepoConfig {
         }
     }
 
-    fn lookup_info(&self, repo: &github::Repo, maybe_branch: Option<&str>) -> Option<&RepoInfo> {
-        if let Ok(url) = Url::parse(&repo.html_url) {
-            return url.host_str().and_then(|host| self.repos.get(host)).and_then(|repos| {
-                // try to match by branch
-                if let Some(branch) = maybe_branch {
-                    for r in repos {
-                        if r.repo == repo.full_name &&
-                            r.branches.clone().map_or(false, |b| b.contains(&branch.to_string()))
-                        {
-                            return Some(r);
-                        }
-                    }
-                }
-                // try to match by org/repo
-                for r in repos {
-                    if r.repo == repo.full_name {
-                        return Some(r);
-                    }
-                }
-                // try to match by org
-                for r in repos {
-                    if r.repo == repo.owner.login() {
-                        return Some(r);
-                    }
+    pub fn auto_merge_label(&self, repo: &github::Repo, branch: &str) -> Option<String> {
+        match self.lookup_info(repo, Some(branch)) {
+            None => None,
+            Some(ref info) => {
+                match info.auto_merge_label {
+                    Some(ref value) if value.len() > 0 => Some(value.clone()),
+                    _ => None,
                 }
-
-                None
-            });
+            }
         }
-
-        None
     }
-}
+
+    pub fn auto_merge_approvals(&self, repo: &github::Repo, branch: &str) -> u32 {
+        match self.lookup_info(repo, Some(branch)) {
+            None => 1,
+            Some(ref info) => info.auto_merge_approvals.unwrap_or(1),
+        }
+    }
+
+    pub fn merge_strategy(&self, repo: &github::Repo, branch: &str) -> String {
+        let default = "merge".to_string();
+        match self.lookup_info(repo, Some(branch)) {
+            None => default,
+            Some(ref info) => {
+                match info.merge_strategy {
+                    Some(ref value) if value == "squash" || value == "rebase" => value.clone(),
+                    _ => default,
+                }
+            }
+        }
+    }
+
+    pub fn backport_strategy(&self, repo: &github::Repo, branch: &str) -> String {
+        let default = "squash".to_string();
+        match self.lookup_info(repo, Some(branch)) {
+            None => default,
+            Some(ref info) => {
+                match info.backport_strategy {
+                    Some(ref value) if value == "rebase" => value.clone(),
+                    _ => default,
+                }
+            }
+        }
+    }
+
+    pub fn backport_message_template(&self, repo: &github::Repo, branch: &str) -> Option<String> {
+        match self.lookup_info(repo, Some(branch)) {
+            None => None,
+            Some(ref info) => {
+                match info.backport_message_template {
+                    Some(ref t) if !t.trim().is_empty() => Some(t.clone()),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    // the milestone configured for backports from `branch` to `target_branch`
+    pub fn backport_milestone(&self, repo: &github::Repo, branch: &str, target_branch: &str) -> Option<String> {
+        let rules = match self.lookup_info(repo, Some(branch)) {
+            Some(&RepoInfo { backport_milestones: Some(ref rules), .. }) => rules,
+            _ => return None,
+        };
+        rules.iter().filter_map(|rule| {
+            let mut parts = rule.trim().splitn(2, char::is_whitespace);
+            match (parts.next(), parts.next()) {
+                (Some(b), Some(milestone)) if b == target_branch && !milestone.trim().is_empty() => {
+                    Some(milestone.trim().to_string())
+                }
+                _ => None,
+            }
+        }).next()
+    }
+
+    // the branch that backport `label` on a PR into `branch` asks for, if it is a backport label
+    pub fn backport_branch(&self, repo: &github::Repo, branch: &str, label: &str) -> Option<String> {
+        if let Some(&RepoInfo { backport_branches: Some(ref rules), .. }) = self.lookup_info(repo, Some(branch)) {
+            for rule in rules {
+                let mut parts = rule.trim().splitn(2, char::is_whitespace);
+                let (pattern, target) = match (parts.next(), parts.next()) {
+                    (Some(p), Some(t)) if !t.trim().is_empty() => (p, t.trim()),
+                    _ => continue,
+                };
+                // the whole label has to match, ignoring case like "backport-" does
+                let re = match Regex::new(&format!("(?i)^(?:{})$", pattern)) {
+                    Ok(re) => re,
+                    Err(e) => {
+                        error!("Invalid backport branch rule '{}': {}", rule, e);
+                        continue;
+                    }
+                };
+                if re.is_match(label) {
+                    return Some(re.replace(label, target).into_owned());
+                }
+            }
+        }
+
+        let re = Regex::new(r"(?i)backport-(.+)").unwrap();
+        re.captures(label).map(|c| self.release_branch_prefix(repo, branch) + &c[1])
+    }
+
+    pub fn chain_backports(&self, repo: &github::Repo, branch: &str) -> bool {
+        match self.lookup_info(repo, Some(branch)) {
+            None => false,
+            Some(ref info) => info.chain_backports.unwrap_or(false),
+        }
+    }
+
+    pub fn backport_mirrors(&self, repo: &github::Repo, branch: &str) -> Vec<BackportMirror> {
+        let rules = match self.lookup_info(repo, Some(branch)) {
+            Some(&RepoInfo { backport_mirrors: Some(ref rules), .. }) => rules,
+            _ => return vec![],
+        };
+        rules.iter().filter_map(|rule| {
+            let mut parts = rule.split_whitespace();
+            let mirror = match parts.next() {
+                Some(name) if name.split('/').count() == 2 && name != repo.full_name => name,
+                _ => {
+                    error!("Invalid backport mirror rule '{}'", rule);
+                    return None;
+                }
+            };
+            let mut path_mappings = vec![];
+            for mapping in parts {
+                let mut paths = mapping.splitn(2, '=');
+                match (paths.next(), paths.next()) {
+                    (Some(from), Some(to)) => path_mappings.push((from.to_string(), to.to_string())),
+                    _ => {
+                        error!("Invalid path mapping '{}' for backport mirror {}", mapping, mirror);
+                        return None;
+                    }
+                }
+            }
+            Some(BackportMirror {
+                repo: mirror.to_string(),
+                path_mappings: path_mappings,
+            })
+        }).collect()
+    }
+
+    // (label, heading) pairs for the sections of release notes
+    pub fn release_note_sections(&self, repo: &github::Repo) -> Vec<(String, String)> {
+        let default = vec!["feature Features", "enhancement Features", "bug Bug fixes"];
+        let rules: Vec<String> = match self.lookup_info(repo, None) {
+            Some(&RepoInfo { release_note_sections: Some(ref rules), .. }) => rules.clone(),
+            _ => default.into_iter().map(|r| r.to_string()).collect(),
+        };
+        rules
+            .iter()
+            .filter_map(|rule| {
+                let mut parts = rule.trim().splitn(2, char::is_whitespace);
+                match (parts.next(), parts.next()) {
+                    (Some(label), Some(heading)) if !heading.trim().is_empty() => {
+                        Some((label.to_string(), heading.trim().to_string()))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    pub fn release_channel(&self, repo: &github::Repo) -> Option<String> {
+        match self.lookup_info(repo, None) {
+            Some(&RepoInfo { release_channel: Some(ref channel), .. }) if !channel.trim().is_empty() => {
+                Some(channel.trim().to_string())
+            }
+            _ => None,
+        }
+    }
+
+    // the (status checks, approvals) that merges into a release branch cut from `branch` need
+    pub fn release_branch_protection(&self, repo: &github::Repo, branch: &str) -> (Vec<String>, u32) {
+        match self.lookup_info(repo, Some(branch)) {
+            Some(info) => {
+                let checks = info.release_branch_checks.clone().unwrap_or(vec![]);
+                let checks = checks.into_iter().map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect();
+                (checks, info.release_branch_approvals.unwrap_or(0))
+            }
+            None => (vec![], 0),
+        }
+    }
+
+    // (path, template) pairs for the files a release branch cut from `branch` starts with
+    pub fn release_version_files(&self, repo: &github::Repo, branch: &str) -> Vec<(String, String)> {
+        let rules = match self.lookup_info(repo, Some(branch)) {
+            Some(&RepoInfo { release_version_files: Some(ref rules), .. }) => rules,
+            _ => return vec![],
+        };
+        rules.iter().filter_map(|rule| {
+            let mut parts = rule.trim().splitn(2, char::is_whitespace);
+            match (parts.next(), parts.next()) {
+                (Some(path), Some(template)) if !template.trim().is_empty() => {
+                    Some((path.to_string(), template.trim().to_string()))
+                }
+                _ => None,
+            }
+        }).collect()
+    }
+
+    pub fn version_bump_rules(&self, repo: &github::Repo, branch: &str) -> Vec<BumpRule> {
+        let rules = match self.lookup_info(repo, Some(branch)) {
+            Some(&RepoInfo { version_bump_files: Some(ref rules), .. }) => rules,
+            _ => return vec![],
+        };
+        rules.iter().filter_map(|rule| {
+            let parsed = BumpRule::parse(rule);
+            if parsed.is_none() {
+                error!("Invalid version bump rule '{}'", rule);
+            }
+            parsed
+        }).collect()
+    }
+
+    pub fn release_manager(&self, repo: &github::Repo) -> Option<String> {
+        match self.lookup_info(repo, None) {
+            Some(&RepoInfo { release_manager: Some(ref login), .. }) if !login.trim().is_empty() => {
+                Some(login.trim().trim_left_matches('@').to_string())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn deployment_channel(&self, repo: &github::Repo, environment: &str) -> Option<String> {
+        let rules = match self.lookup_info(repo, None) {
+            Some(&RepoInfo { deployment_channels: Some(ref rules), .. }) => rules,
+            _ => return None,
+        };
+        rules.iter().filter_map(|rule| {
+            let mut parts = rule.trim().splitn(2, char::is_whitespace);
+            match (parts.next(), parts.next()) {
+                (Some(env), Some(channel)) if env == environment && !channel.trim().is_empty() => {
+                    Some(channel.trim().to_string())
+                }
+                _ => None,
+            }
+        }).next()
+    }
+
+    pub fn watches_workflow(&self, repo: &github::Repo, workflow: &str) -> bool {
+        match self.lookup_info(repo, None) {
+            Some(&RepoInfo { watched_workflows: Some(ref workflows), .. }) => workflows.iter().any(|w| w == workflow),
+            _ => false,
+        }
+    }
+
+    pub fn broken_branch_escalation(&self, repo: &github::Repo) -> Option<u32> {
+        match self.lookup_info(repo, None) {
+            None => None,
+            Some(ref info) => info.broken_branch_escalation.and_then(|n| if n > 0 { Some(n) } else { None }),
+        }
+    }
+
+    pub fn issue_channel(&self, repo: &github::Repo, labels: &Vec<github::Label>) -> Option<String> {
+        let rules = match self.lookup_info(repo, None) {
+            Some(&RepoInfo { issue_channels: Some(ref rules), .. }) => rules,
+            _ => return None,
+        };
+        rules.iter().filter_map(|rule| {
+            let mut parts = rule.trim().splitn(2, char::is_whitespace);
+            match (parts.next(), parts.next()) {
+                (Some(label), Some(channel)) if labels.iter().any(|l| l.name == label) => {
+                    Some(channel.trim().to_string())
+                }
+                _ => None,
+            }
+        }).next()
+    }
+
+    pub fn security_channel(&self, repo: &github::Repo) -> Option<String> {
+        match self.lookup_info(repo, None) {
+            Some(&RepoInfo { security_channel: Some(ref c), .. }) if !c.is_empty() => Some(c.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn alerts_direct_push(&self, repo: &github::Repo, branch: &str) -> bool {
+        match self.lookup_info(repo, Some(branch)) {
+            Some(&RepoInfo { direct_push_branches: Some(ref branches), .. }) => {
+                branches.iter().any(|b| branch_matches(b, branch))
+            }
+            _ => false,
+        }
+    }
+
+    // whether a push to the branch by the given github login should notify no one
+    pub fn ignores_push(&self, repo: &github::Repo, branch: &str, author: &str) -> bool {
+        match self.lookup_info(repo, None) {
+            Some(info) => {
+                let matches = |patterns: &Option<Vec<String>>, value: &str| {
+                    patterns.iter().flat_map(|p| p.iter()).any(|p| branch_matches(p, value))
+                };
+                matches(&info.push_ignore_branches, branch) || matches(&info.push_ignore_authors, author)
+            }
+            None => false,
+        }
+    }
+
+    // falls back to the org's admin channel even for repos with entries of their own
+    pub fn admin_channel(&self, repo: &github::Repo) -> Option<String> {
+        let admin_channel = |info: Option<&RepoInfo>| match info {
+            Some(&RepoInfo { admin_channel: Some(ref c), .. }) if !c.is_empty() => Some(c.clone()),
+            _ => None,
+        };
+        admin_channel(self.lookup_info(repo, None)).or_else(|| admin_channel(self.lookup_org_info(repo)))
+    }
+
+    pub fn pr_threads(&self, repo: &github::Repo, branch: Option<&str>) -> bool {
+        match self.lookup_info(repo, branch) {
+            Some(&RepoInfo { pr_threads: Some(value), .. }) => value,
+            _ => false,
+        }
+    }
+
+    pub fn comment_edits(&self, repo: &github::Repo, branch: Option<&str>) -> bool {
+        match self.lookup_info(repo, branch) {
+            Some(&RepoInfo { comment_edits: Some(value), .. }) => value,
+            _ => false,
+        }
+    }
+
+    pub fn weekly_metrics(&self, repo: &github::Repo) -> bool {
+        match self.lookup_info(repo, None) {
+            Some(&RepoInfo { weekly_metrics: Some(value), .. }) => value,
+            _ => false,
+        }
+    }
+
+    pub fn event_rules(&self, repo: &github::Repo, branch: Option<&str>) -> EventRules {
+        match self.lookup_info(repo, branch) {
+            Some(&RepoInfo { rules: Some(ref rules), .. }) => EventRules::parse(rules),
+            _ => EventRules::parse(&vec![]),
+        }
+    }
+
+    pub fn messages(&self, repo: &github::Repo, branch: Option<&str>) -> Messages {
+        match self.lookup_info(repo, branch) {
+            Some(&RepoInfo { message_templates: Some(ref templates), .. }) => Messages::new(templates),
+            _ => Messages::defaults(),
+        }
+    }
+
+    pub fn locale(&self, repo: &github::Repo) -> Option<String> {
+        match self.lookup_info(repo, None) {
+            Some(&RepoInfo { locale: Some(ref locale), .. }) if !locale.is_empty() => Some(locale.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn reviewer_pool(&self, repo: &github::Repo, branch: &str) -> Vec<String> {
+        match self.lookup_info(repo, Some(branch)) {
+            None => vec![],
+            Some(ref info) => {
+                match info.reviewer_pool {
+                    Some(ref value) => value.iter().filter(|r| r.len() > 0).cloned().collect(),
+                    None => vec![],
+                }
+            }
+        }
+    }
+
+    pub fn reviewer_count(&self, repo: &github::Repo, branch: &str) -> usize {
+        match self.lookup_info(repo, Some(branch)) {
+            None => 1,
+            Some(ref info) => info.reviewer_count.map_or(1, |c| std::cmp::max(1, std::cmp::min(2, c as usize))),
+        }
+    }
+
+    pub fn codeowners_notify(&self, repo: &github::Repo, branch: &str) -> bool {
+        match self.lookup_info(repo, Some(branch)) {
+            None => false,
+            Some(ref info) => info.codeowners_notify.unwrap_or(false),
+        }
+    }
+
+    pub fn notify_conflicts(&self, repo: &github::Repo, branch: &str) -> bool {
+        match self.lookup_info(repo, Some(branch)) {
+            None => false,
+            Some(ref info) => info.notify_conflicts.unwrap_or(false),
+        }
+    }
+
+    pub fn keep_updated(&self, repo: &github::Repo, branch: &str) -> bool {
+        match self.lookup_info(repo, Some(branch)) {
+            None => false,
+            Some(ref info) => info.keep_updated.unwrap_or(false),
+        }
+    }
+
+    pub fn track_dependencies(&self, repo: &github::Repo, branch: &str) -> bool {
+        match self.lookup_info(repo, Some(branch)) {
+            None => false,
+            Some(ref info) => info.track_dependencies.unwrap_or(false),
+        }
+    }
+
+    pub fn codeowners_request_review(&self, repo: &github::Repo, branch: &str) -> bool {
+        match self.lookup_info(repo, Some(branch)) {
+            None => false,
+            Some(ref info) => info.codeowners_request_review.unwrap_or(false),
+        }
+    }
+
+    // everything is sent unless filtered out
+    pub fn notify_channel(&self, repo: &github::Repo, kind: &str) -> bool {
+        match self.lookup_info(repo, None) {
+            None => true,
+            Some(ref info) => {
+                match info.notifications {
+                    Some(Notifications { channel: Some(ref filter), .. }) => filter.allows(kind),
+                    _ => true,
+                }
+            }
+        }
+    }
+
+    pub fn notify_direct(&self, repo: &github::Repo, kind: &str) -> bool {
+        match self.lookup_info(repo, None) {
+            None => true,
+            Some(ref info) => {
+                match info.notifications {
+                    Some(Notifications { direct: Some(ref filter), .. }) => filter.allows(kind),
+                    _ => true,
+                }
+            }
+        }
+    }
+
+    pub fn digest_channel(&self, repo: &github::Repo, kind: &str) -> bool {
+        match self.lookup_info(repo, None) {
+            None => false,
+            Some(ref info) => {
+                match info.digest_events {
+                    Some(ref value) => value.iter().any(|k| k == kind),
+                    None => false,
+                }
+            }
+        }
+    }
+
+    pub fn stale_pr_days(&self, repo: &github::Repo) -> Option<u32> {
+        match self.lookup_info(repo, None) {
+            None => None,
+            Some(ref info) => info.stale_pr_days.and_then(|d| if d > 0 { Some(d) } else { None }),
+        }
+    }
+
+    // seconds after midnight UTC
+    pub fn stale_pr_reminder_time(&self, repo: &github::Repo) -> i64 {
+        let default = 9 * 60 * 60;
+        match self.lookup_info(repo, None) {
+            None => default,
+            Some(ref info) => {
+                match info.stale_pr_reminder_time {
+                    Some(ref value) => quiet_hours::parse_time_of_day(value).unwrap_or(default),
+                    None => default,
+                }
+            }
+        }
+    }
+
+    pub fn review_status_approvals(&self, repo: &github::Repo, branch: &str) -> Option<u32> {
+        match self.lookup_info(repo, Some(branch)) {
+            None => None,
+            Some(ref info) => info.review_status_approvals.and_then(|n| if n > 0 { Some(n) } else { None }),
+        }
+    }
+
+    pub fn conventions(&self, repo: &github::Repo, branch: &str) -> Conventions {
+        let not_empty = |p: &Option<String>| p.clone().and_then(|p| if p.is_empty() { None } else { Some(p) });
+        match self.lookup_info(repo, Some(branch)) {
+            None => Conventions { title_pattern: None, commit_pattern: None },
+            Some(ref info) => {
+                Conventions {
+                    title_pattern: not_empty(&info.title_pattern),
+                    commit_pattern: not_empty(&info.commit_message_pattern),
+                }
+            }
+        }
+    }
+
+    // None if PRs don't need changelog entries
+    pub fn changelog_rule(&self, repo: &github::Repo, branch: &str) -> Option<ChangelogRule> {
+        let info = self.lookup_info(repo, Some(branch))?;
+        let file = info.changelog_file.clone().and_then(|f| if f.trim().is_empty() { None } else { Some(f) });
+        let rule = ChangelogRule::parse(info.changelog_paths.as_ref()?, file);
+        if rule.is_empty() { None } else { Some(rule) }
+    }
+
+    // None if the repo doesn't want size labels
+    pub fn size_label_thresholds(&self, repo: &github::Repo, branch: &str) -> Option<Vec<u32>> {
+        match self.lookup_info(repo, Some(branch)) {
+            Some(ref info) if info.size_labels == Some(true) => {
+                Some(info.size_label_thresholds.clone().unwrap_or(size_labels::DEFAULT_THRESHOLDS.to_vec()))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn path_labels(&self, repo: &github::Repo, branch: &str) -> PathLabels {
+        match self.lookup_info(repo, Some(branch)) {
+            Some(&RepoInfo { path_labels: Some(ref rules), .. }) => PathLabels::parse(rules),
+            _ => PathLabels::parse(&vec![]),
+        }
+    }
+
+    // where mentions of team ("org/team") go, if not to its members
+    pub fn team_channel(&self, repo: &github::Repo, team: &str) -> Option<String> {
+        let rules = match self.lookup_info(repo, None) {
+            Some(&RepoInfo { team_channels: Some(ref rules), .. }) => rules,
+            _ => return None,
+        };
+        for rule in rules {
+            let mut parts = rule.split_whitespace();
+            if let (Some(t), Some(channel)) = (parts.next(), parts.next()) {
+                if t.trim_left_matches('@').eq_ignore_ascii_case(team) {
+                    return Some(channel.to_string());
+                }
+            }
+        }
+        None
+    }
+
+    pub fn review_reminder_hours(&self, repo: &github::Repo, branch: &str) -> Option<u32> {
+        match self.lookup_info(repo, Some(branch)) {
+            None => None,
+            Some(ref info) => info.review_reminder_hours.and_then(|h| if h > 0 { Some(h) } else { None }),
+        }
+    }
+
+    pub fn review_escalation_hours(&self, repo: &github::Repo, branch: &str) -> Option<u32> {
+        match self.lookup_info(repo, Some(branch)) {
+            None => None,
+            Some(ref info) => info.review_escalation_hours.and_then(|h| if h > 0 { Some(h) } else { None }),
+        }
+    }
+
+    // who to tell about unreviewed PRs, and after how many hours
+    pub fn review_lead(&self, repo: &github::Repo, branch: &str) -> Option<(String, u32)> {
+        match self.lookup_info(repo, Some(branch)) {
+            Some(&RepoInfo { review_lead: Some(ref lead), review_lead_hours: Some(hours), .. })
+                if !lead.is_empty() && hours > 0 => Some((lead.clone(), hours)),
+            _ => None,
+        }
+    }
+
+    pub fn review_here_hours(&self, repo: &github::Repo, branch: &str) -> Option<u32> {
+        match self.lookup_info(repo, Some(branch)) {
+            None => None,
+            Some(ref info) => info.review_here_hours.and_then(|h| if h > 0 { Some(h) } else { None }),
+        }
+    }
+
+    pub fn slack_email_domain(&self, repo: &github::Repo) -> Option<String> {
+        match self.lookup_info(repo, None) {
+            None => None,
+            Some(ref info) => info.slack_email_domain.clone().and_then(|d| if d.is_empty() { None } else { Some(d) }),
+        }
+    }
+
+    pub fn webhook_urls(&self, repo: &github::Repo, branch: Option<&str>) -> Vec<String> {
+        match self.lookup_info(repo, branch) {
+            None => vec![],
+            Some(ref info) => {
+                match info.webhook_urls {
+                    Some(ref urls) => urls.iter().filter(|u| !u.is_empty()).cloned().collect(),
+                    None => vec![],
+                }
+            }
+        }
+    }
+
+    pub fn dry_run(&self, repo: &github::Repo) -> bool {
+        match self.lookup_info(repo, None) {
+            None => false,
+            Some(ref info) => info.dry_run.unwrap_or(false),
+        }
+    }
+
+    // the repos (not orgs or patterns) configured on the given host, or only the ones whose messages go to `channel`
+    pub fn host_repos(&self, host: &str, channel: Option<&str>) -> Vec<github::Repo> {
+        let repos = match self.repos.get(host) {
+            Some(r) => r,
+            None => return vec![],
+        };
+
+        let mut found: Vec<github::Repo> = vec![];
+        for info in repos {
+            let is_repo = info.repo.contains('/') && !info.repo.contains('*');
+            if !is_repo || found.iter().any(|r| r.full_name == info.repo) {
+                continue;
+            }
+            if channel.map_or(false, |c| c.trim_left_matches('#') != info.channel.trim_left_matches('#')) {
+                continue;
+            }
+            if let Ok(repo) = github::Repo::parse(&format!("https://{}/{}", host, info.repo)) {
+                found.push(repo);
+            }
+        }
+        found
+    }
+
+    // repos on the given host with stale PR reminders turned on. org-wide entries aren't included
+    // since we'd have to list the org's repos to find them.
+    pub fn stale_pr_repos(&self, host: &str) -> Vec<github::Repo> {
+        self.host_repos(host, None).into_iter().filter(|r| self.stale_pr_days(r).is_some()).collect()
+    }
+
+    pub fn weekly_metrics_repos(&self, host: &str) -> Vec<github::Repo> {
+        self.host_repos(host, None).into_iter().filter(|r| self.weekly_metrics(r)).collect()
+    }
+
+    fn lookup_info(&self, repo: &github::Repo, maybe_branch: Option<&str>) -> Option<&RepoInfo> {
+        if let Ok(url) = Url::parse(&repo.html_url) {
+            return url.host_str().and_then(|host| self.repos.get(host)).and_then(|repos| {
+                let by_repo = |r: &&RepoInfo| r.repo == repo.full_name;
+                let by_pattern = |r: &&RepoInfo| matches_by_pattern(r, repo);
+                let by_org = |r: &&RepoInfo| matches_by_org(r, repo);
+
+                // try to match by org/repo, then by repo pattern, then by org. in each case try the branch first.
+                if let Some(branch) = maybe_branch {
+                    if let Some(r) = find_by_branch(repos, &by_repo, branch) {
+                        return Some(r);
+                    }
+                }
+                if let Some(r) = find_default(repos, &by_repo) {
+                    return Some(r);
+                }
+                if let Some(branch) = maybe_branch {
+                    if let Some(r) = find_by_branch(repos, &by_pattern, branch) {
+                        return Some(r);
+                    }
+                }
+                if let Some(r) = find_default(repos, &by_pattern) {
+                    return Some(r);
+                }
+                if let Some(branch) = maybe_branch {
+                    if let Some(r) = find_by_branch(repos, &by_org, branch) {
+                        return Some(r);
+                    }
+                }
+                if let Some(r) = find_default(repos, &by_org) {
+                    return Some(r);
+                }
+
+                None
+            });
+        }
+
+        None
+    }
+
+    fn lookup_org_info(&self, repo: &github::Repo) -> Option<&RepoInfo> {
+        if let Ok(url) = Url::parse(&repo.html_url) {
+            return url.host_str().and_then(|host| self.repos.get(host)).and_then(|repos| {
+                find_default(repos, &|r: &&RepoInfo| matches_by_org(r, repo))
+            });
+        }
+
+        None
+    }
+}
+
+fn matches_by_pattern(info: &RepoInfo, repo: &github::Repo) -> bool {
+    info.repo.contains('*') && branch_matches(&info.repo, &repo.full_name) && !is_excluded(info, repo)
+}
+
+fn matches_by_org(info: &RepoInfo, repo: &github::Repo) -> bool {
+    info.repo == repo.owner.login() && !is_excluded(info, repo)
+}
+
+fn is_excluded(info: &RepoInfo, repo: &github::Repo) -> bool {
+    info.exclude_repos.iter().flat_map(|e| e.iter()).any(|e| branch_matches(e, &repo.full_name))
+}
+
+fn find_by_branch<'a, F>(repos: &'a Vec<RepoInfo>, matches: &F, branch: &str) -> Option<&'a RepoInfo>
+where
+    F: Fn(&&RepoInfo) -> bool,
+{
+    let branches = |r: &&RepoInfo| r.branches.clone().unwrap_or(vec![]);
+    if let Some(r) = repos.iter().filter(matches).find(|r| branches(r).iter().any(|b| b == branch)) {
+        return Some(r);
+    }
+    repos.iter().filter(matches).find(|r| {
+        branches(r).iter().any(|b| branch_matches(b, branch))
+    })
+}
+
+// prefers entries that aren't limited to some branches
+fn find_default<'a, F>(repos: &'a Vec<RepoInfo>, matches: &F) -> Option<&'a RepoInfo>
+where
+    F: Fn(&&RepoInfo) -> bool,
+{
+    match repos.iter().filter(matches).find(|r| r.branches.is_none()) {
+        Some(r) => Some(r),
+        None => repos.iter().filter(matches).next(),
+    }
+}
+
+// Matches a branch name against a pattern where '*' matches anything, e.g. "release/*". Also used for repo names,
+// e.g. "some-org/service-*"
+pub fn branch_matches(pattern: &str, branch: &str) -> bool {
+    if !pattern.contains('*') {
+        return pattern == branch;
+    }
+
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !branch.starts_with(first) || branch.len() < first.len() + last.len() || !branch.ends_with(last) {
+        return false;
+    }
+
+    let mut rest = &branch[first.len()..branch.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use github;
+    use messages;
+
+    #[test]
+    fn lookup_channel_by_repo_full_name() {
+        let mut repos = RepoConfig::new();
+        // insert org-level one first in the list to make sure most specific matches first
+        repos.insert("git.company.com", "some-user", "SOME_OTHER_CHANNEL");
+        repos.insert("git.company.com", "some-user/the-repo", "the-repo-reviews");
+
+        let repo = github::Repo::parse("http://git.company.com/some-user/the-repo").unwrap();
+        assert_eq!("the-repo-reviews", repos.lookup_channel(&repo, None).unwrap());
+    }
+
+    #[test]
+    fn lookup_channel_by_repo_owner() {
+        let mut repos = RepoConfig::new();
+        repos.insert("git.company.com", "some-user", "the-repo-reviews");
+
+        let repo = github::Repo::parse("http://git.company.com/some-user/some-other-repo").unwrap();
+        assert_eq!("the-repo-reviews", repos.lookup_channel(&repo, None).unwrap());
+    }
+
+    #[test]
+    fn lookup_channel_none() {
+        let mut repos = RepoConfig::new();
+        repos.insert("git.company.com", "some-user", "the-repo-reviews");
+
+        // fail by channel/repo
+        {
+            let repo = github::Repo::parse("http://git.company.com/someone-else/some-other-repo").unwrap();
+            assert!(repos.lookup_channel(&repo, None).is_none());
+        }
+
+        // fail by git host
+        {
+            let repo = github::Repo::parse("http://git.other-company.com/some-user/the-repo").unwrap();
+            assert!(repos.lookup_channel(&repo, None).is_none());
+        }
+    }
+
+    #[test]
+    fn test_review_status_approvals() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/gated-repo", "reviews").with_review_status_approvals(Some(2)),
+        );
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/zero-repo", "reviews").with_review_status_approvals(Some(0)),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/other-repo", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert_eq!(Some(2), repos.review_status_approvals(&repo("gated-repo"), "master"));
+        assert_eq!(None, repos.review_status_approvals(&repo("zero-repo"), "master"));
+        assert_eq!(None, repos.review_status_approvals(&repo("other-repo"), "master"));
+        assert_eq!(None, repos.review_status_approvals(&repo("unknown-repo"), "master"));
+    }
+
+    #[test]
+    fn test_conventions() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/strict-repo", "reviews").with_conventions(Some("^[A-Z]+-[0-9]+".into()), None),
+        );
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/blank-repo", "reviews").with_conventions(Some("".into()), Some("".into())),
+        );
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        let strict = repos.conventions(&repo("strict-repo"), "master");
+        assert_eq!(Some("^[A-Z]+-[0-9]+".to_string()), strict.title_pattern);
+        assert_eq!(None, strict.commit_pattern);
+        assert!(repos.conventions(&repo("blank-repo"), "master").is_empty());
+        assert!(repos.conventions(&repo("unknown-repo"), "master").is_empty());
+    }
+
+    #[test]
+    fn test_size_label_thresholds() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/default-repo", "reviews").with_size_labels(None));
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/custom-repo", "reviews").with_size_labels(Some(vec![5, 50, 500, 5000])),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/other-repo", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert_eq!(Some(vec![10, 30, 100, 500]), repos.size_label_thresholds(&repo("default-repo"), "master"));
+        assert_eq!(Some(vec![5, 50, 500, 5000]), repos.size_label_thresholds(&repo("custom-repo"), "master"));
+        assert_eq!(None, repos.size_label_thresholds(&repo("other-repo"), "master"));
+    }
+
+    #[test]
+    fn test_notify_conflicts() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/some-repo", "reviews").with_notify_conflicts(true));
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/other-repo", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert!(repos.notify_conflicts(&repo("some-repo"), "master"));
+        assert!(repos.notify_conflicts(&repo("some-repo"), "develop"));
+        assert!(!repos.notify_conflicts(&repo("other-repo"), "master"));
+    }
+
+    #[test]
+    fn test_keep_updated() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/some-repo", "reviews").with_keep_updated(true));
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert!(repos.keep_updated(&repo("some-repo"), "master"));
+        assert!(!repos.keep_updated(&repo("other-repo"), "master"));
+    }
+
+    #[test]
+    fn test_track_dependencies() {
+        let mut repos = RepoConfig::new();
+        let info = RepoInfo::new("some-user/some-repo", "reviews").with_branches(vec!["master".into()]);
+        repos.insert_info("git.foo.com", info.with_track_dependencies(true));
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/some-repo", "reviews"));
+
+        let repo = github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap();
+        assert!(repos.track_dependencies(&repo, "master"));
+        assert!(!repos.track_dependencies(&repo, "release/1.0"));
+    }
+
+    #[test]
+    fn test_changelog_rule() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/strict-repo", "reviews").with_changelog_check(vec!["src/".into()], None),
+        );
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/changes-repo", "reviews")
+                .with_changelog_check(vec!["lib/".into()], Some("CHANGES.md".into())),
+        );
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/blank-repo", "reviews").with_changelog_check(vec![], None),
+        );
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert_eq!("CHANGELOG.md", repos.changelog_rule(&repo("strict-repo"), "master").unwrap().file);
+        assert_eq!("CHANGES.md", repos.changelog_rule(&repo("changes-repo"), "master").unwrap().file);
+        assert!(repos.changelog_rule(&repo("blank-repo"), "master").is_none());
+        assert!(repos.changelog_rule(&repo("unknown-repo"), "master").is_none());
+    }
+
+    #[test]
+    fn test_path_labels() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/labeled-repo", "reviews").with_path_labels(vec!["docs/ documentation".into()]),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/other-repo", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        let docs = vec!["docs/index.md".to_string()];
+        assert_eq!(vec!["documentation"], repos.path_labels(&repo("labeled-repo"), "master").labels_for(&docs));
+        assert!(repos.path_labels(&repo("other-repo"), "master").is_empty());
+    }
+
+    #[test]
+    fn test_team_channel() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/team-repo", "reviews")
+                .with_team_channels(vec!["some-org/backend #backend".into(), "@some-org/Web-Team #web".into()]),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/other-repo", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert_eq!(Some("#backend".into()), repos.team_channel(&repo("team-repo"), "some-org/backend"));
+        assert_eq!(Some("#web".into()), repos.team_channel(&repo("team-repo"), "some-org/web-team"));
+        assert_eq!(None, repos.team_channel(&repo("team-repo"), "some-org/other"));
+        assert_eq!(None, repos.team_channel(&repo("other-repo"), "some-org/backend"));
+    }
+
+    #[test]
+    fn test_force_push_dismiss_approvals() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/strict-repo", "reviews").with_force_push_dismiss_approvals(Some(true)),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/other-repo", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert!(repos.force_push_dismiss_approvals(&repo("strict-repo")));
+        assert!(!repos.force_push_dismiss_approvals(&repo("other-repo")));
+        assert!(!repos.force_push_dismiss_approvals(&repo("unknown-repo")));
+    }
+
+    #[test]
+    fn test_notify_force_push() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/noisy-repo-by-default", "reviews"),
+        );
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/noisy-repo-on-purpose", "reviews").with_force_push(Some(true)),
+        );
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/quiet-repo", "reviews").with_force_push(Some(false)),
+        );
+        {
+            let repo = github::Repo::parse("http://git.company.com/someone-else/some-other-repo").unwrap();
+            assert_eq!(false, repos.notify_force_push(&repo));
+        }
+
+        {
+            let repo = github::Repo::parse(
+                "http://git.company.\
+                                            com/some-user/noisy-repo-by-default",
+            ).unwrap();
+            assert_eq!(true, repos.notify_force_push(&repo));
+        }
+
+        {
+            let repo = github::Repo::parse(
+                "http://git.company.\
+                                            com/some-user/noisy-repo-on-purpose",
+            ).unwrap();
+            assert_eq!(true, repos.notify_force_push(&repo));
+        }
+
+        {
+            let repo = github::Repo::parse("http://git.company.com/some-user/quiet-repo").unwrap();
+            assert_eq!(false, repos.notify_force_push(&repo));
+        }
+    }
+
+    #[test]
+    fn test_jira_enabled() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info("git.company.com", RepoInfo::new("some-user/no-config", "reviews"));
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/with-config", "reviews").with_jira(vec!["a".into(), "b".into()]),
+        );
+
+        {
+            let repo = github::Repo::parse("http://git.company.com/someone-else/some-other-repo").unwrap();
+            assert_eq!(Vec::<String>::new(), repos.jira_projects(&repo, "any"));
+        }
+
+        {
+            let repo = github::Repo::parse("http://git.company.com/some-user/no-config").unwrap();
+            assert_eq!(Vec::<String>::new(), repos.jira_projects(&repo, "any"));
+        }
+
+        {
+            let repo = github::Repo::parse("http://git.company.com/some-user/with-config").unwrap();
+            assert_eq!(vec!["a", "b"], repos.jira_projects(&repo, "any"));
+        }
+
+    }
+
+    #[test]
+    fn test_all_jira_projects() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews").with_jira(vec!["ORG".into()]));
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/the-repo", "reviews").with_jira(vec!["SOME".into(), "ORG".into()]),
+        );
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/the-repo", "reviews")
+                .with_branches(vec!["release/*".into()])
+                .with_jira(vec!["REL".into()]),
+        );
+
+        let repo = github::Repo::parse("http://git.foo.com/some-user/the-repo").unwrap();
+        assert_eq!(vec!["ORG", "SOME", "REL"], repos.all_jira_projects(&repo));
+        let other = github::Repo::parse("http://git.foo.com/other-user/the-repo").unwrap();
+        assert_eq!(Vec::<String>::new(), repos.all_jira_projects(&other));
+    }
+
+    #[test]
+    fn test_jira_by_branch() {
+        let mut repos = RepoConfig::new();
+        repos.insert("git.company.com", "some-user", "SOME_OTHER_CHANNEL");
+
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/the-repo", "the-repo-reviews").with_jira(vec!["SOME".into()]),
+        );
+
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/the-repo", "the-repo-reviews")
+                .with_branches(vec!["the-branch".to_string()])
+                .with_jira(vec!["THE-BRANCH".into()]),
+        );
+
+        let repo = github::Repo::parse("http://git.company.com/some-user/the-repo").unwrap();
+
+        assert_eq!(vec!["THE-BRANCH"], repos.jira_projects(&repo, "the-branch"));
+        assert_eq!(vec!["SOME"], repos.jira_projects(&repo, "any-other-branch"));
+    }
+
+    #[test]
+    fn test_auto_merge() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info("git.company.com", RepoInfo::new("some-user/no-config", "reviews"));
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/with-config", "reviews")
+                .with_auto_merge(Some("automerge".into()), Some(2))
+                .with_merge_strategy(Some("squash".into())),
+        );
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/bad-config", "reviews")
+                .with_auto_merge(Some("".into()), None)
+                .with_merge_strategy(Some("octopus".into())),
+        );
+
+        {
+            let repo = github::Repo::parse("http://git.company.com/some-user/no-config").unwrap();
+            assert_eq!(None, repos.auto_merge_label(&repo, "master"));
+            assert_eq!(1, repos.auto_merge_approvals(&repo, "master"));
+            assert_eq!("merge", repos.merge_strategy(&repo, "master"));
+        }
+
+        {
+            let repo = github::Repo::parse("http://git.company.com/some-user/with-config").unwrap();
+            assert_eq!(Some("automerge".to_string()), repos.auto_merge_label(&repo, "master"));
+            assert_eq!(2, repos.auto_merge_approvals(&repo, "master"));
+            assert_eq!("squash", repos.merge_strategy(&repo, "master"));
+        }
+
+        {
+            let repo = github::Repo::parse("http://git.company.com/some-user/bad-config").unwrap();
+            assert_eq!(None, repos.auto_merge_label(&repo, "master"));
+            assert_eq!("merge", repos.merge_strategy(&repo, "master"));
+        }
+    }
+
+    #[test]
+    fn test_backport_strategy() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info("git.company.com", RepoInfo::new("some-user/no-config", "reviews"));
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/with-config", "reviews")
+                .with_backport_strategy(Some("rebase".into()), Some("{title} [{target_branch}]".into())),
+        );
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/bad-config", "reviews")
+                .with_backport_strategy(Some("octopus".into()), Some(" ".into())),
+        );
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.company.com/some-user/{}", name)).unwrap();
+        assert_eq!("squash", repos.backport_strategy(&repo("no-config"), "master"));
+        assert_eq!(None, repos.backport_message_template(&repo("no-config"), "master"));
+        assert_eq!("rebase", repos.backport_strategy(&repo("with-config"), "master"));
+        assert_eq!(
+            Some("{title} [{target_branch}]".to_string()),
+            repos.backport_message_template(&repo("with-config"), "master")
+        );
+        assert_eq!("squash", repos.backport_strategy(&repo("bad-config"), "master"));
+        assert_eq!(None, repos.backport_message_template(&repo("bad-config"), "master"));
+    }
 
     #[test]
-    fn lookup_channel_by_repo_full_name() {
+    fn test_backport_milestone() {
         let mut repos = RepoConfig::new();
-        // insert org-level one first in the list to make sure most specific matches first
-        repos.insert("git.company.com", "some-user", "SOME_OTHER_CHANNEL");
-        repos.insert("git.company.com", "some-user/the-repo", "the-repo-reviews");
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/some-repo", "reviews")
+                .with_backport_milestones(vec!["release/1.0  1.0 LTS".into(), "release/2.0".into()]),
+        );
+        repos.insert_info("git.company.com", RepoInfo::new("some-user/other-repo", "reviews"));
 
-        let repo = github::Repo::parse("http://git.company.com/some-user/the-repo").unwrap();
-        assert_eq!("the-repo-reviews", repos.lookup_channel(&repo).unwrap());
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.company.com/some-user/{}", name)).unwrap();
+        assert_eq!(
+            Some("1.0 LTS".to_string()),
+            repos.backport_milestone(&repo("some-repo"), "master", "release/1.0")
+        );
+        assert_eq!(None, repos.backport_milestone(&repo("some-repo"), "master", "release/2.0"));
+        assert_eq!(None, repos.backport_milestone(&repo("some-repo"), "master", "release/3.0"));
+        assert_eq!(None, repos.backport_milestone(&repo("other-repo"), "master", "release/1.0"));
     }
 
     #[test]
-    fn lookup_channel_by_repo_owner() {
+    fn test_backport_branch() {
         let mut repos = RepoConfig::new();
-        repos.insert("git.company.com", "some-user", "the-repo-reviews");
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/some-repo", "reviews")
+                .with_release_branch_prefix(Some("stable-".into()))
+                .with_backport_branches(
+                    vec!["hotfix-(.*)  hotfix/$1".into(), "backport-esr esr/stable".into(), "bad-(rule bad".into()],
+                ),
+        );
+        repos.insert_info("git.company.com", RepoInfo::new("some-user/other-repo", "reviews"));
 
-        let repo = github::Repo::parse("http://git.company.com/some-user/some-other-repo").unwrap();
-        assert_eq!("the-repo-reviews", repos.lookup_channel(&repo).unwrap());
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.company.com/some-user/{}", name)).unwrap();
+        let some_repo = repo("some-repo");
+        let backport_branch = |label: &str| repos.backport_branch(&some_repo, "master", label);
+        assert_eq!(Some("hotfix/1.2".to_string()), backport_branch("HOTFIX-1.2"));
+        assert_eq!(Some("esr/stable".to_string()), backport_branch("backport-esr"));
+        assert_eq!(Some("stable-1.0".to_string()), backport_branch("backport-1.0"));
+        assert_eq!(None, backport_branch("some-hotfix-1.2"));
+        assert_eq!(None, backport_branch("bug"));
+
+        assert_eq!(
+            Some("release/1.0".to_string()),
+            repos.backport_branch(&repo("other-repo"), "master", "backport-1.0")
+        );
+        assert_eq!(None, repos.backport_branch(&repo("other-repo"), "master", "hotfix-1.0"));
     }
 
     #[test]
-    fn lookup_channel_none() {
+    fn test_chain_backports() {
         let mut repos = RepoConfig::new();
-        repos.insert("git.company.com", "some-user", "the-repo-reviews");
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/some-repo", "reviews").with_chain_backports(true));
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
 
-        // fail by channel/repo
-        {
-            let repo = github::Repo::parse("http://git.company.com/someone-else/some-other-repo").unwrap();
-            assert!(repos.lookup_channel(&repo).is_none());
-        }
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert!(repos.chain_backports(&repo("some-repo"), "master"));
+        assert!(!repos.chain_backports(&repo("other-repo"), "master"));
+    }
 
-        // fail by git host
-        {
-            let repo = github::Repo::parse("http://git.other-company.com/some-user/the-repo").unwrap();
-            assert!(repos.lookup_channel(&repo).is_none());
-        }
+    #[test]
+    fn test_backport_mirrors() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews").with_backport_mirrors(vec![
+                "internal/some-repo".into(),
+                "internal/vendor  lib/=third_party/lib/ docs/=".into(),
+                "no-repo".into(),
+                "some-user/some-repo".into(),
+                "internal/bad lib/".into(),
+            ]),
+        );
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert_eq!(
+            vec![
+                BackportMirror {
+                    repo: "internal/some-repo".into(),
+                    path_mappings: vec![],
+                },
+                BackportMirror {
+                    repo: "internal/vendor".into(),
+                    path_mappings: vec![
+                        ("lib/".into(), "third_party/lib/".into()),
+                        ("docs/".into(), "".into()),
+                    ],
+                },
+            ],
+            repos.backport_mirrors(&repo("some-repo"), "master")
+        );
+        assert!(repos.backport_mirrors(&repo("other-repo"), "master").is_empty());
     }
 
     #[test]
-    fn test_notify_force_push() {
+    fn test_release_note_sections() {
         let mut repos = RepoConfig::new();
         repos.insert_info(
-            "git.company.com",
-            RepoInfo::new("some-user/noisy-repo-by-default", "reviews"),
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews")
+                .with_release_note_sections(vec!["security  Security fixes".into(), "docs".into()]),
         );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert_eq!(
+            vec![("security".to_string(), "Security fixes".to_string())],
+            repos.release_note_sections(&repo("some-repo"))
+        );
+        assert_eq!(3, repos.release_note_sections(&repo("other-repo")).len());
+        assert_eq!(
+            ("bug".to_string(), "Bug fixes".to_string()),
+            repos.release_note_sections(&repo("other-repo"))[2]
+        );
+    }
+
+    #[test]
+    fn test_release_channel() {
+        let mut repos = RepoConfig::new();
         repos.insert_info(
-            "git.company.com",
-            RepoInfo::new("some-user/noisy-repo-on-purpose", "reviews").with_force_push(Some(true)),
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews").with_release_channel("releases"),
         );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/blank-repo", "reviews").with_release_channel(" "));
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert_eq!(Some("releases".to_string()), repos.release_channel(&repo("some-repo")));
+        assert_eq!(None, repos.release_channel(&repo("blank-repo")));
+        assert_eq!(None, repos.release_channel(&repo("other-repo")));
+    }
+
+    #[test]
+    fn test_release_branch_config() {
+        let mut repos = RepoConfig::new();
         repos.insert_info(
-            "git.company.com",
-            RepoInfo::new("some-user/quiet-repo", "reviews").with_force_push(Some(false)),
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews")
+                .with_release_branch_protection(vec!["ci/build".into(), " ".into()], 2)
+                .with_release_version_files(vec!["VERSION  {version}.0".into(), "no-template".into()]),
         );
-        {
-            let repo = github::Repo::parse("http://git.company.com/someone-else/some-other-repo").unwrap();
-            assert_eq!(false, repos.notify_force_push(&repo));
-        }
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
 
-        {
-            let repo = github::Repo::parse(
-                "http://git.company.\
-                                            com/some-user/noisy-repo-by-default",
-            ).unwrap();
-            assert_eq!(true, repos.notify_force_push(&repo));
-        }
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert_eq!((vec!["ci/build".to_string()], 2), repos.release_branch_protection(&repo("some-repo"), "master"));
+        assert_eq!(
+            vec![("VERSION".to_string(), "{version}.0".to_string())],
+            repos.release_version_files(&repo("some-repo"), "master")
+        );
+        assert_eq!((vec![], 0), repos.release_branch_protection(&repo("other-repo"), "master"));
+        assert!(repos.release_version_files(&repo("other-repo"), "master").is_empty());
+    }
 
-        {
-            let repo = github::Repo::parse(
-                "http://git.company.\
-                                            com/some-user/noisy-repo-on-purpose",
-            ).unwrap();
-            assert_eq!(true, repos.notify_force_push(&repo));
-        }
+    #[test]
+    fn test_version_bump_rules() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews").with_version_bump_files(
+                vec!["VERSION patch (.*)".into(), "setup.py sideways (.*)".into()],
+                Some("@the-manager".into()),
+            ),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
 
-        {
-            let repo = github::Repo::parse("http://git.company.com/some-user/quiet-repo").unwrap();
-            assert_eq!(false, repos.notify_force_push(&repo));
-        }
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert_eq!(
+            vec![BumpRule::parse("VERSION patch (.*)").unwrap()],
+            repos.version_bump_rules(&repo("some-repo"), "master")
+        );
+        assert_eq!(Some("the-manager".to_string()), repos.release_manager(&repo("some-repo")));
+        assert!(repos.version_bump_rules(&repo("other-repo"), "master").is_empty());
+        assert_eq!(None, repos.release_manager(&repo("other-repo")));
     }
 
     #[test]
-    fn test_jira_enabled() {
+    fn test_deployment_channel() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews")
+                .with_deployment_channels(vec!["production #deploys".into(), "staging".into()]),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert_eq!(Some("#deploys".to_string()), repos.deployment_channel(&repo("some-repo"), "production"));
+        assert_eq!(None, repos.deployment_channel(&repo("some-repo"), "staging"));
+        assert_eq!(None, repos.deployment_channel(&repo("other-repo"), "production"));
+    }
+
+    #[test]
+    fn test_watches_workflow() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews").with_watched_workflows(vec!["CI".into(), "Deploy".into()]),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert!(repos.watches_workflow(&repo("some-repo"), "CI"));
+        assert!(!repos.watches_workflow(&repo("some-repo"), "Lint"));
+        assert!(!repos.watches_workflow(&repo("other-repo"), "CI"));
+    }
+
+    #[test]
+    fn test_broken_branch_escalation() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews").with_broken_branch_escalation(3),
+        );
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/zero-repo", "reviews").with_broken_branch_escalation(0),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert_eq!(Some(3), repos.broken_branch_escalation(&repo("some-repo")));
+        assert_eq!(None, repos.broken_branch_escalation(&repo("zero-repo")));
+        assert_eq!(None, repos.broken_branch_escalation(&repo("other-repo")));
+    }
+
+    #[test]
+    fn test_issue_channel() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews")
+                .with_issue_channels(vec!["security #security".into(), "bug #bugs".into()]),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        let labels = |names: Vec<&str>| names.into_iter().map(github::Label::new).collect::<Vec<_>>();
+        assert_eq!(Some("#bugs".into()), repos.issue_channel(&repo("some-repo"), &labels(vec!["bug"])));
+        assert_eq!(
+            Some("#security".into()),
+            repos.issue_channel(&repo("some-repo"), &labels(vec!["bug", "security"]))
+        );
+        assert_eq!(None, repos.issue_channel(&repo("some-repo"), &labels(vec!["question"])));
+        assert_eq!(None, repos.issue_channel(&repo("other-repo"), &labels(vec!["bug"])));
+    }
+
+    #[test]
+    fn test_security_channel() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews").with_security_channel("security"),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert_eq!(Some("security".into()), repos.security_channel(&repo("some-repo")));
+        assert_eq!(None, repos.security_channel(&repo("other-repo")));
+    }
+
+    #[test]
+    fn test_alerts_direct_push() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews")
+                .with_direct_push_branches(vec!["master".into(), "release/*".into()]),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert!(repos.alerts_direct_push(&repo("some-repo"), "master"));
+        assert!(repos.alerts_direct_push(&repo("some-repo"), "release/1.0"));
+        assert!(!repos.alerts_direct_push(&repo("some-repo"), "feature"));
+        assert!(!repos.alerts_direct_push(&repo("other-repo"), "master"));
+    }
+
+    #[test]
+    fn test_ignores_push() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews")
+                .with_push_ignore_branches(vec!["gh-pages".into(), "renovate/*".into()])
+                .with_push_ignore_authors(vec!["*[bot]".into()]),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert!(repos.ignores_push(&repo("some-repo"), "gh-pages", "joe"));
+        assert!(repos.ignores_push(&repo("some-repo"), "renovate/serde-1.x", "joe"));
+        assert!(repos.ignores_push(&repo("some-repo"), "feature", "dependabot[bot]"));
+        assert!(!repos.ignores_push(&repo("some-repo"), "feature", "joe"));
+        assert!(!repos.ignores_push(&repo("other-repo"), "gh-pages", "dependabot[bot]"));
+    }
+
+    #[test]
+    fn test_admin_channel() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user", "reviews").with_admin_channel("org-admins"));
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/some-repo", "reviews"));
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/own-repo", "reviews").with_admin_channel("own-admins"),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("other-user", "reviews"));
+
+        let repo = |url: &str| github::Repo::parse(url).unwrap();
+        assert_eq!(Some("org-admins".into()), repos.admin_channel(&repo("http://git.foo.com/some-user/new-repo")));
+        assert_eq!(Some("org-admins".into()), repos.admin_channel(&repo("http://git.foo.com/some-user/some-repo")));
+        assert_eq!(Some("own-admins".into()), repos.admin_channel(&repo("http://git.foo.com/some-user/own-repo")));
+        assert_eq!(None, repos.admin_channel(&repo("http://git.foo.com/other-user/some-repo")));
+    }
+
+    #[test]
+    fn test_rename_repo() {
+        let mut repos = RepoConfig::new();
+        repos.insert("git.foo.com", "some-user/old-repo", "reviews");
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/old-repo", "releases").with_branches(vec!["release/*".into()]),
+        );
+        repos.insert("git.foo.com", "some-user/other-repo", "reviews");
+        repos.insert("git.bar.com", "some-user/old-repo", "reviews");
+
+        assert_eq!(2, repos.rename_repo("git.foo.com", "some-user/old-repo", "some-user/new-repo"));
+        assert_eq!(0, repos.rename_repo("git.foo.com", "some-user/old-repo", "some-user/new-repo"));
+        assert_eq!(0, repos.rename_repo("git.baz.com", "some-user/old-repo", "some-user/new-repo"));
+
+        let repo = |url: &str| github::Repo::parse(url).unwrap();
+        assert_eq!(Some("reviews".into()), repos.lookup_channel(&repo("http://git.foo.com/some-user/new-repo"), None));
+        assert_eq!(
+            Some("releases".into()),
+            repos.lookup_channel(&repo("http://git.foo.com/some-user/new-repo"), Some("release/1.0"))
+        );
+        assert_eq!(None, repos.lookup_channel(&repo("http://git.foo.com/some-user/old-repo"), None));
+        assert_eq!(Some("reviews".into()), repos.lookup_channel(&repo("http://git.bar.com/some-user/old-repo"), None));
+    }
+
+    #[test]
+    fn test_lookup_channel_by_pattern() {
+        let mut repos = RepoConfig::new();
+        repos.insert("git.foo.com", "some-org", "org-reviews");
+        repos.insert("git.foo.com", "some-org/service-*", "service-reviews");
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-org/service-*", "service-releases").with_branches(vec!["release/*".into()]),
+        );
+        repos.insert("git.foo.com", "some-org/service-auth", "auth-reviews");
+
+        let repo = |url: &str| github::Repo::parse(url).unwrap();
+        let channel = |name: &str, branch: Option<&str>| {
+            repos.lookup_channel(&repo(&format!("http://git.foo.com/{}", name)), branch)
+        };
+        // the repo's own entry wins over the pattern, which wins over the org
+        assert_eq!(Some("auth-reviews".into()), channel("some-org/service-auth", None));
+        assert_eq!(Some("auth-reviews".into()), channel("some-org/service-auth", Some("release/1.0")));
+        assert_eq!(Some("service-reviews".into()), channel("some-org/service-billing", None));
+        assert_eq!(Some("service-reviews".into()), channel("some-org/service-billing", Some("master")));
+        assert_eq!(Some("service-releases".into()), channel("some-org/service-billing", Some("release/1.0")));
+        assert_eq!(Some("org-reviews".into()), channel("some-org/website", None));
+        assert_eq!(None, channel("other-org/service-billing", None));
+
+        assert!(repos.is_configured(&repo("http://git.foo.com/some-org/service-billing")));
+    }
+
+    #[test]
+    fn test_exclude_repos() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-org", "org-reviews").with_exclude_repos(vec!["some-org/sandbox-*".into()]),
+        );
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-org/service-*", "service-reviews")
+                .with_exclude_repos(vec!["some-org/service-legacy".into()]),
+        );
+        repos.insert("git.foo.com", "some-org/sandbox-kept", "kept-reviews");
+
+        let repo = |url: &str| github::Repo::parse(url).unwrap();
+        let channel = |name: &str| repos.lookup_channel(&repo(&format!("http://git.foo.com/{}", name)), None);
+        assert_eq!(Some("org-reviews".into()), channel("some-org/website"));
+        assert_eq!(None, channel("some-org/sandbox-test"));
+        // a repo's own entry isn't affected by exclusions
+        assert_eq!(Some("kept-reviews".into()), channel("some-org/sandbox-kept"));
+        assert_eq!(Some("service-reviews".into()), channel("some-org/service-billing"));
+        // excluded from the pattern, so it falls back to the org
+        assert_eq!(Some("org-reviews".into()), channel("some-org/service-legacy"));
+
+        assert!(!repos.is_configured(&repo("http://git.foo.com/some-org/sandbox-test")));
+    }
+
+    #[test]
+    fn test_pr_threads() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/some-repo", "reviews").with_pr_threads(true));
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "releases")
+                .with_branches(vec!["release/*".into()])
+                .with_pr_threads(false),
+        );
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert!(repos.pr_threads(&repo("some-repo"), None));
+        assert!(repos.pr_threads(&repo("some-repo"), Some("master")));
+        assert!(!repos.pr_threads(&repo("some-repo"), Some("release/1.0")));
+        assert!(!repos.pr_threads(&repo("other-repo"), None));
+    }
+
+    #[test]
+    fn test_comment_edits() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/some-repo", "reviews").with_comment_edits(true));
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/quiet-repo", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert!(repos.comment_edits(&repo("some-repo"), None));
+        assert!(repos.comment_edits(&repo("some-repo"), Some("master")));
+        assert!(!repos.comment_edits(&repo("quiet-repo"), None));
+        assert!(!repos.comment_edits(&repo("other-repo"), None));
+    }
+
+    #[test]
+    fn test_weekly_metrics() {
+        let mut repos = RepoConfig::new();
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/some-repo", "reviews").with_weekly_metrics(true));
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/quiet-repo", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert!(repos.weekly_metrics(&repo("some-repo")));
+        assert!(!repos.weekly_metrics(&repo("quiet-repo")));
+        assert!(!repos.weekly_metrics(&repo("other-repo")));
+        let names: Vec<String> = repos.weekly_metrics_repos("git.foo.com").into_iter().map(|r| r.full_name).collect();
+        assert_eq!(vec!["some-user/some-repo".to_string()], names);
+        assert!(repos.weekly_metrics_repos("git.bar.com").is_empty());
+    }
+
+    #[test]
+    fn test_event_rules() {
+        let mut rule = EventRule::new();
+        rule.labels = Some(vec!["security".into()]);
+        rule.notify_channel = Some("#security".into());
+        let mut repos = RepoConfig::new();
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/some-repo", "reviews").with_rules(vec![rule]));
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/other-repo", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        assert!(!repos.event_rules(&repo("some-repo"), None).is_empty());
+        assert!(!repos.event_rules(&repo("some-repo"), Some("master")).is_empty());
+        assert!(repos.event_rules(&repo("other-repo"), None).is_empty());
+    }
+
+    #[test]
+    fn test_messages() {
+        let templates = hashmap!{messages::PR_MERGED.to_string() => "Shipped #{number}".to_string()};
+        let mut repos = RepoConfig::new();
+        repos.insert_info(
+            "git.foo.com",
+            RepoInfo::new("some-user/some-repo", "reviews").with_message_templates(templates),
+        );
+        repos.insert_info("git.foo.com", RepoInfo::new("some-user/other-repo", "reviews"));
+
+        let repo = |name: &str| github::Repo::parse(&format!("http://git.foo.com/some-user/{}", name)).unwrap();
+        let values = [("number", "12")];
+        assert_eq!(
+            "Shipped #12",
+            repos.messages(&repo("some-repo"), Some("master")).format(messages::PR_MERGED, &values)
+        );
+        assert_eq!(
+            "Pull Request merged",
+            repos.messages(&repo("other-repo"), None).format(messages::PR_MERGED, &values)
+        );
+    }
+
+    #[test]
+    fn test_notifications() {
         let mut repos = RepoConfig::new();
         repos.insert_info("git.company.com", RepoInfo::new("some-user/no-config", "reviews"));
         repos.insert_info(
             "git.company.com",
-            RepoInfo::new("some-user/with-config", "reviews").with_jira(vec!["a".into(), "b".into()]),
+            RepoInfo::new("some-user/with-config", "reviews").with_notifications(
+                Some(EventFilter::new(None, Some(vec!["comment".into(), "push".into()]))),
+                Some(EventFilter::new(Some(vec!["review".into(), "comment".into()]), Some(vec!["comment".into()]))),
+            ),
         );
 
-        {
-            let repo = github::Repo::parse("http://git.company.com/someone-else/some-other-repo").unwrap();
-            assert_eq!(Vec::<String>::new(), repos.jira_projects(&repo, "any"));
-        }
-
         {
             let repo = github::Repo::parse("http://git.company.com/some-user/no-config").unwrap();
-            assert_eq!(Vec::<String>::new(), repos.jira_projects(&repo, "any"));
+            assert!(repos.notify_channel(&repo, "comment"));
+            assert!(repos.notify_direct(&repo, "comment"));
         }
 
         {
             let repo = github::Repo::parse("http://git.company.com/some-user/with-config").unwrap();
-            assert_eq!(vec!["a", "b"], repos.jira_projects(&repo, "any"));
-        }
+            assert!(repos.notify_channel(&repo, "review"));
+            assert!(!repos.notify_channel(&repo, "comment"));
+            assert!(!repos.notify_channel(&repo, "push"));
 
+            assert!(repos.notify_direct(&repo, "review"));
+            assert!(!repos.notify_direct(&repo, "comment"));
+            assert!(!repos.notify_direct(&repo, "push"));
+        }
     }
 
     #[test]
-    fn test_jira_by_branch() {
+    fn test_webhook_urls() {
         let mut repos = RepoConfig::new();
-        repos.insert("git.company.com", "some-user", "SOME_OTHER_CHANNEL");
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/the-repo", "reviews").with_webhook_urls(vec!["https://dashboard/events".into()]),
+        );
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/the-repo", "release-reviews").with_branches(vec!["release/*".into()]),
+        );
+
+        let repo = github::Repo::parse("http://git.company.com/some-user/the-repo").unwrap();
+        assert_eq!(vec!["https://dashboard/events"], repos.webhook_urls(&repo, Some("master")));
+        assert_eq!(Vec::<String>::new(), repos.webhook_urls(&repo, Some("release/1.0")));
+
+        let other = github::Repo::parse("http://git.company.com/some-user/other-repo").unwrap();
+        assert_eq!(Vec::<String>::new(), repos.webhook_urls(&other, None));
+    }
 
+    #[test]
+    fn test_host_repos() {
+        let mut repos = RepoConfig::new();
+        repos.insert("git.company.com", "some-user/the-repo", "reviews");
         repos.insert_info(
             "git.company.com",
-            RepoInfo::new("some-user/the-repo", "the-repo-reviews").with_jira(vec!["SOME".into()]),
+            RepoInfo::new("some-user/the-repo", "release-reviews").with_branches(vec!["release/*".into()]),
         );
+        repos.insert("git.company.com", "some-user/other-repo", "#other-reviews");
+        repos.insert("git.company.com", "some-org", "reviews");
+        repos.insert("git.company.com", "some-org/service-*", "reviews");
+        repos.insert("git.other.com", "some-user/third-repo", "reviews");
+
+        let names = |found: Vec<github::Repo>| found.into_iter().map(|r| r.full_name).collect::<Vec<_>>();
+        assert_eq!(
+            vec!["some-user/the-repo", "some-user/other-repo"],
+            names(repos.host_repos("git.company.com", None))
+        );
+        assert_eq!(vec!["some-user/the-repo"], names(repos.host_repos("git.company.com", Some("reviews"))));
+        assert_eq!(vec!["some-user/other-repo"], names(repos.host_repos("git.company.com", Some("other-reviews"))));
+        assert!(repos.host_repos("git.nowhere.com", None).is_empty());
+    }
+
+    #[test]
+    fn test_branch_matches() {
+        assert!(branch_matches("master", "master"));
+        assert!(!branch_matches("master", "master2"));
+        assert!(branch_matches("release/*", "release/1.0"));
+        assert!(!branch_matches("release/*", "other/release/1.0"));
+        assert!(branch_matches("*-hotfix", "1.0-hotfix"));
+        assert!(branch_matches("release/*/hotfix-*", "release/1.0/hotfix-2"));
+        assert!(!branch_matches("release/*/hotfix-*", "release/1.0"));
+        assert!(!branch_matches("a*a", "a"));
+    }
+
+    #[test]
+    fn test_is_configured() {
+        let mut repos = RepoConfig::new();
+        repos.insert("git.foo.com", "some-user/some-repo", "reviews");
+        repos.insert("git.foo.com", "some-org", "org-reviews");
+
+        let repo = |url: &str| github::Repo::parse(url).unwrap();
+        assert!(repos.is_configured(&repo("http://git.foo.com/some-user/some-repo")));
+        assert!(repos.is_configured(&repo("http://git.foo.com/some-org/any-repo")));
+        assert!(!repos.is_configured(&repo("http://git.foo.com/some-user/other-repo")));
+        assert!(!repos.is_configured(&repo("http://git.bar.com/some-user/some-repo")));
+    }
 
+    #[test]
+    fn test_lookup_channel_by_branch() {
+        let mut repos = RepoConfig::new();
         repos.insert_info(
             "git.company.com",
-            RepoInfo::new("some-user/the-repo", "the-repo-reviews")
-                .with_branches(vec!["the-branch".to_string()])
-                .with_jira(vec!["THE-BRANCH".into()]),
+            RepoInfo::new("some-user", "release-reviews").with_branches(vec!["release/*".into()]),
+        );
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/the-repo", "dev-reviews").with_branches(vec!["master".into()]),
+        );
+        repos.insert_info("git.company.com", RepoInfo::new("some-user/the-repo", "the-repo-reviews"));
+        repos.insert_info(
+            "git.company.com",
+            RepoInfo::new("some-user/the-repo", "the-repo-release-reviews").with_branches(vec!["release/*".into()]),
         );
 
         let repo = github::Repo::parse("http://git.company.com/some-user/the-repo").unwrap();
+        assert_eq!("dev-reviews", repos.lookup_channel(&repo, Some("master")).unwrap());
+        assert_eq!("the-repo-release-reviews", repos.lookup_channel(&repo, Some("release/1.0")).unwrap());
+        assert_eq!("the-repo-reviews", repos.lookup_channel(&repo, Some("some-branch")).unwrap());
+        assert_eq!("the-repo-reviews", repos.lookup_channel(&repo, None).unwrap());
 
-        assert_eq!(vec!["THE-BRANCH"], repos.jira_projects(&repo, "the-branch"));
-        assert_eq!(vec!["SOME"], repos.jira_projects(&repo, "any-other-branch"));
+        let repo = github::Repo::parse("http://git.company.com/some-user/other-repo").unwrap();
+        assert_eq!("release-reviews", repos.lookup_channel(&repo, Some("release/1.0")).unwrap());
+        assert_eq!("release-reviews", repos.lookup_channel(&repo, Some("master")).unwrap());
     }
 }
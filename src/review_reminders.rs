@@ -0,0 +1,371 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use cluster::Cluster;
+use config::Config;
+use github;
+use json_store::JsonStore;
+use messenger::{self, Messenger, MessengerFactory};
+use quiet_hours::{self, WorkHours};
+use slack::{SlackAttachment, SlackAttachmentBuilder};
+use worker;
+
+const HOUR_SECS: i64 = 60 * 60;
+const DEFAULT_WORK_HOURS: &str = "09:00-17:00";
+const CHECK_INTERVAL_SECS: u64 = 60;
+
+// A reviewer who has been asked to look at a PR and hasn't yet.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct WaitingReview {
+    repo: github::Repo,
+    number: u32,
+    title: String,
+    html_url: String,
+    branch: String,
+    reviewer: String,
+    since: i64,
+    reminded_at: Option<i64>,
+    escalated: bool,
+}
+
+// An open PR, from when it was first seen until someone reviews it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct UnreviewedPr {
+    repo: github::Repo,
+    number: u32,
+    title: String,
+    html_url: String,
+    branch: String,
+    since: i64,
+    first_review_at: Option<i64>,
+    lead_told: bool,
+    here_sent: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PendingReviews {
+    waiting: Vec<WaitingReview>,
+    #[serde(default)]
+    unreviewed: Vec<UnreviewedPr>,
+}
+
+#[derive(Debug)]
+pub enum Reminder {
+    Reviewer(github::Repo, String, SlackAttachment),
+    Channel(github::Repo, String, String, SlackAttachment),
+    // repo, lead, and hours without a review
+    Lead(github::Repo, String, i64, SlackAttachment),
+    // repo, branch, and hours without a review
+    Here(github::Repo, String, i64, SlackAttachment),
+}
+
+impl PendingReviews {
+    // Brings the reviewers waited on for this PR in line with its current state: requested reviewers
+    // and assignees who haven't reviewed yet are waited on, starting now if they weren't already.
+    pub fn sync(&mut self, repo: &github::Repo, pull_request: &github::PullRequest, now: i64) {
+        let awaiting = if pull_request.is_open() { awaiting_review(pull_request) } else { vec![] };
+
+        self.waiting.retain(|w| {
+            w.repo.html_url != repo.html_url || w.number != pull_request.number || awaiting.contains(&w.reviewer)
+        });
+
+        for reviewer in awaiting {
+            let tracked = self.waiting.iter().any(|w| {
+                w.repo.html_url == repo.html_url && w.number == pull_request.number && w.reviewer == reviewer
+            });
+            if !tracked {
+                self.waiting.push(WaitingReview {
+                    repo: repo.clone(),
+                    number: pull_request.number,
+                    title: pull_request.title.clone(),
+                    html_url: pull_request.html_url.clone(),
+                    branch: pull_request.base.ref_name.clone(),
+                    reviewer: reviewer,
+                    since: now,
+                    reminded_at: None,
+                    escalated: false,
+                });
+            }
+        }
+
+        self.sync_unreviewed(repo, pull_request, now);
+    }
+
+    // Keeps track of when open PRs were first seen and first reviewed. Drafts aren't waiting for reviews yet.
+    fn sync_unreviewed(&mut self, repo: &github::Repo, pull_request: &github::PullRequest, now: i64) {
+        let is_pr = |u: &UnreviewedPr| u.repo.html_url == repo.html_url && u.number == pull_request.number;
+        if !pull_request.is_open() || pull_request.is_draft() {
+            self.unreviewed.retain(|u| !is_pr(u));
+            return;
+        }
+
+        let reviewed = pull_request.reviews.as_ref().map_or(false, |r| {
+            r.iter().any(|r| r.user.login() != pull_request.user.login())
+        });
+        if let Some(u) = self.unreviewed.iter_mut().find(|u| is_pr(u)) {
+            if reviewed && u.first_review_at.is_none() {
+                u.first_review_at = Some(now);
+            }
+            return;
+        }
+        self.unreviewed.push(UnreviewedPr {
+            repo: repo.clone(),
+            number: pull_request.number,
+            title: pull_request.title.clone(),
+            html_url: pull_request.html_url.clone(),
+            branch: pull_request.base.ref_name.clone(),
+            since: now,
+            first_review_at: if reviewed { Some(now) } else { None },
+            lead_told: false,
+            here_sent: false,
+        });
+    }
+
+    // Reminds reviewers once they've been waited on for the repo's reminder time, and tells the channel
+    // if they still haven't reviewed after the escalation time. Each happens only once per reviewer.
+    pub fn take_due(&mut self, config: &Config, now: i64) -> Vec<Reminder> {
+        let mut reminders = vec![];
+        for w in self.waiting.iter_mut() {
+            let repos = config.repos();
+            let remind_hours = match repos.review_reminder_hours(&w.repo, &w.branch) {
+                Some(h) => h as i64,
+                None => continue,
+            };
+
+            // reviewers who are out of office aren't nagged, and the wait starts over when they're back
+            if config.users().is_out_of_office(&w.reviewer, &w.repo) {
+                match w.reminded_at {
+                    None => w.since = now,
+                    Some(_) => w.reminded_at = Some(now),
+                }
+                continue;
+            }
+
+            match w.reminded_at {
+                None => {
+                    if working_secs(config, w, w.since, now) >= remind_hours * HOUR_SECS {
+                        w.reminded_at = Some(now);
+                        reminders.push(Reminder::Reviewer(w.repo.clone(), w.reviewer.clone(), attachment(w)));
+                    }
+                }
+                Some(reminded_at) if !w.escalated => {
+                    let escalate_hours = match repos.review_escalation_hours(&w.repo, &w.branch) {
+                        Some(h) => h as i64,
+                        None => continue,
+                    };
+                    if working_secs(config, w, reminded_at, now) >= escalate_hours * HOUR_SECS {
+                        w.escalated = true;
+                        reminders.push(
+                            Reminder::Channel(w.repo.clone(), w.branch.clone(), w.reviewer.clone(), attachment(w)),
+                        );
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        for u in self.unreviewed.iter_mut().filter(|u| u.first_review_at.is_none()) {
+            let repos = config.repos();
+            let hours = (now - u.since) / HOUR_SECS;
+            if let Some((lead, lead_hours)) = repos.review_lead(&u.repo, &u.branch) {
+                if !u.lead_told && hours >= lead_hours as i64 {
+                    u.lead_told = true;
+                    reminders.push(Reminder::Lead(u.repo.clone(), lead, hours, unreviewed_attachment(u)));
+                }
+            }
+            if let Some(here_hours) = repos.review_here_hours(&u.repo, &u.branch) {
+                if !u.here_sent && hours >= here_hours as i64 {
+                    u.here_sent = true;
+                    reminders.push(Reminder::Here(u.repo.clone(), u.branch.clone(), hours, unreviewed_attachment(u)));
+                }
+            }
+        }
+        reminders
+    }
+
+    // when the PR was first reviewed, if it's being tracked
+    pub fn first_review_at(&self, repo: &github::Repo, number: u32) -> Option<i64> {
+        self.unreviewed
+            .iter()
+            .find(|u| u.repo.html_url == repo.html_url && u.number == number)
+            .and_then(|u| u.first_review_at)
+    }
+
+    pub fn len(&self) -> usize {
+        self.waiting.len()
+    }
+}
+
+fn awaiting_review(pull_request: &github::PullRequest) -> Vec<String> {
+    let reviewed = |login: &str| {
+        pull_request.reviews.as_ref().map_or(false, |r| r.iter().any(|r| r.user.login() == login))
+    };
+
+    // github takes reviewers off the requested list once they review, and puts them back when asked again
+    let mut awaiting: Vec<String> = vec![];
+    if let Some(ref requested) = pull_request.requested_reviewers {
+        awaiting.extend(requested.iter().map(|u| u.login().to_string()));
+    }
+    for assignee in &pull_request.assignees {
+        if !reviewed(assignee.login()) {
+            awaiting.push(assignee.login().to_string());
+        }
+    }
+
+    awaiting.retain(|r| r != pull_request.user.login());
+    awaiting.sort();
+    awaiting.dedup();
+    awaiting
+}
+
+fn attachment(w: &WaitingReview) -> SlackAttachment {
+    pr_attachment(w.number, &w.title, &w.html_url)
+}
+
+fn unreviewed_attachment(u: &UnreviewedPr) -> SlackAttachment {
+    pr_attachment(u.number, &u.title, &u.html_url)
+}
+
+fn pr_attachment(number: u32, title: &str, html_url: &str) -> SlackAttachment {
+    SlackAttachmentBuilder::new("")
+        .title(format!("Pull Request #{}: \"{}\"", number, title))
+        .title_link(html_url)
+        .build()
+}
+
+// The reviewer's working time since the given time: their work hours if they set some, or 09:00-17:00 UTC
+// on weekdays.
+fn working_secs(config: &Config, w: &WaitingReview, since: i64, now: i64) -> i64 {
+    let hours = config.users().work_hours(&w.reviewer, &w.repo).or_else(|| WorkHours::parse(DEFAULT_WORK_HOURS, "UTC"));
+    match hours {
+        Some(h) => h.working_secs(since, now),
+        None => now - since,
+    }
+}
+
+pub fn send_reminders(messenger: &Messenger, config: &Config, pending: &JsonStore<PendingReviews>, now: i64) {
+    for reminder in pending.write(|p| p.take_due(config, now)) {
+        match reminder {
+            Reminder::Reviewer(repo, reviewer, attach) => {
+                messenger.send_to_users(
+                    "Your review is still pending",
+                    &vec![attach],
+                    &vec![github::User::new(&reviewer)],
+                    &repo,
+                    messenger::Reason::ReviewRequest,
+                );
+            }
+            Reminder::Channel(repo, branch, reviewer, attach) => {
+                let name = config.users().slack_user_name(&reviewer, &repo);
+                let msg = format!("Pull Request is still waiting for review from {}", name);
+                messenger.send_to_channel(&msg, &vec![attach], &repo, Some(&branch));
+            }
+            Reminder::Lead(repo, lead, hours, attach) => {
+                let msg = format!("Pull Request hasn't been reviewed in {} hours", hours);
+                if lead.starts_with('#') {
+                    messenger.send_to_team_channel(&msg, &vec![attach], &lead, &repo);
+                } else {
+                    let users = vec![github::User::new(&lead)];
+                    messenger.send_to_users(&msg, &vec![attach], &users, &repo, messenger::Reason::Participant);
+                }
+            }
+            Reminder::Here(repo, branch, hours, attach) => {
+                let msg = format!("<!here> Pull Request still hasn't been reviewed after {} hours", hours);
+                messenger.send_to_channel(&msg, &vec![attach], &repo, Some(&branch));
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReviewReminderRequest {
+    pub repo: github::Repo,
+    pub pull_request: github::PullRequest,
+}
+
+pub fn req(repo: &github::Repo, pull_request: &github::PullRequest) -> ReviewReminderRequest {
+    ReviewReminderRequest {
+        repo: repo.clone(),
+        pull_request: pull_request.clone(),
+    }
+}
+
+struct Runner {
+    pending: Arc<JsonStore<PendingReviews>>,
+}
+
+pub fn new_worker(pending: Arc<JsonStore<PendingReviews>>) -> worker::Worker<ReviewReminderRequest> {
+    worker::Worker::new("review-reminders", Runner { pending: pending })
+}
+
+// Checks every minute for reviews that are due a reminder or an escalation. pending is one host's reviews, so each
+// host that wants reminders starts its own. In a cluster, one node sends them.
+pub fn start(
+    config: Arc<Config>,
+    host: String,
+    pending: Arc<JsonStore<PendingReviews>>,
+    messengers: MessengerFactory,
+    cluster: Option<Arc<Cluster>>,
+) {
+    let result = thread::Builder::new().name("review-reminders-check".into()).spawn(move || {
+        let messenger = messengers.new_messenger();
+        let job = format!("review-reminders:{}", host);
+        loop {
+            thread::sleep(Duration::from_secs(CHECK_INTERVAL_SECS));
+            if let Some(ref cluster) = cluster {
+                if !cluster.is_leader(&job, 3 * CHECK_INTERVAL_SECS) {
+                    continue;
+                }
+            }
+            send_reminders(&*messenger, &config, &pending, quiet_hours::now());
+        }
+    });
+
+    if let Err(e) = result {
+        error!("Error starting review reminder thread: {}", e);
+    }
+}
+
+impl worker::Runner<ReviewReminderRequest> for Runner {
+    fn handle(&self, req: ReviewReminderRequest) {
+        let now = quiet_hours::now();
+        self.pending.write(|p| p.sync(&req.repo, &req.pull_request, now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2017-10-02 00:00:00 UTC, a monday
+    const MONDAY: i64 = 1506902400;
+
+    #[test]
+    fn test_sync() {
+        let repo = github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap();
+        let mut pr = github::PullRequest::new();
+        pr.number = 32;
+        pr.user = github::User::new("the-author");
+        pr.assignees = vec![github::User::new("the-author"), github::User::new("assign1")];
+        pr.requested_reviewers = Some(vec![github::User::new("joe-reviewer")]);
+
+        let mut pending = PendingReviews::default();
+        pending.sync(&repo, &pr, MONDAY);
+        assert_eq!(2, pending.len());
+
+        // joe reviewed, and the assignee doesn't have to any more
+        pr.requested_reviewers = Some(vec![]);
+        pr.reviews = Some(vec![github::Review::new("lgtm", github::User::new("assign1"))]);
+        pending.sync(&repo, &pr, MONDAY + HOUR_SECS);
+        assert_eq!(0, pending.len());
+
+        pr.requested_reviewers = Some(vec![github::User::new("joe-reviewer")]);
+        pending.sync(&repo, &pr, MONDAY + HOUR_SECS);
+        assert_eq!(1, pending.len());
+
+        pr.state = "closed".into();
+        pending.sync(&repo, &pr, MONDAY + HOUR_SECS);
+        assert_eq!(0, pending.len());
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use errors::*;
+use github;
+use github::api::Session;
+
+pub const CONTEXT: &'static str = "octobot/reviews";
+
+// The "octobot/reviews" status for a pull request: failing while anyone's latest review requests changes,
+// pending until `approvals` reviewers other than the author approved, and passing after that.
+pub fn review_status(
+    pull_request: &github::PullRequest,
+    reviews: &Vec<github::Review>,
+    approvals: u32,
+) -> github::Status {
+    let latest = latest_reviews(pull_request, reviews);
+    let changes_requested = reviewers_in(&latest, "changes_requested");
+    let approved = reviewers_in(&latest, "approved").len() as u32;
+
+    let (state, description) = if !changes_requested.is_empty() {
+        ("failure", format!("Changes requested by {}", changes_requested.join(", ")))
+    } else if approved >= approvals {
+        ("success", format!("{} of {} approvals", approved, approvals))
+    } else {
+        ("pending", format!("{} of {} approvals", approved, approvals))
+    };
+
+    github::Status {
+        state: state.into(),
+        target_url: Some(pull_request.html_url.clone()),
+        context: Some(CONTEXT.into()),
+        description: Some(description),
+        creator: None,
+    }
+}
+
+// each reviewer's latest approval, request for changes, or dismissal. Comments don't change it, and the author's
+// own reviews don't count.
+fn latest_reviews(pull_request: &github::PullRequest, reviews: &Vec<github::Review>) -> HashMap<String, String> {
+    let mut latest: HashMap<String, String> = HashMap::new();
+    for review in reviews {
+        if review.user.login() == pull_request.user.login() {
+            continue;
+        }
+        let state = review.state.to_lowercase();
+        if state == "approved" || state == "changes_requested" || state == "dismissed" {
+            latest.insert(review.user.login().to_string(), state);
+        }
+    }
+    latest
+}
+
+fn reviewers_in<'a>(latest: &'a HashMap<String, String>, state: &str) -> Vec<&'a str> {
+    let mut reviewers: Vec<&str> = latest.iter().filter(|&(_, s)| s == state).map(|(u, _)| u.as_str()).collect();
+    reviewers.sort();
+    reviewers
+}
+
+// What a merged PR went in with, e.g. "Approved by joe, sue\nCI was green". CI is left out if there were no
+// statuses at all.
+pub fn merge_summary(
+    pull_request: &github::PullRequest,
+    reviews: &Vec<github::Review>,
+    combined: Option<&github::CombinedStatus>,
+) -> String {
+    let latest = latest_reviews(pull_request, reviews);
+    let approved = reviewers_in(&latest, "approved");
+    let changes_requested = reviewers_in(&latest, "changes_requested");
+
+    let mut lines = vec![];
+    if approved.is_empty() {
+        lines.push("Not approved".to_string());
+    } else {
+        lines.push(format!("Approved by {}", approved.join(", ")));
+    }
+    if !changes_requested.is_empty() {
+        lines.push(format!("Changes requested by {}", changes_requested.join(", ")));
+    }
+    if let Some(combined) = combined {
+        if !combined.statuses.is_empty() {
+            lines.push(if combined.is_success() {
+                "CI was green".to_string()
+            } else if combined.is_failure() {
+                "CI was failing".to_string()
+            } else {
+                "CI was still running".to_string()
+            });
+        }
+    }
+    lines.join("\n")
+}
+
+pub fn update(
+    github: &Session,
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+    approvals: u32,
+) -> Result<()> {
+    let owner = repo.owner.login();
+    let reviews = github.get_pull_request_reviews(owner, &repo.name, pull_request.number)?;
+    let status = review_status(pull_request, &reviews, approvals);
+    github.create_status(owner, &repo.name, &pull_request.head.sha, &status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review(login: &str, state: &str) -> github::Review {
+        let mut review = github::Review::new("", github::User::new(login));
+        review.state = state.into();
+        review
+    }
+
+    fn pull_request() -> github::PullRequest {
+        let mut pr = github::PullRequest::new();
+        pr.user = github::User::new("the-author");
+        pr.html_url = "http://the-pr".into();
+        pr
+    }
+
+    fn state(reviews: Vec<github::Review>, approvals: u32) -> (String, String) {
+        let status = review_status(&pull_request(), &reviews, approvals);
+        (status.state, status.description.unwrap())
+    }
+
+    #[test]
+    fn test_review_status() {
+        assert_eq!(("pending".into(), "0 of 1 approvals".into()), state(vec![], 1));
+        assert_eq!(("success".into(), "1 of 1 approvals".into()), state(vec![review("joe", "APPROVED")], 1));
+        assert_eq!(
+            ("pending".into(), "1 of 2 approvals".into()),
+            state(vec![review("joe", "APPROVED"), review("joe", "APPROVED")], 2)
+        );
+
+        // the author's own approval doesn't count
+        assert_eq!(("pending".into(), "0 of 1 approvals".into()), state(vec![review("the-author", "APPROVED")], 1));
+
+        // latest review wins, and comments don't reset it
+        assert_eq!(
+            ("success".into(), "1 of 1 approvals".into()),
+            state(vec![review("joe", "CHANGES_REQUESTED"), review("joe", "APPROVED"), review("joe", "COMMENTED")], 1)
+        );
+        assert_eq!(
+            ("pending".into(), "0 of 1 approvals".into()),
+            state(vec![review("joe", "APPROVED"), review("joe", "DISMISSED")], 1)
+        );
+
+        assert_eq!(
+            ("failure".into(), "Changes requested by bob, sue".into()),
+            state(
+                vec![review("sue", "CHANGES_REQUESTED"), review("joe", "APPROVED"), review("bob", "CHANGES_REQUESTED")],
+                1,
+            )
+        );
+    }
+
+    #[test]
+    fn test_merge_summary() {
+        let combined = |state: &str, statuses: usize| {
+            github::CombinedStatus {
+                state: state.into(),
+                sha: "ffff0000".into(),
+                statuses: (0..statuses).map(|_| review_status(&pull_request(), &vec![], 0)).collect(),
+            }
+        };
+        let reviews = vec![review("sue", "APPROVED"), review("bob", "CHANGES_REQUESTED"), review("joe", "APPROVED")];
+
+        assert_eq!("Not approved", merge_summary(&pull_request(), &vec![], None));
+        assert_eq!("Not approved", merge_summary(&pull_request(), &vec![], Some(&combined("pending", 0))));
+        assert_eq!(
+            "Approved by joe, sue\nChanges requested by bob\nCI was green",
+            merge_summary(&pull_request(), &reviews, Some(&combined("success", 2)))
+        );
+        assert_eq!(
+            "Approved by joe, sue\nChanges requested by bob\nCI was failing",
+            merge_summary(&pull_request(), &reviews, Some(&combined("failure", 2)))
+        );
+        assert_eq!(
+            "Not approved\nCI was still running",
+            merge_summary(&pull_request(), &vec![], Some(&combined("pending", 1)))
+        );
+    }
+
+    #[test]
+    fn test_review_status_fields() {
+        let status = review_status(&pull_request(), &vec![], 1);
+        assert_eq!(Some(CONTEXT.into()), status.context);
+        assert_eq!(Some("http://the-pr".into()), status.target_url);
+        assert!(status.creator.is_none());
+    }
+}
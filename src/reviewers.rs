@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use codeowners::CodeOwnersCache;
+use config::Config;
+use github;
+use github::api::Session;
+use json_store::JsonStore;
+use messenger::{self, Messenger, MessengerFactory};
+use slack::{SlackAttachment, SlackAttachmentBuilder};
+use worker;
+
+// Where each repo's reviewer pool left off, so that restarts don't send everything back to the
+// first reviewer in the list.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ReviewerRotation {
+    next: HashMap<String, usize>,
+}
+
+impl ReviewerRotation {
+    pub fn new() -> ReviewerRotation {
+        ReviewerRotation { next: HashMap::new() }
+    }
+
+    pub fn pick(&mut self, key: &str, pool: &Vec<String>, count: usize, author: &str) -> Vec<String> {
+        self.pick_except(key, pool, count, &vec![author.to_string()])
+    }
+
+    // like pick, passing over everyone in skipped, e.g. the author and reviewers who are out of office
+    pub fn pick_except(&mut self, key: &str, pool: &Vec<String>, count: usize, skipped: &Vec<String>) -> Vec<String> {
+        if pool.is_empty() {
+            return vec![];
+        }
+
+        let start = self.next.get(key).map_or(0, |n| *n % pool.len());
+        let mut picked = vec![];
+        let mut next = start;
+        for i in 0..pool.len() {
+            if picked.len() >= count {
+                break;
+            }
+            let index = (start + i) % pool.len();
+            next = (index + 1) % pool.len();
+            if !skipped.contains(&pool[index]) && !picked.contains(&pool[index]) {
+                picked.push(pool[index].clone());
+            }
+        }
+
+        self.next.insert(key.to_string(), next);
+        picked
+    }
+}
+
+#[derive(Debug)]
+pub struct ReviewerRequest {
+    pub repo: github::Repo,
+    pub pull_request: github::PullRequest,
+}
+
+pub fn req(repo: &github::Repo, pull_request: &github::PullRequest) -> ReviewerRequest {
+    ReviewerRequest {
+        repo: repo.clone(),
+        pull_request: pull_request.clone(),
+    }
+}
+
+// returns the reviewers that were requested
+pub fn assign_reviewers(
+    github: &Session,
+    config: &Config,
+    messenger: &Messenger,
+    rotation: &JsonStore<ReviewerRotation>,
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+) -> Vec<String> {
+    let branch = &pull_request.base.ref_name;
+    let pool = config.repos().reviewer_pool(repo, branch);
+    if pool.is_empty() {
+        return vec![];
+    }
+
+    // leave it alone if the author already picked someone
+    if pull_request.requested_reviewers.as_ref().map_or(false, |r| !r.is_empty()) {
+        return vec![];
+    }
+
+    let count = config.repos().reviewer_count(repo, branch);
+    let mut skipped = vec![pull_request.user.login().to_string()];
+    skipped.extend(pool.iter().filter(|r| config.users().is_out_of_office(r, repo)).cloned());
+    let reviewers = rotation.write(|r| r.pick_except(&repo.html_url, &pool, count, &skipped));
+    if reviewers.is_empty() {
+        return vec![];
+    }
+
+    if let Err(e) = github.request_review(repo.owner.login(), &repo.name, pull_request.number, reviewers.clone()) {
+        error!("Error requesting review for PR #{}: {}", pull_request.number, e);
+        return vec![];
+    }
+
+    let users = reviewers.iter().map(|r| github::User::new(r)).collect();
+    let msg = "Your review was requested";
+    messenger.send_to_users(msg, &pr_attachments(pull_request), &users, repo, messenger::Reason::ReviewRequest);
+
+    reviewers
+}
+
+// Let the owners of the files a PR touches know about it, skipping anyone who was already
+// asked to review.
+pub fn notify_code_owners(
+    github: &Session,
+    config: &Config,
+    messenger: &Messenger,
+    codeowners: &CodeOwnersCache,
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+    already_requested: &Vec<String>,
+) {
+    let branch = &pull_request.base.ref_name;
+    let notify = config.repos().codeowners_notify(repo, branch);
+    let request_review = config.repos().codeowners_request_review(repo, branch);
+    if !notify && !request_review {
+        return;
+    }
+
+    let files = match github.get_pull_request_files(repo.owner.login(), &repo.name, pull_request.number) {
+        Ok(f) => f.into_iter().map(|f| f.filename).collect(),
+        Err(e) => {
+            error!("Error getting files for PR #{}: {}", pull_request.number, e);
+            return;
+        }
+    };
+
+    let mut owners = codeowners.get(github, repo, branch).user_owners(&files);
+    owners.retain(|o| o != pull_request.user.login() && !already_requested.contains(o));
+    if let Some(ref requested) = pull_request.requested_reviewers {
+        owners.retain(|o| !requested.iter().any(|r| r.login() == o));
+    }
+    if owners.is_empty() {
+        return;
+    }
+
+    if request_review {
+        if let Err(e) = github.request_review(repo.owner.login(), &repo.name, pull_request.number, owners.clone()) {
+            error!("Error requesting review from code owners for PR #{}: {}", pull_request.number, e);
+        }
+    }
+
+    if notify {
+        // owners who were just asked to review count as review requests for their notification settings
+        let reason = if request_review { messenger::Reason::ReviewRequest } else { messenger::Reason::Participant };
+        let users = owners.iter().map(|o| github::User::new(o)).collect();
+        messenger.send_to_users(
+            "Pull Request touches files you own",
+            &pr_attachments(pull_request),
+            &users,
+            repo,
+            reason,
+        );
+    }
+}
+
+fn pr_attachments(pull_request: &github::PullRequest) -> Vec<SlackAttachment> {
+    vec![
+        SlackAttachmentBuilder::new("")
+            .title(format!("Pull Request #{}: \"{}\"", pull_request.number, pull_request.title))
+            .title_link(pull_request.html_url.as_str())
+            .build(),
+    ]
+}
+
+struct Runner {
+    config: Arc<Config>,
+    github_session: Arc<Session>,
+    messengers: MessengerFactory,
+    rotation: JsonStore<ReviewerRotation>,
+    codeowners: CodeOwnersCache,
+}
+
+pub fn new_worker(
+    config: Arc<Config>,
+    github_session: Arc<Session>,
+    messengers: MessengerFactory,
+) -> worker::Worker<ReviewerRequest> {
+    let rotation = JsonStore::new(config.host_data_file(github_session.github_host(), "reviewer_rotation.json"));
+    worker::Worker::new(
+        "reviewers",
+        Runner {
+            config: config,
+            github_session: github_session,
+            messengers: messengers,
+            rotation: rotation,
+            codeowners: CodeOwnersCache::new(),
+        },
+    )
+}
+
+impl worker::Runner<ReviewerRequest> for Runner {
+    fn handle(&self, req: ReviewerRequest) {
+        let messenger = self.messengers.for_event(None, Some(&req.pull_request));
+        let github = &*self.github_session;
+
+        let requested =
+            assign_reviewers(github, &self.config, &*messenger, &self.rotation, &req.repo, &req.pull_request);
+        notify_code_owners(
+            github,
+            &self.config,
+            &*messenger,
+            &self.codeowners,
+            &req.repo,
+            &req.pull_request,
+            &requested,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> Vec<String> {
+        vec!["joe".into(), "bob".into(), "sue".into()]
+    }
+
+    #[test]
+    fn test_pick_round_robin() {
+        let mut rotation = ReviewerRotation::new();
+        assert_eq!(vec!["joe"], rotation.pick("repo", &pool(), 1, "author"));
+        assert_eq!(vec!["bob"], rotation.pick("repo", &pool(), 1, "author"));
+        assert_eq!(vec!["sue"], rotation.pick("repo", &pool(), 1, "author"));
+        assert_eq!(vec!["joe"], rotation.pick("repo", &pool(), 1, "author"));
+
+        // other repos have their own rotation
+        assert_eq!(vec!["joe"], rotation.pick("other-repo", &pool(), 1, "author"));
+    }
+
+    #[test]
+    fn test_pick_two() {
+        let mut rotation = ReviewerRotation::new();
+        assert_eq!(vec!["joe", "bob"], rotation.pick("repo", &pool(), 2, "author"));
+        assert_eq!(vec!["sue", "joe"], rotation.pick("repo", &pool(), 2, "author"));
+    }
+
+    #[test]
+    fn test_pick_skips_author() {
+        let mut rotation = ReviewerRotation::new();
+        assert_eq!(vec!["bob"], rotation.pick("repo", &pool(), 1, "joe"));
+        assert_eq!(vec!["sue"], rotation.pick("repo", &pool(), 1, "joe"));
+        assert_eq!(vec!["bob"], rotation.pick("repo", &pool(), 1, "joe"));
+
+        // can't pick more than there are
+        let pool = vec!["joe".to_string(), "bob".to_string()];
+        assert_eq!(vec!["bob"], rotation.pick("small-repo", &pool, 2, "joe"));
+    }
+
+    #[test]
+    fn test_pick_except() {
+        let mut rotation = ReviewerRotation::new();
+        let skipped = vec!["author".to_string(), "bob".to_string()];
+        assert_eq!(vec!["joe"], rotation.pick_except("repo", &pool(), 1, &skipped));
+        assert_eq!(vec!["sue"], rotation.pick_except("repo", &pool(), 1, &skipped));
+        assert_eq!(vec!["joe", "sue"], rotation.pick_except("repo", &pool(), 2, &skipped));
+    }
+}
@@ -1,20 +1,21 @@
 use std::borrow::Borrow;
 use std::collections::HashMap;
-use std::fs;
-use std::io::Write;
 use std::sync::Arc;
 
-use hyper::StatusCode;
+use hyper::{Method, StatusCode};
 use hyper::header::ContentType;
 use hyper::server::{Request, Response};
 use serde_json;
 use tokio_core::reactor::Remote;
+use url::form_urlencoded;
 
 use config::{Config, JiraConfig};
+use errors::*;
 use jira;
-use repos::RepoHostMap;
+use repos::{RepoHostMap, RepoInfo};
+use server::github_handler::GithubHandlerState;
 use server::http::{FutureResponse, Handler, parse_json};
-use users::UserHostMap;
+use users::{UserHostMap, UserInfo};
 use version;
 
 pub struct GetUsers {
@@ -87,112 +88,195 @@ impl Handler for UpdateUsers {
     fn handle(&self, req: Request) -> FutureResponse {
         let config = self.config.clone();
 
-        parse_json(req, move |users: UserHostMap| {
-            let json = match serde_json::to_string_pretty(&users) {
-                Ok(j) => j,
-                Err(e) => {
-                    error!("Error serializing users: {}", e);
-                    return Response::new().with_status(StatusCode::InternalServerError).with_body(format!(
-                        "Error serializing JSON: {}",
-                        e
-                    ));
-                }
-            };
+        parse_json(req, move |users: UserHostMap| save_response(config.save_users(users)))
+    }
+}
 
-            let config_file = config.main.users_config_file.clone();
-            let config_file_tmp = config_file.clone() + ".tmp";
+impl Handler for UpdateRepos {
+    fn handle(&self, req: Request) -> FutureResponse {
+        let config = self.config.clone();
 
-            let mut file = match fs::File::create(&config_file_tmp) {
-                Ok(f) => f,
-                Err(e) => {
-                    error!("Error opening file: {}", e);
-                    return Response::new().with_status(StatusCode::InternalServerError).with_body(format!(
-                        "Error opening file: {}",
-                        e
-                    ));
-                }
-            };
+        parse_json(req, move |repos: RepoHostMap| save_response(config.save_repos(repos)))
+    }
+}
 
-            if let Err(e) = file.write_all(json.as_bytes()) {
-                error!("Error writing file: {}", e);
-                return Response::new().with_status(StatusCode::InternalServerError).with_body(format!(
-                    "Error writing file: {}",
-                    e
-                ));
-            }
+fn save_response(result: Result<()>) -> Response {
+    match result {
+        Ok(()) => Response::new(),
+        Err(e) => {
+            error!("Error saving config: {}", e);
+            Response::new().with_status(StatusCode::InternalServerError).with_body(format!(
+                "Error saving config: {}",
+                e
+            ))
+        }
+    }
+}
 
-            if let Err(e) = fs::rename(&config_file_tmp, &config_file) {
-                error!("Error renaming file: {}", e);
-                return Response::new().with_status(StatusCode::InternalServerError).with_body(format!(
-                    "Error renaming file: {}",
-                    e
-                ));
-            }
+// Manage single users and repos rather than replacing the whole list:
+//   GET    lists them
+//   POST   adds one ({"host": ..., "user": {...}} or {"host": ..., "repo": {...}}), replacing any with the same
+//          github login, or the same repo and branches. With main.public_url set, a repo's webhook gets set up
+//          too, and the answer says how that went ({"webhook": "...", "ok": true})
+//   DELETE removes them (?host=...&github=... or ?host=...&repo=...)
+pub struct AdminUsers {
+    config: Arc<Config>,
+}
 
-            if let Err(e) = config.reload_users_repos() {
-                error!("Error reloading config: {}", e);
-            }
+pub struct AdminRepos {
+    config: Arc<Config>,
+    states: Vec<Arc<GithubHandlerState>>,
+}
 
-            Response::new()
+impl AdminUsers {
+    pub fn new(config: Arc<Config>) -> Box<AdminUsers> {
+        Box::new(AdminUsers { config: config })
+    }
+}
+
+impl AdminRepos {
+    pub fn new(config: Arc<Config>, states: Vec<Arc<GithubHandlerState>>) -> Box<AdminRepos> {
+        Box::new(AdminRepos {
+            config: config,
+            states: states,
         })
     }
 }
 
-impl Handler for UpdateRepos {
-    fn handle(&self, req: Request) -> FutureResponse {
-        let config = self.config.clone();
+#[derive(Deserialize)]
+struct UserEntry {
+    host: String,
+    user: UserInfo,
+}
 
-        parse_json(req, move |repos: RepoHostMap| {
-            let json = match serde_json::to_string_pretty(&repos) {
-                Ok(j) => j,
-                Err(e) => {
-                    error!("Error serializing repos: {}", e);
-                    return Response::new().with_status(StatusCode::InternalServerError).with_body(format!(
-                        "Error serializing JSON: {}",
-                        e
-                    ));
-                }
-            };
+#[derive(Deserialize)]
+struct RepoEntry {
+    host: String,
+    repo: RepoInfo,
+}
 
-            let config_file = config.main.repos_config_file.clone();
-            let config_file_tmp = config_file.clone() + ".tmp";
+fn query_params(req: &Request) -> HashMap<String, String> {
+    match req.query() {
+        Some(q) => form_urlencoded::parse(q.as_bytes()).into_owned().collect(),
+        None => HashMap::new(),
+    }
+}
 
-            let mut file = match fs::File::create(&config_file_tmp) {
-                Ok(f) => f,
-                Err(e) => {
-                    error!("Error opening file: {}", e);
-                    return Response::new().with_status(StatusCode::InternalServerError).with_body(format!(
-                        "Error opening file: {}",
-                        e
-                    ));
-                }
-            };
+// "release/*,master" -> ["release/*", "master"]
+fn split_branches(branches: &str) -> Vec<String> {
+    branches.split(',').map(|b| b.trim()).filter(|b| !b.is_empty()).map(|b| b.to_string()).collect()
+}
 
-            if let Err(e) = file.write_all(json.as_bytes()) {
-                error!("Error writing file: {}", e);
-                return Response::new().with_status(StatusCode::InternalServerError).with_body(format!(
-                    "Error writing file: {}",
-                    e
-                ));
-            }
+// Entries are keyed on (repo, branches), like updates are, so that removing one branch's
+// entry leaves the repo's other ones alone. No branches means the entry for all of them.
+fn remove_repo(host_repos: &mut Vec<RepoInfo>, repo: &str, branches: &Vec<String>) {
+    host_repos.retain(|r| r.repo != repo || r.branches.as_ref().unwrap_or(&vec![]) != branches);
+}
 
-            if let Err(e) = fs::rename(&config_file_tmp, &config_file) {
-                error!("Error renaming file: {}", e);
-                return Response::new().with_status(StatusCode::InternalServerError).with_body(format!(
-                    "Error renaming file: {}",
-                    e
-                ));
-            }
+impl Handler for AdminUsers {
+    fn handle(&self, req: Request) -> FutureResponse {
+        let config = self.config.clone();
 
-            if let Err(e) = config.reload_users_repos() {
-                error!("Error reloading config: {}", e);
+        match req.method().clone() {
+            Method::Get => GetUsers::new(config).handle(req),
+            Method::Post => {
+                parse_json(req, move |entry: UserEntry| {
+                    let UserEntry { host, user } = entry;
+                    let mut users = config.users().host_map().clone();
+                    {
+                        let host_users = users.entry(host).or_insert(vec![]);
+                        host_users.retain(|u| u.github != user.github);
+                        host_users.push(user);
+                    }
+                    save_response(config.save_users(users))
+                })
+            }
+            Method::Delete => {
+                let params = query_params(&req);
+                let (host, github) = match (params.get("host"), params.get("github")) {
+                    (Some(h), Some(g)) => (h, g),
+                    _ => return self.respond_with(StatusCode::BadRequest, "host and github are required"),
+                };
+
+                let mut users = config.users().host_map().clone();
+                if let Some(host_users) = users.get_mut(host) {
+                    host_users.retain(|u| &u.github != github);
+                }
+                self.respond(save_response(config.save_users(users)))
             }
+            _ => self.respond(Response::new().with_status(StatusCode::MethodNotAllowed)),
+        }
+    }
+}
 
-            Response::new()
-        })
+impl Handler for AdminRepos {
+    fn handle(&self, req: Request) -> FutureResponse {
+        let config = self.config.clone();
+
+        match req.method().clone() {
+            Method::Get => GetRepos::new(config).handle(req),
+            Method::Post => {
+                let states = self.states.clone();
+                parse_json(req, move |entry: RepoEntry| {
+                    let RepoEntry { host, repo } = entry;
+                    let repo_name = repo.repo.clone();
+                    let mut repos = config.repos().host_map().clone();
+                    {
+                        let host_repos = repos.entry(host.clone()).or_insert(vec![]);
+                        host_repos.retain(|r| r.repo != repo.repo || r.branches != repo.branches);
+                        host_repos.push(repo);
+                    }
+                    if let Err(e) = config.save_repos(repos) {
+                        return save_response(Err(e));
+                    }
+                    match config.main.public_url {
+                        Some(ref u) if !u.is_empty() => webhook_response(&states, &host, &repo_name),
+                        _ => Response::new(),
+                    }
+                })
+            }
+            Method::Delete => {
+                let params = query_params(&req);
+                let (host, repo) = match (params.get("host"), params.get("repo")) {
+                    (Some(h), Some(r)) => (h, r),
+                    _ => return self.respond_with(StatusCode::BadRequest, "host and repo are required"),
+                };
+
+                let branches = params.get("branches").map(|b| split_branches(b)).unwrap_or(vec![]);
+
+                let mut repos = config.repos().host_map().clone();
+                if let Some(host_repos) = repos.get_mut(host) {
+                    remove_repo(host_repos, repo, &branches);
+                }
+                self.respond(save_response(config.save_repos(repos)))
+            }
+            _ => self.respond(Response::new().with_status(StatusCode::MethodNotAllowed)),
+        }
     }
 }
 
+// sets up the webhook of a newly added repo. orgs are left alone: their webhooks are set up on the org
+fn webhook_response(states: &Vec<Arc<GithubHandlerState>>, host: &str, repo: &str) -> Response {
+    let mut parts = repo.splitn(2, '/');
+    let (owner, name) = match (parts.next(), parts.next()) {
+        (Some(o), Some(n)) if !n.is_empty() => (o, n),
+        _ => return Response::new(),
+    };
+    let state = match states.iter().find(|s| s.github_session.github_host() == host) {
+        Some(s) => s,
+        None => return Response::new(),
+    };
+    let (ok, webhook) = match state.ensure_webhook(owner, name) {
+        Ok(setup) => (true, setup.describe(owner, name)),
+        Err(e) => {
+            error!("Error setting up the webhook of {}: {}", repo, e);
+            (false, format!("Error setting up the webhook of {}: {}", repo, e))
+        }
+    };
+    let body = json!({ "webhook": webhook, "ok": ok }).to_string();
+    Response::new().with_header(ContentType::json()).with_body(body)
+}
+
 pub struct MergeVersions {
     config: Arc<Config>,
     core_remote: Remote,
@@ -302,3 +386,213 @@ impl Handler for MergeVersions {
         })
     }
 }
+
+// Handles a github webhook delivery again, with whichever host handled it the first time.
+pub struct ReplayDelivery {
+    states: Vec<Arc<GithubHandlerState>>,
+    delivery_id: String,
+}
+
+impl ReplayDelivery {
+    pub fn new(states: Vec<Arc<GithubHandlerState>>, delivery_id: &str) -> Box<ReplayDelivery> {
+        Box::new(ReplayDelivery {
+            states: states,
+            delivery_id: delivery_id.to_string(),
+        })
+    }
+}
+
+impl Handler for ReplayDelivery {
+    fn handle(&self, _: Request) -> FutureResponse {
+        for state in &self.states {
+            match state.replay(&self.delivery_id) {
+                Ok(Some((status, resp))) => return self.respond_with(status, &resp),
+                Ok(None) => (),
+                Err(e) => {
+                    error!("Error replaying delivery {}: {}", self.delivery_id, e);
+                    return self.respond_with(StatusCode::BadRequest, &format!("{}", e));
+                }
+            };
+        }
+        self.respond_with(StatusCode::NotFound, &format!("No such delivery: {}", self.delivery_id))
+    }
+}
+
+// Deliveries that failed after they were queued, by host. POST /admin/replay/<id> handles one again.
+pub struct FailedDeliveries {
+    states: Vec<Arc<GithubHandlerState>>,
+}
+
+impl FailedDeliveries {
+    pub fn new(states: Vec<Arc<GithubHandlerState>>) -> Box<FailedDeliveries> {
+        Box::new(FailedDeliveries { states: states })
+    }
+}
+
+impl Handler for FailedDeliveries {
+    fn handle(&self, _: Request) -> FutureResponse {
+        let mut failed = HashMap::new();
+        for state in &self.states {
+            failed.insert(state.github_session.github_host().to_string(), state.failed_deliveries());
+        }
+        let failed = match serde_json::to_string(&failed) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("Error serializing failed deliveries: {}", e);
+                String::new()
+            }
+        };
+        self.respond(Response::new().with_header(ContentType::json()).with_body(failed))
+    }
+}
+
+// Slack messages that failed for good: GET lists them by host, and POST /admin/slack-resend/<id> sends one again.
+pub struct FailedSlackMessages {
+    states: Vec<Arc<GithubHandlerState>>,
+}
+
+impl FailedSlackMessages {
+    pub fn new(states: Vec<Arc<GithubHandlerState>>) -> Box<FailedSlackMessages> {
+        Box::new(FailedSlackMessages { states: states })
+    }
+}
+
+impl Handler for FailedSlackMessages {
+    fn handle(&self, _: Request) -> FutureResponse {
+        let mut failed = HashMap::new();
+        for state in &self.states {
+            failed.insert(state.github_session.github_host().to_string(), state.failed_slack_messages());
+        }
+        let failed = match serde_json::to_string(&failed) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("Error serializing failed slack messages: {}", e);
+                String::new()
+            }
+        };
+        self.respond(Response::new().with_header(ContentType::json()).with_body(failed))
+    }
+}
+
+pub struct ResendSlackMessage {
+    states: Vec<Arc<GithubHandlerState>>,
+    id: String,
+}
+
+impl ResendSlackMessage {
+    pub fn new(states: Vec<Arc<GithubHandlerState>>, id: &str) -> Box<ResendSlackMessage> {
+        Box::new(ResendSlackMessage {
+            states: states,
+            id: id.to_string(),
+        })
+    }
+}
+
+impl Handler for ResendSlackMessage {
+    fn handle(&self, _: Request) -> FutureResponse {
+        for state in &self.states {
+            match state.resend_slack_message(&self.id) {
+                Ok(true) => return self.respond_with(StatusCode::Ok, "re-sent"),
+                Ok(false) => (),
+                Err(e) => {
+                    error!("{}", e);
+                    return self.respond_with(StatusCode::InternalServerError, &format!("{}", e));
+                }
+            };
+        }
+        self.respond_with(StatusCode::NotFound, &format!("No such failed slack message: {}", self.id))
+    }
+}
+
+// Changes octobot made and messages it sent, newest first, by host. ?action= picks one kind of change
+// (e.g. merge_pull_request, slack_message), and ?limit= how many to list per host. The planned ones are
+// only those that dry run mode held back.
+pub struct AuditLogEntries {
+    states: Vec<Arc<GithubHandlerState>>,
+    planned: bool,
+}
+
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+
+impl AuditLogEntries {
+    pub fn new(states: Vec<Arc<GithubHandlerState>>) -> Box<AuditLogEntries> {
+        Box::new(AuditLogEntries {
+            states: states,
+            planned: false,
+        })
+    }
+
+    pub fn planned(states: Vec<Arc<GithubHandlerState>>) -> Box<AuditLogEntries> {
+        Box::new(AuditLogEntries {
+            states: states,
+            planned: true,
+        })
+    }
+}
+
+impl Handler for AuditLogEntries {
+    fn handle(&self, req: Request) -> FutureResponse {
+        let params = query_params(&req);
+        let limit = match params.get("limit").map(|l| l.parse::<usize>()) {
+            Some(Ok(l)) => l,
+            Some(Err(_)) => return self.respond_with(StatusCode::BadRequest, "limit must be a number"),
+            None => DEFAULT_AUDIT_LIMIT,
+        };
+        let action = params.get("action").map(|a| a.as_str());
+
+        let mut entries = HashMap::new();
+        for state in &self.states {
+            match state.audit_entries(action, self.planned, limit) {
+                Ok(e) => entries.insert(state.github_session.github_host().to_string(), e),
+                Err(e) => {
+                    error!("Error reading audit log: {}", e);
+                    return self.respond_with(StatusCode::InternalServerError, &format!("{}", e));
+                }
+            };
+        }
+        let entries = match serde_json::to_string(&entries) {
+            Ok(e) => e,
+            Err(e) => {
+                error!("Error serializing audit log: {}", e);
+                String::new()
+            }
+        };
+        self.respond(Response::new().with_header(ContentType::json()).with_body(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_branches() {
+        assert_eq!(vec!["release/*".to_string(), "master".to_string()], split_branches("release/*, master"));
+        assert_eq!(Vec::<String>::new(), split_branches(""));
+    }
+
+    #[test]
+    fn test_remove_repo_by_branches() {
+        let mut repos = vec![
+            RepoInfo::new("some-user/the-repo", "the-reviews"),
+            RepoInfo::new("some-user/the-repo", "release-reviews").with_branches(vec!["release/*".into()]),
+            RepoInfo::new("some-user/the-repo", "dev-reviews").with_branches(vec!["master".into(), "develop".into()]),
+            RepoInfo::new("some-user/other-repo", "other-reviews").with_branches(vec!["release/*".into()]),
+        ];
+
+        remove_repo(&mut repos, "some-user/the-repo", &vec!["release/*".into()]);
+        assert_eq!(
+            vec!["the-reviews", "dev-reviews", "other-reviews"],
+            repos.iter().map(|r| r.channel.as_str()).collect::<Vec<_>>()
+        );
+
+        remove_repo(&mut repos, "some-user/the-repo", &vec![]);
+        assert_eq!(
+            vec!["dev-reviews", "other-reviews"],
+            repos.iter().map(|r| r.channel.as_str()).collect::<Vec<_>>()
+        );
+
+        remove_repo(&mut repos, "some-user/the-repo", &split_branches("master,develop"));
+        assert_eq!(vec!["other-reviews"], repos.iter().map(|r| r.channel.as_str()).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use futures::Future;
+use futures::Stream;
+use hyper::StatusCode;
+use hyper::server::{Request, Response};
+
+use bitbucket;
+use bitbucket::api::BitbucketSession;
+use github;
+use server::github_handler::GithubHandlerState;
+use server::github_verify::GithubWebhookVerifier;
+use server::http::{FutureResponse, Handler};
+
+// Bitbucket Server webhooks: like gitlab's, their events are turned into github events and handled
+// against the bitbucket API. Pushes don't list their commits, so those are looked up first.
+pub struct BitbucketHandler {
+    state: Arc<GithubHandlerState>,
+    bitbucket: Arc<BitbucketSession>,
+}
+
+impl BitbucketHandler {
+    pub fn new(state: Arc<GithubHandlerState>, bitbucket: Arc<BitbucketSession>) -> Box<BitbucketHandler> {
+        Box::new(BitbucketHandler {
+            state: state,
+            bitbucket: bitbucket,
+        })
+    }
+}
+
+impl Handler for BitbucketHandler {
+    fn handle(&self, req: Request) -> FutureResponse {
+        let (host, verifier) = match self.state.config.bitbucket {
+            Some(ref c) => (c.host.clone(), GithubWebhookVerifier { secret: c.webhook_secret.clone() }),
+            None => return self.respond_with(StatusCode::NotFound, "Bitbucket is not configured"),
+        };
+
+        let event: String = match req.headers().get_raw("x-event-key") {
+            Some(ref h) if h.len() == 1 => String::from_utf8_lossy(&h[0]).into_owned(),
+            None | Some(..) => {
+                error!("Expected to find exactly one event header");
+                return self.respond_with(StatusCode::BadRequest, "Expected to find exactly one event header");
+            }
+        };
+
+        let state = self.state.clone();
+        let bitbucket = self.bitbucket.clone();
+        let headers = req.headers().clone();
+        Box::new(req.body().concat2().map(move |body| {
+            if !verifier.is_req_valid(&headers, &body) {
+                return Response::new().with_status(StatusCode::Forbidden).with_body("Invalid signature");
+            }
+
+            let events = match bitbucket::hook_events(&event, &body, &host) {
+                Ok(e) => e,
+                Err(e) => {
+                    error!("{}\n---\n{}\n---\n", e, String::from_utf8_lossy(&body));
+                    state.report_error(&format!("{}", e), Some(&String::from_utf8_lossy(&body)));
+                    return Response::new().with_status(StatusCode::BadRequest).with_body(format!("{}", e));
+                }
+            };
+            if events.is_empty() {
+                return Response::new().with_status(StatusCode::Ok).with_body(format!("Unhandled event: {}", event));
+            }
+
+            let responses: Vec<String> = events
+                .into_iter()
+                .map(|(github_event, mut data)| {
+                    if github_event == "push" {
+                        add_push_commits(&bitbucket, &mut data);
+                    }
+                    state.handle_hook(&github_event, data).1
+                })
+                .collect();
+            Response::new().with_status(StatusCode::Ok).with_body(responses.join(", "))
+        }))
+    }
+}
+
+fn add_push_commits(bitbucket: &BitbucketSession, data: &mut github::HookBody) {
+    if data.created() || data.deleted() {
+        return;
+    }
+    match bitbucket.get_push_commits(
+        data.repository.owner.login(),
+        &data.repository.name,
+        data.before(),
+        data.after(),
+    ) {
+        Ok(commits) => data.commits = Some(commits),
+        Err(e) => error!("Error looking up pushed commits: {}", e),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::BitbucketConfig;
+    use mock_http::MockHttp;
+
+    fn new_session(http: &MockHttp) -> BitbucketSession {
+        http.expect_get("/rest/api/1.0/users/octobot", json!({"name": "octobot"}));
+        let config = BitbucketConfig {
+            webhook_secret: String::new(),
+            host: "the-bitbucket-host".into(),
+            username: "octobot".into(),
+            api_token: "the-token".into(),
+            api_base: Some(http.url("/rest/api/1.0")),
+        };
+        BitbucketSession::new(http.core_remote(), &config).expect("bitbucket session")
+    }
+
+    fn push(before: &str, after: &str) -> github::HookBody {
+        let mut data = github::HookBody::new();
+        data.repository = github::Repo::parse("https://the-bitbucket-host/PRJ/some-repo").unwrap();
+        data.before = Some(before.into());
+        data.after = Some(after.into());
+        data
+    }
+
+    #[test]
+    fn test_add_push_commits() {
+        let http = MockHttp::new();
+        let bitbucket = new_session(&http);
+        http.expect_get(
+            "/rest/api/1.0/projects/PRJ/repos/some-repo/commits?since=abc123&until=def456&limit=100",
+            json!({"values": [{"id": "def456", "message": "The change"}]}),
+        );
+
+        let mut data = push("abc123", "def456");
+        add_push_commits(&bitbucket, &mut data);
+        let commits = data.commits.expect("commits");
+        assert_eq!(1, commits.len());
+        assert_eq!("def456", commits[0].id);
+        assert_eq!("The change", commits[0].message);
+    }
+
+    #[test]
+    fn test_add_push_commits_new_branch() {
+        let http = MockHttp::new();
+        let bitbucket = new_session(&http);
+
+        // the mock fails the test on any lookup
+        let mut data = push("0000000000000000000000000000000000000000", "def456");
+        data.created = Some(true);
+        add_push_commits(&bitbucket, &mut data);
+        assert!(data.commits.is_none());
+
+        let mut data = push("abc123", "0000000000000000000000000000000000000000");
+        data.deleted = Some(true);
+        add_push_commits(&bitbucket, &mut data);
+        assert!(data.commits.is_none());
+    }
+
+    #[test]
+    fn test_add_push_commits_lookup_error() {
+        let http = MockHttp::new();
+        let bitbucket = new_session(&http);
+        http.expect(
+            "GET",
+            "/rest/api/1.0/projects/PRJ/repos/some-repo/commits?since=abc123&until=def456&limit=100",
+            None,
+            404,
+            json!({"errors": [{"message": "Commit 'abc123' does not exist"}]}),
+        );
+
+        let mut data = push("abc123", "def456");
+        add_push_commits(&bitbucket, &mut data);
+        assert!(data.commits.is_none());
+    }
+}
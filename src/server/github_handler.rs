@@ -1,3 +1,7 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 use std::sync::{Arc, Mutex};
 
@@ -5,41 +9,152 @@ use futures::Future;
 use futures::Stream;
 use hyper::StatusCode;
 use hyper::server::{Request, Response};
-use regex::Regex;
 use serde_json;
 use tokio_core::reactor::Remote;
-
-use config::Config;
+use url::Url;
+use url::form_urlencoded;
+
+use audit::{self, AuditEntry, AuditLog, AuditedSession};
+use auto_merge::{self, AutoMergeRequest};
+use branch_updates::{self, BranchUpdateRequest};
+use changelog;
+use cluster::Cluster;
+use config::{Config, UnknownRepos};
+use conflicts::{self, ConflictRequest};
+use conventions;
+use deliveries::{Delivery, DeliveryStore, FailedDeliveries, FailedDelivery, HandledDeliveries};
+use error_reporting::{self, ErrorReporter};
+use errors::*;
+use event_export::{self, ExportedEvent};
+use event_rules::{self, RuleSubject};
 use force_push::{self, ForcePushRequest};
 use git_clone_manager::GitCloneManager;
 use github;
 use github::CommentLike;
 use jira;
-use messenger::{self, Messenger};
+use json_store::JsonStore;
+use merge_queue::{self, MergeQueueRequest};
+use messages::{self, Messages};
+use messenger::{Messenger, MessengerFactory, Reason};
+use pagerduty::{self, PagerDutySession};
+use path_labels;
+use pr_commands::{self, PrCommand};
+use pr_dependencies::{self, Dependents};
 use pr_merge::{self, PRMergeRequest};
+use pr_threads;
+use quiet_hours;
+use release_branches;
+use repo_hooks::{self, HookSetup};
 use repo_version::{self, RepoVersionRequest};
+use review_reminders::{self, PendingReviews, ReviewReminderRequest};
+use review_status;
+use reviewers::{self, ReviewerRequest};
 use server::github_verify::GithubWebhookVerifier;
 use server::http::{FutureResponse, Handler};
-use slack::{self, SlackAttachmentBuilder, SlackRequest};
+use server::slack_commands;
+use size_labels;
+use slack::{self, SlackAttachment, SlackAttachmentBuilder, SlackRequest};
+use slack_api::SlackApi;
+use slack_retries::{FailedMessage, SlackRetries};
+use slack_users::{self, SlackUserRequest, SlackUserResolver};
+use stale_prs;
+use teams::TeamCache;
+use tracing::{self, Tracer};
 use util;
-use worker::{WorkSender, Worker};
+use version::Version;
+use version_bumps::{self, VersionBumpRequest};
+use webhooks::{self, WebhookRequest};
+use weekly_metrics::{self, WeeklyMetrics};
+use worker::{self, WorkSender, Worker};
+use workflow_alerts::FailingWorkflows;
+
+// taking this label off a PR counts as taking it out of WIP, like taking "WIP:" off its title
+const WIP_LABEL: &'static str = "wip";
+// how much of a new PR's description goes in the channel
+const MAX_DESCRIPTION_CHARS: usize = 500;
+// the worst alerts are red. code scanning alerts without a security severity are "error", "warning", or "note"
+fn severity_color(severity: Option<&str>) -> Option<&'static str> {
+    match severity.map(|s| s.to_lowercase()) {
+        Some(ref s) if s == "critical" || s == "high" || s == "error" => Some("danger"),
+        Some(ref s) if s == "medium" || s == "moderate" || s == "warning" => Some("warning"),
+        _ => None,
+    }
+}
+
+// slack only shows so many attachments on a message
+const DEFAULT_MAX_PUSH_COMMITS: usize = 20;
 
 pub struct GithubHandlerState {
     pub config: Arc<Config>,
     pub github_session: Arc<github::api::Session>,
     pub jira_session: Option<Arc<jira::api::Session>>,
+    pagerduty_session: Option<Arc<pagerduty::Session>>,
     git_clone_manager: Arc<GitCloneManager>,
     pr_merge_worker: Worker<PRMergeRequest>,
     repo_version_worker: Worker<RepoVersionRequest>,
     force_push_worker: Worker<ForcePushRequest>,
+    merge_queue_worker: Worker<MergeQueueRequest>,
+    auto_merge_worker: Worker<AutoMergeRequest>,
+    reviewers_worker: Worker<ReviewerRequest>,
+    review_reminders_worker: Worker<ReviewReminderRequest>,
+    pending_reviews: Arc<JsonStore<PendingReviews>>,
+    dependents: Arc<JsonStore<Dependents>>,
+    failing_workflows: Arc<JsonStore<FailingWorkflows>>,
+    weekly_metrics: Arc<JsonStore<WeeklyMetrics>>,
+    unknown_repos: Arc<Mutex<HashSet<String>>>,
+    conflicts_worker: Worker<ConflictRequest>,
+    branch_updates_worker: Worker<BranchUpdateRequest>,
+    version_bumps_worker: Worker<VersionBumpRequest>,
+    // messages get to these through `messengers`, except for re-sent failed slack messages
     slack_worker: Worker<SlackRequest>,
-    recent_events: Mutex<Vec<String>>,
+    #[allow(dead_code)]
+    webhooks_worker: Worker<WebhookRequest>,
+    export_worker: Option<Worker<ExportedEvent>>,
+    tracer: Option<Arc<Tracer>>,
+    error_reporter: Option<Arc<ErrorReporter>>,
+    cluster: Option<Arc<Cluster>>,
+    messengers: MessengerFactory,
+    slack_retries: Arc<JsonStore<SlackRetries>>,
+    audit_log: Arc<AuditLog>,
+    slack_users: Option<(Arc<SlackUserResolver>, Worker<SlackUserRequest>)>,
+    webhook_secret: String,
+    handled_deliveries: HandledDeliveries,
+    deliveries: DeliveryStore,
+    failed_deliveries: FailedDeliveries,
+    teams: Arc<TeamCache>,
 }
 
+// Handles events from all of the github hosts we know about, one state per host.
 pub struct GithubHandler {
+    states: Vec<Arc<GithubHandlerState>>,
+    events: Arc<EventQueue>,
+}
+
+pub struct GithubEvent {
     state: Arc<GithubHandlerState>,
+    // the state whose list of handled deliveries the event goes on once it's handled
+    handled: Arc<GithubHandlerState>,
+    delivery_id: String,
+    event: String,
+    data: github::HookBody,
+}
+
+impl fmt::Debug for GithubEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} event, delivery {}: {:?}", self.event, self.delivery_id, self.data)
+    }
+}
+
+// Handles webhook events in the background, so that github gets its answer before it times out (after 10s)
+// however long slack and github take. Events for the same repo always go to the same worker, so they are
+// handled in the order they came in.
+pub struct EventQueue {
+    workers: Vec<Worker<GithubEvent>>,
 }
 
+const EVENT_WORKERS: usize = 4;
+const MAX_QUEUED_EVENTS: usize = 1000;
+
 pub struct GithubEventHandler {
     pub messenger: Box<Messenger>,
     pub config: Arc<Config>,
@@ -48,10 +163,24 @@ pub struct GithubEventHandler {
     pub action: String,
     pub github_session: Arc<github::api::Session>,
     pub jira_session: Option<Arc<jira::api::Session>>,
+    pub pagerduty_session: Option<Arc<pagerduty::Session>>,
     pub pr_merge: WorkSender<PRMergeRequest>,
     pub repo_version: WorkSender<RepoVersionRequest>,
     pub force_push: WorkSender<ForcePushRequest>,
+    pub merge_queue: WorkSender<MergeQueueRequest>,
+    pub auto_merge: WorkSender<AutoMergeRequest>,
+    pub reviewers: WorkSender<ReviewerRequest>,
+    pub review_reminders: WorkSender<ReviewReminderRequest>,
+    pub conflicts: WorkSender<ConflictRequest>,
+    pub branch_updates: WorkSender<BranchUpdateRequest>,
+    pub version_bumps: WorkSender<VersionBumpRequest>,
     pub git_clone_manager: Arc<GitCloneManager>,
+    pub teams: Arc<TeamCache>,
+    pub failing_workflows: Arc<JsonStore<FailingWorkflows>>,
+    pub weekly_metrics: Arc<JsonStore<WeeklyMetrics>>,
+    pub dependents: Arc<JsonStore<Dependents>>,
+    // the unconfigured repos the admin channel has been told about since octobot started
+    pub unknown_repos: Arc<Mutex<HashSet<String>>>,
 }
 
 const MAX_CONCURRENT_MERGES: usize = 20;
@@ -65,16 +194,87 @@ impl GithubHandlerState {
         jira_session: Option<Arc<jira::api::Session>>,
         core_remote: Remote,
     ) -> GithubHandlerState {
+        let export_worker = config.export.as_ref().and_then(|c| {
+            match event_export::new_sink(core_remote.clone(), &c.url) {
+                Ok(sink) => Some(event_export::new_worker(github_session.github_host(), sink)),
+                Err(e) => {
+                    error!("Not exporting events: {}", e);
+                    None
+                }
+            }
+        });
+
+        let tracer = config.tracing.as_ref().map(|c| Arc::new(Tracer::new(core_remote.clone(), c)));
+        let cluster = config.cluster.as_ref().and_then(|c| match Cluster::new(c) {
+            Ok(cluster) => Some(Arc::new(cluster)),
+            Err(e) => {
+                error!("Not sharing state with the cluster: {}", e);
+                None
+            }
+        });
+
+        // everything below makes its changes through this session, so that they all get audited
+        let audit_log = Arc::new(
+            AuditLog::new(config.host_data_file(github_session.github_host(), "audit.log"))
+                .with_export(export_worker.as_ref().map(|w| w.new_sender())),
+        );
+        let github_session: Arc<github::api::Session> =
+            Arc::new(AuditedSession::new(github_session, audit_log.clone(), config.clone()));
 
+        let webhook_secret = match config.github_config(github_session.github_host()) {
+            Some(c) => c.webhook_secret.clone(),
+            None => config.github.webhook_secret.clone(),
+        };
         let git_clone_manager = Arc::new(GitCloneManager::new(github_session.clone(), config.clone()));
 
-        let slack_worker = slack::new_worker(core_remote, &config.main.slack_webhook_url);
+        let slack_users = config.main.slack_api_token.as_ref().map(|token| {
+            let resolver = Arc::new(SlackUserResolver::new(
+                github_session.clone(),
+                Box::new(SlackApi::new(core_remote.clone(), token)),
+                config.host_data_file(github_session.github_host(), "slack_users.json"),
+            ));
+            (resolver.clone(), slack_users::new_worker(config.clone(), resolver))
+        });
+        let webhooks_worker = webhooks::new_worker(core_remote.clone());
+        let pagerduty_session = config.pagerduty.as_ref().map(|c| {
+            let session: Arc<pagerduty::Session> = Arc::new(PagerDutySession::new(core_remote.clone(), c));
+            session
+        });
+        let slack_retries =
+            Arc::new(JsonStore::new(config.host_data_file(github_session.github_host(), "slack_retries.json")));
+        let slack_api = match config.main.slack_api_token {
+            Some(ref token) if config.main.slack_web_api.unwrap_or(false) => {
+                Some(Arc::new(SlackApi::new(core_remote.clone(), token)))
+            }
+            _ => None,
+        };
+        let slack_worker = slack::new_worker(
+            core_remote,
+            &config.main.slack_webhook_url,
+            config.host_data_file(github_session.github_host(), "deferred_messages.json"),
+            config.host_data_file(github_session.github_host(), "digests.json"),
+            slack_retries.clone(),
+            audit_log.clone(),
+            config.main.slack_blocks.unwrap_or(false),
+            slack_api,
+            config.host_data_file(github_session.github_host(), "pr_threads.json"),
+            tracer.clone(),
+            config.host_data_file(github_session.github_host(), "slack_queue.json"),
+            cluster.clone(),
+            github_session.github_host(),
+        );
+        let messengers = MessengerFactory::new(config.clone(), slack_worker.new_sender())
+            .with_webhooks(webhooks_worker.new_sender());
         let pr_merge_worker = pr_merge::new_worker(
             MAX_CONCURRENT_MERGES,
             config.clone(),
             github_session.clone(),
             git_clone_manager.clone(),
-            slack_worker.new_sender(),
+            messengers.clone(),
+            audit_log.clone(),
+            tracer.clone(),
+            config.host_data_file(github_session.github_host(), "pr_merge_queue.json"),
+            config.host_data_file(github_session.github_host(), "running_merges.json"),
         );
         let repo_version_worker = repo_version::new_worker(
             MAX_CONCURRENT_VERSIONS,
@@ -82,29 +282,485 @@ impl GithubHandlerState {
             github_session.clone(),
             jira_session.clone(),
             git_clone_manager.clone(),
-            slack_worker.new_sender(),
+            messengers.clone(),
         );
         let force_push_worker = force_push::new_worker(
             MAX_CONCURRENT_FORCE_PUSH,
             config.clone(),
             github_session.clone(),
             git_clone_manager.clone(),
+            messengers.clone(),
+        );
+        let merge_queue_worker =
+            merge_queue::new_worker(config.clone(), github_session.clone(), messengers.clone());
+        let auto_merge_worker =
+            auto_merge::new_worker(config.clone(), github_session.clone(), messengers.clone());
+        let reviewers_worker =
+            reviewers::new_worker(config.clone(), github_session.clone(), messengers.clone());
+        let pending_reviews = Arc::new(
+            JsonStore::new(config.host_data_file(github_session.github_host(), "pending_reviews.json"))
+                .with_cluster(cluster.clone(), &format!("pending_reviews:{}", github_session.github_host())),
+        );
+        let review_reminders_worker = review_reminders::new_worker(pending_reviews.clone());
+        let dependents =
+            Arc::new(JsonStore::new(config.host_data_file(github_session.github_host(), "pr_dependents.json")));
+        let failing_workflows =
+            Arc::new(JsonStore::new(config.host_data_file(github_session.github_host(), "failing_workflows.json")));
+        let weekly_metrics = Arc::new(
+            JsonStore::new(config.host_data_file(github_session.github_host(), "weekly_metrics.json"))
+                .with_cluster(cluster.clone(), &format!("weekly_metrics:{}", github_session.github_host())),
+        );
+        let conflicted =
+            Arc::new(JsonStore::new(config.host_data_file(github_session.github_host(), "conflicted_prs.json")));
+        let conflicts_worker = conflicts::new_worker(github_session.clone(), messengers.clone(), conflicted.clone());
+        let branch_updates_worker = branch_updates::new_worker(
+            github_session.clone(),
+            git_clone_manager.clone(),
+            audit_log.clone(),
+            messengers.clone(),
+            conflicted,
+        );
+        let version_bumps_worker = version_bumps::new_worker(
+            config.clone(),
+            github_session.clone(),
+            git_clone_manager.clone(),
+            audit_log.clone(),
+            messengers.clone(),
         );
 
         GithubHandlerState {
             config: config.clone(),
             github_session: github_session.clone(),
             jira_session: jira_session.clone(),
+            pagerduty_session: pagerduty_session,
             git_clone_manager: git_clone_manager.clone(),
             pr_merge_worker: pr_merge_worker,
             repo_version_worker: repo_version_worker,
             force_push_worker: force_push_worker,
+            merge_queue_worker: merge_queue_worker,
+            auto_merge_worker: auto_merge_worker,
+            reviewers_worker: reviewers_worker,
+            review_reminders_worker: review_reminders_worker,
+            pending_reviews: pending_reviews,
+            dependents: dependents,
+            failing_workflows: failing_workflows,
+            weekly_metrics: weekly_metrics,
+            unknown_repos: Arc::new(Mutex::new(HashSet::new())),
+            conflicts_worker: conflicts_worker,
+            branch_updates_worker: branch_updates_worker,
+            version_bumps_worker: version_bumps_worker,
             slack_worker: slack_worker,
-            recent_events: Mutex::new(Vec::new()),
+            webhooks_worker: webhooks_worker,
+            export_worker: export_worker,
+            tracer: tracer,
+            error_reporter: None,
+            cluster: cluster.clone(),
+            messengers: messengers,
+            slack_retries: slack_retries,
+            audit_log: audit_log,
+            slack_users: slack_users,
+            webhook_secret: webhook_secret,
+            handled_deliveries: HandledDeliveries::new(
+                config.host_data_file(github_session.github_host(), "handled_deliveries.json"),
+            ).with_cluster(cluster),
+            deliveries: DeliveryStore::new(config.host_data_file(github_session.github_host(), "deliveries")),
+            failed_deliveries: FailedDeliveries::new(
+                config.host_data_file(github_session.github_host(), "failed_deliveries.json"),
+            ),
+            teams: Arc::new(TeamCache::new()),
+        }
+    }
+    // Starts the stale PR and review reminder checks and the weekly metrics for this host. They only look at this
+    // host's repos and pending reviews, so every host that should send reminders needs its own.
+    pub fn start_schedulers(&self) {
+        stale_prs::start(
+            self.config.clone(),
+            self.github_session.clone(),
+            self.messengers.clone(),
+            self.cluster.clone(),
+        );
+        review_reminders::start(
+            self.config.clone(),
+            self.github_session.github_host().to_string(),
+            self.pending_reviews.clone(),
+            self.messengers.clone(),
+            self.cluster.clone(),
+        );
+        weekly_metrics::start(
+            self.config.clone(),
+            self.github_session.github_host().to_string(),
+            self.weekly_metrics.clone(),
+            self.messengers.clone(),
+            self.cluster.clone(),
+        );
+    }
+
+    // points the repo's webhook at octobot, with this host's secret
+    pub fn ensure_webhook(&self, owner: &str, repo: &str) -> Result<HookSetup> {
+        let public_url = match self.config.main.public_url {
+            Some(ref u) if !u.is_empty() => u,
+            _ => return Err("public_url isn't set in [main]".into()),
+        };
+        let url = format!("{}/hooks/github", public_url.trim_right_matches('/'));
+        repo_hooks::ensure_hook(self.github_session.as_ref(), owner, repo, &url, &self.webhook_secret)
+    }
+
+    // deliveries that failed in the background, oldest first. they can be replayed
+    pub fn failed_deliveries(&self) -> Vec<FailedDelivery> {
+        self.failed_deliveries.list()
+    }
+
+    // slack messages that were given up on
+    pub fn failed_slack_messages(&self) -> Vec<FailedMessage> {
+        self.slack_retries.read(|r| r.failed().clone())
+    }
+
+    // Sends a failed slack message again. false if this state doesn't have that message.
+    pub fn resend_slack_message(&self, id: &str) -> Result<bool> {
+        let req = match self.slack_retries.write(|r| r.take_failed(id)) {
+            Some(r) => r,
+            None => return Ok(false),
+        };
+        self.slack_worker.new_sender().send(req).map_err(|e| {
+            Error::from(format!("Error re-sending slack message {}: {}", id, e))
+        })?;
+        Ok(true)
+    }
+
+    // the latest changes made on github and messages sent for this host, newest first. only the ones that
+    // dry run mode held back if `planned`.
+    pub fn audit_entries(&self, action: Option<&str>, planned: bool, limit: usize) -> Result<Vec<AuditEntry>> {
+        self.audit_log.entries(
+            |e| action.map_or(true, |a| e.action == a) && (!planned || e.outcome == audit::DRY_RUN),
+            limit,
+        )
+    }
+
+    // Backports a merged PR into the branch a backport label asks for, or a "backport-<version>" label for just the
+    // version, e.g. to try again after its backport failed. Returns the branch it's going into.
+    pub fn backport(&self, repo: &github::Repo, number: u32, label: &str) -> Result<String> {
+        let pull_request = self.github_session.get_pull_request(repo.owner.login(), &repo.name, number)?;
+        if !pull_request.is_merged() {
+            return Err(format!("{}#{} isn't merged", repo.full_name, number).into());
+        }
+
+        let repos = self.config.repos();
+        let base = &pull_request.base.ref_name;
+        let target_branch = match repos.backport_branch(repo, base, label).or_else(|| {
+            repos.backport_branch(repo, base, &format!("backport-{}", label))
+        }) {
+            Some(b) => b,
+            None => return Err(format!("Invalid backport label: {}", label).into()),
+        };
+
+        self.pr_merge_worker.new_sender().send(pr_merge::req(repo, &pull_request, &target_branch)).map_err(|e| {
+            Error::from(format!("Error sending merge request message: {}", e))
+        })?;
+        Ok(target_branch)
+    }
+
+    // Cuts a release branch for `version` on behalf of `login`, announcing it in the repo's release channel. If that
+    // fails they get a direct message about it instead.
+    pub fn cut_release(&self, repo: &github::Repo, version: &str, login: &str) {
+        let cut = release_branches::cut(
+            &*self.github_session,
+            &self.git_clone_manager,
+            &self.audit_log,
+            &self.config,
+            repo,
+            version,
+        );
+        let messenger = self.messengers.new_messenger().for_kind("release");
+        let messages = self.config.messages(repo, None);
+        let branch = match cut {
+            Ok(b) => b,
+            Err(e) => {
+                error!("Error cutting release branch {} of {}: {}", version, repo.full_name, e);
+                let attach = SlackAttachmentBuilder::new(&format!("{}", e)).color("danger").build();
+                let wording = messages::wording(
+                    messages::RELEASE_BRANCH_FAILED,
+                    vec![("version", version.to_string()), ("repo", repo.full_name.clone())],
+                );
+                let msg = wording(&messages);
+                let user = github::User::new(login);
+                let messenger = messenger.with_wording(wording);
+                messenger.send_to_users(&msg, &vec![attach], &vec![user], repo, Reason::Participant);
+                return;
+            }
+        };
+
+        let from =
+            messages.format(messages::RELEASE_BRANCH_FROM, &[("branch", &branch.from_branch), ("sha", &branch.sha)]);
+        let mut attachments = vec![
+            SlackAttachmentBuilder::new(&from)
+                .title(branch.name.as_str())
+                .title_link(format!("{}/tree/{}", repo.html_url, branch.name))
+                .build(),
+        ];
+        if let Some(ref e) = branch.protect_error {
+            let warning = messages.format(messages::RELEASE_BRANCH_UNPROTECTED, &[("error", &format!("{}", e))]);
+            attachments.push(SlackAttachmentBuilder::new(&warning).color("warning").build());
+        }
+        let msg = messages.format(messages::RELEASE_BRANCH_CUT, &[("user", login), ("branch", &branch.name)]);
+        match self.config.repos().release_channel(repo) {
+            Some(channel) => messenger.send_to_team_channel(&msg, &attachments, &channel, repo),
+            None => messenger.send_to_channel(&msg, &attachments, repo, None),
+        };
+
+        if !self.config.repos().version_bump_rules(repo, &branch.from_branch).is_empty() {
+            let req = version_bumps::req(repo, version, &format!("cutting {}", branch.name));
+            if let Err(e) = self.version_bumps_worker.new_sender().send(req) {
+                error!("Error sending version bump message: {}", e);
+            }
+        }
+    }
+
+    pub fn with_error_reporter(self, error_reporter: Option<Arc<ErrorReporter>>) -> GithubHandlerState {
+        let mut state = self;
+        state.error_reporter = error_reporter;
+        state
+    }
+
+    pub fn is_pr_merge_alive(&self) -> bool {
+        self.pr_merge_worker.is_alive()
+    }
+
+    // Handles a delivery that this state handled before again, e.g. after slack was down. None if it
+    // doesn't have that delivery (any more).
+    pub fn replay(&self, delivery_id: &str) -> Result<Option<EventResponse>> {
+        let delivery = match self.deliveries.load(delivery_id)? {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        let data: github::HookBody = serde_json::from_str(&delivery.body).map_err(|e| {
+            let msg = format!("Error parsing delivery {}: {}", delivery_id, e);
+            self.report_error(&msg, Some(&delivery.body));
+            Error::from(msg)
+        })?;
+
+        info!("Replaying delivery {} ({})", delivery_id, delivery.event);
+        let trigger = format!("{} (replay of delivery {})", delivery.event, delivery_id);
+        let (status, resp) = audit::with_trigger(&trigger, || self.handle_hook(&delivery.event, data));
+        if status.is_success() {
+            self.failed_deliveries.remove(delivery_id);
+        }
+        Ok(Some((status, resp)))
+    }
+
+    // Handles an event that has been verified to come from this state's host.
+    pub fn handle_hook(&self, event: &str, data: github::HookBody) -> EventResponse {
+        // the event goes with anything reported while handling it
+        let payload = self.error_reporter.as_ref().map(|_| serde_json::to_string(&data).unwrap_or(String::new()));
+        let attributes = [
+            ("event", event.to_string()),
+            ("action", data.action.clone().unwrap_or(String::new())),
+            ("repo", data.repository.full_name.clone()),
+        ];
+        error_reporting::with_payload(payload, || {
+            tracing::trace(self.tracer.as_ref().map(|t| &**t), "handle_hook", None, &attributes, || {
+                let (status, resp) = self.handle_traced_hook(event, data);
+                if status.is_server_error() {
+                    tracing::set_error(&resp);
+                    self.report_error(&format!("Error handling {} event: {}", event, resp), None);
+                }
+                (status, resp)
+            })
+        })
+    }
+
+    // Reports an unexpected error to sentry, if it's set up. `payload` is what it happened on, if it isn't the
+    // event being handled.
+    pub fn report_error(&self, message: &str, payload: Option<&str>) {
+        if let Some(ref reporter) = self.error_reporter {
+            reporter.report_error(message, payload);
+        }
+    }
+
+    fn handle_traced_hook(&self, event: &str, mut data: github::HookBody) -> EventResponse {
+        if let Some(ref worker) = self.export_worker {
+            if let Err(e) = worker.new_sender().send(ExportedEvent::event(event, &data)) {
+                error!("Error sending {} event to the event export: {}", event, e);
+            }
+        }
+
+        let action = match data.action {
+            Some(ref a) => a.clone(),
+            None => String::new(),
+        };
+
+        let github_session = self.github_session.clone();
+        let config = self.config.clone();
+
+        // Try to remap issues which are PRs as pull requests. This gives us access to PR information
+        // like reviewers which do not exist for issues.
+        if let Some(ref issue) = data.issue {
+            if data.pull_request.is_none() && issue.html_url.contains("/pull/") {
+                data.pull_request = match github_session.get_pull_request(
+                    &data.repository.owner.login(),
+                    &data.repository.name,
+                    issue.number,
+                ) {
+                    Ok(pr) => Some(pr),
+                    Err(e) => {
+                        error!("Error refetching issue #{} as pull request: {}", issue.number, e);
+                        None
+                    }
+                };
+            }
+        }
+
+        // refetch PR if present to get requested reviewers: they don't come on each webhook :cry:
+        let mut changed_pr = None;
+        if let Some(ref pull_request) = data.pull_request {
+            if pull_request.requested_reviewers.is_none() {
+                match github_session.get_pull_request(
+                    &data.repository.owner.login(),
+                    &data.repository.name,
+                    pull_request.number,
+                ) {
+                    Ok(pr) => changed_pr = Some(pr),
+                    Err(e) => error!("Error refetching pull request to get reviewers: {}", e),
+                };
+            }
+        }
+        if let Some(changed_pr) = changed_pr {
+            data.pull_request = Some(changed_pr);
+        }
+
+        // fetch PR's reviewers, they get removed from requested_reviewers after they submit a review. :cry:
+        if let Some(ref mut pull_request) = data.pull_request {
+            if pull_request.reviews.is_none() {
+                match github_session.get_pull_request_reviews(
+                    &data.repository.owner.login(),
+                    &data.repository.name,
+                    pull_request.number,
+                ) {
+                    Ok(r) => pull_request.reviews = Some(r),
+                    Err(e) => error!("Error refetching pull request reviews: {}", e),
+                };
+            }
+        }
+
+        if let Some((ref resolver, ref worker)) = self.slack_users {
+            let unresolved = resolver.unresolved(&involved_users(&data), quiet_hours::now());
+            if !unresolved.is_empty() {
+                let req = SlackUserRequest {
+                    repo: data.repository.clone(),
+                    users: unresolved,
+                };
+                if let Err(e) = worker.new_sender().send(req) {
+                    error!("Error sending to slack users worker: {}", e);
+                }
+            }
+        }
+
+        let messenger = self.messengers.for_event(data.action.as_ref().map(|a| a.as_str()), data.pull_request.as_ref());
+        let handler = GithubEventHandler {
+            event: event.to_string(),
+            data: data,
+            action: action,
+            config: config.clone(),
+            messenger: messenger,
+            github_session: github_session,
+            git_clone_manager: self.git_clone_manager.clone(),
+            teams: self.teams.clone(),
+            failing_workflows: self.failing_workflows.clone(),
+            weekly_metrics: self.weekly_metrics.clone(),
+            dependents: self.dependents.clone(),
+            unknown_repos: self.unknown_repos.clone(),
+            jira_session: self.jira_session.clone(),
+            pagerduty_session: self.pagerduty_session.clone(),
+            pr_merge: self.pr_merge_worker.new_sender(),
+            repo_version: self.repo_version_worker.new_sender(),
+            force_push: self.force_push_worker.new_sender(),
+            merge_queue: self.merge_queue_worker.new_sender(),
+            auto_merge: self.auto_merge_worker.new_sender(),
+            reviewers: self.reviewers_worker.new_sender(),
+            review_reminders: self.review_reminders_worker.new_sender(),
+            conflicts: self.conflicts_worker.new_sender(),
+            branch_updates: self.branch_updates_worker.new_sender(),
+            version_bumps: self.version_bumps_worker.new_sender(),
+        };
+
+        match handler.handle_event() {
+            Some(r) => r,
+            None => (StatusCode::Ok, format!("Unhandled event: {}", event)),
+        }
+    }
+}
+
+impl EventQueue {
+    pub fn new() -> EventQueue {
+        EventQueue {
+            workers: (0..EVENT_WORKERS)
+                .map(|i| Worker::bounded(&format!("github-events-{}", i), EventRunner, MAX_QUEUED_EVENTS))
+                .collect(),
+        }
+    }
+
+    fn send(&self, req: GithubEvent) -> Result<()> {
+        let mut hasher = DefaultHasher::new();
+        req.data.repository.full_name.hash(&mut hasher);
+        let worker = &self.workers[hasher.finish() as usize % self.workers.len()];
+        worker.new_sender().send(req).map_err(|_| "Event queue is full".into())
+    }
+}
+
+struct EventRunner;
+
+impl worker::Runner<GithubEvent> for EventRunner {
+    fn handle(&self, req: GithubEvent) {
+        let trigger = format!("{} (delivery {})", req.event, req.delivery_id);
+        let (state, event, data) = (req.state, req.event, req.data);
+        let (status, resp) = audit::with_trigger(&trigger, || state.handle_hook(&event, data));
+        let handled = &req.handled.handled_deliveries;
+        finish_delivery(handled, &state.failed_deliveries, &req.delivery_id, &event, status, &resp);
+    }
+}
+
+// Queues a delivery with `send`: github hears back 202 if it was queued, 200 if the same delivery is already queued
+// or was handled, and 503 if there's no room for it, so that it can be redelivered once there is.
+fn queue_delivery<F>(handled: &HandledDeliveries, id: &str, event: &str, send: F) -> Response
+where
+    F: FnOnce() -> Result<()>,
+{
+    if !handled.start(id) {
+        info!("Ignoring duplicate delivery: {}", id);
+        return Response::new().with_status(StatusCode::Ok).with_body("duplicate delivery [ignored]");
+    }
+    match send() {
+        Ok(()) => Response::new().with_status(StatusCode::Accepted).with_body(format!("{} [queued]", event)),
+        Err(e) => {
+            error!("Error queueing delivery {}: {}", id, e);
+            handled.abandon(id);
+            Response::new().with_status(StatusCode::ServiceUnavailable).with_body(format!("{}", e))
         }
     }
 }
 
+// Github was told the delivery was queued, so it won't redeliver it if it fails: failures are kept to be replayed.
+fn finish_delivery(
+    handled: &HandledDeliveries,
+    failed: &FailedDeliveries,
+    id: &str,
+    event: &str,
+    status: StatusCode,
+    resp: &str,
+) {
+    if status.is_success() {
+        handled.add(id);
+        return;
+    }
+    error!("Error handling delivery {} ({}): {}: {}", id, event, status, resp);
+    handled.abandon(id);
+    failed.add(FailedDelivery {
+        id: id.to_string(),
+        event: event.to_string(),
+        failed_at: quiet_hours::now(),
+        error: format!("{}: {}", status, resp),
+    });
+}
+
 impl GithubHandler {
     pub fn new(
         config: Arc<Config>,
@@ -113,14 +769,30 @@ impl GithubHandler {
         core_remote: Remote,
     ) -> Box<GithubHandler> {
         let state = GithubHandlerState::new(config, github_session, jira_session, core_remote);
-        GithubHandler::from_state(Arc::new(state))
+        state.start_schedulers();
+        GithubHandler::from_state(Arc::new(state), Arc::new(EventQueue::new()))
+    }
+
+    pub fn from_state(state: Arc<GithubHandlerState>, events: Arc<EventQueue>) -> Box<GithubHandler> {
+        GithubHandler::from_states(vec![state], events)
     }
 
-    pub fn from_state(state: Arc<GithubHandlerState>) -> Box<GithubHandler> {
-        Box::new(GithubHandler { state: state })
+    pub fn from_states(states: Vec<Arc<GithubHandlerState>>, events: Arc<EventQueue>) -> Box<GithubHandler> {
+        Box::new(GithubHandler {
+            states: states,
+            events: events,
+        })
     }
 }
 
+// Of the hosts whose secret the event was signed with, the one the repo is on. Falls back to the first
+// so that a lone host doesn't have to match the repo urls exactly.
+fn state_for_repo(signed: &Vec<Arc<GithubHandlerState>>, repo: &github::Repo) -> Arc<GithubHandlerState> {
+    let host = Url::parse(&repo.html_url).ok().and_then(|u| u.host_str().map(|h| h.to_string()));
+    let found = signed.iter().find(|s| host.as_ref().map_or(false, |h| h == s.github_session.github_host()));
+    found.unwrap_or(&signed[0]).clone()
+}
+
 impl Handler for GithubHandler {
     fn handle(&self, req: Request) -> FutureResponse {
         let event_id: String = match req.headers().get_raw("x-github-delivery") {
@@ -131,13 +803,12 @@ impl Handler for GithubHandler {
                 return self.respond_with(StatusCode::BadRequest, &msg);
             }
         };
-        {
-            let mut recent_events = self.state.recent_events.lock().unwrap();
-            if !util::check_unique_event(event_id.clone(), &mut *recent_events, 1000, 100) {
-                let msg = format!("Duplicate X-Github-Delivery header: {}", event_id);
-                error!("{}", msg);
-                return self.respond_with(StatusCode::BadRequest, &msg);
-            }
+        // github redelivers events when we're slow to answer: ack those without handling them twice, whether
+        // they were handled or are still queued. delivery ids are unique across hosts, so the first host's list
+        // will do for all of them.
+        if self.states[0].handled_deliveries.contains(&event_id) {
+            info!("Ignoring duplicate delivery: {}", event_id);
+            return self.respond_with(StatusCode::Ok, "duplicate delivery [ignored]");
         }
 
         let event: String = match req.headers().get_raw("x-github-event") {
@@ -149,119 +820,96 @@ impl Handler for GithubHandler {
         };
 
         let headers = req.headers().clone();
-        let github_session = self.state.github_session.clone();
-        let config = self.state.config.clone();
-        let git_clone_manager = self.state.git_clone_manager.clone();
-        let jira_session = self.state.jira_session.clone();
-        let pr_merge = self.state.pr_merge_worker.new_sender();
-        let repo_version = self.state.repo_version_worker.new_sender();
-        let force_push = self.state.force_push_worker.new_sender();
-        let slack = self.state.slack_worker.new_sender();
+        let states = self.states.clone();
+        let handled = self.states[0].clone();
+        let events = self.events.clone();
 
         Box::new(req.body().concat2().map(move |body| {
-            let verifier = GithubWebhookVerifier { secret: config.github.webhook_secret.clone() };
-            if !verifier.is_req_valid(&headers, &body) {
+            let signed: Vec<Arc<GithubHandlerState>> = states
+                .into_iter()
+                .filter(|s| {
+                    let verifier = GithubWebhookVerifier { secret: s.webhook_secret.clone() };
+                    verifier.is_req_valid(&headers, &body)
+                })
+                .collect();
+            if signed.is_empty() {
                 return Response::new().with_status(StatusCode::Forbidden).with_body("Invalid signature");
             }
 
-            let mut data: github::HookBody = match serde_json::from_slice(&body) {
+            let data: github::HookBody = match serde_json::from_slice(&body) {
                 Ok(h) => h,
                 Err(e) => {
                     error!("Error parsing json: {}\n---\n{}\n---\n", e, String::from_utf8_lossy(&body));
+                    signed[0].report_error(
+                        &format!("Error parsing {} event: {}", event, e),
+                        Some(&String::from_utf8_lossy(&body)),
+                    );
                     return Response::new().with_status(StatusCode::BadRequest).with_body(
                         format!("Error parsing JSON: {}", e),
                     );
                 }
             };
 
-            let action = match data.action {
-                Some(ref a) => a.clone(),
-                None => String::new(),
+            let state = state_for_repo(&signed, &data.repository);
+            let delivery = Delivery {
+                id: event_id.clone(),
+                event: event.clone(),
+                received_at: quiet_hours::now(),
+                body: String::from_utf8_lossy(&body).into_owned(),
             };
-
-            // Try to remap issues which are PRs as pull requests. This gives us access to PR information
-            // like reviewers which do not exist for issues.
-            if let Some(ref issue) = data.issue {
-                if data.pull_request.is_none() && issue.html_url.contains("/pull/") {
-                    data.pull_request = match github_session.get_pull_request(
-                        &data.repository.owner.login(),
-                        &data.repository.name,
-                        issue.number,
-                    ) {
-                        Ok(pr) => Some(pr),
-                        Err(e) => {
-                            error!("Error refetching issue #{} as pull request: {}", issue.number, e);
-                            None
-                        }
-                    };
-                }
-            }
-
-            // refetch PR if present to get requested reviewers: they don't come on each webhook :cry:
-            let mut changed_pr = None;
-            if let Some(ref pull_request) = data.pull_request {
-                if pull_request.requested_reviewers.is_none() {
-                    match github_session.get_pull_request(
-                        &data.repository.owner.login(),
-                        &data.repository.name,
-                        pull_request.number,
-                    ) {
-                        Ok(pr) => changed_pr = Some(pr),
-                        Err(e) => error!("Error refetching pull request to get reviewers: {}", e),
-                    };
-                }
-            }
-            if let Some(changed_pr) = changed_pr {
-                data.pull_request = Some(changed_pr);
-            }
-
-            // fetch PR's reviewers, they get removed from requested_reviewers after they submit a review. :cry:
-            if let Some(ref mut pull_request) = data.pull_request {
-                if pull_request.reviews.is_none() {
-                    match github_session.get_pull_request_reviews(
-                        &data.repository.owner.login(),
-                        &data.repository.name,
-                        pull_request.number,
-                    ) {
-                        Ok(r) => pull_request.reviews = Some(r),
-                        Err(e) => error!("Error refetching pull request reviews: {}", e),
-                    };
-                }
+            if let Err(e) = state.deliveries.save(delivery) {
+                error!("Error saving delivery: {}", e);
             }
 
-            let handler = GithubEventHandler {
+            let req = GithubEvent {
+                state: state,
+                handled: handled.clone(),
+                delivery_id: event_id.clone(),
                 event: event.clone(),
                 data: data,
-                action: action,
-                config: config.clone(),
-                messenger: messenger::new(config.clone(), slack),
-                github_session: github_session,
-                git_clone_manager: git_clone_manager,
-                jira_session: jira_session,
-                pr_merge: pr_merge,
-                repo_version: repo_version,
-                force_push: force_push,
             };
-
-            match handler.handle_event() {
-                Some((status, resp)) => Response::new().with_status(status).with_body(resp),
-                None => {
-                    Response::new().with_status(StatusCode::Ok).with_body(
-                        format!("Unhandled event: {}", event),
-                    )
-                }
-            }
+            queue_delivery(&handled.handled_deliveries, &event_id, &event, || events.send(req))
         }))
     }
 }
 
-type EventResponse = (StatusCode, String);
+// everyone we might send a message to (or about) for this event
+fn involved_users(data: &github::HookBody) -> Vec<github::User> {
+    let mut users = vec![data.sender.clone()];
+    if let Some(ref issue) = data.issue {
+        users.push(issue.user.clone());
+        users.extend(issue.assignees.iter().cloned());
+    }
+    if let Some(ref pull_request) = data.pull_request {
+        users.push(pull_request.user.clone());
+        users.extend(pull_request.assignees.iter().cloned());
+        if let Some(ref requested) = pull_request.requested_reviewers {
+            users.extend(requested.iter().cloned());
+        }
+        if let Some(ref reviews) = pull_request.reviews {
+            users.extend(reviews.iter().map(|r| r.user.clone()));
+        }
+    }
+    if let Some(ref comment) = data.comment {
+        users.push(comment.user.clone());
+    }
+    if let Some(ref review) = data.review {
+        users.push(review.user.clone());
+    }
+    users
+}
+
+pub type EventResponse = (StatusCode, String);
 
 impl GithubEventHandler {
     pub fn handle_event(&self) -> Option<EventResponse> {
         info!("Received event: {}", self.event);
         if self.event == "ping" {
             Some(self.handle_ping())
+        } else if let Some(resp) = self.check_unknown_repo() {
+            Some(resp)
+        } else if let Some(resp) = self.apply_rules() {
+            Some(resp)
         } else if self.event == "pull_request" {
             Some(self.handle_pr())
         } else if self.event == "pull_request_review_comment" {
@@ -274,15 +922,55 @@ impl GithubEventHandler {
             Some(self.handle_issue_comment())
         } else if self.event == "push" {
             Some(self.handle_push())
+        } else if self.event == "status" {
+            Some(self.handle_status())
+        } else if self.event == "create" {
+            Some(self.handle_create())
+        } else if self.event == "release" {
+            Some(self.handle_release())
+        } else if self.event == "deployment" {
+            Some(self.handle_deployment())
+        } else if self.event == "deployment_status" {
+            Some(self.handle_deployment_status())
+        } else if self.event == "issues" {
+            Some(self.handle_issues())
+        } else if self.event == "dependabot_alert" || self.event == "code_scanning_alert" ||
+                   self.event == "repository_vulnerability_alert"
+        {
+            Some(self.handle_security_alert())
+        } else if self.event == "workflow_run" {
+            Some(self.handle_workflow_run())
+        } else if self.event == "repository" {
+            Some(self.handle_repository())
         } else {
             None
         }
     }
 
+    fn messenger_for(&self, kind: &str) -> Box<Messenger> {
+        self.messenger.for_kind(kind)
+    }
+
+    // the base branch of the PR this event is about, if any
+    fn branch(&self) -> Option<&str> {
+        self.data.pull_request.as_ref().map(|pr| pr.base.ref_name.as_str())
+    }
+
+    // the repo's wording of the built-in messages
+    fn messages(&self) -> Messages {
+        self.config.messages(&self.data.repository, self.branch())
+    }
+
     fn slack_user_name(&self, user: &github::User) -> String {
         self.config.users().slack_user_name(user.login(), &self.data.repository)
     }
 
+    // octobot itself, and bots whose activity would only be noise
+    fn is_ignored_user(&self, user: &github::User) -> bool {
+        user.login() == self.github_session.user().login() ||
+            self.config.ignored_users().iter().any(|u| u == user.login())
+    }
+
     fn pull_request_commits(&self, pull_request: &github::PullRequestLike) -> Vec<github::Commit> {
         match self.github_session.get_pull_request_commits(
             &self.data.repository.owner.login(),
@@ -322,81 +1010,230 @@ impl GithubEventHandler {
         participants
     }
 
+    // the users @mentioned in body, including the members of @org/teams that don't have a channel of their own
+    fn mentioned_users(&self, body: &str) -> Vec<github::User> {
+        let mut mentioned: Vec<github::User> =
+            util::get_mentioned_usernames(body).into_iter().map(|u| github::User::new(u)).collect();
+
+        let repos = self.config.repos();
+        for team in util::get_mentioned_teams(body) {
+            if repos.team_channel(&self.data.repository, team).is_some() {
+                continue;
+            }
+            match self.teams.members(self.github_session.deref(), team) {
+                Ok(members) => {
+                    for member in members {
+                        if !mentioned.iter().any(|u| u.login() == member.login()) {
+                            mentioned.push(member);
+                        }
+                    }
+                }
+                Err(e) => error!("Error looking up members of {}: {}", team, e),
+            };
+        }
+        mentioned
+    }
+
+    // the message also goes to the channels of @org/teams mentioned in body
+    fn send_to_team_channels(&self, kind: &str, body: &str, msg: &str, attachments: &Vec<SlackAttachment>) {
+        let repos = self.config.repos();
+        for team in util::get_mentioned_teams(body) {
+            if let Some(channel) = repos.team_channel(&self.data.repository, team) {
+                self.messenger_for(kind).send_to_team_channel(msg, attachments, &channel, &self.data.repository);
+            }
+        }
+    }
+
     fn handle_ping(&self) -> EventResponse {
         (StatusCode::Ok, "ping".into())
     }
 
+    // answers events of repos that aren't in the repos config instead of handling them, if that's the policy
+    fn check_unknown_repo(&self) -> Option<EventResponse> {
+        let repo = &self.data.repository;
+        let policy = match self.config.main.unknown_repos {
+            Some(p) if p != UnknownRepos::Channel => p,
+            _ => return None,
+        };
+        if self.config.repos().is_configured(repo) {
+            return None;
+        }
+
+        info!("Dropping {} event for unconfigured repo {}", self.event, repo.full_name);
+        if policy == UnknownRepos::Notify && self.unknown_repos.lock().unwrap().insert(repo.html_url.clone()) {
+            let messages = self.messages();
+            let msg = messages.format(
+                messages::UNCONFIGURED_REPO,
+                &[("event", &self.event), ("repo", &util::make_link(&repo.html_url, &repo.full_name))],
+            );
+            let mut attachments = vec![];
+            if let Some(ref public_url) = self.config.main.public_url {
+                let query = form_urlencoded::Serializer::new(String::new())
+                    .append_pair("host", self.github_session.github_host())
+                    .append_pair("repo", &repo.full_name)
+                    .finish();
+                let url = format!("{}/#/repos?{}", public_url.trim_right_matches('/'), query);
+                let link = util::make_link(&url, &messages.format(messages::UNCONFIGURED_REPO_ADD, &[]));
+                attachments.push(SlackAttachmentBuilder::new(&link).build());
+            }
+            self.messenger.send_to_admins(&msg, &attachments);
+        }
+
+        Some((StatusCode::Ok, format!("Unconfigured repo: {}", repo.full_name)))
+    }
+
+    // does what the repo's rules say about the event, and answers it instead of handling it if a rule ignores it
+    fn apply_rules(&self) -> Option<EventResponse> {
+        // e.g. the labeled events for labels that rules added
+        if self.data.sender.login() == self.github_session.user().login() {
+            return None;
+        }
+        let subject = RuleSubject::new(&self.event, &self.action, &self.data);
+        let rules = self.config.repos().event_rules(&self.data.repository, subject.branch.as_ref().map(|b| b.as_str()));
+        let matching = rules.matching(&subject);
+        if matching.is_empty() {
+            return None;
+        }
+
+        if matching.iter().any(|r| r.ignore == Some(true)) {
+            info!("Ignoring {} event for {} by rule", self.event, self.data.repository.full_name);
+            return Some((StatusCode::Ok, format!("Ignored by rule: {}", self.event)));
+        }
+        let result = event_rules::apply(
+            self.github_session.deref(),
+            self.messenger.deref(),
+            &self.data,
+            &subject,
+            &matching,
+        );
+        if let Err(e) = result {
+            error!("Error applying rules to {} event: {}", self.event, e);
+        }
+        None
+    }
+
     fn handle_pr(&self) -> EventResponse {
         if let Some(ref pull_request) = self.data.pull_request {
-            let verb: Option<String>;
+            self.record_pr_metrics(pull_request);
+
+            // the name of the message to send (see messages), if any
+            let message: Option<&str>;
+            let mut values = vec![
+                ("number", pull_request.number.to_string()),
+                ("title", pull_request.title.clone()),
+                ("author", self.slack_user_name(&pull_request.user)),
+            ];
             let notify_channel_only;
-            if self.action == "opened" {
-                verb = Some(format!("opened by {}", self.slack_user_name(&pull_request.user)));
+            // drafts are only announced once they're ready for review
+            let opened = (self.action == "opened" && !pull_request.is_draft()) || self.action == "ready_for_review";
+            if opened {
+                message = Some(messages::PR_OPENED);
+                values.push(("user", self.slack_user_name(&pull_request.user)));
                 notify_channel_only = true;
             } else if self.action == "closed" {
                 if pull_request.merged == Some(true) {
-                    verb = Some("merged".to_string());
+                    message = Some(messages::PR_MERGED);
                 } else {
-                    verb = Some("closed".to_string());
+                    message = Some(messages::PR_CLOSED);
                 }
                 notify_channel_only = false;
             } else if self.action == "reopened" {
-                verb = Some("reopened".to_string());
+                message = Some(messages::PR_REOPENED);
                 notify_channel_only = true;
             } else if self.action == "assigned" {
                 let assignees_str = self.config
                     .users()
                     .slack_user_names(&pull_request.assignees, &self.data.repository)
                     .join(", ");
-                verb = Some(format!("assigned to {}", assignees_str));
+                message = Some(messages::PR_ASSIGNED);
+                values.push(("assignees", assignees_str));
                 notify_channel_only = false;
             } else if self.action == "unassigned" {
-                verb = Some("unassigned".to_string());
+                message = Some(messages::PR_UNASSIGNED);
                 notify_channel_only = true;
             } else if self.action == "review_requested" {
-                if let Some(ref reviewers) = pull_request.requested_reviewers {
+                if let Some(ref reviewer) = self.data.requested_reviewer {
+                    self.notify_review_request(pull_request, reviewer, true);
+                    message = None;
+                } else if let Some(ref reviewers) = pull_request.requested_reviewers {
                     let assignees_str =
                         self.config.users().slack_user_names(reviewers, &self.data.repository).join(", ");
-                    verb = Some(format!("submitted for review to {}", assignees_str));
+                    message = Some(messages::PR_SUBMITTED_FOR_REVIEW);
+                    values.push(("reviewers", assignees_str));
                 } else {
-                    verb = None;
+                    message = None;
                 }
                 notify_channel_only = false;
+            } else if self.is_wip_removed(pull_request) {
+                self.notify_ready_for_review(pull_request);
+                message = None;
+                notify_channel_only = true;
+            } else if self.action == "review_request_removed" {
+                if let Some(ref reviewer) = self.data.requested_reviewer {
+                    self.notify_review_request(pull_request, reviewer, false);
+                }
+                message = None;
+                notify_channel_only = true;
             } else {
-                verb = None;
+                message = None;
                 notify_channel_only = true;
             }
 
-            if let Some(ref verb) = verb {
+            let stats = if opened || self.action == "reopened" || self.action == "synchronize" {
+                self.update_path_labels(pull_request);
+                self.update_size_label(pull_request)
+            } else {
+                None
+            };
+
+            if let Some(message) = message {
                 let commits = self.pull_request_commits(&pull_request);
 
                 if !pull_request.is_wip() {
-                    let msg = format!("Pull Request {}", verb);
-                    let attachments =
-                        vec![SlackAttachmentBuilder::new("")
-                                               .title(format!("Pull Request #{}: \"{}\"",
-                                                              pull_request.number,
-                                                              pull_request.title.as_str()))
-                                               .title_link(pull_request.html_url.as_str())
-                                               .build()];
+                    let wording = messages::wording(message, values);
+                    let msg = wording(&self.messages());
+                    let mut attachments = pull_request_attachments(pull_request, &self.messages());
+                    if opened {
+                        let stats = stats.or_else(|| pull_request.diff_stats());
+                        attachments = vec![self.opened_attachment(pull_request, stats.as_ref())];
+                    } else if message == messages::PR_MERGED {
+                        attachments[0].text = self.merge_summary(pull_request);
+                    }
 
+                    let messenger = if message == messages::PR_MERGED {
+                        self.messenger_for("merge").with_reaction(pr_threads::MERGED)
+                    } else {
+                        self.messenger_for("pull_request")
+                    };
+                    let messenger = messenger.with_wording(wording);
                     if notify_channel_only {
-                        self.messenger.send_to_channel(&msg, &attachments, &self.data.repository);
+                        messenger.send_to_channel(&msg, &attachments, &self.data.repository, self.branch());
+                        if opened {
+                            self.notify_mentioned_in_description(pull_request, &msg, &attachments);
+                        }
                     } else {
-                        self.messenger.send_to_all(
+                        messenger.send_to_all(
                             &msg,
                             &attachments,
                             &pull_request.user,
                             &self.data.sender,
                             &self.data.repository,
+                            self.branch(),
                             &self.all_participants_with_commits(&pull_request, &commits),
+                            &vec![],
                         );
 
                     }
                 }
 
+                if opened && !pull_request.is_wip() {
+                    if let Err(e) = self.reviewers.send(reviewers::req(&self.data.repository, pull_request)) {
+                        error!("Error sending reviewers message: {}", e);
+                    }
+                }
+
                 // Mark JIRAs in review for PR open
-                if self.action == "opened" {
+                if opened {
                     if let Some(ref jira_config) = self.config.jira {
                         if let Some(ref jira_session) = self.jira_session {
                             let jira_projects =
@@ -414,91 +1251,297 @@ impl GithubEventHandler {
                 }
             }
 
-            let release_branch_prefix = self.config.repos().release_branch_prefix(
-                &self.data.repository,
-                &pull_request.base.ref_name,
-            );
             if self.action == "labeled" {
                 if let Some(ref label) = self.data.label {
-                    self.merge_pull_request(pull_request, label, &release_branch_prefix);
+                    self.merge_pull_request(pull_request, label);
+
+                    let auto_merge_label =
+                        self.config.repos().auto_merge_label(&self.data.repository, &pull_request.base.ref_name);
+                    if Some(label.name.clone()) == auto_merge_label {
+                        self.send_auto_merge(auto_merge::pr_req(&self.data.repository, pull_request));
+                    }
                 }
-            } else if verb == Some("merged".to_string()) {
-                self.merge_pull_request_all_labels(pull_request, &release_branch_prefix);
+            } else if message == Some(messages::PR_MERGED) {
+                self.merge_pull_request_all_labels(pull_request);
+            }
+
+            if opened || self.action == "reopened" || self.action == "synchronize" {
+                self.update_review_status(pull_request);
             }
+            if opened || self.action == "reopened" || self.action == "synchronize" || self.action == "edited" {
+                self.check_conventions(pull_request);
+            }
+            if opened || self.action == "reopened" || self.action == "synchronize" || self.action == "labeled" ||
+                self.action == "unlabeled"
+            {
+                self.check_changelog(pull_request);
+            }
+            if self.action == "opened" || self.action == "ready_for_review" || self.action == "reopened" ||
+                self.action == "synchronize" || self.action == "edited" || self.action == "closed"
+            {
+                self.update_dependencies(pull_request);
+            }
+            self.update_merge_queue(pull_request);
+            self.update_keep_updated(pull_request);
+            self.update_review_reminders(pull_request);
         }
 
         (StatusCode::Ok, "pr".into())
     }
 
-    fn handle_pr_review_comment(&self) -> EventResponse {
-        if let Some(ref pull_request) = self.data.pull_request {
-            if let Some(ref comment) = self.data.comment {
-                if self.action == "created" {
-                    self.do_pull_request_comment(&pull_request, &comment)
-                }
-
+    // whether this event took a PR out of WIP, by its title or by its label
+    fn is_wip_removed(&self, pull_request: &github::PullRequest) -> bool {
+        if pull_request.is_wip() || !pull_request.is_open() {
+            return false;
+        }
+        match self.action.as_str() {
+            "edited" => {
+                let old_title = self.data.changes.as_ref().and_then(|c| c.title.as_ref());
+                old_title.map_or(false, |t| t.from.to_lowercase().starts_with("wip:"))
             }
+            "unlabeled" => self.data.label.as_ref().map_or(false, |l| l.name.to_lowercase() == WIP_LABEL),
+            _ => false,
         }
-
-        (StatusCode::Ok, "pr_review_comment".into())
     }
 
-    fn handle_pr_review(&self) -> EventResponse {
-        if let Some(ref pull_request) = self.data.pull_request {
-            if let Some(ref review) = self.data.review {
-                if self.action == "submitted" {
+    fn notify_ready_for_review(&self, pull_request: &github::PullRequest) {
+        let messages = self.messages();
+        let wording = messages::wording(messages::PR_READY_FOR_REVIEW, vec![]);
+        let msg = wording(&messages);
+        let attachments = pull_request_attachments(pull_request, &messages);
+        let messenger = self.messenger_for("pull_request").with_wording(wording);
+        messenger.send_to_channel(&msg, &attachments, &self.data.repository, self.branch());
+        let assignees = &pull_request.assignees;
+        messenger.send_to_users(&msg, &attachments, assignees, &self.data.repository, Reason::Participant);
+
+        // WIP PRs don't get reviewers when they're opened
+        if let Err(e) = self.reviewers.send(reviewers::req(&self.data.repository, pull_request)) {
+            error!("Error sending reviewers message: {}", e);
+        }
+    }
 
-                    // just a comment. should just be handled by regular comment handler.
-                    if review.state == "commented" {
-                        self.do_pull_request_comment(&pull_request, &review);
-                        return (StatusCode::Ok, "pr_review [comment]".into());
-                    }
+    // the channel hears about new review requests, but only the reviewer gets a direct message
+    fn notify_review_request(&self, pull_request: &github::PullRequest, reviewer: &github::User, requested: bool) {
+        if pull_request.is_wip() {
+            return;
+        }
 
-                    let action_msg;
+        let messages = self.messages();
+        let attachments = pull_request_attachments(pull_request, &messages);
+        let messenger = self.messenger_for("pull_request");
+        let sender = self.slack_user_name(&self.data.sender);
+        let wording = if requested {
+            let reviewers = self.slack_user_name(reviewer);
+            let msg = messages.format(messages::PR_SUBMITTED_FOR_REVIEW, &[("reviewers", &reviewers)]);
+            messenger.send_to_channel(&msg, &attachments, &self.data.repository, self.branch());
+            // the reviewers worker already let the ones octobot picked know
+            if self.data.sender.login() == self.github_session.user().login() {
+                return;
+            }
+            messages::wording(messages::REVIEW_REQUESTED, vec![("user", sender)])
+        } else {
+            messages::wording(messages::REVIEW_REQUEST_REMOVED, vec![("user", sender)])
+        };
+
+        let msg = wording(&messages);
+        let reviewers = vec![reviewer.clone()];
+        messenger.with_wording(wording).send_to_users(
+            &msg,
+            &attachments,
+            &reviewers,
+            &self.data.repository,
+            Reason::ReviewRequest,
+        );
+    }
+
+    // enough about a new PR to triage it from slack: the start of its description, its branches, and its size
+    fn opened_attachment(
+        &self,
+        pull_request: &github::PullRequest,
+        stats: Option<&github::DiffStats>,
+    ) -> SlackAttachment {
+        let body = pull_request.body.as_ref().map_or("", |b| b.as_str());
+        let mut attachment = SlackAttachmentBuilder::new(
+            &slack::comment_excerpt(body, &pull_request.html_url, MAX_DESCRIPTION_CHARS),
+        );
+        let messages = self.messages();
+        attachment
+            .title(pull_request_title(pull_request, &messages))
+            .title_link(pull_request.html_url.as_str())
+            .field(
+                messages.format(messages::FIELD_BRANCH, &[]),
+                format!("{} ← {}", pull_request.base.ref_name, pull_request.head.ref_name),
+            )
+            .field(messages.format(messages::FIELD_AUTHOR, &[]), self.slack_user_name(&pull_request.user));
+        if let Some(stats) = stats {
+            attachment.field(messages.format(messages::FIELD_CHANGES, &[]), stats.describe());
+        }
+        if let Some(ref labels) = pull_request.labels {
+            if !labels.is_empty() {
+                let names: Vec<&str> = labels.iter().map(|l| l.name.as_str()).collect();
+                attachment.field(messages.format(messages::FIELD_LABELS, &[]), names.join(", "));
+            }
+        }
+        attachment.build()
+    }
+
+    // who approved the PR and whether CI passed, as of the merge. Empty if the reviews can't be looked up.
+    fn merge_summary(&self, pull_request: &github::PullRequest) -> String {
+        let owner = self.data.repository.owner.login();
+        let repo = &self.data.repository.name;
+        let reviews = match self.github_session.get_pull_request_reviews(owner, repo, pull_request.number) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Error getting reviews of merged PR #{}: {}", pull_request.number, e);
+                return String::new();
+            }
+        };
+        let combined = match self.github_session.get_combined_status(owner, repo, &pull_request.head.sha) {
+            Ok(c) => Some(c),
+            Err(e) => {
+                error!("Error getting CI status of merged PR #{}: {}", pull_request.number, e);
+                None
+            }
+        };
+        review_status::merge_summary(pull_request, &reviews, combined.as_ref())
+    }
+
+    // the users @mentioned in a new PR's description, who aren't told about it otherwise
+    fn notify_mentioned_in_description(
+        &self,
+        pull_request: &github::PullRequest,
+        msg: &str,
+        attachments: &Vec<SlackAttachment>,
+    ) {
+        let body = pull_request.body.as_ref().map_or("", |b| b.as_str());
+        let mentioned: Vec<github::User> = self.mentioned_users(body)
+            .into_iter()
+            .filter(|u| u.login() != pull_request.user.login())
+            .collect();
+        if !mentioned.is_empty() {
+            self.messenger_for("pull_request").send_to_users(
+                msg,
+                attachments,
+                &mentioned,
+                &self.data.repository,
+                Reason::Mention,
+            );
+        }
+        self.send_to_team_channels("pull_request", body, msg, attachments);
+    }
+
+    fn handle_pr_review_comment(&self) -> EventResponse {
+        if let Some(ref pull_request) = self.data.pull_request {
+            if let Some(ref comment) = self.data.comment {
+                if self.action == "created" {
+                    if comment.pull_request_review_id.is_some() {
+                        // announced along with the review they're part of
+                        return (StatusCode::Ok, "pr_review_comment [in review]".into());
+                    }
+                    self.do_pull_request_comment(&pull_request, &comment, &[])
+                } else if self.action == "edited" || self.action == "deleted" {
+                    self.do_comment_change(
+                        &pull_request,
+                        &comment,
+                        messages::COMMENT_EDITED,
+                        messages::COMMENT_DELETED,
+                    );
+                }
+
+            }
+        }
+
+        (StatusCode::Ok, "pr_review_comment".into())
+    }
+
+    fn handle_pr_review(&self) -> EventResponse {
+        if let Some(ref pull_request) = self.data.pull_request {
+            if let Some(ref review) = self.data.review {
+                if self.action == "submitted" {
+
+                    // just a comment. should just be handled by regular comment handler.
+                    if review.state == "commented" {
+                        if !self.is_ignored_user(&review.user) {
+                            self.record_review_metrics(pull_request, review);
+                        }
+                        let inline = self.review_comments(pull_request, review);
+                        self.do_pull_request_comment(&pull_request, &review, &inline);
+                        return (StatusCode::Ok, "pr_review [comment]".into());
+                    }
+
+                    let review_msg;
                     let state_msg;
                     let color;
+                    let reaction;
                     if review.state == "changes_requested" {
-                        action_msg = "requested changes to";
-                        state_msg = "Changes Requested";
+                        review_msg = messages::REVIEW_CHANGES_REQUESTED_BY;
+                        state_msg = messages::REVIEW_CHANGES_REQUESTED;
                         color = "danger";
+                        reaction = pr_threads::CHANGES_REQUESTED;
 
                     } else if review.state == "approved" {
-                        action_msg = "approved";
-                        state_msg = "Approved";
+                        review_msg = messages::REVIEW_APPROVED_BY;
+                        state_msg = messages::REVIEW_APPROVED;
                         color = "good";
+                        reaction = pr_threads::APPROVED;
 
                     } else {
                         return (StatusCode::Ok, "pr_review [ignored]".into());
                     }
 
-                    let msg = format!(
-                        "{} {} PR \"{}\"",
-                        self.slack_user_name(&review.user),
-                        action_msg,
-                        util::make_link(pull_request.html_url.as_str(), pull_request.title.as_str())
+                    let wording = messages::wording(
+                        review_msg,
+                        vec![
+                            ("user", self.slack_user_name(&review.user)),
+                            ("link", util::make_link(pull_request.html_url.as_str(), pull_request.title.as_str())),
+                        ],
                     );
-
-                    let attachments = vec![
-                        SlackAttachmentBuilder::new(review.body())
-                            .title(format!("Review: {}", state_msg))
+                    let messages = self.messages();
+                    let msg = wording(&messages);
+                    let state = messages.format(state_msg, &[]);
+                    let mut attachments = vec![
+                        SlackAttachmentBuilder::new(&slack::comment_text(review.body(), &review.html_url))
+                            .title(messages.format(messages::REVIEW_TITLE, &[("state", &state)]))
                             .title_link(review.html_url.as_str())
                             .color(color)
                             .build(),
                     ];
 
-                    let mut participants = self.all_participants(&pull_request);
-                    for username in util::get_mentioned_usernames(review.body()) {
-                        participants.push(github::User::new(username))
+                    if self.is_ignored_user(&review.user) {
+                        info!("Ignoring review from {}", review.user.login());
+                    } else {
+                        self.record_review_metrics(pull_request, review);
+                        let inline = self.review_comments(pull_request, review);
+                        if !inline.is_empty() {
+                            let title = inline_comments_title(inline.len(), &messages);
+                            attachments.push(inline_comments_attachment(&inline, title, &review.html_url, &messages));
+                        }
+                        let bodies = comment_bodies(review.body(), &inline);
+                        let mentioned = self.mentioned_users(&bodies);
+
+                        let messenger = self.messenger_for("review").with_reaction(reaction).with_wording(wording);
+                        messenger.send_to_all(
+                            &msg,
+                            &attachments,
+                            &pull_request.user,
+                            &self.data.sender,
+                            &self.data.repository,
+                            self.branch(),
+                            &self.all_participants(&pull_request),
+                            &mentioned,
+                        );
+                        self.send_to_team_channels("review", &bodies, &msg, &attachments);
                     }
 
-                    self.messenger.send_to_all(
-                        &msg,
-                        &attachments,
-                        &pull_request.user,
-                        &self.data.sender,
-                        &self.data.repository,
-                        &participants,
-                    );
+                    if review.state == "approved" {
+                        self.send_auto_merge(auto_merge::pr_req(&self.data.repository, pull_request));
+                    }
+                    self.update_review_status(pull_request);
+                    self.update_review_reminders(pull_request);
+                } else if self.action == "dismissed" {
+                    self.update_review_status(pull_request);
+                } else if self.action == "edited" {
+                    self.do_comment_change(&pull_request, &review, messages::REVIEW_EDITED, messages::REVIEW_DELETED);
                 }
             }
         }
@@ -506,45 +1549,160 @@ impl GithubEventHandler {
         (StatusCode::Ok, "pr_review".into())
     }
 
-    fn do_pull_request_comment(&self, pull_request: &github::PullRequestLike, comment: &github::CommentLike) {
-        if comment.body().trim().len() == 0 {
+    // opened and merged PRs for the repo's weekly metrics, if it has them
+    fn record_pr_metrics(&self, pull_request: &github::PullRequest) {
+        let repo = &self.data.repository;
+        if !self.config.repos().weekly_metrics(repo) {
             return;
         }
+        if (self.action == "opened" && !pull_request.is_draft()) || self.action == "ready_for_review" {
+            self.weekly_metrics.write(|m| m.opened(repo));
+        } else if self.action == "closed" {
+            let open_secs = self.open_secs(pull_request);
+            self.weekly_metrics.write(|m| m.closed(repo, &pull_request.html_url, pull_request.is_merged(), open_secs));
+        }
+    }
 
-        if comment.user().login() == self.github_session.user().login() {
-            info!("Ignoring message from octobot ({}): {}", self.github_session.user().login(), comment.body());
+    fn record_review_metrics(&self, pull_request: &github::PullRequest, review: &github::Review) {
+        let repo = &self.data.repository;
+        // replies to reviews of their own PRs aren't reviews
+        if !self.config.repos().weekly_metrics(repo) || review.user.login() == pull_request.user.login() {
             return;
         }
+        let open_secs = self.open_secs(pull_request);
+        self.weekly_metrics.write(|m| m.reviewed(repo, &pull_request.html_url, review.user.login(), open_secs));
+    }
 
-        let msg = format!("Comment on \"{}\"", util::make_link(pull_request.html_url(), pull_request.title()));
+    fn open_secs(&self, pull_request: &github::PullRequest) -> Option<i64> {
+        pull_request.created_at.as_ref().and_then(|t| weekly_metrics::secs_since(t, quiet_hours::now()))
+    }
 
-        let attachments = vec![
-            SlackAttachmentBuilder::new(comment.body().trim())
-                .title(format!("{} said:", self.slack_user_name(comment.user())))
-                .title_link(comment.html_url())
-                .build(),
-        ];
+    // the inline comments that were submitted with a review, which are announced with it rather than one by one
+    fn review_comments(&self, pull_request: &github::PullRequest, review: &github::Review) -> Vec<github::Comment> {
+        let review_id = match review.id {
+            Some(id) => id,
+            None => return vec![],
+        };
+        let repo = &self.data.repository;
+        match self.github_session.get_review_comments(repo.owner.login(), &repo.name, pull_request.number, review_id) {
+            Ok(comments) => comments,
+            Err(e) => {
+                error!("Error fetching comments of review {} on PR #{}: {}", review_id, pull_request.number, e);
+                vec![]
+            }
+        }
+    }
 
-        let mut participants = self.all_participants(pull_request);
-        for username in util::get_mentioned_usernames(comment.body()) {
-            participants.push(github::User::new(username))
+    fn do_pull_request_comment(
+        &self,
+        pull_request: &github::PullRequestLike,
+        comment: &github::CommentLike,
+        inline: &[github::Comment],
+    ) {
+        if comment.body().trim().len() == 0 && inline.is_empty() {
+            return;
         }
 
-        self.messenger.send_to_all(
+        if self.is_ignored_user(comment.user()) {
+            info!("Ignoring message from {}: {}", comment.user().login(), comment.body());
+            return;
+        }
+
+        let messages = self.messages();
+        let wording = messages::wording(
+            messages::COMMENT_ON_PR,
+            vec![("link", util::make_link(pull_request.html_url(), pull_request.title()))],
+        );
+        let msg = wording(&messages);
+        let user = self.slack_user_name(comment.user());
+
+        let mut attachments = vec![];
+        if comment.body().trim().len() > 0 {
+            let text = with_code_context(comment, slack::comment_text(comment.body(), comment.html_url()));
+            attachments.push(
+                SlackAttachmentBuilder::new(&text)
+                    .title(messages.format(messages::COMMENT_SAID, &[("user", &user)]))
+                    .title_link(comment.html_url())
+                    .build(),
+            );
+        }
+        if !inline.is_empty() {
+            let inline_title = inline_comments_title(inline.len(), &messages);
+            let title = if attachments.is_empty() {
+                messages.format(messages::COMMENT_LEFT, &[("user", &user), ("inline", &inline_title)])
+            } else {
+                inline_title
+            };
+            attachments.push(inline_comments_attachment(inline, title, comment.html_url(), &messages));
+        }
+
+        let bodies = comment_bodies(comment.body(), inline);
+        let mentioned = self.mentioned_users(&bodies);
+
+        self.messenger_for("comment").with_wording(wording).send_to_all(
             &msg,
             &attachments,
             pull_request.user(),
             &self.data.sender,
             &self.data.repository,
-            &participants,
+            self.branch(),
+            &self.all_participants(pull_request),
+            &mentioned,
         );
+        self.send_to_team_channels("comment", &bodies, &msg, &attachments);
 
     }
 
+    // the github user who authored a commit, if github knows who made it
+    fn commit_author(&self, sha: &str) -> Option<github::User> {
+        let repo = &self.data.repository;
+        match self.github_session.get_commit(repo.owner.login(), &repo.name, sha) {
+            Ok(commit) => commit.author,
+            Err(e) => {
+                error!("Error looking up author of commit {}: {}", sha, e);
+                None
+            }
+        }
+    }
+
+    // a note in the channel that a comment or review was edited, with what it says now, or that it was deleted
+    fn do_comment_change(
+        &self,
+        pull_request: &github::PullRequestLike,
+        comment: &github::CommentLike,
+        edited: &str,
+        deleted: &str,
+    ) {
+        if !self.config.repos().comment_edits(&self.data.repository, self.branch()) {
+            return;
+        }
+        if self.is_ignored_user(comment.user()) {
+            return;
+        }
+
+        let messages = self.messages();
+        let link = util::make_link(pull_request.html_url(), pull_request.title());
+        let author = self.slack_user_name(comment.user());
+        let (msg, attachments) = if self.action == "deleted" {
+            (messages.format(deleted, &[("author", &author), ("link", &link)]), vec![])
+        } else {
+            let text = with_code_context(comment, slack::comment_text(comment.body(), comment.html_url()));
+            let attachment = SlackAttachmentBuilder::new(&text)
+                .title(messages.format(messages::COMMENT_NOW_SAYS, &[("author", &author)]))
+                .title_link(comment.html_url())
+                .build();
+            (messages.format(edited, &[("link", &link)]), vec![attachment])
+        };
+
+        self.messenger_for("comment").send_to_channel(&msg, &attachments, &self.data.repository, self.branch());
+    }
+
     fn handle_commit_comment(&self) -> EventResponse {
         if let Some(ref comment) = self.data.comment {
             if self.action == "created" {
-                if let Some(ref commit_id) = comment.commit_id {
+                if self.is_ignored_user(&comment.user) {
+                    info!("Ignoring commit comment from {}", comment.user.login());
+                } else if let Some(ref commit_id) = comment.commit_id {
                     let commit: &str = &commit_id[0..7];
                     let commit_url = format!("{}/commit/{}", self.data.repository.html_url, commit_id);
                     let commit_path: String;
@@ -554,24 +1712,35 @@ impl GithubEventHandler {
                         commit_path = commit.to_string();
                     }
 
-                    let msg =
-                        format!("Comment on \"{}\" ({})", commit_path, util::make_link(commit_url.as_str(), commit));
+                    let messages = self.messages();
+                    let wording = messages::wording(
+                        messages::COMMENT_ON_COMMIT,
+                        vec![("path", commit_path), ("commit", util::make_link(commit_url.as_str(), commit))],
+                    );
+                    let msg = wording(&messages);
 
+                    let user = self.slack_user_name(&comment.user);
                     let attachments = vec![
-                        SlackAttachmentBuilder::new(comment.body())
-                            .title(format!("{} said:", self.slack_user_name(&comment.user)))
+                        SlackAttachmentBuilder::new(&slack::comment_text(comment.body(), &comment.html_url))
+                            .title(messages.format(messages::COMMENT_SAID, &[("user", &user)]))
                             .title_link(comment.html_url.as_str())
                             .build(),
                     ];
 
-                    self.messenger.send_to_all(
+                    // whoever made the commit is the one who needs to see what's said about it
+                    let author = self.commit_author(commit_id).unwrap_or(comment.user.clone());
+
+                    self.messenger_for("comment").with_wording(wording).send_to_all(
                         &msg,
                         &attachments,
-                        &comment.user,
+                        &author,
                         &self.data.sender,
                         &self.data.repository,
+                        None,
                         &vec![],
+                        &self.mentioned_users(comment.body()),
                     );
+                    self.send_to_team_channels("comment", comment.body(), &msg, &attachments);
                 }
             }
         }
@@ -584,15 +1753,357 @@ impl GithubEventHandler {
             if self.action == "created" {
                 // Check to see if we remapped this "issue" to a PR
                 if let Some(ref pr) = self.data.pull_request {
-                    self.do_pull_request_comment(&pr, &comment);
+                    self.do_pull_request_comment(&pr, &comment, &[]);
+                    self.do_pull_request_commands(&pr, &comment);
                 } else if let Some(ref issue) = self.data.issue {
-                    self.do_pull_request_comment(&issue, &comment);
+                    self.do_pull_request_comment(&issue, &comment, &[]);
+                }
+            } else if self.action == "edited" || self.action == "deleted" {
+                if let Some(ref pr) = self.data.pull_request {
+                    self.do_comment_change(&pr, &comment, messages::COMMENT_EDITED, messages::COMMENT_DELETED);
+                } else if let Some(ref issue) = self.data.issue {
+                    self.do_comment_change(&issue, &comment, messages::COMMENT_EDITED, messages::COMMENT_DELETED);
                 }
             }
         }
         (StatusCode::Ok, "issue_comment".into())
     }
 
+    // runs the octobot commands in a PR comment for users who can push to the repo, and replies with how they went
+    fn do_pull_request_commands(&self, pull_request: &github::PullRequest, comment: &github::Comment) {
+        let commands = pr_commands::parse(comment.body());
+        if commands.is_empty() || self.is_ignored_user(&comment.user) {
+            return;
+        }
+
+        let owner = self.data.repository.owner.login();
+        let repo = &self.data.repository.name;
+        let login = comment.user.login();
+        let results = match self.github_session.get_user_permission(owner, repo, login) {
+            Ok(ref p) if p == "admin" || p == "write" => {
+                commands.iter().map(|c| self.run_pull_request_command(pull_request, c)).collect()
+            }
+            Ok(_) => vec![self.messages().format(messages::COMMAND_NOT_ALLOWED, &[("repo", repo)])],
+            Err(e) => {
+                error!("Error checking permissions for octobot command: {}", e);
+                vec![self.messages().format(messages::COMMAND_PERMISSION_ERROR, &[("error", &format!("{}", e))])]
+            }
+        };
+
+        let reply = format!("@{} {}", login, results.join("\n"));
+        if let Err(e) = self.github_session.comment_pull_request(owner, repo, pull_request.number, &reply) {
+            error!("Error replying to octobot command: {}", e);
+        }
+    }
+
+    fn run_pull_request_command(&self, pull_request: &github::PullRequest, command: &PrCommand) -> String {
+        let messages = self.messages();
+        match *command {
+            PrCommand::Backport(ref versions) => {
+                if !pull_request.is_merged() {
+                    let labels: Vec<String> = versions.iter().map(|v| format!("`backport-{}`", v)).collect();
+                    return messages.format(messages::BACKPORT_LABEL_INSTEAD, &[("labels", &labels.join(", "))]);
+                }
+                let targets: Vec<(String, String)> = versions
+                    .iter()
+                    .map(|v| format!("backport-{}", v))
+                    .filter_map(|l| self.backport_branch(pull_request, &l).map(|b| (l, b)))
+                    .collect();
+                messages.format(messages::BACKPORTING, &[("branches", &self.send_backports(pull_request, targets))])
+            }
+            PrCommand::RetryBackport => {
+                if !pull_request.is_merged() {
+                    return messages.format(messages::BACKPORT_NOT_MERGED, &[]);
+                }
+                let labels = match self.github_session.get_pull_request_labels(
+                    &self.data.repository.owner.login(),
+                    &self.data.repository.name,
+                    pull_request.number,
+                ) {
+                    Ok(l) => l,
+                    Err(e) => return messages.format(messages::BACKPORT_LABELS_ERROR, &[("error", &format!("{}", e))]),
+                };
+                let targets = self.backport_targets(pull_request, &labels);
+                if targets.is_empty() {
+                    return messages.format(messages::BACKPORT_NO_LABELS, &[]);
+                }
+                let branches = self.send_backports(pull_request, targets);
+                messages.format(messages::BACKPORTING_AGAIN, &[("branches", &branches)])
+            }
+            PrCommand::Merge => {
+                if !pull_request.is_open() {
+                    return messages.format(messages::MERGE_NOT_OPEN, &[]);
+                }
+                let merge_method =
+                    self.config.repos().merge_strategy(&self.data.repository, &pull_request.base.ref_name);
+                match self.github_session.merge_pull_request(
+                    &self.data.repository.owner.login(),
+                    &self.data.repository.name,
+                    pull_request.number,
+                    &pull_request.head.sha,
+                    &merge_method,
+                ) {
+                    Ok(()) => messages.format(messages::MERGE_DONE, &[]),
+                    Err(e) => messages.format(messages::MERGE_ERROR, &[("error", &format!("{}", e))]),
+                }
+            }
+            PrCommand::Unknown(ref text) => {
+                messages.format(messages::UNKNOWN_COMMAND, &[("command", text), ("help", pr_commands::HELP)])
+            }
+        }
+    }
+
+    // a line for each pushed commit, up to max_push_commits, then a link to the rest
+    fn push_commit_attachments(&self) -> Vec<SlackAttachment> {
+        let num_commits = self.data.commits.as_ref().map_or(0, |c| c.len());
+        let max_commits = self.config.main.max_push_commits.unwrap_or(DEFAULT_MAX_PUSH_COMMITS);
+        let mut attachments: Vec<_>;
+        if let Some(ref commits) = self.data.commits {
+            attachments = commits
+                .iter()
+                .take(max_commits)
+                .map(|commit| {
+                    let msg = commit.message.lines().next().unwrap_or("");
+                    let hash: &str = &commit.id[0..7];
+                    let attach = format!("{}: {}", util::make_link(&commit.url, hash), msg);
+                    SlackAttachmentBuilder::new(&attach).build()
+                })
+                .collect();
+        } else {
+            attachments = vec![];
+        }
+        if num_commits > max_commits {
+            let count = (num_commits - max_commits).to_string();
+            let more = self.messages().format(messages::PUSH_MORE_COMMITS, &[("count", &count)]);
+            let more = match self.data.compare {
+                Some(ref url) => util::make_link(url, &more),
+                None => more,
+            };
+            attachments.push(SlackAttachmentBuilder::new(&more).build());
+        }
+        attachments
+    }
+
+    // A push to a branch that should only change through pull requests is fine if the new head is (or merged) a
+    // closed pull request into it. Otherwise the security channel, or the admins, hear about it.
+    fn check_direct_push(&self, branch_name: &str) {
+        let repo = &self.data.repository;
+        if self.data.sender.login() == self.github_session.user().login() {
+            return;
+        }
+
+        match self.github_session.get_pull_requests_for_commit(repo.owner.login(), &repo.name, self.data.after()) {
+            Ok(prs) => {
+                if prs.iter().any(|pr| !pr.is_open() && pr.base.ref_name == branch_name) {
+                    return;
+                }
+            }
+            Err(e) => {
+                error!("Error looking up PRs for {} ({}): {}", branch_name, self.data.after(), e);
+                return;
+            }
+        };
+
+        let num_commits = self.data.commits.as_ref().map_or(0, |c| c.len());
+        let msg = self.messages().format(
+            messages::DIRECT_PUSH,
+            &[
+                ("user", &self.slack_user_name(&self.data.sender)),
+                ("count", &num_commits.to_string()),
+                ("branch", branch_name),
+            ],
+        );
+        let attachments = self.push_commit_attachments();
+        let messenger = self.messenger_for("security");
+        match self.config.repos().security_channel(repo) {
+            Some(channel) => messenger.send_to_team_channel(&msg, &attachments, &channel, repo),
+            None => {
+                let msg = format!("{} ({})", msg, util::make_link(&repo.html_url, &repo.full_name));
+                messenger.send_to_admins(&msg, &attachments);
+            }
+        };
+    }
+
+    // Issues go to the channel of the first of the repo's issue channel rules whose label they have, if any. Issues
+    // labeled with one of those later are announced there too.
+    fn handle_issues(&self) -> EventResponse {
+        if let Some(ref issue) = self.data.issue {
+            let repo = &self.data.repository;
+            let sender = self.slack_user_name(&self.data.sender);
+            let messenger = self.messenger_for("issue");
+            let messages = self.messages();
+
+            let mut attachment = SlackAttachmentBuilder::new("");
+            attachment
+                .title(messages.format(
+                    messages::ISSUE_TITLE,
+                    &[("number", &issue.number.to_string()), ("title", &issue.title)],
+                ))
+                .title_link(issue.html_url.as_str());
+
+            match self.action.as_str() {
+                "opened" | "reopened" | "closed" => {
+                    if self.action == "opened" {
+                        let body = issue.body.as_ref().map_or("", |b| b.as_str());
+                        attachment.text(slack::comment_excerpt(body, &issue.html_url, MAX_DESCRIPTION_CHARS));
+                    }
+                    let name = match self.action.as_str() {
+                        "opened" => messages::ISSUE_OPENED,
+                        "reopened" => messages::ISSUE_REOPENED,
+                        _ => messages::ISSUE_CLOSED,
+                    };
+                    let msg = messages.format(name, &[("user", &sender)]);
+                    let attachments = vec![attachment.build()];
+                    match self.config.repos().issue_channel(repo, &issue.labels) {
+                        Some(channel) => messenger.send_to_team_channel(&msg, &attachments, &channel, repo),
+                        None => messenger.send_to_channel(&msg, &attachments, repo, None),
+                    };
+                }
+                "assigned" => {
+                    if let Some(ref assignee) = self.data.assignee {
+                        if assignee.login() != self.data.sender.login() {
+                            let wording = messages::wording(messages::ISSUE_ASSIGNED, vec![("user", sender)]);
+                            let msg = wording(&messages);
+                            let attachments = vec![attachment.build()];
+                            let assignees = vec![assignee.clone()];
+                            let messenger = messenger.with_wording(wording);
+                            messenger.send_to_users(&msg, &attachments, &assignees, repo, Reason::Participant);
+                        }
+                    }
+                }
+                "labeled" => {
+                    if let Some(ref label) = self.data.label {
+                        if let Some(channel) = self.config.repos().issue_channel(repo, &vec![label.clone()]) {
+                            let values = [("label", label.name.as_str()), ("user", sender.as_str())];
+                            let msg = messages.format(messages::ISSUE_LABELED, &values);
+                            messenger.send_to_team_channel(&msg, &vec![attachment.build()], &channel, repo);
+                        }
+                    }
+                }
+                _ => (),
+            };
+        }
+
+        (StatusCode::Ok, "issues".into())
+    }
+
+    fn handle_repository(&self) -> EventResponse {
+        let response = (StatusCode::Ok, "repository".into());
+        let action = match self.action.as_str() {
+            "created" => messages::REPO_CREATED,
+            "deleted" => messages::REPO_DELETED,
+            "archived" => messages::REPO_ARCHIVED,
+            "unarchived" => messages::REPO_UNARCHIVED,
+            "renamed" => messages::REPO_RENAMED,
+            "transferred" => messages::REPO_TRANSFERRED,
+            "publicized" => messages::REPO_PUBLICIZED,
+            "privatized" => messages::REPO_PRIVATIZED,
+            _ => return response,
+        };
+        let repo = &self.data.repository;
+        let sender = self.slack_user_name(&self.data.sender);
+        let messages = self.messages();
+        let action = messages.format(action, &[]);
+
+        let mut attachments = vec![];
+        let previous_name = self.data.changes.as_ref().and_then(|c| c.previous_repo_name(repo));
+        let msg = match previous_name {
+            Some(ref previous) if self.action == "renamed" || self.action == "transferred" => {
+                // the repos config still has the old name: follow it so that the repo's notifications keep going
+                let mut repos = self.config.repos().clone();
+                if repos.rename_repo(self.github_session.github_host(), previous, &repo.full_name) > 0 {
+                    let text = match self.config.save_repos(repos.host_map().clone()) {
+                        Ok(()) => messages.format(messages::REPOS_CONFIG_UPDATED, &[("previous", previous)]),
+                        Err(e) => {
+                            error!("Error renaming {} to {} in repos config: {}", previous, repo.full_name, e);
+                            messages.format(
+                                messages::REPOS_CONFIG_ERROR,
+                                &[("previous", previous), ("error", &format!("{}", e))],
+                            )
+                        }
+                    };
+                    attachments.push(SlackAttachmentBuilder::new(&text).build());
+                }
+                messages.format(messages::REPO_MOVED, &[("action", &action), ("previous", previous), ("user", &sender)])
+            }
+            _ => messages.format(messages::REPO_CHANGED, &[("action", &action), ("user", &sender)]),
+        };
+
+        let messenger = self.messenger_for("repository");
+        match self.config.repos().admin_channel(repo) {
+            Some(channel) => messenger.send_to_team_channel(&msg, &attachments, &channel, repo),
+            None => {
+                let msg = format!("{} ({})", msg, util::make_link(&repo.html_url, &repo.full_name));
+                messenger.send_to_admins(&msg, &attachments);
+            }
+        };
+
+        response
+    }
+
+    fn handle_security_alert(&self) -> EventResponse {
+        let response = (StatusCode::Ok, self.event.clone());
+        let alert = match self.data.alert {
+            Some(ref a) => a,
+            None => return response,
+        };
+        let repo = &self.data.repository;
+        let channel = match self.config.repos().security_channel(repo) {
+            Some(c) => c,
+            None => return response,
+        };
+        // repository_vulnerability_alert has its own names for the actions
+        let action = match self.action.as_str() {
+            "created" | "create" => messages::ALERT_OPENED,
+            "reopened" | "reopened_by_user" => messages::ALERT_REOPENED,
+            "fixed" | "resolve" => messages::ALERT_FIXED,
+            "dismissed" | "dismiss" | "closed_by_user" => messages::ALERT_DISMISSED,
+            "auto_dismissed" => messages::ALERT_AUTO_DISMISSED,
+            _ => return response,
+        };
+        let kind = match self.event.as_str() {
+            "dependabot_alert" => messages::DEPENDABOT_ALERT,
+            "code_scanning_alert" => messages::CODE_SCANNING_ALERT,
+            _ => messages::VULNERABILITY_ALERT,
+        };
+        let messages = self.messages();
+
+        let mut attachment = SlackAttachmentBuilder::new("");
+        attachment.title(alert.summary());
+        if let Some(url) = alert.html_url.as_ref().or(alert.external_reference.as_ref()) {
+            attachment.title_link(url.as_str());
+        }
+        if let Some(ref advisory) = alert.security_advisory {
+            let advisory_url = format!("https://github.com/advisories/{}", advisory.ghsa_id);
+            attachment.text(util::make_link(&advisory_url, &advisory.ghsa_id));
+        }
+        if let Some(severity) = alert.severity() {
+            attachment.field(messages.format(messages::FIELD_SEVERITY, &[]), severity);
+        }
+        if let Some(package) = alert.package() {
+            attachment.field(messages.format(messages::FIELD_PACKAGE, &[]), package);
+        }
+        if let Some(ref tool) = alert.tool {
+            attachment.field(messages.format(messages::FIELD_TOOL, &[]), tool.name.as_str());
+        }
+        let color = match action {
+            messages::ALERT_OPENED | messages::ALERT_REOPENED => severity_color(alert.severity()),
+            messages::ALERT_FIXED => Some("good"),
+            _ => None,
+        };
+        if let Some(color) = color {
+            attachment.color(color);
+        }
+
+        let msg = messages.format(
+            messages::SECURITY_ALERT,
+            &[("alert", &messages.format(kind, &[])), ("action", &messages.format(action, &[]))],
+        );
+        self.messenger_for("security").send_to_team_channel(&msg, &vec![attachment.build()], &channel, repo);
+
+        response
+    }
+
     fn handle_push(&self) -> EventResponse {
         if self.data.deleted() || self.data.created() {
             // ignore
@@ -605,13 +2116,21 @@ impl GithubEventHandler {
             let is_main_branch = branch_name == "master" || branch_name == "develop" ||
                 branch_name.starts_with("release");
 
+            let ignored = self.is_ignored_user(&self.data.sender) ||
+                self.config.repos().ignores_push(&self.data.repository, &branch_name, self.data.sender.login());
+            if ignored {
+                info!("Ignoring push to '{}' by {}", branch_name, self.data.sender.login());
+            }
+
             // only lookup PRs for non-main branches
-            if !is_main_branch {
-                let prs = match self.github_session.get_pull_requests(
+            if !is_main_branch && !ignored {
+                // there appears to be a race condition in github where the get PR's call may not
+                // yet return the new hash, so check both.
+                let prs = match self.github_session.get_open_pull_requests_for_head(
                     &self.data.repository.owner.login(),
                     &self.data.repository.name,
-                    Some("open"),
-                    None,
+                    &branch_name,
+                    &[self.data.before(), self.data.after()],
                 ) {
                     Ok(p) => p,
                     Err(e) => {
@@ -620,36 +2139,24 @@ impl GithubEventHandler {
                     }
                 };
 
-                // there appears to be a race condition in github where the get PR's call may not
-                // yet return the new hash, so check both.
-                let prs: Vec<github::PullRequest> = prs.into_iter()
-                    .filter(|pr| pr.head.sha == self.data.before() || pr.head.sha == self.data.after())
-                    .collect();
-
                 if prs.len() == 0 {
                     info!("No PRs found for '{}' ({})", branch_name, self.data.after());
                 } else {
-                    let attachments: Vec<_>;
-                    if let Some(ref commits) = self.data.commits {
-                        attachments = commits
-                            .iter()
-                            .map(|commit| {
-                                let msg = commit.message.lines().next().unwrap_or("");
-                                let hash: &str = &commit.id[0..7];
-                                let attach = format!("{}: {}", util::make_link(&commit.url, hash), msg);
-                                SlackAttachmentBuilder::new(&attach).build()
-                            })
-                            .collect();
-                    } else {
-                        attachments = vec![];
-                    }
-
-                    let message = format!(
-                        "{} pushed {} commit(s) to branch {}",
-                        self.slack_user_name(&self.data.sender),
-                        attachments.len(),
-                        branch_name
+                    let num_commits = self.data.commits.as_ref().map_or(0, |c| c.len());
+                    let commit_attachments = self.push_commit_attachments();
+
+                    let messages = self.messages();
+                    let wording = messages::wording(
+                        messages::PUSH,
+                        vec![
+                            ("user", self.slack_user_name(&self.data.sender)),
+                            ("count", num_commits.to_string()),
+                            ("branch", branch_name.clone()),
+                        ],
                     );
+                    let message = wording(&messages);
+                    let messenger = self.messenger_for(if self.data.forced() { "force_push" } else { "push" })
+                        .with_wording(wording);
 
                     for pull_request in &prs {
                         if pull_request.is_wip() {
@@ -657,27 +2164,18 @@ impl GithubEventHandler {
                             continue;
                         }
 
-                        let mut attachments = attachments.clone();
-                        attachments
-                            .insert(
-                                0,
-                                SlackAttachmentBuilder::new("")
-                                    .title(format!(
-                                        "Pull Request #{}: \"{}\"",
-                                        pull_request.number,
-                                        pull_request.title.as_str()
-                                    ))
-                                    .title_link(pull_request.html_url.as_str())
-                                    .build(),
-                            );
+                        let mut attachments = pull_request_attachments(pull_request, &messages);
+                        attachments.extend(commit_attachments.iter().cloned());
 
-                        self.messenger.send_to_all(
+                        messenger.for_pull_request(pull_request).send_to_all(
                             &message,
                             &attachments,
                             &pull_request.user,
                             &self.data.sender,
                             &self.data.repository,
+                            Some(pull_request.base.ref_name.as_str()),
                             &self.all_participants(&pull_request),
+                            &vec![],
                         );
 
                         if self.data.forced() && self.config.repos().notify_force_push(&self.data.repository) {
@@ -695,6 +2193,10 @@ impl GithubEventHandler {
                 }
             }
 
+            if self.config.repos().alerts_direct_push(&self.data.repository, &branch_name) {
+                self.check_direct_push(&branch_name);
+            }
+
             // Mark JIRAs as merged
             if is_main_branch {
                 if let Some(ref jira_config) = self.config.jira {
@@ -734,12 +2236,524 @@ impl GithubEventHandler {
                     }
                 }
             }
+
+            // open PRs targeting the branch may not merge cleanly any more
+            if self.config.repos().notify_conflicts(&self.data.repository, &branch_name) {
+                if let Err(e) = self.conflicts.send(conflicts::req(&self.data.repository, &branch_name)) {
+                    error!("Error sending conflicts message: {}", e);
+                }
+            }
+            if self.config.repos().keep_updated(&self.data.repository, &branch_name) {
+                let req = branch_updates::branch_req(&self.data.repository, &branch_name);
+                if let Err(e) = self.branch_updates.send(req) {
+                    error!("Error sending branch update message: {}", e);
+                }
+            }
         }
 
         (StatusCode::Ok, "push".into())
     }
 
-    fn merge_pull_request_all_labels(&self, pull_request: &github::PullRequest, release_branch_prefix: &str) {
+    fn handle_create(&self) -> EventResponse {
+        if self.data.ref_type.as_ref().map(|t| t.as_str()) == Some("tag") {
+            let tag = self.data.ref_name();
+            let user = self.slack_user_name(&self.data.sender);
+            let msg = self.messages().format(messages::TAG_CREATED, &[("tag", tag), ("user", &user)]);
+            let attachments = match self.compare_link(tag) {
+                Some(link) => vec![SlackAttachmentBuilder::new(&link).build()],
+                None => vec![],
+            };
+            self.announce_release(&msg, &attachments);
+            self.bump_version(tag);
+        }
+
+        (StatusCode::Ok, "create".into())
+    }
+
+    fn handle_release(&self) -> EventResponse {
+        if let Some(ref release) = self.data.release {
+            if self.action == "published" || self.action == "edited" {
+                let name = match (release.prerelease, self.action.as_str()) {
+                    (true, "published") => messages::PRERELEASE_PUBLISHED,
+                    (true, _) => messages::PRERELEASE_EDITED,
+                    (false, "published") => messages::RELEASE_PUBLISHED,
+                    (false, _) => messages::RELEASE_EDITED,
+                };
+                let user = self.slack_user_name(&release.author);
+                let msg = self.messages().format(name, &[("name", release.name()), ("user", &user)]);
+
+                let mut text = release_excerpt(release.body.as_ref().map_or("", |b| b.as_str()));
+                if let Some(link) = self.compare_link(&release.tag_name) {
+                    text = if text.is_empty() { link } else { format!("{}\n\n{}", text, link) };
+                }
+                let attachments = vec![
+                    SlackAttachmentBuilder::new(&text)
+                        .title(format!("{} ({})", release.name(), release.tag_name))
+                        .title_link(release.html_url.as_str())
+                        .color("good")
+                        .build(),
+                ];
+                self.announce_release(&msg, &attachments);
+            }
+        }
+
+        (StatusCode::Ok, "release".into())
+    }
+
+    fn handle_deployment(&self) -> EventResponse {
+        if let Some(ref deployment) = self.data.deployment {
+            let msg = self.messages().format(
+                messages::DEPLOYING,
+                &[
+                    ("ref", &deployment.ref_name),
+                    ("environment", &deployment.environment),
+                    ("user", &self.slack_user_name(&deployment.creator)),
+                ],
+            );
+            let attachments = vec![self.deployment_attachment(deployment, None)];
+            self.announce_deployment(&deployment.environment, &msg, &attachments);
+        }
+
+        (StatusCode::Ok, "deployment".into())
+    }
+
+    fn handle_deployment_status(&self) -> EventResponse {
+        if let (Some(deployment), Some(status)) =
+            (self.data.deployment.as_ref(), self.data.deployment_status.as_ref())
+        {
+            let name = match status.state.as_str() {
+                "success" => messages::DEPLOYMENT_SUCCEEDED,
+                "failure" | "error" => messages::DEPLOYMENT_FAILED,
+                "inactive" => messages::DEPLOYMENT_REPLACED,
+                "in_progress" => messages::DEPLOYMENT_IN_PROGRESS,
+                _ => messages::DEPLOYMENT_PENDING,
+            };
+            let values = [("ref", deployment.ref_name.as_str()), ("environment", deployment.environment.as_str())];
+            let msg = self.messages().format(name, &values);
+            let attachments = vec![self.deployment_attachment(deployment, Some(status))];
+            self.announce_deployment(&deployment.environment, &msg, &attachments);
+        }
+
+        (StatusCode::Ok, "deployment_status".into())
+    }
+
+    // the deployed commit, any open PRs it's the head of, and the links the status has
+    fn deployment_attachment(
+        &self,
+        deployment: &github::Deployment,
+        status: Option<&github::DeploymentStatus>,
+    ) -> SlackAttachment {
+        let repo = &self.data.repository;
+        let sha = &deployment.sha;
+        let short_sha = if sha.len() > 7 { &sha[0..7] } else { sha.as_str() };
+        let commit_url = format!("{}/commit/{}", repo.html_url, sha);
+        let messages = self.messages();
+        let mut lines = vec![messages.format(
+            messages::AT_COMMIT,
+            &[("ref", &deployment.ref_name), ("commit", &util::make_link(&commit_url, short_sha))],
+        )];
+
+        match self.github_session.get_open_pull_requests_for_head(
+            repo.owner.login(),
+            &repo.name,
+            &deployment.ref_name,
+            &[sha.as_str()],
+        ) {
+            Ok(ref prs) if !prs.is_empty() => {
+                let links: Vec<String> = prs.iter()
+                    .map(|pr| util::make_link(&pr.html_url, &format!("#{}: {}", pr.number, pr.title)))
+                    .collect();
+                lines.push(messages.format(messages::DEPLOYMENT_PRS, &[("links", &links.join(", "))]));
+            }
+            Ok(_) => (),
+            Err(e) => error!("Error looking up deployed PRs: {}", e),
+        };
+
+        let mut color = None;
+        if let Some(status) = status {
+            if let Some(ref description) = status.description {
+                if !description.is_empty() {
+                    lines.push(description.clone());
+                }
+            }
+            if let Some(ref url) = status.environment_url {
+                lines.push(util::make_link(url, &deployment.environment));
+            }
+            if let Some(ref url) = status.target_url {
+                lines.push(util::make_link(url, &messages.format(messages::DEPLOYMENT_LOGS, &[])));
+            }
+            color = match status.state.as_str() {
+                "success" => Some("good"),
+                "failure" | "error" => Some("danger"),
+                "inactive" => None,
+                _ => Some("warning"),
+            };
+        } else if let Some(ref description) = deployment.description {
+            if !description.is_empty() {
+                lines.push(description.clone());
+            }
+        }
+
+        let mut attachment = SlackAttachmentBuilder::new(&lines.join("\n"));
+        if let Some(color) = color {
+            attachment.color(color);
+        }
+        attachment.build()
+    }
+
+    // to the environment's channel, if it has one
+    fn announce_deployment(&self, environment: &str, msg: &str, attachments: &Vec<SlackAttachment>) {
+        let messenger = self.messenger_for("deployment");
+        match self.config.repos().deployment_channel(&self.data.repository, environment) {
+            Some(channel) => messenger.send_to_team_channel(msg, attachments, &channel, &self.data.repository),
+            None => messenger.send_to_channel(msg, attachments, &self.data.repository, None),
+        };
+    }
+
+    fn handle_workflow_run(&self) -> EventResponse {
+        if self.action == "completed" {
+            if let Some(ref run) = self.data.workflow_run {
+                self.alert_workflow_run(run);
+            }
+        }
+
+        (StatusCode::Ok, "workflow_run".into())
+    }
+
+    // failures of watched workflows on protected branches go to the channel and whoever triggered the run; the
+    // next success says the branch is back to green
+    fn alert_workflow_run(&self, run: &github::WorkflowRun) {
+        let repo = &self.data.repository;
+        let branch = match run.head_branch {
+            Some(ref b) => b,
+            None => return,
+        };
+        if !self.config.repos().watches_workflow(repo, &run.name) {
+            return;
+        }
+        let failed = match run.conclusion.as_ref().map(|c| c.as_str()) {
+            Some("failure") | Some("timed_out") => true,
+            Some("success") => false,
+            // cancelled or skipped runs don't say anything about the branch
+            _ => return,
+        };
+        match self.github_session.is_branch_protected(repo.owner.login(), &repo.name, branch) {
+            Ok(true) => (),
+            Ok(false) => return,
+            Err(e) => {
+                error!("Error looking up protection of {} in {}: {}", branch, repo.full_name, e);
+                return;
+            }
+        };
+
+        let previous_failures = self.failing_workflows.write(|f| {
+            let previous = f.failures(repo, &run.name, branch);
+            f.update(repo, &run.name, branch, failed);
+            previous
+        });
+        if !failed && previous_failures == 0 {
+            return;
+        }
+        let escalate_after = match repo.default_branch {
+            Some(ref b) if b == branch => self.config.repos().broken_branch_escalation(repo),
+            _ => None,
+        };
+
+        let short_sha = if run.head_sha.len() > 7 { &run.head_sha[0..7] } else { run.head_sha.as_str() };
+        let commit_url = format!("{}/commit/{}", repo.html_url, run.head_sha);
+        let messages = self.messages();
+        let mut attachment = SlackAttachmentBuilder::new(&messages.format(
+            messages::AT_COMMIT,
+            &[("ref", branch), ("commit", &util::make_link(&commit_url, short_sha))],
+        ));
+        attachment.title(format!("{} #{}", run.name, run.run_number)).title_link(run.html_url.as_str());
+
+        let messenger = self.messenger_for("workflow");
+        if failed {
+            if self.config.main.slack_signing_secret.as_ref().map_or(false, |s| !s.is_empty()) {
+                let value = format!("{} {}", repo.html_url, run.id);
+                let rerun = messages.format(messages::RERUN_FAILED_JOBS, &[]);
+                attachment.button(slack_commands::RERUN_ACTION, rerun, value);
+            }
+            let wording = messages::wording(
+                messages::WORKFLOW_FAILED,
+                vec![("workflow", run.name.clone()), ("branch", branch.clone())],
+            );
+            let msg = wording(&messages);
+            let attachments = vec![attachment.color("danger").build()];
+            messenger.send_to_channel(&msg, &attachments, repo, Some(branch));
+            if let Some(ref actor) = run.actor {
+                let actors = vec![actor.clone()];
+                messenger.with_wording(wording).send_to_users(&msg, &attachments, &actors, repo, Reason::Participant);
+            }
+            // escalated once, when the count is reached, and not again on the failures after that
+            let failures = previous_failures + 1;
+            if escalate_after == Some(failures) {
+                let summary = messages.format(
+                    messages::WORKFLOW_FAILING,
+                    &[("workflow", &run.name), ("count", &failures.to_string()), ("branch", branch)],
+                );
+                messenger.send_to_channel(&format!("<!here> {}", summary), &attachments, repo, Some(branch));
+                self.page(&run.name, branch, Some(&summary), &run.html_url);
+            }
+        } else {
+            let msg = messages.format(messages::WORKFLOW_FIXED, &[("workflow", &run.name), ("branch", branch)]);
+            let attachments = vec![attachment.color("good").build()];
+            messenger.send_to_channel(&msg, &attachments, repo, Some(branch));
+            if escalate_after.map_or(false, |n| previous_failures >= n) {
+                self.page(&run.name, branch, None, &run.html_url);
+            }
+        }
+    }
+
+    // triggers the PagerDuty incident for a broken branch when given a summary, and resolves it otherwise
+    fn page(&self, workflow: &str, branch: &str, summary: Option<&str>, link: &str) {
+        let session = match self.pagerduty_session {
+            Some(ref s) => s,
+            None => return,
+        };
+        let repo = &self.data.repository;
+        let dedup_key = format!("octobot {} {} {}", repo.full_name, workflow, branch);
+        let res = match summary {
+            Some(summary) => {
+                let summary = format!("{}: {}", repo.full_name, summary);
+                session.trigger(&dedup_key, &summary, &repo.html_url, link)
+            }
+            None => session.resolve(&dedup_key),
+        };
+        if let Err(e) = res {
+            error!("Error paging for {} on {} in {}: {}", workflow, branch, repo.full_name, e);
+        }
+    }
+
+    // after a version tag, if the repo has version files to bump
+    fn bump_version(&self, tag: &str) {
+        let repo = &self.data.repository;
+        let default_branch = repo.default_branch.as_ref().map_or("master", |b| b.as_str());
+        let released = match Version::parse_trailing(tag) {
+            Some(v) => v,
+            None => return,
+        };
+        if self.config.repos().version_bump_rules(repo, default_branch).is_empty() {
+            return;
+        }
+
+        let req = version_bumps::req(repo, &released.to_string(), &format!("tagging {}", tag));
+        if let Err(e) = self.version_bumps.send(req) {
+            error!("Error sending version bump message: {}", e);
+        }
+    }
+
+    // to the repo's release channel, if it has one
+    fn announce_release(&self, msg: &str, attachments: &Vec<SlackAttachment>) {
+        let messenger = self.messenger_for("release");
+        match self.config.repos().release_channel(&self.data.repository) {
+            Some(channel) => messenger.send_to_team_channel(msg, attachments, &channel, &self.data.repository),
+            None => messenger.send_to_channel(msg, attachments, &self.data.repository, None),
+        };
+    }
+
+    // a link to the changes since the tag before `tag`, going by the versions they end in
+    fn compare_link(&self, tag: &str) -> Option<String> {
+        let version = match Version::parse_trailing(tag) {
+            Some(v) => v,
+            None => return None,
+        };
+        let tags = match self.github_session.get_tags(self.data.repository.owner.login(), &self.data.repository.name) {
+            Ok(t) => t,
+            Err(e) => {
+                error!("Error looking up tags: {}", e);
+                return None;
+            }
+        };
+        let previous = tags.iter()
+            .filter_map(|t| Version::parse_trailing(t).map(|v| (v, t)))
+            .filter(|&(ref v, _)| *v < version)
+            .max_by(|a, b| a.0.cmp(&b.0));
+        previous.map(|(_, previous)| {
+            let url = format!("{}/compare/{}...{}", self.data.repository.html_url, previous, tag);
+            util::make_link(&url, &self.messages().format(messages::CHANGES_SINCE, &[("previous", previous)]))
+        })
+    }
+
+    fn handle_status(&self) -> EventResponse {
+        // statuses don't say which PR they belong to: let the merge queue figure out if it cares.
+        if let Err(e) = self.merge_queue.send(merge_queue::check_req(&self.data.repository)) {
+            error!("Error sending merge queue message: {}", e);
+        }
+
+        if self.data.state.as_ref().map_or(false, |s| s == "success") {
+            if let Some(ref sha) = self.data.sha {
+                self.send_auto_merge(auto_merge::commit_req(&self.data.repository, sha));
+            }
+        }
+
+        (StatusCode::Ok, "status".into())
+    }
+
+    fn send_auto_merge(&self, req: AutoMergeRequest) {
+        if let Err(e) = self.auto_merge.send(req) {
+            error!("Error sending auto-merge message: {}", e);
+        }
+    }
+
+    fn update_merge_queue(&self, pull_request: &github::PullRequest) {
+        let is_queue_label = self.data.label.as_ref().map_or(false, |l| l.name == merge_queue::QUEUE_LABEL);
+
+        let req = if self.action == "labeled" && is_queue_label && pull_request.is_open() {
+            merge_queue::add_req(&self.data.repository, pull_request)
+        } else if (self.action == "unlabeled" && is_queue_label) || self.action == "closed" {
+            merge_queue::remove_req(&self.data.repository, pull_request)
+        } else {
+            return;
+        };
+
+        if let Err(e) = self.merge_queue.send(req) {
+            error!("Error sending merge queue message: {}", e);
+        }
+    }
+
+    // a PR that was just labeled "keep-updated" gets updated right away rather than the next time its base moves
+    fn update_keep_updated(&self, pull_request: &github::PullRequest) {
+        let is_label = self.data.label.as_ref().map_or(false, |l| l.name == branch_updates::LABEL);
+        if self.action != "labeled" || !is_label ||
+            !self.config.repos().keep_updated(&self.data.repository, &pull_request.base.ref_name)
+        {
+            return;
+        }
+
+        if let Err(e) = self.branch_updates.send(branch_updates::pr_req(&self.data.repository, pull_request)) {
+            error!("Error sending branch update message: {}", e);
+        }
+    }
+
+    fn update_review_status(&self, pull_request: &github::PullRequest) {
+        let approvals = match self.config.repos().review_status_approvals(
+            &self.data.repository,
+            &pull_request.base.ref_name,
+        ) {
+            Some(a) => a,
+            None => return,
+        };
+        if !pull_request.is_open() {
+            return;
+        }
+
+        let result = review_status::update(self.github_session.deref(), &self.data.repository, pull_request, approvals);
+        if let Err(e) = result {
+            error!("Error updating review status of PR #{}: {}", pull_request.number, e);
+        }
+    }
+
+    fn update_path_labels(&self, pull_request: &github::PullRequest) {
+        let path_labels = self.config.repos().path_labels(&self.data.repository, &pull_request.base.ref_name);
+        if path_labels.is_empty() {
+            return;
+        }
+        let result = path_labels::apply(self.github_session.deref(), &self.data.repository, pull_request, &path_labels);
+        if let Err(e) = result {
+            error!("Error updating path labels of PR #{}: {}", pull_request.number, e);
+        }
+    }
+
+    // the PR's diff stats, if the repo labels PRs by size
+    fn update_size_label(&self, pull_request: &github::PullRequest) -> Option<github::DiffStats> {
+        let thresholds = self.config.repos().size_label_thresholds(
+            &self.data.repository,
+            &pull_request.base.ref_name,
+        )?;
+        match size_labels::apply(self.github_session.deref(), &self.data.repository, pull_request, &thresholds) {
+            Ok(stats) => Some(stats),
+            Err(e) => {
+                error!("Error updating size label of PR #{}: {}", pull_request.number, e);
+                None
+            }
+        }
+    }
+
+    fn check_conventions(&self, pull_request: &github::PullRequest) {
+        let conventions = self.config.repos().conventions(&self.data.repository, &pull_request.base.ref_name);
+        if conventions.is_empty() || !pull_request.is_open() {
+            return;
+        }
+
+        // what the status said before this push, so that the same problems aren't commented on again
+        let previous_sha = match self.data.before {
+            Some(ref before) if self.action == "synchronize" => before.as_str(),
+            _ => pull_request.head.sha.as_str(),
+        };
+        let result = conventions::check(
+            self.github_session.deref(),
+            &self.data.repository,
+            pull_request,
+            &conventions,
+            previous_sha,
+        );
+        if let Err(e) = result {
+            error!("Error checking conventions of PR #{}: {}", pull_request.number, e);
+        }
+    }
+
+    fn check_changelog(&self, pull_request: &github::PullRequest) {
+        let rule = match self.config.repos().changelog_rule(&self.data.repository, &pull_request.base.ref_name) {
+            Some(rule) => rule,
+            None => return,
+        };
+        if !pull_request.is_open() {
+            return;
+        }
+
+        // only a push moves the head: the status before it says whether the missing entry was already pointed out
+        let previous_sha = match self.data.before {
+            Some(ref before) if self.action == "synchronize" => before.as_str(),
+            _ => pull_request.head.sha.as_str(),
+        };
+        let result =
+            changelog::check(self.github_session.deref(), &self.data.repository, pull_request, &rule, previous_sha);
+        if let Err(e) = result {
+            error!("Error checking changelog of PR #{}: {}", pull_request.number, e);
+        }
+    }
+
+    fn update_dependencies(&self, pull_request: &github::PullRequest) {
+        let github = self.github_session.deref();
+        if pull_request.is_merged() {
+            pr_dependencies::dependency_merged(
+                github,
+                &*self.messenger,
+                &self.data.repository,
+                pull_request,
+                &self.dependents,
+            );
+        }
+
+        // closed PRs stop waiting, whatever their repo's settings are now
+        if pull_request.is_open() &&
+            !self.config.repos().track_dependencies(&self.data.repository, &pull_request.base.ref_name)
+        {
+            return;
+        }
+        if let Err(e) = pr_dependencies::check(github, &self.data.repository, pull_request, &self.dependents) {
+            error!("Error checking dependencies of PR #{}: {}", pull_request.number, e);
+        }
+    }
+
+    fn update_review_reminders(&self, pull_request: &github::PullRequest) {
+        let repos = self.config.repos();
+        let branch = &pull_request.base.ref_name;
+        if repos.review_reminder_hours(&self.data.repository, branch).is_none() &&
+            repos.review_lead(&self.data.repository, branch).is_none() &&
+            repos.review_here_hours(&self.data.repository, branch).is_none()
+        {
+            return;
+        }
+
+        if let Err(e) = self.review_reminders.send(review_reminders::req(&self.data.repository, pull_request)) {
+            error!("Error sending review reminders message: {}", e);
+        }
+    }
+
+    fn merge_pull_request_all_labels(&self, pull_request: &github::PullRequest) {
         if !pull_request.is_merged() {
             return;
         }
@@ -752,40 +2766,231 @@ impl GithubEventHandler {
             Ok(l) => l,
             Err(e) => {
                 self.messenger.send_to_owner(
-                    "Error getting Pull Request labels",
+                    &self.messages().format(messages::PR_LABELS_ERROR, &[]),
                     &vec![SlackAttachmentBuilder::new(&format!("{}", e)).color("danger").build()],
                     &pull_request.user,
                     &self.data.repository,
+                    Some(pull_request.base.ref_name.as_str()),
                 );
                 return;
             }
         };
 
-        for label in &labels {
-            self.merge_pull_request(pull_request, label, release_branch_prefix);
-        }
+        let targets = self.backport_targets(pull_request, &labels);
+        self.send_backports(pull_request, targets);
     }
 
-    fn merge_pull_request(
+    // the (label, branch) of each of the labels that asks for a backport
+    fn backport_targets(
         &self,
         pull_request: &github::PullRequest,
-        label: &github::Label,
-        release_branch_prefix: &str,
-    ) {
+        labels: &Vec<github::Label>,
+    ) -> Vec<(String, String)> {
+        labels
+            .iter()
+            .filter_map(|l| self.backport_branch(pull_request, &l.name).map(|b| (l.name.clone(), b)))
+            .collect()
+    }
+
+    // Backports to the (label, branch) targets, oldest branch first. When the repo chains backports only the oldest
+    // one is made now, and its backport PR gets the other labels so that merging it backports it on to the next.
+    // Describes where it's going.
+    fn send_backports(&self, pull_request: &github::PullRequest, targets: Vec<(String, String)>) -> String {
+        let mut targets = targets;
+        pr_merge::sort_backport_branches(&mut targets, |t| t.1.as_str());
+
+        let branches: Vec<String> = targets.iter().map(|t| t.1.clone()).collect();
+        let chain = self.config.repos().chain_backports(&self.data.repository, &pull_request.base.ref_name);
+        if !chain || targets.len() < 2 {
+            for branch in &branches {
+                self.send_pr_merge(pull_request, branch);
+            }
+            return branches.join(", ");
+        }
+
+        let carried_labels = targets[1..].iter().map(|t| t.0.clone()).collect();
+        let req = pr_merge::chained_req(&self.data.repository, pull_request, &branches[0], carried_labels);
+        if let Err(e) = self.pr_merge.send(req) {
+            error!("Error sending merge request message: {}", e)
+        }
+        self.send_mirror_merges(pull_request, &branches[0]);
+        self.messages().format(
+            messages::BACKPORT_CHAINED,
+            &[("first", &branches[0]), ("rest", &branches[1..].join(", "))],
+        )
+    }
+
+    fn merge_pull_request(&self, pull_request: &github::PullRequest, label: &github::Label) {
         if !pull_request.is_merged() {
             return;
         }
 
-        let re = Regex::new(r"(?i)backport-(.+)").unwrap();
-        let backport = match re.captures(&label.name) {
-            Some(c) => c[1].to_string(),
-            None => return,
-        };
-        let target_branch = release_branch_prefix.to_string() + &backport;
+        if let Some(target_branch) = self.backport_branch(pull_request, &label.name) {
+            self.send_pr_merge(pull_request, &target_branch);
+        }
+    }
+
+    fn backport_branch(&self, pull_request: &github::PullRequest, label: &str) -> Option<String> {
+        self.config.repos().backport_branch(&self.data.repository, &pull_request.base.ref_name, label)
+    }
 
-        let req = pr_merge::req(&self.data.repository, pull_request, &target_branch);
+    fn send_pr_merge(&self, pull_request: &github::PullRequest, target_branch: &str) {
+        let req = pr_merge::req(&self.data.repository, pull_request, target_branch);
         if let Err(e) = self.pr_merge.send(req) {
             error!("Error sending merge request message: {}", e)
         }
+        self.send_mirror_merges(pull_request, target_branch);
+    }
+
+    // the same backport into each of the repo's mirrors
+    fn send_mirror_merges(&self, pull_request: &github::PullRequest, target_branch: &str) {
+        let repo = &self.data.repository;
+        for mirror in self.config.repos().backport_mirrors(repo, &pull_request.base.ref_name) {
+            let sent = pr_merge::mirror_req(repo, pull_request, target_branch, &mirror).and_then(|req| {
+                self.pr_merge.send(req).map_err(|e| Error::from(format!("{}", e)))
+            });
+            if let Err(e) = sent {
+                error!("Error sending merge request message for {}: {}", mirror.repo, e)
+            }
+        }
+    }
+}
+
+// the start of a release's notes, for slack
+fn release_excerpt(body: &str) -> String {
+    const MAX_LINES: usize = 10;
+    const MAX_CHARS: usize = 500;
+
+    let lines: Vec<&str> = body.trim().lines().collect();
+    let mut excerpt = lines.iter().take(MAX_LINES).cloned().collect::<Vec<&str>>().join("\n");
+    let mut cut = lines.len() > MAX_LINES;
+    if excerpt.chars().count() > MAX_CHARS {
+        excerpt = excerpt.chars().take(MAX_CHARS).collect();
+        cut = true;
+    }
+    if cut {
+        excerpt += "\u{2026}";
+    }
+    excerpt
+}
+
+fn inline_comments_title(count: usize, messages: &Messages) -> String {
+    if count == 1 {
+        messages.format(messages::INLINE_COMMENT, &[])
+    } else {
+        messages.format(messages::INLINE_COMMENTS, &[("count", &count.to_string())])
+    }
+}
+
+// an inline comment's text, after the file and line it's on and the code leading up to it
+fn with_code_context(comment: &github::CommentLike, text: String) -> String {
+    let location = match comment.location() {
+        Some(location) => location,
+        None => return text,
+    };
+    let code = comment.diff_hunk().map(slack::diff_context).unwrap_or(String::new());
+    if code.is_empty() {
+        format!("*{}*: {}", location, text)
+    } else {
+        format!("*{}*\n{}\n{}", location, code, text)
+    }
+}
+
+// a preview of the first few of a review's inline comments, each with the code it's on
+fn inline_comments_attachment(
+    comments: &[github::Comment],
+    title: String,
+    url: &str,
+    messages: &Messages,
+) -> SlackAttachment {
+    const MAX_PREVIEWS: usize = 3;
+    const MAX_CHARS: usize = 200;
+
+    let mut previews: Vec<String> = comments
+        .iter()
+        .take(MAX_PREVIEWS)
+        .map(|c| with_code_context(&c, slack::comment_excerpt((&c).body(), &c.html_url, MAX_CHARS)))
+        .collect();
+    if comments.len() > MAX_PREVIEWS {
+        let count = (comments.len() - MAX_PREVIEWS).to_string();
+        previews.push(messages.format(messages::MORE_INLINE_COMMENTS, &[("count", &count)]));
+    }
+
+    SlackAttachmentBuilder::new(&previews.join("\n"))
+        .title(title)
+        .title_link(url)
+        .build()
+}
+
+// a review's or comment's body along with its inline comments, to look for @mentions in
+fn comment_bodies(body: &str, inline: &[github::Comment]) -> String {
+    let mut bodies = vec![body];
+    bodies.extend(inline.iter().map(|c| c.body.as_ref().map_or("", |b| b.as_str())));
+    bodies.join("\n")
+}
+
+fn pull_request_title(pull_request: &github::PullRequest, messages: &Messages) -> String {
+    messages.format(messages::PR_TITLE, &[("number", &pull_request.number.to_string()), ("title", &pull_request.title)])
+}
+
+fn pull_request_attachments(pull_request: &github::PullRequest, messages: &Messages) -> Vec<SlackAttachment> {
+    vec![
+        SlackAttachmentBuilder::new("")
+            .title(pull_request_title(pull_request, messages))
+            .title_link(pull_request.html_url.as_str())
+            .build(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_delivery() {
+        let handled = HandledDeliveries::new(None);
+        let failed = FailedDeliveries::new(None);
+
+        let resp = queue_delivery(&handled, "a", "pull_request", || Ok(()));
+        assert_eq!(StatusCode::Accepted, resp.status());
+
+        // github redelivers it before the first one got its turn
+        let resp = queue_delivery(&handled, "a", "pull_request", || panic!("queued twice"));
+        assert_eq!(StatusCode::Ok, resp.status());
+
+        finish_delivery(&handled, &failed, "a", "pull_request", StatusCode::Ok, "pr");
+        let resp = queue_delivery(&handled, "a", "pull_request", || panic!("queued twice"));
+        assert_eq!(StatusCode::Ok, resp.status());
+        assert!(failed.list().is_empty());
+    }
+
+    #[test]
+    fn test_queue_delivery_full() {
+        let handled = HandledDeliveries::new(None);
+
+        let resp = queue_delivery(&handled, "a", "pull_request", || Err("Event queue is full".into()));
+        assert_eq!(StatusCode::ServiceUnavailable, resp.status());
+
+        // so that github's redelivery gets in once there's room
+        let resp = queue_delivery(&handled, "a", "pull_request", || Ok(()));
+        assert_eq!(StatusCode::Accepted, resp.status());
+    }
+
+    #[test]
+    fn test_queued_delivery_failed() {
+        let handled = HandledDeliveries::new(None);
+        let failed = FailedDeliveries::new(None);
+
+        queue_delivery(&handled, "a", "pull_request", || Ok(()));
+        finish_delivery(&handled, &failed, "a", "pull_request", StatusCode::InternalServerError, "slack is down");
+
+        let failures = failed.list();
+        assert_eq!(1, failures.len());
+        assert_eq!("a", failures[0].id);
+        assert_eq!("500 Internal Server Error: slack is down", failures[0].error);
+
+        // github won't redeliver it by itself, but one from the webhook settings gets handled
+        let resp = queue_delivery(&handled, "a", "pull_request", || Ok(()));
+        assert_eq!(StatusCode::Accepted, resp.status());
     }
 }
@@ -20,18 +20,17 @@ impl GithubWebhookVerifier {
     }
 
     pub fn is_valid(&self, data: &[u8], signature: &str) -> bool {
-        // assume it starts with 'sha1='
-        if signature.len() < 6 {
-            error!("Invalid signature value: {}", signature);
+        // github signs with sha1, bitbucket server with sha256
+        let (algorithm, sig_hex) = if signature.starts_with("sha1=") {
+            (&digest::SHA1, &signature[5..])
+        } else if signature.starts_with("sha256=") {
+            (&digest::SHA256, &signature[7..])
+        } else {
+            error!("Invalid signature value. Expected sha1 or sha256: {}", signature);
             return false;
-        }
-        let sig_prefix = &signature[0..5];
-        if sig_prefix != "sha1=" {
-            error!("Invalid signature value. Expected sha1: {}", signature);
-            return false;
-        }
+        };
 
-        let sig_bytes: Vec<u8> = match signature[5..].from_hex() {
+        let sig_bytes: Vec<u8> = match sig_hex.from_hex() {
             Ok(s) => s,
             Err(e) => {
                 error!("Invalid hex value. {}", e);
@@ -39,7 +38,7 @@ impl GithubWebhookVerifier {
             }
         };
 
-        let key = hmac::VerificationKey::new(&digest::SHA1, self.secret.as_bytes());
+        let key = hmac::VerificationKey::new(algorithm, self.secret.as_bytes());
         match hmac::verify(&key, data, &sig_bytes) {
             Ok(_) => {
                 debug!("Signature verified!");
@@ -73,6 +72,21 @@ mod tests {
         assert!(verifier.is_valid(msg.as_bytes(), &signature_hex));
     }
 
+    #[test]
+    fn verify_sig_valid_sha256() {
+        let key_value = String::from("this is my secret key!");
+        let key = hmac::SigningKey::new(&digest::SHA256, key_value.as_bytes());
+
+        let msg = "a message from bitbucket.";
+        let signature = hmac::sign(&key, msg.as_bytes());
+        let signature_hex = "sha256=".to_string() + signature.as_ref().to_hex().as_str();
+
+        let verifier = GithubWebhookVerifier { secret: key_value.clone() };
+
+        assert!(verifier.is_valid(msg.as_bytes(), &signature_hex));
+        assert!(!verifier.is_valid(msg.as_bytes(), &signature_hex.replace("sha256=", "sha1=")));
+    }
+
     #[test]
     fn verify_sig_wrong_digest() {
         let key_value = String::from("this is my secret key!");
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use futures::Future;
+use futures::Stream;
+use hyper::StatusCode;
+use hyper::header::Headers;
+use hyper::server::{Request, Response};
+use ring::constant_time;
+
+use gitlab;
+use server::github_handler::GithubHandlerState;
+use server::http::{FutureResponse, Handler};
+
+// Gitlab webhooks: their events are turned into the github events they amount to and handled
+// just like those, against the gitlab API.
+pub struct GitlabHandler {
+    state: Arc<GithubHandlerState>,
+}
+
+impl GitlabHandler {
+    pub fn from_state(state: Arc<GithubHandlerState>) -> Box<GitlabHandler> {
+        Box::new(GitlabHandler { state: state })
+    }
+}
+
+impl Handler for GitlabHandler {
+    fn handle(&self, req: Request) -> FutureResponse {
+        let secret = self.state.config.gitlab.as_ref().map(|c| c.webhook_secret.as_str());
+        if !is_token_valid(secret, req.headers()) {
+            return self.respond_with(StatusCode::Forbidden, "Invalid token");
+        }
+
+        let event: String = match req.headers().get_raw("x-gitlab-event") {
+            Some(ref h) if h.len() == 1 => String::from_utf8_lossy(&h[0]).into_owned(),
+            None | Some(..) => {
+                error!("Expected to find exactly one event header");
+                return self.respond_with(StatusCode::BadRequest, "Expected to find exactly one event header");
+            }
+        };
+
+        let state = self.state.clone();
+        Box::new(req.body().concat2().map(move |body| {
+            let events = match gitlab::hook_events(&event, &body) {
+                Ok(e) => e,
+                Err(e) => {
+                    error!("{}\n---\n{}\n---\n", e, String::from_utf8_lossy(&body));
+                    state.report_error(&format!("{}", e), Some(&String::from_utf8_lossy(&body)));
+                    return Response::new().with_status(StatusCode::BadRequest).with_body(format!("{}", e));
+                }
+            };
+            if events.is_empty() {
+                return Response::new().with_status(StatusCode::Ok).with_body(format!("Unhandled event: {}", event));
+            }
+
+            let responses: Vec<String> = events
+                .into_iter()
+                .map(|(github_event, data)| state.handle_hook(&github_event, data).1)
+                .collect();
+            Response::new().with_status(StatusCode::Ok).with_body(responses.join(", "))
+        }))
+    }
+}
+
+// gitlab sends the webhook's secret token as it is, in X-Gitlab-Token
+fn is_token_valid(secret: Option<&str>, headers: &Headers) -> bool {
+    let secret = match secret {
+        Some(s) => s,
+        None => return false,
+    };
+    match headers.get_raw("x-gitlab-token") {
+        Some(ref h) if h.len() == 1 => constant_time::verify_slices_are_equal(&h[0], secret.as_bytes()).is_ok(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(tokens: Vec<&str>) -> Headers {
+        let mut headers = Headers::new();
+        if !tokens.is_empty() {
+            headers.set_raw("X-Gitlab-Token", tokens.iter().map(|t| t.as_bytes().to_vec()).collect::<Vec<_>>());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_is_token_valid() {
+        assert!(is_token_valid(Some("the-secret"), &headers(vec!["the-secret"])));
+
+        assert!(!is_token_valid(Some("the-secret"), &headers(vec!["the-secre"])));
+        assert!(!is_token_valid(Some("the-secret"), &headers(vec!["the-secret-and-more"])));
+        assert!(!is_token_valid(Some("the-secret"), &headers(vec![])));
+        assert!(!is_token_valid(Some("the-secret"), &headers(vec!["the-secret", "the-secret"])));
+    }
+
+    #[test]
+    fn test_is_token_valid_without_gitlab() {
+        assert!(!is_token_valid(None, &headers(vec!["the-secret"])));
+        assert!(!is_token_valid(None, &headers(vec![])));
+    }
+}
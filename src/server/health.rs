@@ -0,0 +1,141 @@
+use std::sync::{Arc, Mutex};
+
+use hyper::StatusCode;
+use hyper::header::ContentType;
+use hyper::server::{Request, Response};
+use serde_json;
+use tokio_core::reactor::Remote;
+
+use config::Config;
+use errors::*;
+use quiet_hours;
+use server::github_handler::GithubHandlerState;
+use server::http::{FutureResponse, Handler};
+use slack;
+use slack_api::SlackApi;
+
+// how long to trust the github and slack checks: probes come every few seconds
+const OK_CACHE_SECS: i64 = 60;
+const FAILED_CACHE_SECS: i64 = 10;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+impl Check {
+    fn new(name: &str, result: Result<()>) -> Check {
+        Check {
+            name: name.to_string(),
+            ok: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<Check>,
+}
+
+// What /ready checks: that each host's API token works, that slack takes messages, and that
+// each host's pr_merge worker is still running.
+pub struct HealthChecks {
+    config: Arc<Config>,
+    states: Vec<Arc<GithubHandlerState>>,
+    core_remote: Remote,
+    // (checked at, checks) for the checks that go over the network
+    cached: Mutex<Option<(i64, Vec<Check>)>>,
+}
+
+impl HealthChecks {
+    pub fn new(config: Arc<Config>, states: Vec<Arc<GithubHandlerState>>, core_remote: Remote) -> HealthChecks {
+        HealthChecks {
+            config: config,
+            states: states,
+            core_remote: core_remote,
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub fn readiness(&self, now: i64) -> Readiness {
+        let mut checks = self.remote_checks(now);
+        for state in &self.states {
+            let host = state.github_session.github_host();
+            let result = if state.is_pr_merge_alive() { Ok(()) } else { Err("pr_merge worker stopped".into()) };
+            checks.push(Check::new(&format!("pr_merge {}", host), result));
+        }
+
+        Readiness {
+            ready: checks.iter().all(|c| c.ok),
+            checks: checks,
+        }
+    }
+
+    fn remote_checks(&self, now: i64) -> Vec<Check> {
+        // not locked while checking: a slow github or slack shouldn't hold up every other probe
+        if let Some((checked_at, ref checks)) = *self.cached.lock().unwrap() {
+            let ttl = if checks.iter().all(|c| c.ok) { OK_CACHE_SECS } else { FAILED_CACHE_SECS };
+            if now - checked_at < ttl {
+                return checks.clone();
+            }
+        }
+
+        let mut checks = vec![];
+        for state in &self.states {
+            let github = &state.github_session;
+            let result = github.get_user(github.user().login()).map(|_| ());
+            checks.push(Check::new(&format!("api {}", github.github_host()), result));
+        }
+
+        let result = match self.config.main.slack_api_token {
+            Some(ref token) if !token.is_empty() => SlackApi::new(self.core_remote.clone(), token).check_auth(),
+            _ => slack::check_webhook(self.core_remote.clone(), &self.config.main.slack_webhook_url),
+        };
+        checks.push(Check::new("slack", result));
+
+        *self.cached.lock().unwrap() = Some((now, checks.clone()));
+        checks
+    }
+}
+
+// Liveness: answers as long as the server does.
+pub struct HealthHandler;
+
+impl Handler for HealthHandler {
+    fn handle(&self, _: Request) -> FutureResponse {
+        self.respond(json_response(StatusCode::Ok, &json!({"status": "ok"})))
+    }
+}
+
+pub struct ReadyHandler {
+    checks: Arc<HealthChecks>,
+}
+
+impl ReadyHandler {
+    pub fn new(checks: Arc<HealthChecks>) -> Box<ReadyHandler> {
+        Box::new(ReadyHandler { checks: checks })
+    }
+}
+
+impl Handler for ReadyHandler {
+    fn handle(&self, _: Request) -> FutureResponse {
+        let readiness = self.checks.readiness(quiet_hours::now());
+        for check in readiness.checks.iter().filter(|c| !c.ok) {
+            error!("Readiness check {} failed: {}", check.name, check.error.as_ref().unwrap_or(&String::new()));
+        }
+
+        let status = if readiness.ready { StatusCode::Ok } else { StatusCode::ServiceUnavailable };
+        self.respond(json_response(status, &readiness))
+    }
+}
+
+fn json_response<T: ::serde::Serialize>(status: StatusCode, body: &T) -> Response {
+    match serde_json::to_string(body) {
+        Ok(json) => Response::new().with_status(status).with_header(ContentType::json()).with_body(json),
+        Err(e) => Response::new().with_status(StatusCode::InternalServerError).with_body(format!("{}", e)),
+    }
+}
@@ -6,7 +6,7 @@ use hyper::server::{Request, Response};
 use ring::{digest, pbkdf2};
 use rustc_serialize::hex::{FromHex, ToHex};
 
-use config::Config;
+use config::{Config, Role};
 use ldap_auth;
 use server::http::{Filter, FilterResult, FutureResponse, Handler, parse_json};
 use server::sessions::Sessions;
@@ -43,8 +43,12 @@ pub struct LogoutHandler {
     sessions: Arc<Sessions>,
 }
 
-pub struct LoginSessionFilter {
+// Lets requests through that have a session from logging in, or an API token from the config, whose role is
+// at least `role`.
+pub struct AuthFilter {
     sessions: Arc<Sessions>,
+    config: Arc<Config>,
+    role: Role,
 }
 
 impl LoginHandler {
@@ -62,9 +66,13 @@ impl LogoutHandler {
     }
 }
 
-impl LoginSessionFilter {
-    pub fn new(sessions: Arc<Sessions>) -> Box<LoginSessionFilter> {
-        Box::new(LoginSessionFilter { sessions: sessions })
+impl AuthFilter {
+    pub fn new(sessions: Arc<Sessions>, config: Arc<Config>, role: Role) -> Box<AuthFilter> {
+        Box::new(AuthFilter {
+            sessions: sessions,
+            config: config,
+            role: role,
+        })
     }
 }
 
@@ -81,6 +89,20 @@ fn get_session(req: &Request) -> Option<String> {
     }
 }
 
+fn get_api_token(req: &Request) -> Option<String> {
+    match req.headers().get_raw("authorization") {
+        Some(ref h) if h.len() == 1 => {
+            let value = String::from_utf8_lossy(&h[0]).into_owned();
+            if value.starts_with("Bearer ") {
+                Some(value["Bearer ".len()..].trim().to_string())
+            } else {
+                None
+            }
+        }
+        None | Some(..) => None,
+    }
+}
+
 impl Handler for LoginHandler {
     fn handle(&self, req: Request) -> FutureResponse {
         let config = self.config.clone();
@@ -88,6 +110,7 @@ impl Handler for LoginHandler {
 
         parse_json(req, move |login_req: LoginRequest| {
             let mut success = None;
+            let mut role = Role::Admin;
             if let Some(ref admin) = config.admin {
                 if admin.name == login_req.username {
                     if verify_password(&login_req.password, &admin.salt, &admin.pass_hash) {
@@ -105,6 +128,7 @@ impl Handler for LoginHandler {
                     match ldap_auth::auth(&login_req.username, &login_req.password, ldap) {
                         Ok(true) => {
                             info!("LDAP auth successfor user: {}", login_req.username);
+                            role = ldap.role.unwrap_or(Role::Admin);
                             success = Some(true)
                         }
                         Ok(false) => warn!("LDAP auth failure for user: {}", login_req.username),
@@ -114,9 +138,10 @@ impl Handler for LoginHandler {
             }
 
             if success == Some(true) {
-                let sess_id = sessions.new_session();
+                let sess_id = sessions.new_session_with_role(role);
                 let json = json!({
                     "session": sess_id,
+                    "role": role,
                 });
 
                 Response::new().with_header(ContentType::json()).with_body(json.to_string())
@@ -144,17 +169,21 @@ impl Handler for LogoutHandler {
     }
 }
 
-impl Filter for LoginSessionFilter {
+impl Filter for AuthFilter {
     fn filter(&self, req: &Request) -> FilterResult {
-        let sess: String = match get_session(&req) {
-            Some(s) => s.to_string(),
-            None => return FilterResult::Halt(invalid_session()),
+        let role = match get_session(&req) {
+            Some(sess) => self.sessions.session_role(&sess),
+            None => get_api_token(&req).and_then(|token| self.config.api_token_role(&token)),
         };
 
-        if self.sessions.is_valid_session(&sess) {
-            FilterResult::Continue
-        } else {
-            FilterResult::Halt(invalid_session())
+        match role {
+            None => FilterResult::Halt(invalid_session()),
+            Some(role) if role < self.role => {
+                FilterResult::Halt(Response::new().with_status(StatusCode::Forbidden).with_body(
+                    format!("Requires the {:?} role", self.role).to_lowercase(),
+                ))
+            }
+            Some(_) => FilterResult::Continue,
         }
     }
 }
@@ -163,6 +192,53 @@ impl Filter for LoginSessionFilter {
 mod tests {
     use super::*;
 
+    use config::ApiTokenConfig;
+    use hyper::Method;
+
+    fn is_allowed(filter: &AuthFilter, header: &str, value: &str) -> bool {
+        let mut req = Request::new(Method::Get, "/api/users".parse().unwrap());
+        req.headers_mut().set_raw(header.to_string(), value.to_string());
+        match filter.filter(&req) {
+            FilterResult::Continue => true,
+            FilterResult::Halt(_) => false,
+        }
+    }
+
+    #[test]
+    fn test_auth_filter() {
+        let mut config = Config::empty_config();
+        config.api_tokens = Some(vec![
+            ApiTokenConfig {
+                name: "dashboard".into(),
+                token: "viewer-token".into(),
+                role: Role::Viewer,
+            },
+            ApiTokenConfig {
+                name: "deploys".into(),
+                token: "admin-token".into(),
+                role: Role::Admin,
+            },
+        ]);
+        let config = Arc::new(config);
+        let sessions = Arc::new(Sessions::new());
+        let admin_session = sessions.new_session();
+        let viewer_session = sessions.new_session_with_role(Role::Viewer);
+
+        let viewers = AuthFilter::new(sessions.clone(), config.clone(), Role::Viewer);
+        assert!(is_allowed(&viewers, "session", &viewer_session));
+        assert!(is_allowed(&viewers, "session", &admin_session));
+        assert!(is_allowed(&viewers, "Authorization", "Bearer viewer-token"));
+        assert!(!is_allowed(&viewers, "Authorization", "Bearer wrong-token"));
+        assert!(!is_allowed(&viewers, "Authorization", "viewer-token"));
+        assert!(!is_allowed(&viewers, "session", "no-such-session"));
+
+        let admins = AuthFilter::new(sessions.clone(), config.clone(), Role::Admin);
+        assert!(is_allowed(&admins, "session", &admin_session));
+        assert!(!is_allowed(&admins, "session", &viewer_session));
+        assert!(is_allowed(&admins, "Authorization", "Bearer admin-token"));
+        assert!(!is_allowed(&admins, "Authorization", "Bearer viewer-token"));
+    }
+
     #[test]
     fn test_password() {
         let pw_hash = store_password("the-pass", "some-salt");
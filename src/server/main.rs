@@ -1,27 +1,28 @@
-use std::fs;
-use std::io;
-use std::io::Seek;
 use std::net::SocketAddr;
 use std::sync::{Arc, mpsc};
 use std::thread;
 
 use hyper::server::Http;
-use rustls;
-use rustls::internal::pemfile;
 use tokio_core::reactor::Core;
 use tokio_proto;
 use tokio_rustls;
 
 use config::Config;
+use config_watcher;
+use bitbucket::api::BitbucketSession;
+use error_reporting::{self, ErrorReporter};
 use errors::*;
 use github;
 use github::api::GithubSession;
+use gitlab::api::GitlabSession;
 use jira;
 use jira::api::JiraSession;
-use server::github_handler::GithubHandlerState;
+use server::github_handler::{EventQueue, GithubHandlerState};
+use server::health::HealthChecks;
 use server::octobot_service::OctobotService;
 use server::redirect_service::RedirectService;
 use server::sessions::Sessions;
+use server::tls;
 
 pub fn start(config: Config) -> Result<()> {
     let config = Arc::new(config);
@@ -42,11 +43,18 @@ pub fn start(config: Config) -> Result<()> {
     }));
     let core_remote = core_rx.recv().expect("recv core handle");
 
-    let github: Arc<github::api::Session> =
-        match GithubSession::new(core_remote.clone(), &config.github.host, &config.github.api_token) {
-            Ok(s) => Arc::new(s),
-            Err(e) => panic!("Error initiating github session: {}", e),
-        };
+    let error_reporter = config.error_reporting.as_ref().and_then(|c| {
+        match ErrorReporter::new(core_remote.clone(), c) {
+            Ok(r) => Some(Arc::new(r)),
+            Err(e) => {
+                error!("Not reporting errors: {}", e);
+                None
+            }
+        }
+    });
+    if let Some(ref reporter) = error_reporter {
+        error_reporting::report_panics(reporter.clone());
+    }
 
     let jira: Option<Arc<jira::api::Session>>;
     if let Some(ref jira_config) = config.jira {
@@ -71,13 +79,7 @@ pub fn start(config: Config) -> Result<()> {
     let tls;
     if let Some(ref cert_file) = config.main.ssl_cert_file {
         if let Some(ref key_file) = config.main.ssl_key_file {
-            let key = load_private_key(key_file);
-            let certs = load_certs(cert_file);
-
-            let mut the_cfg = rustls::ServerConfig::new();
-            the_cfg.set_single_cert(certs, key);
-
-            tls = Some(Arc::new(the_cfg));
+            tls = Some(tls::server_config(cert_file, key_file, config.main.ssl_reload_interval_secs));
         } else {
             warn!("Warning: No SSL configured");
             tls = None;
@@ -87,12 +89,66 @@ pub fn start(config: Config) -> Result<()> {
         tls = None;
     }
 
+    config_watcher::start(config.clone());
+
     let ui_sessions = Arc::new(Sessions::new());
-    let github_handler_state =
-        Arc::new(GithubHandlerState::new(config.clone(), github.clone(), jira.clone(), core_remote.clone()));
+    let mut github_handler_states = vec![];
+    for github_config in config.github_configs() {
+        let github: Arc<github::api::Session> = match GithubSession::new(core_remote.clone(), github_config) {
+            Ok(s) => Arc::new(s),
+            Err(e) => panic!("Error initiating github session for {}: {}", github_config.host, e),
+        };
+        github_handler_states.push(Arc::new(
+            GithubHandlerState::new(config.clone(), github, jira.clone(), core_remote.clone())
+                .with_error_reporter(error_reporter.clone()),
+        ));
+    }
+
+    let gitlab_handler_state = config.gitlab.as_ref().map(|gitlab_config| {
+        let gitlab: Arc<github::api::Session> = match GitlabSession::new(core_remote.clone(), gitlab_config) {
+            Ok(s) => Arc::new(s),
+            Err(e) => panic!("Error initiating gitlab session: {}", e),
+        };
+        Arc::new(
+            GithubHandlerState::new(config.clone(), gitlab, jira.clone(), core_remote.clone())
+                .with_error_reporter(error_reporter.clone()),
+        )
+    });
+
+    let bitbucket_handler_state = config.bitbucket.as_ref().map(|bitbucket_config| {
+        let bitbucket = match BitbucketSession::new(core_remote.clone(), bitbucket_config) {
+            Ok(s) => Arc::new(s),
+            Err(e) => panic!("Error initiating bitbucket session: {}", e),
+        };
+        let session: Arc<github::api::Session> = bitbucket.clone();
+        let state = Arc::new(
+            GithubHandlerState::new(config.clone(), session, jira.clone(), core_remote.clone())
+                .with_error_reporter(error_reporter.clone()),
+        );
+        (state, bitbucket)
+    });
+
+    let mut all_states = github_handler_states.clone();
+    all_states.extend(gitlab_handler_state.clone());
+    all_states.extend(bitbucket_handler_state.as_ref().map(|&(ref state, _)| state.clone()));
+    // gitlab and bitbucket repos get stale PR and review reminders too, from their own host's config and state
+    for state in &all_states {
+        state.start_schedulers();
+    }
+    let health_checks = Arc::new(HealthChecks::new(config.clone(), all_states, core_remote.clone()));
+    let github_events = Arc::new(EventQueue::new());
 
     let main_service = move || {
-        Ok(OctobotService::new(config.clone(), ui_sessions.clone(), github_handler_state.clone(), core_remote.clone()))
+        Ok(OctobotService::new(
+            config.clone(),
+            ui_sessions.clone(),
+            github_handler_states.clone(),
+            github_events.clone(),
+            gitlab_handler_state.clone(),
+            bitbucket_handler_state.clone(),
+            health_checks.clone(),
+            core_remote.clone(),
+        ))
     };
     match tls {
         Some(tls) => {
@@ -128,27 +184,3 @@ pub fn start(config: Config) -> Result<()> {
 
     Ok(())
 }
-
-fn load_certs(filename: &str) -> Vec<rustls::Certificate> {
-    let certfile = fs::File::open(filename).expect("cannot open certificate file");
-    let mut reader = io::BufReader::new(certfile);
-    pemfile::certs(&mut reader).unwrap()
-}
-
-fn load_private_key(filename: &str) -> rustls::PrivateKey {
-    let keyfile = fs::File::open(filename).expect("cannot open private key file");
-    let mut reader = io::BufReader::new(keyfile);
-
-    let keys = pemfile::rsa_private_keys(&mut reader).unwrap();
-    if keys.len() == 1 {
-        return keys[0].clone();
-    }
-
-    reader.seek(io::SeekFrom::Start(0)).unwrap();
-    let keys = pemfile::pkcs8_private_keys(&mut reader).unwrap();
-    if keys.len() == 1 {
-        return keys[0].clone();
-    }
-
-    panic!("Unable to find private key in file {}", filename);
-}
@@ -1,10 +1,15 @@
 mod admin;
+pub mod bitbucket_handler;
 pub mod github_handler;
 mod github_verify;
+pub mod gitlab_handler;
+pub mod health;
 mod html_handler;
 mod http;
 mod octobot_service;
 mod redirect_service;
 pub mod login;
 mod sessions;
+mod slack_commands;
+mod tls;
 pub mod main;
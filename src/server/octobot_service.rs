@@ -6,19 +6,28 @@ use hyper::server::{Request, Response, Service};
 use time;
 use tokio_core::reactor::Remote;
 
-use config::Config;
+use bitbucket::api::BitbucketSession;
+use config::{Config, Role};
 use server::admin;
-use server::github_handler::{GithubHandler, GithubHandlerState};
+use server::bitbucket_handler::BitbucketHandler;
+use server::github_handler::{EventQueue, GithubHandler, GithubHandlerState};
+use server::gitlab_handler::GitlabHandler;
+use server::health::{HealthChecks, HealthHandler, ReadyHandler};
 use server::html_handler::HtmlHandler;
 use server::http::{FilteredHandler, FutureResponse, Handler, NotFoundHandler};
-use server::login::{LoginHandler, LoginSessionFilter, LogoutHandler};
+use server::login::{AuthFilter, LoginHandler, LogoutHandler};
 use server::sessions::Sessions;
+use server::slack_commands::{SlackActionHandler, SlackCommandHandler};
 use util;
 
 pub struct OctobotService {
     config: Arc<Config>,
     ui_sessions: Arc<Sessions>,
-    github_handler_state: Arc<GithubHandlerState>,
+    github_handler_states: Vec<Arc<GithubHandlerState>>,
+    github_events: Arc<EventQueue>,
+    gitlab_handler_state: Option<Arc<GithubHandlerState>>,
+    bitbucket_handler_state: Option<(Arc<GithubHandlerState>, Arc<BitbucketSession>)>,
+    health_checks: Arc<HealthChecks>,
     core_remote: Remote,
 }
 
@@ -26,13 +35,21 @@ impl OctobotService {
     pub fn new(
         config: Arc<Config>,
         ui_sessions: Arc<Sessions>,
-        github_handler_state: Arc<GithubHandlerState>,
+        github_handler_states: Vec<Arc<GithubHandlerState>>,
+        github_events: Arc<EventQueue>,
+        gitlab_handler_state: Option<Arc<GithubHandlerState>>,
+        bitbucket_handler_state: Option<(Arc<GithubHandlerState>, Arc<BitbucketSession>)>,
+        health_checks: Arc<HealthChecks>,
         core_remote: Remote,
     ) -> OctobotService {
         OctobotService {
             config: config,
             ui_sessions: ui_sessions,
-            github_handler_state: github_handler_state,
+            github_handler_states: github_handler_states,
+            github_events: github_events,
+            gitlab_handler_state: gitlab_handler_state,
+            bitbucket_handler_state: bitbucket_handler_state,
+            health_checks: health_checks,
             core_remote: core_remote,
         }
     }
@@ -70,9 +87,12 @@ impl OctobotService {
     fn route(&self, req: &Request) -> Box<Handler> {
         use hyper::Method::{Get, Post};
 
+        // looking is for viewers, changing anything is for admins
+        let role = if *req.method() == Get { Role::Viewer } else { Role::Admin };
+
         // API routes
         if req.path().starts_with("/api") {
-            let filter = LoginSessionFilter::new(self.ui_sessions.clone());
+            let filter = AuthFilter::new(self.ui_sessions.clone(), self.config.clone(), role);
 
             return FilteredHandler::new(
                 filter,
@@ -81,15 +101,53 @@ impl OctobotService {
                     (&Post, "/api/users") => admin::UpdateUsers::new(self.config.clone()),
                     (&Get, "/api/repos") => admin::GetRepos::new(self.config.clone()),
                     (&Post, "/api/repos") => admin::UpdateRepos::new(self.config.clone()),
+                    (_, "/api/admin/users") => admin::AdminUsers::new(self.config.clone()),
+                    (_, "/api/admin/repos") => {
+                        admin::AdminRepos::new(self.config.clone(), self.github_handler_states.clone())
+                    }
                     (&Post, "/api/merge-versions") => {
                         admin::MergeVersions::new(self.config.clone(), self.core_remote.clone())
                     }
+                    (&Get, "/api/delivery-failures") => {
+                        admin::FailedDeliveries::new(self.github_handler_states.clone())
+                    }
+                    (&Get, "/api/slack-failures") => {
+                        admin::FailedSlackMessages::new(self.github_handler_states.clone())
+                    }
+                    (&Get, "/api/audit-log") => admin::AuditLogEntries::new(self.github_handler_states.clone()),
+                    (&Get, "/api/planned-actions") => {
+                        admin::AuditLogEntries::planned(self.github_handler_states.clone())
+                    }
 
                     _ => Box::new(NotFoundHandler),
                 },
             );
         }
 
+        if req.path().starts_with("/admin/replay/") {
+            let filter = AuthFilter::new(self.ui_sessions.clone(), self.config.clone(), role);
+            let handler: Box<Handler> = match *req.method() {
+                Post => {
+                    let delivery_id = &req.path()["/admin/replay/".len()..];
+                    admin::ReplayDelivery::new(self.github_handler_states.clone(), delivery_id)
+                }
+                _ => Box::new(NotFoundHandler),
+            };
+            return FilteredHandler::new(filter, handler);
+        }
+
+        if req.path().starts_with("/admin/slack-resend/") {
+            let filter = AuthFilter::new(self.ui_sessions.clone(), self.config.clone(), role);
+            let handler: Box<Handler> = match *req.method() {
+                Post => {
+                    let id = &req.path()["/admin/slack-resend/".len()..];
+                    admin::ResendSlackMessage::new(self.github_handler_states.clone(), id)
+                }
+                _ => Box::new(NotFoundHandler),
+            };
+            return FilteredHandler::new(filter, handler);
+        }
+
         // static routes
         match (req.method(), req.path()) {
             // web ui resources. kinda a funny way of doing this maybe, but avoids worries about
@@ -98,17 +156,50 @@ impl OctobotService {
             (&Get, "/login.html") => HtmlHandler::new("login.html", include_str!("../../src/assets/login.html")),
             (&Get, "/users.html") => HtmlHandler::new("users.html", include_str!("../../src/assets/users.html")),
             (&Get, "/repos.html") => HtmlHandler::new("repos.html", include_str!("../../src/assets/repos.html")),
+            (&Get, "/mappings.html") => {
+                HtmlHandler::new("mappings.html", include_str!("../../src/assets/mappings.html"))
+            }
             (&Get, "/versions.html") => {
                 HtmlHandler::new("versions.html", include_str!("../../src/assets/versions.html"))
             }
             (&Get, "/app.js") => HtmlHandler::new("app.js", include_str!("../../src/assets/app.js")),
 
+            // probes
+            (&Get, "/health") => Box::new(HealthHandler),
+            (&Get, "/ready") => ReadyHandler::new(self.health_checks.clone()),
+
             // auth
             (&Post, "/auth/login") => LoginHandler::new(self.ui_sessions.clone(), self.config.clone()),
             (&Post, "/auth/logout") => LogoutHandler::new(self.ui_sessions.clone()),
 
             // hooks
-            (&Post, "/hooks/github") => GithubHandler::from_state(self.github_handler_state.clone()),
+            (&Post, "/hooks/github") => {
+                GithubHandler::from_states(self.github_handler_states.clone(), self.github_events.clone())
+            }
+            (&Post, "/hooks/gitlab") => {
+                match self.gitlab_handler_state {
+                    Some(ref state) => GitlabHandler::from_state(state.clone()),
+                    None => Box::new(NotFoundHandler),
+                }
+            }
+            (&Post, "/hooks/bitbucket") => {
+                match self.bitbucket_handler_state {
+                    Some((ref state, ref bitbucket)) => BitbucketHandler::new(state.clone(), bitbucket.clone()),
+                    None => Box::new(NotFoundHandler),
+                }
+            }
+
+            // checked against slack_signing_secret instead of logins
+            (&Post, "/slack/commands") => {
+                SlackCommandHandler::new(
+                    self.config.clone(),
+                    self.github_handler_states.clone(),
+                    self.core_remote.clone(),
+                )
+            }
+            (&Post, "/slack/actions") => {
+                SlackActionHandler::new(self.config.clone(), self.github_handler_states.clone())
+            }
 
             _ => Box::new(NotFoundHandler),
         }
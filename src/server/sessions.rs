@@ -5,6 +5,8 @@ use ring::rand::SecureRandom;
 use ring::rand::SystemRandom;
 use rustc_serialize::hex::ToHex;
 
+use config::Role;
+
 static SESSION_EXPIRY_SECS: u64 = 15 * 60;
 static PRUNE_SECS: u64 = 30;
 
@@ -18,6 +20,7 @@ struct Session {
     // Note: could change this to last_accessed, but then we'd have to worry about max
     // session time too. Keep it simple for now.
     created_at: Instant,
+    role: Role,
 }
 
 impl Sessions {
@@ -29,6 +32,10 @@ impl Sessions {
     }
 
     pub fn new_session(&self) -> String {
+        self.new_session_with_role(Role::Admin)
+    }
+
+    pub fn new_session_with_role(&self, role: Role) -> String {
         let mut bytes: [u8; 32] = [0; 32];
         // Doesn't look like SecureRandom, but docs claim it is.
         SystemRandom::new().fill(&mut bytes).expect("get random");
@@ -37,6 +44,7 @@ impl Sessions {
         let session = Session {
             id: sess_id.clone(),
             created_at: Instant::now(),
+            role: role,
         };
 
         self.sessions.write().unwrap().push(session);
@@ -50,10 +58,14 @@ impl Sessions {
     }
 
     pub fn is_valid_session(&self, sess_id: &str) -> bool {
+        self.session_role(sess_id).is_some()
+    }
+
+    pub fn session_role(&self, sess_id: &str) -> Option<Role> {
         self.prune(); // maybe prune out old sessions first
 
         let sessions = self.sessions.read().unwrap();
-        sessions.iter().find(|s| s.id == sess_id).is_some()
+        sessions.iter().find(|s| s.id == sess_id).map(|s| s.role)
     }
 
     fn needs_prune(&self) -> bool {
@@ -96,6 +108,17 @@ mod tests {
         assert_eq!(false, sessions.is_valid_session(&sess2));
     }
 
+    #[test]
+    fn test_session_roles() {
+        let sessions = Sessions::new();
+        let admin = sessions.new_session();
+        let viewer = sessions.new_session_with_role(Role::Viewer);
+
+        assert_eq!(Some(Role::Admin), sessions.session_role(&admin));
+        assert_eq!(Some(Role::Viewer), sessions.session_role(&viewer));
+        assert_eq!(None, sessions.session_role("no-such-session"));
+    }
+
     #[test]
     fn test_sessions_timeout() {
         let sessions = Sessions::new();
@@ -0,0 +1,921 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+
+use futures::{Future, Stream};
+use hyper::StatusCode;
+use hyper::header::{ContentType, Headers};
+use hyper::server::{Request, Response};
+use ring::{digest, hmac};
+use rustc_serialize::hex::FromHex;
+use serde_json;
+use time;
+use tokio_core::reactor::Remote;
+use url::{Url, form_urlencoded};
+
+use config::Config;
+use github;
+use http_client::HTTPClient;
+use quiet_hours;
+use release_notes;
+use server::github_handler::GithubHandlerState;
+use server::http::{FutureResponse, Handler};
+use slack::{SlackAttachment, SlackAttachmentBuilder};
+use users;
+
+// slack retries requests it doesn't hear back about, but never this late: older ones may be replayed
+const MAX_REQUEST_AGE_SECS: i64 = 5 * 60;
+const MAX_LISTED_PRS: usize = 25;
+// pull requests waiting for a review this long are shown in yellow, and then in red
+const LATE_REVIEW_SECS: i64 = 24 * 60 * 60;
+const OVERDUE_REVIEW_SECS: i64 = 3 * 24 * 60 * 60;
+
+const HELP: &'static str = "Usage:\n\
+    `/octobot prs`: open pull requests of the repos that post to this channel\n\
+    `/octobot prs me`: your open pull requests, and the ones assigned to you or waiting for your review\n\
+    `/octobot prs <org>/<repo>`: open pull requests of a repo\n\
+    `/octobot reviews`: pull requests assigned to you or waiting for your review that you haven't reviewed yet\n\
+    `/octobot backport <org>/<repo>#<number> <label>`: backport a merged pull request again for a `backport-` label\n\
+    `/octobot release-notes <org>/<repo> <from-tag> <to-tag> [publish]`: draft release notes for the pull requests \
+    merged between two tags, or publish them as the release of the second one\n\
+    `/octobot cut-release <org>/<repo> <version>`: cut a release branch for the version from the default branch\n\
+    `/octobot ooo [<github user>]`: you're out of office, optionally with someone to get your direct messages\n\
+    `/octobot back`: you're back";
+
+// the name of the button on failed workflow runs, whose value is the repo's url and the run's id
+pub const RERUN_ACTION: &'static str = "rerun-failed-jobs";
+
+// Answers slack slash commands, e.g. "/octobot prs", with a message that only the user who asked sees.
+pub struct SlackCommandHandler {
+    config: Arc<Config>,
+    states: Vec<Arc<GithubHandlerState>>,
+    core_remote: Remote,
+}
+
+// Answers clicks on the buttons of octobot's messages the same way.
+pub struct SlackActionHandler {
+    config: Arc<Config>,
+    states: Vec<Arc<GithubHandlerState>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Prs(PrsFilter),
+    Backport(String, u32, String),
+    // repo, from tag, to tag, and whether to publish the release
+    ReleaseNotes(String, String, String, bool),
+    // repo and version
+    CutRelease(String, String),
+    // whether the user is out of office, and their delegate
+    OutOfOffice(bool, Option<String>),
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+enum PrsFilter {
+    Channel,
+    Mine,
+    Repo(String),
+    Reviews,
+}
+
+#[derive(Serialize)]
+struct CommandResponse {
+    response_type: String,
+    text: String,
+    attachments: Vec<SlackAttachment>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    // repo url and workflow run id
+    RerunFailedJobs(String, u64),
+    Unknown,
+}
+
+// what slack posts as the "payload" form field: buttons of attachments have a name, buttons of blocks an action_id
+#[derive(Deserialize)]
+struct ActionPayload {
+    user: ActionUser,
+    actions: Vec<ClickedAction>,
+}
+
+#[derive(Deserialize)]
+struct ActionUser {
+    name: Option<String>,
+    username: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ClickedAction {
+    name: Option<String>,
+    action_id: Option<String>,
+    value: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ActionResponse {
+    response_type: String,
+    replace_original: bool,
+    text: String,
+}
+
+impl SlackCommandHandler {
+    pub fn new(
+        config: Arc<Config>,
+        states: Vec<Arc<GithubHandlerState>>,
+        core_remote: Remote,
+    ) -> Box<SlackCommandHandler> {
+        Box::new(SlackCommandHandler {
+            config: config,
+            states: states,
+            core_remote: core_remote,
+        })
+    }
+}
+
+impl Handler for SlackCommandHandler {
+    fn handle(&self, req: Request) -> FutureResponse {
+        let secret = match self.config.main.slack_signing_secret {
+            Some(ref s) if !s.is_empty() => s.clone(),
+            _ => return self.respond_with(StatusCode::NotFound, "Slack commands are not configured"),
+        };
+
+        let headers = req.headers().clone();
+        let config = self.config.clone();
+        let states = self.states.clone();
+        let core_remote = self.core_remote.clone();
+
+        Box::new(req.body().concat2().map(move |body| {
+            if !is_req_valid(&secret, &headers, &body, quiet_hours::now()) {
+                return Response::new().with_status(StatusCode::Forbidden).with_body("Invalid signature");
+            }
+
+            let params: HashMap<String, String> = form_urlencoded::parse(&body).into_owned().collect();
+            let param = |name: &str| params.get(name).map(|p| p.as_str()).unwrap_or("");
+            info!("Slack command from {}: {} {}", param("user_name"), param("command"), param("text"));
+
+            let resp = match parse_command(param("text")) {
+                Command::Prs(filter) => {
+                    // looking up the pull requests of every repo takes longer than slack waits for an answer
+                    let (config, states, core_remote) = (config.clone(), states.clone(), core_remote.clone());
+                    let user_name = param("user_name").to_string();
+                    let channel = param("channel_name").to_string();
+                    let response_url = param("response_url").to_string();
+                    thread::spawn(move || {
+                        let resp = list_prs(&config, &states, &filter, &user_name, &channel);
+                        respond_later(core_remote, &response_url, &resp);
+                    });
+                    ephemeral("Looking up the pull requests...", vec![])
+                }
+                Command::Backport(repo, number, label) => {
+                    backport(&config, &states, &repo, number, &label, param("user_name"))
+                }
+                Command::ReleaseNotes(repo, from, to, publish) => {
+                    release_notes(&config, &states, &repo, &from, &to, publish, param("user_name"))
+                }
+                Command::CutRelease(repo, version) => {
+                    cut_release(&config, &states, &repo, &version, param("user_name"))
+                }
+                Command::OutOfOffice(away, delegate) => {
+                    out_of_office(&config, &states, away, delegate.as_ref().map(|d| d.as_str()), param("user_name"))
+                }
+                Command::Help => ephemeral(HELP, vec![]),
+            };
+
+            match serde_json::to_string(&resp) {
+                Ok(json) => Response::new().with_header(ContentType::json()).with_body(json),
+                Err(e) => {
+                    error!("Error serializing slack command response: {}", e);
+                    Response::new().with_status(StatusCode::InternalServerError)
+                }
+            }
+        }))
+    }
+}
+
+impl SlackActionHandler {
+    pub fn new(config: Arc<Config>, states: Vec<Arc<GithubHandlerState>>) -> Box<SlackActionHandler> {
+        Box::new(SlackActionHandler {
+            config: config,
+            states: states,
+        })
+    }
+}
+
+impl Handler for SlackActionHandler {
+    fn handle(&self, req: Request) -> FutureResponse {
+        let secret = match self.config.main.slack_signing_secret {
+            Some(ref s) if !s.is_empty() => s.clone(),
+            _ => return self.respond_with(StatusCode::NotFound, "Slack actions are not configured"),
+        };
+
+        let headers = req.headers().clone();
+        let config = self.config.clone();
+        let states = self.states.clone();
+
+        Box::new(req.body().concat2().map(move |body| {
+            if !is_req_valid(&secret, &headers, &body, quiet_hours::now()) {
+                return Response::new().with_status(StatusCode::Forbidden).with_body("Invalid signature");
+            }
+
+            let params: HashMap<String, String> = form_urlencoded::parse(&body).into_owned().collect();
+            let (user_name, action) = match parse_action(params.get("payload").map(|p| p.as_str()).unwrap_or("")) {
+                Some(a) => a,
+                None => return Response::new().with_status(StatusCode::BadRequest).with_body("Invalid payload"),
+            };
+            info!("Slack action from {}: {:?}", user_name, action);
+
+            let resp = match action {
+                Action::RerunFailedJobs(repo_url, run_id) => {
+                    rerun_failed_jobs(&config, &states, &repo_url, run_id, &user_name)
+                }
+                Action::Unknown => action_reply("Sorry, octobot doesn't know that button"),
+            };
+
+            match serde_json::to_string(&resp) {
+                Ok(json) => Response::new().with_header(ContentType::json()).with_body(json),
+                Err(e) => {
+                    error!("Error serializing slack action response: {}", e);
+                    Response::new().with_status(StatusCode::InternalServerError)
+                }
+            }
+        }))
+    }
+}
+
+fn is_req_valid(secret: &str, headers: &Headers, body: &[u8], now: i64) -> bool {
+    let header = |name: &str| match headers.get_raw(name) {
+        Some(h) if h.len() == 1 => Some(String::from_utf8_lossy(&h[0]).into_owned()),
+        _ => None,
+    };
+    match (header("x-slack-request-timestamp"), header("x-slack-signature")) {
+        (Some(timestamp), Some(signature)) => is_valid_signature(secret, &timestamp, body, &signature, now),
+        _ => {
+            error!("Expected to find exactly one slack timestamp and signature header");
+            false
+        }
+    }
+}
+
+// See https://api.slack.com/authentication/verifying-requests-from-slack
+fn is_valid_signature(secret: &str, timestamp: &str, body: &[u8], signature: &str, now: i64) -> bool {
+    match timestamp.parse::<i64>() {
+        Ok(t) if (now - t).abs() <= MAX_REQUEST_AGE_SECS => (),
+        _ => {
+            error!("Invalid or stale slack request timestamp: {}", timestamp);
+            return false;
+        }
+    };
+    if !signature.starts_with("v0=") {
+        error!("Invalid slack signature version: {}", signature);
+        return false;
+    }
+    let sig_bytes: Vec<u8> = match signature[3..].from_hex() {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Invalid hex value. {}", e);
+            return false;
+        }
+    };
+
+    let mut data = format!("v0:{}:", timestamp).into_bytes();
+    data.extend_from_slice(body);
+    let key = hmac::VerificationKey::new(&digest::SHA256, secret.as_bytes());
+    match hmac::verify(&key, &data, &sig_bytes) {
+        Ok(_) => true,
+        Err(e) => {
+            error!("Slack signature verify failed: {}", e);
+            false
+        }
+    }
+}
+
+fn parse_command(text: &str) -> Command {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words == vec!["reviews"] {
+        return Command::Prs(PrsFilter::Reviews);
+    }
+    if words == vec!["back"] {
+        return Command::OutOfOffice(false, None);
+    }
+    if words.len() == 3 && words[0] == "cut-release" && words[1].contains('/') {
+        return Command::CutRelease(words[1].to_string(), words[2].to_string());
+    }
+    if words.len() <= 2 && words.first() == Some(&"ooo") {
+        return Command::OutOfOffice(true, words.get(1).map(|d| d.trim_left_matches('@').to_string()));
+    }
+    if words.len() == 3 && words[0] == "backport" {
+        let pr: Vec<&str> = words[1].splitn(2, '#').collect();
+        return match (pr.get(1).and_then(|n| n.parse::<u32>().ok()), pr[0].contains('/')) {
+            (Some(number), true) => Command::Backport(pr[0].to_string(), number, words[2].to_string()),
+            _ => Command::Help,
+        };
+    }
+    if (words.len() == 4 || words.len() == 5 && words[4] == "publish") && words[0] == "release-notes" &&
+        words[1].contains('/')
+    {
+        let publish = words.len() == 5;
+        return Command::ReleaseNotes(words[1].to_string(), words[2].to_string(), words[3].to_string(), publish);
+    }
+    if words.is_empty() || words.len() > 2 || words[0] != "prs" {
+        return Command::Help;
+    }
+    match words.get(1) {
+        None => Command::Prs(PrsFilter::Channel),
+        Some(&"me") => Command::Prs(PrsFilter::Mine),
+        Some(repo) if repo.contains('/') => Command::Prs(PrsFilter::Repo(repo.to_string())),
+        Some(_) => Command::Help,
+    }
+}
+
+// the user who clicked, and the first button they clicked
+fn parse_action(payload: &str) -> Option<(String, Action)> {
+    let payload: ActionPayload = match serde_json::from_str(payload) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Error parsing slack action payload: {}", e);
+            return None;
+        }
+    };
+    let user_name = payload.user.name.or(payload.user.username)?;
+    let clicked = payload.actions.into_iter().next()?;
+
+    let name = clicked.name.or(clicked.action_id).unwrap_or(String::new());
+    let value = clicked.value.unwrap_or(String::new());
+    let words: Vec<&str> = value.split_whitespace().collect();
+    let action = match (name.as_str(), words.get(1).and_then(|id| id.parse::<u64>().ok())) {
+        (RERUN_ACTION, Some(run_id)) if words.len() == 2 => Action::RerunFailedJobs(words[0].to_string(), run_id),
+        _ => Action::Unknown,
+    };
+    Some((user_name, action))
+}
+
+fn action_reply(text: &str) -> ActionResponse {
+    ActionResponse {
+        response_type: "ephemeral".into(),
+        replace_original: false,
+        text: text.into(),
+    }
+}
+
+fn ephemeral(text: &str, attachments: Vec<SlackAttachment>) -> CommandResponse {
+    CommandResponse {
+        response_type: "ephemeral".into(),
+        text: text.into(),
+        attachments: attachments,
+    }
+}
+
+// slack takes up to five more answers to a command at its response_url, for 30 minutes
+fn respond_later(core_remote: Remote, response_url: &str, resp: &CommandResponse) {
+    let client = HTTPClient::new(core_remote, "").with_headers(hashmap!{
+        "Content-Type" => "application/json".to_string(),
+    });
+    if let Err(e) = client.post_void(response_url, resp) {
+        error!("Error answering slack command at {}: {}", response_url, e);
+    }
+}
+
+fn list_prs(
+    config: &Config,
+    states: &Vec<Arc<GithubHandlerState>>,
+    filter: &PrsFilter,
+    user_name: &str,
+    channel: &str,
+) -> CommandResponse {
+    let mut found = vec![];
+    let mut errors = vec![];
+    let now = quiet_hours::now();
+
+    for state in states {
+        let host = state.github_session.github_host();
+        let login = config.users().github_login(host, user_name);
+        let repos = match *filter {
+            PrsFilter::Channel => config.repos().host_repos(host, Some(channel)),
+            PrsFilter::Mine | PrsFilter::Reviews => config.repos().host_repos(host, None),
+            PrsFilter::Repo(ref name) => {
+                // the first host is the default one
+                if host != states[0].github_session.github_host() {
+                    continue;
+                }
+                github::Repo::parse(&format!("https://{}/{}", host, name)).into_iter().collect()
+            }
+        };
+
+        for repo in repos {
+            let session = &state.github_session;
+            let prs = match session.get_pull_requests(repo.owner.login(), &repo.name, Some("open"), None) {
+                Ok(p) => p,
+                Err(e) => {
+                    error!("Error looking up open PRs for {}: {}", repo.full_name, e);
+                    errors.push(repo.full_name.clone());
+                    continue;
+                }
+            };
+
+            for pr in prs {
+                let keep = match *filter {
+                    PrsFilter::Mine => involves(&pr, &login),
+                    PrsFilter::Reviews => pr.user.login() != login && is_reviewer(&pr, &login),
+                    _ => true,
+                };
+                if !keep {
+                    continue;
+                }
+
+                // the others only need the reviews of the listed ones
+                let reviews = if *filter == PrsFilter::Reviews {
+                    let reviews = session.get_pull_request_reviews(repo.owner.login(), &repo.name, pr.number);
+                    let reviews = reviews.unwrap_or(vec![]);
+                    if reviews.iter().any(|r| r.user.login() == login) {
+                        continue;
+                    }
+                    Some(reviews)
+                } else {
+                    None
+                };
+                found.push((pr, (state.clone(), repo.clone(), reviews)));
+            }
+        }
+    }
+
+    let total = found.len();
+    let mut attachments = vec![];
+    for (pr, (state, repo, reviews)) in oldest_first(found, now) {
+        let reviews = reviews.unwrap_or_else(|| {
+            let reviews = state.github_session.get_pull_request_reviews(repo.owner.login(), &repo.name, pr.number);
+            reviews.unwrap_or(vec![])
+        });
+        let mut attachment = pr_attachment(&repo, &pr, &reviews, now);
+        // the older the redder
+        if *filter == PrsFilter::Reviews {
+            attachment.color = age_color(age_secs(&pr, now).unwrap_or(0)).map(|c| c.to_string());
+        }
+        attachments.push(attachment);
+    }
+
+    let what = match *filter {
+        PrsFilter::Reviews => "pull request(s) waiting for your review",
+        _ => "open pull request(s)",
+    };
+    let mut text = match attachments.len() {
+        0 => format!("No {}", what),
+        n if n < total => format!("The oldest {} of {} {}", n, total, what),
+        n => format!("{} {}", n, what),
+    };
+    if !errors.is_empty() {
+        text += &format!(" (couldn't look up {})", errors.join(", "));
+    }
+    ephemeral(&text, attachments)
+}
+
+// for users who can push to the repo, so that they don't have to take off and put back the label on github
+fn backport(
+    config: &Config,
+    states: &Vec<Arc<GithubHandlerState>>,
+    repo_name: &str,
+    number: u32,
+    label: &str,
+    user_name: &str,
+) -> CommandResponse {
+    // the first host is the default one
+    let state = match states.first() {
+        Some(s) => s,
+        None => return ephemeral("No github host is configured", vec![]),
+    };
+    let host = state.github_session.github_host();
+    let repo = match github::Repo::parse(&format!("https://{}/{}", host, repo_name)) {
+        Ok(r) => r,
+        Err(e) => return ephemeral(&format!("Invalid repo {}: {}", repo_name, e), vec![]),
+    };
+
+    let login = config.users().github_login(host, user_name);
+    match state.github_session.get_user_permission(repo.owner.login(), &repo.name, &login) {
+        Ok(ref p) if p == "admin" || p == "write" => (),
+        Ok(_) => return ephemeral(&format!("Only users with write access to {} can backport", repo_name), vec![]),
+        Err(e) => {
+            error!("Error checking permissions for slack backport: {}", e);
+            return ephemeral(&format!("Could not check your permissions: {}", e), vec![]);
+        }
+    };
+
+    match state.backport(&repo, number, label) {
+        Ok(branch) => ephemeral(&format!("Backporting {}#{} to {}", repo_name, number, branch), vec![]),
+        Err(e) => ephemeral(&format!("Could not backport {}#{}: {}", repo_name, number, e), vec![]),
+    }
+}
+
+// Publishing them as a release is for users who can push to the repo.
+fn release_notes(
+    config: &Config,
+    states: &Vec<Arc<GithubHandlerState>>,
+    repo_name: &str,
+    from_tag: &str,
+    to_tag: &str,
+    publish: bool,
+    user_name: &str,
+) -> CommandResponse {
+    // the first host is the default one
+    let state = match states.first() {
+        Some(s) => s,
+        None => return ephemeral("No github host is configured", vec![]),
+    };
+    let session = &state.github_session;
+    let host = session.github_host();
+    let repo = match github::Repo::parse(&format!("https://{}/{}", host, repo_name)) {
+        Ok(r) => r,
+        Err(e) => return ephemeral(&format!("Invalid repo {}: {}", repo_name, e), vec![]),
+    };
+
+    if publish {
+        let login = config.users().github_login(host, user_name);
+        match session.get_user_permission(repo.owner.login(), &repo.name, &login) {
+            Ok(ref p) if p == "admin" || p == "write" => (),
+            Ok(_) => {
+                return ephemeral(&format!("Only users with write access to {} can publish releases", repo_name), vec![])
+            }
+            Err(e) => {
+                error!("Error checking permissions for slack release notes: {}", e);
+                return ephemeral(&format!("Could not check your permissions: {}", e), vec![]);
+            }
+        };
+    }
+
+    let notes = match release_notes::merged_pull_requests(&**session, &repo, from_tag, to_tag) {
+        Ok(n) => n,
+        Err(e) => return ephemeral(&format!("Could not look up the pull requests: {}", e), vec![]),
+    };
+    let body = release_notes::render(&notes, &config.repos().release_note_sections(&repo));
+
+    if !publish {
+        let text = format!("Release notes for {} {}...{}:\n```\n{}\n```", repo_name, from_tag, to_tag, body);
+        return ephemeral(&text, vec![]);
+    }
+    match session.create_release(repo.owner.login(), &repo.name, to_tag, to_tag, &body) {
+        Ok(()) => ephemeral(&format!("Published release {} of {}", to_tag, repo_name), vec![]),
+        Err(e) => ephemeral(&format!("Could not publish release {} of {}: {}", to_tag, repo_name, e), vec![]),
+    }
+}
+
+// For users who can push to the repo. Cloning can take longer than slack waits for an answer, so the branch is cut
+// in the background and announced when it's there.
+fn cut_release(
+    config: &Config,
+    states: &Vec<Arc<GithubHandlerState>>,
+    repo_name: &str,
+    version: &str,
+    user_name: &str,
+) -> CommandResponse {
+    // the first host is the default one
+    let state = match states.first() {
+        Some(s) => s.clone(),
+        None => return ephemeral("No github host is configured", vec![]),
+    };
+    let host = state.github_session.github_host().to_string();
+    let repo = match github::Repo::parse(&format!("https://{}/{}", host, repo_name)) {
+        Ok(r) => r,
+        Err(e) => return ephemeral(&format!("Invalid repo {}: {}", repo_name, e), vec![]),
+    };
+
+    let login = config.users().github_login(&host, user_name);
+    match state.github_session.get_user_permission(repo.owner.login(), &repo.name, &login) {
+        Ok(ref p) if p == "admin" || p == "write" => (),
+        Ok(_) => {
+            return ephemeral(&format!("Only users with write access to {} can cut releases", repo_name), vec![])
+        }
+        Err(e) => {
+            error!("Error checking permissions for slack cut-release: {}", e);
+            return ephemeral(&format!("Could not check your permissions: {}", e), vec![]);
+        }
+    };
+
+    let version_name = version.to_string();
+    thread::spawn(move || state.cut_release(&repo, &version_name, &login));
+    ephemeral(&format!("Cutting a release branch for {} of {}", version, repo_name), vec![])
+}
+
+// Marks the user out of office, or back, in the users config of the default host.
+fn out_of_office(
+    config: &Config,
+    states: &Vec<Arc<GithubHandlerState>>,
+    away: bool,
+    delegate: Option<&str>,
+    user_name: &str,
+) -> CommandResponse {
+    let host = match states.first() {
+        Some(s) => s.github_session.github_host(),
+        None => return ephemeral("No github host is configured", vec![]),
+    };
+
+    let login = config.users().github_login(host, user_name);
+    if delegate == Some(login.as_str()) {
+        return ephemeral("You can't be your own delegate", vec![]);
+    }
+    let mut users = config.users().host_map().clone();
+    users::set_out_of_office(&mut users, host, &login, user_name, away, delegate);
+    if let Err(e) = config.save_users(users) {
+        error!("Error saving out of office for {}: {}", login, e);
+        return ephemeral(&format!("Could not save that: {}", e), vec![]);
+    }
+
+    let text = match (away, delegate) {
+        (true, Some(d)) => format!("You're out of office: {} gets your direct messages until you're back", d),
+        (true, None) => "You're out of office: no direct messages or reviews until you're back".to_string(),
+        (false, _) => "Welcome back!".to_string(),
+    };
+    ephemeral(&text, vec![])
+}
+
+// for users who can push to the repo, like re-running them on github
+fn rerun_failed_jobs(
+    config: &Config,
+    states: &Vec<Arc<GithubHandlerState>>,
+    repo_url: &str,
+    run_id: u64,
+    user_name: &str,
+) -> ActionResponse {
+    let host = match Url::parse(repo_url) {
+        Ok(ref u) => u.host_str().unwrap_or("").to_string(),
+        Err(e) => return action_reply(&format!("Invalid repo {}: {}", repo_url, e)),
+    };
+    let state = match states.iter().find(|s| s.github_session.github_host() == host) {
+        Some(s) => s,
+        None => return action_reply(&format!("Unknown github host {}", host)),
+    };
+    let repo = match github::Repo::parse(repo_url) {
+        Ok(r) => r,
+        Err(e) => return action_reply(&format!("Invalid repo {}: {}", repo_url, e)),
+    };
+
+    let session = &state.github_session;
+    let login = config.users().github_login(&host, user_name);
+    match session.get_user_permission(repo.owner.login(), &repo.name, &login) {
+        Ok(ref p) if p == "admin" || p == "write" => (),
+        Ok(_) => return action_reply(&format!("Only users with write access to {} can re-run checks", repo.full_name)),
+        Err(e) => {
+            error!("Error checking permissions for slack re-run: {}", e);
+            return action_reply(&format!("Could not check your permissions: {}", e));
+        }
+    };
+
+    match session.rerun_failed_jobs(repo.owner.login(), &repo.name, run_id) {
+        Ok(()) => action_reply(&format!("Re-running the failed jobs of {} run {}", repo.full_name, run_id)),
+        Err(e) => action_reply(&format!("Could not re-run {} run {}: {}", repo.full_name, run_id, e)),
+    }
+}
+
+// at most MAX_LISTED_PRS of them
+fn oldest_first<T>(mut found: Vec<(github::PullRequest, T)>, now: i64) -> Vec<(github::PullRequest, T)> {
+    found.sort_by_key(|&(ref pr, _)| -age_secs(pr, now).unwrap_or(0));
+    found.truncate(MAX_LISTED_PRS);
+    found
+}
+
+fn involves(pr: &github::PullRequest, login: &str) -> bool {
+    pr.user.login() == login || is_reviewer(pr, login)
+}
+
+// assigned to it or asked to review it
+fn is_reviewer(pr: &github::PullRequest, login: &str) -> bool {
+    pr.assignees.iter().any(|a| a.login() == login) ||
+        pr.requested_reviewers.as_ref().map_or(false, |r| r.iter().any(|u| u.login() == login))
+}
+
+fn age_color(secs: i64) -> Option<&'static str> {
+    if secs >= OVERDUE_REVIEW_SECS {
+        Some("danger")
+    } else if secs >= LATE_REVIEW_SECS {
+        Some("warning")
+    } else {
+        None
+    }
+}
+
+fn pr_attachment(
+    repo: &github::Repo,
+    pr: &github::PullRequest,
+    reviews: &Vec<github::Review>,
+    now: i64,
+) -> SlackAttachment {
+    let mut details = vec![];
+    if let Some(secs) = age_secs(pr, now) {
+        details.push(format!("opened {} ago by {}", format_age(secs), pr.user.login()));
+    }
+    details.push(review_state(reviews).to_string());
+    if !pr.assignees.is_empty() {
+        let assignees: Vec<&str> = pr.assignees.iter().map(|a| a.login()).collect();
+        details.push(format!("assigned to {}", assignees.join(", ")));
+    }
+
+    SlackAttachmentBuilder::new(&details.join(" | "))
+        .title(format!("{}#{}: {}", repo.full_name, pr.number, pr.title))
+        .title_link(pr.html_url.as_str())
+        .build()
+}
+
+// each reviewer's latest say counts
+fn review_state(reviews: &Vec<github::Review>) -> &'static str {
+    let mut latest: HashMap<&str, &str> = HashMap::new();
+    for review in reviews {
+        if review.state == "APPROVED" || review.state == "CHANGES_REQUESTED" || review.state == "DISMISSED" {
+            latest.insert(review.user.login(), review.state.as_str());
+        }
+    }
+
+    if latest.values().any(|s| *s == "CHANGES_REQUESTED") {
+        "changes requested"
+    } else if latest.values().any(|s| *s == "APPROVED") {
+        "approved"
+    } else if !reviews.is_empty() {
+        "commented on"
+    } else {
+        "not reviewed yet"
+    }
+}
+
+fn age_secs(pr: &github::PullRequest, now: i64) -> Option<i64> {
+    pr.created_at
+        .as_ref()
+        .and_then(|t| time::strptime(t, "%Y-%m-%dT%H:%M:%SZ").ok())
+        .map(|tm| now - tm.to_timespec().sec)
+}
+
+fn format_age(secs: i64) -> String {
+    let hours = secs / 3600;
+    if hours < 1 {
+        format!("{} minutes", secs / 60)
+    } else if hours < 48 {
+        format!("{} hours", hours)
+    } else {
+        format!("{} days", hours / 24)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_serialize::hex::ToHex;
+
+    fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+        let key = hmac::SigningKey::new(&digest::SHA256, secret.as_bytes());
+        let signature = hmac::sign(&key, format!("v0:{}:{}", timestamp, body).as_bytes());
+        "v0=".to_string() + signature.as_ref().to_hex().as_str()
+    }
+
+    #[test]
+    fn test_signature() {
+        let body = b"command=%2Foctobot&text=prs";
+        let signature = sign("the-secret", "1500000000", "command=%2Foctobot&text=prs");
+
+        assert!(is_valid_signature("the-secret", "1500000000", body, &signature, 1500000010));
+        assert!(!is_valid_signature("other-secret", "1500000000", body, &signature, 1500000010));
+        assert!(!is_valid_signature("the-secret", "1500000000", b"text=merge", &signature, 1500000010));
+        assert!(!is_valid_signature("the-secret", "1500000001", body, &signature, 1500000010));
+        // replayed
+        assert!(!is_valid_signature("the-secret", "1500000000", body, &signature, 1500001000));
+        assert!(!is_valid_signature("the-secret", "1500000000", body, &signature.replace("v0=", "v1="), 1500000010));
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(Command::Prs(PrsFilter::Channel), parse_command("prs"));
+        assert_eq!(Command::Prs(PrsFilter::Mine), parse_command(" prs  me "));
+        assert_eq!(Command::Prs(PrsFilter::Repo("org/repo".into())), parse_command("prs org/repo"));
+        assert_eq!(Command::Prs(PrsFilter::Reviews), parse_command("reviews"));
+        assert_eq!(Command::Help, parse_command("reviews me"));
+        assert_eq!(Command::Help, parse_command("prs nope"));
+        assert_eq!(Command::Help, parse_command(""));
+        assert_eq!(Command::Help, parse_command("help"));
+        assert_eq!(
+            Command::Backport("org/repo".into(), 12, "backport-2.3".into()),
+            parse_command("backport org/repo#12 backport-2.3")
+        );
+        assert_eq!(Command::Help, parse_command("backport org/repo 12 2.3"));
+        assert_eq!(Command::Help, parse_command("backport repo#12 2.3"));
+        assert_eq!(Command::Help, parse_command("backport org/repo#abc 2.3"));
+        assert_eq!(
+            Command::ReleaseNotes("org/repo".into(), "v1.0".into(), "v1.1".into(), false),
+            parse_command("release-notes org/repo v1.0 v1.1")
+        );
+        assert_eq!(
+            Command::ReleaseNotes("org/repo".into(), "v1.0".into(), "v1.1".into(), true),
+            parse_command("release-notes org/repo v1.0 v1.1 publish")
+        );
+        assert_eq!(Command::Help, parse_command("release-notes org/repo v1.0"));
+        assert_eq!(Command::Help, parse_command("release-notes repo v1.0 v1.1"));
+        assert_eq!(Command::Help, parse_command("release-notes org/repo v1.0 v1.1 now"));
+        assert_eq!(
+            Command::CutRelease("org/repo".into(), "2.4".into()),
+            parse_command("cut-release org/repo 2.4")
+        );
+        assert_eq!(Command::Help, parse_command("cut-release repo 2.4"));
+        assert_eq!(Command::Help, parse_command("cut-release org/repo"));
+        assert_eq!(Command::OutOfOffice(true, None), parse_command("ooo"));
+        assert_eq!(Command::OutOfOffice(true, Some("jane".into())), parse_command("ooo @jane"));
+        assert_eq!(Command::OutOfOffice(false, None), parse_command("back"));
+        assert_eq!(Command::Help, parse_command("ooo jane bob"));
+    }
+
+    #[test]
+    fn test_parse_action() {
+        let attachment_click = r#"{
+            "type": "interactive_message",
+            "callback_id": "rerun-failed-jobs",
+            "user": {"id": "U1", "name": "joe.smith"},
+            "actions": [{"name": "rerun-failed-jobs", "type": "button", "value": "http://git.foo.com/org/repo 123"}]
+        }"#;
+        assert_eq!(
+            Some(("joe.smith".into(), Action::RerunFailedJobs("http://git.foo.com/org/repo".into(), 123))),
+            parse_action(attachment_click)
+        );
+
+        let block_click = r#"{
+            "type": "block_actions",
+            "user": {"id": "U1", "username": "joe.smith"},
+            "actions": [{"action_id": "rerun-failed-jobs", "value": "http://git.foo.com/org/repo 123"}]
+        }"#;
+        assert_eq!(
+            Some(("joe.smith".into(), Action::RerunFailedJobs("http://git.foo.com/org/repo".into(), 123))),
+            parse_action(block_click)
+        );
+
+        let other = r#"{"user": {"name": "joe.smith"}, "actions": [{"name": "other", "value": "x 1"}]}"#;
+        assert_eq!(Some(("joe.smith".into(), Action::Unknown)), parse_action(other));
+        assert_eq!(None, parse_action(r#"{"user": {"name": "joe.smith"}, "actions": []}"#));
+        assert_eq!(None, parse_action("not json"));
+    }
+
+    #[test]
+    fn test_review_state() {
+        let review = |login: &str, state: &str| {
+            let mut r = github::Review::new("", github::User::new(login));
+            r.state = state.into();
+            r
+        };
+
+        assert_eq!("not reviewed yet", review_state(&vec![]));
+        assert_eq!("commented on", review_state(&vec![review("joe", "COMMENTED")]));
+        assert_eq!("approved", review_state(&vec![review("joe", "CHANGES_REQUESTED"), review("joe", "APPROVED")]));
+        assert_eq!(
+            "changes requested",
+            review_state(&vec![review("joe", "APPROVED"), review("jane", "CHANGES_REQUESTED")])
+        );
+    }
+
+    #[test]
+    fn test_is_reviewer() {
+        let mut pr = github::PullRequest::new();
+        pr.user = github::User::new("joe");
+        pr.assignees = vec![github::User::new("jane")];
+        pr.requested_reviewers = Some(vec![github::User::new("bob")]);
+
+        assert!(is_reviewer(&pr, "jane"));
+        assert!(is_reviewer(&pr, "bob"));
+        assert!(!is_reviewer(&pr, "joe"));
+        assert!(involves(&pr, "joe"));
+        assert!(!involves(&pr, "alice"));
+    }
+
+    #[test]
+    fn test_age_color() {
+        assert_eq!(None, age_color(60 * 60));
+        assert_eq!(Some("warning"), age_color(LATE_REVIEW_SECS));
+        assert_eq!(Some("danger"), age_color(5 * 24 * 60 * 60));
+    }
+
+    #[test]
+    fn test_oldest_first() {
+        // newest first, so that the oldest ones are the last found
+        let found = (0..30)
+            .map(|i| {
+                let mut pr = github::PullRequest::new();
+                pr.number = i;
+                pr.created_at = Some(format!("2017-08-01T12:{:02}:00Z", 29 - i));
+                (pr, ())
+            })
+            .collect();
+
+        let listed: Vec<u32> = oldest_first(found, 1504224000).iter().map(|&(ref pr, _)| pr.number).collect();
+        assert_eq!(MAX_LISTED_PRS, listed.len());
+        assert_eq!(Some(&29), listed.first());
+        assert_eq!(Some(&5), listed.last());
+    }
+
+    #[test]
+    fn test_pr_attachment() {
+        let repo = github::Repo::parse("https://github.com/org/repo").unwrap();
+        let mut pr = github::PullRequest::new();
+        pr.number = 12;
+        pr.title = "Fix things".into();
+        pr.html_url = "https://github.com/org/repo/pull/12".into();
+        pr.user = github::User::new("joe");
+        pr.assignees = vec![github::User::new("jane")];
+        pr.created_at = Some("2017-08-01T12:00:00Z".into());
+
+        let now = time::strptime("2017-08-04T13:00:00Z", "%Y-%m-%dT%H:%M:%SZ").unwrap().to_timespec().sec;
+        let attachment = pr_attachment(&repo, &pr, &vec![], now);
+        assert_eq!(Some("org/repo#12: Fix things".to_string()), attachment.title);
+        assert_eq!("opened 3 days ago by joe | not reviewed yet | assigned to jane", attachment.text);
+    }
+}
@@ -0,0 +1,81 @@
+use errors::*;
+use github;
+use github::api::Session;
+
+pub const LABEL_PREFIX: &'static str = "size/";
+const SIZES: [&'static str; 5] = ["XS", "S", "M", "L", "XL"];
+
+// changed lines (added plus removed) up to which a pull request is XS, S, M, and L. Anything bigger is XL.
+pub const DEFAULT_THRESHOLDS: [u32; 4] = [10, 30, 100, 500];
+
+// e.g. "size/M". Missing thresholds fall back to the defaults, so [5] means XS up to 5 lines, then S up to 30.
+pub fn size_label(stats: &github::DiffStats, thresholds: &Vec<u32>) -> String {
+    let lines = stats.changed_lines();
+    let size = DEFAULT_THRESHOLDS
+        .iter()
+        .enumerate()
+        .map(|(i, default)| *thresholds.get(i).unwrap_or(default))
+        .position(|max| lines <= max)
+        .map_or(SIZES[SIZES.len() - 1], |i| SIZES[i]);
+    format!("{}{}", LABEL_PREFIX, size)
+}
+
+// Labels the pull request with its size, and takes off any other size label it had from before a push. The stats
+// are returned even if labeling fails, e.g. on bitbucket, which has no labels.
+pub fn apply(
+    github: &Session,
+    repo: &github::Repo,
+    pull_request: &github::PullRequest,
+    thresholds: &Vec<u32>,
+) -> Result<github::DiffStats> {
+    let stats = github.get_pull_request_stats(repo.owner.login(), &repo.name, pull_request.number)?;
+    let label = size_label(&stats, thresholds);
+    if let Err(e) = set_label(github, repo, pull_request, &label) {
+        error!("Error labeling PR #{} {}: {}", pull_request.number, label, e);
+    }
+    Ok(stats)
+}
+
+fn set_label(github: &Session, repo: &github::Repo, pull_request: &github::PullRequest, label: &str) -> Result<()> {
+    let owner = repo.owner.login();
+    let labels = github.get_pull_request_labels(owner, &repo.name, pull_request.number)?;
+    for old in labels.iter().filter(|l| l.name.starts_with(LABEL_PREFIX) && l.name != label) {
+        github.remove_pull_request_label(owner, &repo.name, pull_request.number, &old.name)?;
+    }
+    if !labels.iter().any(|l| l.name == label) {
+        github.add_pull_request_labels(owner, &repo.name, pull_request.number, vec![label.to_string()])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(additions: u32, deletions: u32) -> github::DiffStats {
+        github::DiffStats {
+            additions: additions,
+            deletions: deletions,
+            changed_files: 1,
+        }
+    }
+
+    #[test]
+    fn test_size_label() {
+        let defaults = DEFAULT_THRESHOLDS.to_vec();
+        assert_eq!("size/XS", size_label(&stats(0, 0), &defaults));
+        assert_eq!("size/XS", size_label(&stats(5, 5), &defaults));
+        assert_eq!("size/S", size_label(&stats(6, 5), &defaults));
+        assert_eq!("size/M", size_label(&stats(100, 0), &defaults));
+        assert_eq!("size/L", size_label(&stats(120, 40), &defaults));
+        assert_eq!("size/XL", size_label(&stats(500, 1), &defaults));
+    }
+
+    #[test]
+    fn test_size_label_thresholds() {
+        assert_eq!("size/S", size_label(&stats(6, 0), &vec![5]));
+        assert_eq!("size/M", size_label(&stats(31, 0), &vec![5]));
+        assert_eq!("size/XL", size_label(&stats(51, 0), &vec![1, 2, 3, 50]));
+        assert_eq!("size/XS", size_label(&stats(10, 0), &vec![]));
+    }
+}
@@ -1,18 +1,59 @@
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
 use futures::{Future, future};
+use hyper::StatusCode;
+use regex::{Captures, Regex};
+use serde_json::Value;
 use tokio_core::reactor::Remote;
 
+use audit::{self, AuditLog};
+use cluster::Cluster;
+use digest::Digests;
+use errors::*;
 use http_client::HTTPClient;
+use json_store::JsonStore;
+use pr_threads::{self, PrThread, PrThreads};
+use quiet_hours::{self, DeferredMessages};
+use slack_api::{ChatMessage, SlackApi};
+use slack_retries::SlackRetries;
+use tracing::{self, SpanContext, Tracer};
 use util;
 use worker;
 
-#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
 pub struct SlackAttachment {
     pub text: String,
     pub title: Option<String>,
     pub title_link: Option<String>,
     pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<SlackField>,
+    // which of octobot's handlers slack sends a click on one of the buttons to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<SlackAction>,
+}
+
+// a short "title: value" shown in a table under the attachment's text
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct SlackField {
+    pub title: String,
+    pub value: String,
+    pub short: bool,
+}
+
+// A button that slack posts back to /slack/actions when it's clicked, with its name and value
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct SlackAction {
+    pub name: String,
+    pub text: String,
+    #[serde(rename = "type")]
+    pub action_type: String,
+    pub value: String,
 }
 
 impl SlackAttachment {
@@ -22,6 +63,9 @@ impl SlackAttachment {
             title: None,
             title_link: None,
             color: None,
+            fields: vec![],
+            callback_id: None,
+            actions: vec![],
         }
     }
 }
@@ -54,16 +98,270 @@ impl SlackAttachmentBuilder {
         self
     }
 
+    pub fn field<S: Into<String>, T: Into<String>>(&mut self, title: S, value: T) -> &mut SlackAttachmentBuilder {
+        self.attachment.fields.push(SlackField {
+            title: title.into(),
+            value: value.into(),
+            short: true,
+        });
+        self
+    }
+
+    // the attachment's callback id is the name of its first button
+    pub fn button<S: Into<String>, T: Into<String>, V: Into<String>>(
+        &mut self,
+        name: S,
+        text: T,
+        value: V,
+    ) -> &mut SlackAttachmentBuilder {
+        let name = name.into();
+        if self.attachment.callback_id.is_none() {
+            self.attachment.callback_id = Some(name.clone());
+        }
+        self.attachment.actions.push(SlackAction {
+            name: name,
+            text: text.into(),
+            action_type: "button".into(),
+            value: value.into(),
+        });
+        self
+    }
+
     pub fn build(&self) -> SlackAttachment {
         self.attachment.clone()
     }
 }
 
+// slack shows at most this many fields in a section block
+const MAX_BLOCK_FIELDS: usize = 10;
+
+// Builds block kit layouts, which newer slack installations show instead of attachments.
+pub struct BlockKitBuilder {
+    blocks: Vec<Value>,
+}
+
+impl BlockKitBuilder {
+    pub fn new() -> BlockKitBuilder {
+        BlockKitBuilder { blocks: vec![] }
+    }
+
+    pub fn section<S: Into<String>>(&mut self, text: S) -> &mut BlockKitBuilder {
+        self.blocks.push(json!({"type": "section", "text": mrkdwn(text.into())}));
+        self
+    }
+
+    pub fn fields(&mut self, fields: &Vec<SlackField>) -> &mut BlockKitBuilder {
+        for chunk in fields.chunks(MAX_BLOCK_FIELDS) {
+            let texts: Vec<Value> = chunk.iter().map(|f| mrkdwn(format!("*{}*\n{}", f.title, f.value))).collect();
+            self.blocks.push(json!({"type": "section", "fields": texts}));
+        }
+        self
+    }
+
+    // small print, e.g. who did something and when
+    pub fn context<S: Into<String>>(&mut self, text: S) -> &mut BlockKitBuilder {
+        self.blocks.push(json!({"type": "context", "elements": [mrkdwn(text.into())]}));
+        self
+    }
+
+    // buttons that follow each other go in the same row
+    pub fn button<S: Into<String>, U: Into<String>>(&mut self, text: S, url: U) -> &mut BlockKitBuilder {
+        let button = json!({
+            "type": "button",
+            "text": {"type": "plain_text", "text": text.into()},
+            "url": url.into(),
+        });
+        self.push_button(button)
+    }
+
+    // a button that slack posts back to /slack/actions when it's clicked
+    pub fn action_button<S, A, V>(&mut self, text: S, action_id: A, value: V) -> &mut BlockKitBuilder
+    where
+        S: Into<String>,
+        A: Into<String>,
+        V: Into<String>,
+    {
+        let button = json!({
+            "type": "button",
+            "text": {"type": "plain_text", "text": text.into()},
+            "action_id": action_id.into(),
+            "value": value.into(),
+        });
+        self.push_button(button)
+    }
+
+    fn push_button(&mut self, button: Value) -> &mut BlockKitBuilder {
+        if let Some(&mut Value::Object(ref mut last)) = self.blocks.last_mut() {
+            if last.get("type") == Some(&json!("actions")) {
+                if let Some(&mut Value::Array(ref mut elements)) = last.get_mut("elements") {
+                    elements.push(button);
+                    return self;
+                }
+            }
+        }
+        self.blocks.push(json!({"type": "actions", "elements": [button]}));
+        self
+    }
+
+    pub fn build(&self) -> Vec<Value> {
+        self.blocks.clone()
+    }
+}
+
+fn mrkdwn(text: String) -> Value {
+    json!({"type": "mrkdwn", "text": text})
+}
+
+// The same message as blocks: the text, then a section for each attachment with its title, text, and fields.
+pub fn blocks(msg: &str, attachments: &Vec<SlackAttachment>) -> Vec<Value> {
+    let mut builder = BlockKitBuilder::new();
+    builder.section(msg);
+    for attachment in attachments {
+        let mut text = match (&attachment.title, &attachment.title_link) {
+            (&Some(ref title), &Some(ref link)) => format!("*{}*", util::make_link(link, title)),
+            (&Some(ref title), &None) => format!("*{}*", title),
+            _ => String::new(),
+        };
+        if !attachment.text.is_empty() {
+            if !text.is_empty() {
+                text += "\n";
+            }
+            text += &attachment.text;
+        }
+        if !text.is_empty() {
+            builder.section(text);
+        }
+        builder.fields(&attachment.fields);
+        for action in &attachment.actions {
+            builder.action_button(action.text.as_str(), action.name.as_str(), action.value.as_str());
+        }
+    }
+    builder.build()
+}
+
+// comment bodies longer than this are cut off, with a link to the rest
+const MAX_COMMENT_CHARS: usize = 2000;
+
+// A github comment body as slack text: converted to mrkdwn, and cut short with a "read more" link to `url`
+// if it's long.
+pub fn comment_text(body: &str, url: &str) -> String {
+    comment_excerpt(body, url, MAX_COMMENT_CHARS)
+}
+
+// the same, cut short at max_chars
+pub fn comment_excerpt(body: &str, url: &str, max_chars: usize) -> String {
+    let body = body.trim();
+    if body.chars().count() <= max_chars {
+        return markdown_to_mrkdwn(body);
+    }
+
+    let mut cut: String = body.chars().take(max_chars).collect();
+    // at a word break, if there is one
+    if let Some(i) = cut.rfind(char::is_whitespace) {
+        cut.truncate(i);
+    }
+    // and without leaving a code block open
+    if cut.lines().filter(|l| l.trim_left().starts_with("```")).count() % 2 == 1 {
+        cut += "\n```";
+    }
+    format!("{}\n… {}", markdown_to_mrkdwn(&cut), util::make_link(url, "read more"))
+}
+
+// how many lines of the diff leading up to an inline comment go with it
+const DIFF_CONTEXT_LINES: usize = 4;
+
+// The end of an inline comment's diff hunk, which is the commented line and a few before it, as a code block
+pub fn diff_context(diff_hunk: &str) -> String {
+    let lines: Vec<String> = diff_hunk.lines().filter(|l| !l.starts_with("@@")).map(escape).collect();
+    if lines.is_empty() {
+        return String::new();
+    }
+    let start = lines.len().saturating_sub(DIFF_CONTEXT_LINES);
+    format!("```\n{}\n```", lines[start..].join("\n"))
+}
+
+// Converts github flavored markdown to slack's mrkdwn: links, images, bold, strikethrough, headings, and lists.
+// Code is only escaped, and code blocks lose their language, which slack doesn't use.
+pub fn markdown_to_mrkdwn(markdown: &str) -> String {
+    let mut lines = vec![];
+    let mut in_code_block = false;
+    for line in markdown.lines() {
+        if line.trim_left().starts_with("```") {
+            in_code_block = !in_code_block;
+            lines.push("```".to_string());
+        } else if in_code_block {
+            lines.push(escape(line));
+        } else {
+            lines.push(convert_line(line));
+        }
+    }
+    lines.join("\n")
+}
+
+fn escape(text: &str) -> String {
+    text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;")
+}
+
+fn convert_line(line: &str) -> String {
+    let text = line.trim_left();
+    let indent = &line[..line.len() - text.len()];
+
+    let heading = Regex::new(r"^#{1,6}\s+(.*?)[\s#]*$").unwrap();
+    let list_item = Regex::new(r"^[-*+]\s+(\[[ xX]\]\s+)?(.*)$").unwrap();
+    if let Some(c) = heading.captures(text) {
+        format!("{}*{}*", indent, convert_inline(&c[1]))
+    } else if let Some(c) = list_item.captures(text) {
+        let check = match c.get(1).map(|m| m.as_str().trim()) {
+            Some("[ ]") => "☐ ",
+            Some(_) => "☑ ",
+            None => "",
+        };
+        format!("{}• {}{}", indent, check, convert_inline(&c[2]))
+    } else if text.starts_with(">") {
+        // the one ">" that has to stay as it is
+        format!("{}>{}", indent, convert_inline(&text[1..]))
+    } else {
+        format!("{}{}", indent, convert_inline(text))
+    }
+}
+
+// everything but `inline code`
+fn convert_inline(text: &str) -> String {
+    let pieces: Vec<String> = text.split('`')
+        .enumerate()
+        .map(|(i, piece)| if i % 2 == 1 { escape(piece) } else { convert_formatting(piece) })
+        .collect();
+    pieces.join("`")
+}
+
+fn convert_formatting(text: &str) -> String {
+    let text = escape(text);
+    // images before links, which they look like with a "!" in front
+    let image = Regex::new(r"!\[([^\]]*)\]\(([^)\s]+)[^)]*\)").unwrap();
+    let text = image.replace_all(&text, |c: &Captures| {
+        let alt = if c[1].is_empty() { "image" } else { &c[1] };
+        format!("<{}|{}>", &c[2], alt)
+    });
+    let link = Regex::new(r"\[([^\]]+)\]\(([^)\s]+)[^)]*\)").unwrap();
+    let text = link.replace_all(&text, "<$2|$1>");
+
+    // bold is *text* in slack, which is italic in markdown, so bold waits as \u{1}text\u{1} until italics are done
+    let bold = Regex::new(r"(\*\*|__)(\S(?:.*?\S)?)(\*\*|__)").unwrap();
+    let text = bold.replace_all(&text, "\u{1}$2\u{1}");
+    let italic = Regex::new(r"\*(\S(?:[^*]*?\S)?)\*").unwrap();
+    let text = italic.replace_all(&text, "_${1}_");
+    let strike = Regex::new(r"~~(\S(?:.*?\S)?)~~").unwrap();
+    let text = strike.replace_all(&text, "~$1~");
+    text.replace('\u{1}', "*")
+}
+
 
 #[derive(Serialize, Clone, PartialEq)]
 struct SlackMessage {
     text: String,
     attachments: Vec<SlackAttachment>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    blocks: Vec<Value>,
     channel: String,
 }
 
@@ -71,13 +369,29 @@ struct SlackMessage {
 struct Slack {
     client: HTTPClient,
     recent_messages: Mutex<Vec<SlackMessage>>,
+    retries: Arc<JsonStore<SlackRetries>>,
+    audit_log: Arc<AuditLog>,
+    // send blocks instead of attachments
+    use_blocks: bool,
+    // post with chat.postMessage instead of the incoming webhook
+    api: Option<Arc<SlackApi>>,
+    threads: Arc<JsonStore<PrThreads>>,
 }
 
 const TRIM_MESSAGES_AT: usize = 200;
 const TRIM_MESSAGES_TO: usize = 20;
+const SCHEDULER_INTERVAL_SECS: u64 = 60;
 
 impl Slack {
-    pub fn new(core_remote: Remote, webhook_url: &str) -> Slack {
+    pub fn new(
+        core_remote: Remote,
+        webhook_url: &str,
+        retries: Arc<JsonStore<SlackRetries>>,
+        audit_log: Arc<AuditLog>,
+        use_blocks: bool,
+        api: Option<Arc<SlackApi>>,
+        threads: Arc<JsonStore<PrThreads>>,
+    ) -> Slack {
         let client = HTTPClient::new(core_remote, webhook_url).with_headers(hashmap!{
                 "Content-Type" => "application/json".to_string(),
             });
@@ -85,47 +399,195 @@ impl Slack {
         Slack {
             client: client,
             recent_messages: Mutex::new(Vec::new()),
+            retries: retries,
+            audit_log: audit_log,
+            use_blocks: use_blocks,
+            api: api,
+            threads: threads,
         }
     }
 
-    fn send(&self, channel: &str, msg: &str, attachments: Vec<SlackAttachment>) {
-        let slack_msg = SlackMessage {
-            text: msg.to_string(),
-            attachments: attachments,
-            channel: channel.to_string(),
-        };
+    fn slack_message(&self, channel: &str, msg: &str, attachments: &Vec<SlackAttachment>) -> SlackMessage {
+        // with blocks, the text is only what notifications show
+        if self.use_blocks {
+            SlackMessage {
+                text: msg.to_string(),
+                attachments: vec![],
+                blocks: blocks(msg, attachments),
+                channel: channel.to_string(),
+            }
+        } else {
+            SlackMessage {
+                text: msg.to_string(),
+                attachments: attachments.clone(),
+                blocks: vec![],
+                channel: channel.to_string(),
+            }
+        }
+    }
 
-        if !self.is_unique(&slack_msg) {
-            info!("Skipping duplicate message to {}", channel);
+    fn send(&self, req: SlackRequest) {
+        let slack_msg = self.slack_message(&req.channel, &req.msg, &req.attachments);
+
+        // retries are always duplicates of a message that was sent before
+        if req.attempts == 0 && !self.is_unique(&slack_msg) {
+            info!("Skipping duplicate message to {}", req.channel);
             return;
         }
 
-        info!("Sending message to #{}", channel);
+        if req.dry_run {
+            info!("Dry run: not sending message to {}: {}", req.channel, req.msg);
+            self.audit_log.record("slack_message", &req.channel, audit::DRY_RUN);
+            return;
+        }
 
-        self.client.spawn(self.client.post_void_async("", &slack_msg).then(|res| {
-            match res {
-                Ok(_) => info!("Successfully sent slack message"),
-                Err(e) => error!("Error sending slack message: {}", e),
+        info!("Sending message to #{}", req.channel);
+
+        // the web API is called right here, one message after the other, so that it can pace them
+        if let Some(ref api) = self.api {
+            let res = match req.thread {
+                Some(ref pull_request) => self.send_threaded(api, pull_request, &req, slack_msg),
+                None => api.post_message(&chat_message(slack_msg)).map(|_| ()),
             };
+            sent(&self.audit_log, &self.retries, req, res);
+            return;
+        }
+
+        let retries = self.retries.clone();
+        let audit_log = self.audit_log.clone();
+        // the reply comes in on another thread
+        let trigger = audit::current_trigger();
+        self.client.spawn(self.client.post_void_async("", &slack_msg).then(move |res| {
+            audit::with_trigger(&trigger, || sent(&audit_log, &retries, req, res));
             future::ok::<(), ()>(())
         }));
     }
 
+    // The first message about a PR in a channel starts its thread. The ones after it go in that thread, and the
+    // first one is updated to show the latest of them.
+    fn send_threaded(
+        &self,
+        api: &SlackApi,
+        pull_request: &str,
+        req: &SlackRequest,
+        slack_msg: SlackMessage,
+    ) -> Result<()> {
+        let now = quiet_hours::now();
+        let mut msg = chat_message(slack_msg);
+        let posted = match self.threads.read(|t| t.get(pull_request, &req.channel)) {
+            None => {
+                let thread = PrThread {
+                    posted: api.post_message(&msg)?,
+                    text: req.msg.clone(),
+                    attachments: req.attachments.clone(),
+                    updated_at: now,
+                };
+                let posted = thread.posted.clone();
+                self.threads.write(|t| t.add(pull_request, &req.channel, thread, now));
+                posted
+            }
+            Some(thread) => {
+                msg.channel = thread.posted.channel.clone();
+                msg.thread_ts = Some(thread.posted.ts.clone());
+                api.post_message(&msg)?;
+
+                // the reply is what matters: it isn't sent again if only the update fails
+                let (text, attachments) = thread.with_latest(&req.msg);
+                let update = chat_message(self.slack_message(&thread.posted.channel, &text, &attachments));
+                if let Err(e) = api.update_message(&thread.posted, &update) {
+                    error!("Error updating the thread of {} in {}: {}", pull_request, req.channel, e);
+                }
+                self.threads.write(|t| t.touch(pull_request, &req.channel, now));
+                thread.posted
+            }
+        };
+
+        if let Some(ref reaction) = req.reaction {
+            for replaced in pr_threads::replaced_reactions(reaction) {
+                if let Err(e) = api.remove_reaction(&posted, replaced) {
+                    error!("Error removing :{}: from the message of {}: {}", replaced, pull_request, e);
+                }
+            }
+            if let Err(e) = api.add_reaction(&posted, reaction) {
+                error!("Error adding :{}: to the message of {}: {}", reaction, pull_request, e);
+            }
+        }
+        Ok(())
+    }
+
     fn is_unique(&self, req: &SlackMessage) -> bool {
         let mut recent_messages = self.recent_messages.lock().unwrap();
         util::check_unique_event(req.clone(), &mut *recent_messages, TRIM_MESSAGES_AT, TRIM_MESSAGES_TO)
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+fn chat_message(msg: SlackMessage) -> ChatMessage {
+    ChatMessage::new(&msg.channel, &msg.text, msg.attachments, msg.blocks)
+}
+
+// audits how sending the message went, and keeps it to try again later if it failed
+fn sent(audit_log: &AuditLog, retries: &JsonStore<SlackRetries>, req: SlackRequest, res: Result<()>) {
+    audit_log.record("slack_message", &req.channel, &audit::outcome(&res));
+    match res {
+        Ok(_) => info!("Successfully sent slack message"),
+        Err(e) => {
+            error!("Error sending slack message: {}", e);
+            retries.write(|r| r.add_failure(req, &e, quiet_hours::now()));
+        }
+    };
+}
+
+// Checks that the incoming webhook still exists. Incoming webhooks have no way to check them other than
+// posting to them, so this posts an empty message, which slack never shows: it answers 400 ("no_text")
+// for it if the webhook exists, and 403/404/410 if the webhook was removed or its channel archived.
+pub fn check_webhook(core_remote: Remote, webhook_url: &str) -> Result<()> {
+    let client = HTTPClient::new(core_remote, webhook_url).with_headers(hashmap!{
+        "Content-Type" => "application/json".to_string(),
+    });
+    webhook_check_result(client.post_void("", &hashmap!{ "text" => "" }))
+}
+
+fn webhook_check_result(result: Result<()>) -> Result<()> {
+    match result {
+        Ok(_) => Ok(()),
+        Err(ref e) if e.to_string().contains(&format!("HTTP {}", StatusCode::BadRequest)) => Ok(()),
+        Err(e) => Err(format!("Slack webhook check failed: {}", e).into()),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct SlackRequest {
     pub channel: String,
     pub msg: String,
     pub attachments: Vec<SlackAttachment>,
+    // hold the message until this time (unix seconds), e.g. until the recipient's working hours
+    pub deliver_at: Option<i64>,
+    // collect the message into a single digest that is sent at deliver_at
+    #[serde(default)]
+    pub digest: bool,
+    // how many times sending this failed already
+    #[serde(default)]
+    pub attempts: u32,
+    // only audit the message instead of sending it
+    #[serde(default)]
+    pub dry_run: bool,
+    // the url of the PR the message is about, to thread it under the PR's first message in the channel. only
+    // messages sent through the web API are threaded
+    #[serde(default)]
+    pub thread: Option<String>,
+    // an emoji to react to the PR's first message with, e.g. for an approval. only for threaded messages
+    #[serde(default)]
+    pub reaction: Option<String>,
+    // the span that sent the message, so that sending it goes on the same trace. not kept for held messages
+    #[serde(skip)]
+    pub trace: Option<SpanContext>,
 }
 
 struct Runner {
     slack: Arc<Slack>,
+    deferred: Arc<JsonStore<DeferredMessages>>,
+    digests: Arc<JsonStore<Digests>>,
+    tracer: Option<Arc<Tracer>>,
 }
 
 pub fn req(channel: &str, msg: &str, attachments: Vec<SlackAttachment>) -> SlackRequest {
@@ -133,15 +595,234 @@ pub fn req(channel: &str, msg: &str, attachments: Vec<SlackAttachment>) -> Slack
         channel: channel.into(),
         msg: msg.into(),
         attachments: attachments,
+        deliver_at: None,
+        digest: false,
+        attempts: 0,
+        dry_run: false,
+        thread: None,
+        reaction: None,
+        trace: None,
+    }
+}
+
+pub fn deferred_req(channel: &str, msg: &str, attachments: Vec<SlackAttachment>, deliver_at: i64) -> SlackRequest {
+    SlackRequest {
+        channel: channel.into(),
+        msg: msg.into(),
+        attachments: attachments,
+        deliver_at: Some(deliver_at),
+        digest: false,
+        attempts: 0,
+        dry_run: false,
+        thread: None,
+        reaction: None,
+        trace: None,
+    }
+}
+
+pub fn digest_req(channel: &str, msg: &str, attachments: Vec<SlackAttachment>, deliver_at: i64) -> SlackRequest {
+    SlackRequest {
+        channel: channel.into(),
+        msg: msg.into(),
+        attachments: attachments,
+        deliver_at: Some(deliver_at),
+        digest: true,
+        attempts: 0,
+        dry_run: false,
+        thread: None,
+        reaction: None,
+        trace: None,
     }
 }
 
-pub fn new_worker(core_remote: Remote, webhook_url: &str) -> worker::Worker<SlackRequest> {
-    worker::Worker::new("slack", Runner { slack: Arc::new(Slack::new(core_remote, webhook_url)) })
+pub fn new_worker(
+    core_remote: Remote,
+    webhook_url: &str,
+    deferred_file: Option<PathBuf>,
+    digest_file: Option<PathBuf>,
+    retries: Arc<JsonStore<SlackRetries>>,
+    audit_log: Arc<AuditLog>,
+    use_blocks: bool,
+    api: Option<Arc<SlackApi>>,
+    threads_file: Option<PathBuf>,
+    tracer: Option<Arc<Tracer>>,
+    queue_file: Option<PathBuf>,
+    cluster: Option<Arc<Cluster>>,
+    host: &str,
+) -> worker::Worker<SlackRequest> {
+    let deferred = Arc::new(JsonStore::new(deferred_file));
+    // a user's digest collects the events that every node handled, and goes out from one of them
+    let digests = Arc::new(JsonStore::new(digest_file).with_cluster(cluster.clone(), &format!("digests:{}", host)));
+    let threads = Arc::new(JsonStore::new(threads_file));
+    let worker = worker::Worker::persistent(
+        "slack",
+        Runner {
+            slack: Arc::new(
+                Slack::new(core_remote, webhook_url, retries.clone(), audit_log, use_blocks, api, threads),
+            ),
+            deferred: deferred.clone(),
+            digests: digests.clone(),
+            tracer: tracer,
+        },
+        queue_file,
+    );
+    let job = format!("slack-digests:{}", host);
+    start_scheduler(deferred, digests, retries, worker.new_sender(), cluster, job);
+    worker
+}
+
+// Checks for held messages, digests and retries that are due every minute and hands them back to the worker.
+// Held messages and retries are only ever on the node that was sending them, but in a cluster digests are shared,
+// and one node sends them.
+fn start_scheduler(
+    deferred: Arc<JsonStore<DeferredMessages>>,
+    digests: Arc<JsonStore<Digests>>,
+    retries: Arc<JsonStore<SlackRetries>>,
+    slack: worker::WorkSender<SlackRequest>,
+    cluster: Option<Arc<Cluster>>,
+    job: String,
+) {
+    let result = thread::Builder::new().name("slack-scheduler".into()).spawn(move || loop {
+        thread::sleep(Duration::from_secs(SCHEDULER_INTERVAL_SECS));
+
+        let now = quiet_hours::now();
+        let mut due = deferred.write(|d| d.take_due(now));
+        if cluster.as_ref().map_or(true, |c| c.is_leader(&job, 3 * SCHEDULER_INTERVAL_SECS)) {
+            due.extend(digests.write(|d| d.take_due(now)));
+        }
+        due.extend(retries.write(|r| r.take_due(now)));
+        for mut req in due {
+            req.deliver_at = None;
+            req.trace = None;
+            if let Err(e) = slack.send(req) {
+                error!("Error sending scheduled slack message: {}", e);
+            }
+        }
+    });
+
+    if let Err(e) = result {
+        error!("Error starting slack scheduler thread: {}", e);
+    }
 }
 
 impl worker::Runner<SlackRequest> for Runner {
     fn handle(&self, req: SlackRequest) {
-        self.slack.send(&req.channel, &req.msg, req.attachments);
+        if req.digest {
+            info!("Adding message to {} to digest", req.channel);
+            self.digests.write(|d| d.add(req));
+            return;
+        }
+
+        if req.deliver_at.map_or(false, |t| t > quiet_hours::now()) {
+            info!("Holding message to {} until working hours", req.channel);
+            self.deferred.write(|d| d.add(req));
+            return;
+        }
+
+        let parent = req.trace.clone();
+        let attributes = [("slack.channel", req.channel.clone())];
+        tracing::trace(self.tracer.as_ref().map(|t| &**t), "slack.send", parent.as_ref(), &attributes, || {
+            self.slack.send(req)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_check_result() {
+        assert!(webhook_check_result(Ok(())).is_ok());
+        assert!(webhook_check_result(Err("Failed request to : HTTP 400 Bad Request\n---\nno_text".into())).is_ok());
+        assert!(webhook_check_result(Err("Failed request to : HTTP 404 Not Found\n---\nno_service".into())).is_err());
+        let archived = "Failed request to : HTTP 410 Gone\n---\nchannel_is_archived";
+        assert!(webhook_check_result(Err(archived.into())).is_err());
+    }
+
+    #[test]
+    fn test_block_kit_builder() {
+        let blocks = BlockKitBuilder::new()
+            .section("the text")
+            .context("small print")
+            .button("Open", "http://the-pr")
+            .button("Diff", "http://the-diff")
+            .action_button("Re-run", "rerun", "the-run")
+            .build();
+
+        assert_eq!(3, blocks.len());
+        assert_eq!(json!({"type": "section", "text": {"type": "mrkdwn", "text": "the text"}}), blocks[0]);
+        assert_eq!(json!({"type": "context", "elements": [{"type": "mrkdwn", "text": "small print"}]}), blocks[1]);
+        assert_eq!(json!("actions"), blocks[2]["type"]);
+        assert_eq!(json!("http://the-diff"), blocks[2]["elements"][1]["url"]);
+        assert_eq!(json!("rerun"), blocks[2]["elements"][2]["action_id"]);
+        assert_eq!(json!("the-run"), blocks[2]["elements"][2]["value"]);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let attachments = vec![
+            SlackAttachmentBuilder::new("+1 −2, 3 files")
+                .title("Pull Request #32")
+                .title_link("http://the-pr")
+                .field("Branch", "pr-branch → master")
+                .build(),
+            SlackAttachment::new(""),
+            SlackAttachmentBuilder::new("").button("rerun", "Re-run checks", "the-run").build(),
+        ];
+        let blocks = blocks("Pull Request opened", &attachments);
+
+        assert_eq!(4, blocks.len());
+        assert_eq!(json!("Pull Request opened"), blocks[0]["text"]["text"]);
+        assert_eq!(json!("*<http://the-pr|Pull Request #32>*\n+1 −2, 3 files"), blocks[1]["text"]["text"]);
+        assert_eq!(json!([{"type": "mrkdwn", "text": "*Branch*\npr-branch → master"}]), blocks[2]["fields"]);
+        assert_eq!(json!("rerun"), blocks[3]["elements"][0]["action_id"]);
+    }
+
+    #[test]
+    fn test_markdown_to_mrkdwn() {
+        assert_eq!("plain @text", markdown_to_mrkdwn("plain @text"));
+        assert_eq!(
+            "*bold*, *also bold*, _italic_, ~gone~",
+            markdown_to_mrkdwn("**bold**, __also bold__, *italic*, ~~gone~~")
+        );
+        assert_eq!(
+            "see <http://the-docs?a=1&amp;b=2|the docs> and <http://the-image.png|a screenshot>",
+            markdown_to_mrkdwn("see [the docs](http://the-docs?a=1&b=2) and ![a screenshot](http://the-image.png)")
+        );
+        assert_eq!("<http://the-image.png|image>", markdown_to_mrkdwn("![](http://the-image.png \"title\")"));
+        assert_eq!(
+            "*Heading*\n• one\n  • ☑ two\n• ☐ three",
+            markdown_to_mrkdwn("## Heading ##\n- one\n  * [x] two\n+ [ ] three")
+        );
+        assert_eq!("> quoted &lt;b&gt; and 1 &amp; 2", markdown_to_mrkdwn("> quoted <b> and 1 & 2"));
+    }
+
+    #[test]
+    fn test_markdown_to_mrkdwn_code() {
+        assert_eq!("`**not bold**` but *bold*", markdown_to_mrkdwn("`**not bold**` but **bold**"));
+        assert_eq!(
+            "```\nif a &lt; b { **x** }\n```\n*after*",
+            markdown_to_mrkdwn("```rust\nif a < b { **x** }\n```\n**after**")
+        );
+    }
+
+    #[test]
+    fn test_comment_text() {
+        assert_eq!("*short*", comment_text("  **short**\n", "http://the-comment"));
+
+        let long = format!("```\n{}\n```", "word ".repeat(500));
+        let text = comment_text(&long, "http://the-comment");
+        assert!(text.starts_with("```\nword word"));
+        assert!(text.ends_with("word\n```\n… <http://the-comment|read more>"), "{}", text);
+        assert!(text.chars().count() < MAX_COMMENT_CHARS + 50);
+    }
+
+    #[test]
+    fn test_diff_context() {
+        let hunk = "@@ -1,5 +1,6 @@\n one\n two\n-three\n+3\n four\n+if a < b {";
+        assert_eq!("```\n-three\n+3\n four\n+if a &lt; b {\n```", diff_context(hunk));
+        assert_eq!("```\n+fn main() {}\n```", diff_context("@@ -0,0 +1 @@\n+fn main() {}"));
+        assert_eq!("", diff_context("@@ -0,0 +1 @@"));
     }
 }
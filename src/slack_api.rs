@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{self, Value};
+use tokio_core::reactor::Remote;
+use url::form_urlencoded;
+
+use config::RetryConfig;
+use errors::*;
+use http_client::{HTTPClient, RetryPolicy};
+use slack::SlackAttachment;
+
+// slack allows about one message a second per channel
+const CHANNEL_INTERVAL_MS: u64 = 1000;
+
+// A message for chat.postMessage or chat.update
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct ChatMessage {
+    pub channel: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<SlackAttachment>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<Value>,
+    // post it as a reply in the thread of this message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_ts: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(channel: &str, text: &str, attachments: Vec<SlackAttachment>, blocks: Vec<Value>) -> ChatMessage {
+        ChatMessage {
+            channel: channel.into(),
+            text: text.into(),
+            attachments: attachments,
+            blocks: blocks,
+            thread_ts: None,
+        }
+    }
+}
+
+// Where a message slack accepted ended up, to update it or reply to it later
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PostedMessage {
+    // the channel's id, which chat.update needs instead of its name
+    pub channel: String,
+    pub ts: String,
+}
+
+#[derive(Deserialize)]
+struct SlackUser {
+    name: String,
+}
+
+// every web API method answers 200 with "ok" false and an error code when it fails
+#[derive(Deserialize)]
+struct ApiResponse {
+    ok: bool,
+    error: Option<String>,
+    channel: Option<String>,
+    ts: Option<String>,
+    user: Option<SlackUser>,
+}
+
+impl ApiResponse {
+    fn check(self, method: &str) -> Result<ApiResponse> {
+        if self.ok {
+            Ok(self)
+        } else {
+            Err(format!("Slack {} failed: {}", method, self.error.unwrap_or(String::new())).into())
+        }
+    }
+}
+
+// Spaces out messages to the same channel, so that bursts (e.g. a push to a branch with many PRs) don't run into
+// slack's rate limits.
+pub struct ChannelPacer {
+    interval: Duration,
+    // when each channel may get its next message
+    next_slots: Mutex<HashMap<String, Instant>>,
+}
+
+impl ChannelPacer {
+    pub fn new(interval: Duration) -> ChannelPacer {
+        ChannelPacer {
+            interval: interval,
+            next_slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // how long to wait before sending to the channel, keeping the slot after that for the next message
+    pub fn reserve(&self, channel: &str, now: Instant) -> Duration {
+        let mut next_slots = self.next_slots.lock().unwrap();
+        let slot = match next_slots.get(channel) {
+            Some(slot) if *slot > now => *slot,
+            _ => now,
+        };
+        next_slots.insert(channel.to_string(), slot + self.interval);
+        slot - now
+    }
+}
+
+// The slack web API, with a bot or user token. Rate limited calls are tried again after the Retry-After slack
+// sends along with its 429s.
+pub struct SlackApi {
+    client: HTTPClient,
+    pacer: ChannelPacer,
+}
+
+impl SlackApi {
+    pub fn new(core_remote: Remote, token: &str) -> SlackApi {
+        let client = HTTPClient::new(core_remote, "https://slack.com/api")
+            .with_headers(hashmap!{
+                "Accept" => "application/json".to_string(),
+                "Content-Type" => "application/json; charset=utf-8".to_string(),
+                "Authorization" => format!("Bearer {}", token),
+            })
+            .with_retries(RetryPolicy::new(&RetryConfig::default()));
+
+        SlackApi {
+            client: client,
+            pacer: ChannelPacer::new(Duration::from_millis(CHANNEL_INTERVAL_MS)),
+        }
+    }
+
+    pub fn check_auth(&self) -> Result<()> {
+        let resp: ApiResponse = self.client.get("auth.test").map_err(|e| {
+            Error::from(format!("Error checking slack token: {}", e))
+        })?;
+        resp.check("token check").map(|_| ())
+    }
+
+    // the slack user name for this email, or None if there is no such user
+    pub fn lookup_by_email(&self, email: &str) -> Result<Option<String>> {
+        let email: String = form_urlencoded::byte_serialize(email.as_bytes()).collect();
+        let resp: ApiResponse = self.client.get(&format!("users.lookupByEmail?email={}", email)).map_err(
+            |e| Error::from(format!("Error looking up slack user: {}", e)),
+        )?;
+
+        match (resp.ok, resp.user) {
+            (true, Some(user)) => Ok(Some(user.name)),
+            _ => {
+                match resp.error {
+                    Some(ref e) if e == "users_not_found" => Ok(None),
+                    e => Err(format!("Error looking up slack user: {}", e.unwrap_or(String::new())).into()),
+                }
+            }
+        }
+    }
+
+    // waits for the channel's turn if messages were just sent to it
+    pub fn post_message(&self, msg: &ChatMessage) -> Result<PostedMessage> {
+        let wait = self.pacer.reserve(&msg.channel, Instant::now());
+        if wait > Duration::from_millis(0) {
+            thread::sleep(wait);
+        }
+
+        let resp: ApiResponse = self.client.post("chat.postMessage", msg).map_err(|e| {
+            Error::from(format!("Error posting slack message: {}", e))
+        })?;
+        let resp = resp.check("chat.postMessage")?;
+        match (resp.channel, resp.ts) {
+            (Some(channel), Some(ts)) => Ok(PostedMessage { channel: channel, ts: ts }),
+            _ => Err("Slack chat.postMessage didn't say where the message went".into()),
+        }
+    }
+
+    // replaces the text, attachments, and blocks of a message that was posted before
+    pub fn update_message(&self, posted: &PostedMessage, msg: &ChatMessage) -> Result<()> {
+        let body = update_body(posted, msg)?;
+        let resp: ApiResponse = self.client.post("chat.update", &body).map_err(|e| {
+            Error::from(format!("Error updating slack message: {}", e))
+        })?;
+        resp.check("chat.update").map(|_| ())
+    }
+
+    // adds an emoji reaction (by its name, e.g. "white_check_mark") to a message. it's fine if it's there already
+    pub fn add_reaction(&self, posted: &PostedMessage, name: &str) -> Result<()> {
+        self.react("reactions.add", posted, name, "already_reacted")
+    }
+
+    // it's fine if it wasn't there
+    pub fn remove_reaction(&self, posted: &PostedMessage, name: &str) -> Result<()> {
+        self.react("reactions.remove", posted, name, "no_reaction")
+    }
+
+    fn react(&self, method: &str, posted: &PostedMessage, name: &str, ok_error: &str) -> Result<()> {
+        let body = json!({"channel": posted.channel, "timestamp": posted.ts, "name": name});
+        let resp: ApiResponse = self.client.post(method, &body).map_err(|e| {
+            Error::from(format!("Error calling slack {}: {}", method, e))
+        })?;
+        if resp.error.as_ref().map_or(false, |e| e == ok_error) {
+            return Ok(());
+        }
+        resp.check(method).map(|_| ())
+    }
+}
+
+fn update_body(posted: &PostedMessage, msg: &ChatMessage) -> Result<Value> {
+    let mut body = serde_json::to_value(msg).map_err(|e| Error::from(format!("Error encoding message: {}", e)))?;
+    if let Value::Object(ref mut fields) = body {
+        fields.remove("thread_ts");
+        fields.insert("channel".into(), json!(posted.channel));
+        fields.insert("ts".into(), json!(posted.ts));
+        // empty lists clear what the message had before
+        fields.entry("attachments").or_insert(json!([]));
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_pacer() {
+        let pacer = ChannelPacer::new(Duration::from_millis(1000));
+        let now = Instant::now();
+
+        assert_eq!(Duration::from_millis(0), pacer.reserve("reviews", now));
+        assert_eq!(Duration::from_millis(1000), pacer.reserve("reviews", now));
+        assert_eq!(Duration::from_millis(1500), pacer.reserve("reviews", now + Duration::from_millis(500)));
+        assert_eq!(Duration::from_millis(0), pacer.reserve("releases", now));
+
+        // the channel's slots are all in the past by now
+        assert_eq!(Duration::from_millis(0), pacer.reserve("reviews", now + Duration::from_millis(5000)));
+    }
+
+    #[test]
+    fn test_update_body() {
+        let posted = PostedMessage {
+            channel: "C024BE91L".into(),
+            ts: "1401383885.000061".into(),
+        };
+        let mut msg = ChatMessage::new("reviews", "PR merged", vec![], vec![]);
+        msg.thread_ts = Some("1401383800.000001".into());
+
+        assert_eq!(
+            json!({"channel": "C024BE91L", "ts": "1401383885.000061", "text": "PR merged", "attachments": []}),
+            update_body(&posted, &msg).unwrap()
+        );
+    }
+}
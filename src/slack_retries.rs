@@ -0,0 +1,159 @@
+use std::cmp;
+
+use hyper::StatusCode;
+
+use errors::*;
+use slack::SlackRequest;
+
+// tries in all, including the first one
+const MAX_ATTEMPTS: u32 = 6;
+const RETRY_BACKOFF_SECS: i64 = 60;
+const MAX_RETRY_BACKOFF_SECS: i64 = 60 * 60;
+// failed messages kept around for an admin to look at, oldest dropped first
+const MAX_FAILED: usize = 200;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FailedMessage {
+    pub id: String,
+    pub req: SlackRequest,
+    pub attempts: u32,
+    pub error: String,
+    pub failed_at: i64,
+    pub retry_at: i64,
+}
+
+// Slack messages that couldn't be sent: ones that may go through later (rate limits, slack or network
+// being down) are tried again with backoff, the rest are kept so that they can be looked at and re-sent.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SlackRetries {
+    retrying: Vec<FailedMessage>,
+    failed: Vec<FailedMessage>,
+    next_id: u64,
+}
+
+// whether sending again later might work
+pub fn is_retryable(err: &Error) -> bool {
+    match *err.kind() {
+        ErrorKind::HttpStatus(_, status, _, _) => status == StatusCode::TooManyRequests || status.is_server_error(),
+        ErrorKind::HttpCancelled => true,
+        _ => false,
+    }
+}
+
+impl SlackRetries {
+    pub fn add_failure(&mut self, req: SlackRequest, err: &Error, now: i64) {
+        let attempts = req.attempts + 1;
+        self.next_id += 1;
+        let mut failure = FailedMessage {
+            id: format!("{}-{}", now, self.next_id),
+            req: req,
+            attempts: attempts,
+            error: err.to_string(),
+            failed_at: now,
+            retry_at: now,
+        };
+
+        if is_retryable(err) && attempts < MAX_ATTEMPTS {
+            let backoff = RETRY_BACKOFF_SECS.saturating_mul(1 << (attempts - 1));
+            failure.retry_at = now + cmp::min(backoff, MAX_RETRY_BACKOFF_SECS);
+            self.retrying.push(failure);
+            return;
+        }
+
+        error!("Giving up on slack message to {} after {} attempt(s): {}", failure.req.channel, attempts, err);
+        self.failed.push(failure);
+        if self.failed.len() > MAX_FAILED {
+            let extra = self.failed.len() - MAX_FAILED;
+            self.failed.drain(0..extra);
+        }
+    }
+
+    // removes the messages that are due to be tried again and returns them
+    pub fn take_due(&mut self, now: i64) -> Vec<SlackRequest> {
+        let (due, waiting): (Vec<FailedMessage>, Vec<FailedMessage>) =
+            self.retrying.drain(..).partition(|f| f.retry_at <= now);
+        self.retrying = waiting;
+        due.into_iter()
+            .map(|f| {
+                let mut req = f.req;
+                req.attempts = f.attempts;
+                req
+            })
+            .collect()
+    }
+
+    // messages that won't be tried again
+    pub fn failed(&self) -> &Vec<FailedMessage> {
+        &self.failed
+    }
+
+    // removes a failed message to send it again, with a fresh set of retries
+    pub fn take_failed(&mut self, id: &str) -> Option<SlackRequest> {
+        let index = self.failed.iter().position(|f| f.id == id)?;
+        let mut req = self.failed.remove(index).req;
+        req.attempts = 0;
+        Some(req)
+    }
+
+    pub fn len(&self) -> usize {
+        self.retrying.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slack;
+
+    fn http_error(status: StatusCode) -> Error {
+        ErrorKind::HttpStatus("".into(), status, None, "".into()).into()
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(&http_error(StatusCode::TooManyRequests)));
+        assert!(is_retryable(&http_error(StatusCode::BadGateway)));
+        assert!(is_retryable(&ErrorKind::HttpCancelled.into()));
+        assert!(!is_retryable(&http_error(StatusCode::NotFound)));
+        assert!(!is_retryable(&"Error json-encoding body".into()));
+    }
+
+    #[test]
+    fn test_retry_with_backoff() {
+        let mut retries = SlackRetries::default();
+        retries.add_failure(slack::req("the-channel", "hello", vec![]), &http_error(StatusCode::BadGateway), 1000);
+        assert_eq!(1, retries.len());
+        assert_eq!(0, retries.take_due(1059).len());
+
+        let due = retries.take_due(1060);
+        assert_eq!(1, due.len());
+        assert_eq!(1, due[0].attempts);
+        assert_eq!(0, retries.len());
+
+        retries.add_failure(due[0].clone(), &http_error(StatusCode::TooManyRequests), 2000);
+        assert_eq!(0, retries.take_due(2119).len());
+        assert_eq!(2, retries.take_due(2120)[0].attempts);
+        assert!(retries.failed().is_empty());
+    }
+
+    #[test]
+    fn test_give_up() {
+        let mut retries = SlackRetries::default();
+        let mut req = slack::req("the-channel", "hello", vec![]);
+        req.attempts = MAX_ATTEMPTS - 1;
+        retries.add_failure(req, &http_error(StatusCode::ServiceUnavailable), 1000);
+        retries.add_failure(slack::req("@joe", "hi", vec![]), &http_error(StatusCode::NotFound), 1000);
+
+        assert_eq!(0, retries.len());
+        let channels: Vec<&str> = retries.failed().iter().map(|f| f.req.channel.as_str()).collect();
+        assert_eq!(vec!["the-channel", "@joe"], channels);
+        assert_eq!(MAX_ATTEMPTS, retries.failed()[0].attempts);
+
+        let id = retries.failed()[1].id.clone();
+        let req = retries.take_failed(&id).unwrap();
+        assert_eq!("@joe", req.channel);
+        assert_eq!(0, req.attempts);
+        assert_eq!(1, retries.failed().len());
+        assert_eq!(None, retries.take_failed(&id));
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use config::Config;
+use errors::*;
+use github;
+use github::api::Session;
+use json_store::JsonStore;
+use quiet_hours;
+use slack_api::SlackApi;
+use worker;
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+// how long to trust a lookup before asking again
+const FOUND_TTL_SECS: i64 = 7 * DAY_SECS;
+const NOT_FOUND_TTL_SECS: i64 = DAY_SECS;
+
+pub trait SlackDirectory: Send + Sync {
+    // the slack user name for this email, or None if there is no such user
+    fn lookup_by_email(&self, email: &str) -> Result<Option<String>>;
+}
+
+impl SlackDirectory for SlackApi {
+    fn lookup_by_email(&self, email: &str) -> Result<Option<String>> {
+        SlackApi::lookup_by_email(self, email)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct LookupResult {
+    slack: Option<String>,
+    checked_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct SlackUserCache {
+    // by github login
+    users: HashMap<String, LookupResult>,
+}
+
+// Finds slack users by the email of their github account so that they don't all have to be
+// configured by hand. Anyone who can't be found keeps their configured slack name.
+pub struct SlackUserResolver {
+    github: Arc<Session>,
+    slack: Box<SlackDirectory>,
+    cache: JsonStore<SlackUserCache>,
+}
+
+impl SlackUserResolver {
+    pub fn new(github: Arc<Session>, slack: Box<SlackDirectory>, cache_file: Option<PathBuf>) -> SlackUserResolver {
+        SlackUserResolver {
+            github: github,
+            slack: slack,
+            cache: JsonStore::new(cache_file),
+        }
+    }
+
+    // Looks up any of the given users we don't know about yet (or not for a while), and gives what
+    // we know to the users config.
+    pub fn resolve(&self, config: &Config, repo: &github::Repo, users: &Vec<github::User>, now: i64) {
+        let mut found: Vec<(String, Option<String>)> = vec![];
+        for login in logins(users) {
+            let cached = self.cache.read(|c| c.users.get(login).cloned());
+            let result = match cached {
+                Some(ref r) if !is_expired(r, now) => r.clone(),
+                _ => {
+                    match self.lookup(config, repo, login) {
+                        Ok(slack) => {
+                            let result = LookupResult {
+                                slack: slack,
+                                checked_at: now,
+                            };
+                            self.cache.write(|c| c.users.insert(login.to_string(), result.clone()));
+                            result
+                        }
+                        Err(e) => {
+                            error!("Error looking up slack user for {}: {}", login, e);
+                            continue;
+                        }
+                    }
+                }
+            };
+            found.push((login.to_string(), result.slack));
+        }
+
+        // the users lock is what every handler reads names with: only take it if there's something new
+        found.retain(|&(ref login, ref slack)| config.users().lookup_resolved(login, repo) != *slack);
+        if !found.is_empty() {
+            let mut users = config.users_write();
+            for (login, slack) in found {
+                users.set_resolved(&login, repo, slack);
+            }
+        }
+    }
+
+    // the users that have to be looked up (again) before we know their slack names
+    pub fn unresolved(&self, users: &Vec<github::User>, now: i64) -> Vec<github::User> {
+        let stale: Vec<&str> = logins(users)
+            .into_iter()
+            .filter(|login| self.cache.read(|c| c.users.get(*login).map_or(true, |r| is_expired(r, now))))
+            .collect();
+        stale.into_iter().map(github::User::new).collect()
+    }
+
+    fn lookup(&self, config: &Config, repo: &github::Repo, login: &str) -> Result<Option<String>> {
+        let email = match self.github.get_user(login)?.email {
+            Some(ref e) if !e.is_empty() => Some(e.clone()),
+            _ => config.repos().slack_email_domain(repo).map(|d| format!("{}@{}", login, d)),
+        };
+
+        match email {
+            Some(email) => self.slack.lookup_by_email(&email),
+            None => Ok(None),
+        }
+    }
+}
+
+fn logins(users: &Vec<github::User>) -> Vec<&str> {
+    let mut logins: Vec<&str> = users.iter().map(|u| u.login()).filter(|l| !l.is_empty()).collect();
+    logins.sort();
+    logins.dedup();
+    logins
+}
+
+fn is_expired(result: &LookupResult, now: i64) -> bool {
+    let ttl = if result.slack.is_some() { FOUND_TTL_SECS } else { NOT_FOUND_TTL_SECS };
+    now - result.checked_at >= ttl
+}
+
+#[derive(Clone, Debug)]
+pub struct SlackUserRequest {
+    pub repo: github::Repo,
+    pub users: Vec<github::User>,
+}
+
+struct Runner {
+    config: Arc<Config>,
+    resolver: Arc<SlackUserResolver>,
+}
+
+// Does the lookups off the webhook request path: the event that brings a new user along goes out with
+// the names known so far, and the ones after it get the looked up names.
+pub fn new_worker(config: Arc<Config>, resolver: Arc<SlackUserResolver>) -> worker::Worker<SlackUserRequest> {
+    worker::Worker::new(
+        "slack-users",
+        Runner {
+            config: config,
+            resolver: resolver,
+        },
+    )
+}
+
+impl worker::Runner<SlackUserRequest> for Runner {
+    fn handle(&self, req: SlackUserRequest) {
+        self.resolver.resolve(&self.config, &req.repo, &req.users, quiet_hours::now());
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use time;
+
+use cluster::Cluster;
+use config::Config;
+use github;
+use github::PullRequestLike;
+use github::api::Session;
+use messenger::{self, Messenger, MessengerFactory};
+use quiet_hours;
+use slack::SlackAttachmentBuilder;
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+const CHECK_INTERVAL_SECS: u64 = 60;
+// how long a node keeps sending the reminders for the cluster after it last said it would
+const LEADER_LEASE_SECS: u64 = 3 * CHECK_INTERVAL_SECS;
+
+// seconds since the PR was last touched, if github told us
+pub fn idle_secs(pull_request: &github::PullRequest, now: i64) -> Option<i64> {
+    let updated_at = match pull_request.updated_at {
+        Some(ref t) => t,
+        None => return None,
+    };
+    match time::strptime(updated_at, "%Y-%m-%dT%H:%M:%SZ") {
+        Ok(tm) => Some(now - tm.to_timespec().sec),
+        Err(e) => {
+            error!("Invalid updated_at for PR #{}: '{}': {}", pull_request.number, updated_at, e);
+            None
+        }
+    }
+}
+
+// Remind the channel and the assignees about open PRs that nobody has touched in a while.
+pub fn remind(github: &Session, config: &Config, messenger: &Messenger, repo: &github::Repo, now: i64) {
+    let days = match config.repos().stale_pr_days(repo) {
+        Some(d) => d,
+        None => return,
+    };
+
+    let pull_requests = match github.get_pull_requests(repo.owner.login(), &repo.name, Some("open"), None) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Error looking up open PRs for {}: {}", repo.full_name, e);
+            return;
+        }
+    };
+
+    // drafts aren't expected to move along yet
+    for pull_request in pull_requests.into_iter().filter(|pr| !pr.is_draft()) {
+        let idle_days = match idle_secs(&pull_request, now) {
+            Some(secs) if secs >= days as i64 * DAY_SECS => secs / DAY_SECS,
+            _ => continue,
+        };
+
+        let msg = format!("Pull Request has been idle for {} days", idle_days);
+        let attachments = vec![
+            SlackAttachmentBuilder::new("")
+                .title(format!("Pull Request #{}: \"{}\"", pull_request.number, pull_request.title))
+                .title_link(pull_request.html_url.as_str())
+                .build(),
+        ];
+
+        let branch = Some(pull_request.base.ref_name.as_str());
+        messenger.send_to_channel(&msg, &attachments, repo, branch);
+        let mut assignees = (&pull_request).assignees();
+        assignees.retain(|a| !config.users().is_out_of_office(a.login(), repo));
+        messenger.send_to_users(&msg, &attachments, &assignees, repo, messenger::Reason::Participant);
+    }
+}
+
+// Checks every minute whether any repo is due for its daily stale PR reminder. In a cluster, one node sends them.
+pub fn start(config: Arc<Config>, github: Arc<Session>, messengers: MessengerFactory, cluster: Option<Arc<Cluster>>) {
+    let result = thread::Builder::new().name("stale-prs".into()).spawn(move || {
+        let messenger = messengers.new_messenger();
+        let mut next_run: HashMap<String, i64> = HashMap::new();
+        let job = format!("stale-prs:{}", github.github_host());
+
+        loop {
+            thread::sleep(Duration::from_secs(CHECK_INTERVAL_SECS));
+            if let Some(ref cluster) = cluster {
+                if !cluster.is_leader(&job, LEADER_LEASE_SECS) {
+                    next_run.clear();
+                    continue;
+                }
+            }
+
+            let now = quiet_hours::now();
+            for repo in config.repos().stale_pr_repos(github.github_host()) {
+                let time_of_day = config.repos().stale_pr_reminder_time(&repo);
+                let due = *next_run.entry(repo.full_name.clone()).or_insert_with(|| {
+                    quiet_hours::next_time_of_day(time_of_day, 0, now)
+                });
+                if due > now {
+                    continue;
+                }
+
+                remind(&*github, &config, &*messenger, &repo, now);
+                next_run.insert(repo.full_name.clone(), quiet_hours::next_time_of_day(time_of_day, 0, now + 1));
+            }
+        }
+    });
+
+    if let Err(e) = result {
+        error!("Error starting stale PR thread: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_secs() {
+        let mut pr = github::PullRequest::new();
+        assert_eq!(None, idle_secs(&pr, 1506902400));
+
+        // 2017-10-02 00:00:00 UTC
+        pr.updated_at = Some("2017-09-30T12:00:00Z".into());
+        assert_eq!(Some(DAY_SECS + DAY_SECS / 2), idle_secs(&pr, 1506902400));
+
+        pr.updated_at = Some("yesterday".into());
+        assert_eq!(None, idle_secs(&pr, 1506902400));
+    }
+}
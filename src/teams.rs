@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use errors::*;
+use github;
+use github::api::Session;
+
+const CACHE_SECS: u64 = 10 * 60;
+
+// Team members by "host:org/team", so that a busy thread mentioning a team doesn't look the team up on every
+// comment. Failed lookups aren't cached.
+pub struct TeamCache {
+    entries: Mutex<HashMap<String, (Instant, Vec<github::User>)>>,
+}
+
+impl TeamCache {
+    pub fn new() -> TeamCache {
+        TeamCache { entries: Mutex::new(HashMap::new()) }
+    }
+
+    // team is "org/team"
+    pub fn members(&self, github: &Session, team: &str) -> Result<Vec<github::User>> {
+        let key = format!("{}:{}", github.github_host(), team);
+        if let Some(&(ref fetched, ref members)) = self.entries.lock().unwrap().get(&key) {
+            if fetched.elapsed() < Duration::from_secs(CACHE_SECS) {
+                return Ok(members.clone());
+            }
+        }
+
+        let (org, slug) = match team.find('/') {
+            Some(i) => (&team[..i], &team[i + 1..]),
+            None => return Err(format!("Not a team: {}", team).into()),
+        };
+        let members = github.get_team_members(org, slug)?;
+        self.entries.lock().unwrap().insert(key, (Instant::now(), members.clone()));
+        Ok(members)
+    }
+}
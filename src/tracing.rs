@@ -0,0 +1,354 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::mem;
+
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::Value;
+use time;
+use tokio_core::reactor::Remote;
+
+use config::TracingConfig;
+use http_client::HTTPClient;
+use worker::{self, WorkSender, Worker};
+
+const DEFAULT_SERVICE_NAME: &'static str = "octobot";
+// OTLP status codes
+const STATUS_ERROR: u32 = 2;
+
+// Where a span is, so that work handed to another thread (e.g. a backport) can go on the same trace
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpanContext {
+    pub trace_id: String,
+    pub span_id: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    pub start_ns: u64,
+    pub end_ns: u64,
+    pub attributes: Vec<(String, String)>,
+    // why the span failed, if it did
+    pub error: Option<String>,
+}
+
+// The trace being recorded on this thread: the spans still open, innermost last, and the ones that are done
+struct ActiveTrace {
+    exporter: WorkSender<Vec<Span>>,
+    open: Vec<Span>,
+    done: Vec<Span>,
+}
+
+thread_local! {
+    static TRACE: RefCell<Option<ActiveTrace>> = RefCell::new(None);
+}
+
+// Sends the spans of each trace recorded through it to an OTLP collector, once the trace's root span ends.
+pub struct Tracer {
+    worker: Worker<Vec<Span>>,
+}
+
+impl Tracer {
+    pub fn new(core_remote: Remote, config: &TracingConfig) -> Tracer {
+        let client = HTTPClient::new(core_remote, "").with_headers(hashmap!{
+            "Content-Type" => "application/json".to_string(),
+        });
+        let runner = Runner {
+            client: client,
+            url: format!("{}/v1/traces", config.otlp_endpoint.trim_right_matches('/')),
+            service_name: config.service_name.clone().unwrap_or(DEFAULT_SERVICE_NAME.into()),
+        };
+        Tracer { worker: worker::Worker::new("tracing", runner) }
+    }
+
+    // Runs `func` as the root span of a new trace, or as a child of `parent` when it goes on with work from
+    // another thread. Inside a trace already, it's just another span in it.
+    pub fn trace<T, F>(&self, name: &str, parent: Option<&SpanContext>, attributes: &[(&str, String)], func: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        if current().is_some() {
+            return span(name, attributes, func);
+        }
+
+        TRACE.with(|t| {
+            *t.borrow_mut() = Some(ActiveTrace {
+                exporter: self.worker.new_sender(),
+                open: vec![],
+                done: vec![],
+            })
+        });
+        let _trace = TraceGuard;
+        let _span = SpanGuard::start(name, parent, attributes);
+        func()
+    }
+}
+
+// `Tracer::trace` when tracing is on, otherwise just `func`
+pub fn trace<T, F>(
+    tracer: Option<&Tracer>,
+    name: &str,
+    parent: Option<&SpanContext>,
+    attributes: &[(&str, String)],
+    func: F,
+) -> T
+where
+    F: FnOnce() -> T,
+{
+    match tracer {
+        Some(tracer) => tracer.trace(name, parent, attributes, func),
+        None => func(),
+    }
+}
+
+// Runs `func` as a span of the trace going on in this thread, if there's one.
+pub fn span<T, F: FnOnce() -> T>(name: &str, attributes: &[(&str, String)], func: F) -> T {
+    let _span = SpanGuard::start(name, None, attributes);
+    func()
+}
+
+// Like `span`, and marks the span failed if `func` does.
+pub fn span_result<T, E: fmt::Display, F>(name: &str, attributes: &[(&str, String)], func: F) -> Result<T, E>
+where
+    F: FnOnce() -> Result<T, E>,
+{
+    span(name, attributes, || {
+        let result = func();
+        if let Err(ref e) = result {
+            set_error(&e.to_string());
+        }
+        result
+    })
+}
+
+// Marks the innermost open span on this thread failed
+pub fn set_error(error: &str) {
+    with_trace(|trace| if let Some(span) = trace.open.last_mut() {
+        span.error = Some(error.to_string());
+    });
+}
+
+// The innermost open span on this thread, if a trace is going on in it
+pub fn current() -> Option<SpanContext> {
+    TRACE.with(|t| match *t.borrow() {
+        Some(ref trace) => trace.open.last().map(|s| {
+            SpanContext {
+                trace_id: s.trace_id.clone(),
+                span_id: s.span_id.clone(),
+            }
+        }),
+        None => None,
+    })
+}
+
+fn with_trace<F: FnOnce(&mut ActiveTrace)>(func: F) {
+    TRACE.with(|t| if let Some(ref mut trace) = *t.borrow_mut() {
+        func(trace);
+    });
+}
+
+fn now_ns() -> u64 {
+    let now = time::get_time();
+    now.sec as u64 * 1_000_000_000 + now.nsec as u64
+}
+
+fn random_id(bytes: usize) -> String {
+    let mut buf = vec![0; bytes];
+    SystemRandom::new().fill(&mut buf).expect("get random");
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// ends its span when it goes out of scope, even if by panicking
+struct SpanGuard;
+
+impl SpanGuard {
+    fn start(name: &str, parent: Option<&SpanContext>, attributes: &[(&str, String)]) -> SpanGuard {
+        with_trace(|trace| {
+            let (trace_id, parent_span_id) = match (trace.open.last(), parent) {
+                (Some(s), _) => (s.trace_id.clone(), Some(s.span_id.clone())),
+                (None, Some(p)) => (p.trace_id.clone(), Some(p.span_id.clone())),
+                (None, None) => (random_id(16), None),
+            };
+            trace.open.push(Span {
+                trace_id: trace_id,
+                span_id: random_id(8),
+                parent_span_id: parent_span_id,
+                name: name.to_string(),
+                start_ns: now_ns(),
+                end_ns: 0,
+                attributes: attributes.iter().map(|&(k, ref v)| (k.to_string(), v.clone())).collect(),
+                error: None,
+            });
+        });
+        SpanGuard
+    }
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        with_trace(|trace| if let Some(mut span) = trace.open.pop() {
+            span.end_ns = now_ns();
+            trace.done.push(span);
+        });
+    }
+}
+
+// sends off the trace's spans once its root span is done
+struct TraceGuard;
+
+impl Drop for TraceGuard {
+    fn drop(&mut self) {
+        let trace = TRACE.with(|t| mem::replace(&mut *t.borrow_mut(), None));
+        if let Some(trace) = trace {
+            if let Err(e) = trace.exporter.send(trace.done) {
+                error!("Error sending spans to the tracing exporter: {}", e);
+            }
+        }
+    }
+}
+
+// The OTLP/JSON export request for a trace's spans
+pub fn otlp_request(service_name: &str, spans: &[Span]) -> Value {
+    let attribute = |key: &str, value: &str| json!({"key": key, "value": {"stringValue": value}});
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|s| {
+            let mut span = json!({
+                "traceId": s.trace_id,
+                "spanId": s.span_id,
+                "name": s.name,
+                "kind": 1,
+                "startTimeUnixNano": s.start_ns.to_string(),
+                "endTimeUnixNano": s.end_ns.to_string(),
+                "attributes": s.attributes.iter().map(|&(ref k, ref v)| attribute(k, v)).collect::<Vec<_>>(),
+            });
+            if let Some(ref parent) = s.parent_span_id {
+                span["parentSpanId"] = json!(parent);
+            }
+            if let Some(ref error) = s.error {
+                span["status"] = json!({"code": STATUS_ERROR, "message": error});
+            }
+            span
+        })
+        .collect();
+
+    json!({
+        "resourceSpans": [{
+            "resource": {"attributes": [attribute("service.name", service_name)]},
+            "scopeSpans": [{"scope": {"name": "octobot"}, "spans": spans}],
+        }]
+    })
+}
+
+struct Runner {
+    client: HTTPClient,
+    url: String,
+    service_name: String,
+}
+
+impl worker::Runner<Vec<Span>> for Runner {
+    fn handle(&self, spans: Vec<Span>) {
+        if spans.is_empty() {
+            return;
+        }
+        if let Err(e) = self.client.post_void(&self.url, &otlp_request(&self.service_name, &spans)) {
+            error!("Error exporting {} spans to {}: {}", spans.len(), self.url, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use worker::WorkMessage;
+
+    fn record<F: FnOnce()>(func: F) -> Vec<Span> {
+        let (tx, rx) = channel();
+        TRACE.with(|t| {
+            *t.borrow_mut() = Some(ActiveTrace {
+                exporter: WorkSender::new(tx),
+                open: vec![],
+                done: vec![],
+            })
+        });
+        {
+            let _trace = TraceGuard;
+            func();
+        }
+        match rx.recv().unwrap() {
+            WorkMessage::WorkItem(spans) => spans,
+            WorkMessage::Stop => panic!("no spans"),
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let spans = record(|| {
+            span("handle_hook", &[("event", "pull_request".into())], || {
+                let _: Result<(), String> = span_result("GET /repos", &[], || Err("HTTP 502".into()));
+                span("slack", &[], || ());
+            })
+        });
+
+        assert_eq!(3, spans.len());
+        let (get, slack, root) = (&spans[0], &spans[1], &spans[2]);
+        assert_eq!("handle_hook", root.name);
+        assert_eq!(None, root.parent_span_id);
+        assert_eq!(vec![("event".to_string(), "pull_request".to_string())], root.attributes);
+        assert_eq!(32, root.trace_id.len());
+        assert_eq!(16, root.span_id.len());
+        assert!(root.end_ns >= root.start_ns);
+
+        assert_eq!("GET /repos", get.name);
+        assert_eq!(Some(root.span_id.clone()), get.parent_span_id);
+        assert_eq!(root.trace_id, get.trace_id);
+        assert_eq!(Some("HTTP 502".to_string()), get.error);
+        assert_eq!(Some(root.span_id.clone()), slack.parent_span_id);
+        assert_eq!(None, slack.error);
+        assert!(current().is_none());
+    }
+
+    #[test]
+    fn test_no_trace() {
+        assert_eq!(5, span("nothing", &[], || 5));
+        assert!(current().is_none());
+    }
+
+    #[test]
+    fn test_otlp_request() {
+        let span = Span {
+            trace_id: "0af7651916cd43dd8448eb211c80319c".into(),
+            span_id: "b7ad6b7169203331".into(),
+            parent_span_id: Some("00f067aa0ba902b7".into()),
+            name: "merge".into(),
+            start_ns: 1000,
+            end_ns: 2000,
+            attributes: vec![("repo".into(), "some-user/some-repo".into())],
+            error: Some("conflicts".into()),
+        };
+        let req = otlp_request("octobot", &[span]);
+
+        assert_eq!(
+            json!([{"key": "service.name", "value": {"stringValue": "octobot"}}]),
+            req["resourceSpans"][0]["resource"]["attributes"]
+        );
+        assert_eq!(
+            json!({
+                "traceId": "0af7651916cd43dd8448eb211c80319c",
+                "spanId": "b7ad6b7169203331",
+                "parentSpanId": "00f067aa0ba902b7",
+                "name": "merge",
+                "kind": 1,
+                "startTimeUnixNano": "1000",
+                "endTimeUnixNano": "2000",
+                "attributes": [{"key": "repo", "value": {"stringValue": "some-user/some-repo"}}],
+                "status": {"code": 2, "message": "conflicts"},
+            }),
+            req["resourceSpans"][0]["scopeSpans"][0]["spans"][0]
+        );
+    }
+}
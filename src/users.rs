@@ -5,19 +5,91 @@ use std::io::Read;
 use url::Url;
 
 use github;
+use quiet_hours::{self, WorkHours};
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct UserInfo {
     pub github: String,
     pub slack: String,
+    // which direct messages to get: "all", "reviews" (only review requests), "mentions", or "none".
+    // Defaults to "all"
+    pub direct_messages: Option<String>,
+    // direct messages outside of these hours wait for the next working day, e.g. "09:00-17:00"
+    pub work_hours: Option<String>,
+    // offset from UTC for work_hours, e.g. "-08:00". Defaults to UTC
+    pub timezone: Option<String>,
+    // batch comments and pushes into a daily digest
+    pub digest: Option<bool>,
+    // away on vacation: no direct messages, review nags, or reviews from the reviewer pool
+    pub out_of_office: Option<bool>,
+    // github login of whoever gets the direct messages of this user while they're out of office.
+    // Without one, they're dropped
+    pub delegate: Option<String>,
+    // the language of their direct messages, e.g. "fr". Defaults to the repo's
+    pub locale: Option<String>,
 }
 
+impl UserInfo {
+    pub fn new(git_user: &str, slack_user: &str) -> UserInfo {
+        UserInfo {
+            github: git_user.to_string(),
+            slack: slack_user.to_string(),
+            direct_messages: None,
+            work_hours: None,
+            timezone: None,
+            digest: None,
+            out_of_office: None,
+            delegate: None,
+            locale: None,
+        }
+    }
+
+    pub fn with_direct_messages(self, value: &str) -> UserInfo {
+        let mut info = self;
+        info.direct_messages = Some(value.to_string());
+        info
+    }
+
+    pub fn with_digest(self, value: bool) -> UserInfo {
+        let mut info = self;
+        info.digest = Some(value);
+        info
+    }
+
+    pub fn with_out_of_office(self, delegate: Option<&str>) -> UserInfo {
+        let mut info = self;
+        info.out_of_office = Some(true);
+        info.delegate = delegate.map(|d| d.to_string());
+        info
+    }
+
+    pub fn with_locale(self, value: &str) -> UserInfo {
+        let mut info = self;
+        info.locale = Some(value.to_string());
+        info
+    }
+
+    pub fn with_work_hours(self, hours: &str, timezone: &str) -> UserInfo {
+        let mut info = self;
+        info.work_hours = Some(hours.to_string());
+        info.timezone = Some(timezone.to_string());
+        info
+    }
+}
+
+// slack name for users who don't want any messages
+pub const DND_MARKER: &'static str = "DO NOT DISTURB";
+
 // maps github host to list of users
 pub type UserHostMap = HashMap<String, Vec<UserInfo>>;
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct UserConfig {
     users: UserHostMap,
+    // slack names looked up through the slack API, by host and github login. These take precedence
+    // over the configured names.
+    #[serde(skip)]
+    resolved: HashMap<String, HashMap<String, String>>,
 }
 
 pub fn load_config(file: &str) -> std::io::Result<UserConfig> {
@@ -27,19 +99,48 @@ pub fn load_config(file: &str) -> std::io::Result<UserConfig> {
 
     let users: UserHostMap = serde_json::from_str(&contents).expect("Invalid JSON in users configuration file");
 
-    Ok(UserConfig { users: users })
+    Ok(UserConfig::from_host_map(users))
 }
 
 impl UserConfig {
     pub fn new() -> UserConfig {
-        UserConfig { users: UserHostMap::new() }
+        UserConfig::from_host_map(UserHostMap::new())
+    }
+
+    pub fn from_host_map(users: UserHostMap) -> UserConfig {
+        UserConfig {
+            users: users,
+            resolved: HashMap::new(),
+        }
+    }
+
+    pub fn host_map(&self) -> &UserHostMap {
+        &self.users
     }
 
     pub fn insert(&mut self, host: &str, git_user: &str, slack_user: &str) {
-        self.users.entry(host.to_string()).or_insert(vec![]).push(UserInfo {
-            github: git_user.to_string(),
-            slack: slack_user.to_string(),
-        });
+        self.insert_info(host, UserInfo::new(git_user, slack_user));
+    }
+
+    pub fn insert_info(&mut self, host: &str, info: UserInfo) {
+        self.users.entry(host.to_string()).or_insert(vec![]).push(info);
+    }
+
+    pub fn set_resolved(&mut self, login: &str, repo: &github::Repo, slack_user: Option<String>) {
+        let host = match Url::parse(&repo.html_url) {
+            Ok(u) => u.host_str().unwrap_or("").to_string(),
+            Err(_) => return,
+        };
+        let names = self.resolved.entry(host).or_insert(HashMap::new());
+        match slack_user {
+            Some(name) => names.insert(login.to_string(), name),
+            None => names.remove(login),
+        };
+    }
+
+    // keep what was looked up when the config is reloaded
+    pub fn keep_resolved(&mut self, old: &UserConfig) {
+        self.resolved = old.resolved.clone();
     }
 
     // our slack convention is to use '.' but github replaces dots with dashes.
@@ -59,10 +160,104 @@ impl UserConfig {
         users.iter().map(|a| self.slack_user_name(a.login(), repo)).collect()
     }
 
-    fn lookup_name(&self, login: &str, repo: &github::Repo) -> Option<String> {
+    pub fn direct_messages(&self, login: &str, repo: &github::Repo) -> String {
+        let default = "all".to_string();
+        match self.lookup_info(login, repo) {
+            None => default,
+            Some(info) => {
+                match info.direct_messages {
+                    Some(ref value) if value == "reviews" || value == "mentions" || value == "none" => value.clone(),
+                    _ => default,
+                }
+            }
+        }
+    }
+
+    pub fn work_hours(&self, login: &str, repo: &github::Repo) -> Option<WorkHours> {
         match self.lookup_info(login, repo) {
-            Some(info) => Some(info.slack.clone()),
-            None => None,
+            Some(&UserInfo { work_hours: Some(ref hours), ref timezone, .. }) if !hours.is_empty() => {
+                let timezone = timezone.clone().unwrap_or(String::new());
+                let work_hours = WorkHours::parse(hours, &timezone);
+                if work_hours.is_none() {
+                    error!("Invalid work hours for {}: '{}' ({})", login, hours, timezone);
+                }
+                work_hours
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_out_of_office(&self, login: &str, repo: &github::Repo) -> bool {
+        match self.lookup_info(login, repo) {
+            Some(info) => info.out_of_office.unwrap_or(false),
+            None => false,
+        }
+    }
+
+    // who gets the user's direct messages while they're out of office, unless they're away too
+    pub fn delegate(&self, login: &str, repo: &github::Repo) -> Option<String> {
+        if !self.is_out_of_office(login, repo) {
+            return None;
+        }
+        match self.lookup_info(login, repo).and_then(|info| info.delegate.as_ref()) {
+            Some(delegate) if !delegate.is_empty() && !self.is_out_of_office(delegate, repo) => Some(delegate.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn digest(&self, login: &str, repo: &github::Repo) -> bool {
+        match self.lookup_info(login, repo) {
+            Some(info) => info.digest.unwrap_or(false),
+            None => false,
+        }
+    }
+
+    pub fn locale(&self, login: &str, repo: &github::Repo) -> Option<String> {
+        match self.lookup_info(login, repo) {
+            Some(&UserInfo { locale: Some(ref locale), .. }) if !locale.is_empty() => Some(locale.clone()),
+            _ => None,
+        }
+    }
+
+    // seconds east of UTC of the user's timezone
+    pub fn utc_offset(&self, login: &str, repo: &github::Repo) -> i64 {
+        self.lookup_info(login, repo)
+            .and_then(|info| info.timezone.as_ref())
+            .and_then(|tz| quiet_hours::parse_utc_offset(tz))
+            .unwrap_or(0)
+    }
+
+    // The github login of a slack user: the reverse of slack_user_name.
+    pub fn github_login(&self, host: &str, slack_name: &str) -> String {
+        let resolved = self.resolved.get(host).and_then(|names| names.iter().find(|&(_, name)| name == slack_name));
+        if let Some((login, _)) = resolved {
+            return login.clone();
+        }
+        let configured = self.users.get(host).and_then(|users| users.iter().find(|u| u.slack == slack_name));
+        match configured {
+            Some(info) => info.github.clone(),
+            None => slack_name.replace('.', "-"),
+        }
+    }
+
+    fn lookup_name(&self, login: &str, repo: &github::Repo) -> Option<String> {
+        let configured = self.lookup_info(login, repo).map(|info| info.slack.clone());
+        // someone who asked not to be disturbed stays that way
+        if configured.as_ref().map_or(false, |name| name == DND_MARKER) {
+            return configured;
+        }
+        self.lookup_resolved(login, repo).or(configured)
+    }
+
+    // the slack name that was looked up for this user, if any
+    pub fn lookup_resolved(&self, login: &str, repo: &github::Repo) -> Option<String> {
+        match Url::parse(&repo.html_url) {
+            Ok(u) => {
+                u.host_str().and_then(|h| self.resolved.get(h)).and_then(|names| names.get(login)).map(
+                    |name| name.clone(),
+                )
+            }
+            Err(_) => None,
         }
     }
 
@@ -78,6 +273,25 @@ impl UserConfig {
     }
 }
 
+// Marks a user as out of office, or back, adding them to the host's users if they aren't there yet.
+pub fn set_out_of_office(
+    users: &mut UserHostMap,
+    host: &str,
+    login: &str,
+    slack_user: &str,
+    out_of_office: bool,
+    delegate: Option<&str>,
+) {
+    let host_users = users.entry(host.to_string()).or_insert(vec![]);
+    if !host_users.iter().any(|u| u.github == login) {
+        host_users.push(UserInfo::new(login, slack_user));
+    }
+    for info in host_users.iter_mut().filter(|u| u.github == login) {
+        info.out_of_office = if out_of_office { Some(true) } else { None };
+        info.delegate = if out_of_office { delegate.map(|d| d.to_string()) } else { None };
+    }
+}
+
 pub fn mention<S: Into<String>>(username: S) -> String {
     format!("@{}", username.into())
 }
@@ -127,6 +341,106 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_direct_messages() {
+        let mut users = UserConfig::new();
+        users.insert("git.company.com", "some-user", "the-slacker");
+        users.insert_info("git.company.com", UserInfo::new("quiet-user", "shh").with_direct_messages("none"));
+        users.insert_info("git.company.com", UserInfo::new("odd-user", "odd").with_direct_messages("sometimes"));
+
+        let repo = github::Repo::parse("http://git.company.com/some-user/the-repo").unwrap();
+        assert_eq!("all", users.direct_messages("some-user", &repo));
+        assert_eq!("all", users.direct_messages("unknown-user", &repo));
+        assert_eq!("none", users.direct_messages("quiet-user", &repo));
+        assert_eq!("all", users.direct_messages("odd-user", &repo));
+    }
+
+    #[test]
+    fn test_out_of_office() {
+        let mut users = UserConfig::new();
+        users.insert("git.company.com", "some-user", "the-slacker");
+        users.insert_info("git.company.com", UserInfo::new("away-user", "away").with_out_of_office(Some("some-user")));
+        users.insert_info("git.company.com", UserInfo::new("gone-user", "gone").with_out_of_office(Some("away-user")));
+        users.insert_info("git.company.com", UserInfo::new("alone-user", "alone").with_out_of_office(None));
+
+        let repo = github::Repo::parse("http://git.company.com/some-user/the-repo").unwrap();
+        assert!(!users.is_out_of_office("some-user", &repo));
+        assert!(!users.is_out_of_office("unknown-user", &repo));
+        assert!(users.is_out_of_office("away-user", &repo));
+        assert_eq!(None, users.delegate("some-user", &repo));
+        assert_eq!(Some("some-user".to_string()), users.delegate("away-user", &repo));
+        // delegates who are away themselves don't count
+        assert_eq!(None, users.delegate("gone-user", &repo));
+        assert_eq!(None, users.delegate("alone-user", &repo));
+    }
+
+    #[test]
+    fn test_locale() {
+        let mut users = UserConfig::new();
+        users.insert("git.company.com", "some-user", "the-slacker");
+        users.insert_info("git.company.com", UserInfo::new("french-user", "fr").with_locale("fr"));
+        users.insert_info("git.company.com", UserInfo::new("blank-user", "blank").with_locale(""));
+
+        let repo = github::Repo::parse("http://git.company.com/some-user/the-repo").unwrap();
+        assert_eq!(Some("fr".to_string()), users.locale("french-user", &repo));
+        assert_eq!(None, users.locale("some-user", &repo));
+        assert_eq!(None, users.locale("blank-user", &repo));
+        assert_eq!(None, users.locale("unknown-user", &repo));
+    }
+
+    #[test]
+    fn test_set_out_of_office() {
+        let mut users = UserHostMap::new();
+        set_out_of_office(&mut users, "git.company.com", "joe", "joe.smith", true, Some("jane"));
+        assert_eq!(1, users["git.company.com"].len());
+        assert_eq!("joe.smith", users["git.company.com"][0].slack);
+        assert_eq!(Some(true), users["git.company.com"][0].out_of_office);
+        assert_eq!(Some("jane".to_string()), users["git.company.com"][0].delegate);
+
+        set_out_of_office(&mut users, "git.company.com", "joe", "joe.smith", false, Some("jane"));
+        assert_eq!(1, users["git.company.com"].len());
+        assert_eq!(None, users["git.company.com"][0].out_of_office);
+        assert_eq!(None, users["git.company.com"][0].delegate);
+    }
+
+    #[test]
+    fn test_github_login() {
+        let mut users = UserConfig::new();
+        users.insert("git.company.com", "some-user", "the-slacker");
+
+        let repo = github::Repo::parse("http://git.company.com/some-user/the-repo").unwrap();
+        users.set_resolved("new-user", &repo, Some("newbie".into()));
+
+        assert_eq!("some-user", users.github_login("git.company.com", "the-slacker"));
+        assert_eq!("new-user", users.github_login("git.company.com", "newbie"));
+        assert_eq!("some-one", users.github_login("git.company.com", "some.one"));
+        // other hosts have users of their own
+        assert_eq!("the-slacker", users.github_login("git.other.com", "the-slacker"));
+    }
+
+    #[test]
+    fn test_slack_user_name_resolved() {
+        let mut users = UserConfig::new();
+        users.insert("git.company.com", "some-user", "the-slacker");
+        users.insert("git.company.com", "quiet-user", DND_MARKER);
+
+        let repo = github::Repo::parse("http://git.company.com/some-user/the-repo").unwrap();
+        users.set_resolved("some-user", &repo, Some("the-real-slacker".into()));
+        users.set_resolved("quiet-user", &repo, Some("quiet.user".into()));
+        users.set_resolved("new-user", &repo, Some("newbie".into()));
+        assert_eq!("the-real-slacker", users.slack_user_name("some-user", &repo));
+        assert_eq!(DND_MARKER, users.slack_user_name("quiet-user", &repo));
+        assert_eq!("newbie", users.slack_user_name("new-user", &repo));
+
+        // falls back to the configured name
+        users.set_resolved("some-user", &repo, None);
+        assert_eq!("the-slacker", users.slack_user_name("some-user", &repo));
+
+        let mut reloaded = UserConfig::new();
+        reloaded.keep_resolved(&users);
+        assert_eq!("newbie", reloaded.slack_user_name("new-user", &repo));
+    }
+
     #[test]
     fn test_mention() {
         assert_eq!("@me", mention("me"));
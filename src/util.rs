@@ -23,11 +23,37 @@ fn find_github_username(name: &str) -> Option<&str> {
     Some(name)
 }
 
+// what follows each @ in a comment. Quoted lines and code blocks don't count: they're usually someone else's
+// words, or code.
+fn get_mentions(body: &str) -> Vec<&str> {
+    let mut mentions = Vec::new();
+    let mut in_code_block = false;
+    for line in body.lines() {
+        let trimmed = line.trim_left();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block || trimmed.starts_with(">") {
+            continue;
+        }
+
+        for token in line.split_whitespace() {
+            if token.starts_with("@") && token.len() > 1 {
+                mentions.push(token.split_at(1).1);
+            }
+        }
+    }
+    mentions
+}
+
+// the @logins in a comment, once each. Team mentions, like @org/team, aren't logins.
 pub fn get_mentioned_usernames(body: &str) -> Vec<&str> {
     let mut mentions = Vec::new();
-    for token in body.split_whitespace() {
-        if token.starts_with("@") && token.len() > 1 {
-            if let Some(username) = find_github_username(token.split_at(1).1) {
+    for name in get_mentions(body) {
+        if let Some(username) = find_github_username(name) {
+            let is_team = name[username.len()..].starts_with("/");
+            if !username.is_empty() && !is_team && !mentions.contains(&username) {
                 mentions.push(username);
             }
         }
@@ -35,6 +61,26 @@ pub fn get_mentioned_usernames(body: &str) -> Vec<&str> {
     mentions
 }
 
+// the @org/team mentions in a comment, once each, as "org/team"
+pub fn get_mentioned_teams(body: &str) -> Vec<&str> {
+    let mut teams = Vec::new();
+    for name in get_mentions(body) {
+        let org = match find_github_username(name) {
+            Some(org) if !org.is_empty() && name[org.len()..].starts_with("/") => org,
+            _ => continue,
+        };
+        let slug = match find_github_username(&name[org.len() + 1..]) {
+            Some(slug) if !slug.is_empty() => slug,
+            _ => continue,
+        };
+        let team = &name[..org.len() + 1 + slug.len()];
+        if !teams.contains(&team) {
+            teams.push(team);
+        }
+    }
+    teams
+}
+
 pub fn format_duration(dur: time::Duration) -> String {
     let seconds = dur.num_seconds();
     // get ms as a float
@@ -103,7 +149,27 @@ mod tests {
             get_mentioned_usernames("Hey @mentioned-user, let me know what @other-mentioned-user thinks")
         );
         assert_eq!(Vec::<&str>::new(), get_mentioned_usernames("This won't count as a mention@notamention"));
+        assert_eq!(vec!["joe"], get_mentioned_usernames("@joe: and @joe again, and @, and @some-org/some-team"));
+    }
+
+    #[test]
+    fn test_mentioned_users_skips_quotes_and_code() {
+        let body = "> @quoted said this\n\n@joe what do you think?\n```\n@decorator\n```\nthanks @bob";
+        assert_eq!(vec!["joe", "bob"], get_mentioned_usernames(body));
+    }
 
+    #[test]
+    fn test_mentioned_teams() {
+        assert_eq!(
+            vec!["some-org/some-team", "some-org/other-team"],
+            get_mentioned_teams("@joe, @some-org/some-team: and @some-org/other-team, or @some-org/some-team again")
+        );
+        assert_eq!(Vec::<&str>::new(), get_mentioned_teams("not @some-org/, @/team, or org/team"));
+        assert_eq!(vec!["org/b"], get_mentioned_teams("> @org/a
+```
+@org/c
+```
+@org/b"));
     }
 
     #[test]
@@ -1,6 +1,7 @@
 use std::cmp::{self, Ordering};
 use std::fmt;
 
+use regex::Regex;
 use serde::ser::{Serialize, Serializer};
 
 #[derive(Clone, Debug)]
@@ -22,6 +23,12 @@ impl Version {
         Some(Version { parts: parts })
     }
 
+    // the version that a name like "release/1.2" or "v1.2.3" ends in
+    pub fn parse_trailing(name: &str) -> Option<Version> {
+        let re = Regex::new(r"(\d+(\.\d+)*)$").unwrap();
+        re.captures(name).and_then(|c| Version::parse(&c[1]))
+    }
+
     pub fn to_string(&self) -> String {
         self.parts.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(".")
     }
@@ -108,6 +115,15 @@ impl Serialize for Version {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_version_parse_trailing() {
+        assert_eq!("1.2.0", Version::parse_trailing("release/1.2").unwrap().to_string());
+        assert_eq!("1.2.3", Version::parse_trailing("v1.2.3").unwrap().to_string());
+        assert_eq!("2.0.0", Version::parse_trailing("2").unwrap().to_string());
+        assert!(Version::parse_trailing("esr/stable").is_none());
+        assert!(Version::parse_trailing("v1.2-rc").is_none());
+    }
+
     #[test]
     fn test_version_parse() {
         assert_eq!("1.2.3.4.5", Version::parse("1.2.3.4.5").unwrap().to_string());
@@ -0,0 +1,296 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use regex::Regex;
+
+use audit::{self, AuditLog};
+use config::Config;
+use errors::*;
+use git::Git;
+use git_clone_manager::GitCloneManager;
+use github;
+use github::api::Session;
+use messenger::{MessengerFactory, Reason};
+use slack::SlackAttachmentBuilder;
+use worker;
+
+// A release was cut or tagged: the default branch's version files move on to the next version.
+#[derive(Debug)]
+pub struct VersionBumpRequest {
+    pub repo: github::Repo,
+    // the version that was released, e.g. "2.4" for release/2.4 or "2.4.1" for the tag v2.4.1
+    pub released: String,
+    // what was released, for the PR's description
+    pub reason: String,
+}
+
+pub fn req(repo: &github::Repo, released: &str, reason: &str) -> VersionBumpRequest {
+    VersionBumpRequest {
+        repo: repo.clone(),
+        released: released.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+// A version bump rule, e.g. "Cargo.toml minor ^version = \"(.*)\"": the first capture of the pattern's first match in
+// the file is the version, which goes to the released version's next minor version.
+#[derive(Debug, PartialEq)]
+pub struct BumpRule {
+    pub path: String,
+    // "major", "minor", or "patch"
+    pub component: String,
+    pub pattern: String,
+}
+
+impl BumpRule {
+    pub fn parse(rule: &str) -> Option<BumpRule> {
+        let mut parts = rule.trim().splitn(3, char::is_whitespace);
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(path), Some(component), Some(pattern)) if component_index(component).is_some() &&
+                !pattern.trim().is_empty() => {
+                Some(BumpRule {
+                    path: path.to_string(),
+                    component: component.to_string(),
+                    pattern: pattern.trim().to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+fn component_index(component: &str) -> Option<usize> {
+    match component {
+        "major" => Some(0),
+        "minor" => Some(1),
+        "patch" => Some(2),
+        _ => None,
+    }
+}
+
+fn parse_parts(version: &str) -> Option<Vec<u32>> {
+    version.trim().trim_left_matches('v').split('.').map(|p| p.parse::<u32>().ok()).collect()
+}
+
+fn padded(parts: &Vec<u32>, len: usize) -> Vec<u32> {
+    let mut parts = parts.clone();
+    while parts.len() < len {
+        parts.push(0);
+    }
+    parts
+}
+
+// The version `current` goes to after `released`: `released` with `component` bumped, written with as many parts as
+// `current`. None if `current` is already there or past it, or either isn't a version.
+pub fn next_version(current: &str, released: &str, component: &str) -> Option<String> {
+    let index = match component_index(component) {
+        Some(i) => i,
+        None => return None,
+    };
+    let (current, released) = match (parse_parts(current), parse_parts(released)) {
+        (Some(c), Some(r)) => (c, r),
+        _ => return None,
+    };
+
+    let len = *[current.len(), released.len(), index + 1].iter().max().unwrap();
+    let mut next = padded(&released, len);
+    next[index] += 1;
+    for part in next.iter_mut().skip(index + 1) {
+        *part = 0;
+    }
+    if padded(&current, len) >= next {
+        return None;
+    }
+
+    next.truncate(*[current.len(), index + 1].iter().max().unwrap());
+    Some(next.iter().map(|p| p.to_string()).collect::<Vec<_>>().join("."))
+}
+
+// The file's contents with the version the rule finds bumped, and the (old, new) versions. None if it's already
+// bumped. An error if the rule doesn't find a version.
+pub fn bump_contents(contents: &str, rule: &BumpRule, released: &str) -> Result<Option<(String, String, String)>> {
+    let regex = Regex::new(&format!("(?m){}", rule.pattern))
+        .map_err(|e| Error::from(format!("Invalid version bump pattern '{}': {}", rule.pattern, e)))?;
+    let version = match regex.captures(contents).and_then(|c| c.get(1)) {
+        Some(v) => v,
+        None => return Err(format!("No version matching '{}' in {}", rule.pattern, rule.path).into()),
+    };
+
+    let old = version.as_str().to_string();
+    Ok(next_version(&old, released, &rule.component).map(|new| {
+        let bumped = format!("{}{}{}", &contents[..version.start()], new, &contents[version.end()..]);
+        (bumped, old, new)
+    }))
+}
+
+// Opens a pull request on the repo's default branch that bumps its version files past `released`, assigned to the
+// repo's release manager. Nothing is opened if they're already bumped, or there's already a PR for it.
+pub fn open_pull_request(
+    session: &Session,
+    clone_mgr: &GitCloneManager,
+    audit_log: &AuditLog,
+    config: &Config,
+    req: &VersionBumpRequest,
+) -> Result<Option<github::PullRequest>> {
+    let repo = &req.repo;
+    let (owner, repo_name) = (repo.owner.login(), repo.name.as_str());
+
+    let held_clone_dir = clone_mgr.clone(owner, repo_name)?;
+    let clone_dir = held_clone_dir.dir();
+    let git = Git::new(session.github_host(), session.github_token(), clone_dir);
+
+    // the clone's origin/HEAD is the default branch
+    let head = git.run(&["symbolic-ref", "--short", "refs/remotes/origin/HEAD"])?;
+    let default_branch = head.trim_left_matches("origin/").to_string();
+    let rules = config.repos().version_bump_rules(repo, &default_branch);
+    if rules.is_empty() {
+        return Ok(None);
+    }
+
+    let mut bumped = vec![];
+    let mut files = vec![];
+    for rule in &rules {
+        let mut contents = String::new();
+        fs::File::open(clone_dir.join(&rule.path))?.read_to_string(&mut contents)?;
+        if let Some((contents, old, new)) = bump_contents(&contents, rule, &req.released)? {
+            files.push((rule.path.clone(), contents));
+            bumped.push((rule.path.clone(), old, new));
+        }
+    }
+    let next = match bumped.first() {
+        Some(&(_, _, ref new)) => new.clone(),
+        None => return Ok(None),
+    };
+
+    let branch = format!("octobot/version-bump-{}", next);
+    if !git.run(&["ls-remote", "--heads", "origin", &branch])?.is_empty() {
+        info!("{} already has {}, not bumping its version again", repo.full_name, branch);
+        return Ok(None);
+    }
+    git.checkout_branch(&branch, &head)?;
+    for &(ref path, ref contents) in &files {
+        fs::File::create(clone_dir.join(path))?.write_all(contents.as_bytes())?;
+        git.run(&["add", path])?;
+    }
+    let title = format!("Bump version to {}", next);
+    git.run(&["commit", "-m", &title])?;
+
+    let target = format!("{}/{} {}", owner, repo_name, branch);
+    if session.dry_run(owner, repo_name) {
+        audit_log.record("push_branch", &target, audit::DRY_RUN);
+        return Err(format!("Dry run: not pushing {}", branch).into());
+    }
+    let pushed = git.run(&["push", "origin", &format!("{}:refs/heads/{}", branch, branch)]);
+    audit_log.record("push_branch", &target, &audit::outcome(&pushed));
+    pushed?;
+
+    let mut body = format!("After {}:\n", req.reason);
+    for &(ref path, ref old, ref new) in &bumped {
+        body += &format!("\n- `{}`: {} -> {}", path, old, new);
+    }
+    let pull_request = session.create_pull_request(owner, repo_name, &title, &body, &branch, &default_branch)?;
+
+    if let Some(manager) = config.repos().release_manager(repo) {
+        if let Err(e) = session.assign_pull_request(owner, repo_name, pull_request.number, vec![manager]) {
+            error!("Error assigning version bump PR {}#{}: {}", repo.full_name, pull_request.number, e);
+        }
+    }
+    Ok(Some(pull_request))
+}
+
+struct Runner {
+    config: Arc<Config>,
+    github_session: Arc<Session>,
+    clone_mgr: Arc<GitCloneManager>,
+    audit_log: Arc<AuditLog>,
+    messengers: MessengerFactory,
+}
+
+pub fn new_worker(
+    config: Arc<Config>,
+    github_session: Arc<Session>,
+    clone_mgr: Arc<GitCloneManager>,
+    audit_log: Arc<AuditLog>,
+    messengers: MessengerFactory,
+) -> worker::Worker<VersionBumpRequest> {
+    worker::Worker::new(
+        "version-bumps",
+        Runner {
+            config: config,
+            github_session: github_session,
+            clone_mgr: clone_mgr,
+            audit_log: audit_log,
+            messengers: messengers,
+        },
+    )
+}
+
+impl worker::Runner<VersionBumpRequest> for Runner {
+    fn handle(&self, req: VersionBumpRequest) {
+        let trigger = format!("version bump after {}", req.reason);
+        let opened = audit::with_trigger(&trigger, || {
+            open_pull_request(&*self.github_session, &self.clone_mgr, &self.audit_log, &self.config, &req)
+        });
+
+        // the release manager hears about it if there is one: otherwise it's only in the log
+        if let Err(e) = opened {
+            error!("Error bumping the version of {}: {}", req.repo.full_name, e);
+            if let Some(manager) = self.config.repos().release_manager(&req.repo) {
+                let attach = SlackAttachmentBuilder::new(&format!("{}", e)).color("danger").build();
+                self.messengers.new_messenger().send_to_users(
+                    &format!("Could not open a version bump pull request after {}", req.reason),
+                    &vec![attach],
+                    &vec![github::User::new(&manager)],
+                    &req.repo,
+                    Reason::Participant,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            Some(BumpRule {
+                path: "Cargo.toml".into(),
+                component: "minor".into(),
+                pattern: "^version = \"(.*)\"".into(),
+            }),
+            BumpRule::parse("Cargo.toml minor ^version = \"(.*)\"")
+        );
+        assert_eq!(None, BumpRule::parse("Cargo.toml minor"));
+        assert_eq!(None, BumpRule::parse("Cargo.toml build (.*)"));
+    }
+
+    #[test]
+    fn test_next_version() {
+        assert_eq!(Some("2.5".to_string()), next_version("2.4", "2.4", "minor"));
+        assert_eq!(Some("2.5.0".to_string()), next_version("2.4.3", "2.4", "minor"));
+        assert_eq!(Some("3.0.0".to_string()), next_version("2.4.3", "2.4.3", "major"));
+        assert_eq!(Some("2.4.4".to_string()), next_version("2.4.3", "v2.4.3", "patch"));
+        assert_eq!(Some("2.4.1".to_string()), next_version("2.4", "2.4", "patch"));
+
+        // already bumped
+        assert_eq!(None, next_version("2.5", "2.4", "minor"));
+        assert_eq!(None, next_version("3.0.0", "2.4.0", "minor"));
+        assert_eq!(None, next_version("2.x", "2.4", "minor"));
+    }
+
+    #[test]
+    fn test_bump_contents() {
+        let rule = BumpRule::parse("Cargo.toml minor ^version = \"(.*)\"").unwrap();
+        let contents = "[package]\nname = \"octobot\"\nversion = \"2.4.0\"\n";
+
+        let (bumped, old, new) = bump_contents(contents, &rule, "2.4").unwrap().unwrap();
+        assert_eq!("[package]\nname = \"octobot\"\nversion = \"2.5.0\"\n", bumped);
+        assert_eq!(("2.4.0", "2.5.0"), (old.as_str(), new.as_str()));
+        assert_eq!(None, bump_contents(contents, &rule, "2.3").unwrap());
+        assert!(bump_contents("[package]\n", &rule, "2.4").is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use futures::{Future, future};
+use tokio_core::reactor::Remote;
+
+use github;
+use http_client::HTTPClient;
+use slack::SlackAttachment;
+use worker;
+
+// What gets POSTed to a repo's webhook_urls for each notification octobot sends: the same
+// message as slack gets, plus what it was about.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct OutboundEvent {
+    // "some-org/some-repo"
+    pub repo: String,
+    pub repo_url: String,
+    pub branch: Option<String>,
+    pub pull_request: Option<PullRequestRef>,
+    // the kind of notification: "pull_request", "merge", "review", "comment", "push", "force_push", "release",
+    // "deployment", "workflow", "issue", "security", or "repository"
+    pub kind: Option<String>,
+    // the github action of the event, e.g. "opened" or "submitted"
+    pub action: Option<String>,
+    pub message: String,
+    pub attachments: Vec<SlackAttachment>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PullRequestRef {
+    pub number: u32,
+    pub title: String,
+    pub html_url: String,
+    pub user: String,
+}
+
+impl PullRequestRef {
+    pub fn new(pull_request: &github::PullRequest) -> PullRequestRef {
+        PullRequestRef {
+            number: pull_request.number,
+            title: pull_request.title.clone(),
+            html_url: pull_request.html_url.clone(),
+            user: pull_request.user.login().to_string(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct WebhookRequest {
+    pub url: String,
+    pub event: OutboundEvent,
+}
+
+struct Runner {
+    client: HTTPClient,
+}
+
+pub fn new_worker(core_remote: Remote) -> worker::Worker<WebhookRequest> {
+    let client = HTTPClient::new(core_remote, "").with_headers(hashmap!{
+        "Content-Type" => "application/json".to_string(),
+    });
+    worker::Worker::new("webhooks", Runner { client: client })
+}
+
+impl worker::Runner<WebhookRequest> for Runner {
+    fn handle(&self, req: WebhookRequest) {
+        let url = req.url;
+        info!("Sending event to {}", url);
+        self.client.spawn(self.client.post_void_async(&url, &req.event).then(move |res| {
+            if let Err(e) = res {
+                error!("Error sending event to {}: {}", url, e);
+            }
+            future::ok::<(), ()>(())
+        }));
+    }
+}
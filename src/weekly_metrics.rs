@@ -0,0 +1,266 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use time;
+
+use cluster::Cluster;
+use config::Config;
+use github;
+use json_store::JsonStore;
+use messenger::{Messenger, MessengerFactory};
+use quiet_hours;
+use slack::{SlackAttachment, SlackAttachmentBuilder};
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+const CHECK_INTERVAL_SECS: u64 = 60;
+// mondays at 09:00 UTC
+const REPORT_TIME_OF_DAY: i64 = 9 * 60 * 60;
+
+// A repo's pull request activity since its last weekly report
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RepoWeek {
+    pub opened: u32,
+    // seconds from opening to merging, for each PR merged
+    pub merge_secs: Vec<i64>,
+    // seconds from opening to the first review, for each PR that got its first review
+    pub first_review_secs: Vec<i64>,
+    // reviews submitted, by reviewer login
+    pub reviews: HashMap<String, u32>,
+}
+
+impl RepoWeek {
+    pub fn is_empty(&self) -> bool {
+        self.opened == 0 && self.merge_secs.is_empty() && self.reviews.is_empty()
+    }
+}
+
+// The weeks so far of the repos with weekly metrics on, recorded as their events come in
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WeeklyMetrics {
+    // by repo url
+    weeks: HashMap<String, RepoWeek>,
+    // the urls of open PRs that have been reviewed, so that only their first review counts for time to first review
+    reviewed: HashSet<String>,
+}
+
+impl WeeklyMetrics {
+    pub fn opened(&mut self, repo: &github::Repo) {
+        self.week(repo).opened += 1;
+    }
+
+    // open_secs is how long the PR was open, if github said when it was opened
+    pub fn closed(&mut self, repo: &github::Repo, pull_request: &str, merged: bool, open_secs: Option<i64>) {
+        self.reviewed.remove(pull_request);
+        if merged {
+            if let Some(secs) = open_secs {
+                self.week(repo).merge_secs.push(secs);
+            }
+        }
+    }
+
+    pub fn reviewed(&mut self, repo: &github::Repo, pull_request: &str, reviewer: &str, open_secs: Option<i64>) {
+        let first = self.reviewed.insert(pull_request.to_string());
+        let week = self.week(repo);
+        *week.reviews.entry(reviewer.to_string()).or_insert(0) += 1;
+        if first {
+            if let Some(secs) = open_secs {
+                week.first_review_secs.push(secs);
+            }
+        }
+    }
+
+    // removes and returns what the repo has done since the last report
+    pub fn take_week(&mut self, repo: &github::Repo) -> RepoWeek {
+        self.weeks.remove(&repo.html_url).unwrap_or(RepoWeek::default())
+    }
+
+    fn week(&mut self, repo: &github::Repo) -> &mut RepoWeek {
+        self.weeks.entry(repo.html_url.clone()).or_insert(RepoWeek::default())
+    }
+}
+
+// seconds from a github timestamp (e.g. a PR's created_at) until now
+pub fn secs_since(timestamp: &str, now: i64) -> Option<i64> {
+    match time::strptime(timestamp, "%Y-%m-%dT%H:%M:%SZ") {
+        Ok(tm) => Some(now - tm.to_timespec().sec),
+        Err(e) => {
+            error!("Invalid timestamp '{}': {}", timestamp, e);
+            None
+        }
+    }
+}
+
+pub fn median(values: &[i64]) -> Option<i64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort();
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        Some((sorted[mid - 1] + sorted[mid]) / 2)
+    } else {
+        Some(sorted[mid])
+    }
+}
+
+// e.g. "2d 3h", "5h 10m", or "12m"
+fn format_span(secs: i64) -> String {
+    let minutes = secs / 60;
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+// The week's numbers, with reviewers by their slack names, most reviews first
+pub fn summary(week: &RepoWeek, config: &Config, repo: &github::Repo) -> Vec<SlackAttachment> {
+    let span = |values: &[i64]| median(values).map(format_span).unwrap_or("-".into());
+    let mut lines = vec![
+        format!("PRs opened: {}", week.opened),
+        format!("PRs merged: {}", week.merge_secs.len()),
+        format!("Median time to first review: {}", span(&week.first_review_secs)),
+        format!("Median time to merge: {}", span(&week.merge_secs)),
+    ];
+
+    let mut reviewers: Vec<(&String, &u32)> = week.reviews.iter().collect();
+    reviewers.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    if !reviewers.is_empty() {
+        let counts: Vec<String> = reviewers
+            .iter()
+            .map(|&(login, count)| format!("{} ({})", config.users().slack_user_name(login, repo), count))
+            .collect();
+        lines.push(format!("Reviews: {}", counts.join(", ")));
+    }
+
+    vec![SlackAttachmentBuilder::new(&lines.join("\n")).build()]
+}
+
+// Posts the repo's week to its channel and starts the next one. Quiet weeks aren't reported.
+pub fn report(config: &Config, metrics: &JsonStore<WeeklyMetrics>, messenger: &Messenger, repo: &github::Repo) {
+    let week = metrics.write(|m| m.take_week(repo));
+    if week.is_empty() {
+        return;
+    }
+    messenger.send_to_channel("Pull requests this week", &summary(&week, config, repo), repo, None);
+}
+
+// the next monday morning after now. 1970-01-01 was a thursday
+pub fn next_report_time(now: i64) -> i64 {
+    let mut next = quiet_hours::next_time_of_day(REPORT_TIME_OF_DAY, 0, now + 1);
+    while (next / DAY_SECS + 3) % 7 != 0 {
+        next += DAY_SECS;
+    }
+    next
+}
+
+// Checks every minute whether the week is up, and reports it for the host's repos that have weekly metrics on.
+// In a cluster, one node reports them.
+pub fn start(
+    config: Arc<Config>,
+    host: String,
+    metrics: Arc<JsonStore<WeeklyMetrics>>,
+    messengers: MessengerFactory,
+    cluster: Option<Arc<Cluster>>,
+) {
+    let result = thread::Builder::new().name("weekly-metrics".into()).spawn(move || {
+        let messenger = messengers.new_messenger();
+        let mut next_run = next_report_time(quiet_hours::now());
+        let job = format!("weekly-metrics:{}", host);
+
+        loop {
+            thread::sleep(Duration::from_secs(CHECK_INTERVAL_SECS));
+            if let Some(ref cluster) = cluster {
+                if !cluster.is_leader(&job, 3 * CHECK_INTERVAL_SECS) {
+                    continue;
+                }
+            }
+
+            let now = quiet_hours::now();
+            if next_run > now {
+                continue;
+            }
+            for repo in config.repos().weekly_metrics_repos(&host) {
+                report(&config, &metrics, &*messenger, &repo);
+            }
+            next_run = next_report_time(now);
+        }
+    });
+
+    if let Err(e) = result {
+        error!("Error starting weekly metrics thread: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weekly_metrics() {
+        let repo = github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap();
+        let other = github::Repo::parse("http://git.foo.com/some-user/other-repo").unwrap();
+
+        let mut metrics = WeeklyMetrics::default();
+        metrics.opened(&repo);
+        metrics.opened(&repo);
+        metrics.opened(&other);
+        metrics.reviewed(&repo, "http://pr/1", "joe", Some(3600));
+        metrics.reviewed(&repo, "http://pr/1", "bob", Some(7200));
+        metrics.reviewed(&repo, "http://pr/2", "joe", None);
+        metrics.closed(&repo, "http://pr/1", true, Some(10000));
+        metrics.closed(&repo, "http://pr/2", false, Some(20000));
+
+        let week = metrics.take_week(&repo);
+        assert_eq!(2, week.opened);
+        assert_eq!(vec![10000], week.merge_secs);
+        assert_eq!(vec![3600], week.first_review_secs);
+        assert_eq!(Some(&2), week.reviews.get("joe"));
+        assert_eq!(Some(&1), week.reviews.get("bob"));
+
+        // taken weeks start over
+        assert!(metrics.take_week(&repo).is_empty());
+        assert_eq!(1, metrics.take_week(&other).opened);
+
+        // closed PRs are forgotten, so a reopened one's next review counts as its first again
+        metrics.reviewed(&repo, "http://pr/1", "joe", Some(50));
+        assert_eq!(vec![50], metrics.take_week(&repo).first_review_secs);
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(None, median(&[]));
+        assert_eq!(Some(5), median(&[5]));
+        assert_eq!(Some(3), median(&[9, 1, 3]));
+        assert_eq!(Some(4), median(&[9, 1, 3, 5]));
+    }
+
+    #[test]
+    fn test_format_span() {
+        assert_eq!("12m", format_span(12 * 60 + 30));
+        assert_eq!("5h 10m", format_span(5 * 3600 + 10 * 60));
+        assert_eq!("2d 3h", format_span(2 * DAY_SECS + 3 * 3600 + 59));
+    }
+
+    #[test]
+    fn test_next_report_time() {
+        // 2017-10-02 00:00:00 UTC, a monday
+        let monday = 1506902400;
+        assert_eq!(monday + REPORT_TIME_OF_DAY, next_report_time(monday));
+        assert_eq!(monday + 7 * DAY_SECS + REPORT_TIME_OF_DAY, next_report_time(monday + REPORT_TIME_OF_DAY));
+        assert_eq!(monday + 7 * DAY_SECS + REPORT_TIME_OF_DAY, next_report_time(monday + 3 * DAY_SECS));
+    }
+
+    #[test]
+    fn test_secs_since() {
+        // 2017-10-02 00:00:00 UTC
+        assert_eq!(Some(DAY_SECS + DAY_SECS / 2), secs_since("2017-09-30T12:00:00Z", 1506902400));
+        assert_eq!(None, secs_since("yesterday", 1506902400));
+    }
+}
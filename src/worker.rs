@@ -1,7 +1,18 @@
-use std::sync::Mutex;
-use std::sync::mpsc::{SendError, Sender, channel};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, SendError, Sender, SyncSender, TrySendError, channel, sync_channel};
 use std::thread::{self, JoinHandle};
 
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use error_reporting;
+use json_store::JsonStore;
+
 #[derive(Debug)]
 pub enum WorkMessage<T> {
     Stop,
@@ -9,13 +20,72 @@ pub enum WorkMessage<T> {
 }
 
 pub struct Worker<T: Send + 'static> {
-    sender: Mutex<Sender<WorkMessage<T>>>,
+    sender: Mutex<QueueSender<T>>,
+    journal: Option<Journal<T>>,
     thread: Option<JoinHandle<()>>,
+    alive: Arc<AtomicBool>,
+}
+
+// The requests sent to a persistent worker that it hasn't finished handling, as JSON, in the order it gets
+// them. Kept on disk so that they're handled after a crash or restart instead of getting lost with the queue.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PendingWork {
+    requests: VecDeque<Value>,
+}
+
+struct Journal<T> {
+    pending: Arc<JsonStore<PendingWork>>,
+    encode: fn(&T) -> Option<Value>,
+}
+
+impl<T> Clone for Journal<T> {
+    fn clone(&self) -> Journal<T> {
+        Journal {
+            pending: self.pending.clone(),
+            encode: self.encode,
+        }
+    }
+}
+
+fn encode<T: Serialize>(req: &T) -> Option<Value> {
+    match serde_json::to_value(req) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            error!("Error saving worker request: {}", e);
+            None
+        }
+    }
+}
+
+// clears the worker's alive flag when its thread ends, even if by panicking
+struct AliveGuard(Arc<AtomicBool>);
+
+impl Drop for AliveGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+// Bounded queues turn requests away once they're full instead of letting them pile up, e.g. during a burst
+// of webhooks.
+enum QueueSender<T: Send + 'static> {
+    Unbounded(Sender<WorkMessage<T>>),
+    Bounded(String, SyncSender<WorkMessage<T>>),
+}
+
+impl<T: Send + 'static> Clone for QueueSender<T> {
+    fn clone(&self) -> QueueSender<T> {
+        match *self {
+            QueueSender::Unbounded(ref s) => QueueSender::Unbounded(s.clone()),
+            QueueSender::Bounded(ref name, ref s) => QueueSender::Bounded(name.clone(), s.clone()),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct WorkSender<T: Send + 'static> {
-    sender: Sender<WorkMessage<T>>,
+    sender: QueueSender<T>,
+    journal: Option<Journal<T>>,
 }
 
 pub trait Runner<T: Send + 'static>: Send {
@@ -24,15 +94,51 @@ pub trait Runner<T: Send + 'static>: Send {
 
 impl<T: Send + 'static> WorkSender<T> {
     pub fn new(sender: Sender<WorkMessage<T>>) -> WorkSender<T> {
-        WorkSender { sender: sender }
+        WorkSender {
+            sender: QueueSender::Unbounded(sender),
+            journal: None,
+        }
     }
 
+    // fails right away if the worker's queue is full
     pub fn send(&self, msg: T) -> Result<(), SendError<WorkMessage<T>>> {
-        self.sender.send(WorkMessage::WorkItem(msg))
+        let journal = match self.journal {
+            Some(ref j) => j,
+            None => return self.send_now(msg),
+        };
+        let saved = (journal.encode)(&msg);
+        // one sender at a time, so that the journal has the requests in the order the worker gets them
+        journal.pending.write(|p| {
+            let result = self.send_now(msg);
+            if let (&Ok(_), Some(saved)) = (&result, saved) {
+                p.requests.push_back(saved);
+            }
+            result
+        })
     }
 
+    fn send_now(&self, msg: T) -> Result<(), SendError<WorkMessage<T>>> {
+        match self.sender {
+            QueueSender::Unbounded(ref s) => s.send(WorkMessage::WorkItem(msg)),
+            QueueSender::Bounded(ref name, ref s) => {
+                match s.try_send(WorkMessage::WorkItem(msg)) {
+                    Ok(()) => Ok(()),
+                    Err(TrySendError::Full(msg)) => {
+                        error!("The {} queue is full, dropping request", name);
+                        Err(SendError(msg))
+                    }
+                    Err(TrySendError::Disconnected(msg)) => Err(SendError(msg)),
+                }
+            }
+        }
+    }
+
+    // waits for room in the queue if need be, so that the worker always gets to stop
     pub fn stop(&mut self) -> Result<(), SendError<WorkMessage<T>>> {
-        self.sender.send(WorkMessage::Stop)
+        match self.sender {
+            QueueSender::Unbounded(ref s) => s.send(WorkMessage::Stop),
+            QueueSender::Bounded(_, ref s) => s.send(WorkMessage::Stop),
+        }
     }
 }
 
@@ -51,29 +157,228 @@ impl<T: Send + 'static> Drop for Worker<T> {
     }
 }
 
-impl<T: Send + 'static> Worker<T> {
+impl<T: Send + Debug + 'static> Worker<T> {
     pub fn new<R: Runner<T> + 'static>(name: &str, handler: R) -> Worker<T> {
         let (tx, rx) = channel();
+        Worker::start(name, handler, QueueSender::Unbounded(tx), rx, None)
+    }
+
+    // a worker that holds at most `capacity` requests that it hasn't gotten to yet
+    pub fn bounded<R: Runner<T> + 'static>(name: &str, handler: R, capacity: usize) -> Worker<T> {
+        let (tx, rx) = sync_channel(capacity);
+        Worker::start(name, handler, QueueSender::Bounded(name.to_string(), tx), rx, None)
+    }
+
+    fn start<R: Runner<T> + 'static>(
+        name: &str,
+        handler: R,
+        sender: QueueSender<T>,
+        rx: Receiver<WorkMessage<T>>,
+        journal: Option<Journal<T>>,
+    ) -> Worker<T> {
+        let alive = Arc::new(AtomicBool::new(true));
+        let guard = AliveGuard(alive.clone());
+        let done = journal.as_ref().map(|j| j.pending.clone());
 
         Worker {
-            sender: Mutex::new(tx),
+            sender: Mutex::new(sender),
+            journal: journal,
             thread: Some(
                 thread::Builder::new()
                     .name(name.to_string() + "-runner")
-                    .spawn(move || loop {
-                        match rx.recv() {
-                            Ok(WorkMessage::Stop) => break,
-                            Ok(WorkMessage::WorkItem(req)) => handler.handle(req),
-                            Err(e) => error!("Error receiving message: {}", e),
-                        };
+                    .spawn(move || {
+                        let _guard = guard;
+                        loop {
+                            match rx.recv() {
+                                Ok(WorkMessage::Stop) => break,
+                                Ok(WorkMessage::WorkItem(req)) => {
+                                    // a panic handling it gets reported with the request
+                                    let payload = error_reporting::payload_of(&req);
+                                    error_reporting::with_payload(payload, || handler.handle(req));
+                                    if let Some(ref done) = done {
+                                        done.write(|p| p.requests.pop_front());
+                                    }
+                                }
+                                Err(e) => error!("Error receiving message: {}", e),
+                            };
+                        }
                     })
                     .unwrap(),
             ),
+            alive: alive,
+        }
+    }
+}
+
+impl<T: Send + Debug + Serialize + DeserializeOwned + 'static> Worker<T> {
+    // a worker whose requests are kept in `file` until they're handled, and handled first thing when it
+    // starts again
+    pub fn persistent<R: Runner<T> + 'static>(name: &str, handler: R, file: Option<PathBuf>) -> Worker<T> {
+        let (tx, rx) = channel();
+        Worker::start_persistent(name, handler, QueueSender::Unbounded(tx), rx, file)
+    }
+
+    pub fn bounded_persistent<R: Runner<T> + 'static>(
+        name: &str,
+        handler: R,
+        capacity: usize,
+        file: Option<PathBuf>,
+    ) -> Worker<T> {
+        let (tx, rx) = sync_channel(capacity);
+        Worker::start_persistent(name, handler, QueueSender::Bounded(name.to_string(), tx), rx, file)
+    }
+
+    fn start_persistent<R: Runner<T> + 'static>(
+        name: &str,
+        handler: R,
+        sender: QueueSender<T>,
+        rx: Receiver<WorkMessage<T>>,
+        file: Option<PathBuf>,
+    ) -> Worker<T> {
+        let pending: Arc<JsonStore<PendingWork>> = Arc::new(JsonStore::new(file));
+        // what was left over from last time goes first. requests that can't be read any more are dropped
+        let left_over: Vec<T> = pending.write(|p| {
+            let mut left_over = vec![];
+            p.requests.retain(|r| match serde_json::from_value(r.clone()) {
+                Ok(req) => {
+                    left_over.push(req);
+                    true
+                }
+                Err(e) => {
+                    error!("Dropping {} request that can't be read: {}", name, e);
+                    false
+                }
+            });
+            left_over
+        });
+        if !left_over.is_empty() {
+            info!("Resuming {} unfinished {} requests", left_over.len(), name);
         }
+
+        let journal = Journal {
+            pending: pending,
+            encode: encode::<T>,
+        };
+        let worker = Worker::start(name, handler, sender, rx, Some(journal));
+        let sender = worker.sender.lock().unwrap().clone();
+        for req in left_over {
+            // these are in the journal already
+            let sent = match sender {
+                QueueSender::Unbounded(ref s) => s.send(WorkMessage::WorkItem(req)),
+                QueueSender::Bounded(_, ref s) => s.send(WorkMessage::WorkItem(req)),
+            };
+            if let Err(e) = sent {
+                error!("Error resuming {} request: {}", name, e);
+            }
+        }
+        worker
+    }
+}
+
+impl<T: Send + 'static> Worker<T> {
+    // false once the worker's thread has stopped, e.g. because handling a request panicked
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
     }
 
     pub fn new_sender(&self) -> WorkSender<T> {
         let sender = self.sender.lock().unwrap();
-        WorkSender { sender: sender.clone() }
+        WorkSender {
+            sender: sender.clone(),
+            journal: self.journal.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+    use self::tempdir::TempDir;
+    use super::*;
+    use std::time::Duration;
+
+    struct PanickingRunner;
+
+    impl Runner<bool> for PanickingRunner {
+        fn handle(&self, should_panic: bool) {
+            if should_panic {
+                panic!("runner failed");
+            }
+        }
+    }
+
+    struct SlowRunner;
+
+    impl Runner<u32> for SlowRunner {
+        fn handle(&self, _: u32) {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn test_bounded() {
+        let worker = Worker::bounded("test", SlowRunner, 1);
+        let sender = worker.new_sender();
+        assert!(sender.send(1).is_ok());
+        thread::sleep(Duration::from_millis(20));
+
+        // 1 is being handled, 2 waits in the queue, and there's no room for 3
+        assert!(sender.send(2).is_ok());
+        assert!(sender.send(3).is_err());
+
+        thread::sleep(Duration::from_millis(150));
+        assert!(sender.send(4).is_ok());
+    }
+
+    // handles what it gets, until it gets 0
+    struct RecordingRunner(Arc<Mutex<Vec<u32>>>);
+
+    impl Runner<u32> for RecordingRunner {
+        fn handle(&self, req: u32) {
+            if req == 0 {
+                panic!("stopping short");
+            }
+            self.0.lock().unwrap().push(req);
+        }
+    }
+
+    #[test]
+    fn test_persistent() {
+        let dir = TempDir::new("worker.rs").expect("create temp dir for worker.rs test");
+        let file = dir.path().join("queue.json");
+        let handled = Arc::new(Mutex::new(vec![]));
+
+        {
+            let worker = Worker::persistent("test", RecordingRunner(handled.clone()), Some(file.clone()));
+            worker.new_sender().send(1).unwrap();
+            worker.new_sender().send(0).unwrap();
+            thread::sleep(Duration::from_millis(50));
+            assert!(!worker.is_alive());
+        }
+        assert_eq!(vec![1], *handled.lock().unwrap());
+
+        // the one it was on when it stopped is still there, and gets handled when it starts again, along with
+        // the ones after it
+        let store: JsonStore<PendingWork> = JsonStore::new(Some(file.clone()));
+        assert_eq!(vec![json!(0)], store.read(|p| p.requests.iter().cloned().collect::<Vec<_>>()));
+        store.write(|p| p.requests = vec![json!(4), json!(2), json!(3)].into_iter().collect());
+        let worker = Worker::persistent("test", RecordingRunner(handled.clone()), Some(file.clone()));
+        worker.new_sender().send(5).unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        assert_eq!(vec![1, 4, 2, 3, 5], *handled.lock().unwrap());
+        assert_eq!(0, JsonStore::<PendingWork>::new(Some(file)).read(|p| p.requests.len()));
+    }
+
+    #[test]
+    fn test_is_alive() {
+        let worker = Worker::new("test", PanickingRunner);
+        worker.new_sender().send(false).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(worker.is_alive());
+
+        worker.new_sender().send(true).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(!worker.is_alive());
     }
 }
@@ -0,0 +1,83 @@
+use github;
+
+// The watched workflows whose last run on a branch failed, so that the next successful run can say the branch is
+// back to green, and how many times in a row they have failed there.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FailingWorkflows {
+    failing: Vec<FailingWorkflow>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct FailingWorkflow {
+    repo_url: String,
+    workflow: String,
+    branch: String,
+    #[serde(default)]
+    failures: u32,
+}
+
+impl FailingWorkflow {
+    fn is(&self, repo: &github::Repo, workflow: &str, branch: &str) -> bool {
+        self.repo_url == repo.html_url && self.workflow == workflow && self.branch == branch
+    }
+}
+
+impl FailingWorkflows {
+    // records the outcome of a run, and returns whether the workflow was failing on the branch before it
+    pub fn update(&mut self, repo: &github::Repo, workflow: &str, branch: &str, failed: bool) -> bool {
+        let was_failing = self.failures(repo, workflow, branch) > 0;
+        if !failed {
+            self.failing.retain(|f| !f.is(repo, workflow, branch));
+        } else if was_failing {
+            for f in self.failing.iter_mut().filter(|f| f.is(repo, workflow, branch)) {
+                f.failures += 1;
+            }
+        } else {
+            self.failing.push(FailingWorkflow {
+                repo_url: repo.html_url.clone(),
+                workflow: workflow.to_string(),
+                branch: branch.to_string(),
+                failures: 1,
+            });
+        }
+        was_failing
+    }
+
+    // how many runs in a row have failed on the branch. entries saved before runs were counted count as one
+    pub fn failures(&self, repo: &github::Repo, workflow: &str, branch: &str) -> u32 {
+        match self.failing.iter().find(|f| f.is(repo, workflow, branch)) {
+            Some(f) => if f.failures > 0 { f.failures } else { 1 },
+            None => 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.failing.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failing_workflows() {
+        let repo = github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap();
+        let other = github::Repo::parse("http://git.foo.com/some-user/other-repo").unwrap();
+
+        let mut failing = FailingWorkflows::default();
+        assert!(!failing.update(&repo, "CI", "master", true));
+        assert!(failing.update(&repo, "CI", "master", true));
+        assert!(!failing.update(&repo, "CI", "release/1.0", true));
+        assert!(!failing.update(&other, "CI", "master", false));
+        assert_eq!(2, failing.len());
+        assert_eq!(2, failing.failures(&repo, "CI", "master"));
+        assert_eq!(1, failing.failures(&repo, "CI", "release/1.0"));
+        assert_eq!(0, failing.failures(&other, "CI", "master"));
+
+        assert!(failing.update(&repo, "CI", "master", false));
+        assert!(!failing.update(&repo, "CI", "master", false));
+        assert_eq!(1, failing.len());
+        assert_eq!(0, failing.failures(&repo, "CI", "master"));
+    }
+}
@@ -0,0 +1,64 @@
+extern crate octobot;
+
+mod mocks;
+
+use std::sync::Arc;
+
+use octobot::audit::{self, AuditLog, AuditedSession};
+use octobot::config::Config;
+use octobot::github::api::Session;
+use octobot::repos::{RepoConfig, RepoInfo};
+use octobot::users::UserConfig;
+
+use mocks::mock_github::MockGithub;
+
+fn new_session(github: Arc<MockGithub>, repos: RepoConfig) -> (AuditedSession, Arc<AuditLog>) {
+    let log = Arc::new(AuditLog::in_memory());
+    let config = Arc::new(Config::new(UserConfig::new(), repos));
+    (AuditedSession::new(github, log.clone(), config), log)
+}
+
+#[test]
+fn test_records_changes() {
+    let github = Arc::new(MockGithub::new());
+    github.mock_comment_pull_request("some-user", "the-repo", 32, "hello", Ok(()));
+    github.mock_comment_pull_request("some-user", "the-repo", 33, "hello", Err("whoops".into()));
+    let (session, log) = new_session(github, RepoConfig::new());
+
+    audit::with_trigger("issue_comment (delivery 1)", || {
+        assert!(session.comment_pull_request("some-user", "the-repo", 32, "hello").is_ok());
+        assert!(session.comment_pull_request("some-user", "the-repo", 33, "hello").is_err());
+    });
+
+    let entries = log.entries(|_| true, 10).unwrap();
+    assert_eq!(2, entries.len());
+    assert_eq!("comment_pull_request", entries[1].action);
+    assert_eq!("some-user/the-repo#32", entries[1].target);
+    assert_eq!("issue_comment (delivery 1)", entries[1].trigger);
+    assert_eq!("ok", entries[1].outcome);
+    assert_eq!("error: whoops", entries[0].outcome);
+}
+
+#[test]
+fn test_dry_run() {
+    let mut repos = RepoConfig::new();
+    repos.insert_info("the-github-host", RepoInfo::new("some-user/the-repo", "the-channel").with_dry_run(true));
+    repos.insert("the-github-host", "some-user/other-repo", "the-channel");
+
+    let github = Arc::new(MockGithub::new());
+    github.mock_comment_pull_request("some-user", "other-repo", 5, "hello", Ok(()));
+    let (session, log) = new_session(github, repos);
+
+    // the mock fails the test if the dry run repo's calls get to it
+    assert!(session.dry_run("some-user", "the-repo"));
+    assert!(session.comment_pull_request("some-user", "the-repo", 32, "hello").is_ok());
+    assert!(session.merge_pull_request("some-user", "the-repo", 32, "abcdef", "merge").is_ok());
+    assert!(session.create_pull_request("some-user", "the-repo", "title", "body", "head", "base").is_err());
+
+    assert!(!session.dry_run("some-user", "other-repo"));
+    assert!(session.comment_pull_request("some-user", "other-repo", 5, "hello").is_ok());
+
+    let planned: Vec<String> =
+        log.entries(|e| e.outcome == audit::DRY_RUN, 10).unwrap().into_iter().map(|e| e.action).collect();
+    assert_eq!(vec!["create_pull_request", "merge_pull_request", "comment_pull_request"], planned);
+}
@@ -0,0 +1,197 @@
+extern crate octobot;
+
+mod mocks;
+
+use std::sync::Arc;
+
+use octobot::auto_merge;
+use octobot::config::Config;
+use octobot::github;
+use octobot::messenger;
+use octobot::repos::{RepoConfig, RepoInfo};
+use octobot::slack::{self, SlackAttachmentBuilder};
+use octobot::users::UserConfig;
+
+use mocks::mock_github::MockGithub;
+use mocks::mock_slack::MockSlack;
+
+const REPO_MSG: &'static str = "(<http://git.foo.com/some-user/some-repo|some-user/some-repo>)";
+
+fn the_repo() -> github::Repo {
+    github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap()
+}
+
+fn new_config() -> Arc<Config> {
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel")
+            .with_auto_merge(Some("automerge".into()), None)
+            .with_merge_strategy(Some("squash".into())),
+    );
+    Arc::new(Config::new(UserConfig::new(), repos))
+}
+
+fn the_pr() -> github::PullRequest {
+    let mut pr = github::PullRequest::new();
+    pr.number = 32;
+    pr.title = "The PR".into();
+    pr.html_url = "http://the-pr".into();
+    pr.state = "open".into();
+    pr.user = github::User::new("the-pr-owner");
+    pr.head.sha = "abcdef32".into();
+    pr.base.ref_name = "master".into();
+    pr
+}
+
+fn approved() -> Vec<github::Review> {
+    let mut review = github::Review::new("lgtm", github::User::new("joe-reviewer"));
+    review.state = "APPROVED".into();
+    vec![review]
+}
+
+fn success() -> github::CombinedStatus {
+    github::CombinedStatus {
+        state: "success".into(),
+        sha: "abcdef32".into(),
+        statuses: vec![],
+    }
+}
+
+#[test]
+fn test_auto_merge() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("Pull Request automatically merged {}", REPO_MSG),
+            vec![
+                SlackAttachmentBuilder::new("")
+                    .title("Pull Request #32: \"The PR\"")
+                    .title_link("http://the-pr")
+                    .build(),
+            ],
+        ),
+    ]);
+    let config = new_config();
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+
+    github.mock_get_pull_request_labels("some-user", "some-repo", 32, Ok(vec![github::Label::new("automerge")]));
+    github.mock_get_pull_request_reviews("some-user", "some-repo", 32, Ok(approved()));
+    github.mock_get_combined_status("some-user", "some-repo", "abcdef32", Ok(success()));
+    github.mock_merge_pull_request("some-user", "some-repo", 32, "abcdef32", "squash", Ok(()));
+
+    auto_merge::auto_merge_pull_request(&github, &config, &*messenger, &the_repo(), &the_pr());
+}
+
+#[test]
+fn test_auto_merge_threaded() {
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel")
+            .with_auto_merge(Some("automerge".into()), None)
+            .with_pr_threads(true),
+    );
+    let config = Arc::new(Config::new(UserConfig::new(), repos));
+
+    let mut merged = slack::req(
+        "the-reviews-channel",
+        &format!("Pull Request automatically merged {}", REPO_MSG),
+        vec![
+            SlackAttachmentBuilder::new("")
+                .title("Pull Request #32: \"The PR\"")
+                .title_link("http://the-pr")
+                .build(),
+        ],
+    );
+    merged.thread = Some("http://the-pr".into());
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![merged]);
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+
+    github.mock_get_pull_request_labels("some-user", "some-repo", 32, Ok(vec![github::Label::new("automerge")]));
+    github.mock_get_pull_request_reviews("some-user", "some-repo", 32, Ok(approved()));
+    github.mock_get_combined_status("some-user", "some-repo", "abcdef32", Ok(success()));
+    github.mock_merge_pull_request("some-user", "some-repo", 32, "abcdef32", "merge", Ok(()));
+
+    auto_merge::auto_merge_pull_request(&github, &config, &*messenger, &the_repo(), &the_pr());
+}
+
+#[test]
+fn test_auto_merge_no_label() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![]);
+    let config = new_config();
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+
+    github.mock_get_pull_request_labels("some-user", "some-repo", 32, Ok(vec![github::Label::new("other")]));
+
+    auto_merge::auto_merge_pull_request(&github, &config, &*messenger, &the_repo(), &the_pr());
+}
+
+#[test]
+fn test_auto_merge_not_approved() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![]);
+    let config = new_config();
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+
+    github.mock_get_pull_request_labels("some-user", "some-repo", 32, Ok(vec![github::Label::new("automerge")]));
+    github.mock_get_pull_request_reviews("some-user", "some-repo", 32, Ok(vec![]));
+
+    auto_merge::auto_merge_pull_request(&github, &config, &*messenger, &the_repo(), &the_pr());
+}
+
+#[test]
+fn test_auto_merge_pending() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![]);
+    let config = new_config();
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+
+    let mut status = success();
+    status.state = "pending".into();
+    status.statuses = vec![
+        github::Status {
+            state: "pending".into(),
+            target_url: None,
+            context: Some("ci/build".into()),
+            description: None,
+            creator: None,
+        },
+    ];
+
+    github.mock_get_pull_request_labels("some-user", "some-repo", 32, Ok(vec![github::Label::new("automerge")]));
+    github.mock_get_pull_request_reviews("some-user", "some-repo", 32, Ok(approved()));
+    github.mock_get_combined_status("some-user", "some-repo", "abcdef32", Ok(status));
+
+    auto_merge::auto_merge_pull_request(&github, &config, &*messenger, &the_repo(), &the_pr());
+}
+
+#[test]
+fn test_auto_merge_error() {
+    let github = MockGithub::new();
+
+    let msg = "Error auto-merging Pull Request";
+    let attach = vec![
+        SlackAttachmentBuilder::new("Merge conflict")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .color("danger")
+            .build(),
+    ];
+    let slack = MockSlack::new(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+    ]);
+    let config = new_config();
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+
+    github.mock_get_pull_request_labels("some-user", "some-repo", 32, Ok(vec![github::Label::new("automerge")]));
+    github.mock_get_pull_request_reviews("some-user", "some-repo", 32, Ok(approved()));
+    github.mock_get_combined_status("some-user", "some-repo", "abcdef32", Ok(success()));
+    github.mock_merge_pull_request("some-user", "some-repo", 32, "abcdef32", "squash", Err("Merge conflict".into()));
+
+    auto_merge::auto_merge_pull_request(&github, &config, &*messenger, &the_repo(), &the_pr());
+}
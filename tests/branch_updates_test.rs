@@ -0,0 +1,82 @@
+extern crate octobot;
+
+mod mocks;
+
+use std::sync::Arc;
+
+use octobot::audit::AuditLog;
+use octobot::branch_updates;
+use octobot::config::Config;
+use octobot::git_clone_manager::GitCloneManager;
+use octobot::github;
+use octobot::json_store::JsonStore;
+use octobot::messenger;
+use octobot::repos::{RepoConfig, RepoInfo};
+use octobot::users::UserConfig;
+
+use mocks::mock_github::MockGithub;
+use mocks::mock_slack::MockSlack;
+
+fn the_repo() -> github::Repo {
+    github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap()
+}
+
+fn new_config() -> Arc<Config> {
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_keep_updated(true),
+    );
+    Arc::new(Config::new(UserConfig::new(), repos))
+}
+
+fn the_pr() -> github::PullRequest {
+    let mut pr = github::PullRequest::new();
+    pr.number = 32;
+    pr.title = "The PR".into();
+    pr.state = "open".into();
+    pr.user = github::User::new("the-pr-owner");
+    pr.head.ref_name = "the-branch".into();
+    pr.head.repo = the_repo();
+    pr.base.ref_name = "master".into();
+    pr
+}
+
+// none of these get as far as cloning the repo
+fn update(github: Arc<MockGithub>, pull_request: &github::PullRequest) {
+    let config = new_config();
+    let slack = MockSlack::new(vec![]);
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    let clone_mgr = GitCloneManager::new(github.clone(), config);
+
+    branch_updates::update_pull_request(
+        &*github,
+        &clone_mgr,
+        &AuditLog::new(None),
+        &*messenger,
+        &JsonStore::in_memory(),
+        &the_repo(),
+        pull_request,
+    );
+}
+
+#[test]
+fn test_not_labeled() {
+    let github = Arc::new(MockGithub::new());
+    github.mock_get_pull_request_labels("some-user", "some-repo", 32, Ok(vec![github::Label::new("other")]));
+
+    update(github, &the_pr());
+}
+
+#[test]
+fn test_skips_forks_and_closed() {
+    let github = Arc::new(MockGithub::new());
+
+    let mut fork = the_pr();
+    fork.head.repo = github::Repo::parse("http://git.foo.com/other-user/some-repo").unwrap();
+    update(github.clone(), &fork);
+
+    let mut closed = the_pr();
+    closed.state = "closed".into();
+    update(github, &closed);
+}
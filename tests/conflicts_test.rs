@@ -0,0 +1,136 @@
+extern crate octobot;
+
+mod mocks;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use octobot::config::Config;
+use octobot::conflicts::{self, ConflictedPullRequests};
+use octobot::github;
+use octobot::json_store::JsonStore;
+use octobot::messenger;
+use octobot::repos::{RepoConfig, RepoInfo};
+use octobot::slack::{self, SlackAttachmentBuilder};
+use octobot::users::UserConfig;
+
+use mocks::mock_github::MockGithub;
+use mocks::mock_slack::MockSlack;
+
+fn the_repo() -> github::Repo {
+    github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap()
+}
+
+fn new_config() -> Arc<Config> {
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_notify_conflicts(true),
+    );
+    Arc::new(Config::new(UserConfig::new(), repos))
+}
+
+fn the_pr(number: u32, base: &str) -> github::PullRequest {
+    let mut pr = github::PullRequest::new();
+    pr.number = number;
+    pr.title = "The PR".into();
+    pr.html_url = "http://the-pr".into();
+    pr.state = "open".into();
+    pr.user = github::User::new("the-pr-owner");
+    pr.base.ref_name = base.into();
+    pr
+}
+
+fn mergeable(number: u32, mergeable: Option<bool>) -> github::PullRequest {
+    let mut pr = the_pr(number, "master");
+    pr.mergeable = mergeable;
+    pr
+}
+
+fn conflict_msg() -> slack::SlackRequest {
+    slack::req(
+        "@the.pr.owner",
+        "Pull Request now has conflicts with master",
+        vec![
+            SlackAttachmentBuilder::new("Merge or rebase the branch to resolve them.")
+                .title("Pull Request #32: \"The PR\"")
+                .title_link("http://the-pr")
+                .color("warning")
+                .build(),
+        ],
+    )
+}
+
+fn check(github: &MockGithub, slack: &MockSlack, conflicted: &JsonStore<ConflictedPullRequests>) {
+    let messenger = messenger::new(new_config(), slack.new_sender());
+    conflicts::check_branch(github, &*messenger, conflicted, &the_repo(), "master", Duration::from_millis(0));
+}
+
+#[test]
+fn test_notifies_owner_once() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![conflict_msg()]);
+    let conflicted = JsonStore::in_memory();
+
+    // only the PR targeting master is looked at, and github takes a moment to decide
+    github.mock_get_pull_requests(
+        "some-user",
+        "some-repo",
+        Some("open"),
+        None,
+        Ok(vec![the_pr(32, "master"), the_pr(33, "develop")]),
+    );
+    github.get_pull_request("some-user", "some-repo", 32, Ok(mergeable(32, None)));
+    github.get_pull_request("some-user", "some-repo", 32, Ok(mergeable(32, Some(false))));
+    check(&github, &slack, &conflicted);
+
+    // still conflicted: no new message
+    github.mock_get_pull_requests("some-user", "some-repo", Some("open"), None, Ok(vec![the_pr(32, "master")]));
+    github.get_pull_request("some-user", "some-repo", 32, Ok(mergeable(32, Some(false))));
+    check(&github, &slack, &conflicted);
+
+    assert_eq!(1, conflicted.read(|c| c.len()));
+}
+
+#[test]
+fn test_notifies_again_after_resolved() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![conflict_msg(), conflict_msg()]);
+    let conflicted = JsonStore::in_memory();
+
+    for state in vec![false, true, false] {
+        github.mock_get_pull_requests("some-user", "some-repo", Some("open"), None, Ok(vec![the_pr(32, "master")]));
+        github.get_pull_request("some-user", "some-repo", 32, Ok(mergeable(32, Some(state))));
+        check(&github, &slack, &conflicted);
+    }
+}
+
+#[test]
+fn test_forgets_closed_prs() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![conflict_msg()]);
+    let conflicted = JsonStore::in_memory();
+
+    github.mock_get_pull_requests("some-user", "some-repo", Some("open"), None, Ok(vec![the_pr(32, "master")]));
+    github.get_pull_request("some-user", "some-repo", 32, Ok(mergeable(32, Some(false))));
+    check(&github, &slack, &conflicted);
+    assert_eq!(1, conflicted.read(|c| c.len()));
+
+    github.mock_get_pull_requests("some-user", "some-repo", Some("open"), None, Ok(vec![]));
+    check(&github, &slack, &conflicted);
+    assert_eq!(0, conflicted.read(|c| c.len()));
+}
+
+#[test]
+fn test_undecided_is_not_conflicted() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![]);
+    let conflicted = JsonStore::in_memory();
+
+    github.mock_get_pull_requests("some-user", "some-repo", Some("open"), None, Ok(vec![the_pr(32, "master")]));
+    for _ in 0..4 {
+        github.get_pull_request("some-user", "some-repo", 32, Ok(mergeable(32, None)));
+    }
+    check(&github, &slack, &conflicted);
+    assert_eq!(0, conflicted.read(|c| c.len()));
+}
@@ -1,12 +1,23 @@
 extern crate octobot;
+#[macro_use]
+extern crate maplit;
 
 mod mocks;
 
+use std::sync::Arc;
+
 use mocks::mock_github::MockGithub;
+use mocks::mock_slack::MockSlack;
 
+use octobot::config::Config;
 use octobot::diffs::DiffOfDiffs;
 use octobot::force_push;
 use octobot::github;
+use octobot::messages::{self, Messages};
+use octobot::messenger::MessengerFactory;
+use octobot::repos::{RepoConfig, RepoInfo};
+use octobot::slack::{self, SlackAttachmentBuilder};
+use octobot::users::UserConfig;
 
 #[test]
 fn test_force_push_identical() {
@@ -30,6 +41,7 @@ fn test_force_push_identical() {
 
     force_push::comment_force_push(
         diffs,
+        None,
         vec![],
         &github,
         "some-user",
@@ -37,6 +49,36 @@ fn test_force_push_identical() {
         &pr,
         "abcdef0999999",
         "1111abc9999999",
+        &Messages::defaults(),
+    ).unwrap();
+}
+
+#[test]
+fn test_force_push_comment_template() {
+    let mut pr = github::PullRequest::new();
+    pr.number = 32;
+
+    let diff = "this is a big diff".to_string();
+    let diffs = Ok(DiffOfDiffs::new(&diff, &diff));
+
+    let github = MockGithub::new();
+    github.mock_comment_pull_request("some-user", "some-repo", 32, "Rebased 1111abc on #32, all good", Ok(()));
+    github.mock_get_statuses("some-user", "some-repo", "abcdef0999999", Ok(vec![]));
+
+    let messages = Messages::new(&hashmap!{
+        messages::FORCE_PUSH_COMMENT.to_string() => "Rebased {after} on #{number}, all good".to_string(),
+    });
+    force_push::comment_force_push(
+        diffs,
+        None,
+        vec![],
+        &github,
+        "some-user",
+        "some-repo",
+        &pr,
+        "abcdef0999999",
+        "1111abc9999999",
+        &messages,
     ).unwrap();
 }
 
@@ -124,6 +166,7 @@ fn test_force_push_identical_with_statuses() {
 
     force_push::comment_force_push(
         diffs,
+        None,
         vec!["ci/build".into(), "checks/cla".into()],
         &github,
         "some-user",
@@ -131,6 +174,7 @@ fn test_force_push_identical_with_statuses() {
         &pr,
         "the-before-hash",
         "the-after-hash",
+        &Messages::defaults(),
     ).unwrap();
 }
 
@@ -153,6 +197,7 @@ fn test_force_push_different() {
 
     force_push::comment_force_push(
         diffs,
+        None,
         vec![],
         &github,
         "some-user",
@@ -160,6 +205,7 @@ fn test_force_push_different() {
         &pr,
         "abcdef0999999",
         "1111abc9999999",
+        &Messages::defaults(),
     ).unwrap();
 }
 
@@ -287,6 +333,7 @@ index 33667da..3503c28 100644
 
     force_push::comment_force_push(
         diffs,
+        None,
         vec![],
         &github,
         "some-user",
@@ -294,6 +341,7 @@ index 33667da..3503c28 100644
         &pr,
         "abcdef0999999",
         "1111abc9999999",
+        &Messages::defaults(),
     ).unwrap();
 }
 #[test]
@@ -312,6 +360,39 @@ fn test_force_push_error() {
 
     force_push::comment_force_push(
         Err("Ahh!!".into()),
+        None,
+        vec![],
+        &github,
+        "some-user",
+        "some-repo",
+        &pr,
+        "abcdef0999999",
+        "1111abc9999999",
+        &Messages::defaults(),
+    ).unwrap();
+}
+
+#[test]
+fn test_force_push_commits() {
+    let mut pr = github::PullRequest::new();
+    pr.number = 32;
+
+    let diffs = Ok(DiffOfDiffs::new("diff1", "diff2"));
+    let comparison = github::Comparison { ahead_by: 5, behind_by: 2 };
+
+    let github = MockGithub::new();
+    github.mock_comment_pull_request(
+        "some-user",
+        "some-repo",
+        32,
+        "Force-push detected: before: abcdef0, after: 1111abc: Diff changed post-rebase\n\n\
+         2 commit(s) removed, 5 added",
+        Ok(()),
+    );
+
+    force_push::comment_force_push(
+        diffs,
+        Some(&comparison),
         vec![],
         &github,
         "some-user",
@@ -319,5 +400,84 @@ fn test_force_push_error() {
         &pr,
         "abcdef0999999",
         "1111abc9999999",
+        &Messages::defaults(),
     ).unwrap();
 }
+
+#[test]
+fn test_dismiss_approvals() {
+    let mut pr = github::PullRequest::new();
+    pr.number = 32;
+
+    let review = |id: u64, login: &str, state: &str| {
+        let mut review = github::Review::new("", github::User::new(login));
+        review.id = Some(id);
+        review.state = state.into();
+        review
+    };
+
+    let github = MockGithub::new();
+    github.mock_get_pull_request_reviews(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(vec![
+            review(1, "joe", "APPROVED"),
+            review(2, "jane", "CHANGES_REQUESTED"),
+            review(3, "bob", "CHANGES_REQUESTED"),
+            review(4, "bob", "APPROVED"),
+            review(5, "jane", "COMMENTED"),
+        ]),
+    );
+    let msg = "The diff changed in force-push abcdef0..1111abc";
+    github.mock_dismiss_review("some-user", "some-repo", 32, 1, msg, Ok(()));
+    github.mock_dismiss_review("some-user", "some-repo", 32, 4, msg, Ok(()));
+    github.mock_request_review("some-user", "some-repo", 32, vec!["joe", "bob"], Ok(()));
+
+    force_push::dismiss_approvals(&github, "some-user", "some-repo", &pr, "abcdef0999999", "1111abc9999999").unwrap();
+}
+
+#[test]
+fn test_dismiss_approvals_none() {
+    let mut pr = github::PullRequest::new();
+    pr.number = 32;
+
+    let github = MockGithub::new();
+    github.mock_get_pull_request_reviews("some-user", "some-repo", 32, Ok(vec![]));
+
+    force_push::dismiss_approvals(&github, "some-user", "some-repo", &pr, "abcdef0999999", "1111abc9999999").unwrap();
+}
+
+#[test]
+fn test_notify_force_push() {
+    let mut repos = RepoConfig::new();
+    repos.insert_info("git.foo.com", RepoInfo::new("some-user/some-repo", "the-reviews-channel"));
+    let config = Arc::new(Config::new(UserConfig::new(), repos));
+    let repo = github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap();
+
+    let mut pr = github::PullRequest::new();
+    pr.number = 32;
+    pr.title = "The PR".into();
+    pr.html_url = "http://the-pr".into();
+    pr.base.ref_name = "master".into();
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let slack = MockSlack::new(vec![
+        slack::req(
+            "the-reviews-channel",
+            "Force-push to Pull Request #32: Identical diff post-rebase, 2 commit(s) removed, 2 added \
+             (<http://git.foo.com/some-user/some-repo|some-user/some-repo>)",
+            attach,
+        ),
+    ]);
+    let messengers = MessengerFactory::new(config, slack.new_sender());
+
+    let diffs = Ok(DiffOfDiffs::new("diff", "diff"));
+    let comparison = github::Comparison { ahead_by: 2, behind_by: 2 };
+    force_push::notify_force_push(&messengers, &diffs, Some(&comparison), &repo, &pr, &Messages::defaults());
+}
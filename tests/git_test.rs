@@ -281,3 +281,70 @@ fn test_get_commit_desc() {
         test.git.get_commit_desc("HEAD").unwrap()
     );
 }
+
+#[test]
+fn test_merge() {
+    let test = GitTest::new();
+
+    test.run_git(&["checkout", "-b", "the-branch"]);
+    test.add_repo_file("horses.txt", "Stallion\n", "Horses");
+    test.run_git(&["checkout", "master"]);
+    test.add_repo_file("cows.txt", "Bessie\n", "Cows");
+
+    test.run_git(&["checkout", "the-branch"]);
+    assert_eq!(true, test.git.merge("master", "Merge branch 'master' into the-branch").unwrap());
+    assert_eq!(
+        ("Merge branch 'master' into the-branch".into(), String::new()),
+        test.git.get_commit_desc("HEAD").unwrap()
+    );
+    let master = test.run_git(&["rev-parse", "master"]);
+    assert_eq!(true, test.git.does_branch_contain(&master, "the-branch").unwrap());
+}
+
+#[test]
+fn test_merge_conflicts() {
+    let test = GitTest::new();
+
+    test.run_git(&["checkout", "-b", "the-branch"]);
+    test.add_repo_file("horses.txt", "Stallion\n", "Horses");
+    test.run_git(&["checkout", "master"]);
+    test.add_repo_file("horses.txt", "Mustang\n", "Other horses");
+
+    test.run_git(&["checkout", "the-branch"]);
+    let before = test.run_git(&["rev-parse", "HEAD"]);
+    assert_eq!(false, test.git.merge("master", "Merge branch 'master' into the-branch").unwrap());
+
+    // left as it was
+    assert_eq!(before, test.run_git(&["rev-parse", "HEAD"]));
+    assert_eq!("", test.run_git(&["status", "--porcelain"]));
+
+    assert!(test.git.merge("no-such-branch", "Merge").is_err());
+}
+
+#[test]
+fn test_has_equivalent_commits() {
+    let test = GitTest::new();
+
+    test.add_repo_file("file.txt", "contents1", "commit 1");
+    test.run_git(&["checkout", "-b", "the-branch"]);
+    test.add_repo_file("horses.txt", "Stallion\n", "Horses");
+    test.add_repo_file("cows.txt", "Bull\n", "Cows");
+    let horses = test.run_git(&["rev-parse", "HEAD^"]);
+    let cows = test.run_git(&["rev-parse", "HEAD"]);
+
+    test.run_git(&["checkout", "master"]);
+    test.add_repo_file("pigs.txt", "Boar\n", "Pigs");
+    assert!(!test.git.has_equivalent_commits("master", &horses, &format!("{}^", horses)).unwrap());
+
+    // picked by hand, so it's a different commit with the same changes
+    test.run_git(&["cherry-pick", &horses]);
+    assert!(test.git.has_equivalent_commits("master", &horses, &format!("{}^", horses)).unwrap());
+    assert!(!test.git.has_equivalent_commits("master", &cows, &format!("{}^", horses)).unwrap());
+
+    test.run_git(&["cherry-pick", &cows]);
+    assert!(test.git.has_equivalent_commits("master", &cows, &format!("{}^", horses)).unwrap());
+
+    // and once it's merged, the commits themselves are there
+    test.run_git(&["checkout", "-b", "other-branch", "the-branch"]);
+    assert!(test.git.has_equivalent_commits("the-branch", &cows, &format!("{}^", horses)).unwrap());
+}
@@ -1,33 +1,53 @@
 extern crate hyper;
+#[macro_use]
+extern crate maplit;
 extern crate octobot;
+extern crate tempdir;
 
 mod mocks;
 
-use std::sync::Arc;
+use std::fs;
+use std::io::Write;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{Receiver, channel};
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
 use hyper::StatusCode;
+use tempdir::TempDir;
 
-use octobot::config::{Config, JiraConfig};
+use octobot::auto_merge::AutoMergeRequest;
+use octobot::branch_updates::{self, BranchUpdateRequest};
+use octobot::config::{Config, JiraConfig, UnknownRepos};
+use octobot::conflicts::ConflictRequest;
+use octobot::event_rules::EventRule;
 use octobot::force_push::ForcePushRequest;
 use octobot::git_clone_manager::GitCloneManager;
 use octobot::github::*;
 use octobot::github::api::Session;
 use octobot::jira;
+use octobot::json_store::JsonStore;
+use octobot::merge_queue::MergeQueueRequest;
+use octobot::messages;
 use octobot::messenger;
+use octobot::pr_dependencies::{Dependents, PrRef};
 use octobot::pr_merge::PRMergeRequest;
 use octobot::repo_version::RepoVersionRequest;
 use octobot::repos;
-use octobot::repos::RepoConfig;
+use octobot::repos::{EventFilter, RepoConfig};
+use octobot::review_reminders::ReviewReminderRequest;
+use octobot::reviewers::ReviewerRequest;
 use octobot::server::github_handler::GithubEventHandler;
 use octobot::slack::{self, SlackAttachmentBuilder};
-use octobot::users::UserConfig;
+use octobot::teams::TeamCache;
+use octobot::users::{UserConfig, UserInfo};
+use octobot::version_bumps::VersionBumpRequest;
 use octobot::worker::{WorkMessage, WorkSender};
 
 use mocks::mock_github::MockGithub;
 use mocks::mock_jira::MockJira;
+use mocks::mock_pagerduty::MockPagerDuty;
 use mocks::mock_slack::MockSlack;
 
 // this message gets appended only to review channel messages, not to slackbots
@@ -46,6 +66,13 @@ struct GithubHandlerTest {
     pr_merge_rx: Option<Receiver<WorkMessage<PRMergeRequest>>>,
     repo_version_rx: Option<Receiver<WorkMessage<RepoVersionRequest>>>,
     force_push_rx: Option<Receiver<WorkMessage<ForcePushRequest>>>,
+    merge_queue_rx: Option<Receiver<WorkMessage<MergeQueueRequest>>>,
+    auto_merge_rx: Option<Receiver<WorkMessage<AutoMergeRequest>>>,
+    reviewers_rx: Option<Receiver<WorkMessage<ReviewerRequest>>>,
+    review_reminders_rx: Option<Receiver<WorkMessage<ReviewReminderRequest>>>,
+    conflicts_rx: Option<Receiver<WorkMessage<ConflictRequest>>>,
+    branch_updates_rx: Option<Receiver<WorkMessage<BranchUpdateRequest>>>,
+    version_bumps_rx: Option<Receiver<WorkMessage<VersionBumpRequest>>>,
 }
 
 impl GithubHandlerTest {
@@ -81,6 +108,13 @@ fn new_test_with(jira: Option<JiraConfig>) -> GithubHandlerTest {
     let (pr_merge_tx, pr_merge_rx) = channel();
     let (repo_version_tx, repo_version_rx) = channel();
     let (force_push_tx, force_push_rx) = channel();
+    let (merge_queue_tx, merge_queue_rx) = channel();
+    let (auto_merge_tx, auto_merge_rx) = channel();
+    let (reviewers_tx, reviewers_rx) = channel();
+    let (review_reminders_tx, review_reminders_rx) = channel();
+    let (conflicts_tx, conflicts_rx) = channel();
+    let (branch_updates_tx, branch_updates_rx) = channel();
+    let (version_bumps_tx, version_bumps_rx) = channel();
 
     let mut repos = RepoConfig::new();
     let mut data = HookBody::new();
@@ -110,6 +144,13 @@ fn new_test_with(jira: Option<JiraConfig>) -> GithubHandlerTest {
         pr_merge_rx: Some(pr_merge_rx),
         repo_version_rx: Some(repo_version_rx),
         force_push_rx: Some(force_push_rx),
+        merge_queue_rx: Some(merge_queue_rx),
+        auto_merge_rx: Some(auto_merge_rx),
+        reviewers_rx: Some(reviewers_rx),
+        review_reminders_rx: Some(review_reminders_rx),
+        conflicts_rx: Some(conflicts_rx),
+        branch_updates_rx: Some(branch_updates_rx),
+        version_bumps_rx: Some(version_bumps_rx),
         handler: GithubEventHandler {
             event: "ping".to_string(),
             data: data,
@@ -118,10 +159,23 @@ fn new_test_with(jira: Option<JiraConfig>) -> GithubHandlerTest {
             messenger: messenger::new(config.clone(), slack_sender),
             github_session: github.clone(),
             git_clone_manager: git_clone_manager.clone(),
+            teams: Arc::new(TeamCache::new()),
+            failing_workflows: Arc::new(JsonStore::in_memory()),
+            weekly_metrics: Arc::new(JsonStore::in_memory()),
+            dependents: Arc::new(JsonStore::in_memory()),
+            unknown_repos: Arc::new(Mutex::new(HashSet::new())),
             jira_session: None,
+            pagerduty_session: None,
             pr_merge: WorkSender::new(pr_merge_tx.clone()),
             repo_version: WorkSender::new(repo_version_tx.clone()),
             force_push: WorkSender::new(force_push_tx.clone()),
+            merge_queue: WorkSender::new(merge_queue_tx.clone()),
+            auto_merge: WorkSender::new(auto_merge_tx.clone()),
+            reviewers: WorkSender::new(reviewers_tx.clone()),
+            review_reminders: WorkSender::new(review_reminders_tx.clone()),
+            conflicts: WorkSender::new(conflicts_tx.clone()),
+            branch_updates: WorkSender::new(branch_updates_tx.clone()),
+            version_bumps: WorkSender::new(version_bumps_tx.clone()),
         },
     }
 }
@@ -160,6 +214,16 @@ fn some_pr() -> Option<PullRequest> {
         assignees: vec![User::new("assign1")],
         requested_reviewers: Some(vec![User::new("joe-reviewer")]),
         reviews: None,
+        mergeable: None,
+        mergeable_state: None,
+        created_at: None,
+        updated_at: None,
+        draft: None,
+        labels: None,
+        milestone: None,
+        additions: None,
+        deletions: None,
+        changed_files: None,
         head: BranchRef {
             ref_name: "pr-branch".into(),
             sha: "ffff0000".into(),
@@ -194,6 +258,15 @@ fn some_commits() -> Vec<Commit> {
 
 }
 
+fn some_commit(author: &str) -> Commit {
+    Commit {
+        sha: "abcdef00001111".into(),
+        html_url: "http://commit/abcdef00001111".into(),
+        author: Some(User::new(author)),
+        commit: CommitDetails { message: "I made a commit!".into() },
+    }
+}
+
 #[test]
 fn test_ping() {
     let mut test = new_test();
@@ -214,18 +287,108 @@ fn test_commit_comment_with_path() {
         body: Some("I think this file should change".into()),
         html_url: "http://the-comment".into(),
         user: User::new("joe-reviewer"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
     });
     test.handler.data.sender = User::new("joe-reviewer");
+    test.github.mock_get_commit("some-user", "some-repo", "abcdef00001111", Ok(some_commit("bob-author")));
 
+    let msg = "Comment on \"src/main.rs\" (<http://the-github-host/some-user/some-repo/commit/abcdef00001111|abcdef0>)";
+    let attach = vec![
+        SlackAttachmentBuilder::new("I think this file should change")
+            .title("joe.reviewer said:")
+            .title_link("http://the-comment")
+            .build(),
+    ];
     test.slack.expect(vec![
-        slack::req(
-            "the-reviews-channel",
-            &format!("Comment on \"src/main.rs\" (<http://the-github-host/some-user/some-repo/commit/abcdef00001111|abcdef0>) {}", REPO_MSG),
-            vec![SlackAttachmentBuilder::new("I think this file should change")
-                .title("joe.reviewer said:")
-                .title_link("http://the-comment")
-                .build()]
-        )
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "commit_comment".into()), resp);
+}
+
+#[test]
+fn test_commit_comment_mentions() {
+    let mut test = new_test();
+    test.handler.event = "commit_comment".into();
+    test.handler.action = "created".into();
+    test.handler.data.comment = Some(Comment {
+        commit_id: Some("abcdef00001111".into()),
+        path: None,
+        body: Some("@mentioned-participant should this change?".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+    test.github.mock_get_commit("some-user", "some-repo", "abcdef00001111", Ok(some_commit("joe-reviewer")));
+
+    let msg = "Comment on \"abcdef0\" (<http://the-github-host/some-user/some-repo/commit/abcdef00001111|abcdef0>)";
+    let attach = vec![
+        SlackAttachmentBuilder::new("@mentioned-participant should this change?")
+            .title("joe.reviewer said:")
+            .title_link("http://the-comment")
+            .build(),
+    ];
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@mentioned.participant", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "commit_comment".into()), resp);
+}
+
+#[test]
+fn test_commit_comment_team_mentions() {
+    let mut test = new_test();
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/team-repo", "the-reviews-channel")
+            .with_team_channels(vec!["some-org/backend #backend".into()]),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/team-repo", test.github.github_host())).unwrap();
+    test.handler.event = "commit_comment".into();
+    test.handler.action = "created".into();
+    let body = "@some-org/backend @some-org/web should this change?";
+    test.handler.data.comment = Some(Comment {
+        commit_id: Some("abcdef00001111".into()),
+        path: None,
+        body: Some(body.into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+
+    test.github.mock_get_commit("some-user", "team-repo", "abcdef00001111", Ok(some_commit("joe-reviewer")));
+    // only the team without a channel gets looked up
+    test.github.mock_get_team_members(
+        "some-org",
+        "web",
+        Ok(vec![User::new("web-dev"), User::new("joe-reviewer")]),
+    );
+
+    let msg = "Comment on \"abcdef0\" (<http://the-github-host/some-user/team-repo/commit/abcdef00001111|abcdef0>)";
+    let repo_msg = "(<http://the-github-host/some-user/team-repo|some-user/team-repo>)";
+    let attach = vec![
+        SlackAttachmentBuilder::new(body)
+            .title("joe.reviewer said:")
+            .title_link("http://the-comment")
+            .build(),
+    ];
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, repo_msg), attach.clone()),
+        slack::req("@web.dev", msg, attach.clone()),
+        slack::req("#backend", &format!("{} {}", msg, repo_msg), attach.clone()),
     ]);
 
     let resp = test.handler.handle_event().unwrap();
@@ -243,8 +406,13 @@ fn test_commit_comment_no_path() {
         body: Some("I think this file should change".into()),
         html_url: "http://the-comment".into(),
         user: User::new("joe-reviewer"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
     });
     test.handler.data.sender = User::new("joe-reviewer");
+    // the comment still goes to the channel without the commit's author
+    test.github.mock_get_commit("some-user", "some-repo", "abcdef00001111", Err("no such commit".into()));
 
     test.slack.expect(vec![
         slack::req(
@@ -270,8 +438,10 @@ fn test_issue_comment() {
         number: 5,
         title: "The Issue".into(),
         html_url: "http://the-issue".into(),
+        body: None,
         user: User::new("the-pr-owner"),
         assignees: vec![User::new("assign1"), User::new("joe-reviewer")],
+        labels: vec![],
     });
     test.handler.data.comment = Some(Comment {
         commit_id: Some("abcdef00001111".into()),
@@ -279,6 +449,9 @@ fn test_issue_comment() {
         body: Some("I think this file should change, cc: @mentioned-participant".into()),
         html_url: "http://the-comment".into(),
         user: User::new("joe-reviewer"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
     });
     test.handler.data.sender = User::new("joe-reviewer");
     test.github.mock_get_pull_request_commits("some-user", "some-repo", 5, Ok(some_commits()));
@@ -303,394 +476,525 @@ fn test_issue_comment() {
     assert_eq!((StatusCode::Ok, "issue_comment".into()), resp);
 }
 
+fn some_issue() -> Issue {
+    Issue {
+        number: 5,
+        title: "The Issue".into(),
+        html_url: "http://the-issue".into(),
+        body: Some("It's broken".into()),
+        user: User::new("the-pr-owner"),
+        assignees: vec![],
+        labels: vec![],
+    }
+}
+
 #[test]
-fn test_pull_request_comment() {
+fn test_issue_opened() {
     let mut test = new_test();
-    test.handler.event = "pull_request_review_comment".into();
-    test.handler.action = "created".into();
-    test.handler.data.pull_request = some_pr();
-    test.handler.data.comment = Some(Comment {
-        commit_id: Some("abcdef00001111".into()),
-        path: Some("src/main.rs".into()),
-        body: Some("I think this file should change, cc: @mentioned-participant".into()),
-        html_url: "http://the-comment".into(),
-        user: User::new("joe-reviewer"),
-    });
-    test.handler.data.sender = User::new("joe-reviewer");
-    test.github.mock_get_pull_request_commits(
-        "some-user",
-        "some-repo",
-        32,
-        Ok(some_commits()),
-    );
-
-    let attach = vec![
-        SlackAttachmentBuilder::new("I think this file should change, cc: @mentioned-participant")
-            .title("joe.reviewer said:")
-            .title_link("http://the-comment")
-            .build(),
-    ];
-    let msg = "Comment on \"<http://the-pr|The PR>\"";
+    test.handler.event = "issues".into();
+    test.handler.action = "opened".into();
+    test.handler.data.issue = Some(some_issue());
+    test.handler.data.sender = User::new("the-pr-owner");
 
     test.slack.expect(vec![
-        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
-        slack::req("@the.pr.owner", msg, attach.clone()),
-        slack::req("@assign1", msg, attach.clone()),
-        slack::req("@bob.author", msg, attach.clone()),
-        slack::req("@mentioned.participant", msg, attach.clone()),
+        slack::req(
+            "the-reviews-channel",
+            &format!("Issue opened by the.pr.owner {}", REPO_MSG),
+            vec![
+                SlackAttachmentBuilder::new("It's broken")
+                    .title("Issue #5: \"The Issue\"")
+                    .title_link("http://the-issue")
+                    .build(),
+            ],
+        ),
     ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr_review_comment".into()), resp);
+    assert_eq!((StatusCode::Ok, "issues".into()), resp);
 }
 
-#[test]
-fn test_pull_request_review_commented() {
+fn new_rules_test(rules: Vec<EventRule>) -> GithubHandlerTest {
     let mut test = new_test();
-    test.handler.event = "pull_request_review".into();
-    test.handler.action = "submitted".into();
-    test.handler.data.pull_request = some_pr();
-    test.handler.data.review = Some(Review {
-        state: "commented".into(),
-        body: Some("I think this file should change, cc: @mentioned-participant".into()),
-        html_url: "http://the-comment".into(),
-        user: User::new("joe-reviewer"),
-    });
-    test.handler.data.sender = User::new("joe-reviewer");
-    test.github.mock_get_pull_request_commits(
-        "some-user",
-        "some-repo",
-        32,
-        Ok(some_commits()),
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/ruled-repo", "the-reviews-channel").with_rules(rules),
     );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/ruled-repo", test.github.github_host())).unwrap();
+    test.handler.event = "issues".into();
+    test.handler.action = "opened".into();
+    test.handler.data.issue = Some(some_issue());
+    test.handler.data.sender = User::new("the-pr-owner");
+    test
+}
 
-    let attach = vec![
-        SlackAttachmentBuilder::new("I think this file should change, cc: @mentioned-participant")
-            .title("joe.reviewer said:")
-            .title_link("http://the-comment")
-            .build(),
-    ];
-    let msg = "Comment on \"<http://the-pr|The PR>\"";
-
+#[test]
+fn test_issue_opened_with_rules() {
+    let mut triage = EventRule::new();
+    triage.events = Some(vec!["issues".into()]);
+    triage.actions = Some(vec!["opened".into()]);
+    triage.title = Some("(?i)issue".into());
+    triage.notify_channel = Some("the-triage-channel".into());
+    triage.add_labels = Some(vec!["triage".into()]);
+
+    let mut closed = EventRule::new();
+    closed.actions = Some(vec!["closed".into()]);
+    closed.notify_channel = Some("the-closed-channel".into());
+
+    let mut test = new_rules_test(vec![triage, closed]);
+    test.github.mock_add_pull_request_labels("some-user", "ruled-repo", 5, "triage", Ok(()));
+
+    let repo_msg = "(<http://the-github-host/some-user/ruled-repo|some-user/ruled-repo>)";
     test.slack.expect(vec![
-        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
-        slack::req("@the.pr.owner", msg, attach.clone()),
-        slack::req("@assign1", msg, attach.clone()),
-        slack::req("@bob.author", msg, attach.clone()),
-        slack::req("@mentioned.participant", msg, attach.clone()),
+        slack::req(
+            "the-triage-channel",
+            &format!("issues opened on <http://the-issue|#5: The Issue> by the-pr-owner {}", repo_msg),
+            vec![],
+        ),
+        slack::req(
+            "the-reviews-channel",
+            &format!("Issue opened by the.pr.owner {}", repo_msg),
+            vec![
+                SlackAttachmentBuilder::new("It's broken")
+                    .title("Issue #5: \"The Issue\"")
+                    .title_link("http://the-issue")
+                    .build(),
+            ],
+        ),
     ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr_review [comment]".into()), resp);
+    assert_eq!((StatusCode::Ok, "issues".into()), resp);
 }
 
 #[test]
-fn test_pull_request_comments_ignore_empty_messages() {
+fn test_issue_opened_ignored_by_rule() {
+    let mut notify = EventRule::new();
+    notify.notify_channel = Some("the-triage-channel".into());
+    let mut bots = EventRule::new();
+    bots.authors = Some(vec!["the-pr-*".into()]);
+    bots.ignore = Some(true);
+
+    let test = new_rules_test(vec![notify, bots]);
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "Ignored by rule: issues".into()), resp);
+}
+
+fn new_unknown_repo_test(policy: UnknownRepos) -> GithubHandlerTest {
     let mut test = new_test();
-    test.handler.event = "pull_request_review_comment".into();
-    test.handler.action = "created".into();
-    test.handler.data.pull_request = some_pr();
-    test.handler.data.comment = Some(Comment {
-        commit_id: Some("abcdef00001111".into()),
-        path: Some("src/main.rs".into()),
-        body: Some("".into()),
-        html_url: "http://the-comment".into(),
-        user: User::new("joe-reviewer"),
-    });
-    test.handler.data.sender = User::new("joe-reviewer");
+    test.handler.event = "issues".into();
+    test.handler.action = "opened".into();
+    test.handler.data.issue = Some(some_issue());
+    test.handler.data.sender = User::new("the-pr-owner");
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/other-user/unknown-repo", test.github.github_host())).unwrap();
+
+    let mut config = Config::new(UserConfig::new(), RepoConfig::new());
+    config.main.unknown_repos = Some(policy);
+    config.main.unknown_repos_channel = Some("the-unconfigured-channel".into());
+    config.main.admin_channel = Some("the-admins".into());
+    config.main.public_url = Some("https://octobot.company.com/".into());
+    let config = Arc::new(config);
+    test.handler.config = config.clone();
+    test.handler.messenger = messenger::new(config, test.slack.new_sender());
+    test
+}
 
-    test.slack.expect(vec![]);
+#[test]
+fn test_unknown_repo_notify() {
+    let mut test = new_unknown_repo_test(UnknownRepos::Notify);
+
+    let repo_link = "<http://the-github-host/other-user/unknown-repo|other-user/unknown-repo>";
+    let add_link =
+        "<https://octobot.company.com/#/repos?host=the-github-host&amp;repo=other-user%2Funknown-repo|Add it>";
+    test.slack.expect(vec![
+        slack::req(
+            "the-admins",
+            &format!("Received issues events for unconfigured repo {}", repo_link),
+            vec![SlackAttachmentBuilder::new(add_link).build()],
+        ),
+    ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr_review_comment".into()), resp);
+    assert_eq!((StatusCode::Ok, "Unconfigured repo: other-user/unknown-repo".into()), resp);
+
+    // only the first event of the repo is announced
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "Unconfigured repo: other-user/unknown-repo".into()), resp);
 }
 
 #[test]
-fn test_pull_request_comments_ignore_octobot() {
-    let mut test = new_test();
-    test.handler.event = "pull_request_review_comment".into();
-    test.handler.action = "created".into();
-    test.handler.data.pull_request = some_pr();
-    test.handler.data.comment = Some(Comment {
-        commit_id: Some("abcdef00001111".into()),
-        path: Some("src/main.rs".into()),
-        body: Some("I think this file should change, cc: @mentioned-participant".into()),
-        html_url: "http://the-comment".into(),
-        user: User::new("octobot"),
-    });
-    test.handler.data.sender = User::new("joe-reviewer");
+fn test_unknown_repo_drop() {
+    let test = new_unknown_repo_test(UnknownRepos::Drop);
 
-    test.slack.expect(vec![]);
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "Unconfigured repo: other-user/unknown-repo".into()), resp);
+}
+
+#[test]
+fn test_unknown_repo_channel() {
+    let mut test = new_unknown_repo_test(UnknownRepos::Channel);
+
+    test.slack.expect(vec![
+        slack::req(
+            "the-unconfigured-channel",
+            "Issue opened by the.pr.owner (<http://the-github-host/other-user/unknown-repo|other-user/unknown-repo>)",
+            vec![
+                SlackAttachmentBuilder::new("It's broken")
+                    .title("Issue #5: \"The Issue\"")
+                    .title_link("http://the-issue")
+                    .build(),
+            ],
+        ),
+    ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr_review_comment".into()), resp);
+    assert_eq!((StatusCode::Ok, "issues".into()), resp);
 }
 
 #[test]
-fn test_pull_request_review_approved() {
+fn test_issue_closed_to_label_channel() {
     let mut test = new_test();
-    test.handler.event = "pull_request_review".into();
-    test.handler.action = "submitted".into();
-    test.handler.data.pull_request = some_pr();
-    test.handler.data.review = Some(Review {
-        state: "approved".into(),
-        body: Some("I like it! cc: @mentioned-participant".into()),
-        html_url: "http://the-comment".into(),
-        user: User::new("joe-reviewer"),
-    });
-    test.handler.data.sender = User::new("joe-reviewer");
-    test.github.mock_get_pull_request_commits(
-        "some-user",
-        "some-repo",
-        32,
-        Ok(some_commits()),
-    );
+    test.handler.event = "issues".into();
+    test.handler.action = "closed".into();
+    let mut issue = some_issue();
+    issue.labels = vec![Label::new("security")];
+    test.handler.data.issue = Some(issue);
 
-    let attach = vec![
-        SlackAttachmentBuilder::new("I like it! cc: @mentioned-participant")
-            .title("Review: Approved")
-            .title_link("http://the-comment")
-            .color("good")
-            .build(),
-    ];
-    let msg = "joe.reviewer approved PR \"<http://the-pr|The PR>\"";
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/issues-repo", "the-reviews-channel")
+            .with_issue_channels(vec!["security the-security-channel".into()]),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/issues-repo", test.github.github_host())).unwrap();
 
+    let repo_msg = "(<http://the-github-host/some-user/issues-repo|some-user/issues-repo>)";
     test.slack.expect(vec![
-        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
-        slack::req("@the.pr.owner", msg, attach.clone()),
-        slack::req("@assign1", msg, attach.clone()),
-        slack::req("@bob.author", msg, attach.clone()),
-        slack::req("@mentioned.participant", msg, attach.clone()),
+        slack::req(
+            "the-security-channel",
+            &format!("Issue closed by joe.sender {}", repo_msg),
+            vec![
+                SlackAttachmentBuilder::new("")
+                    .title("Issue #5: \"The Issue\"")
+                    .title_link("http://the-issue")
+                    .build(),
+            ],
+        ),
     ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr_review".into()), resp);
+    assert_eq!((StatusCode::Ok, "issues".into()), resp);
 }
 
 #[test]
-fn test_pull_request_review_changes_requested() {
+fn test_issue_assigned() {
     let mut test = new_test();
-    test.handler.event = "pull_request_review".into();
-    test.handler.action = "submitted".into();
-    test.handler.data.pull_request = some_pr();
-    test.handler.data.review = Some(Review {
-        state: "changes_requested".into(),
-        body: Some("It needs some work! cc: @mentioned-participant".into()),
-        html_url: "http://the-comment".into(),
-        user: User::new("joe-reviewer"),
-    });
-    test.handler.data.sender = User::new("joe-reviewer");
-    test.github.mock_get_pull_request_commits(
-        "some-user",
-        "some-repo",
-        32,
-        Ok(some_commits()),
-    );
+    test.handler.event = "issues".into();
+    test.handler.action = "assigned".into();
+    test.handler.data.issue = Some(some_issue());
+    test.handler.data.assignee = Some(User::new("assign1"));
 
-    let attach = vec![
-        SlackAttachmentBuilder::new("It needs some work! cc: @mentioned-participant")
-            .title("Review: Changes Requested")
-            .title_link("http://the-comment")
-            .color("danger")
-            .build(),
-    ];
-    let msg = "joe.reviewer requested changes to PR \"<http://the-pr|The PR>\"";
     test.slack.expect(vec![
-        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
-        slack::req("@the.pr.owner", msg, attach.clone()),
-        slack::req("@assign1", msg, attach.clone()),
-        slack::req("@bob.author", msg, attach.clone()),
-        slack::req("@mentioned.participant", msg, attach.clone()),
+        slack::req(
+            "@assign1",
+            "Issue assigned to you by joe.sender",
+            vec![
+                SlackAttachmentBuilder::new("")
+                    .title("Issue #5: \"The Issue\"")
+                    .title_link("http://the-issue")
+                    .build(),
+            ],
+        ),
     ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr_review".into()), resp);
+    assert_eq!((StatusCode::Ok, "issues".into()), resp);
 }
 
 #[test]
-fn test_pull_request_opened() {
+fn test_issue_opened_locale() {
     let mut test = new_test();
-    test.handler.event = "pull_request".into();
-    test.handler.action = "opened".into();
-    test.handler.data.pull_request = some_pr();
-    test.handler.data.sender = User::new("the-pr-owner");
-    test.github.mock_get_pull_request_commits(
-        "some-user",
-        "some-repo",
-        32,
-        Ok(some_commits()),
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_locale("fr"),
     );
+    test.handler.config = Arc::new(Config::new(UserConfig::new(), repos));
+    test.handler.messenger = messenger::new(test.handler.config.clone(), test.slack.new_sender());
 
-    let attach = vec![
-        SlackAttachmentBuilder::new("")
-            .title("Pull Request #32: \"The PR\"")
-            .title_link("http://the-pr")
-            .build(),
-    ];
-    let msg = "Pull Request opened by the.pr.owner";
+    test.handler.event = "issues".into();
+    test.handler.action = "opened".into();
+    test.handler.data.issue = Some(some_issue());
+    test.handler.data.sender = User::new("the-pr-owner");
 
     test.slack.expect(vec![
         slack::req(
             "the-reviews-channel",
-            &format!("{} {}", msg, REPO_MSG),
-            attach.clone()
+            &format!("Issue ouverte par the.pr.owner {}", REPO_MSG),
+            vec![
+                SlackAttachmentBuilder::new("It's broken")
+                    .title("Issue #5 : \"The Issue\"")
+                    .title_link("http://the-issue")
+                    .build(),
+            ],
         ),
     ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+    assert_eq!((StatusCode::Ok, "issues".into()), resp);
 }
 
 #[test]
-fn test_pull_request_closed() {
+fn test_issue_assigned_user_locale() {
     let mut test = new_test();
-    test.handler.event = "pull_request".into();
-    test.handler.action = "closed".into();
-    test.handler.data.pull_request = some_pr();
-    test.handler.data.sender = User::new("the-pr-closer");
-    test.github.mock_get_pull_request_commits(
-        "some-user",
-        "some-repo",
-        32,
-        Ok(some_commits()),
-    );
+    let mut users = UserConfig::new();
+    users.insert_info(test.github.github_host(), UserInfo::new("assign1", "assign1").with_locale("es"));
+    let repos = test.handler.config.repos().clone();
+    test.handler.config = Arc::new(Config::new(users, repos));
+    test.handler.messenger = messenger::new(test.handler.config.clone(), test.slack.new_sender());
 
-    let attach = vec![
-        SlackAttachmentBuilder::new("")
-            .title("Pull Request #32: \"The PR\"")
-            .title_link("http://the-pr")
-            .build(),
-    ];
-    let msg = "Pull Request closed";
+    test.handler.event = "issues".into();
+    test.handler.action = "assigned".into();
+    test.handler.data.issue = Some(some_issue());
+    test.handler.data.assignee = Some(User::new("assign1"));
 
+    // the attachment stays in the repo's language
     test.slack.expect(vec![
-        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
-        slack::req("@the.pr.owner", msg, attach.clone()),
-        slack::req("@assign1", msg, attach.clone()),
-        slack::req("@bob.author", msg, attach.clone()),
-        slack::req("@joe.reviewer", msg, attach.clone()),
+        slack::req(
+            "@assign1",
+            "Issue asignada a ti por joe.sender",
+            vec![
+                SlackAttachmentBuilder::new("")
+                    .title("Issue #5: \"The Issue\"")
+                    .title_link("http://the-issue")
+                    .build(),
+            ],
+        ),
     ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+    assert_eq!((StatusCode::Ok, "issues".into()), resp);
 }
 
 #[test]
-fn test_pull_request_reopened() {
+fn test_dependabot_alert() {
     let mut test = new_test();
-    test.handler.event = "pull_request".into();
-    test.handler.action = "reopened".into();
-    test.handler.data.pull_request = some_pr();
-    test.handler.data.sender = User::new("the-pr-closer");
-    test.github.mock_get_pull_request_commits(
-        "some-user",
-        "some-repo",
-        32,
-        Ok(some_commits()),
-    );
+    test.handler.event = "dependabot_alert".into();
+    test.handler.action = "created".into();
+    test.handler.data.alert = Some(SecurityAlert {
+        html_url: Some("http://the-alert".into()),
+        security_advisory: Some(SecurityAdvisory {
+            ghsa_id: "GHSA-1234".into(),
+            summary: "Remote code execution in left-pad".into(),
+            severity: "critical".into(),
+        }),
+        dependency: Some(AlertDependency { package: AlertPackage { name: "left-pad".into() } }),
+        ..SecurityAlert::default()
+    });
 
-    let attach = vec![
-        SlackAttachmentBuilder::new("")
-            .title("Pull Request #32: \"The PR\"")
-            .title_link("http://the-pr")
-            .build(),
-    ];
-    let msg = "Pull Request reopened";
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/secure-repo", "the-reviews-channel").with_security_channel("the-security"),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/secure-repo", test.github.github_host())).unwrap();
 
+    let repo_msg = "(<http://the-github-host/some-user/secure-repo|some-user/secure-repo>)";
     test.slack.expect(vec![
         slack::req(
-            "the-reviews-channel",
-            &format!("{} {}", msg, REPO_MSG),
-            attach.clone()
+            "the-security",
+            &format!("Dependabot alert opened {}", repo_msg),
+            vec![
+                SlackAttachmentBuilder::new("<https://github.com/advisories/GHSA-1234|GHSA-1234>")
+                    .title("Remote code execution in left-pad")
+                    .title_link("http://the-alert")
+                    .field("Severity", "critical")
+                    .field("Package", "left-pad")
+                    .color("danger")
+                    .build(),
+            ],
         ),
     ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+    assert_eq!((StatusCode::Ok, "dependabot_alert".into()), resp);
 }
 
 #[test]
-fn test_pull_request_assigned() {
+fn test_code_scanning_alert_without_security_channel() {
     let mut test = new_test();
-    test.handler.event = "pull_request".into();
-    test.handler.action = "assigned".into();
+    test.handler.event = "code_scanning_alert".into();
+    test.handler.action = "created".into();
+    test.handler.data.alert = Some(SecurityAlert {
+        html_url: Some("http://the-alert".into()),
+        rule: Some(AlertRule {
+            id: "js/sql-injection".into(),
+            description: None,
+            severity: Some("error".into()),
+            security_severity_level: Some("high".into()),
+        }),
+        tool: Some(AlertTool { name: "CodeQL".into() }),
+        ..SecurityAlert::default()
+    });
+
+    // not in the review channel
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "code_scanning_alert".into()), resp);
+}
+
+// an issue_comment event on the PR from `login`, with the usual notifications for it
+fn new_pr_command_test(body: &str, login: &str) -> GithubHandlerTest {
+    let mut test = new_test();
+    test.handler.event = "issue_comment".into();
+    test.handler.action = "created".into();
     test.handler.data.pull_request = some_pr();
-    if let Some(ref mut pr) = test.handler.data.pull_request {
-        pr.assignees.push(User::new("assign2"));
-    }
-    test.handler.data.sender = User::new("the-pr-closer");
-    test.github.mock_get_pull_request_commits(
-        "some-user",
-        "some-repo",
-        32,
-        Ok(some_commits()),
-    );
+    test.handler.data.comment = Some(Comment {
+        commit_id: None,
+        path: None,
+        body: Some(body.into()),
+        html_url: "http://the-comment".into(),
+        user: User::new(login),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
+    });
+    test.handler.data.sender = User::new(login);
+    test.github.mock_get_pull_request_commits("some-user", "some-repo", 32, Ok(vec![]));
 
     let attach = vec![
-        SlackAttachmentBuilder::new("")
-            .title("Pull Request #32: \"The PR\"")
-            .title_link("http://the-pr")
+        SlackAttachmentBuilder::new(body)
+            .title(format!("{} said:", login.replace("-", ".")))
+            .title_link("http://the-comment")
             .build(),
     ];
-    let msg = "Pull Request assigned to assign1, assign2";
-
+    let msg = "Comment on \"<http://the-pr|The PR>\"";
     test.slack.expect(vec![
         slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
         slack::req("@the.pr.owner", msg, attach.clone()),
         slack::req("@assign1", msg, attach.clone()),
-        slack::req("@assign2", msg, attach.clone()),
-        slack::req("@bob.author", msg, attach.clone()),
-        slack::req("@joe.reviewer", msg, attach.clone()),
     ]);
+    test
+}
+
+#[test]
+fn test_pr_command_merge() {
+    let test = new_pr_command_test("octobot merge", "joe-reviewer");
+    test.github.mock_get_user_permission("some-user", "some-repo", "joe-reviewer", Ok("write".into()));
+    test.github.mock_merge_pull_request("some-user", "some-repo", 32, "ffff0000", "merge", Ok(()));
+    test.github.mock_comment_pull_request("some-user", "some-repo", 32, "@joe-reviewer Merged.", Ok(()));
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+    assert_eq!((StatusCode::Ok, "issue_comment".into()), resp);
 }
 
 #[test]
-fn test_pull_request_unassigned() {
-    let mut test = new_test();
-    test.handler.event = "pull_request".into();
-    test.handler.action = "unassigned".into();
-    test.handler.data.pull_request = some_pr();
-    test.handler.data.sender = User::new("the-pr-closer");
-    test.github.mock_get_pull_request_commits(
+fn test_pr_command_backport() {
+    let mut test = new_pr_command_test("Thanks!\n\n@octobot backport 1.0 2.0", "joe-reviewer");
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.merged = Some(true);
+    }
+    test.github.mock_get_user_permission("some-user", "some-repo", "joe-reviewer", Ok("admin".into()));
+    test.github.mock_comment_pull_request(
         "some-user",
         "some-repo",
         32,
-        Ok(some_commits()),
+        "@joe-reviewer Backporting to release/1.0, release/2.0.",
+        Ok(()),
     );
 
-    let attach = vec![
-        SlackAttachmentBuilder::new("")
-            .title("Pull Request #32: \"The PR\"")
-            .title_link("http://the-pr")
-            .build(),
-    ];
-    let msg = "Pull Request unassigned";
+    let expect_thread = test.expect_will_merge_branches(vec!["release/1.0".into(), "release/2.0".into()]);
 
-    test.slack.expect(vec![
-        slack::req(
-            "the-reviews-channel",
-            &format!("{} {}", msg, REPO_MSG),
-            attach.clone()
-        ),
-    ]);
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "issue_comment".into()), resp);
+
+    expect_thread.join().unwrap();
+}
+
+#[test]
+fn test_pr_command_retry_backport() {
+    let mut test = new_pr_command_test("octobot retry-backport", "joe-reviewer");
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.merged = Some(true);
+    }
+    test.github.mock_get_user_permission("some-user", "some-repo", "joe-reviewer", Ok("write".into()));
+    test.github.mock_get_pull_request_labels(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(vec![Label::new("other"), Label::new("backport-1.0")]),
+    );
+    test.github.mock_comment_pull_request(
+        "some-user",
+        "some-repo",
+        32,
+        "@joe-reviewer Backporting again to release/1.0.",
+        Ok(()),
+    );
+
+    let expect_thread = test.expect_will_merge_branches(vec!["release/1.0".into()]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+    assert_eq!((StatusCode::Ok, "issue_comment".into()), resp);
+
+    expect_thread.join().unwrap();
 }
 
 #[test]
-fn test_pull_request_review_requested() {
+fn test_pr_command_not_merged() {
+    let mut test = new_pr_command_test("octobot backport 1.0\noctobot what", "joe-reviewer");
+    test.github.mock_get_user_permission("some-user", "some-repo", "joe-reviewer", Ok("write".into()));
+    test.github.mock_comment_pull_request(
+        "some-user",
+        "some-repo",
+        32,
+        "@joe-reviewer Only merged PRs can be backported: label it `backport-1.0` instead.\n\
+         Unknown command `what`. octobot commands: `octobot backport <version>...`, `octobot retry-backport`, \
+         `octobot merge`",
+        Ok(()),
+    );
+
+    let expect_thread = test.expect_will_merge_branches(vec![]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "issue_comment".into()), resp);
+
+    expect_thread.join().unwrap();
+}
+
+#[test]
+fn test_pr_command_no_permission() {
+    let test = new_pr_command_test("octobot merge", "joe-reviewer");
+    test.github.mock_get_user_permission("some-user", "some-repo", "joe-reviewer", Ok("read".into()));
+    test.github.mock_comment_pull_request(
+        "some-user",
+        "some-repo",
+        32,
+        "@joe-reviewer Only users with write access to some-repo can give octobot commands.",
+        Ok(()),
+    );
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "issue_comment".into()), resp);
+}
+
+#[test]
+fn test_pull_request_comment() {
     let mut test = new_test();
-    test.handler.event = "pull_request".into();
-    test.handler.action = "review_requested".into();
+    test.handler.event = "pull_request_review_comment".into();
+    test.handler.action = "created".into();
     test.handler.data.pull_request = some_pr();
-    if let Some(ref mut pr) = test.handler.data.pull_request {
-        pr.requested_reviewers = Some(vec![User::new("joe-reviewer"), User::new("smith-reviewer")]);
-    }
-    test.handler.data.sender = User::new("the-pr-closer");
+    test.handler.data.comment = Some(Comment {
+        commit_id: Some("abcdef00001111".into()),
+        path: Some("src/main.rs".into()),
+        body: Some("I think this file should change, cc: @mentioned-participant".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
     test.github.mock_get_pull_request_commits(
         "some-user",
         "some-repo",
@@ -699,292 +1003,2603 @@ fn test_pull_request_review_requested() {
     );
 
     let attach = vec![
-        SlackAttachmentBuilder::new("")
-            .title("Pull Request #32: \"The PR\"")
-            .title_link("http://the-pr")
+        SlackAttachmentBuilder::new("I think this file should change, cc: @mentioned-participant")
+            .title("joe.reviewer said:")
+            .title_link("http://the-comment")
             .build(),
     ];
-    let msg = "Pull Request submitted for review to joe.reviewer, smith.reviewer";
+    let msg = "Comment on \"<http://the-pr|The PR>\"";
 
     test.slack.expect(vec![
         slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
         slack::req("@the.pr.owner", msg, attach.clone()),
         slack::req("@assign1", msg, attach.clone()),
         slack::req("@bob.author", msg, attach.clone()),
-        slack::req("@joe.reviewer", msg, attach.clone()),
-        slack::req("@smith.reviewer", msg, attach.clone()),
+        slack::req("@mentioned.participant", msg, attach.clone()),
     ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+    assert_eq!((StatusCode::Ok, "pr_review_comment".into()), resp);
 }
 
 #[test]
-fn test_pull_request_other() {
+fn test_pull_request_comment_code_context() {
     let mut test = new_test();
-    test.handler.event = "pull_request".into();
-    test.handler.action = "some-other-action".into();
-    test.handler.data.pull_request = some_pr();
+    test.handler.event = "pull_request_review_comment".into();
+    test.handler.action = "created".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.comment = Some(Comment {
+        commit_id: Some("abcdef00001111".into()),
+        path: Some("src/main.rs".into()),
+        body: Some("Shouldn't this be `&&`?".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+        pull_request_review_id: None,
+        diff_hunk: Some(
+            "@@ -10,6 +10,8 @@ fn main() {\n     let a = true;\n     let b = false;\n+    let c = a || b;\n \
+             \n+    if c || a {"
+                .into(),
+        ),
+        line: Some(14),
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new(
+            "*src/main.rs:14*\n```\n     let b = false;\n+    let c = a || b;\n \n+    if c || a {\n```\n\
+             Shouldn't this be `&amp;&amp;`?",
+        ).title("joe.reviewer said:")
+            .title_link("http://the-comment")
+            .build(),
+    ];
+    let msg = "Comment on \"<http://the-pr|The PR>\"";
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review_comment".into()), resp);
+}
+
+fn edits_test() -> GithubHandlerTest {
+    let mut test = new_test();
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/edits-repo", "the-reviews-channel").with_comment_edits(true),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/edits-repo", test.github.github_host())).unwrap();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.sender = User::new("joe-reviewer");
+    test
+}
+
+#[test]
+fn test_pull_request_comment_edited() {
+    let mut test = edits_test();
+    test.handler.event = "issue_comment".into();
+    test.handler.action = "edited".into();
+    test.handler.data.comment = Some(Comment {
+        commit_id: None,
+        path: None,
+        body: Some("I meant the other file".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
+    });
+
+    let repo_msg = "(<http://the-github-host/some-user/edits-repo|some-user/edits-repo>)";
+    test.slack.expect(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("Comment edited on \"<http://the-pr|The PR>\" {}", repo_msg),
+            vec![
+                SlackAttachmentBuilder::new("I meant the other file")
+                    .title("joe.reviewer now says:")
+                    .title_link("http://the-comment")
+                    .build(),
+            ],
+        ),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "issue_comment".into()), resp);
+}
+
+#[test]
+fn test_pull_request_comment_deleted() {
+    let mut test = edits_test();
+    test.handler.event = "pull_request_review_comment".into();
+    test.handler.action = "deleted".into();
+    test.handler.data.comment = Some(Comment {
+        commit_id: Some("abcdef00001111".into()),
+        path: Some("src/main.rs".into()),
+        body: Some("Typo here".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+        pull_request_review_id: Some(77),
+        diff_hunk: None,
+        line: Some(3),
+    });
+
+    let repo_msg = "(<http://the-github-host/some-user/edits-repo|some-user/edits-repo>)";
+    test.slack.expect(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("Comment from joe.reviewer deleted on \"<http://the-pr|The PR>\" {}", repo_msg),
+            vec![],
+        ),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review_comment".into()), resp);
+}
+
+#[test]
+fn test_pull_request_comment_edits_off() {
+    let mut test = new_test();
+    test.handler.event = "issue_comment".into();
+    test.handler.action = "edited".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.comment = Some(Comment {
+        commit_id: None,
+        path: None,
+        body: Some("I meant the other file".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+
+    test.slack.expect(vec![]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "issue_comment".into()), resp);
+}
+
+#[test]
+fn test_pull_request_review_records_metrics() {
+    let mut test = new_test();
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/metrics-repo", "the-reviews-channel").with_weekly_metrics(true),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/metrics-repo", test.github.github_host())).unwrap();
+    test.handler.event = "pull_request_review".into();
+    test.handler.action = "submitted".into();
+    let mut pr = some_pr().unwrap();
+    pr.created_at = Some("2017-10-02T00:00:00Z".into());
+    test.handler.data.pull_request = Some(pr);
+    // no body, so there's nothing to tell anyone
+    test.handler.data.review = Some(Review {
+        id: None,
+        state: "commented".into(),
+        body: None,
+        html_url: "http://the-review".into(),
+        user: User::new("joe-reviewer"),
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+
+    test.slack.expect(vec![]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review [comment]".into()), resp);
+
+    let week = test.handler.weekly_metrics.write(|m| m.take_week(&test.handler.data.repository));
+    assert_eq!(Some(&1), week.reviews.get("joe-reviewer"));
+    assert_eq!(1, week.first_review_secs.len());
+}
+
+#[test]
+fn test_pull_request_review_commented() {
+    let mut test = new_test();
+    test.handler.event = "pull_request_review".into();
+    test.handler.action = "submitted".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.review = Some(Review {
+        id: None,
+        state: "commented".into(),
+        body: Some("I think this file should change, cc: @mentioned-participant".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("I think this file should change, cc: @mentioned-participant")
+            .title("joe.reviewer said:")
+            .title_link("http://the-comment")
+            .build(),
+    ];
+    let msg = "Comment on \"<http://the-pr|The PR>\"";
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+        slack::req("@mentioned.participant", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review [comment]".into()), resp);
+}
+
+#[test]
+fn test_pull_request_review_with_inline_comments() {
+    let mut test = new_test();
+    test.handler.event = "pull_request_review".into();
+    test.handler.action = "submitted".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.review = Some(Review {
+        id: Some(77),
+        state: "commented".into(),
+        body: None,
+        html_url: "http://the-review".into(),
+        user: User::new("joe-reviewer"),
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+
+    let inline = |path: &str, line: u32, body: &str| {
+        Comment {
+            commit_id: Some("abcdef00001111".into()),
+            path: Some(path.into()),
+            body: Some(body.into()),
+            html_url: "http://the-comment".into(),
+            user: User::new("joe-reviewer"),
+            pull_request_review_id: Some(77),
+            diff_hunk: None,
+            line: Some(line),
+        }
+    };
+    test.github.mock_get_review_comments(
+        "some-user",
+        "some-repo",
+        32,
+        77,
+        Ok(vec![
+            inline("src/main.rs", 3, "Typo here"),
+            inline("src/lib.rs", 10, "cc: @mentioned-participant"),
+            inline("src/lib.rs", 22, "Needs a test"),
+            inline("README.md", 1, "Outdated"),
+        ]),
+    );
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new(
+            "*src/main.rs:3*: Typo here\n*src/lib.rs:10*: cc: @mentioned-participant\n*src/lib.rs:22*: Needs a test\n\
+             … and 1 more",
+        ).title("joe.reviewer left 4 inline comments:")
+            .title_link("http://the-review")
+            .build(),
+    ];
+    let msg = "Comment on \"<http://the-pr|The PR>\"";
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+        slack::req("@mentioned.participant", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review [comment]".into()), resp);
+}
+
+#[test]
+fn test_pull_request_comment_in_review() {
+    let mut test = new_test();
+    test.handler.event = "pull_request_review_comment".into();
+    test.handler.action = "created".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.comment = Some(Comment {
+        commit_id: Some("abcdef00001111".into()),
+        path: Some("src/main.rs".into()),
+        body: Some("Typo here".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+        pull_request_review_id: Some(77),
+        diff_hunk: None,
+        line: None,
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+
+    test.slack.expect(vec![]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review_comment [in review]".into()), resp);
+}
+
+#[test]
+fn test_pull_request_comments_ignore_empty_messages() {
+    let mut test = new_test();
+    test.handler.event = "pull_request_review_comment".into();
+    test.handler.action = "created".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.comment = Some(Comment {
+        commit_id: Some("abcdef00001111".into()),
+        path: Some("src/main.rs".into()),
+        body: Some("".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+
+    test.slack.expect(vec![]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review_comment".into()), resp);
+}
+
+#[test]
+fn test_pull_request_comments_ignore_octobot() {
+    let mut test = new_test();
+    test.handler.event = "pull_request_review_comment".into();
+    test.handler.action = "created".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.comment = Some(Comment {
+        commit_id: Some("abcdef00001111".into()),
+        path: Some("src/main.rs".into()),
+        body: Some("I think this file should change, cc: @mentioned-participant".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("octobot"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+
+    test.slack.expect(vec![]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review_comment".into()), resp);
+}
+
+#[test]
+fn test_pull_request_comments_ignore_bots() {
+    let mut test = new_test();
+    test.handler.event = "issue_comment".into();
+    test.handler.action = "created".into();
+    test.handler.data.issue = Some(some_issue());
+    test.handler.data.comment = Some(Comment {
+        commit_id: None,
+        path: None,
+        body: Some("Bumps serde from 1.0.1 to 1.0.2".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("dependabot[bot]"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
+    });
+    test.handler.data.sender = User::new("dependabot[bot]");
+
+    test.slack.expect(vec![]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "issue_comment".into()), resp);
+}
+
+#[test]
+fn test_pull_request_review_ignores_bots() {
+    let mut test = new_test();
+    test.handler.event = "pull_request_review".into();
+    test.handler.action = "submitted".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.review = Some(Review {
+        id: None,
+        state: "approved".into(),
+        body: Some("Auto-approved".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("renovate[bot]"),
+    });
+    test.handler.data.sender = User::new("renovate[bot]");
+
+    test.slack.expect(vec![]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review".into()), resp);
+
+    // the approval still counts
+    let rx = test.auto_merge_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("auto-merge message") {
+        WorkMessage::WorkItem(AutoMergeRequest::PullRequest(_, pr)) => assert_eq!(32, pr.number),
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
+}
+
+#[test]
+fn test_pull_request_review_approved() {
+    let mut test = new_test();
+    test.handler.event = "pull_request_review".into();
+    test.handler.action = "submitted".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.review = Some(Review {
+        id: None,
+        state: "approved".into(),
+        body: Some("I like it! cc: @mentioned-participant".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("I like it! cc: @mentioned-participant")
+            .title("Review: Approved")
+            .title_link("http://the-comment")
+            .color("good")
+            .build(),
+    ];
+    let msg = "joe.reviewer approved PR \"<http://the-pr|The PR>\"";
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+        slack::req("@mentioned.participant", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review".into()), resp);
+
+    let rx = test.auto_merge_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("auto-merge message") {
+        WorkMessage::WorkItem(AutoMergeRequest::PullRequest(_, pr)) => assert_eq!(32, pr.number),
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
+}
+
+#[test]
+fn test_pull_request_review_status() {
+    let mut test = new_test();
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/gated-repo", "the-reviews-channel").with_review_status_approvals(Some(2)),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/gated-repo", test.github.github_host())).unwrap();
+    test.handler.event = "pull_request_review".into();
+    test.handler.action = "dismissed".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.review = Some(Review {
+        id: Some(5),
+        state: "dismissed".into(),
+        body: None,
+        html_url: "http://the-review".into(),
+        user: User::new("joe-reviewer"),
+    });
+
+    let mut approved = Review::new("", User::new("bob-reviewer"));
+    approved.state = "APPROVED".into();
+    let mut dismissed = Review::new("", User::new("joe-reviewer"));
+    dismissed.state = "DISMISSED".into();
+    test.github.mock_get_pull_request_reviews("some-user", "gated-repo", 32, Ok(vec![approved, dismissed]));
+    test.github.mock_create_status(
+        "some-user",
+        "gated-repo",
+        "ffff0000",
+        &Status {
+            state: "pending".into(),
+            target_url: Some("http://the-pr".into()),
+            context: Some("octobot/reviews".into()),
+            description: Some("1 of 2 approvals".into()),
+            creator: None,
+        },
+        Ok(()),
+    );
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review".into()), resp);
+}
+
+#[test]
+fn test_pull_request_review_changes_requested() {
+    let mut test = new_test();
+    test.handler.event = "pull_request_review".into();
+    test.handler.action = "submitted".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.review = Some(Review {
+        id: None,
+        state: "changes_requested".into(),
+        body: Some("It needs some work! cc: @mentioned-participant".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("It needs some work! cc: @mentioned-participant")
+            .title("Review: Changes Requested")
+            .title_link("http://the-comment")
+            .color("danger")
+            .build(),
+    ];
+    let msg = "joe.reviewer requested changes to PR \"<http://the-pr|The PR>\"";
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+        slack::req("@mentioned.participant", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review".into()), resp);
+}
+
+#[test]
+fn test_pull_request_opened() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "opened".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.sender = User::new("the-pr-owner");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("The body")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .field("Branch", "master ← pr-branch")
+            .field("Author", "the.pr.owner")
+            .build(),
+    ];
+    let msg = "Pull Request opened by the.pr.owner";
+
+    test.slack.expect(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("{} {}", msg, REPO_MSG),
+            attach.clone()
+        ),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    let rx = test.reviewers_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("reviewers message") {
+        WorkMessage::WorkItem(req) => assert_eq!(32, req.pull_request.number),
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
+}
+
+#[test]
+fn test_pull_request_opened_size_label() {
+    let mut test = new_test();
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/sized-repo", "the-reviews-channel").with_size_labels(None),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/sized-repo", test.github.github_host())).unwrap();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "opened".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.sender = User::new("the-pr-owner");
+
+    let stats = DiffStats {
+        additions: 120,
+        deletions: 40,
+        changed_files: 7,
+    };
+    test.github.mock_get_pull_request_stats("some-user", "sized-repo", 32, Ok(stats));
+    test.github.mock_get_pull_request_labels(
+        "some-user",
+        "sized-repo",
+        32,
+        Ok(vec![Label::new("size/XS"), Label::new("bug")]),
+    );
+    test.github.mock_remove_pull_request_label("some-user", "sized-repo", 32, "size/XS", Ok(()));
+    test.github.mock_add_pull_request_labels("some-user", "sized-repo", 32, "size/L", Ok(()));
+    test.github.mock_get_pull_request_commits("some-user", "sized-repo", 32, Ok(some_commits()));
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("The body")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .field("Branch", "master ← pr-branch")
+            .field("Author", "the.pr.owner")
+            .field("Changes", "+120 −40, 7 files")
+            .build(),
+    ];
+    let msg = "Pull Request opened by the.pr.owner \
+               (<http://the-github-host/some-user/sized-repo|some-user/sized-repo>)";
+    test.slack.expect(vec![slack::req("the-reviews-channel", msg, attach)]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_synchronize_same_size() {
+    let mut test = new_test();
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/sized-repo", "the-reviews-channel").with_size_labels(Some(vec![200])),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/sized-repo", test.github.github_host())).unwrap();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "synchronize".into();
+    test.handler.data.pull_request = some_pr();
+
+    let stats = DiffStats {
+        additions: 120,
+        deletions: 40,
+        changed_files: 7,
+    };
+    test.github.mock_get_pull_request_stats("some-user", "sized-repo", 32, Ok(stats));
+    test.github.mock_get_pull_request_labels("some-user", "sized-repo", 32, Ok(vec![Label::new("size/XS")]));
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_synchronize_path_labels() {
+    let mut test = new_test();
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/labeled-repo", "the-reviews-channel")
+            .with_path_labels(vec!["docs/** documentation".into(), "migrations/ db-change".into()]),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/labeled-repo", test.github.github_host())).unwrap();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "synchronize".into();
+    test.handler.data.pull_request = some_pr();
+
+    let file = |name: &str| {
+        PullRequestFile {
+            filename: name.into(),
+            status: "modified".into(),
+            additions: 1,
+            deletions: 1,
+            changes: 2,
+        }
+    };
+    test.github.mock_get_pull_request_files(
+        "some-user",
+        "labeled-repo",
+        32,
+        Ok(vec![file("docs/setup.md"), file("src/main.rs")]),
+    );
+    test.github.mock_get_pull_request_labels(
+        "some-user",
+        "labeled-repo",
+        32,
+        Ok(vec![Label::new("db-change"), Label::new("bug")]),
+    );
+    test.github.mock_remove_pull_request_label("some-user", "labeled-repo", 32, "db-change", Ok(()));
+    test.github.mock_add_pull_request_labels("some-user", "labeled-repo", 32, "documentation", Ok(()));
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+fn conventions_status(state: &str, description: &str) -> Status {
+    Status {
+        state: state.into(),
+        target_url: Some("http://the-pr".into()),
+        context: Some("octobot/conventions".into()),
+        description: Some(description.into()),
+        creator: None,
+    }
+}
+
+#[test]
+fn test_pull_request_conventions() {
+    let mut test = new_test();
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/strict-repo", "the-reviews-channel")
+            .with_conventions(Some("^[A-Z]+-[0-9]+".into()), None),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/strict-repo", test.github.github_host())).unwrap();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "edited".into();
+    test.handler.data.pull_request = some_pr();
+    test.github.mock_get_pull_request_commits("some-user", "strict-repo", 32, Ok(some_commits()));
+
+    let problem = "The title \"The PR\" doesn't match `^[A-Z]+-[0-9]+`";
+    test.github.mock_get_statuses("some-user", "strict-repo", "ffff0000", Ok(vec![]));
+    test.github.mock_comment_pull_request(
+        "some-user",
+        "strict-repo",
+        32,
+        &format!(
+            "Thanks for the pull request! It doesn't follow this repo's conventions yet:\n\n* {}\n\n\
+             Edit the title or reword the commits, and I'll check again.",
+            problem
+        ),
+        Ok(()),
+    );
+    test.github.mock_create_status(
+        "some-user",
+        "strict-repo",
+        "ffff0000",
+        &conventions_status("failure", problem),
+        Ok(()),
+    );
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_conventions_already_told() {
+    let mut test = new_test();
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/strict-repo", "the-reviews-channel")
+            .with_conventions(Some("^[A-Z]+-[0-9]+".into()), None),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/strict-repo", test.github.github_host())).unwrap();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "synchronize".into();
+    test.handler.data.before = Some("eeee0000".into());
+    test.handler.data.pull_request = some_pr();
+    test.github.mock_get_pull_request_commits("some-user", "strict-repo", 32, Ok(some_commits()));
+
+    // same problem as before the push: no new comment
+    let status = conventions_status("failure", "The title \"The PR\" doesn't match `^[A-Z]+-[0-9]+`");
+    test.github.mock_get_statuses("some-user", "strict-repo", "eeee0000", Ok(vec![status.clone()]));
+    test.github.mock_create_status("some-user", "strict-repo", "ffff0000", &status, Ok(()));
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+fn changelog_status(state: &str, description: &str) -> Status {
+    Status {
+        state: state.into(),
+        target_url: Some("http://the-pr".into()),
+        context: Some("octobot/changelog".into()),
+        description: Some(description.into()),
+        creator: None,
+    }
+}
+
+#[test]
+fn test_pull_request_changelog_missing() {
+    let mut test = new_test();
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/changelog-repo", "the-reviews-channel")
+            .with_changelog_check(vec!["src/".into()], None),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/changelog-repo", test.github.github_host())).unwrap();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "synchronize".into();
+    test.handler.data.before = Some("eeee0000".into());
+    test.handler.data.pull_request = some_pr();
+
+    let file = |name: &str| {
+        PullRequestFile {
+            filename: name.into(),
+            status: "modified".into(),
+            additions: 1,
+            deletions: 1,
+            changes: 2,
+        }
+    };
+    test.github.mock_get_pull_request_labels("some-user", "changelog-repo", 32, Ok(vec![Label::new("bug")]));
+    test.github.mock_get_pull_request_files(
+        "some-user",
+        "changelog-repo",
+        32,
+        Ok(vec![file("src/main.rs"), file("README.md")]),
+    );
+    test.github.mock_get_statuses(
+        "some-user",
+        "changelog-repo",
+        "eeee0000",
+        Ok(vec![changelog_status("success", "CHANGELOG.md is up to date")]),
+    );
+    test.github.mock_comment_pull_request(
+        "some-user",
+        "changelog-repo",
+        32,
+        "This pull request changes code but not `CHANGELOG.md`. Add a line there describing the change, or, if it \
+         doesn't need one, add the `no-changelog` label, and I'll check again.",
+        Ok(()),
+    );
+    test.github.mock_create_status(
+        "some-user",
+        "changelog-repo",
+        "ffff0000",
+        &changelog_status("failure", "Code changes need a CHANGELOG.md entry, or the no-changelog label"),
+        Ok(()),
+    );
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_changelog_labeled() {
+    let mut test = new_test();
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/changelog-repo", "the-reviews-channel")
+            .with_changelog_check(vec!["src/".into()], None),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/changelog-repo", test.github.github_host())).unwrap();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "labeled".into();
+    test.handler.data.label = Some(Label::new("no-changelog"));
+    test.handler.data.pull_request = some_pr();
+
+    // the label lets it through without looking at the files
+    test.github.mock_get_pull_request_labels("some-user", "changelog-repo", 32, Ok(vec![Label::new("no-changelog")]));
+    test.github.mock_create_status(
+        "some-user",
+        "changelog-repo",
+        "ffff0000",
+        &changelog_status("success", "Labeled no-changelog"),
+        Ok(()),
+    );
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_opened_mentions() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "opened".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.body = Some("@mentioned-participant can you check the migration? cc @the-pr-owner".into());
+    }
+    test.handler.data.sender = User::new("the-pr-owner");
+    test.github.mock_get_pull_request_commits("some-user", "some-repo", 32, Ok(some_commits()));
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("@mentioned-participant can you check the migration? cc @the-pr-owner")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .field("Branch", "master ← pr-branch")
+            .field("Author", "the.pr.owner")
+            .build(),
+    ];
+    let msg = "Pull Request opened by the.pr.owner";
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@mentioned.participant", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_opened_details() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "opened".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.body = Some(format!("**Why:** {}", "because ".repeat(100)));
+        pr.labels = Some(vec![Label::new("bug"), Label::new("db-change")]);
+        pr.additions = Some(12);
+        pr.deletions = Some(3);
+        pr.changed_files = Some(1);
+    }
+    test.handler.data.sender = User::new("the-pr-owner");
+    test.github.mock_get_pull_request_commits("some-user", "some-repo", 32, Ok(some_commits()));
+
+    let text = format!("*Why:* {}because\n… <http://the-pr|read more>", "because ".repeat(60));
+    let attach = vec![
+        SlackAttachmentBuilder::new(&text)
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .field("Branch", "master ← pr-branch")
+            .field("Author", "the.pr.owner")
+            .field("Changes", "+12 −3, 1 file")
+            .field("Labels", "bug, db-change")
+            .build(),
+    ];
+    let msg = "Pull Request opened by the.pr.owner";
+    test.slack.expect(vec![slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach)]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_draft_opened() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "opened".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.draft = Some(true);
+    }
+    test.handler.data.sender = User::new("the-pr-owner");
+
+    test.slack.expect(vec![]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    let rx = test.reviewers_rx.take().unwrap();
+    assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+}
+
+#[test]
+fn test_pull_request_ready_for_review() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "ready_for_review".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.draft = Some(false);
+    }
+    test.handler.data.sender = User::new("the-pr-owner");
+    test.github.mock_get_pull_request_commits("some-user", "some-repo", 32, Ok(some_commits()));
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("The body")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .field("Branch", "master ← pr-branch")
+            .field("Author", "the.pr.owner")
+            .build(),
+    ];
+    let msg = "Pull Request opened by the.pr.owner";
+
+    test.slack.expect(vec![slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone())]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    let rx = test.reviewers_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("reviewers message") {
+        WorkMessage::WorkItem(req) => assert_eq!(32, req.pull_request.number),
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
+}
+
+fn expect_ready_for_review(test: &mut GithubHandlerTest) {
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request is ready for review";
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+    ]);
+}
+
+#[test]
+fn test_pull_request_wip_title_removed() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "edited".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.changes = Some(Changes {
+        title: Some(ChangedFrom { from: "WIP: The PR".into() }),
+        ..Changes::default()
+    });
+    test.handler.data.sender = User::new("the-pr-owner");
+    expect_ready_for_review(&mut test);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    let rx = test.reviewers_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("reviewers message") {
+        WorkMessage::WorkItem(req) => assert_eq!(32, req.pull_request.number),
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
+}
+
+#[test]
+fn test_pull_request_wip_label_removed() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "unlabeled".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.label = Some(Label::new("WIP"));
+    test.handler.data.sender = User::new("the-pr-owner");
+    expect_ready_for_review(&mut test);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_edited_still_wip() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "edited".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.title = "WIP: The new PR".into();
+    }
+    test.handler.data.changes = Some(Changes {
+        title: Some(ChangedFrom { from: "WIP: The PR".into() }),
+        ..Changes::default()
+    });
+    test.handler.data.sender = User::new("the-pr-owner");
+
+    test.slack.expect(vec![]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_tracks_review_reminders() {
+    let mut test = new_test();
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_review_reminders(Some(24), None),
+    );
+    test.handler.config = Arc::new(Config::new(UserConfig::new(), repos));
+    test.handler.event = "pull_request".into();
+    test.handler.action = "edited".into();
+    test.handler.data.pull_request = some_pr();
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    let rx = test.review_reminders_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("review reminders message") {
+        WorkMessage::WorkItem(req) => assert_eq!(32, req.pull_request.number),
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
+}
+
+#[test]
+fn test_pull_request_closed() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "closed".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.sender = User::new("the-pr-closer");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request closed";
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+        slack::req("@joe.reviewer", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+fn with_notifications(test: &mut GithubHandlerTest, channel: Option<EventFilter>, direct: Option<EventFilter>) {
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_notifications(channel, direct),
+    );
+    test.handler.config = Arc::new(Config::new(UserConfig::new(), repos));
+    test.handler.messenger = messenger::new(test.handler.config.clone(), test.slack.new_sender());
+}
+
+#[test]
+fn test_pull_request_closed_user_locale() {
+    let mut test = new_test();
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_locale("de"),
+    );
+    let mut users = UserConfig::new();
+    users.insert_info(test.github.github_host(), UserInfo::new("bob-author", "bob.author").with_locale("fr"));
+    test.handler.config = Arc::new(Config::new(users, repos));
+    test.handler.messenger = messenger::new(test.handler.config.clone(), test.slack.new_sender());
+
+    test.handler.event = "pull_request".into();
+    test.handler.action = "closed".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.sender = User::new("the-pr-closer");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request geschlossen";
+
+    // the channel and everyone else get the repo's language, and bob-author gets French
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", "Pull Request fermée", attach.clone()),
+        slack::req("@joe.reviewer", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_closed_no_direct_messages() {
+    let mut test = new_test();
+    with_notifications(&mut test, None, Some(EventFilter::new(Some(vec!["review".into()]), None)));
+    test.handler.event = "pull_request".into();
+    test.handler.action = "closed".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.sender = User::new("the-pr-closer");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request closed";
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_comment_no_channel() {
+    let mut test = new_test();
+    with_notifications(&mut test, Some(EventFilter::new(None, Some(vec!["comment".into()]))), None);
+    test.handler.event = "pull_request_review_comment".into();
+    test.handler.action = "created".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.comment = Some(Comment {
+        commit_id: Some("abcdef00001111".into()),
+        path: Some("src/main.rs".into()),
+        body: Some("I think this file should change".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("I think this file should change")
+            .title("joe.reviewer said:")
+            .title_link("http://the-comment")
+            .build(),
+    ];
+    let msg = "Comment on \"<http://the-pr|The PR>\"";
+
+    test.slack.expect(vec![
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr_review_comment".into()), resp);
+}
+
+#[test]
+fn test_pull_request_reopened() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "reopened".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.sender = User::new("the-pr-closer");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request reopened";
+
+    test.slack.expect(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("{} {}", msg, REPO_MSG),
+            attach.clone()
+        ),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_assigned() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "assigned".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.assignees.push(User::new("assign2"));
+    }
+    test.handler.data.sender = User::new("the-pr-closer");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request assigned to assign1, assign2";
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@assign2", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+        slack::req("@joe.reviewer", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_reopened_with_template() {
+    let mut test = new_test();
+    let templates = hashmap!{messages::PR_REOPENED.to_string() => "{author}'s #{number} is back".to_string()};
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/worded-repo", "the-reviews-channel").with_message_templates(templates),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/worded-repo", test.github.github_host())).unwrap();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "reopened".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.sender = User::new("the-pr-closer");
+    test.github.mock_get_pull_request_commits("some-user", "worded-repo", 32, Ok(some_commits()));
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "the.pr.owner's #32 is back (<http://the-github-host/some-user/worded-repo|some-user/worded-repo>)";
+    test.slack.expect(vec![slack::req("the-reviews-channel", msg, attach)]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_unassigned() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "unassigned".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.sender = User::new("the-pr-closer");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request unassigned";
+
+    test.slack.expect(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("{} {}", msg, REPO_MSG),
+            attach.clone()
+        ),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_review_requested() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "review_requested".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.requested_reviewers = Some(vec![User::new("joe-reviewer"), User::new("smith-reviewer")]);
+    }
+    test.handler.data.sender = User::new("the-pr-closer");
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request submitted for review to joe.reviewer, smith.reviewer";
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+        slack::req("@joe.reviewer", msg, attach.clone()),
+        slack::req("@smith.reviewer", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_review_requested_one_reviewer() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "review_requested".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.requested_reviewer = Some(User::new("smith-reviewer"));
+    test.handler.data.sender = User::new("the-pr-owner");
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request submitted for review to smith.reviewer";
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@smith.reviewer", "Your review was requested by the.pr.owner", attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_review_requested_by_octobot() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "review_requested".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.requested_reviewer = Some(User::new("smith-reviewer"));
+    test.handler.data.sender = User::new("octobot");
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request submitted for review to smith.reviewer";
+
+    test.slack.expect(vec![slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone())]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_review_request_removed() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "review_request_removed".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.requested_reviewer = Some(User::new("smith-reviewer"));
+    test.handler.data.sender = User::new("the-pr-owner");
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+
+    test.slack.expect(vec![
+        slack::req("@smith.reviewer", "the.pr.owner removed the request for your review", attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_other() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "some-other-action".into();
+    test.handler.data.pull_request = some_pr();
     test.handler.data.sender = User::new("the-pr-closer");
 
-    // should not do anything!
+    // should not do anything!
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+
+#[test]
+fn test_pull_request_labeled_not_merged() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "labeled".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.merged = Some(false);
+    }
+    test.handler.data.sender = User::new("the-pr-owner");
+
+    // labeled but not merged --> noop
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+// the reviews and CI status of some_pr for the "Pull Request merged" message: either one approval and green CI,
+// or no reviews and no CI at all
+fn mock_merge_summary(test: &GithubHandlerTest, repo: &str, approved: bool) {
+    let mut reviews = vec![];
+    let mut statuses = vec![];
+    if approved {
+        let mut review = Review::new("", User::new("joe-reviewer"));
+        review.state = "APPROVED".into();
+        reviews.push(review);
+        statuses.push(Status {
+            state: "success".into(),
+            target_url: None,
+            context: Some("ci".into()),
+            description: None,
+            creator: None,
+        });
+    }
+    test.github.mock_get_pull_request_reviews("some-user", repo, 32, Ok(reviews));
+    test.github.mock_get_combined_status(
+        "some-user",
+        repo,
+        "ffff0000",
+        Ok(CombinedStatus {
+            state: if approved { "success" } else { "pending" }.into(),
+            sha: "ffff0000".into(),
+            statuses: statuses,
+        }),
+    );
+}
+
+#[test]
+fn test_pull_request_merged_error_getting_labels() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "closed".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.merged = Some(true);
+    }
+    test.handler.data.sender = User::new("the-pr-merger");
+
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+    mock_merge_summary(&test, "some-repo", false);
+    test.github.mock_get_pull_request_labels(
+        "some-user",
+        "some-repo",
+        32,
+        Err("whooops.".into()),
+    );
+
+    let msg1 = "Pull Request merged";
+    let attach1 = vec![
+        SlackAttachmentBuilder::new("Not approved")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+
+    let msg2 = "Error getting Pull Request labels";
+    let attach2 = vec![SlackAttachmentBuilder::new("whooops.").color("danger").build()];
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg1, REPO_MSG), attach1.clone()),
+        slack::req("@the.pr.owner", msg1, attach1.clone()),
+        slack::req("@assign1", msg1, attach1.clone()),
+        slack::req("@bob.author", msg1, attach1.clone()),
+        slack::req("@joe.reviewer", msg1, attach1.clone()),
+
+        slack::req("the-reviews-channel", &format!("{} {}", msg2, REPO_MSG), attach2.clone()),
+        slack::req("@the.pr.owner", msg2, attach2.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_merged_no_labels() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "closed".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.merged = Some(true);
+    }
+    test.handler.data.sender = User::new("the-pr-merger");
+
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+    mock_merge_summary(&test, "some-repo", true);
+    test.github.mock_get_pull_request_labels("some-user", "some-repo", 32, Ok(vec![]));
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("Approved by joe-reviewer\nCI was green")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request merged";
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+        slack::req("@joe.reviewer", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+fn dependencies_status(state: &str, description: &str, url: &str) -> Status {
+    Status {
+        state: state.into(),
+        target_url: Some(url.into()),
+        context: Some("octobot/dependencies".into()),
+        description: Some(description.into()),
+        creator: None,
+    }
+}
+
+#[test]
+fn test_pull_request_depends_on() {
+    let mut test = new_test();
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/dependent-repo", "the-reviews-channel").with_track_dependencies(true),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/dependent-repo", test.github.github_host())).unwrap();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "edited".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.body = Some("Depends on #12 and some-user/some-repo#5".into());
+    }
+
+    let mut merged = some_pr().unwrap();
+    merged.state = "closed".into();
+    merged.merged = Some(true);
+    test.github.get_pull_request("some-user", "dependent-repo", 12, Ok(some_pr().unwrap()));
+    test.github.get_pull_request("some-user", "some-repo", 5, Ok(merged));
+    test.github.mock_create_status(
+        "some-user",
+        "dependent-repo",
+        "ffff0000",
+        &dependencies_status("pending", "Waiting for some-user/dependent-repo#12 to merge", "http://the-pr"),
+        Ok(()),
+    );
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_merged_unblocks_dependents() {
+    let mut test = new_test();
+    let host = test.github.github_host().to_string();
+    let dependent_repo = Repo::parse(&format!("http://{}/some-user/dependent-repo", host)).unwrap();
+    test.handler.dependents.write(|d: &mut Dependents| {
+        d.set(&PrRef::new(&dependent_repo, 40), &vec![PrRef::new(&test.handler.data.repository, 32)])
+    });
+    test.handler.event = "pull_request".into();
+    test.handler.action = "closed".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.merged = Some(true);
+        pr.state = "closed".into();
+    }
+    test.handler.data.sender = User::new("the-pr-merger");
+
+    test.github.mock_get_pull_request_commits("some-user", "some-repo", 32, Ok(some_commits()));
+    mock_merge_summary(&test, "some-repo", true);
+    test.github.mock_get_pull_request_labels("some-user", "some-repo", 32, Ok(vec![]));
+
+    let mut dependent = some_pr().unwrap();
+    dependent.number = 40;
+    dependent.title = "The next PR".into();
+    dependent.html_url = "http://the-next-pr".into();
+    dependent.body = Some("Blocked by some-user/some-repo#32".into());
+    dependent.user = User::new("next-pr-owner");
+    dependent.head.sha = "dddd0000".into();
+    test.github.get_pull_request("some-user", "dependent-repo", 40, Ok(dependent));
+    test.github.get_pull_request("some-user", "some-repo", 32, Ok(test.handler.data.pull_request.clone().unwrap()));
+    test.github.mock_create_status(
+        "some-user",
+        "dependent-repo",
+        "dddd0000",
+        &dependencies_status("success", "Nothing to wait for", "http://the-next-pr"),
+        Ok(()),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("Approved by joe-reviewer\nCI was green")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request merged";
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+        slack::req("@joe.reviewer", msg, attach.clone()),
+        slack::req(
+            "@next.pr.owner",
+            "Pull Request some-user/some-repo#32 was merged",
+            vec![
+                SlackAttachmentBuilder::new("")
+                    .title("Pull Request #40: \"The next PR\"")
+                    .title_link("http://the-next-pr")
+                    .build(),
+            ],
+        ),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+}
+
+#[test]
+fn test_pull_request_merged_backport_labels() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "closed".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.merged = Some(true);
+    }
+    test.handler.data.sender = User::new("the-pr-merger");
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("Not approved")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request merged";
+
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(some_commits()),
+    );
+    mock_merge_summary(&test, "some-repo", false);
+    test.github.mock_get_pull_request_labels(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(vec![
+            Label::new("other"),
+            Label::new("backport-1.0"),
+            Label::new("BACKPORT-2.0"),
+            Label::new("BACKport-other-thing"),
+            Label::new("non-matching"),
+        ]),
+    );
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+        slack::req("@joe.reviewer", msg, attach.clone()),
+    ]);
+
+    let expect_thread = test.expect_will_merge_branches(
+        vec!["release/1.0".into(), "release/2.0".into(), "release/other-thing".into()],
+    );
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    expect_thread.join().unwrap();
+}
+
+#[test]
+fn test_pull_request_merged_backport_labels_custom_pattern() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "closed".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.merged = Some(true);
+    }
+    test.handler.data.sender = User::new("the-pr-merger");
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/custom-branches-repo", "the-reviews-channel")
+            .with_release_branch_prefix(Some("the-other-prefix-".into())),
+    );
+
+    // change the repo to an unconfigured one
+    test.handler.data.repository = Repo::parse(
+        &format!("http://{}/some-user/custom-branches-repo", test.github.github_host()),
+    ).unwrap();
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("Not approved")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request merged";
+
+    test.github.mock_get_pull_request_commits(
+        "some-user",
+        "custom-branches-repo",
+        32,
+        Ok(some_commits()),
+    );
+    mock_merge_summary(&test, "custom-branches-repo", false);
+    test.github.mock_get_pull_request_labels(
+        "some-user",
+        "custom-branches-repo",
+        32,
+        Ok(vec![
+            Label::new("other"),
+            Label::new("backport-1.0"),
+            Label::new("BACKPORT-2.0"),
+            Label::new("BACKport-other-thing"),
+            Label::new("non-matching"),
+        ]),
+    );
+
+    let repo_msg = "(<http://the-github-host/some-user/custom-branches-repo|some-user/custom-branches-repo>)";
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, repo_msg), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+        slack::req("@joe.reviewer", msg, attach.clone()),
+    ]);
+
+    let expect_thread = test.expect_will_merge_branches(vec![
+        "the-other-prefix-1.0".into(),
+        "the-other-prefix-2.0".into(),
+        "the-other-prefix-other-thing".into(),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    expect_thread.join().unwrap();
+}
+
+#[test]
+fn test_pull_request_merged_chained_backports() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "closed".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.merged = Some(true);
+    }
+    test.handler.data.sender = User::new("the-pr-merger");
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/chained-repo", "the-reviews-channel").with_chain_backports(true),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/chained-repo", test.github.github_host())).unwrap();
+
+    test.github.mock_get_pull_request_commits("some-user", "chained-repo", 32, Ok(some_commits()));
+    mock_merge_summary(&test, "chained-repo", false);
+    test.github.mock_get_pull_request_labels(
+        "some-user",
+        "chained-repo",
+        32,
+        Ok(vec![
+            Label::new("backport-1.10"),
+            Label::new("other"),
+            Label::new("backport-2.0"),
+            Label::new("backport-1.9"),
+        ]),
+    );
+
+    let attach = vec![
+        SlackAttachmentBuilder::new("Not approved")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let msg = "Pull Request merged";
+    let repo_msg = "(<http://the-github-host/some-user/chained-repo|some-user/chained-repo>)";
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, repo_msg), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@bob.author", msg, attach.clone()),
+        slack::req("@joe.reviewer", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    // only the oldest for now, and its backport carries on to the others
+    let rx = test.pr_merge_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("pr merge message") {
+        WorkMessage::WorkItem(req) => {
+            assert_eq!("release/1.9", req.target_branch);
+            assert_eq!(vec!["backport-1.10".to_string(), "backport-2.0".to_string()], req.carried_labels);
+        }
+        _ => panic!("Unexpected message"),
+    };
+    assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+}
+
+#[test]
+fn test_pull_request_merged_retroactively_labeled() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "labeled".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.merged = Some(true);
+    }
+    test.handler.data.label = Some(Label::new("backport-7.123"));
+    test.handler.data.sender = User::new("the-pr-merger");
+
+    let expect_thread = test.expect_will_merge_branches(vec!["release/7.123".into()]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    expect_thread.join().unwrap();
+}
+
+#[test]
+fn test_pull_request_merged_backport_mirrors() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "labeled".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.merged = Some(true);
+    }
+    test.handler.data.label = Some(Label::new("backport-7.123"));
+    test.handler.data.sender = User::new("the-pr-merger");
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/mirrored-repo", "the-reviews-channel")
+            .with_backport_mirrors(vec!["internal/mirrored-repo".into(), "internal/vendor lib/=third_party/".into()]),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/mirrored-repo", test.github.github_host())).unwrap();
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    // the repo's own backport, then one in each mirror
+    let rx = test.pr_merge_rx.take().unwrap();
+    let mut targets = vec![];
+    while let Ok(msg) = rx.recv_timeout(Duration::from_millis(300)) {
+        match msg {
+            WorkMessage::WorkItem(req) => {
+                assert_eq!("release/7.123", req.target_branch);
+                targets.push((req.target().full_name.clone(), req.path_mappings.clone()));
+            }
+            _ => panic!("Unexpected message"),
+        };
+    }
+    assert_eq!(
+        vec![
+            ("some-user/mirrored-repo".to_string(), vec![]),
+            ("internal/mirrored-repo".to_string(), vec![]),
+            ("internal/vendor".to_string(), vec![("lib/".to_string(), "third_party/".to_string())]),
+        ],
+        targets
+    );
+}
+
+#[test]
+fn test_pull_request_labeled_backport_branch_rule() {
+    let mut test = new_test();
+    test.handler.event = "pull_request".into();
+    test.handler.action = "labeled".into();
+    test.handler.data.pull_request = some_pr();
+    if let Some(ref mut pr) = test.handler.data.pull_request {
+        pr.merged = Some(true);
+    }
+    test.handler.data.label = Some(Label::new("hotfix-2.1"));
+    test.handler.data.sender = User::new("the-pr-merger");
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/hotfix-repo", "the-reviews-channel")
+            .with_backport_branches(vec!["hotfix-(.*) hotfix/$1".into()]),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/hotfix-repo", test.github.github_host())).unwrap();
+
+    let expect_thread = test.expect_will_merge_branches(vec!["hotfix/2.1".into()]);
 
     let resp = test.handler.handle_event().unwrap();
     assert_eq!((StatusCode::Ok, "pr".into()), resp);
-}
 
+    expect_thread.join().unwrap();
+}
 
 #[test]
-fn test_pull_request_labeled_not_merged() {
+fn test_pull_request_queue_for_merge() {
     let mut test = new_test();
     test.handler.event = "pull_request".into();
     test.handler.action = "labeled".into();
     test.handler.data.pull_request = some_pr();
-    if let Some(ref mut pr) = test.handler.data.pull_request {
-        pr.merged = Some(false);
-    }
+    test.handler.data.label = Some(Label::new("queue-for-merge"));
     test.handler.data.sender = User::new("the-pr-owner");
 
-    // labeled but not merged --> noop
-
     let resp = test.handler.handle_event().unwrap();
     assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    let rx = test.merge_queue_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("merge queue message") {
+        WorkMessage::WorkItem(MergeQueueRequest::Add(repo, pr)) => {
+            assert_eq!(test.handler.data.repository.html_url, repo.html_url);
+            assert_eq!(32, pr.number);
+        }
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
 }
 
 #[test]
-fn test_pull_request_merged_error_getting_labels() {
+fn test_pull_request_unqueue_for_merge() {
     let mut test = new_test();
     test.handler.event = "pull_request".into();
-    test.handler.action = "closed".into();
+    test.handler.action = "unlabeled".into();
     test.handler.data.pull_request = some_pr();
-    if let Some(ref mut pr) = test.handler.data.pull_request {
-        pr.merged = Some(true);
-    }
-    test.handler.data.sender = User::new("the-pr-merger");
+    test.handler.data.label = Some(Label::new("queue-for-merge"));
+    test.handler.data.sender = User::new("the-pr-owner");
 
-    test.github.mock_get_pull_request_commits(
-        "some-user",
-        "some-repo",
-        32,
-        Ok(some_commits()),
-    );
-    test.github.mock_get_pull_request_labels(
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    let rx = test.merge_queue_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("merge queue message") {
+        WorkMessage::WorkItem(MergeQueueRequest::Remove(_, pr)) => assert_eq!(32, pr.number),
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
+}
+
+#[test]
+fn test_status_success() {
+    let mut test = new_test();
+    test.handler.event = "status".into();
+    test.handler.data.sha = Some("ffff0000".into());
+    test.handler.data.state = Some("success".into());
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "status".into()), resp);
+
+    let rx = test.merge_queue_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("merge queue message") {
+        WorkMessage::WorkItem(MergeQueueRequest::Check(repo)) => {
+            assert_eq!(test.handler.data.repository.html_url, repo.html_url)
+        }
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
+
+    let rx = test.auto_merge_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("auto-merge message") {
+        WorkMessage::WorkItem(AutoMergeRequest::Commit(_, sha)) => assert_eq!("ffff0000", sha),
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
+}
+
+#[test]
+fn test_create_tag() {
+    let mut test = new_test();
+    test.handler.event = "create".into();
+    test.handler.data.ref_type = Some("tag".into());
+    test.handler.data.ref_name = Some("v1.10.0".into());
+    test.handler.data.sender = User::new("the-pr-owner");
+
+    test.github.mock_get_tags(
         "some-user",
         "some-repo",
-        32,
-        Err("whooops.".into()),
+        Ok(vec!["v1.10.0".into(), "v1.9.1".into(), "nightly".into(), "v1.2.0".into(), "v2.0.0".into()]),
     );
 
-    let msg1 = "Pull Request merged";
-    let attach1 = vec![
-        SlackAttachmentBuilder::new("")
-            .title("Pull Request #32: \"The PR\"")
-            .title_link("http://the-pr")
-            .build(),
-    ];
+    let link = "<http://the-github-host/some-user/some-repo/compare/v1.9.1...v1.10.0|Changes since v1.9.1>";
+    let repo_msg = "(<http://the-github-host/some-user/some-repo|some-user/some-repo>)";
+    test.slack.expect(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("Tag v1.10.0 created by the.pr.owner {}", repo_msg),
+            vec![SlackAttachmentBuilder::new(link).build()],
+        ),
+    ]);
 
-    let msg2 = "Error getting Pull Request labels";
-    let attach2 = vec![SlackAttachmentBuilder::new("whooops.").color("danger").build()];
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "create".into()), resp);
 
-    test.slack.expect(vec![
-        slack::req("the-reviews-channel", &format!("{} {}", msg1, REPO_MSG), attach1.clone()),
-        slack::req("@the.pr.owner", msg1, attach1.clone()),
-        slack::req("@assign1", msg1, attach1.clone()),
-        slack::req("@bob.author", msg1, attach1.clone()),
-        slack::req("@joe.reviewer", msg1, attach1.clone()),
+    // the repo has no version files to bump
+    assert!(test.version_bumps_rx.take().unwrap().recv_timeout(Duration::from_millis(100)).is_err());
+}
 
-        slack::req("the-reviews-channel", &format!("{} {}", msg2, REPO_MSG), attach2.clone()),
-        slack::req("@the.pr.owner", msg2, attach2.clone()),
+#[test]
+fn test_create_tag_bumps_version() {
+    let mut test = new_test();
+    test.handler.event = "create".into();
+    test.handler.data.ref_type = Some("tag".into());
+    test.handler.data.ref_name = Some("v1.10.0".into());
+    test.handler.data.sender = User::new("the-pr-owner");
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/bumped-repo", "the-reviews-channel")
+            .with_version_bump_files(vec!["VERSION patch (.*)".into()], None),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/bumped-repo", test.github.github_host())).unwrap();
+
+    test.github.mock_get_tags("some-user", "bumped-repo", Ok(vec!["v1.10.0".into()]));
+    let repo_msg = "(<http://the-github-host/some-user/bumped-repo|some-user/bumped-repo>)";
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("Tag v1.10.0 created by the.pr.owner {}", repo_msg), vec![]),
     ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+    assert_eq!((StatusCode::Ok, "create".into()), resp);
+
+    let rx = test.version_bumps_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("version bump message") {
+        WorkMessage::WorkItem(req) => {
+            assert_eq!("some-user/bumped-repo", req.repo.full_name);
+            assert_eq!("1.10.0", req.released);
+            assert_eq!("tagging v1.10.0", req.reason);
+        }
+        _ => panic!("Unexpected message"),
+    };
+    assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
 }
 
 #[test]
-fn test_pull_request_merged_no_labels() {
+fn test_create_branch_ignored() {
     let mut test = new_test();
-    test.handler.event = "pull_request".into();
-    test.handler.action = "closed".into();
-    test.handler.data.pull_request = some_pr();
-    if let Some(ref mut pr) = test.handler.data.pull_request {
-        pr.merged = Some(true);
-    }
-    test.handler.data.sender = User::new("the-pr-merger");
+    test.handler.event = "create".into();
+    test.handler.data.ref_type = Some("branch".into());
+    test.handler.data.ref_name = Some("v1.10.0".into());
 
-    test.github.mock_get_pull_request_commits(
-        "some-user",
-        "some-repo",
-        32,
-        Ok(some_commits()),
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "create".into()), resp);
+}
+
+#[test]
+fn test_release_published() {
+    let mut test = new_test();
+    test.handler.event = "release".into();
+    test.handler.action = "published".into();
+    test.handler.data.release = Some(Release {
+        tag_name: "nightly-3".into(),
+        name: Some("Nightly".into()),
+        body: Some("Fixes things\n\nAnd more".into()),
+        html_url: "http://the-release".into(),
+        prerelease: true,
+        author: User::new("the-pr-owner"),
+    });
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/released-repo", "the-reviews-channel").with_release_channel("the-releases"),
     );
-    test.github.mock_get_pull_request_labels("some-user", "some-repo", 32, Ok(vec![]));
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/released-repo", test.github.github_host())).unwrap();
 
-    let attach = vec![
-        SlackAttachmentBuilder::new("")
-            .title("Pull Request #32: \"The PR\"")
-            .title_link("http://the-pr")
-            .build(),
-    ];
-    let msg = "Pull Request merged";
+    // there's no tag before it
+    test.github.mock_get_tags("some-user", "released-repo", Ok(vec!["nightly-3".into()]));
 
+    let repo_msg = "(<http://the-github-host/some-user/released-repo|some-user/released-repo>)";
     test.slack.expect(vec![
-        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
-        slack::req("@the.pr.owner", msg, attach.clone()),
-        slack::req("@assign1", msg, attach.clone()),
-        slack::req("@bob.author", msg, attach.clone()),
-        slack::req("@joe.reviewer", msg, attach.clone()),
+        slack::req(
+            "the-releases",
+            &format!("Pre-release Nightly published by the.pr.owner {}", repo_msg),
+            vec![
+                SlackAttachmentBuilder::new("Fixes things\n\nAnd more")
+                    .title("Nightly (nightly-3)")
+                    .title_link("http://the-release")
+                    .color("good")
+                    .build(),
+            ],
+        ),
     ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+    assert_eq!((StatusCode::Ok, "release".into()), resp);
 }
 
 #[test]
-fn test_pull_request_merged_backport_labels() {
+fn test_deployment_created() {
     let mut test = new_test();
-    test.handler.event = "pull_request".into();
-    test.handler.action = "closed".into();
-    test.handler.data.pull_request = some_pr();
-    if let Some(ref mut pr) = test.handler.data.pull_request {
-        pr.merged = Some(true);
-    }
-    test.handler.data.sender = User::new("the-pr-merger");
+    test.handler.event = "deployment".into();
+    test.handler.action = "created".into();
+    test.handler.data.deployment = Some(Deployment {
+        sha: "ffff0000aaaa".into(),
+        ref_name: "pr-branch".into(),
+        environment: "staging".into(),
+        description: Some("Preview".into()),
+        creator: User::new("the-pr-owner"),
+    });
 
-    let attach = vec![
-        SlackAttachmentBuilder::new("")
-            .title("Pull Request #32: \"The PR\"")
-            .title_link("http://the-pr")
-            .build(),
-    ];
-    let msg = "Pull Request merged";
+    test.github.mock_get_pull_requests("some-user", "some-repo", Some("open".into()), None, Ok(vec![]));
 
-    test.github.mock_get_pull_request_commits(
-        "some-user",
-        "some-repo",
-        32,
-        Ok(some_commits()),
+    let repo_msg = "(<http://the-github-host/some-user/some-repo|some-user/some-repo>)";
+    test.slack.expect(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("Deploying pr-branch to staging (the.pr.owner) {}", repo_msg),
+            vec![
+                SlackAttachmentBuilder::new(
+                    "pr-branch at <http://the-github-host/some-user/some-repo/commit/ffff0000aaaa|ffff000>\nPreview",
+                ).build(),
+            ],
+        ),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "deployment".into()), resp);
+}
+
+#[test]
+fn test_deployment_status_failure() {
+    let mut test = new_test();
+    test.handler.event = "deployment_status".into();
+    test.handler.action = "created".into();
+    test.handler.data.deployment = Some(Deployment {
+        sha: "ffff0000".into(),
+        ref_name: "pr-branch".into(),
+        environment: "production".into(),
+        description: None,
+        creator: User::new("the-pr-owner"),
+    });
+    test.handler.data.deployment_status = Some(DeploymentStatus {
+        state: "failure".into(),
+        description: None,
+        target_url: Some("http://the-logs".into()),
+        environment_url: None,
+    });
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/deployed-repo", "the-reviews-channel")
+            .with_deployment_channels(vec!["production the-deploys".into()]),
     );
-    test.github.mock_get_pull_request_labels(
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/deployed-repo", test.github.github_host())).unwrap();
+
+    test.github.mock_get_pull_requests(
         "some-user",
-        "some-repo",
-        32,
-        Ok(vec![
-            Label::new("other"),
-            Label::new("backport-1.0"),
-            Label::new("BACKPORT-2.0"),
-            Label::new("BACKport-other-thing"),
-            Label::new("non-matching"),
-        ]),
+        "deployed-repo",
+        Some("open".into()),
+        None,
+        Ok(vec![some_pr().unwrap()]),
     );
 
+    let repo_msg = "(<http://the-github-host/some-user/deployed-repo|some-user/deployed-repo>)";
+    let text = "pr-branch at <http://the-github-host/some-user/deployed-repo/commit/ffff0000|ffff000>\n\
+                Pull requests: <http://the-pr|#32: The PR>\n\
+                <http://the-logs|Logs>";
     test.slack.expect(vec![
-        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
-        slack::req("@the.pr.owner", msg, attach.clone()),
-        slack::req("@assign1", msg, attach.clone()),
-        slack::req("@bob.author", msg, attach.clone()),
-        slack::req("@joe.reviewer", msg, attach.clone()),
+        slack::req(
+            "the-deploys",
+            &format!("Deployment of pr-branch to production failed {}", repo_msg),
+            vec![SlackAttachmentBuilder::new(text).color("danger").build()],
+        ),
     ]);
 
-    let expect_thread = test.expect_will_merge_branches(
-        vec!["release/1.0".into(), "release/2.0".into(), "release/other-thing".into()],
-    );
-
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+    assert_eq!((StatusCode::Ok, "deployment_status".into()), resp);
+}
 
-    expect_thread.join().unwrap();
+fn some_workflow_run(conclusion: &str) -> WorkflowRun {
+    WorkflowRun {
+        id: 345,
+        name: "CI".into(),
+        run_number: 12,
+        head_branch: Some("master".into()),
+        head_sha: "ffff0000aaaa".into(),
+        conclusion: Some(conclusion.into()),
+        html_url: "http://the-run".into(),
+        actor: Some(User::new("the-pr-owner")),
+    }
 }
 
 #[test]
-fn test_pull_request_merged_backport_labels_custom_pattern() {
+fn test_workflow_run_failed() {
     let mut test = new_test();
-    test.handler.event = "pull_request".into();
-    test.handler.action = "closed".into();
-    test.handler.data.pull_request = some_pr();
-    if let Some(ref mut pr) = test.handler.data.pull_request {
-        pr.merged = Some(true);
-    }
-    test.handler.data.sender = User::new("the-pr-merger");
+    test.handler.event = "workflow_run".into();
+    test.handler.action = "completed".into();
+    test.handler.data.workflow_run = Some(some_workflow_run("failure"));
 
     test.config.repos_write().insert_info(
         test.github.github_host(),
-        repos::RepoInfo::new("some-user/custom-branches-repo", "the-reviews-channel")
-            .with_release_branch_prefix(Some("the-other-prefix-".into())),
+        repos::RepoInfo::new("some-user/ci-repo", "the-reviews-channel").with_watched_workflows(vec!["CI".into()]),
     );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/ci-repo", test.github.github_host())).unwrap();
 
-    // change the repo to an unconfigured one
-    test.handler.data.repository = Repo::parse(
-        &format!("http://{}/some-user/custom-branches-repo", test.github.github_host()),
-    ).unwrap();
+    test.github.mock_is_branch_protected("some-user", "ci-repo", "master", Ok(true));
 
+    let repo_msg = "(<http://the-github-host/some-user/ci-repo|some-user/ci-repo>)";
+    let text = "master at <http://the-github-host/some-user/ci-repo/commit/ffff0000aaaa|ffff000>";
     let attach = vec![
-        SlackAttachmentBuilder::new("")
-            .title("Pull Request #32: \"The PR\"")
-            .title_link("http://the-pr")
+        SlackAttachmentBuilder::new(text)
+            .title("CI #12")
+            .title_link("http://the-run")
+            .color("danger")
             .build(),
     ];
-    let msg = "Pull Request merged";
+    let msg = "Workflow CI failed on master";
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, repo_msg), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+    ]);
 
-    test.github.mock_get_pull_request_commits(
-        "some-user",
-        "custom-branches-repo",
-        32,
-        Ok(some_commits()),
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "workflow_run".into()), resp);
+}
+
+#[test]
+fn test_workflow_run_back_to_green() {
+    let mut test = new_test();
+    test.handler.event = "workflow_run".into();
+    test.handler.action = "completed".into();
+    test.handler.data.workflow_run = Some(some_workflow_run("success"));
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/ci-repo", "the-reviews-channel").with_watched_workflows(vec!["CI".into()]),
     );
-    test.github.mock_get_pull_request_labels(
-        "some-user",
-        "custom-branches-repo",
-        32,
-        Ok(vec![
-            Label::new("other"),
-            Label::new("backport-1.0"),
-            Label::new("BACKPORT-2.0"),
-            Label::new("BACKport-other-thing"),
-            Label::new("non-matching"),
-        ]),
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/ci-repo", test.github.github_host())).unwrap();
+    let repo = test.handler.data.repository.clone();
+    test.handler.failing_workflows.write(|f| f.update(&repo, "CI", "master", true));
+
+    test.github.mock_is_branch_protected("some-user", "ci-repo", "master", Ok(true));
+
+    let repo_msg = "(<http://the-github-host/some-user/ci-repo|some-user/ci-repo>)";
+    let text = "master at <http://the-github-host/some-user/ci-repo/commit/ffff0000aaaa|ffff000>";
+    test.slack.expect(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("Workflow CI is back to green on master {}", repo_msg),
+            vec![
+                SlackAttachmentBuilder::new(text)
+                    .title("CI #12")
+                    .title_link("http://the-run")
+                    .color("good")
+                    .build(),
+            ],
+        ),
+    ]);
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "workflow_run".into()), resp);
+    assert_eq!(0, test.handler.failing_workflows.read(|f| f.len()));
+}
+
+fn new_broken_branch_test(conclusion: &str) -> (GithubHandlerTest, Arc<MockPagerDuty>) {
+    let mut test = new_test();
+    test.handler.event = "workflow_run".into();
+    test.handler.action = "completed".into();
+    test.handler.data.workflow_run = Some(some_workflow_run(conclusion));
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/ci-repo", "the-reviews-channel")
+            .with_watched_workflows(vec!["CI".into()])
+            .with_broken_branch_escalation(3),
     );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/ci-repo", test.github.github_host())).unwrap();
+    test.handler.data.repository.default_branch = Some("master".into());
+    let repo = test.handler.data.repository.clone();
+    test.handler.failing_workflows.write(|f| {
+        f.update(&repo, "CI", "master", true);
+        f.update(&repo, "CI", "master", true);
+    });
 
-    let repo_msg = "(<http://the-github-host/some-user/custom-branches-repo|some-user/custom-branches-repo>)";
+    let pagerduty = Arc::new(MockPagerDuty::new());
+    test.handler.pagerduty_session = Some(pagerduty.clone());
+
+    test.github.mock_is_branch_protected("some-user", "ci-repo", "master", Ok(true));
+    (test, pagerduty)
+}
+
+#[test]
+fn test_workflow_run_escalates_broken_branch() {
+    let (mut test, pagerduty) = new_broken_branch_test("failure");
 
+    let repo_msg = "(<http://the-github-host/some-user/ci-repo|some-user/ci-repo>)";
+    let text = "master at <http://the-github-host/some-user/ci-repo/commit/ffff0000aaaa|ffff000>";
+    let attach = vec![
+        SlackAttachmentBuilder::new(text)
+            .title("CI #12")
+            .title_link("http://the-run")
+            .color("danger")
+            .build(),
+    ];
+    let msg = "Workflow CI failed on master";
     test.slack.expect(vec![
         slack::req("the-reviews-channel", &format!("{} {}", msg, repo_msg), attach.clone()),
         slack::req("@the.pr.owner", msg, attach.clone()),
-        slack::req("@assign1", msg, attach.clone()),
-        slack::req("@bob.author", msg, attach.clone()),
-        slack::req("@joe.reviewer", msg, attach.clone()),
+        slack::req(
+            "the-reviews-channel",
+            &format!("<!here> CI has failed 3 times in a row on master {}", repo_msg),
+            attach.clone(),
+        ),
     ]);
+    pagerduty.mock_trigger(
+        "octobot some-user/ci-repo CI master",
+        "some-user/ci-repo: CI has failed 3 times in a row on master",
+        "http://the-github-host/some-user/ci-repo",
+        "http://the-run",
+        Ok(()),
+    );
 
-    let expect_thread = test.expect_will_merge_branches(vec![
-        "the-other-prefix-1.0".into(),
-        "the-other-prefix-2.0".into(),
-        "the-other-prefix-other-thing".into(),
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "workflow_run".into()), resp);
+}
+
+#[test]
+fn test_workflow_run_resolves_broken_branch() {
+    let (mut test, pagerduty) = new_broken_branch_test("success");
+    let repo = test.handler.data.repository.clone();
+    test.handler.failing_workflows.write(|f| f.update(&repo, "CI", "master", true));
+
+    let repo_msg = "(<http://the-github-host/some-user/ci-repo|some-user/ci-repo>)";
+    let text = "master at <http://the-github-host/some-user/ci-repo/commit/ffff0000aaaa|ffff000>";
+    test.slack.expect(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("Workflow CI is back to green on master {}", repo_msg),
+            vec![
+                SlackAttachmentBuilder::new(text)
+                    .title("CI #12")
+                    .title_link("http://the-run")
+                    .color("good")
+                    .build(),
+            ],
+        ),
     ]);
+    pagerduty.mock_resolve("octobot some-user/ci-repo CI master", Ok(()));
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+    assert_eq!((StatusCode::Ok, "workflow_run".into()), resp);
+}
 
-    expect_thread.join().unwrap();
+#[test]
+fn test_workflow_run_unprotected_branch() {
+    let mut test = new_test();
+    test.handler.event = "workflow_run".into();
+    test.handler.action = "completed".into();
+    test.handler.data.workflow_run = Some(some_workflow_run("failure"));
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/ci-repo", "the-reviews-channel").with_watched_workflows(vec!["CI".into()]),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/ci-repo", test.github.github_host())).unwrap();
+
+    test.github.mock_is_branch_protected("some-user", "ci-repo", "master", Ok(false));
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "workflow_run".into()), resp);
 }
 
 #[test]
-fn test_pull_request_merged_retroactively_labeled() {
+fn test_repository_created() {
     let mut test = new_test();
-    test.handler.event = "pull_request".into();
-    test.handler.action = "labeled".into();
-    test.handler.data.pull_request = some_pr();
-    if let Some(ref mut pr) = test.handler.data.pull_request {
-        pr.merged = Some(true);
-    }
-    test.handler.data.label = Some(Label::new("backport-7.123"));
-    test.handler.data.sender = User::new("the-pr-merger");
+    test.handler.event = "repository".into();
+    test.handler.action = "created".into();
 
-    let expect_thread = test.expect_will_merge_branches(vec!["release/7.123".into()]);
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user", "the-reviews-channel").with_admin_channel("the-admins"),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/new-repo", test.github.github_host())).unwrap();
+
+    let repo_msg = "(<http://the-github-host/some-user/new-repo|some-user/new-repo>)";
+    test.slack.expect(vec![
+        slack::req("the-admins", &format!("Repository created by joe.sender {}", repo_msg), vec![]),
+    ]);
 
     let resp = test.handler.handle_event().unwrap();
-    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+    assert_eq!((StatusCode::Ok, "repository".into()), resp);
+}
 
-    expect_thread.join().unwrap();
+#[test]
+fn test_repository_renamed() {
+    let mut test = new_test();
+    test.handler.event = "repository".into();
+    test.handler.action = "renamed".into();
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/new-name", test.github.github_host())).unwrap();
+    test.handler.data.changes = Some(Changes {
+        repository: Some(RepositoryChanges { name: Some(ChangedFrom { from: "old-name".into() }) }),
+        ..Changes::default()
+    });
+
+    // renaming saves the repos config, so it needs files to save it to
+    let dir = TempDir::new("github_handler_test.rs").expect("create temp dir for github_handler_test.rs");
+    let users_file = dir.path().join("users.json");
+    fs::File::create(&users_file).unwrap().write_all(b"{}").unwrap();
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user", "the-reviews-channel").with_admin_channel("the-admins"),
+    );
+    repos.insert(test.github.github_host(), "some-user/old-name", "the-old-name-channel");
+    let mut config = Config::new(UserConfig::new(), repos);
+    config.main.users_config_file = users_file.to_string_lossy().into_owned();
+    config.main.repos_config_file = dir.path().join("repos.json").to_string_lossy().into_owned();
+    let config = Arc::new(config);
+    test.handler.config = config.clone();
+    test.handler.messenger = messenger::new(config.clone(), test.slack.new_sender());
+
+    let repo_msg = "(<http://the-github-host/some-user/new-name|some-user/new-name>)";
+    test.slack.expect(vec![
+        slack::req(
+            "the-admins",
+            &format!("Repository renamed from some-user/old-name by joe.sender {}", repo_msg),
+            vec![SlackAttachmentBuilder::new("Updated the repos config from some-user/old-name").build()],
+        ),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "repository".into()), resp);
+    assert_eq!(
+        Some("the-old-name-channel".into()),
+        config.repos().lookup_channel(&test.handler.data.repository, None)
+    );
 }
 
 #[test]
@@ -1007,6 +3622,118 @@ fn test_push_no_pr() {
     assert_eq!((StatusCode::Ok, "push".into()), resp);
 }
 
+#[test]
+fn test_push_ignored() {
+    let mut test = new_test();
+    test.handler.event = "push".into();
+    test.handler.data.ref_name = Some("refs/heads/renovate/serde-1.x".into());
+    test.handler.data.before = Some("abcdef0000".into());
+    test.handler.data.after = Some("1111abcdef".into());
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/noisy-repo", "the-reviews-channel")
+            .with_push_ignore_branches(vec!["renovate/*".into()]),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/noisy-repo", test.github.github_host())).unwrap();
+
+    // no PR lookup, so no messages
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "push".into()), resp);
+}
+
+#[test]
+fn test_push_ignores_bots() {
+    let mut test = new_test();
+    test.handler.event = "push".into();
+    test.handler.data.ref_name = Some("refs/heads/dependabot/cargo/serde-1.0.2".into());
+    test.handler.data.before = Some("abcdef0000".into());
+    test.handler.data.after = Some("1111abcdef".into());
+    test.handler.data.sender = User::new("dependabot[bot]");
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "push".into()), resp);
+}
+
+#[test]
+fn test_push_ignored_author() {
+    let mut test = new_test();
+    test.handler.event = "push".into();
+    test.handler.data.ref_name = Some("refs/heads/some-branch".into());
+    test.handler.data.before = Some("abcdef0000".into());
+    test.handler.data.after = Some("1111abcdef".into());
+    test.handler.data.sender = User::new("dependabot[bot]");
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/noisy-repo", "the-reviews-channel")
+            .with_push_ignore_authors(vec!["*[bot]".into()]),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/noisy-repo", test.github.github_host())).unwrap();
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "push".into()), resp);
+}
+
+fn new_direct_push_test() -> GithubHandlerTest {
+    let mut test = new_test();
+    test.handler.event = "push".into();
+    test.handler.data.ref_name = Some("refs/heads/master".into());
+    test.handler.data.before = Some("the-before-commit".into());
+    test.handler.data.after = Some("the-after-commit".into());
+    test.handler.data.commits = Some(vec![
+        PushCommit {
+            id: "aaaaaa000000".into(),
+            tree_id: "".into(),
+            message: "hotfix".into(),
+            url: "http://commit1".into(),
+        },
+    ]);
+
+    test.config.repos_write().insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/pushed-repo", "the-reviews-channel")
+            .with_security_channel("the-security")
+            .with_direct_push_branches(vec!["master".into()]),
+    );
+    test.handler.data.repository =
+        Repo::parse(&format!("http://{}/some-user/pushed-repo", test.github.github_host())).unwrap();
+    test
+}
+
+#[test]
+fn test_push_direct_to_protected_branch() {
+    let mut test = new_direct_push_test();
+
+    test.github.mock_get_pull_requests_for_commit("some-user", "pushed-repo", "the-after-commit", Ok(vec![]));
+
+    let repo_msg = "(<http://the-github-host/some-user/pushed-repo|some-user/pushed-repo>)";
+    test.slack.expect(vec![
+        slack::req(
+            "the-security",
+            &format!("joe.sender pushed 1 commit(s) directly to master {}", repo_msg),
+            vec![SlackAttachmentBuilder::new("<http://commit1|aaaaaa0>: hotfix").build()],
+        ),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "push".into()), resp);
+}
+
+#[test]
+fn test_push_merged_pr_to_protected_branch() {
+    let test = new_direct_push_test();
+
+    let mut pr = some_pr().unwrap();
+    pr.state = "closed".into();
+    test.github.mock_get_pull_requests_for_commit("some-user", "pushed-repo", "the-after-commit", Ok(vec![pr]));
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "push".into()), resp);
+}
+
 #[test]
 fn test_push_with_pr() {
     let mut test = new_test();
@@ -1102,6 +3829,134 @@ fn test_push_with_pr() {
     assert_eq!((StatusCode::Ok, "push".into()), resp);
 }
 
+#[test]
+fn test_push_many_commits() {
+    let mut test = new_test();
+    test.handler.event = "push".into();
+    test.handler.data.ref_name = Some("refs/heads/some-branch".into());
+    test.handler.data.before = Some("the-before-commit".into());
+    test.handler.data.after = Some("the-after-commit".into());
+    test.handler.data.compare = Some("http://compare-url".into());
+
+    let commits: Vec<_> = (0..47)
+        .map(|i| {
+            PushCommit {
+                id: format!("{:07}abc", i),
+                tree_id: "".into(),
+                message: format!("change {}", i),
+                url: format!("http://commit{}", i),
+            }
+        })
+        .collect();
+    test.handler.data.commits = Some(commits);
+
+    let mut pr = some_pr().unwrap();
+    pr.head.sha = "the-after-commit".into();
+    pr.requested_reviewers = None;
+    test.github.mock_get_pull_request_commits("some-user", "some-repo", 32, Ok(vec![]));
+    test.github.mock_get_pull_requests(
+        "some-user",
+        "some-repo",
+        Some("open".into()),
+        None,
+        Ok(vec![pr]),
+    );
+
+    let msg = "joe.sender pushed 47 commit(s) to branch some-branch";
+    let mut attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    for i in 0..20 {
+        let text = format!("<http://commit{}|{:07}>: change {}", i, i, i);
+        attach.push(SlackAttachmentBuilder::new(&text).build());
+    }
+    attach.push(SlackAttachmentBuilder::new("<http://compare-url|…and 27 more commits>").build());
+
+    test.slack.expect(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+        slack::req("@assign1", msg, attach.clone()),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "push".into()), resp);
+}
+
+#[test]
+fn test_push_checks_conflicts() {
+    let mut test = new_test();
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_notify_conflicts(true),
+    );
+    test.handler.config = Arc::new(Config::new(UserConfig::new(), repos));
+    test.handler.event = "push".into();
+    test.handler.data.ref_name = Some("refs/heads/master".into());
+    test.handler.data.before = Some("abcdef0000".into());
+    test.handler.data.after = Some("1111abcdef".into());
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "push".into()), resp);
+
+    let rx = test.conflicts_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("conflicts message") {
+        WorkMessage::WorkItem(req) => assert_eq!("master", req.branch),
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
+}
+
+#[test]
+fn test_push_keeps_prs_updated() {
+    let mut test = new_test();
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_keep_updated(true),
+    );
+    test.handler.config = Arc::new(Config::new(UserConfig::new(), repos));
+    test.handler.event = "push".into();
+    test.handler.data.ref_name = Some("refs/heads/master".into());
+    test.handler.data.before = Some("abcdef0000".into());
+    test.handler.data.after = Some("1111abcdef".into());
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "push".into()), resp);
+
+    let rx = test.branch_updates_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("branch update message") {
+        WorkMessage::WorkItem(BranchUpdateRequest::Branch(_, branch)) => assert_eq!("master", branch),
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
+}
+
+#[test]
+fn test_pull_request_labeled_keep_updated() {
+    let mut test = new_test();
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        test.github.github_host(),
+        repos::RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_keep_updated(true),
+    );
+    test.handler.config = Arc::new(Config::new(UserConfig::new(), repos));
+    test.handler.event = "pull_request".into();
+    test.handler.action = "labeled".into();
+    test.handler.data.pull_request = some_pr();
+    test.handler.data.label = Some(Label::new(branch_updates::LABEL));
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "pr".into()), resp);
+
+    let rx = test.branch_updates_rx.take().unwrap();
+    match rx.recv_timeout(Duration::from_millis(300)).expect("branch update message") {
+        WorkMessage::WorkItem(BranchUpdateRequest::PullRequest(_, pr)) => assert_eq!(32, pr.number),
+        msg => panic!("Unexpected message: {:?}", msg),
+    };
+}
+
 #[test]
 fn test_push_force_notify() {
     let mut test = new_test();
@@ -1323,6 +4178,43 @@ fn some_jira_push_commits() -> Vec<PushCommit> {
         },
     ]
 }
+#[test]
+fn test_jira_commit_comment_links_keys() {
+    let mut test = new_test_with_jira();
+    test.handler.event = "commit_comment".into();
+    test.handler.action = "created".into();
+    test.handler.data.comment = Some(Comment {
+        commit_id: Some("abcdef00001111".into()),
+        path: None,
+        body: Some("Is this for SER-12? Not for OTHER-1, or `CLI-3`".into()),
+        html_url: "http://the-comment".into(),
+        user: User::new("joe-reviewer"),
+        pull_request_review_id: None,
+        diff_hunk: None,
+        line: None,
+    });
+    test.handler.data.sender = User::new("joe-reviewer");
+    test.github.mock_get_commit("some-user", "some-repo", "abcdef00001111", Ok(some_commit("joe-reviewer")));
+
+    let msg = "Comment on \"abcdef0\" (<http://the-github-host/some-user/some-repo/commit/abcdef00001111|abcdef0>)";
+    test.slack.expect(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("{} {}", msg, REPO_MSG),
+            vec![
+                SlackAttachmentBuilder::new(
+                    "Is this for <https://the-jira-host/browse/SER-12|SER-12>? Not for OTHER-1, or `CLI-3`",
+                ).title("joe.reviewer said:")
+                    .title_link("http://the-comment")
+                    .build(),
+            ],
+        ),
+    ]);
+
+    let resp = test.handler.handle_event().unwrap();
+    assert_eq!((StatusCode::Ok, "commit_comment".into()), resp);
+}
+
 #[test]
 fn test_jira_pull_request_opened() {
     let mut test = new_test_with_jira();
@@ -1339,9 +4231,11 @@ fn test_jira_pull_request_opened() {
     );
 
     let attach = vec![
-        SlackAttachmentBuilder::new("")
+        SlackAttachmentBuilder::new("The body")
             .title("Pull Request #32: \"The PR\"")
             .title_link("http://the-pr")
+            .field("Branch", "master ← pr-branch")
+            .field("Author", "the.pr.owner")
             .build(),
     ];
     let msg = "Pull Request opened by the.pr.owner";
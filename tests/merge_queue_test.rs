@@ -0,0 +1,158 @@
+extern crate octobot;
+
+mod mocks;
+
+use std::sync::Arc;
+
+use octobot::config::Config;
+use octobot::github;
+use octobot::json_store::JsonStore;
+use octobot::merge_queue::{self, MergeQueue, QueueEntry};
+use octobot::messenger;
+use octobot::repos::RepoConfig;
+use octobot::slack::{self, SlackAttachmentBuilder};
+use octobot::users::UserConfig;
+
+use mocks::mock_github::MockGithub;
+use mocks::mock_slack::MockSlack;
+
+const REPO_MSG: &'static str = "(<http://git.foo.com/some-user/some-repo|some-user/some-repo>)";
+
+fn the_repo() -> github::Repo {
+    github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap()
+}
+
+fn new_config() -> Arc<Config> {
+    let mut repos = RepoConfig::new();
+    repos.insert("git.foo.com", "some-user/some-repo", "the-reviews-channel");
+    Arc::new(Config::new(UserConfig::new(), repos))
+}
+
+fn the_pr(number: u32) -> github::PullRequest {
+    let mut pr = github::PullRequest::new();
+    pr.number = number;
+    pr.title = format!("PR {}", number);
+    pr.html_url = format!("http://the-pr/{}", number);
+    pr.state = "open".into();
+    pr.user = github::User::new("the-pr-owner");
+    pr.head.sha = format!("abcdef{}", number);
+    pr.base.ref_name = "master".into();
+    pr.mergeable = Some(true);
+    pr.mergeable_state = Some("clean".into());
+    pr
+}
+
+fn combined_status(state: &str, sha: &str) -> github::CombinedStatus {
+    github::CombinedStatus {
+        state: state.into(),
+        sha: sha.into(),
+        statuses: vec![
+            github::Status {
+                state: state.into(),
+                target_url: None,
+                context: Some("ci/build".into()),
+                description: None,
+                creator: None,
+            },
+        ],
+    }
+}
+
+fn new_queue(prs: Vec<u32>) -> JsonStore<MergeQueue> {
+    let queue: JsonStore<MergeQueue> = JsonStore::in_memory();
+    for number in prs {
+        queue.write(|q| q.add(&the_repo(), "master", QueueEntry::new(&the_pr(number))));
+    }
+    queue
+}
+
+#[test]
+fn test_merge_queue_merges_green_pr() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("Merge queue for master updated {}", REPO_MSG),
+            vec![
+                SlackAttachmentBuilder::new("")
+                    .title("1. Pull Request #33: \"PR 33\"")
+                    .title_link("http://the-pr/33")
+                    .build(),
+            ],
+        ),
+    ]);
+    let messenger = messenger::new(new_config(), slack.new_sender());
+    let queue = new_queue(vec![32, 33]);
+
+    github.get_pull_request("some-user", "some-repo", 32, Ok(the_pr(32)));
+    github.mock_get_combined_status("some-user", "some-repo", "abcdef32", Ok(combined_status("success", "abcdef32")));
+    github.mock_merge_pull_request("some-user", "some-repo", 32, "abcdef32", "merge", Ok(()));
+
+    // next one is still building
+    github.get_pull_request("some-user", "some-repo", 33, Ok(the_pr(33)));
+    github.mock_get_combined_status("some-user", "some-repo", "abcdef33", Ok(combined_status("pending", "abcdef33")));
+
+    merge_queue::process_queue(&github, &queue, &*messenger, &the_repo(), "master", "merge");
+
+    assert_eq!(vec![33], queue.read(|q| q.entries(&the_repo(), "master")).iter().map(|e| e.number).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_merge_queue_updates_behind_pr() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![]);
+    let messenger = messenger::new(new_config(), slack.new_sender());
+    let queue = new_queue(vec![32]);
+
+    let mut pr = the_pr(32);
+    pr.mergeable_state = Some("behind".into());
+    github.get_pull_request("some-user", "some-repo", 32, Ok(pr));
+    github.mock_update_pull_request_branch("some-user", "some-repo", 32, "abcdef32", Ok(()));
+
+    merge_queue::process_queue(&github, &queue, &*messenger, &the_repo(), "master", "merge");
+
+    assert_eq!(1, queue.read(|q| q.entries(&the_repo(), "master").len()));
+}
+
+#[test]
+fn test_merge_queue_drops_failed_pr() {
+    let github = MockGithub::new();
+
+    let msg = "Pull Request removed from merge queue for master";
+    let attach = vec![
+        SlackAttachmentBuilder::new("Commit status is failure")
+            .title("Pull Request #32: \"PR 32\"")
+            .title_link("http://the-pr/32")
+            .color("danger")
+            .build(),
+    ];
+    let slack = MockSlack::new(vec![
+        slack::req("the-reviews-channel", &format!("{} {}", msg, REPO_MSG), attach.clone()),
+        slack::req("@the.pr.owner", msg, attach.clone()),
+    ]);
+    let messenger = messenger::new(new_config(), slack.new_sender());
+    let queue = new_queue(vec![32]);
+
+    github.get_pull_request("some-user", "some-repo", 32, Ok(the_pr(32)));
+    github.mock_get_combined_status("some-user", "some-repo", "abcdef32", Ok(combined_status("failure", "abcdef32")));
+
+    merge_queue::process_queue(&github, &queue, &*messenger, &the_repo(), "master", "merge");
+
+    assert_eq!(0, queue.read(|q| q.entries(&the_repo(), "master").len()));
+}
+
+#[test]
+fn test_merge_queue_drops_closed_pr() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![]);
+    let messenger = messenger::new(new_config(), slack.new_sender());
+    let queue = new_queue(vec![32]);
+
+    let mut pr = the_pr(32);
+    pr.state = "closed".into();
+    github.get_pull_request("some-user", "some-repo", 32, Ok(pr));
+
+    merge_queue::process_queue(&github, &queue, &*messenger, &the_repo(), "master", "merge");
+
+    assert_eq!(0, queue.read(|q| q.entries(&the_repo(), "master").len()));
+}
@@ -3,13 +3,18 @@ extern crate octobot;
 mod mocks;
 
 use std::sync::Arc;
+use std::sync::mpsc::channel;
 
 use octobot::config::Config;
+use octobot::digest;
 use octobot::github;
-use octobot::messenger::{self, Messenger};
-use octobot::repos::RepoConfig;
+use octobot::messenger::{self, Messenger, MessengerFactory, Reason};
+use octobot::quiet_hours;
+use octobot::repos::{EventFilter, RepoConfig, RepoInfo};
 use octobot::slack;
-use octobot::users::UserConfig;
+use octobot::users::{UserConfig, UserInfo};
+use octobot::webhooks::{OutboundEvent, PullRequestRef, WebhookRequest};
+use octobot::worker::{WorkMessage, WorkSender};
 
 use mocks::mock_slack::MockSlack;
 
@@ -27,6 +32,8 @@ fn test_sends_to_owner() {
         &github::User::new("the-owner"),
         &github::User::new("the-sender"),
         &github::Repo::new(),
+        None,
+        &vec![],
         &vec![],
     );
 }
@@ -46,6 +53,8 @@ fn test_sends_to_mapped_usernames() {
         &github::User::new("the-owner"),
         &github::User::new("the-sender"),
         &github::Repo::parse("http://git.foo.com/some-org/some-repo").unwrap(),
+        None,
+        &vec![],
         &vec![],
     );
 }
@@ -75,10 +84,187 @@ fn test_sends_to_owner_with_channel() {
         &github::User::new("the-owner"),
         &github::User::new("the-sender"),
         &github::Repo::parse("http://git.foo.com/the-owner/the-repo").unwrap(),
+        None,
+        &vec![],
+        &vec![],
+    );
+}
+
+#[test]
+fn test_dry_run_repo() {
+    let mut repos = RepoConfig::new();
+    repos.insert_info("git.foo.com", RepoInfo::new("the-owner/the-repo", "the-review-channel").with_dry_run(true));
+    let config = Arc::new(Config::new(UserConfig::new(), repos));
+
+    let mut channel_msg = slack::req(
+        "the-review-channel",
+        "hello there (<http://git.foo.com/the-owner/the-repo|the-owner/the-repo>)",
+        vec![],
+    );
+    channel_msg.dry_run = true;
+    let mut direct_msg = slack::req("@the.owner", "hello there", vec![]);
+    direct_msg.dry_run = true;
+    let slack = MockSlack::new(vec![channel_msg, direct_msg]);
+    let messenger = messenger::new(config, slack.new_sender());
+
+    messenger.send_to_owner(
+        "hello there",
         &vec![],
+        &github::User::new("the-owner"),
+        &github::Repo::parse("http://git.foo.com/the-owner/the-repo").unwrap(),
+        None,
     );
 }
 
+#[test]
+fn test_sends_to_branch_channel() {
+    let mut repos = RepoConfig::new();
+    repos.insert("git.foo.com", "the-owner/the-repo", "the-review-channel");
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("the-owner/the-repo", "the-release-channel").with_branches(vec!["release/*".into()]),
+    );
+    let config = Arc::new(Config::new(UserConfig::new(), repos));
+
+    let slack = MockSlack::new(vec![
+        slack::req(
+            "the-release-channel",
+            "hello there (<http://git.foo.com/the-owner/the-repo|the-owner/the-repo>)",
+            vec![],
+        ),
+        slack::req(
+            "the-review-channel",
+            "hello there (<http://git.foo.com/the-owner/the-repo|the-owner/the-repo>)",
+            vec![],
+        ),
+    ]);
+    let messenger = messenger::new(config, slack.new_sender());
+
+    let repo = github::Repo::parse("http://git.foo.com/the-owner/the-repo").unwrap();
+    messenger.send_to_channel("hello there", &vec![], &repo, Some("release/1.0"));
+    messenger.send_to_channel("hello there", &vec![], &repo, Some("master"));
+}
+
+#[test]
+fn test_direct_message_preferences() {
+    let mut users = UserConfig::new();
+    users.insert_info("git.foo.com", UserInfo::new("the-owner", "the.owner").with_direct_messages("none"));
+    users.insert_info("git.foo.com", UserInfo::new("assign1", "assign1").with_direct_messages("mentions"));
+    users.insert_info("git.foo.com", UserInfo::new("assign2", "assign2").with_direct_messages("reviews"));
+    users.insert_info("git.foo.com", UserInfo::new("mentioned", "mentioned").with_direct_messages("mentions"));
+    let config = Arc::new(Config::new(users, RepoConfig::new()));
+
+    let slack = MockSlack::new(vec![
+        slack::req("@mentioned", "hello there", vec![]),
+        slack::req("@assign2", "review this", vec![]),
+    ]);
+    let messenger = messenger::new(config, slack.new_sender());
+
+    let repo = github::Repo::parse("http://git.foo.com/the-owner/the-repo").unwrap();
+    messenger.send_to_all(
+        "hello there",
+        &vec![],
+        &github::User::new("the-owner"),
+        &github::User::new("the-sender"),
+        &repo,
+        None,
+        &vec![github::User::new("assign1"), github::User::new("assign2")],
+        &vec![github::User::new("mentioned")],
+    );
+    messenger.send_to_users(
+        "review this",
+        &vec![],
+        &vec![github::User::new("assign1"), github::User::new("assign2")],
+        &repo,
+        Reason::ReviewRequest,
+    );
+}
+
+// work hours are on weekdays only
+fn next_weekday(time: i64) -> i64 {
+    let mut time = time;
+    while !quiet_hours::is_weekday(time) {
+        time += 24 * 60 * 60;
+    }
+    time
+}
+
+#[test]
+fn test_holds_direct_messages_outside_work_hours() {
+    // working hours start in two hours
+    let start = (quiet_hours::now() / 60 + 120) * 60;
+    let (hour, minute) = ((start / 3600) % 24, (start / 60) % 60);
+    let hours = format!("{:02}:{:02}-{:02}:{:02}", hour, minute, (hour + 1) % 24, minute);
+
+    let mut users = UserConfig::new();
+    users.insert_info("git.foo.com", UserInfo::new("the-owner", "the.owner").with_work_hours(&hours, "UTC"));
+    let mut repos = RepoConfig::new();
+    repos.insert("git.foo.com", "the-owner/the-repo", "the-review-channel");
+    let config = Arc::new(Config::new(users, repos));
+
+    let slack = MockSlack::new(vec![
+        slack::req(
+            "the-review-channel",
+            "hello there (<http://git.foo.com/the-owner/the-repo|the-owner/the-repo>)",
+            vec![],
+        ),
+        slack::deferred_req("@the.owner", "hello there", vec![], next_weekday(start)),
+    ]);
+    let messenger = messenger::new(config, slack.new_sender());
+
+    messenger.send_to_owner(
+        "hello there",
+        &vec![],
+        &github::User::new("the-owner"),
+        &github::Repo::parse("http://git.foo.com/the-owner/the-repo").unwrap(),
+        None,
+    );
+}
+
+#[test]
+fn test_digests() {
+    let mut users = UserConfig::new();
+    users.insert_info("git.foo.com", UserInfo::new("the-owner", "the.owner").with_digest(true));
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("the-owner/the-repo", "the-review-channel").with_digest_events(vec!["comment".into()]),
+    );
+    let config = Arc::new(Config::new(users, repos));
+
+    let deliver_at = digest::next_delivery(&config, 0, quiet_hours::now());
+    let slack = MockSlack::new(vec![
+        slack::digest_req(
+            "the-review-channel",
+            "comment (<http://git.foo.com/the-owner/the-repo|the-owner/the-repo>)",
+            vec![],
+            deliver_at,
+        ),
+        slack::digest_req("@the.owner", "comment", vec![], deliver_at),
+        slack::req(
+            "the-review-channel",
+            "review (<http://git.foo.com/the-owner/the-repo|the-owner/the-repo>)",
+            vec![],
+        ),
+        slack::req("@the.owner", "review", vec![]),
+    ]);
+    let messenger = messenger::new(config, slack.new_sender());
+
+    let repo = github::Repo::parse("http://git.foo.com/the-owner/the-repo").unwrap();
+    for kind in vec!["comment", "review"] {
+        messenger.for_kind(kind).send_to_all(
+            kind,
+            &vec![],
+            &github::User::new("the-owner"),
+            &github::User::new("the-sender"),
+            &repo,
+            None,
+            &vec![],
+            &vec![],
+        );
+    }
+}
+
 #[test]
 fn test_sends_to_assignees() {
     let slack = MockSlack::new(vec![
@@ -93,7 +279,9 @@ fn test_sends_to_assignees() {
         &github::User::new("the-owner"),
         &github::User::new("the-sender"),
         &github::Repo::new(),
+        None,
         &vec![github::User::new("assign1"), github::User::new("assign2")],
+        &vec![],
     );
 }
 
@@ -109,7 +297,9 @@ fn test_does_not_send_to_event_sender() {
         &github::User::new("userA"),
         &github::User::new("userA"),
         &github::Repo::new(),
+        None,
         &vec![github::User::new("userA"), github::User::new("userB")],
+        &vec![],
     );
 }
 
@@ -127,7 +317,9 @@ fn test_sends_only_once() {
         &github::User::new("the-owner"),
         &github::User::new("the-sender"),
         &github::Repo::new(),
+        None,
         &vec![github::User::new("the-owner"), github::User::new("assign2")],
+        &vec![],
     );
 }
 
@@ -149,6 +341,207 @@ fn test_peace_and_quiet() {
         &github::User::new("the-owner"),
         &github::User::new("the-sender"),
         &github::Repo::parse("http://git.foo.com/the-owner/the-repo").unwrap(),
+        None,
         &vec![github::User::new("the-owner"), github::User::new("assign2")],
+        &vec![],
+    );
+}
+
+#[test]
+fn test_out_of_office() {
+    let mut users = UserConfig::new();
+    users.insert_info("git.foo.com", UserInfo::new("the-owner", "the.owner").with_out_of_office(Some("the-delegate")));
+    users.insert_info("git.foo.com", UserInfo::new("assign1", "assign1").with_out_of_office(None));
+    users.insert_info("git.foo.com", UserInfo::new("assign2", "assign2").with_out_of_office(Some("assign3")));
+    let config = Arc::new(Config::new(users, RepoConfig::new()));
+
+    // the owner's messages go to their delegate, assign1 has none, and assign3 gets theirs anyway
+    let slack = MockSlack::new(vec![
+        slack::req("@the.delegate", "For the.owner (out of office): hello there", vec![]),
+        slack::req("@assign3", "hello there", vec![]),
+    ]);
+    let messenger = messenger::new(config, slack.new_sender());
+
+    messenger.send_to_all(
+        "hello there",
+        &vec![],
+        &github::User::new("the-owner"),
+        &github::User::new("the-sender"),
+        &github::Repo::parse("http://git.foo.com/the-owner/the-repo").unwrap(),
+        None,
+        &vec![github::User::new("assign1"), github::User::new("assign2"), github::User::new("assign3")],
+        &vec![],
+    );
+}
+
+#[test]
+fn test_threads_pr_messages() {
+    let mut repos = RepoConfig::new();
+    repos.insert_info("git.foo.com", RepoInfo::new("the-owner/the-repo", "the-review-channel").with_pr_threads(true));
+    repos.insert("git.foo.com", "the-owner/other-repo", "the-review-channel");
+    let config = Arc::new(Config::new(UserConfig::new(), repos));
+
+    let mut channel_msg = slack::req(
+        "the-review-channel",
+        "hello there (<http://git.foo.com/the-owner/the-repo|the-owner/the-repo>)",
+        vec![],
+    );
+    channel_msg.thread = Some("http://the-pr".into());
+    let slack = MockSlack::new(vec![
+        channel_msg,
+        // direct messages and repos without threads aren't threaded
+        slack::req("@the.owner", "hello there", vec![]),
+        slack::req(
+            "the-review-channel",
+            "hello there (<http://git.foo.com/the-owner/other-repo|the-owner/other-repo>)",
+            vec![],
+        ),
+    ]);
+    let mut pull_request = github::PullRequest::new();
+    pull_request.html_url = "http://the-pr".into();
+    let messenger = messenger::new(config, slack.new_sender()).for_kind("push").for_pull_request(&pull_request);
+
+    messenger.send_to_owner(
+        "hello there",
+        &vec![],
+        &github::User::new("the-owner"),
+        &github::Repo::parse("http://git.foo.com/the-owner/the-repo").unwrap(),
+        Some("master"),
+    );
+    messenger.send_to_channel(
+        "hello there",
+        &vec![],
+        &github::Repo::parse("http://git.foo.com/the-owner/other-repo").unwrap(),
+        Some("master"),
+    );
+}
+
+#[test]
+fn test_reacts_to_pr_message() {
+    let mut repos = RepoConfig::new();
+    repos.insert_info("git.foo.com", RepoInfo::new("the-owner/the-repo", "the-review-channel").with_pr_threads(true));
+    let config = Arc::new(Config::new(UserConfig::new(), repos));
+
+    let mut channel_msg = slack::req(
+        "the-review-channel",
+        "approved (<http://git.foo.com/the-owner/the-repo|the-owner/the-repo>)",
+        vec![],
+    );
+    channel_msg.thread = Some("http://the-pr".into());
+    channel_msg.reaction = Some("white_check_mark".into());
+    let slack = MockSlack::new(vec![channel_msg]);
+    let mut pull_request = github::PullRequest::new();
+    pull_request.html_url = "http://the-pr".into();
+    let messenger = messenger::new(config, slack.new_sender()).for_pull_request(&pull_request);
+
+    messenger.with_reaction("white_check_mark").send_to_channel(
+        "approved",
+        &vec![],
+        &github::Repo::parse("http://git.foo.com/the-owner/the-repo").unwrap(),
+        None,
+    );
+}
+
+#[test]
+fn test_sends_to_webhooks() {
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("the-owner/the-repo", "the-review-channel")
+            .with_webhook_urls(vec!["https://dashboard/events".into()]),
+    );
+    let config = Arc::new(Config::new(UserConfig::new(), repos));
+
+    let slack = MockSlack::new(vec![
+        slack::req(
+            "the-review-channel",
+            "hello there (<http://git.foo.com/the-owner/the-repo|the-owner/the-repo>)",
+            vec![]
+        ),
+        slack::req("@the.owner", "hello there", vec![]),
+        slack::req("@the.owner", "just you", vec![]),
+    ]);
+    let (webhooks_tx, webhooks_rx) = channel();
+    let mut pull_request = github::PullRequest::new();
+    pull_request.number = 12;
+    pull_request.title = "The PR".into();
+    pull_request.user = github::User::new("the-owner");
+    let messenger = messenger::with_webhooks(
+        messenger::new(config.clone(), slack.new_sender()),
+        config,
+        WorkSender::new(webhooks_tx),
+        Some("opened"),
+        Some(&pull_request),
+    );
+
+    let repo = github::Repo::parse("http://git.foo.com/the-owner/the-repo").unwrap();
+    messenger.for_kind("pull_request").send_to_owner(
+        "hello there",
+        &vec![],
+        &github::User::new("the-owner"),
+        &repo,
+        Some("master"),
     );
+    // direct messages only don't go out
+    messenger.send_to_users("just you", &vec![], &vec![github::User::new("the-owner")], &repo, Reason::Mention);
+
+    let expected = WebhookRequest {
+        url: "https://dashboard/events".into(),
+        event: OutboundEvent {
+            repo: "the-owner/the-repo".into(),
+            repo_url: "http://git.foo.com/the-owner/the-repo".into(),
+            branch: Some("master".into()),
+            pull_request: Some(PullRequestRef {
+                number: 12,
+                title: "The PR".into(),
+                html_url: String::new(),
+                user: "the-owner".into(),
+            }),
+            kind: Some("pull_request".into()),
+            action: Some("opened".into()),
+            message: "hello there".into(),
+            attachments: vec![],
+        },
+    };
+    match webhooks_rx.try_recv() {
+        Ok(WorkMessage::WorkItem(req)) => assert_eq!(expected, req),
+        _ => panic!("Expected a webhook request"),
+    };
+    assert!(webhooks_rx.try_recv().is_err());
+}
+
+#[test]
+fn test_webhooks_follow_channel_filters() {
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("the-owner/the-repo", "the-review-channel")
+            .with_webhook_urls(vec!["https://dashboard/events".into()])
+            .with_notifications(Some(EventFilter::new(None, Some(vec!["comment".into()]))), None),
+    );
+    let config = Arc::new(Config::new(UserConfig::new(), repos));
+
+    let slack = MockSlack::new(vec![
+        slack::req(
+            "the-review-channel",
+            "approved (<http://git.foo.com/the-owner/the-repo|the-owner/the-repo>)",
+            vec![]
+        ),
+    ]);
+    let (webhooks_tx, webhooks_rx) = channel();
+    let messengers = MessengerFactory::new(config, slack.new_sender()).with_webhooks(WorkSender::new(webhooks_tx));
+    let messenger = messengers.new_messenger();
+
+    let repo = github::Repo::parse("http://git.foo.com/the-owner/the-repo").unwrap();
+    messenger.for_kind("comment").send_to_channel("muted", &vec![], &repo, Some("master"));
+    messenger.for_kind("review").send_to_channel("approved", &vec![], &repo, Some("master"));
+
+    match webhooks_rx.try_recv() {
+        Ok(WorkMessage::WorkItem(req)) => {
+            assert_eq!("approved", req.event.message);
+            assert_eq!(Some("review".into()), req.event.kind);
+        }
+        _ => panic!("Expected a webhook request"),
+    };
+    assert!(webhooks_rx.try_recv().is_err());
 }
@@ -9,19 +9,47 @@ pub struct MockGithub {
     user: User,
     host: String,
     token: String,
+    // where repos are cloned from instead of the host, e.g. a local bare repo
+    clone_url: Option<String>,
 
     get_pr_calls: Mutex<Vec<MockCall<PullRequest>>>,
     get_prs_calls: Mutex<Vec<MockCall<Vec<PullRequest>>>>,
     create_pr_calls: Mutex<Vec<MockCall<PullRequest>>>,
     get_pr_labels_calls: Mutex<Vec<MockCall<Vec<Label>>>>,
+    add_pr_labels_calls: Mutex<Vec<MockCall<()>>>,
+    remove_pr_label_calls: Mutex<Vec<MockCall<()>>>,
     get_pr_commits_calls: Mutex<Vec<MockCall<Vec<Commit>>>>,
     get_pr_reviews_calls: Mutex<Vec<MockCall<Vec<Review>>>>,
+    get_review_comments_calls: Mutex<Vec<MockCall<Vec<Comment>>>>,
     assign_pr_calls: Mutex<Vec<MockCall<AssignResponse>>>,
     comment_pr_calls: Mutex<Vec<MockCall<()>>>,
     create_branch_calls: Mutex<Vec<MockCall<()>>>,
     delete_branch_calls: Mutex<Vec<MockCall<()>>>,
     get_status_calls: Mutex<Vec<MockCall<Vec<Status>>>>,
     create_status_calls: Mutex<Vec<MockCall<()>>>,
+    get_combined_status_calls: Mutex<Vec<MockCall<CombinedStatus>>>,
+    get_commit_calls: Mutex<Vec<MockCall<Commit>>>,
+    compare_commits_calls: Mutex<Vec<MockCall<Comparison>>>,
+    merge_pr_calls: Mutex<Vec<MockCall<()>>>,
+    update_pr_branch_calls: Mutex<Vec<MockCall<()>>>,
+    request_review_calls: Mutex<Vec<MockCall<()>>>,
+    dismiss_review_calls: Mutex<Vec<MockCall<()>>>,
+    get_pr_files_calls: Mutex<Vec<MockCall<Vec<PullRequestFile>>>>,
+    get_pr_stats_calls: Mutex<Vec<MockCall<DiffStats>>>,
+    get_file_contents_calls: Mutex<Vec<MockCall<String>>>,
+    get_user_calls: Mutex<Vec<MockCall<User>>>,
+    get_user_permission_calls: Mutex<Vec<MockCall<String>>>,
+    get_team_members_calls: Mutex<Vec<MockCall<Vec<User>>>>,
+    set_milestone_calls: Mutex<Vec<MockCall<()>>>,
+    get_commits_between_calls: Mutex<Vec<MockCall<Vec<String>>>>,
+    create_release_calls: Mutex<Vec<MockCall<()>>>,
+    get_tags_calls: Mutex<Vec<MockCall<Vec<String>>>>,
+    is_branch_protected_calls: Mutex<Vec<MockCall<bool>>>,
+    protect_branch_calls: Mutex<Vec<MockCall<()>>>,
+    rerun_failed_jobs_calls: Mutex<Vec<MockCall<()>>>,
+    get_prs_for_commit_calls: Mutex<Vec<MockCall<Vec<PullRequest>>>>,
+    get_hooks_calls: Mutex<Vec<MockCall<Vec<Hook>>>>,
+    save_hook_calls: Mutex<Vec<MockCall<()>>>,
 }
 
 #[derive(Debug)]
@@ -45,21 +73,54 @@ impl MockGithub {
             user: User::new("octobot"),
             host: "the-github-host".to_string(),
             token: "the-github-token".to_string(),
+            clone_url: None,
 
             get_pr_calls: Mutex::new(vec![]),
             get_prs_calls: Mutex::new(vec![]),
             create_pr_calls: Mutex::new(vec![]),
             get_pr_labels_calls: Mutex::new(vec![]),
+            add_pr_labels_calls: Mutex::new(vec![]),
+            remove_pr_label_calls: Mutex::new(vec![]),
             get_pr_commits_calls: Mutex::new(vec![]),
             get_pr_reviews_calls: Mutex::new(vec![]),
+            get_review_comments_calls: Mutex::new(vec![]),
             assign_pr_calls: Mutex::new(vec![]),
             comment_pr_calls: Mutex::new(vec![]),
             create_branch_calls: Mutex::new(vec![]),
             delete_branch_calls: Mutex::new(vec![]),
             get_status_calls: Mutex::new(vec![]),
             create_status_calls: Mutex::new(vec![]),
+            get_combined_status_calls: Mutex::new(vec![]),
+            get_commit_calls: Mutex::new(vec![]),
+            compare_commits_calls: Mutex::new(vec![]),
+            merge_pr_calls: Mutex::new(vec![]),
+            update_pr_branch_calls: Mutex::new(vec![]),
+            request_review_calls: Mutex::new(vec![]),
+            dismiss_review_calls: Mutex::new(vec![]),
+            get_pr_files_calls: Mutex::new(vec![]),
+            get_pr_stats_calls: Mutex::new(vec![]),
+            get_file_contents_calls: Mutex::new(vec![]),
+            get_user_calls: Mutex::new(vec![]),
+            get_user_permission_calls: Mutex::new(vec![]),
+            get_team_members_calls: Mutex::new(vec![]),
+            set_milestone_calls: Mutex::new(vec![]),
+            get_commits_between_calls: Mutex::new(vec![]),
+            create_release_calls: Mutex::new(vec![]),
+            get_tags_calls: Mutex::new(vec![]),
+            is_branch_protected_calls: Mutex::new(vec![]),
+            protect_branch_calls: Mutex::new(vec![]),
+            rerun_failed_jobs_calls: Mutex::new(vec![]),
+            get_prs_for_commit_calls: Mutex::new(vec![]),
+            get_hooks_calls: Mutex::new(vec![]),
+            save_hook_calls: Mutex::new(vec![]),
         }
     }
+
+    pub fn with_clone_url(self, url: &str) -> MockGithub {
+        let mut github = self;
+        github.clone_url = Some(url.into());
+        github
+    }
 }
 
 impl Drop for MockGithub {
@@ -85,6 +146,21 @@ impl Drop for MockGithub {
                 "Unmet get_pull_request_labels calls: {:?}",
                 *self.get_pr_labels_calls.lock().unwrap()
             );
+            assert!(
+                self.add_pr_labels_calls.lock().unwrap().len() == 0,
+                "Unmet add_pull_request_labels calls: {:?}",
+                *self.add_pr_labels_calls.lock().unwrap()
+            );
+            assert!(
+                self.remove_pr_label_calls.lock().unwrap().len() == 0,
+                "Unmet remove_pull_request_label calls: {:?}",
+                *self.remove_pr_label_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_pr_stats_calls.lock().unwrap().len() == 0,
+                "Unmet get_pull_request_stats calls: {:?}",
+                *self.get_pr_stats_calls.lock().unwrap()
+            );
             assert!(
                 self.assign_pr_calls.lock().unwrap().len() == 0,
                 "Unmet assign_pull_request calls: {:?}",
@@ -95,6 +171,26 @@ impl Drop for MockGithub {
                 "Unmet comment_pull_request calls: {:?}",
                 *self.comment_pr_calls.lock().unwrap()
             );
+            assert!(
+                self.get_pr_reviews_calls.lock().unwrap().len() == 0,
+                "Unmet get_pull_request_reviews calls: {:?}",
+                *self.get_pr_reviews_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_review_comments_calls.lock().unwrap().len() == 0,
+                "Unmet get_review_comments calls: {:?}",
+                *self.get_review_comments_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_status_calls.lock().unwrap().len() == 0,
+                "Unmet get_statuses calls: {:?}",
+                *self.get_status_calls.lock().unwrap()
+            );
+            assert!(
+                self.create_status_calls.lock().unwrap().len() == 0,
+                "Unmet create_status calls: {:?}",
+                *self.create_status_calls.lock().unwrap()
+            );
             assert!(
                 self.create_branch_calls.lock().unwrap().len() == 0,
                 "Unmet create_branch calls: {:?}",
@@ -105,6 +201,116 @@ impl Drop for MockGithub {
                 "Unmet delete_branch calls: {:?}",
                 *self.delete_branch_calls.lock().unwrap()
             );
+            assert!(
+                self.get_combined_status_calls.lock().unwrap().len() == 0,
+                "Unmet get_combined_status calls: {:?}",
+                *self.get_combined_status_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_commit_calls.lock().unwrap().len() == 0,
+                "Unmet get_commit calls: {:?}",
+                *self.get_commit_calls.lock().unwrap()
+            );
+            assert!(
+                self.compare_commits_calls.lock().unwrap().len() == 0,
+                "Unmet compare_commits calls: {:?}",
+                *self.compare_commits_calls.lock().unwrap()
+            );
+            assert!(
+                self.merge_pr_calls.lock().unwrap().len() == 0,
+                "Unmet merge_pull_request calls: {:?}",
+                *self.merge_pr_calls.lock().unwrap()
+            );
+            assert!(
+                self.update_pr_branch_calls.lock().unwrap().len() == 0,
+                "Unmet update_pull_request_branch calls: {:?}",
+                *self.update_pr_branch_calls.lock().unwrap()
+            );
+            assert!(
+                self.request_review_calls.lock().unwrap().len() == 0,
+                "Unmet request_review calls: {:?}",
+                *self.request_review_calls.lock().unwrap()
+            );
+            assert!(
+                self.dismiss_review_calls.lock().unwrap().len() == 0,
+                "Unmet dismiss_review calls: {:?}",
+                *self.dismiss_review_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_pr_files_calls.lock().unwrap().len() == 0,
+                "Unmet get_pull_request_files calls: {:?}",
+                *self.get_pr_files_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_file_contents_calls.lock().unwrap().len() == 0,
+                "Unmet get_file_contents calls: {:?}",
+                *self.get_file_contents_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_user_calls.lock().unwrap().len() == 0,
+                "Unmet get_user calls: {:?}",
+                *self.get_user_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_user_permission_calls.lock().unwrap().len() == 0,
+                "Unmet get_user_permission calls: {:?}",
+                *self.get_user_permission_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_team_members_calls.lock().unwrap().len() == 0,
+                "Unmet get_team_members calls: {:?}",
+                *self.get_team_members_calls.lock().unwrap()
+            );
+            assert!(
+                self.set_milestone_calls.lock().unwrap().len() == 0,
+                "Unmet set_pull_request_milestone calls: {:?}",
+                *self.set_milestone_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_commits_between_calls.lock().unwrap().len() == 0,
+                "Unmet get_commits_between calls: {:?}",
+                *self.get_commits_between_calls.lock().unwrap()
+            );
+            assert!(
+                self.create_release_calls.lock().unwrap().len() == 0,
+                "Unmet create_release calls: {:?}",
+                *self.create_release_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_tags_calls.lock().unwrap().len() == 0,
+                "Unmet get_tags calls: {:?}",
+                *self.get_tags_calls.lock().unwrap()
+            );
+            assert!(
+                self.is_branch_protected_calls.lock().unwrap().len() == 0,
+                "Unmet is_branch_protected calls: {:?}",
+                *self.is_branch_protected_calls.lock().unwrap()
+            );
+            assert!(
+                self.protect_branch_calls.lock().unwrap().len() == 0,
+                "Unmet protect_branch calls: {:?}",
+                *self.protect_branch_calls.lock().unwrap()
+            );
+            assert!(
+                self.rerun_failed_jobs_calls.lock().unwrap().len() == 0,
+                "Unmet rerun_failed_jobs calls: {:?}",
+                *self.rerun_failed_jobs_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_prs_for_commit_calls.lock().unwrap().len() == 0,
+                "Unmet get_pull_requests_for_commit calls: {:?}",
+                *self.get_prs_for_commit_calls.lock().unwrap()
+            );
+            assert!(
+                self.get_hooks_calls.lock().unwrap().len() == 0,
+                "Unmet get_hooks calls: {:?}",
+                *self.get_hooks_calls.lock().unwrap()
+            );
+            assert!(
+                self.save_hook_calls.lock().unwrap().len() == 0,
+                "Unmet save_hook calls: {:?}",
+                *self.save_hook_calls.lock().unwrap()
+            );
         }
     }
 }
@@ -186,6 +392,30 @@ impl Session for MockGithub {
         call.ret
     }
 
+    fn add_pull_request_labels(&self, owner: &str, repo: &str, number: u32, labels: Vec<String>) -> Result<()> {
+        let mut calls = self.add_pr_labels_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to add_pull_request_labels");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], number.to_string());
+        assert_eq!(call.args[3], labels.join(","));
+
+        call.ret
+    }
+
+    fn remove_pull_request_label(&self, owner: &str, repo: &str, number: u32, label: &str) -> Result<()> {
+        let mut calls = self.remove_pr_label_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to remove_pull_request_label");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], number.to_string());
+        assert_eq!(call.args[3], label);
+
+        call.ret
+    }
+
     fn get_pull_request_commits(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<Commit>> {
         let mut calls = self.get_pr_commits_calls.lock().unwrap();
         assert!(calls.len() > 0, "Unexpected call to get_pull_request_commits");
@@ -208,6 +438,18 @@ impl Session for MockGithub {
         call.ret
     }
 
+    fn get_review_comments(&self, owner: &str, repo: &str, number: u32, review_id: u64) -> Result<Vec<Comment>> {
+        let mut calls = self.get_review_comments_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_review_comments");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], number.to_string());
+        assert_eq!(call.args[3], review_id.to_string());
+
+        call.ret
+    }
+
     fn assign_pull_request(
         &self,
         owner: &str,
@@ -283,6 +525,282 @@ impl Session for MockGithub {
 
         call.ret
     }
+
+    fn get_combined_status(&self, owner: &str, repo: &str, ref_name: &str) -> Result<CombinedStatus> {
+        let mut calls = self.get_combined_status_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_combined_status");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], ref_name);
+
+        call.ret
+    }
+
+    fn get_commit(&self, owner: &str, repo: &str, sha: &str) -> Result<Commit> {
+        let mut calls = self.get_commit_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_commit");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], sha);
+
+        call.ret
+    }
+
+    fn compare_commits(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<Comparison> {
+        let mut calls = self.compare_commits_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to compare_commits");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], base);
+        assert_eq!(call.args[3], head);
+
+        call.ret
+    }
+
+    fn merge_pull_request(&self, owner: &str, repo: &str, number: u32, sha: &str, merge_method: &str) -> Result<()> {
+        let mut calls = self.merge_pr_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to merge_pull_request");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], number.to_string());
+        assert_eq!(call.args[3], sha);
+        assert_eq!(call.args[4], merge_method);
+
+        call.ret
+    }
+
+    fn update_pull_request_branch(&self, owner: &str, repo: &str, number: u32, head_sha: &str) -> Result<()> {
+        let mut calls = self.update_pr_branch_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to update_pull_request_branch");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], number.to_string());
+        assert_eq!(call.args[3], head_sha);
+
+        call.ret
+    }
+
+    fn request_review(&self, owner: &str, repo: &str, number: u32, reviewers: Vec<String>) -> Result<()> {
+        let mut calls = self.request_review_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to request_review");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], number.to_string());
+        assert_eq!(call.args[3], reviewers.join(","));
+
+        call.ret
+    }
+
+    fn dismiss_review(&self, owner: &str, repo: &str, number: u32, review_id: u64, message: &str) -> Result<()> {
+        let mut calls = self.dismiss_review_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to dismiss_review");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], number.to_string());
+        assert_eq!(call.args[3], review_id.to_string());
+        assert_eq!(call.args[4], message);
+
+        call.ret
+    }
+
+    fn get_pull_request_files(&self, owner: &str, repo: &str, number: u32) -> Result<Vec<PullRequestFile>> {
+        let mut calls = self.get_pr_files_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_pull_request_files");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], number.to_string());
+
+        call.ret
+    }
+
+    fn get_pull_request_stats(&self, owner: &str, repo: &str, number: u32) -> Result<DiffStats> {
+        let mut calls = self.get_pr_stats_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_pull_request_stats");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], number.to_string());
+
+        call.ret
+    }
+
+    fn get_file_contents(&self, owner: &str, repo: &str, path: &str, ref_name: &str) -> Result<String> {
+        let mut calls = self.get_file_contents_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_file_contents");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], path);
+        assert_eq!(call.args[3], ref_name);
+
+        call.ret
+    }
+
+    fn get_user(&self, login: &str) -> Result<User> {
+        let mut calls = self.get_user_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_user");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], login);
+
+        call.ret
+    }
+
+    fn get_user_permission(&self, owner: &str, repo: &str, login: &str) -> Result<String> {
+        let mut calls = self.get_user_permission_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_user_permission");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], login);
+
+        call.ret
+    }
+
+    fn get_team_members(&self, org: &str, team: &str) -> Result<Vec<User>> {
+        let mut calls = self.get_team_members_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_team_members");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], org);
+        assert_eq!(call.args[1], team);
+
+        call.ret
+    }
+
+    fn set_pull_request_milestone(&self, owner: &str, repo: &str, number: u32, milestone: &str) -> Result<()> {
+        let mut calls = self.set_milestone_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to set_pull_request_milestone");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], number.to_string());
+        assert_eq!(call.args[3], milestone);
+
+        call.ret
+    }
+
+    fn get_commits_between(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<Vec<String>> {
+        let mut calls = self.get_commits_between_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_commits_between");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], base);
+        assert_eq!(call.args[3], head);
+
+        call.ret
+    }
+
+    fn create_release(&self, owner: &str, repo: &str, tag: &str, name: &str, body: &str) -> Result<()> {
+        let mut calls = self.create_release_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to create_release");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], tag);
+        assert_eq!(call.args[3], name);
+        assert_eq!(call.args[4], body);
+
+        call.ret
+    }
+
+    fn get_tags(&self, owner: &str, repo: &str) -> Result<Vec<String>> {
+        let mut calls = self.get_tags_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_tags");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+
+        call.ret
+    }
+
+    fn is_branch_protected(&self, owner: &str, repo: &str, branch: &str) -> Result<bool> {
+        let mut calls = self.is_branch_protected_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to is_branch_protected");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], branch);
+
+        call.ret
+    }
+
+    fn protect_branch(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        required_checks: &Vec<String>,
+        required_approvals: u32,
+    ) -> Result<()> {
+        let mut calls = self.protect_branch_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to protect_branch");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], branch);
+        assert_eq!(call.args[3], required_checks.join(","));
+        assert_eq!(call.args[4], required_approvals.to_string());
+
+        call.ret
+    }
+
+    fn rerun_failed_jobs(&self, owner: &str, repo: &str, run_id: u64) -> Result<()> {
+        let mut calls = self.rerun_failed_jobs_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to rerun_failed_jobs");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], run_id.to_string());
+
+        call.ret
+    }
+
+    fn get_pull_requests_for_commit(&self, owner: &str, repo: &str, sha: &str) -> Result<Vec<PullRequest>> {
+        let mut calls = self.get_prs_for_commit_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_pull_requests_for_commit");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], sha);
+
+        call.ret
+    }
+
+    fn get_hooks(&self, owner: &str, repo: &str) -> Result<Vec<Hook>> {
+        let mut calls = self.get_hooks_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to get_hooks");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+
+        call.ret
+    }
+
+    fn save_hook(&self, owner: &str, repo: &str, hook: &Hook) -> Result<()> {
+        let mut calls = self.save_hook_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to save_hook");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], owner);
+        assert_eq!(call.args[1], repo);
+        assert_eq!(call.args[2], format!("{:?}", hook));
+
+        call.ret
+    }
+
+    fn clone_url(&self, owner: &str, repo: &str) -> String {
+        match self.clone_url {
+            Some(ref url) => url.clone(),
+            None => format!("https://{}@{}/{}/{}", self.user.login(), self.host, owner, repo),
+        }
+    }
 }
 
 impl MockGithub {
@@ -349,6 +867,20 @@ impl MockGithub {
         ));
     }
 
+    pub fn mock_get_review_comments(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u32,
+        review_id: u64,
+        ret: Result<Vec<Comment>>,
+    ) {
+        self.get_review_comments_calls.lock().unwrap().push(MockCall::new(
+            ret,
+            vec![owner, repo, &number.to_string(), &review_id.to_string()],
+        ));
+    }
+
     pub fn mock_comment_pull_request(&self, owner: &str, repo: &str, number: u32, comment: &str, ret: Result<()>) {
         self.comment_pr_calls.lock().unwrap().push(MockCall::new(
             ret,
@@ -394,6 +926,32 @@ impl MockGithub {
         );
     }
 
+    pub fn mock_add_pull_request_labels(&self, owner: &str, repo: &str, number: u32, labels: &str, ret: Result<()>) {
+        self.add_pr_labels_calls.lock().unwrap().push(MockCall::new(
+            ret,
+            vec![owner, repo, &number.to_string(), labels],
+        ));
+    }
+
+    pub fn mock_remove_pull_request_label(&self, owner: &str, repo: &str, number: u32, label: &str, ret: Result<()>) {
+        self.remove_pr_label_calls.lock().unwrap().push(MockCall::new(
+            ret,
+            vec![owner, repo, &number.to_string(), label],
+        ));
+    }
+
+    pub fn mock_get_pull_request_stats(&self, owner: &str, repo: &str, number: u32, ret: Result<DiffStats>) {
+        self.get_pr_stats_calls.lock().unwrap().push(MockCall::new(ret, vec![owner, repo, &number.to_string()]));
+    }
+
+    pub fn mock_get_commit(&self, owner: &str, repo: &str, sha: &str, ret: Result<Commit>) {
+        self.get_commit_calls.lock().unwrap().push(MockCall::new(ret, vec![owner, repo, sha]));
+    }
+
+    pub fn mock_compare_commits(&self, owner: &str, repo: &str, base: &str, head: &str, ret: Result<Comparison>) {
+        self.compare_commits_calls.lock().unwrap().push(MockCall::new(ret, vec![owner, repo, base, head]));
+    }
+
     pub fn mock_create_status(&self, owner: &str, repo: &str, ref_name: &str, status: &Status, ret: Result<()>) {
         self.create_status_calls.lock().unwrap().push(MockCall::new(
             ret,
@@ -405,4 +963,151 @@ impl MockGithub {
             ],
         ));
     }
+
+    pub fn mock_get_combined_status(&self, owner: &str, repo: &str, ref_name: &str, ret: Result<CombinedStatus>) {
+        self.get_combined_status_calls.lock().unwrap().push(
+            MockCall::new(ret, vec![owner, repo, ref_name]),
+        );
+    }
+
+    pub fn mock_merge_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u32,
+        sha: &str,
+        merge_method: &str,
+        ret: Result<()>,
+    ) {
+        self.merge_pr_calls.lock().unwrap().push(MockCall::new(
+            ret,
+            vec![owner, repo, &number.to_string(), sha, merge_method],
+        ));
+    }
+
+    pub fn mock_update_pull_request_branch(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u32,
+        head_sha: &str,
+        ret: Result<()>,
+    ) {
+        self.update_pr_branch_calls.lock().unwrap().push(MockCall::new(
+            ret,
+            vec![owner, repo, &number.to_string(), head_sha],
+        ));
+    }
+
+
+    pub fn mock_request_review(&self, owner: &str, repo: &str, number: u32, reviewers: Vec<&str>, ret: Result<()>) {
+        self.request_review_calls.lock().unwrap().push(MockCall::new(
+            ret,
+            vec![owner, repo, &number.to_string(), &reviewers.join(",")],
+        ));
+    }
+
+    pub fn mock_dismiss_review(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u32,
+        review_id: u64,
+        message: &str,
+        ret: Result<()>,
+    ) {
+        self.dismiss_review_calls.lock().unwrap().push(MockCall::new(
+            ret,
+            vec![owner, repo, &number.to_string(), &review_id.to_string(), message],
+        ));
+    }
+
+
+    pub fn mock_get_pull_request_files(&self, owner: &str, repo: &str, number: u32, ret: Result<Vec<PullRequestFile>>) {
+        self.get_pr_files_calls.lock().unwrap().push(
+            MockCall::new(ret, vec![owner, repo, &number.to_string()]),
+        );
+    }
+
+
+    pub fn mock_get_file_contents(&self, owner: &str, repo: &str, path: &str, ref_name: &str, ret: Result<String>) {
+        self.get_file_contents_calls.lock().unwrap().push(MockCall::new(
+            ret,
+            vec![owner, repo, path, ref_name],
+        ));
+    }
+
+
+    pub fn mock_get_user(&self, login: &str, ret: Result<User>) {
+        self.get_user_calls.lock().unwrap().push(MockCall::new(ret, vec![login]));
+    }
+
+    pub fn mock_get_user_permission(&self, owner: &str, repo: &str, login: &str, ret: Result<String>) {
+        self.get_user_permission_calls.lock().unwrap().push(MockCall::new(ret, vec![owner, repo, login]));
+    }
+
+    pub fn mock_get_team_members(&self, org: &str, team: &str, ret: Result<Vec<User>>) {
+        self.get_team_members_calls.lock().unwrap().push(MockCall::new(ret, vec![org, team]));
+    }
+
+    pub fn mock_set_pull_request_milestone(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u32,
+        milestone: &str,
+        ret: Result<()>,
+    ) {
+        self.set_milestone_calls.lock().unwrap().push(MockCall::new(
+            ret,
+            vec![owner, repo, &number.to_string(), milestone],
+        ));
+    }
+
+    pub fn mock_get_commits_between(&self, owner: &str, repo: &str, base: &str, head: &str, ret: Result<Vec<String>>) {
+        self.get_commits_between_calls.lock().unwrap().push(MockCall::new(ret, vec![owner, repo, base, head]));
+    }
+
+    pub fn mock_create_release(&self, owner: &str, repo: &str, tag: &str, name: &str, body: &str, ret: Result<()>) {
+        self.create_release_calls.lock().unwrap().push(MockCall::new(ret, vec![owner, repo, tag, name, body]));
+    }
+
+    pub fn mock_get_tags(&self, owner: &str, repo: &str, ret: Result<Vec<String>>) {
+        self.get_tags_calls.lock().unwrap().push(MockCall::new(ret, vec![owner, repo]));
+    }
+
+    pub fn mock_is_branch_protected(&self, owner: &str, repo: &str, branch: &str, ret: Result<bool>) {
+        self.is_branch_protected_calls.lock().unwrap().push(MockCall::new(ret, vec![owner, repo, branch]));
+    }
+
+    pub fn mock_protect_branch(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        required_checks: Vec<&str>,
+        required_approvals: u32,
+        ret: Result<()>,
+    ) {
+        self.protect_branch_calls.lock().unwrap().push(MockCall::new(
+            ret,
+            vec![owner, repo, branch, &required_checks.join(","), &required_approvals.to_string()],
+        ));
+    }
+
+    pub fn mock_rerun_failed_jobs(&self, owner: &str, repo: &str, run_id: u64, ret: Result<()>) {
+        self.rerun_failed_jobs_calls.lock().unwrap().push(MockCall::new(ret, vec![owner, repo, &run_id.to_string()]));
+    }
+
+    pub fn mock_get_pull_requests_for_commit(&self, owner: &str, repo: &str, sha: &str, ret: Result<Vec<PullRequest>>) {
+        self.get_prs_for_commit_calls.lock().unwrap().push(MockCall::new(ret, vec![owner, repo, sha]));
+    }
+
+    pub fn mock_get_hooks(&self, owner: &str, repo: &str, ret: Result<Vec<Hook>>) {
+        self.get_hooks_calls.lock().unwrap().push(MockCall::new(ret, vec![owner, repo]));
+    }
+
+    pub fn mock_save_hook(&self, owner: &str, repo: &str, hook: &Hook, ret: Result<()>) {
+        self.save_hook_calls.lock().unwrap().push(MockCall::new(ret, vec![owner, repo, &format!("{:?}", hook)]));
+    }
 }
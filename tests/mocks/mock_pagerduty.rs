@@ -0,0 +1,84 @@
+use std::sync::Mutex;
+use std::thread;
+
+use octobot::errors::*;
+use octobot::pagerduty::Session;
+
+pub struct MockPagerDuty {
+    trigger_calls: Mutex<Vec<MockCall<()>>>,
+    resolve_calls: Mutex<Vec<MockCall<()>>>,
+}
+
+#[derive(Debug)]
+struct MockCall<T> {
+    args: Vec<String>,
+    ret: Result<T>,
+}
+
+impl<T> MockCall<T> {
+    pub fn new(ret: Result<T>, args: Vec<&str>) -> MockCall<T> {
+        MockCall {
+            ret: ret,
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+}
+
+impl MockPagerDuty {
+    pub fn new() -> MockPagerDuty {
+        MockPagerDuty {
+            trigger_calls: Mutex::new(vec![]),
+            resolve_calls: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl Drop for MockPagerDuty {
+    fn drop(&mut self) {
+        if !thread::panicking() {
+            assert!(
+                self.trigger_calls.lock().unwrap().len() == 0,
+                "Unmet trigger calls: {:?}",
+                *self.trigger_calls.lock().unwrap()
+            );
+            assert!(
+                self.resolve_calls.lock().unwrap().len() == 0,
+                "Unmet resolve calls: {:?}",
+                *self.resolve_calls.lock().unwrap()
+            );
+        }
+    }
+}
+
+impl Session for MockPagerDuty {
+    fn trigger(&self, dedup_key: &str, summary: &str, source: &str, link: &str) -> Result<()> {
+        let mut calls = self.trigger_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to trigger");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], dedup_key);
+        assert_eq!(call.args[1], summary);
+        assert_eq!(call.args[2], source);
+        assert_eq!(call.args[3], link);
+
+        call.ret
+    }
+
+    fn resolve(&self, dedup_key: &str) -> Result<()> {
+        let mut calls = self.resolve_calls.lock().unwrap();
+        assert!(calls.len() > 0, "Unexpected call to resolve");
+        let call = calls.remove(0);
+        assert_eq!(call.args[0], dedup_key);
+
+        call.ret
+    }
+}
+
+impl MockPagerDuty {
+    pub fn mock_trigger(&self, dedup_key: &str, summary: &str, source: &str, link: &str, ret: Result<()>) {
+        self.trigger_calls.lock().unwrap().push(MockCall::new(ret, vec![dedup_key, summary, source, link]));
+    }
+
+    pub fn mock_resolve(&self, dedup_key: &str, ret: Result<()>) {
+        self.resolve_calls.lock().unwrap().push(MockCall::new(ret, vec![dedup_key]));
+    }
+}
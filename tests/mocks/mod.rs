@@ -4,3 +4,5 @@ pub mod mock_slack;
 pub mod mock_github;
 #[allow(dead_code, unused_variables)]
 pub mod mock_jira;
+#[allow(dead_code, unused_variables)]
+pub mod mock_pagerduty;
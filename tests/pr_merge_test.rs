@@ -0,0 +1,62 @@
+extern crate octobot;
+
+mod mocks;
+
+use octobot::github;
+use octobot::pr_merge;
+
+use mocks::mock_github::MockGithub;
+
+fn the_pr() -> github::PullRequest {
+    let mut pr = github::PullRequest::new();
+    pr.number = 32;
+    pr.title = "The PR".into();
+    pr.user = github::User::new("the-pr-owner");
+    pr.requested_reviewers = Some(vec![github::User::new("joe-reviewer"), github::User::new("sue-reviewer")]);
+    pr.milestone = Some(github::Milestone { title: "2.0".into() });
+    pr
+}
+
+fn the_backport() -> github::PullRequest {
+    let mut pr = github::PullRequest::new();
+    pr.number = 33;
+    pr
+}
+
+#[test]
+fn test_copy_to_backport() {
+    let github = MockGithub::new();
+    github.mock_get_pull_request_labels(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(vec![github::Label::new("bug"), github::Label::new("backport-1.0"), github::Label::new("Backport-1.1")]),
+    );
+    github.mock_add_pull_request_labels("some-user", "some-repo", 33, "bug", Ok(()));
+    github.mock_request_review("some-user", "some-repo", 33, vec!["joe-reviewer", "sue-reviewer"], Ok(()));
+    github.mock_set_pull_request_milestone("some-user", "some-repo", 33, "2.0", Ok(()));
+
+    pr_merge::copy_to_backport(&github, "some-user", "some-repo", &the_pr(), &the_backport(), None);
+}
+
+#[test]
+fn test_copy_to_backport_mapped_milestone() {
+    let github = MockGithub::new();
+    github.mock_get_pull_request_labels("some-user", "some-repo", 32, Ok(vec![github::Label::new("backport-1.0")]));
+    github.mock_request_review("some-user", "some-repo", 33, vec!["joe-reviewer", "sue-reviewer"], Ok(()));
+    // a failure doesn't stop the rest
+    github.mock_set_pull_request_milestone("some-user", "some-repo", 33, "1.0.5", Err("no such milestone".into()));
+
+    pr_merge::copy_to_backport(&github, "some-user", "some-repo", &the_pr(), &the_backport(), Some(&"1.0.5".into()));
+}
+
+#[test]
+fn test_copy_to_backport_nothing_to_copy() {
+    let github = MockGithub::new();
+    github.mock_get_pull_request_labels("some-user", "some-repo", 32, Err("labels not supported".into()));
+
+    let mut pr = the_pr();
+    pr.requested_reviewers = None;
+    pr.milestone = None;
+    pr_merge::copy_to_backport(&github, "some-user", "some-repo", &pr, &the_backport(), None);
+}
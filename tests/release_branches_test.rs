@@ -0,0 +1,156 @@
+extern crate octobot;
+extern crate tempdir;
+
+mod mocks;
+
+use std::fs;
+use std::io::Write;
+use std::sync::Arc;
+
+use tempdir::TempDir;
+
+use octobot::audit::{self, AuditLog, AuditedSession};
+use octobot::config::Config;
+use octobot::git::Git;
+use octobot::git_clone_manager::GitCloneManager;
+use octobot::github;
+use octobot::github::api::Session;
+use octobot::release_branches;
+use octobot::repos::{RepoConfig, RepoInfo};
+use octobot::users::UserConfig;
+
+use mocks::mock_github::MockGithub;
+
+struct ReleaseBranchesTest {
+    _dir: TempDir,
+    github: Arc<MockGithub>,
+    remote: Git,
+    clone_mgr: GitCloneManager,
+    audit_log: Arc<AuditLog>,
+    config: Arc<Config>,
+}
+
+fn the_repo() -> github::Repo {
+    github::Repo::parse("http://the-github-host/some-user/some-repo").unwrap()
+}
+
+// a bare repo with one commit on master, its default branch, to cut from
+fn new_test(info: RepoInfo) -> ReleaseBranchesTest {
+    let dir = TempDir::new("release_branches_test.rs").expect("create temp dir for release_branches_test.rs");
+    let remote_dir = dir.path().join("remote");
+    let seed_dir = dir.path().join("seed");
+    fs::create_dir(&remote_dir).expect("create remote dir");
+    fs::create_dir(&seed_dir).expect("create seed dir");
+
+    let remote = Git::new("the-github-host", "the-github-token", &remote_dir);
+    remote.run(&["--bare", "init"]).expect("init bare repo");
+    remote.run(&["symbolic-ref", "HEAD", "refs/heads/master"]).expect("set default branch");
+
+    let seed = Git::new("the-github-host", "the-github-token", &seed_dir);
+    seed.run(&["clone", "../remote", "."]).expect("clone bare repo");
+    seed.run(&["config", "commit.gpgsign", "false"]).expect("turn off gpg signing");
+    fs::File::create(seed_dir.join("README.md")).unwrap().write_all(b"# The repo\n").unwrap();
+    seed.run(&["add", "README.md"]).expect("add README.md");
+    seed.run(&["commit", "-m", "Initial commit"]).expect("commit README.md");
+    seed.run(&["push", "origin", "HEAD:refs/heads/master"]).expect("push master");
+
+    let mut repos = RepoConfig::new();
+    repos.insert_info("the-github-host", info);
+    let mut config = Config::new(UserConfig::new(), repos);
+    config.main.clone_root_dir = dir.path().join("clones").to_string_lossy().into_owned();
+    let config = Arc::new(config);
+
+    let github = Arc::new(MockGithub::new().with_clone_url(&remote_dir.to_string_lossy()));
+    let clone_mgr = GitCloneManager::new(github.clone(), config.clone());
+
+    ReleaseBranchesTest {
+        _dir: dir,
+        github: github,
+        remote: remote,
+        clone_mgr: clone_mgr,
+        audit_log: Arc::new(AuditLog::in_memory()),
+        config: config,
+    }
+}
+
+fn the_repo_info() -> RepoInfo {
+    RepoInfo::new("some-user/some-repo", "the-reviews-channel")
+        .with_release_branch_protection(vec!["ci".into()], 1)
+        .with_release_version_files(vec!["VERSION {version}.0".into()])
+}
+
+impl ReleaseBranchesTest {
+    fn cut(&self, session: &Session, version: &str) -> octobot::errors::Result<release_branches::ReleaseBranch> {
+        release_branches::cut(session, &self.clone_mgr, &self.audit_log, &self.config, &the_repo(), version)
+    }
+
+    fn remote_branches(&self) -> String {
+        self.remote.run(&["branch", "--list"]).expect("list remote branches")
+    }
+}
+
+#[test]
+fn test_cut() {
+    let test = new_test(the_repo_info());
+    test.github.mock_protect_branch("some-user", "some-repo", "release/2.4", vec!["ci"], 1, Ok(()));
+
+    let branch = test.cut(&*test.github, "2.4").expect("cut release/2.4");
+    assert_eq!("release/2.4", branch.name);
+    assert_eq!("master", branch.from_branch);
+    assert_eq!(test.remote.run(&["rev-parse", "master"]).unwrap(), branch.sha);
+    assert_eq!(None, branch.protect_error);
+
+    // with the version file committed on top of master
+    assert_eq!("2.4.0", test.remote.run(&["show", "release/2.4:VERSION"]).unwrap());
+    assert_eq!(branch.sha, test.remote.run(&["rev-parse", "release/2.4~1"]).unwrap());
+}
+
+#[test]
+fn test_cut_keeps_unprotected_branch() {
+    let test = new_test(the_repo_info());
+    test.github.mock_protect_branch(
+        "some-user",
+        "some-repo",
+        "release/2.4",
+        vec!["ci"],
+        1,
+        Err("Not allowed".into()),
+    );
+
+    let branch = test.cut(&*test.github, "2.4").expect("cut release/2.4");
+    assert!(branch.protect_error.unwrap().contains("Not allowed"));
+    assert!(test.remote_branches().contains("release/2.4"));
+}
+
+#[test]
+fn test_cut_already_exists() {
+    let test = new_test(the_repo_info().with_release_branch_prefix(Some("rel-".into())));
+    test.remote.run(&["branch", "rel-2.4", "master"]).expect("create rel-2.4");
+
+    // the mock fails the test if it gets as far as protecting the branch
+    let err = test.cut(&*test.github, "2.4").unwrap_err();
+    assert_eq!("rel-2.4 already exists", format!("{}", err));
+}
+
+#[test]
+fn test_cut_dry_run() {
+    let test = new_test(the_repo_info().with_dry_run(true));
+    let session = AuditedSession::new(test.github.clone(), test.audit_log.clone(), test.config.clone());
+
+    let err = test.cut(&session, "2.4").unwrap_err();
+    assert_eq!("Dry run: not pushing release/2.4", format!("{}", err));
+    assert!(!test.remote_branches().contains("release/2.4"));
+
+    let planned = test.audit_log.entries(|e| e.outcome == audit::DRY_RUN, 10).unwrap();
+    assert_eq!(1, planned.len());
+    assert_eq!("push_branch", planned[0].action);
+    assert_eq!("some-user/some-repo release/2.4", planned[0].target);
+}
+
+#[test]
+fn test_cut_invalid_version() {
+    let test = new_test(the_repo_info());
+
+    let err = test.cut(&*test.github, "two").unwrap_err();
+    assert_eq!("Invalid version: two", format!("{}", err));
+}
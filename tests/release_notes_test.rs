@@ -0,0 +1,96 @@
+extern crate octobot;
+
+mod mocks;
+
+use octobot::github;
+use octobot::release_notes::{self, ReleaseNote};
+
+use mocks::mock_github::MockGithub;
+
+fn the_repo() -> github::Repo {
+    github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap()
+}
+
+fn the_pr(number: u32, title: &str, merge_commit_sha: Option<&str>) -> github::PullRequest {
+    let mut pr = github::PullRequest::new();
+    pr.number = number;
+    pr.title = title.into();
+    pr.state = "closed".into();
+    pr.user = github::User::new("the-pr-owner");
+    pr.merge_commit_sha = merge_commit_sha.map(|s| s.to_string());
+    pr
+}
+
+fn note(number: u32, title: &str, labels: Vec<&str>) -> ReleaseNote {
+    ReleaseNote {
+        pull_request: the_pr(number, title, None),
+        labels: labels.into_iter().map(|l| l.to_string()).collect(),
+    }
+}
+
+fn sections() -> Vec<(String, String)> {
+    vec![
+        ("feature".into(), "Features".into()),
+        ("enhancement".into(), "Features".into()),
+        ("bug".into(), "Bug fixes".into()),
+    ]
+}
+
+#[test]
+fn test_merged_pull_requests() {
+    let github = MockGithub::new();
+    github.mock_get_commits_between("some-user", "some-repo", "v1.0", "v1.1", Ok(vec!["aaa".into(), "bbb".into()]));
+    github.mock_get_pull_requests(
+        "some-user",
+        "some-repo",
+        Some("closed"),
+        None,
+        Ok(vec![
+            the_pr(3, "Fix the thing", Some("bbb")),
+            the_pr(2, "Closed without merging", None),
+            the_pr(1, "Add a thing", Some("aaa")),
+            the_pr(0, "Released before", Some("000")),
+        ]),
+    );
+    github.mock_get_pull_request_labels("some-user", "some-repo", 3, Ok(vec![github::Label::new("bug")]));
+    github.mock_get_pull_request_labels("some-user", "some-repo", 1, Ok(vec![]));
+
+    let notes = release_notes::merged_pull_requests(&github, &the_repo(), "v1.0", "v1.1").unwrap();
+    let numbers: Vec<u32> = notes.iter().map(|n| n.pull_request.number).collect();
+    assert_eq!(vec![1, 3], numbers);
+    assert_eq!(vec!["bug".to_string()], notes[1].labels);
+}
+
+#[test]
+fn test_merged_pull_requests_error() {
+    let github = MockGithub::new();
+    github.mock_get_commits_between("some-user", "some-repo", "v1.0", "nope", Err("No such tag".into()));
+
+    assert!(release_notes::merged_pull_requests(&github, &the_repo(), "v1.0", "nope").is_err());
+}
+
+#[test]
+fn test_render() {
+    let notes = vec![
+        note(1, "Fix the thing", vec!["Bug"]),
+        note(2, "Tidy up", vec![]),
+        note(3, "Add a thing", vec!["feature", "ui"]),
+        note(4, "Make a thing faster", vec!["enhancement"]),
+    ];
+
+    assert_eq!(
+        "### Features\n\n\
+         - Add a thing (#3 by @the-pr-owner) [feature, ui]\n\
+         - Make a thing faster (#4 by @the-pr-owner) [enhancement]\n\n\
+         ### Bug fixes\n\n\
+         - Fix the thing (#1 by @the-pr-owner) [Bug]\n\n\
+         ### Other changes\n\n\
+         - Tidy up (#2 by @the-pr-owner)",
+        release_notes::render(&notes, &sections())
+    );
+}
+
+#[test]
+fn test_render_nothing_merged() {
+    assert_eq!("No pull requests were merged.", release_notes::render(&vec![], &sections()));
+}
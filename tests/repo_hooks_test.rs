@@ -0,0 +1,76 @@
+extern crate octobot;
+
+mod mocks;
+
+use octobot::github::{Hook, HookConfig};
+use octobot::repo_hooks::{self, HookSetup};
+
+use mocks::mock_github::MockGithub;
+
+const URL: &'static str = "https://octobot.company.com/hooks/github";
+
+fn all_events() -> Vec<String> {
+    repo_hooks::EVENTS.iter().map(|e| e.to_string()).collect()
+}
+
+fn the_hook(id: Option<u64>, events: Vec<String>, secret: Option<&str>) -> Hook {
+    Hook {
+        id: id,
+        name: "web".into(),
+        active: true,
+        events: events,
+        config: HookConfig {
+            url: URL.into(),
+            content_type: Some("json".into()),
+            secret: secret.map(|s| s.to_string()),
+        },
+    }
+}
+
+#[test]
+fn test_creates_hook() {
+    let github = MockGithub::new();
+    let mut other = the_hook(Some(1), vec!["push".into()], None);
+    other.config.url = "https://ci.company.com/hook".into();
+    github.mock_get_hooks("some-user", "some-repo", Ok(vec![other]));
+    github.mock_save_hook("some-user", "some-repo", &the_hook(None, all_events(), Some("the-secret")), Ok(()));
+
+    let setup = repo_hooks::ensure_hook(&github, "some-user", "some-repo", URL, "the-secret").unwrap();
+    assert_eq!(HookSetup::Created, setup);
+}
+
+#[test]
+fn test_updates_hook_missing_events() {
+    let github = MockGithub::new();
+    let mut existing = the_hook(Some(12), vec!["push".into(), "pull_request".into()], None);
+    existing.active = false;
+    github.mock_get_hooks("some-user", "some-repo", Ok(vec![existing]));
+
+    let mut events = vec!["push".to_string(), "pull_request".to_string()];
+    events.extend(all_events().into_iter().filter(|e| e != "push" && e != "pull_request"));
+    github.mock_save_hook("some-user", "some-repo", &the_hook(Some(12), events, Some("the-secret")), Ok(()));
+
+    let setup = repo_hooks::ensure_hook(&github, "some-user", "some-repo", URL, "the-secret").unwrap();
+    assert_eq!(HookSetup::Updated, setup);
+}
+
+#[test]
+fn test_leaves_hook_alone() {
+    let github = MockGithub::new();
+    github.mock_get_hooks("some-user", "some-repo", Ok(vec![the_hook(Some(12), all_events(), Some("********"))]));
+
+    let setup = repo_hooks::ensure_hook(&github, "some-user", "some-repo", URL, "the-secret").unwrap();
+    assert_eq!(HookSetup::Unchanged, setup);
+
+    github.mock_get_hooks("some-user", "some-repo", Ok(vec![the_hook(Some(12), vec!["*".into()], None)]));
+    let setup = repo_hooks::ensure_hook(&github, "some-user", "some-repo", URL, "the-secret").unwrap();
+    assert_eq!(HookSetup::Unchanged, setup);
+}
+
+#[test]
+fn test_hook_error() {
+    let github = MockGithub::new();
+    github.mock_get_hooks("some-user", "some-repo", Err("Not Found".into()));
+
+    assert!(repo_hooks::ensure_hook(&github, "some-user", "some-repo", URL, "the-secret").is_err());
+}
@@ -0,0 +1,253 @@
+extern crate octobot;
+extern crate tempdir;
+
+mod mocks;
+
+use std::sync::Arc;
+
+use octobot::config::Config;
+use octobot::github;
+use octobot::json_store::JsonStore;
+use octobot::messenger;
+use octobot::repos::{RepoConfig, RepoInfo};
+use octobot::review_reminders::{self, PendingReviews};
+use octobot::slack::{self, SlackAttachmentBuilder};
+use octobot::users::{UserConfig, UserInfo};
+
+use tempdir::TempDir;
+
+use mocks::mock_slack::MockSlack;
+
+// 2017-10-02 00:00:00 UTC, a monday
+const MONDAY: i64 = 1506902400;
+const HOUR: i64 = 60 * 60;
+
+fn the_repo() -> github::Repo {
+    github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap()
+}
+
+fn new_config() -> Arc<Config> {
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_review_reminders(Some(24), Some(8)),
+    );
+    Arc::new(Config::new(UserConfig::new(), repos))
+}
+
+fn the_pr() -> github::PullRequest {
+    let mut pr = github::PullRequest::new();
+    pr.number = 32;
+    pr.title = "The PR".into();
+    pr.html_url = "http://the-pr".into();
+    pr.user = github::User::new("the-pr-owner");
+    pr.requested_reviewers = Some(vec![github::User::new("joe-reviewer")]);
+    pr.base.ref_name = "master".into();
+    pr
+}
+
+#[test]
+fn test_remind_then_escalate() {
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let slack = MockSlack::new(vec![
+        slack::req("@joe.reviewer", "Your review is still pending", attach.clone()),
+        slack::req(
+            "the-reviews-channel",
+            "Pull Request is still waiting for review from joe.reviewer \
+             (<http://git.foo.com/some-user/some-repo|some-user/some-repo>)",
+            attach.clone(),
+        ),
+    ]);
+
+    let config = new_config();
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    let pending = JsonStore::<PendingReviews>::new(None);
+
+    // requested friday at noon. only working hours count (09:00-17:00 UTC, as joe didn't set any), so the
+    // 24 hours are 5 on friday, 8 on monday and tuesday, and 3 on wednesday.
+    let friday = MONDAY - 3 * 24 * HOUR + 12 * HOUR;
+    let wednesday = MONDAY + 2 * 24 * HOUR;
+    pending.write(|p| p.sync(&the_repo(), &the_pr(), friday));
+
+    review_reminders::send_reminders(&*messenger, &config, &pending, MONDAY + 12 * HOUR);
+    review_reminders::send_reminders(&*messenger, &config, &pending, wednesday + 11 * HOUR);
+    review_reminders::send_reminders(&*messenger, &config, &pending, wednesday + 12 * HOUR);
+    // then 8 more: 5 on wednesday and 3 on thursday
+    review_reminders::send_reminders(&*messenger, &config, &pending, wednesday + 24 * HOUR + 11 * HOUR);
+    review_reminders::send_reminders(&*messenger, &config, &pending, wednesday + 24 * HOUR + 12 * HOUR);
+    review_reminders::send_reminders(&*messenger, &config, &pending, wednesday + 7 * 24 * HOUR);
+}
+
+#[test]
+fn test_hosts_keep_separate_reviews() {
+    let dir = TempDir::new("review_reminders_test.rs").expect("create temp dir for review_reminders_test.rs");
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_review_reminders(Some(24), Some(8)),
+    );
+    repos.insert_info(
+        "gitlab.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-gitlab-channel").with_review_reminders(Some(24), Some(8)),
+    );
+    let mut config = Config::new(UserConfig::new(), repos);
+    config.main.data_dir = Some(dir.path().to_string_lossy().into_owned());
+    config.github.host = "git.foo.com".into();
+    let config = Arc::new(config);
+
+    let github_file = config.host_data_file("git.foo.com", "pending_reviews.json");
+    let gitlab_file = config.host_data_file("gitlab.foo.com", "pending_reviews.json");
+    assert!(github_file.is_some());
+    assert!(github_file != gitlab_file);
+
+    {
+        let github_pending = JsonStore::<PendingReviews>::new(github_file.clone());
+        github_pending.write(|p| p.sync(&the_repo(), &the_pr(), MONDAY));
+    }
+
+    // only the host that the PR is on reminds about it, even after a restart
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let slack = MockSlack::new(vec![slack::req("@joe.reviewer", "Your review is still pending", attach)]);
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    let github_pending = JsonStore::<PendingReviews>::new(github_file);
+    let gitlab_pending = JsonStore::<PendingReviews>::new(gitlab_file);
+
+    let due = MONDAY + 4 * 24 * HOUR;
+    review_reminders::send_reminders(&*messenger, &config, &gitlab_pending, due);
+    review_reminders::send_reminders(&*messenger, &config, &github_pending, due);
+}
+
+#[test]
+fn test_reviewer_work_hours() {
+    let mut users = UserConfig::new();
+    users.insert_info(
+        "git.foo.com",
+        UserInfo::new("joe-reviewer", "joe.reviewer").with_work_hours("09:00-21:00", "-08:00"),
+    );
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_review_reminders(Some(24), None),
+    );
+    let config = Config::new(users, repos);
+    let pending = JsonStore::<PendingReviews>::new(None);
+
+    // joe works 12 hours a day, 17:00-05:00 UTC: monday's and tuesday's make 24
+    let wednesday = MONDAY + 48 * HOUR;
+    pending.write(|p| p.sync(&the_repo(), &the_pr(), MONDAY + 12 * HOUR));
+    assert_eq!(0, pending.write(|p| p.take_due(&config, wednesday + 4 * HOUR)).len());
+    assert_eq!(1, pending.write(|p| p.take_due(&config, wednesday + 6 * HOUR)).len());
+}
+
+#[test]
+fn test_reviewer_out_of_office() {
+    let mut users = UserConfig::new();
+    users.insert_info("git.foo.com", UserInfo::new("joe-reviewer", "joe.reviewer").with_out_of_office(None));
+    let config = Config::new(users, new_config().repos().clone());
+    let pending = JsonStore::<PendingReviews>::new(None);
+
+    // requested monday at 09:00: joe is away all week
+    pending.write(|p| p.sync(&the_repo(), &the_pr(), MONDAY + 9 * HOUR));
+    assert_eq!(0, pending.write(|p| p.take_due(&config, MONDAY + 4 * 24 * HOUR)).len());
+
+    // back from friday on, when the 24 working hours started over: 8 on friday, monday, and tuesday
+    let mut back = UserConfig::new();
+    back.insert("git.foo.com", "joe-reviewer", "joe.reviewer");
+    let config = Config::new(back, new_config().repos().clone());
+    assert_eq!(0, pending.write(|p| p.take_due(&config, MONDAY + 7 * 24 * HOUR + 17 * HOUR)).len());
+    assert_eq!(1, pending.write(|p| p.take_due(&config, MONDAY + 9 * 24 * HOUR + 17 * HOUR)).len());
+}
+
+#[test]
+fn test_unreviewed_escalation() {
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let slack = MockSlack::new(vec![
+        slack::req("@the.lead", "Pull Request hasn't been reviewed in 4 hours", attach.clone()),
+        slack::req(
+            "the-reviews-channel",
+            "<!here> Pull Request still hasn't been reviewed after 24 hours \
+             (<http://git.foo.com/some-user/some-repo|some-user/some-repo>)",
+            attach.clone(),
+        ),
+    ]);
+
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_unreviewed_escalation(
+            "the-lead",
+            Some(4),
+            Some(24),
+        ),
+    );
+    let config = Arc::new(Config::new(UserConfig::new(), repos));
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    let pending = JsonStore::<PendingReviews>::new(None);
+
+    // plain hours count here, not working ones
+    pending.write(|p| p.sync(&the_repo(), &the_pr(), MONDAY));
+    review_reminders::send_reminders(&*messenger, &config, &pending, MONDAY + 3 * HOUR);
+    review_reminders::send_reminders(&*messenger, &config, &pending, MONDAY + 4 * HOUR);
+    review_reminders::send_reminders(&*messenger, &config, &pending, MONDAY + 5 * HOUR);
+    review_reminders::send_reminders(&*messenger, &config, &pending, MONDAY + 24 * HOUR);
+    review_reminders::send_reminders(&*messenger, &config, &pending, MONDAY + 48 * HOUR);
+}
+
+#[test]
+fn test_unreviewed_escalation_stops_at_first_review() {
+    let slack = MockSlack::new(vec![
+        slack::req(
+            "#leads",
+            "Pull Request hasn't been reviewed in 4 hours \
+             (<http://git.foo.com/some-user/some-repo|some-user/some-repo>)",
+            vec![
+                SlackAttachmentBuilder::new("")
+                    .title("Pull Request #32: \"The PR\"")
+                    .title_link("http://the-pr")
+                    .build(),
+            ],
+        ),
+    ]);
+
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_unreviewed_escalation(
+            "#leads",
+            Some(4),
+            Some(24),
+        ),
+    );
+    let config = Arc::new(Config::new(UserConfig::new(), repos));
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    let pending = JsonStore::<PendingReviews>::new(None);
+
+    let mut pr = the_pr();
+    pending.write(|p| p.sync(&the_repo(), &pr, MONDAY));
+    review_reminders::send_reminders(&*messenger, &config, &pending, MONDAY + 4 * HOUR);
+
+    // the author's own comments don't count
+    pr.reviews = Some(vec![github::Review::new("wait", github::User::new("the-pr-owner"))]);
+    pending.write(|p| p.sync(&the_repo(), &pr, MONDAY + 5 * HOUR));
+    assert_eq!(None, pending.read(|p| p.first_review_at(&the_repo(), 32)));
+
+    pr.reviews = Some(vec![github::Review::new("lgtm", github::User::new("joe-reviewer"))]);
+    pending.write(|p| p.sync(&the_repo(), &pr, MONDAY + 6 * HOUR));
+    assert_eq!(Some(MONDAY + 6 * HOUR), pending.read(|p| p.first_review_at(&the_repo(), 32)));
+    review_reminders::send_reminders(&*messenger, &config, &pending, MONDAY + 48 * HOUR);
+}
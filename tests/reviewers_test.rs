@@ -0,0 +1,183 @@
+extern crate octobot;
+
+mod mocks;
+
+use std::sync::Arc;
+
+use octobot::codeowners::CodeOwnersCache;
+use octobot::config::Config;
+use octobot::github;
+use octobot::json_store::JsonStore;
+use octobot::messenger;
+use octobot::repos::{RepoConfig, RepoInfo};
+use octobot::reviewers::{self, ReviewerRotation};
+use octobot::slack::{self, SlackAttachmentBuilder};
+use octobot::users::{UserConfig, UserInfo};
+
+use mocks::mock_github::MockGithub;
+use mocks::mock_slack::MockSlack;
+
+fn the_repo() -> github::Repo {
+    github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap()
+}
+
+fn new_config() -> Arc<Config> {
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel")
+            .with_reviewer_pool(vec!["joe-reviewer".into(), "bob-reviewer".into(), "sue-reviewer".into()], Some(2)),
+    );
+    Arc::new(Config::new(UserConfig::new(), repos))
+}
+
+fn the_pr(number: u32) -> github::PullRequest {
+    let mut pr = github::PullRequest::new();
+    pr.number = number;
+    pr.title = "The PR".into();
+    pr.html_url = "http://the-pr".into();
+    pr.user = github::User::new("joe-reviewer");
+    pr.base.ref_name = "master".into();
+    pr
+}
+
+fn attach() -> Vec<slack::SlackAttachment> {
+    vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ]
+}
+
+#[test]
+fn test_assign_reviewers() {
+    let github = MockGithub::new();
+    let msg = "Your review was requested";
+    let slack = MockSlack::new(vec![
+        slack::req("@bob.reviewer", msg, attach()),
+        slack::req("@sue.reviewer", msg, attach()),
+        slack::req("@bob.reviewer", msg, attach()),
+        slack::req("@sue.reviewer", msg, attach()),
+    ]);
+    let config = new_config();
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    let rotation: JsonStore<ReviewerRotation> = JsonStore::in_memory();
+
+    // the author is skipped
+    github.mock_request_review("some-user", "some-repo", 32, vec!["bob-reviewer", "sue-reviewer"], Ok(()));
+    reviewers::assign_reviewers(&github, &config, &*messenger, &rotation, &the_repo(), &the_pr(32));
+
+    github.mock_request_review("some-user", "some-repo", 32, vec!["bob-reviewer", "sue-reviewer"], Ok(()));
+    reviewers::assign_reviewers(&github, &config, &*messenger, &rotation, &the_repo(), &the_pr(32));
+}
+
+#[test]
+fn test_assign_reviewers_out_of_office() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![slack::req("@bob.reviewer", "Your review was requested", attach())]);
+    let mut users = UserConfig::new();
+    users.insert_info("git.foo.com", UserInfo::new("sue-reviewer", "sue.reviewer").with_out_of_office(None));
+    let config = Arc::new(Config::new(users, new_config().repos().clone()));
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    let rotation: JsonStore<ReviewerRotation> = JsonStore::in_memory();
+
+    github.mock_request_review("some-user", "some-repo", 32, vec!["bob-reviewer"], Ok(()));
+    reviewers::assign_reviewers(&github, &config, &*messenger, &rotation, &the_repo(), &the_pr(32));
+}
+
+#[test]
+fn test_assign_reviewers_already_requested() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![]);
+    let config = new_config();
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    let rotation: JsonStore<ReviewerRotation> = JsonStore::in_memory();
+
+    let mut pr = the_pr(32);
+    pr.requested_reviewers = Some(vec![github::User::new("someone")]);
+
+    reviewers::assign_reviewers(&github, &config, &*messenger, &rotation, &the_repo(), &pr);
+}
+
+#[test]
+fn test_assign_reviewers_error() {
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![]);
+    let config = new_config();
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    let rotation: JsonStore<ReviewerRotation> = JsonStore::in_memory();
+
+    github.mock_request_review(
+        "some-user",
+        "some-repo",
+        32,
+        vec!["bob-reviewer", "sue-reviewer"],
+        Err("not a collaborator".into()),
+    );
+    reviewers::assign_reviewers(&github, &config, &*messenger, &rotation, &the_repo(), &the_pr(32));
+}
+
+fn codeowners_config() -> Arc<Config> {
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_codeowners(Some(true), Some(true)),
+    );
+    Arc::new(Config::new(UserConfig::new(), repos))
+}
+
+fn pr_file(filename: &str) -> github::PullRequestFile {
+    github::PullRequestFile {
+        filename: filename.into(),
+        status: "modified".into(),
+        additions: 1,
+        deletions: 1,
+        changes: 2,
+    }
+}
+
+#[test]
+fn test_notify_code_owners() {
+    let github = MockGithub::new();
+    let msg = "Pull Request touches files you own";
+    let slack = MockSlack::new(vec![
+        slack::req("@docs.owner", msg, attach()),
+        slack::req("@bob.reviewer", msg, attach()),
+    ]);
+    let config = codeowners_config();
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    let codeowners = CodeOwnersCache::new();
+
+    github.mock_get_pull_request_files(
+        "some-user",
+        "some-repo",
+        32,
+        Ok(vec![pr_file("src/main.rs"), pr_file("docs/README.md")]),
+    );
+    github.mock_get_file_contents("some-user", "some-repo", "CODEOWNERS", "master", Err("Not found".into()));
+    github.mock_get_file_contents(
+        "some-user",
+        "some-repo",
+        ".github/CODEOWNERS",
+        "master",
+        Ok("*.rs @joe-reviewer @bob-reviewer\n/docs/ @docs-owner @some-org/docs-team\n".into()),
+    );
+    github.mock_request_review("some-user", "some-repo", 32, vec!["docs-owner"], Ok(()));
+
+    // joe-reviewer wrote it and bob-reviewer was already asked to review
+    reviewers::notify_code_owners(
+        &github,
+        &config,
+        &*messenger,
+        &codeowners,
+        &the_repo(),
+        &the_pr(32),
+        &vec!["bob-reviewer".into()],
+    );
+
+    // CODEOWNERS is cached the second time around
+    github.mock_get_pull_request_files("some-user", "some-repo", 32, Ok(vec![pr_file("src/main.rs")]));
+    github.mock_request_review("some-user", "some-repo", 32, vec!["bob-reviewer"], Ok(()));
+    reviewers::notify_code_owners(&github, &config, &*messenger, &codeowners, &the_repo(), &the_pr(32), &vec![]);
+}
@@ -0,0 +1,148 @@
+extern crate octobot;
+
+mod mocks;
+
+use std::sync::{Arc, Mutex};
+
+use octobot::config::Config;
+use octobot::errors::*;
+use octobot::github;
+use octobot::repos::{RepoConfig, RepoInfo};
+use octobot::slack_users::{SlackDirectory, SlackUserResolver};
+use octobot::users::UserConfig;
+
+use mocks::mock_github::MockGithub;
+
+// 2017-10-02 00:00:00 UTC
+const NOW: i64 = 1506902400;
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+struct MockSlackDirectory {
+    lookups: Arc<Mutex<Vec<String>>>,
+}
+
+impl SlackDirectory for MockSlackDirectory {
+    fn lookup_by_email(&self, email: &str) -> Result<Option<String>> {
+        self.lookups.lock().unwrap().push(email.to_string());
+        match email {
+            "joe@foo.com" => Ok(Some("joe.slacker".into())),
+            "broken@foo.com" => Err("slack is down".into()),
+            _ => Ok(None),
+        }
+    }
+}
+
+fn the_repo() -> github::Repo {
+    github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap()
+}
+
+fn user_with_email(login: &str, email: Option<&str>) -> github::User {
+    let mut user = github::User::new(login);
+    user.email = email.map(|e| e.to_string());
+    user
+}
+
+struct ResolverTest {
+    github: Arc<MockGithub>,
+    lookups: Arc<Mutex<Vec<String>>>,
+    resolver: SlackUserResolver,
+    config: Config,
+}
+
+fn new_test(email_domain: Option<&str>) -> ResolverTest {
+    let mut users = UserConfig::new();
+    users.insert("git.foo.com", "joe", "joe.configured");
+    users.insert("git.foo.com", "ann", "ann.configured");
+
+    let mut info = RepoInfo::new("some-user/some-repo", "the-channel");
+    if let Some(domain) = email_domain {
+        info = info.with_slack_email_domain(domain);
+    }
+    let mut repos = RepoConfig::new();
+    repos.insert_info("git.foo.com", info);
+
+    let github = Arc::new(MockGithub::new());
+    let lookups = Arc::new(Mutex::new(vec![]));
+    let resolver = SlackUserResolver::new(
+        github.clone(),
+        Box::new(MockSlackDirectory { lookups: lookups.clone() }),
+        None,
+    );
+
+    ResolverTest {
+        github: github,
+        lookups: lookups,
+        resolver: resolver,
+        config: Config::new(users, repos),
+    }
+}
+
+#[test]
+fn test_resolve_by_public_email() {
+    let test = new_test(None);
+    test.github.mock_get_user("ann", Ok(user_with_email("ann", None)));
+    test.github.mock_get_user("joe", Ok(user_with_email("joe", Some("joe@foo.com"))));
+
+    let users = vec![github::User::new("joe"), github::User::new("ann"), github::User::new("joe")];
+    test.resolver.resolve(&test.config, &the_repo(), &users, NOW);
+
+    assert_eq!(vec!["joe@foo.com"], *test.lookups.lock().unwrap());
+    assert_eq!("joe.slacker", test.config.users().slack_user_name("joe", &the_repo()));
+    // no email: falls back to the configured name
+    assert_eq!("ann.configured", test.config.users().slack_user_name("ann", &the_repo()));
+
+    // cached: no more lookups
+    test.resolver.resolve(&test.config, &the_repo(), &users, NOW + DAY_SECS / 2);
+    assert_eq!(1, test.lookups.lock().unwrap().len());
+
+    // the misses are retried sooner than the hits
+    test.github.mock_get_user("ann", Ok(user_with_email("ann", Some("ann@foo.com"))));
+    test.resolver.resolve(&test.config, &the_repo(), &users, NOW + 2 * DAY_SECS);
+    assert_eq!(vec!["joe@foo.com", "ann@foo.com"], *test.lookups.lock().unwrap());
+    assert_eq!("ann.configured", test.config.users().slack_user_name("ann", &the_repo()));
+}
+
+#[test]
+fn test_resolve_by_repo_email_domain() {
+    let test = new_test(Some("foo.com"));
+    test.github.mock_get_user("joe", Ok(user_with_email("joe", None)));
+
+    test.resolver.resolve(&test.config, &the_repo(), &vec![github::User::new("joe")], NOW);
+
+    assert_eq!(vec!["joe@foo.com"], *test.lookups.lock().unwrap());
+    assert_eq!("joe.slacker", test.config.users().slack_user_name("joe", &the_repo()));
+}
+
+#[test]
+fn test_resolve_errors_not_cached() {
+    let test = new_test(None);
+    test.github.mock_get_user("broken", Ok(user_with_email("broken", Some("broken@foo.com"))));
+    test.github.mock_get_user("joe", Err("github is down".into()));
+
+    let users = vec![github::User::new("joe"), github::User::new("broken")];
+    test.resolver.resolve(&test.config, &the_repo(), &users, NOW);
+    assert_eq!("joe.configured", test.config.users().slack_user_name("joe", &the_repo()));
+    assert_eq!("broken", test.config.users().slack_user_name("broken", &the_repo()));
+
+    // tried again right away
+    test.github.mock_get_user("broken", Ok(user_with_email("broken", Some("broken@foo.com"))));
+    test.github.mock_get_user("joe", Ok(user_with_email("joe", Some("joe@foo.com"))));
+    test.resolver.resolve(&test.config, &the_repo(), &users, NOW + 1);
+    assert_eq!("joe.slacker", test.config.users().slack_user_name("joe", &the_repo()));
+}
+
+#[test]
+fn test_unresolved() {
+    let test = new_test(None);
+    test.github.mock_get_user("ann", Ok(user_with_email("ann", None)));
+    test.github.mock_get_user("joe", Ok(user_with_email("joe", Some("joe@foo.com"))));
+
+    let users = vec![github::User::new("joe"), github::User::new("ann"), github::User::new("joe")];
+    let logins = |users: Vec<github::User>| users.iter().map(|u| u.login().to_string()).collect::<Vec<_>>();
+    assert_eq!(vec!["ann", "joe"], logins(test.resolver.unresolved(&users, NOW)));
+
+    test.resolver.resolve(&test.config, &the_repo(), &users, NOW);
+    assert_eq!(Vec::<String>::new(), logins(test.resolver.unresolved(&users, NOW + DAY_SECS / 2)));
+    // the misses are due again sooner
+    assert_eq!(vec!["ann"], logins(test.resolver.unresolved(&users, NOW + 2 * DAY_SECS)));
+}
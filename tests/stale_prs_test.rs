@@ -0,0 +1,114 @@
+extern crate octobot;
+
+mod mocks;
+
+use std::sync::Arc;
+
+use octobot::config::Config;
+use octobot::github;
+use octobot::messenger;
+use octobot::repos::{RepoConfig, RepoInfo};
+use octobot::slack::{self, SlackAttachmentBuilder};
+use octobot::stale_prs;
+use octobot::users::UserConfig;
+
+use mocks::mock_github::MockGithub;
+use mocks::mock_slack::MockSlack;
+
+// 2017-10-02 00:00:00 UTC
+const NOW: i64 = 1506902400;
+
+fn the_repo() -> github::Repo {
+    github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap()
+}
+
+fn new_config(days: Option<u32>) -> Arc<Config> {
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_stale_prs(days, None),
+    );
+    Arc::new(Config::new(UserConfig::new(), repos))
+}
+
+fn the_pr(number: u32, updated_at: &str) -> github::PullRequest {
+    let mut pr = github::PullRequest::new();
+    pr.number = number;
+    pr.title = "The PR".into();
+    pr.html_url = "http://the-pr".into();
+    pr.user = github::User::new("the-pr-owner");
+    pr.assignees = vec![github::User::new("assign1")];
+    pr.requested_reviewers = Some(vec![github::User::new("joe-reviewer")]);
+    pr.base.ref_name = "master".into();
+    pr.updated_at = Some(updated_at.into());
+    pr
+}
+
+#[test]
+fn test_remind() {
+    let github = MockGithub::new();
+    github.mock_get_pull_requests(
+        "some-user",
+        "some-repo",
+        Some("open"),
+        None,
+        Ok(vec![the_pr(32, "2017-09-28T08:00:00Z"), the_pr(33, "2017-09-30T08:00:00Z")]),
+    );
+
+    let msg = "Pull Request has been idle for 3 days";
+    let attach = vec![
+        SlackAttachmentBuilder::new("")
+            .title("Pull Request #32: \"The PR\"")
+            .title_link("http://the-pr")
+            .build(),
+    ];
+    let slack = MockSlack::new(vec![
+        slack::req(
+            "the-reviews-channel",
+            &format!("{} (<http://git.foo.com/some-user/some-repo|some-user/some-repo>)", msg),
+            attach.clone(),
+        ),
+        slack::req("@assign1", msg, attach.clone()),
+        slack::req("@joe.reviewer", msg, attach.clone()),
+    ]);
+
+    let config = new_config(Some(3));
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    stale_prs::remind(&github, &config, &*messenger, &the_repo(), NOW);
+}
+
+#[test]
+fn test_remind_disabled() {
+    // no calls expected
+    let github = MockGithub::new();
+    let slack = MockSlack::new(vec![]);
+
+    let config = new_config(None);
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    stale_prs::remind(&github, &config, &*messenger, &the_repo(), NOW);
+}
+
+#[test]
+fn test_stale_pr_repos() {
+    let mut repos = RepoConfig::new();
+    repos.insert_info("git.foo.com", RepoInfo::new("some-user/some-repo", "chan").with_stale_prs(Some(3), None));
+    repos.insert_info("git.foo.com", RepoInfo::new("some-user/other-repo", "chan"));
+    repos.insert_info("git.foo.com", RepoInfo::new("some-org", "chan").with_stale_prs(Some(3), None));
+
+    let found = repos.stale_pr_repos("git.foo.com");
+    assert_eq!(vec!["some-user/some-repo"], found.iter().map(|r| r.full_name.as_str()).collect::<Vec<_>>());
+    assert_eq!(0, repos.stale_pr_repos("other.host.com").len());
+}
+
+#[test]
+fn test_remind_skips_drafts() {
+    let github = MockGithub::new();
+    let mut draft = the_pr(32, "2017-09-28T08:00:00Z");
+    draft.draft = Some(true);
+    github.mock_get_pull_requests("some-user", "some-repo", Some("open"), None, Ok(vec![draft]));
+    let slack = MockSlack::new(vec![]);
+
+    let config = new_config(Some(3));
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    stale_prs::remind(&github, &config, &*messenger, &the_repo(), NOW);
+}
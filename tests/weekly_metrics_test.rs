@@ -0,0 +1,70 @@
+extern crate octobot;
+
+mod mocks;
+
+use std::sync::Arc;
+
+use octobot::config::Config;
+use octobot::github;
+use octobot::json_store::JsonStore;
+use octobot::messenger;
+use octobot::repos::{RepoConfig, RepoInfo};
+use octobot::slack::{self, SlackAttachmentBuilder};
+use octobot::users::UserConfig;
+use octobot::weekly_metrics::{self, WeeklyMetrics};
+
+use mocks::mock_slack::MockSlack;
+
+fn the_repo() -> github::Repo {
+    github::Repo::parse("http://git.foo.com/some-user/some-repo").unwrap()
+}
+
+fn new_config() -> Arc<Config> {
+    let mut repos = RepoConfig::new();
+    repos.insert_info(
+        "git.foo.com",
+        RepoInfo::new("some-user/some-repo", "the-reviews-channel").with_weekly_metrics(true),
+    );
+    Arc::new(Config::new(UserConfig::new(), repos))
+}
+
+#[test]
+fn test_report() {
+    let repo = the_repo();
+    let metrics: JsonStore<WeeklyMetrics> = JsonStore::in_memory();
+    metrics.write(|m| {
+        m.opened(&repo);
+        m.opened(&repo);
+        m.reviewed(&repo, "http://pr/1", "joe-reviewer", Some(2 * 3600));
+        m.reviewed(&repo, "http://pr/1", "bob-reviewer", Some(3 * 3600));
+        m.reviewed(&repo, "http://pr/2", "bob-reviewer", Some(4 * 3600));
+        m.closed(&repo, "http://pr/1", true, Some(26 * 3600));
+    });
+
+    let text = "PRs opened: 2\nPRs merged: 1\nMedian time to first review: 3h 0m\nMedian time to merge: 1d 2h\n\
+                Reviews: bob.reviewer (2), joe.reviewer (1)";
+    let slack = MockSlack::new(vec![
+        slack::req(
+            "the-reviews-channel",
+            "Pull requests this week (<http://git.foo.com/some-user/some-repo|some-user/some-repo>)",
+            vec![SlackAttachmentBuilder::new(text).build()],
+        ),
+    ]);
+
+    let config = new_config();
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    weekly_metrics::report(&config, &metrics, &*messenger, &repo);
+
+    // the next week starts from nothing
+    assert!(metrics.write(|m| m.take_week(&repo)).is_empty());
+}
+
+#[test]
+fn test_report_quiet_week() {
+    let metrics: JsonStore<WeeklyMetrics> = JsonStore::in_memory();
+    let slack = MockSlack::new(vec![]);
+
+    let config = new_config();
+    let messenger = messenger::new(config.clone(), slack.new_sender());
+    weekly_metrics::report(&config, &metrics, &*messenger, &the_repo());
+}